@@ -0,0 +1,106 @@
+use cargo::core::Workspace;
+use cargo::core::dependency::Kind;
+use cargo::ops;
+use cargo::util::{CliResult, Config, human};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    arg_crate: Vec<String>,
+    flag_features: Vec<String>,
+    flag_dev: bool,
+    flag_build: bool,
+    flag_optional: bool,
+    flag_path: Option<String>,
+    flag_git: Option<String>,
+    flag_branch: Option<String>,
+    flag_tag: Option<String>,
+    flag_rev: Option<String>,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Add dependencies to the manifest of a package
+
+Usage:
+    cargo add [options] <crate>...
+
+Specifying where the crates come from:
+    --path PATH              Filesystem path to the crate to add
+    --git URL                Git URL to add the crate from
+    --branch BRANCH          Branch to use with --git
+    --tag TAG                Tag to use with --git
+    --rev SHA                Specific commit to use with --git
+
+Options:
+    -h, --help               Print this message
+    --features FEATURES ...  Space-separated list of features to enable
+    --dev                    Add to the dev-dependencies
+    --build                  Add to the build-dependencies
+    --optional               Make the dependencies optional
+    --manifest-path PATH     Path to the manifest to add dependencies to
+    -v, --verbose ...        Use verbose output
+    -q, --quiet              No output printed to stdout
+    --color WHEN             Coloring: auto, always, never
+    --frozen                 Require Cargo.lock and cache are up to date
+    --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
+
+Each crate is a name, optionally followed by `@` and a version requirement, as
+in `cargo add log@0.3`. A crate from crates.io without a requirement is added
+with the newest version released, and one declared in `[workspace.dependencies]`
+of the root of the workspace is inherited from there instead.
+
+The dependencies are declared in the manifest of the package at --manifest-path,
+or the current package, keeping the rest of the manifest as it is. A dependency
+which is already declared there is replaced. The lockfile is then updated for
+the new dependencies, and the manifest is left unchanged if they can't be
+resolved.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+
+    let kind = match (options.flag_dev, options.flag_build) {
+        (true, true) => return Err(human("--dev and --build can't both be \
+                                          specified").into()),
+        (true, false) => Kind::Development,
+        (false, true) => Kind::Build,
+        (false, false) => Kind::Normal,
+    };
+    let features = options.flag_features.iter()
+                          .flat_map(|s| s.split(' '))
+                          .filter(|s| !s.is_empty())
+                          .map(|s| s.to_string())
+                          .collect::<Vec<_>>();
+    let opts = ops::AddOptions {
+        crates: &options.arg_crate,
+        kind: kind,
+        features: &features,
+        optional: options.flag_optional,
+        path: options.flag_path.as_ref().map(|s| &s[..]),
+        git: options.flag_git.as_ref().map(|s| &s[..]),
+        branch: options.flag_branch.as_ref().map(|s| &s[..]),
+        tag: options.flag_tag.as_ref().map(|s| &s[..]),
+        rev: options.flag_rev.as_ref().map(|s| &s[..]),
+    };
+
+    let ws = try!(Workspace::new(&root, config));
+    try!(ops::add(&ws, &opts));
+    Ok(None)
+}
@@ -1,5 +1,5 @@
 use cargo::core::Workspace;
-use cargo::ops;
+use cargo::ops::{self, MessageFormat};
 use cargo::util::{CliResult, CliError, Human, Config, human};
 use cargo::util::important_paths::{find_root_manifest_for_wd};
 
@@ -7,6 +7,9 @@ use cargo::util::important_paths::{find_root_manifest_for_wd};
 pub struct Options {
     flag_no_run: bool,
     flag_package: Vec<String>,
+    flag_changed_since: Option<String>,
+    flag_exclude: Vec<String>,
+    flag_select: Vec<String>,
     flag_jobs: Option<u32>,
     flag_features: Vec<String>,
     flag_all_features: bool,
@@ -16,6 +19,7 @@ pub struct Options {
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
+    flag_message_format: MessageFormat,
     flag_lib: bool,
     flag_bin: Vec<String>,
     flag_example: Vec<String>,
@@ -23,6 +27,10 @@ pub struct Options {
     flag_bench: Vec<String>,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_vendored: Option<String>,
+    flag_dep_archive: Option<String>,
     arg_args: Vec<String>,
 }
 
@@ -41,6 +49,9 @@ Options:
     --bench NAME                 Benchmark only the specified bench target
     --no-run                     Compile, but don't run benchmarks
     -p SPEC, --package SPEC ...  Package to run benchmarks for
+    --changed-since REV          Benchmark only packages changed since REV
+    --exclude SPEC ...           Exclude packages from the benchmark
+    --select EXPR ...            Benchmark only members whose manifest matches EXPR
     -j N, --jobs N               Number of parallel jobs, defaults to # of CPUs
     --features FEATURES          Space-separated list of features to also build
     --all-features               Build all available features
@@ -50,8 +61,13 @@ Options:
     -v, --verbose ...            Use verbose output
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
+    --message-format FMT         Error format: human, json [default: human]
     --frozen                     Require Cargo.lock and cache are up to date
     --locked                     Require Cargo.lock is up to date
+    --offline                    Only access the network as allowed by --allow-net
+    --allow-net SPEC ...         Endpoint to allow while offline, e.g. registry:NAME
+    --vendored DIR               Replace registries with the vendor directory DIR
+    --dep-archive FILE           Use the dependencies archived in FILE
 
 All of the trailing arguments are passed to the benchmark binaries generated
 for filtering benchmarks and generally providing options configuring how they
@@ -62,6 +78,20 @@ which indicates which package should be benchmarked. If it is not given, then
 the current package is benchmarked. For more information on SPEC and its format,
 see the `cargo help pkgid` command.
 
+With --changed-since only the workspace members with files which differ from
+the git revision REV, committed or not, are benchmarked, along with the members which
+depend on them. Changes to Cargo.lock affect every member.
+
+SPEC may also be a glob pattern such as `net-*`, matched against the names of
+the workspace members, and a SPEC prefixed with `!` excludes the members it
+matches, as does --exclude. With --select only the members whose manifest
+passes the test EXPR are benchmarked, e.g. `package.metadata.ci.tier == 1`; a key
+without a comparison must be present and not false.
+
+When run from the root of a workspace without --package, the members listed
+in the `bench` key of the `workspace.default-members` table of its manifest are
+benchmarked, if there are any.
+
 The --jobs argument affects the building of the benchmark executable but does
 not affect how many jobs are used when running the benchmarks.
 
@@ -75,11 +105,32 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    try!(config.configure_vendored(options.flag_vendored.as_ref()
+                                          .map(|s| &s[..])));
+    try!(config.configure_dep_archive(options.flag_dep_archive.as_ref()
+                                             .map(|s| &s[..])));
+    let spec = match options.flag_changed_since {
+        Some(ref rev) => {
+            let changed = try!(ops::changed_specs(&root, config, rev,
+                                                  &options.flag_package));
+            if changed.is_empty() {
+                return Ok(None)
+            }
+            changed
+        }
+        None => options.flag_package.clone(),
+    };
+    let spec = try!(ops::select_specs(&root, config, "bench", &spec,
+                                      &options.flag_exclude,
+                                      &options.flag_select));
 
     let ops = ops::TestOptions {
         no_run: options.flag_no_run,
         no_fail_fast: false,
         only_doc: false,
+        test_shard: None,
         compile_opts: ops::CompileOptions {
             config: config,
             jobs: options.flag_jobs,
@@ -87,7 +138,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
             features: &options.flag_features,
             all_features: options.flag_all_features,
             no_default_features: options.flag_no_default_features,
-            spec: &options.flag_package,
+            spec: &spec,
             exec_engine: None,
             release: true,
             mode: ops::CompileMode::Bench,
@@ -98,10 +149,16 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                                             &options.flag_bench),
             target_rustdoc_args: None,
             target_rustc_args: None,
+            emit_ninja: None,
+            message_format: options.flag_message_format,
+            self_profile: None,
+            preserve_old_artifacts: false,
+            sbom: None,
+            shard: None,
         },
     };
 
-    let ws = try!(Workspace::new(&root, config));
+    let ws = try!(Workspace::new_for_specs(&root, config, &spec));
     let err = try!(ops::run_benches(&ws, &ops, &options.arg_args));
     match err {
         None => Ok(None),
@@ -1,14 +1,16 @@
 use std::env;
 
 use cargo::core::Workspace;
-use cargo::ops::CompileOptions;
+use cargo::ops::{CompileOptions, MessageFormat, SbomFormat};
 use cargo::ops;
-use cargo::util::important_paths::{find_root_manifest_for_wd};
 use cargo::util::{CliResult, Config};
 
 #[derive(RustcDecodable)]
 pub struct Options {
     flag_package: Vec<String>,
+    flag_changed_since: Option<String>,
+    flag_exclude: Vec<String>,
+    flag_select: Vec<String>,
     flag_jobs: Option<u32>,
     flag_features: Vec<String>,
     flag_all_features: bool,
@@ -18,6 +20,7 @@ pub struct Options {
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
+    flag_message_format: MessageFormat,
     flag_release: bool,
     flag_lib: bool,
     flag_bin: Vec<String>,
@@ -25,7 +28,16 @@ pub struct Options {
     flag_test: Vec<String>,
     flag_bench: Vec<String>,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_vendored: Option<String>,
+    flag_dep_archive: Option<String>,
     flag_frozen: bool,
+    flag_determinism_check: bool,
+    flag_self_profile: Option<String>,
+    flag_preserve_old_artifacts: bool,
+    flag_emit_ninja: Option<String>,
+    flag_sbom: Option<SbomFormat>,
 }
 
 pub const USAGE: &'static str = "
@@ -37,6 +49,9 @@ Usage:
 Options:
     -h, --help                   Print this message
     -p SPEC, --package SPEC ...  Package to build
+    --changed-since REV          Build only packages changed since REV
+    --exclude SPEC ...           Exclude packages from the build
+    --select EXPR ...            Build only members whose manifest matches EXPR
     -j N, --jobs N               Number of parallel jobs, defaults to # of CPUs
     --lib                        Build only this package's library
     --bin NAME                   Build only the specified binary
@@ -52,17 +67,74 @@ Options:
     -v, --verbose ...            Use verbose output
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
+    --message-format FMT         Error format: human, json [default: human]
     --frozen                     Require Cargo.lock and cache are up to date
     --locked                     Require Cargo.lock is up to date
+    --offline                    Only access the network as allowed by --allow-net
+    --allow-net SPEC ...         Endpoint to allow while offline, e.g. registry:NAME
+    --vendored DIR               Replace registries with the vendor directory DIR
+    --dep-archive FILE           Use the dependencies archived in FILE
+    --determinism-check          Build twice and check the artifacts are identical
+    --self-profile DIR           Profile rustc and write a summary to DIR
+    --preserve-old-artifacts     Set aside artifacts of a previous compiler
+    --emit-ninja FILE            Write a Ninja build file to FILE instead of building
+    --sbom FMT                   Write a bill of materials: cyclonedx, spdx
 
 If the --package argument is given, then SPEC is a package id specification
 which indicates which package should be built. If it is not given, then the
 current package is built. For more information on SPEC and its format, see the
 `cargo help pkgid` command.
 
+With --changed-since only the workspace members with files which differ from
+the git revision REV, committed or not, are built, along with the members which
+depend on them. Changes to Cargo.lock affect every member.
+
+SPEC may also be a glob pattern such as `net-*`, matched against the names of
+the workspace members, and a SPEC prefixed with `!` excludes the members it
+matches, as does --exclude. With --select only the members whose manifest
+passes the test EXPR are built, e.g. `package.metadata.ci.tier == 1`; a key
+without a comparison must be present and not false.
+
+A `.rs` file given as --manifest-path is built as a single-file package, as
+described in `cargo help run`.
+
+When run from the root of a workspace without --package, the members listed
+in the `build` key of the `workspace.default-members` table of its manifest are
+built, if there are any.
+
+With --sbom a software bill of materials of each package built is written next
+to its artifacts, e.g. `target/debug/foo.cdx.json`, listing the exact version,
+checksum, features, license and target triples of every package compiled into
+it, and the toolchain used.
+
 Compilation can be configured via the use of profiles which are configured in
 the manifest. The default profile for this command is `dev`, but passing
 the --release flag will use the `release` profile instead.
+
+With --determinism-check everything is built a second time into a separate
+target directory, and each artifact of the two builds is compared. Any which
+differ are reported along with a guess at what made them differ, and the
+command fails.
+
+With --self-profile the selected packages are compiled with rustc's
+`-Z time-passes`, which requires a nightly compiler. The timings of each target
+are kept in the `self-profile` directory of the target directory, and a summary
+of the slowest passes of each is written to `summary.txt` in DIR.
+
+Artifacts built by another version of rustc can't be reused, so everything is
+rebuilt when the compiler changes. With --preserve-old-artifacts the artifacts
+of the previous compiler are moved to the `toolchains` directory of the target
+directory instead of being overwritten, and are moved back when building with
+that compiler again.
+
+With --emit-ninja nothing is built, and the build is instead written to FILE as
+a Ninja build file running the same compiler invocations as Cargo, which can
+then be built with `ninja -f FILE`. Build scripts are run by Ninja too, but only
+the `cargo:rustc-link-search`, `cargo:rustc-link-lib`, `cargo:rustc-flags` and
+`cargo:rustc-cfg` lines of their output are applied, which needs a compiler
+reading arguments from `@file`. The metadata of packages with a `links` key is
+passed to the build scripts depending on them as `DEP_*` variables. The
+commands are run by a POSIX shell.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -73,8 +145,35 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    try!(config.configure_vendored(options.flag_vendored.as_ref()
+                                          .map(|s| &s[..])));
+    try!(config.configure_dep_archive(options.flag_dep_archive.as_ref()
+                                             .map(|s| &s[..])));
 
-    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let root = try!(ops::find_script_or_root_manifest(options.flag_manifest_path,
+                                                      config));
+    let spec = match options.flag_changed_since {
+        Some(ref rev) => {
+            let changed = try!(ops::changed_specs(&root, config, rev,
+                                                  &options.flag_package));
+            if changed.is_empty() {
+                return Ok(None)
+            }
+            changed
+        }
+        None => options.flag_package.clone(),
+    };
+    let spec = try!(ops::select_specs(&root, config, "build", &spec,
+                                      &options.flag_exclude,
+                                      &options.flag_select));
+    let self_profile = options.flag_self_profile.as_ref().map(|dir| {
+        config.cwd().join(dir)
+    });
+    let emit_ninja = options.flag_emit_ninja.as_ref().map(|file| {
+        config.cwd().join(file)
+    });
 
     let opts = CompileOptions {
         config: config,
@@ -83,7 +182,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
         features: &options.flag_features,
         all_features: options.flag_all_features,
         no_default_features: options.flag_no_default_features,
-        spec: &options.flag_package,
+        spec: &spec,
         exec_engine: None,
         mode: ops::CompileMode::Build,
         release: options.flag_release,
@@ -94,9 +193,21 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                                         &options.flag_bench),
         target_rustdoc_args: None,
         target_rustc_args: None,
+        emit_ninja: emit_ninja.as_ref().map(|p| &**p),
+        message_format: options.flag_message_format,
+        self_profile: self_profile.as_ref().map(|p| &**p),
+        preserve_old_artifacts: options.flag_preserve_old_artifacts,
+        sbom: options.flag_sbom,
+        shard: None,
     };
 
-    let ws = try!(Workspace::new(&root, config));
-    try!(ops::compile(&ws, &opts));
+    let ws = try!(Workspace::new_for_specs(&root, config, &spec));
+    if options.flag_determinism_check {
+        let mut second = try!(Workspace::new_for_specs(&root, config, &spec));
+        second.set_target_dir(ws.target_dir().join("determinism-check"));
+        try!(ops::check_determinism(&ws, &second, &opts));
+    } else {
+        try!(ops::compile(&ws, &opts));
+    }
     Ok(None)
 }
@@ -12,7 +12,7 @@ use std::env;
 use std::fs;
 use std::path::{Path,PathBuf};
 
-use cargo::core::shell::Verbosity;
+use cargo::core::shell::{Subsystem, Verbosity};
 use cargo::execute_main_without_stdin;
 use cargo::util::{self, CliResult, lev_distance, Config, human, CargoResult};
 use cargo::util::CliError;
@@ -64,6 +64,11 @@ Some common cargo commands are (see all commands with --list):
     install     Install a Rust binary
 
 See 'cargo help <command>' for more information on a specific command.
+
+The verbosity of some parts of cargo can be raised on their own by passing
+`-v=SUBSYSTEM,...` or `-vv=SUBSYSTEM,...` to a command, where the subsystems
+are `download`, `compile`, `uplift` and `fingerprint`. Everything but `compile`
+only has detailed messages, which a single `-v` is enough to show.
 ";
 
 fn main() {
@@ -73,34 +78,52 @@ fn main() {
 
 macro_rules! each_subcommand{
     ($mac:ident) => {
+        $mac!(add);
         $mac!(bench);
         $mac!(build);
         $mac!(clean);
+        $mac!(complete);
         $mac!(doc);
+        $mac!(doctor);
+        $mac!(export);
         $mac!(fetch);
         $mac!(generate_lockfile);
         $mac!(git_checkout);
+        $mac!(hash_inputs);
         $mac!(help);
+        $mac!(info);
         $mac!(init);
         $mac!(install);
+        $mac!(local_registry);
         $mac!(locate_project);
         $mac!(login);
+        $mac!(logout);
         $mac!(metadata);
         $mac!(new);
+        $mac!(outdated);
         $mac!(owner);
         $mac!(package);
+        $mac!(pin_artifacts);
         $mac!(pkgid);
         $mac!(publish);
         $mac!(read_manifest);
+        $mac!(remove);
+        $mac!(report);
+        $mac!(resolve);
         $mac!(run);
+        $mac!(rust_project);
         $mac!(rustc);
         $mac!(rustdoc);
         $mac!(search);
+        $mac!(task);
         $mac!(test);
+        $mac!(tree);
         $mac!(uninstall);
         $mac!(update);
+        $mac!(upgrade);
         $mac!(verify_project);
         $mac!(version);
+        $mac!(vet);
         $mac!(yank);
     }
 }
@@ -184,6 +207,7 @@ fn execute(flags: Flags, config: &Config) -> CliResult<Option<()>> {
             args
         }
     };
+    let args = try!(raise_subsystem_verbosity(config, args));
 
     if try_execute(&config, &args) {
         return Ok(None)
@@ -209,6 +233,47 @@ fn execute(flags: Flags, config: &Config) -> CliResult<Option<()>> {
     Ok(None)
 }
 
+/// Takes the `-v=SUBSYSTEM,...` and `-vv=SUBSYSTEM,...` flags, which raise
+/// the verbosity of the subsystems listed only, out of the arguments of a
+/// subcommand.
+fn raise_subsystem_verbosity(config: &Config, args: Vec<String>)
+                             -> CargoResult<Vec<String>> {
+    let mut rest = Vec::new();
+    let mut passthrough = false;
+    for arg in args {
+        let verbosity = if passthrough {
+            None
+        } else if arg.starts_with("-vv=") {
+            Some((Verbosity::Trace, 4))
+        } else if arg.starts_with("-v=") {
+            Some((Verbosity::Verbose, 3))
+        } else if arg.starts_with("--verbose=") {
+            Some((Verbosity::Verbose, 10))
+        } else {
+            None
+        };
+        match verbosity {
+            Some((verbosity, prefix)) => {
+                for name in arg[prefix..].split(',') {
+                    let subsystem: Subsystem = try!(name.parse());
+                    let verbosity = match subsystem {
+                        Subsystem::Compile => verbosity,
+                        _ => Verbosity::Trace,
+                    };
+                    config.shell().raise_verbosity(subsystem, verbosity);
+                }
+            }
+            None => {
+                // Whatever follows `--` is for the program the subcommand
+                // runs.
+                passthrough |= arg == "--";
+                rest.push(arg);
+            }
+        }
+    }
+    Ok(rest)
+}
+
 fn try_execute(config: &Config, args: &[String]) -> bool {
     macro_rules! cmd {
         ($name:ident) => (if args[1] == stringify!($name).replace("_", "-") {
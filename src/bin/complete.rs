@@ -0,0 +1,65 @@
+use cargo::ops;
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    arg_shell: Option<String>,
+    flag_values: Option<String>,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+}
+
+pub const USAGE: &'static str = "
+Generate shell completions which complete from the workspace
+
+Usage:
+    cargo complete [options] <shell>
+    cargo complete [options] --values KIND
+
+Options:
+    -h, --help              Print this message
+    --values KIND           Print the values of KIND found in the workspace
+    --manifest-path PATH    Path to the manifest of the workspace
+    -v, --verbose ...       Use verbose output
+    -q, --quiet             No output printed to stdout
+    --color WHEN            Coloring: auto, always, never
+    --frozen                Require Cargo.lock and cache are up to date
+    --locked                Require Cargo.lock is up to date
+
+The completion script for <shell>, `bash` or `zsh`, is printed, and can be
+loaded with e.g. `source <(cargo complete bash)`. Flags are completed from the
+`--help` of each command, and the values of -p, --bin, --example, --test,
+--bench, --features and --profile by running `cargo complete --values KIND`.
+
+With --values the names of the packages, bins, examples, tests, benches,
+features or profiles of the workspace are printed, one per line, as KIND
+names them. They're read from the cached output of `cargo metadata`, so only
+the first run after a manifest changes loads the workspace.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+
+    let kind = match options.flag_values {
+        Some(kind) => kind,
+        None => {
+            let shell = options.arg_shell.unwrap();
+            print!("{}", try!(ops::completion_script(&shell)));
+            return Ok(None)
+        }
+    };
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    for value in try!(ops::completion_values(&root, config, &kind)) {
+        println!("{}", value);
+    }
+    Ok(None)
+}
@@ -1,5 +1,5 @@
 use cargo::core::Workspace;
-use cargo::ops;
+use cargo::ops::{self, MessageFormat};
 use cargo::util::{CliResult, Config};
 use cargo::util::important_paths::{find_root_manifest_for_wd};
 
@@ -17,11 +17,16 @@ pub struct Options {
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
+    flag_message_format: MessageFormat,
     flag_package: Vec<String>,
     flag_lib: bool,
     flag_bin: Vec<String>,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_vendored: Option<String>,
+    flag_dep_archive: Option<String>,
 }
 
 pub const USAGE: &'static str = "
@@ -47,8 +52,13 @@ Options:
     -v, --verbose ...            Use verbose output
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
+    --message-format FMT         Error format: human, json [default: human]
     --frozen                     Require Cargo.lock and cache are up to date
     --locked                     Require Cargo.lock is up to date
+    --offline                    Only access the network as allowed by --allow-net
+    --allow-net SPEC ...         Endpoint to allow while offline, e.g. registry:NAME
+    --vendored DIR               Replace registries with the vendor directory DIR
+    --dep-archive FILE           Use the dependencies archived in FILE
 
 By default the documentation for the local package and all dependencies is
 built. The output is all placed in `target/doc` in rustdoc's usual format.
@@ -65,6 +75,12 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    try!(config.configure_vendored(options.flag_vendored.as_ref()
+                                          .map(|s| &s[..])));
+    try!(config.configure_dep_archive(options.flag_dep_archive.as_ref()
+                                             .map(|s| &s[..])));
 
     let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
 
@@ -90,11 +106,17 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                 deps: !options.flag_no_deps,
             },
             target_rustc_args: None,
+            emit_ninja: None,
+            message_format: options.flag_message_format,
+            self_profile: None,
+            preserve_old_artifacts: false,
+            sbom: None,
+            shard: None,
             target_rustdoc_args: None,
         },
     };
 
-    let ws = try!(Workspace::new(&root, config));
+    let ws = try!(Workspace::new_for_specs(&root, config, &options.flag_package));
     try!(ops::doc(&ws, &doc_opts));
     Ok(None)
 }
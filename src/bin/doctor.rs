@@ -0,0 +1,62 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Diagnose problems with the sources and caches Cargo uses
+
+Usage:
+    cargo doctor [options]
+
+Options:
+    -h, --help                 Print this message
+    --manifest-path PATH       Path to the manifest of a project
+    -v, --verbose ...          Use verbose output
+    -q, --quiet                No output printed to stdout
+    --color WHEN               Coloring: auto, always, never
+    --frozen                   Require Cargo.lock and cache are up to date
+    --locked                   Require Cargo.lock is up to date
+    --offline                  Only access the network as allowed by --allow-net
+    --allow-net SPEC ...       Endpoint to allow while offline, e.g. registry:NAME
+
+Every source in the configuration is checked to be reachable, or for those
+replaced with another source, that the replacement is configured properly. The
+downloaded packages the Cargo.lock of the current project refers to are
+checked against the checksums it lists, and the git repositories fetched for
+git dependencies are checked for corruption.
+
+Each problem found is reported along with how to fix it, and the command fails
+if there were any.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+
+    // Outside of a project only what isn't specific to one is checked.
+    let ws = match find_root_manifest_for_wd(options.flag_manifest_path,
+                                             config.cwd()) {
+        Ok(root) => Some(try!(Workspace::new(&root, config))),
+        Err(..) => None,
+    };
+    try!(ops::doctor(config, ws.as_ref()));
+    Ok(None)
+}
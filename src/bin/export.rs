@@ -0,0 +1,78 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_format: String,
+    flag_platforms: String,
+    flag_features: Vec<String>,
+    flag_all_features: bool,
+    flag_no_default_features: bool,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Generate build rules for the dependencies of a project for other build systems
+
+Usage:
+    cargo export [options] --format FMT
+
+Options:
+    -h, --help                 Print this message
+    --format FMT               Build system to generate rules for: bazel, buck2
+    --platforms LABEL          Package of platform conditions [default: //platforms]
+    --features FEATURES        Space-separated list of features to activate
+    --all-features             Activate all available features
+    --no-default-features      Do not activate the `default` feature
+    --manifest-path PATH       Path to the manifest of the workspace
+    -v, --verbose ...          Use verbose output
+    -q, --quiet                No output printed to stdout
+    --color WHEN               Coloring: auto, always, never
+    --frozen                   Require Cargo.lock and cache are up to date
+    --locked                   Require Cargo.lock is up to date
+    --offline                  Only access the network as allowed by --allow-net
+    --allow-net SPEC ...       Endpoint to allow while offline, e.g. registry:NAME
+
+The dependencies of the workspace are resolved and a rule is printed for the
+library of every package which isn't local, named `NAME-VERSION`, with the
+features Cargo would activate. Build scripts get rules of their own, and
+direct dependencies of the workspace which are used in only one version can
+also be referred to by their name.
+
+The rules expect the sources of each package in a directory named after the
+rule. Dependencies which are only used on some platforms are selected with
+conditions in the package given by --platforms named after the platform, with
+everything other than alphanumerics, `-` and `.` replaced by `_`. For example
+a `[target.'cfg(unix)'.dependencies]` entry is selected by
+`//platforms:cfg_unix_`, and these conditions need to be defined separately.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+    let rules = try!(ops::export(&ws, &ops::ExportOptions {
+        format: try!(options.flag_format.parse()),
+        features: options.flag_features,
+        all_features: options.flag_all_features,
+        no_default_features: options.flag_no_default_features,
+        platforms: &options.flag_platforms,
+    }));
+    print!("{}", rules);
+    Ok(None)
+}
@@ -1,5 +1,5 @@
 use cargo::core::Workspace;
-use cargo::ops;
+use cargo::ops::{self, MessageFormat};
 use cargo::util::{CliResult, Config};
 use cargo::util::important_paths::find_root_manifest_for_wd;
 
@@ -11,6 +11,12 @@ pub struct Options {
     flag_color: Option<String>,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_vendored: Option<String>,
+    flag_dep_archive: Option<String>,
+    flag_archive: Option<String>,
+    flag_message_format: MessageFormat,
 }
 
 pub const USAGE: &'static str = "
@@ -27,6 +33,12 @@ Options:
     --color WHEN             Coloring: auto, always, never
     --frozen                 Require Cargo.lock and cache are up to date
     --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
+    --vendored DIR           Replace registries with the vendor directory DIR
+    --dep-archive FILE       Use the dependencies archived in FILE
+    --archive FILE           Also archive the dependencies into FILE
+    --message-format FMT     Progress format: human, json [default: human]
 
 If a lockfile is available, this command will ensure that all of the git
 dependencies and/or registries dependencies are downloaded and locally
@@ -36,6 +48,15 @@ the lockfile changes.
 If the lockfile is not available, then this is the equivalent of
 `cargo generate-lockfile`. A lockfile is generated and dependencies are also
 all updated.
+
+Packages are downloaded several at a time, as many as `net.download-jobs`
+(8 by default), and interrupted downloads pick up where they stopped. With
+`--message-format json` the progress of each download is printed as JSON.
+
+With `--archive FILE` the dependencies from registries and git repositories are
+also written to FILE, so the package can be built where there's no network by
+passing `--offline --dep-archive FILE` to Cargo there. The archive is checked
+against the checksums in Cargo.lock when it's used.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -44,9 +65,23 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    try!(config.configure_vendored(options.flag_vendored.as_ref()
+                                          .map(|s| &s[..])));
+    try!(config.configure_dep_archive(options.flag_dep_archive.as_ref()
+                                             .map(|s| &s[..])));
     let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
     let ws = try!(Workspace::new(&root, config));
-    try!(ops::fetch(&ws));
+    match options.flag_archive {
+        Some(ref file) => {
+            try!(ops::fetch_archive(&ws, options.flag_message_format,
+                                    &config.cwd().join(file)));
+        }
+        None => {
+            try!(ops::fetch(&ws, options.flag_message_format));
+        }
+    }
     Ok(None)
 }
 
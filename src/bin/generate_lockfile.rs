@@ -1,18 +1,21 @@
 use std::env;
 
 use cargo::core::Workspace;
-use cargo::ops;
-use cargo::util::{CliResult, Config};
+use cargo::ops::{self, MessageFormat};
+use cargo::util::{error_json, CliResult, Config};
 use cargo::util::important_paths::find_root_manifest_for_wd;
 
 #[derive(RustcDecodable)]
 pub struct Options {
     flag_manifest_path: Option<String>,
+    flag_format: MessageFormat,
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
 }
 
 pub const USAGE: &'static str = "
@@ -24,11 +27,18 @@ Usage:
 Options:
     -h, --help               Print this message
     --manifest-path PATH     Path to the manifest to generate a lockfile for
+    --format FMT             Format of resolution errors: human, json [default: human]
     -v, --verbose ...        Use verbose output
     -q, --quiet              No output printed to stdout
     --color WHEN             Coloring: auto, always, never
     --frozen                 Require Cargo.lock and cache are up to date
     --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
+
+With `--format json`, when no version of a dependency can be selected, the
+conflicting requirements are also written to stdout as a JSON object, each with
+the chain of packages it comes from.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -38,9 +48,18 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
     let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
 
     let ws = try!(Workspace::new(&root, config));
-    try!(ops::generate_lockfile(&ws));
+    if let Err(e) = ops::generate_lockfile(&ws) {
+        if options.flag_format == MessageFormat::Json {
+            if let Some(json) = error_json(&*e) {
+                println!("{}", json);
+            }
+        }
+        return Err(e.into())
+    }
     Ok(None)
 }
@@ -0,0 +1,60 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_package: Vec<String>,
+    flag_sources: bool,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+}
+
+pub const USAGE: &'static str = "
+Print a hash of everything that goes into building a package
+
+Usage:
+    cargo hash-inputs [options]
+
+Options:
+    -h, --help                   Print this message
+    -p SPEC, --package SPEC ...  Package whose inputs to hash
+    --sources                    Also hash the source files of local packages
+    --manifest-path PATH         Path to the manifest of the workspace
+    -v, --verbose ...            Use verbose output
+    -q, --quiet                  No output printed to stdout
+    --color WHEN                 Coloring: auto, always, never
+    --frozen                     Require Cargo.lock and cache are up to date
+    --locked                     Require Cargo.lock is up to date
+
+The hash covers the workspace's root manifest, Cargo.lock, all configuration
+files in effect, and the manifests of every local package the selected
+packages depend on. With --sources the files of those packages which would be
+included when packaging them are hashed as well. If no --package is given then
+all members of the workspace are selected.
+
+The hash only depends on the contents of these files and not on where the
+workspace is located, which makes it suitable as a cache key in CI. A Cargo.lock
+must exist for this command.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+    let hash = try!(ops::hash_inputs(&ws, &ops::HashInputsOptions {
+        spec: &options.flag_package,
+        sources: options.flag_sources,
+    }));
+    println!("{}", hash);
+    Ok(None)
+}
@@ -0,0 +1,76 @@
+use cargo::core::Workspace;
+use cargo::ops::{self, MessageFormat};
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    arg_crate: String,
+    flag_index: Option<String>,
+    flag_format: MessageFormat,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Describe a crate of the registry
+
+Usage:
+    cargo info [options] <crate>
+
+Options:
+    -h, --help               Print this message
+    --index INDEX            Registry index to look the crate up in
+    --format FMT             Output format: human, json [default: human]
+    --manifest-path PATH     Path to the manifest whose lockfile to check
+    -v, --verbose ...        Use verbose output
+    -q, --quiet              No output printed to stdout
+    --color WHEN             Coloring: auto, always, never
+    --frozen                 Require Cargo.lock and cache are up to date
+    --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
+
+The crate is a name, optionally followed by `@` and a version requirement, as
+in `cargo info log@0.3`. Every version of the crate is listed, along with the
+oldest version of Rust it declares it builds with and whether it was yanked.
+The features and dependencies are those of the newest version which isn't
+yanked or a pre-release, among those matching the requirement if one is given.
+
+The owners of the crate are asked from the registry's API. When run in a
+workspace, the versions of the crate its lockfile uses are listed as well.
+
+With `--format json`, all of this is written as a JSON object with the keys
+`name`, `version`, `versions`, `features`, `dependencies`, `owners` and
+`locked`.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+
+    // Outside of a workspace there's no lockfile to check.
+    let ws = match find_root_manifest_for_wd(options.flag_manifest_path.clone(),
+                                             config.cwd()) {
+        Ok(root) => Some(try!(Workspace::new(&root, config))),
+        Err(..) if options.flag_manifest_path.is_none() => None,
+        Err(e) => return Err(e.into()),
+    };
+    try!(ops::info(config, &ops::InfoOptions {
+        krate: &options.arg_crate,
+        index: options.flag_index.clone(),
+        format: options.flag_format,
+    }, ws.as_ref()));
+    Ok(None)
+}
@@ -27,8 +27,9 @@ Usage:
 Options:
     -h, --help          Print this message
     --vcs VCS           Initialize a new repository for the given version
-                        control system (git or hg) or do not initialize any version
-                        control at all (none) overriding a global configuration.
+                        control system (git, hg, jj, sapling or fossil) or do
+                        not initialize any version control at all (none)
+                        overriding a global configuration.
     --bin               Use a binary (application) template
     --lib               Use a library template
     --name NAME         Set the resulting package name
@@ -54,7 +55,8 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                                      flag_bin,
                                      flag_lib,
                                      tmp,
-                                     flag_name.as_ref().map(|s| s.as_ref()));
+                                     flag_name.as_ref().map(|s| s.as_ref()),
+                                    None);
 
     let opts_lib = opts.lib;
     try!(ops::init(opts, config));
@@ -16,11 +16,18 @@ pub struct Options {
     flag_color: Option<String>,
     flag_root: Option<String>,
     flag_list: bool,
+    flag_outdated: bool,
+    flag_upgrade_all: bool,
+    flag_from_metadata: Option<String>,
+    flag_target: Option<String>,
     flag_force: bool,
+    flag_prebuilt: bool,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
 
-    arg_crate: Option<String>,
+    arg_crate: Vec<String>,
     flag_vers: Option<String>,
 
     flag_git: Option<String>,
@@ -35,8 +42,10 @@ pub const USAGE: &'static str = "
 Install a Rust binary
 
 Usage:
-    cargo install [options] [<crate>]
-    cargo install [options] --list
+    cargo install [options] [<crate>...]
+    cargo install [options] --list [--outdated]
+    cargo install [options] --upgrade-all
+    cargo install [options] --from-metadata FILE
 
 Specifying what crate to install:
     --vers VERS               Specify a version to install from crates.io
@@ -45,23 +54,28 @@ Specifying what crate to install:
     --tag TAG                 Tag to use when installing from git
     --rev SHA                 Specific commit to use when installing from git
     --path PATH               Filesystem path to local crate to install
+    --from-metadata FILE      Install the packages listed in a `.crates2.json` file
 
 Build and install options:
     -h, --help                Print this message
     -j N, --jobs N            Number of parallel jobs, defaults to # of CPUs
     -f, --force               Force overwriting existing crates or binaries
+    --prebuilt                Use the prebuilt binaries of the crate if it has some
     --features FEATURES       Space-separated list of features to activate
     --all-features            Build all available features
     --no-default-features     Do not build the `default` feature
     --debug                   Build in debug mode instead of release mode
     --bin NAME                Only install the binary NAME
     --example EXAMPLE         Install the example EXAMPLE instead of binaries
+    --target TRIPLE           Build for the target triple
     --root DIR                Directory to install packages into
     -v, --verbose ...         Use verbose output
     -q, --quiet               Less output printed to stdout
     --color WHEN              Coloring: auto, always, never
     --frozen                  Require Cargo.lock and cache are up to date
     --locked                  Require Cargo.lock is up to date
+    --offline                 Only access the network as allowed by --allow-net
+    --allow-net SPEC ...      Endpoint to allow while offline, e.g. registry:NAME
 
 This command manages Cargo's local set of installed binary crates. Only packages
 which have [[bin]] targets can be installed, and all binaries are installed into
@@ -91,7 +105,29 @@ As a special convenience, omitting the <crate> specification entirely will
 install the crate in the current directory. That is, `install` is equivalent to
 the more explicit `install --path .`.
 
+Several crates can be installed at once, from the same source. Each one is
+resolved on its own, and they're then built concurrently in the same target
+directory, so the dependencies they have in common are only built once. The
+`--vers`, `--bin` and `--example` flags can't be used then, and the features
+given apply to every crate.
+
+With `--prebuilt`, or the `install.prebuilt` configuration key, the binaries
+which the crate declares in the `[prebuilt]` section of its manifest for the
+host are downloaded instead of being built, after checking them against the
+checksum of the manifest. They're built as usual when they can't be used, or
+when features, `--debug` or `--example` are given.
+
 The `--list` option will list all installed packages (and their versions).
+With `--outdated` too, only the packages from crates.io or other registries
+which have a newer version are listed, with the newest one.
+
+The `--upgrade-all` option installs the newest version of each of those
+packages, with the features, profile and binaries it was installed with.
+
+How each package was installed is recorded in the `.crates2.json` file of the
+installation root, along with its exact version and source and the compiler it
+was built with. The `--from-metadata` option installs again all the packages
+listed in such a file, for instance one copied from another machine.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -100,11 +136,13 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
 
     let compile_opts = ops::CompileOptions {
         config: config,
         jobs: options.flag_jobs,
-        target: None,
+        target: options.flag_target.as_ref().map(|t| &t[..]),
         features: &options.flag_features,
         all_features: options.flag_all_features,
         no_default_features: options.flag_no_default_features,
@@ -115,6 +153,12 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
         filter: ops::CompileFilter::new(false, &options.flag_bin, &[],
                                         &options.flag_example, &[]),
         target_rustc_args: None,
+        emit_ninja: None,
+        message_format: ops::MessageFormat::Human,
+        self_profile: None,
+        preserve_old_artifacts: false,
+        sbom: None,
+        shard: None,
         target_rustdoc_args: None,
     };
 
@@ -132,20 +176,28 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
         SourceId::for_git(&url, gitref)
     } else if let Some(path) = options.flag_path {
         try!(SourceId::for_path(&config.cwd().join(path)))
-    } else if options.arg_crate == None {
+    } else if options.arg_crate.is_empty() {
         try!(SourceId::for_path(&config.cwd()))
     } else {
         try!(SourceId::crates_io(config))
     };
 
-    let krate = options.arg_crate.as_ref().map(|s| &s[..]);
+    let krates = options.arg_crate.iter().map(|s| &s[..]).collect::<Vec<_>>();
     let vers = options.flag_vers.as_ref().map(|s| &s[..]);
     let root = options.flag_root.as_ref().map(|s| &s[..]);
 
     if options.flag_list {
-        try!(ops::install_list(root, config));
+        try!(ops::install_list(root, options.flag_outdated, config));
+    } else if let Some(ref path) = options.flag_from_metadata {
+        try!(ops::install_from_metadata(root, &config.cwd().join(path),
+                                        &compile_opts, options.flag_force,
+                                        options.flag_prebuilt));
+    } else if options.flag_upgrade_all {
+        try!(ops::install_upgrade_all(root, &compile_opts,
+                                      options.flag_prebuilt));
     } else {
-        try!(ops::install(root, krate, &source, vers, &compile_opts, options.flag_force));
+        try!(ops::install(root, &krates, &source, vers, &compile_opts,
+                          options.flag_force, options.flag_prebuilt));
     }
     Ok(None)
 }
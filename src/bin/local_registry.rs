@@ -0,0 +1,65 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    arg_path: String,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Add the dependencies of a package to a local registry
+
+Usage:
+    cargo local-registry [options] <path>
+
+Options:
+    -h, --help               Print this message
+    --manifest-path PATH     Path to the manifest of the package
+    -v, --verbose ...        Use verbose output
+    -q, --quiet              No output printed to stdout
+    --color WHEN             Coloring: auto, always, never
+    --frozen                 Require Cargo.lock and cache are up to date
+    --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
+
+The packages from registries in Cargo.lock are downloaded, and their `.crate`
+files and index entries are added to the local registry at <path>, which is
+created if it doesn't exist. Packages already in it are kept, so the
+dependencies of several packages can be gathered in one local registry.
+
+The local registry can then replace crates.io, in `.cargo/config`:
+
+    [source.crates-io]
+    replace-with = \"local\"
+
+    [source.local]
+    local-registry = \"<path>\"
+
+Dependencies from git repositories can't be added to a registry, they're
+skipped with a warning.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+    try!(ops::local_registry(&ws, &config.cwd().join(&options.arg_path)));
+    Ok(None)
+}
@@ -1,9 +1,17 @@
-use cargo::util::{CliResult, CliError, human, ChainError, Config};
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::{Encodable, Encoder};
+
+use cargo::core::Workspace;
+use cargo::util::{CliResult, CliError, human, ChainError, Config, CargoResult};
 use cargo::util::important_paths::{find_root_manifest_for_wd};
 
 #[derive(RustcDecodable)]
 pub struct LocateProjectFlags {
     flag_manifest_path: Option<String>,
+    flag_workspace: bool,
+    flag_file: Option<String>,
+    flag_config: bool,
 }
 
 pub const USAGE: &'static str = "
@@ -14,23 +22,133 @@ Usage:
 
 Options:
     --manifest-path PATH    Path to the manifest to locate
+    --workspace             Also report the root manifest of the workspace
+    --file PATH             Also report the workspace member owning PATH
+    --config                Also report the active configuration files
     -h, --help              Print this message
+
+When --file is given without --manifest-path, the search for a manifest starts
+from the directory containing PATH instead of the current directory.
 ";
 
-#[derive(RustcEncodable)]
 pub struct ProjectLocation {
-    root: String
+    root: String,
+    workspace_root: Option<String>,
+    member: Option<MemberLocation>,
+    config_files: Option<Vec<String>>,
+}
+
+#[derive(RustcEncodable)]
+pub struct MemberLocation {
+    name: String,
+    root: String,
+}
+
+// Optional fields are only emitted when requested so the output of a bare
+// `cargo locate-project` stays the same.
+impl Encodable for ProjectLocation {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("ProjectLocation", 4, |s| {
+            let mut idx = 0;
+            try!(s.emit_struct_field("root", idx, |s| self.root.encode(s)));
+            if let Some(ref root) = self.workspace_root {
+                idx += 1;
+                try!(s.emit_struct_field("workspace_root", idx, |s| {
+                    root.encode(s)
+                }));
+            }
+            if let Some(ref member) = self.member {
+                idx += 1;
+                try!(s.emit_struct_field("member", idx, |s| member.encode(s)));
+            }
+            if let Some(ref files) = self.config_files {
+                idx += 1;
+                try!(s.emit_struct_field("config_files", idx, |s| {
+                    files.encode(s)
+                }));
+            }
+            Ok(())
+        })
+    }
 }
 
 pub fn execute(flags: LocateProjectFlags,
                config: &Config) -> CliResult<Option<ProjectLocation>> {
-    let root = try!(find_root_manifest_for_wd(flags.flag_manifest_path, config.cwd()));
+    let file = flags.flag_file.as_ref().map(|f| config.cwd().join(f));
+    let search_dir = match (&flags.flag_manifest_path, &file) {
+        (&None, &Some(ref file)) => {
+            file.parent().unwrap_or(config.cwd()).to_path_buf()
+        }
+        _ => config.cwd().to_path_buf(),
+    };
+    let root = try!(find_root_manifest_for_wd(flags.flag_manifest_path,
+                                              &search_dir));
+
+    let mut location = ProjectLocation {
+        root: try!(path_string(&root)),
+        workspace_root: None,
+        member: None,
+        config_files: None,
+    };
+
+    if flags.flag_workspace || file.is_some() {
+        let ws = try!(Workspace::new(&root, config));
+        if flags.flag_workspace {
+            let manifest = ws.root().join("Cargo.toml");
+            location.workspace_root = Some(try!(path_string(&manifest)));
+        }
+        if let Some(ref file) = file {
+            location.member = Some(try!(owning_member(&ws, file)));
+        }
+    }
+
+    if flags.flag_config {
+        let files = try!(config.config_files());
+        location.config_files = Some(try!(files.iter().map(|p| {
+            path_string(p)
+        }).collect()));
+    }
 
-    let string = try!(root.to_str()
-                      .chain_error(|| human("Your project path contains \
-                                             characters not representable in \
-                                             Unicode"))
-                      .map_err(|e| CliError::new(e, 1)));
+    Ok(Some(location))
+}
+
+/// Finds the workspace member whose package directory most closely contains
+/// `file`.
+fn owning_member(ws: &Workspace, file: &Path) -> CliResult<MemberLocation> {
+    let mut best: Option<(PathBuf, String)> = None;
+    for pkg in ws.members() {
+        let dir = pkg.root();
+        if !file.starts_with(dir) {
+            continue
+        }
+        let better = match best {
+            Some((ref prev, _)) => dir.components().count() >
+                                   prev.components().count(),
+            None => true,
+        };
+        if better {
+            best = Some((dir.to_path_buf(), pkg.name().to_string()));
+        }
+    }
+    match best {
+        Some((dir, name)) => {
+            Ok(MemberLocation {
+                name: name,
+                root: try!(path_string(&dir.join("Cargo.toml"))),
+            })
+        }
+        None => {
+            Err(CliError::new(human(format!("`{}` does not belong to any \
+                                             member of the workspace",
+                                            file.display())), 1))
+        }
+    }
+}
 
-    Ok(Some(ProjectLocation { root: string.to_string() }))
+fn path_string(path: &Path) -> CliResult<String> {
+    let string: CargoResult<&str> = path.to_str().chain_error(|| {
+        human("Your project path contains characters not representable in \
+               Unicode")
+    });
+    string.map(|s| s.to_string()).map_err(|e| CliError::new(e, 1))
 }
@@ -10,6 +10,8 @@ use cargo::util::{CliResult, Config, human, ChainError};
 pub struct Options {
     flag_host: Option<String>,
     arg_token: Option<String>,
+    flag_generate_key: bool,
+    flag_key_id: Option<String>,
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
@@ -22,16 +24,23 @@ Save an api token from the registry locally
 
 Usage:
     cargo login [options] [<token>]
+    cargo login [options] --generate-key
 
 Options:
     -h, --help               Print this message
     --host HOST              Host to set the token for
+    --generate-key           Sign requests with a new key instead of a token
+    --key-id ID              ID the registry knows the new key by
     -v, --verbose ...        Use verbose output
     -q, --quiet              No output printed to stdout
     --color WHEN             Coloring: auto, always, never
     --frozen                 Require Cargo.lock and cache are up to date
     --locked                 Require Cargo.lock is up to date
 
+With --generate-key no token is saved. Instead a new private key is written to
+the Cargo home directory, requests to the registry are signed with it, and its
+public key is printed to be added to your account on the registry. Its ID
+defaults to the SHA-256 digest of the public key.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -40,6 +49,13 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    if options.flag_generate_key {
+        let public = try!(ops::registry_generate_key(config,
+                                                     options.flag_key_id.clone()));
+        println!("add this public key to your account on the registry:\n");
+        print!("{}", String::from_utf8_lossy(&public));
+        return Ok(None)
+    }
     let token = match options.arg_token.clone() {
         Some(token) => token,
         None => {
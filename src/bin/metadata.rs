@@ -1,5 +1,8 @@
+use std::io;
+
 use cargo::core::Workspace;
-use cargo::ops::{output_metadata, OutputMetadataOptions, ExportInfo};
+use cargo::ops::{output_metadata, output_metadata_cached, write_metadata};
+use cargo::ops::{MetadataFormat, OutputMetadataOptions};
 use cargo::util::important_paths::find_root_manifest_for_wd;
 use cargo::util::{CliResult, Config};
 
@@ -9,19 +12,36 @@ pub struct Options {
     flag_features: Vec<String>,
     flag_all_features: bool,
     flag_format_version: u32,
+    flag_format: String,
     flag_manifest_path: Option<String>,
     flag_no_default_features: bool,
     flag_no_deps: bool,
+    flag_filter_platform: Vec<String>,
     flag_quiet: Option<bool>,
     flag_verbose: u32,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_vendored: Option<String>,
+    flag_dep_archive: Option<String>,
 }
 
 pub const USAGE: &'static str = "
 Output the resolved dependencies of a project, the concrete used versions
 including overrides, in machine-readable format.
 
+The JSON output is cached and reused until one of the manifests of the
+packages in the output, the lockfile or the configuration changes.
+
+Unless --no-deps is passed, `downloads` lists each resolved package which
+doesn't come from a path along with its checksum, the size of its compressed
+package if the registry records it, and the URL it's downloaded from.
+
+With `--format ndjson` every package is written on a line of its own as
+`{\"package\": ...}`, followed by a line holding the remaining fields, and
+`--format cbor` encodes the JSON output as CBOR.
+
 Usage:
     cargo metadata [options]
 
@@ -32,33 +52,56 @@ Options:
     --no-default-features      Do not include the `default` feature
     --no-deps                  Output information only about the root package
                                and don't fetch dependencies.
+    --filter-platform TRIPLE ...
+                               Also describe the dependencies and features
+                               used when building for TRIPLE
     --manifest-path PATH       Path to the manifest
     --format-version VERSION   Format version [default: 1]
                                Valid values: 1
+    --format FMT               Output format: json, ndjson, cbor [default: json]
     -v, --verbose ...          Use verbose output
     -q, --quiet                No output printed to stdout
     --color WHEN               Coloring: auto, always, never
     --frozen                   Require Cargo.lock and cache are up to date
     --locked                   Require Cargo.lock is up to date
+    --offline                  Only access the network as allowed by --allow-net
+    --allow-net SPEC ...       Endpoint to allow while offline, e.g. registry:NAME
+    --vendored DIR             Replace registries with the vendor directory DIR
+    --dep-archive FILE         Use the dependencies archived in FILE
 ";
 
-pub fn execute(options: Options, config: &Config) -> CliResult<Option<ExportInfo>> {
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
     try!(config.configure(options.flag_verbose,
                           options.flag_quiet,
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    try!(config.configure_vendored(options.flag_vendored.as_ref()
+                                          .map(|s| &s[..])));
+    try!(config.configure_dep_archive(options.flag_dep_archive.as_ref()
+                                             .map(|s| &s[..])));
     let manifest = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
 
+    let format: MetadataFormat = try!(options.flag_format.parse());
     let options = OutputMetadataOptions {
         features: options.flag_features,
         all_features: options.flag_all_features,
         no_default_features: options.flag_no_default_features,
         no_deps: options.flag_no_deps,
         version: options.flag_format_version,
+        platforms: options.flag_filter_platform,
     };
 
-    let ws = try!(Workspace::new(&manifest, config));
-    let result = try!(output_metadata(&ws, &options));
-    Ok(Some(result))
+    if format == MetadataFormat::Json {
+        let output = try!(output_metadata_cached(&manifest, config, &options));
+        println!("{}", output);
+    } else {
+        let ws = try!(Workspace::new(&manifest, config));
+        let info = try!(output_metadata(&ws, &options));
+        let stdout = io::stdout();
+        try!(write_metadata(&info, format, &mut stdout.lock()));
+    }
+    Ok(None)
 }
@@ -1,5 +1,6 @@
 use std::env;
 
+use cargo::core::GitReference;
 use cargo::ops;
 use cargo::util::{CliResult, Config};
 
@@ -12,6 +13,10 @@ pub struct Options {
     flag_lib: bool,
     arg_path: String,
     flag_name: Option<String>,
+    flag_template: Option<String>,
+    flag_branch: Option<String>,
+    flag_tag: Option<String>,
+    flag_rev: Option<String>,
     flag_vcs: Option<ops::VersionControl>,
     flag_frozen: bool,
     flag_locked: bool,
@@ -27,16 +32,37 @@ Usage:
 Options:
     -h, --help          Print this message
     --vcs VCS           Initialize a new repository for the given version
-                        control system (git or hg) or do not initialize any version
-                        control at all (none) overriding a global configuration.
+                        control system (git, hg, jj, sapling or fossil) or do
+                        not initialize any version control at all (none)
+                        overriding a global configuration.
     --bin               Use a binary (application) template
     --lib               Use a library template
     --name NAME         Set the resulting package name
+    --template SRC      Create the package from a template, a directory or
+                        the URL of a git repository
+    --branch BRANCH     Branch of the template to use, from git
+    --tag TAG           Tag of the template to use, from git
+    --rev SHA           Specific commit of the template to use, from git
     -v, --verbose ...   Use verbose output
     -q, --quiet         No output printed to stdout
     --color WHEN        Coloring: auto, always, never
     --frozen            Require Cargo.lock and cache are up to date
     --locked            Require Cargo.lock is up to date
+
+A template is copied into the new package with `{{name}}`, `{{authors}}` and
+`{{license}}` replaced by the name of the package, its author and the
+`cargo-new.license` configuration key. A `cargo-template.toml` file at the root
+of the template can give a default license and commands to run in the new
+package once it's created:
+
+    [template]
+    license = \"MIT OR Apache-2.0\"
+    hooks = [[\"git\", \"add\", \".\"]]
+
+These commands can do anything, so only use templates you trust.
+
+A template from git is taken from the branch the repository's HEAD points to,
+unless `--branch`, `--tag` or `--rev` is given.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -47,17 +73,35 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           options.flag_frozen,
                           options.flag_locked));
 
-    let Options { flag_bin, flag_lib, arg_path, flag_name, flag_vcs, .. } = options;
+    let Options {
+        flag_bin, flag_lib, arg_path, flag_name, flag_vcs, flag_template,
+        flag_branch, flag_tag, flag_rev, ..
+    } = options;
 
-    let opts = ops::NewOptions::new(flag_vcs,
-                                    flag_bin,
-                                    flag_lib,
-                                    &arg_path,
-                                    flag_name.as_ref().map(|s| s.as_ref()));
+    let mut opts = ops::NewOptions::new(flag_vcs,
+                                        flag_bin,
+                                        flag_lib,
+                                        &arg_path,
+                                        flag_name.as_ref().map(|s| s.as_ref()),
+                                        flag_template.as_ref().map(|s| s.as_ref()));
+    opts.template_ref = if let Some(branch) = flag_branch {
+        Some(GitReference::Branch(branch))
+    } else if let Some(tag) = flag_tag {
+        Some(GitReference::Tag(tag))
+    } else if let Some(rev) = flag_rev {
+        Some(GitReference::Rev(rev))
+    } else {
+        None
+    };
 
     let opts_lib = opts.lib;
     try!(ops::new(opts, config));
 
+    if let Some(ref template) = flag_template {
+        try!(config.shell().status("Created", format!("`{}` project from template `{}`",
+                                                       arg_path, template)));
+        return Ok(None)
+    }
     try!(config.shell().status("Created", format!("{} `{}` project",
                                                    if opts_lib { "library" }
                                                    else {"binary (application)"},
@@ -0,0 +1,158 @@
+use std::cmp;
+
+use rustc_serialize::json;
+
+use cargo::core::Workspace;
+use cargo::ops::{self, MessageFormat, OutdatedOptions};
+use cargo::util::{CliResult, CliError, Config, human};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_direct: bool,
+    flag_deny: bool,
+    flag_format: MessageFormat,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+List the dependencies for which newer versions were released
+
+Usage:
+    cargo outdated [options]
+
+Options:
+    -h, --help               Print this message
+    --direct                 Only list the dependencies members declare themselves
+    --deny                   Fail if any dependency is listed
+    --format FMT             Output format: human, json [default: human]
+    --manifest-path PATH     Path to the manifest of the workspace
+    -v, --verbose ...        Use verbose output
+    -q, --quiet              No output printed to stdout
+    --color WHEN             Coloring: auto, always, never
+    --frozen                 Require Cargo.lock and cache are up to date
+    --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
+
+For each member of the workspace, the registry packages it depends on directly
+or through other packages are listed when a newer version of them was released:
+the newest version semver compatible with the locked one, which `cargo update`
+can move to, and the newest version if it's incompatible, which needs the
+requirements on the package to be raised. Packages replaced with `[replace]`
+are left out, as the version locked for them isn't the one used.
+
+The lockfile itself isn't changed, unless it has to be for the manifests as
+with any other command. With `--format json`, the report is written as a JSON
+object with a `members` array, each entry listing the `name` and `version` of
+the member and its `outdated` dependencies with their `name`, `version`,
+`source`, `compatible` and `latest` versions, the last two null when there's
+none, and whether they're `direct`.
+";
+
+#[derive(RustcEncodable)]
+struct Report {
+    members: Vec<Member>,
+}
+
+#[derive(RustcEncodable)]
+struct Member {
+    name: String,
+    version: String,
+    outdated: Vec<Dependency>,
+}
+
+#[derive(RustcEncodable)]
+struct Dependency {
+    name: String,
+    version: String,
+    source: String,
+    compatible: Option<String>,
+    latest: Option<String>,
+    direct: bool,
+}
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+
+    let report = try!(ops::report_outdated(&ws, &OutdatedOptions {
+        direct_only: options.flag_direct,
+    }));
+    let count = report.iter().map(|&(_, ref deps)| deps.len()).sum::<usize>();
+
+    if options.flag_format == MessageFormat::Json {
+        let report = Report {
+            members: report.iter().map(|&(ref member, ref deps)| {
+                Member {
+                    name: member.name().to_string(),
+                    version: member.version().to_string(),
+                    outdated: deps.iter().map(|dep| {
+                        Dependency {
+                            name: dep.id.name().to_string(),
+                            version: dep.id.version().to_string(),
+                            source: dep.id.source_id().to_url(),
+                            compatible: dep.compatible.as_ref().map(|v| v.to_string()),
+                            latest: dep.latest.as_ref().map(|v| v.to_string()),
+                            direct: dep.direct,
+                        }
+                    }).collect(),
+                }
+            }).collect(),
+        };
+        println!("{}", json::encode(&report).unwrap());
+    } else {
+        for &(ref member, ref deps) in report.iter() {
+            println!("{} {}", member.name(), member.version());
+            if deps.is_empty() {
+                println!("    up to date");
+                continue
+            }
+            let mut rows = vec![["name".to_string(), "locked".to_string(),
+                                 "compatible".to_string(), "latest".to_string(),
+                                 "kind".to_string()]];
+            for dep in deps.iter() {
+                let none = || "-".to_string();
+                rows.push([dep.id.name().to_string(),
+                           dep.id.version().to_string(),
+                           dep.compatible.as_ref().map(|v| v.to_string())
+                                                   .unwrap_or_else(&none),
+                           dep.latest.as_ref().map(|v| v.to_string())
+                                               .unwrap_or_else(&none),
+                           if dep.direct {"direct"} else {"transitive"}.to_string()]);
+            }
+            let mut widths = [0; 5];
+            for row in rows.iter() {
+                for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                    *width = cmp::max(*width, cell.len());
+                }
+            }
+            for row in rows.iter() {
+                let cells = row.iter().zip(widths.iter()).map(|(cell, &width)| {
+                    format!("{:1$}", cell, width)
+                }).collect::<Vec<_>>();
+                println!("    {}", cells.join("  ").trim_right());
+            }
+        }
+    }
+
+    if options.flag_deny && count > 0 {
+        return Err(CliError::new(human(format!("{} dependencies are outdated",
+                                               count)), 101))
+    }
+    Ok(None)
+}
@@ -1,6 +1,6 @@
 use cargo::core::Workspace;
 use cargo::ops;
-use cargo::util::{CliResult, Config};
+use cargo::util::{CargoResult, CliResult, Config};
 use cargo::util::important_paths::find_root_manifest_for_wd;
 
 #[derive(RustcDecodable)]
@@ -16,6 +16,9 @@ pub struct Options {
     flag_jobs: Option<u32>,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_format: Vec<String>,
 }
 
 pub const USAGE: &'static str = "
@@ -37,6 +40,15 @@ Options:
     --color WHEN            Coloring: auto, always, never
     --frozen                Require Cargo.lock and cache are up to date
     --locked                Require Cargo.lock is up to date
+    --offline               Only access the network as allowed by --allow-net
+    --allow-net SPEC ...    Endpoint to allow while offline, e.g. registry:NAME
+    --format FMT ...        Also produce the package as FMT: tar.zst, dir
+
+Next to the `.crate` file, which is always produced, the package can also be
+produced as a zstd-compressed tarball (`tar.zst`) or unpacked into a directory
+under `target/package/export` (`dir`). A `.sha256` file is written next to each
+archive, and exported directories contain a `.cargo-checksum.json` so they can
+be used as a directory source.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -45,8 +57,13 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
     let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
     let ws = try!(Workspace::new(&root, config));
+    let formats = try!(options.flag_format.iter().map(|f| {
+        f.parse::<ops::ArchiveFormat>()
+    }).collect::<CargoResult<Vec<_>>>());
     try!(ops::package(&ws, &ops::PackageOpts {
         config: config,
         verify: !options.flag_no_verify,
@@ -54,6 +71,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
         check_metadata: !options.flag_no_metadata,
         allow_dirty: options.flag_allow_dirty,
         jobs: options.flag_jobs,
+        formats: formats,
     }));
     Ok(None)
 }
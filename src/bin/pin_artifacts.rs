@@ -0,0 +1,86 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliError, CliResult, Config, human};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_package: Vec<String>,
+    flag_profile: Option<String>,
+    flag_target: Option<String>,
+    flag_list: bool,
+    flag_unpin: bool,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+}
+
+pub const USAGE: &'static str = "
+Protect built artifacts from being removed by cleaning or rebuilding
+
+Usage:
+    cargo pin-artifacts [options]
+
+Options:
+    -h, --help                   Print this message
+    -p SPEC, --package SPEC ...  Package whose artifacts to pin
+    --profile PROFILE            Profile the artifacts were built with: dev or release
+    --target TRIPLE              Target triple the artifacts were built for
+    -l, --list                   List the pinned artifacts
+    --unpin                      Remove the pinned artifacts of the packages instead
+    --manifest-path PATH         Path to the manifest of the workspace
+    -v, --verbose ...            Use verbose output
+    -q, --quiet                  No output printed to stdout
+    --color WHEN                 Coloring: auto, always, never
+    --frozen                     Require Cargo.lock and cache are up to date
+    --locked                     Require Cargo.lock is up to date
+
+The libraries, binaries and examples of each package last built with the
+profile given (dev by default) are copied to the `pinned` directory of the
+target directory, replacing those pinned for the package before. Rebuilds,
+changes of the compiler and `cargo clean` leave that directory alone, which
+makes it suitable for keeping benchmark baselines or reference binaries around.
+If no --package is given then the artifacts of the current package are pinned.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+
+    if options.flag_list {
+        for file in try!(ops::list_pinned(&ws)) {
+            println!("{}", file);
+        }
+        return Ok(None)
+    }
+
+    let release = match options.flag_profile.as_ref().map(|s| &s[..]) {
+        None | Some("dev") => false,
+        Some("release") => true,
+        Some(profile) => {
+            return Err(CliError::new(human(format!("unknown profile `{}`, \
+                                                    expected `dev` or \
+                                                    `release`", profile)), 101))
+        }
+    };
+    let opts = ops::PinOptions {
+        config: config,
+        spec: &options.flag_package,
+        target: options.flag_target.as_ref().map(|s| &s[..]),
+        release: release,
+    };
+    if options.flag_unpin {
+        try!(ops::unpin_artifacts(&ws, &opts));
+    } else {
+        try!(ops::pin_artifacts(&ws, &opts));
+    }
+    Ok(None)
+}
@@ -15,8 +15,11 @@ pub struct Options {
     flag_allow_dirty: bool,
     flag_jobs: Option<u32>,
     flag_dry_run: bool,
+    flag_provenance: bool,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
 }
 
 pub const USAGE: &'static str = "
@@ -34,12 +37,19 @@ Options:
     --manifest-path PATH     Path to the manifest of the package to publish
     -j N, --jobs N           Number of parallel jobs, defaults to # of CPUs
     --dry-run                Perform all checks without uploading
+    --provenance             Upload a signed provenance attestation as well
     -v, --verbose ...        Use verbose output
     -q, --quiet              No output printed to stdout
     --color WHEN             Coloring: auto, always, never
     --frozen                 Require Cargo.lock and cache are up to date
     --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
 
+With --provenance a statement of who built the package, from which revision,
+with which toolchain and with the digest of every file is signed and uploaded
+along with it. The builder and the signing key or command are configured in the
+`[provenance]` table of the configuration.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -48,6 +58,8 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
     let Options {
         flag_token: token,
         flag_host: host,
@@ -56,6 +68,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
         flag_allow_dirty: allow_dirty,
         flag_jobs: jobs,
         flag_dry_run: dry_run,
+        flag_provenance: provenance,
         ..
     } = options;
 
@@ -69,6 +82,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
         allow_dirty: allow_dirty,
         jobs: jobs,
         dry_run: dry_run,
+        provenance: provenance,
     }));
     Ok(None)
 }
@@ -0,0 +1,80 @@
+use cargo::core::Workspace;
+use cargo::core::dependency::Kind;
+use cargo::ops;
+use cargo::util::{CliResult, Config, human};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    arg_dep: Vec<String>,
+    flag_dev: bool,
+    flag_build: bool,
+    flag_target: Option<String>,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Remove dependencies from the manifest of a package
+
+Usage:
+    cargo remove [options] <dep>...
+
+Options:
+    -h, --help               Print this message
+    --dev                    Remove from the dev-dependencies
+    --build                  Remove from the build-dependencies
+    --target TARGET          Remove from the dependencies for the platform TARGET
+    --manifest-path PATH     Path to the manifest to remove dependencies from
+    -v, --verbose ...        Use verbose output
+    -q, --quiet              No output printed to stdout
+    --color WHEN             Coloring: auto, always, never
+    --frozen                 Require Cargo.lock and cache are up to date
+    --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
+
+The dependencies are removed from the manifest of the package at
+--manifest-path, or the current package, keeping the rest of the manifest as it
+is. TARGET is a target triple or a `cfg(..)` expression, as in
+`[target.'cfg(unix)'.dependencies]`.
+
+Features which enable a removed dependency, or features of it, no longer do so,
+unless the dependency is still declared by another table. The lockfile is then
+updated, leaving the packages which are still used locked at their current
+versions.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+
+    let kind = match (options.flag_dev, options.flag_build) {
+        (true, true) => return Err(human("--dev and --build can't both be \
+                                          specified").into()),
+        (true, false) => Kind::Development,
+        (false, true) => Kind::Build,
+        (false, false) => Kind::Normal,
+    };
+    let opts = ops::RemoveOptions {
+        dependencies: &options.arg_dep,
+        kind: kind,
+        target: options.flag_target.as_ref().map(|s| &s[..]),
+    };
+
+    let ws = try!(Workspace::new(&root, config));
+    try!(ops::remove(&ws, &opts));
+    Ok(None)
+}
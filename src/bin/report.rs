@@ -0,0 +1,73 @@
+use cargo::core::Workspace;
+use cargo::ops::{self, StalenessAction, StalenessOptions};
+use cargo::util::{CliResult, CliError, Config, human};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_manifest_path: Option<String>,
+    flag_max_age: Option<u64>,
+    flag_max_major_behind: Option<u64>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Report on the dependencies of a project
+
+Usage:
+    cargo report staleness [options]
+
+Options:
+    -h, --help                 Print this message
+    --manifest-path PATH       Path to the manifest of a project
+    --max-age YEARS            Flag versions published more than YEARS ago
+    --max-major-behind N       Flag packages more than N major versions behind
+    -v, --verbose ...          Use verbose output
+    -q, --quiet                No output printed to stdout
+    --color WHEN               Coloring: auto, always, never
+    --frozen                   Require Cargo.lock and cache are up to date
+    --locked                   Require Cargo.lock is up to date
+    --offline                  Only access the network as allowed by --allow-net
+    --allow-net SPEC ...       Endpoint to allow while offline, e.g. registry:NAME
+
+The staleness report lists the locked registry dependencies which break the
+policy in the `[staleness]` table of the configuration: those published more
+than `staleness.max-age` years ago, and those more than
+`staleness.max-major-behind` major versions behind the newest release in the
+local copy of the registry's index. The flags take precedence over the
+configuration. If `staleness.action` is `deny` the command fails when any
+dependency is listed.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+
+    let opts = StalenessOptions {
+        max_age: options.flag_max_age,
+        max_major_behind: options.flag_max_major_behind,
+    };
+    let (policy, stale) = try!(ops::report_staleness(&ws, &opts));
+    for dep in stale.iter() {
+        println!("{}: {}", dep.id, dep.describe());
+    }
+    if policy.action == StalenessAction::Deny && !stale.is_empty() {
+        return Err(CliError::new(human(format!("the staleness policy denies \
+                                                {} dependencies",
+                                               stale.len())), 101))
+    }
+    Ok(None)
+}
@@ -0,0 +1,85 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliResult, CliError, Config, human};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_record: Option<String>,
+    flag_bench: Option<String>,
+    flag_iterations: u32,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Record the resolution of a project and measure the resolver with it
+
+Usage:
+    cargo resolve [options]
+
+Options:
+    -h, --help                 Print this message
+    --record FILE              Record the resolution of the project to FILE
+    --bench FILE               Resolve the graph recorded in FILE
+    --iterations N             Number of resolutions with --bench [default: 10]
+    --manifest-path PATH       Path to the manifest of the workspace
+    -v, --verbose ...          Use verbose output
+    -q, --quiet                No output printed to stdout
+    --color WHEN               Coloring: auto, always, never
+    --frozen                   Require Cargo.lock and cache are up to date
+    --locked                   Require Cargo.lock is up to date
+    --offline                  Only access the network as allowed by --allow-net
+    --allow-net SPEC ...       Endpoint to allow while offline, e.g. registry:NAME
+
+This is a tool for developing Cargo. With --record the workspace is resolved
+from scratch, ignoring Cargo.lock, and every package the resolver considers is
+written to FILE. With --bench that graph is resolved again without involving
+any registry, and the time each resolution took is printed.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+
+    match (options.flag_record, options.flag_bench) {
+        (Some(file), None) => {
+            let root = try!(find_root_manifest_for_wd(options.flag_manifest_path,
+                                                      config.cwd()));
+            let ws = try!(Workspace::new(&root, config));
+            try!(ops::record_resolve(&ws, &config.cwd().join(file)));
+        }
+        (None, Some(file)) => {
+            let bench = try!(ops::bench_resolve(&config.cwd().join(file),
+                                                options.flag_iterations));
+            let millis = bench.times.iter().map(|t| {
+                t.as_secs() as f64 * 1000.0 + t.subsec_nanos() as f64 / 1e6
+            }).collect::<Vec<_>>();
+            let min = millis.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = millis.iter().cloned().fold(0.0, f64::max);
+            let mean = millis.iter().sum::<f64>() / millis.len() as f64;
+            println!("packages: {}", bench.packages);
+            println!("candidates: {}", bench.candidates);
+            println!("iterations: {}", millis.len());
+            println!("min: {:.3}ms", min);
+            println!("mean: {:.3}ms", mean);
+            println!("max: {:.3}ms", max);
+        }
+        _ => {
+            return Err(CliError::new(human("exactly one of --record and \
+                                            --bench must be given"), 101))
+        }
+    }
+    Ok(None)
+}
@@ -1,7 +1,6 @@
 use cargo::core::Workspace;
-use cargo::ops;
+use cargo::ops::{self, MessageFormat};
 use cargo::util::{CliResult, CliError, Config, Human};
-use cargo::util::important_paths::{find_root_manifest_for_wd};
 
 #[derive(RustcDecodable)]
 pub struct Options {
@@ -16,9 +15,15 @@ pub struct Options {
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
+    flag_message_format: MessageFormat,
     flag_release: bool,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_vendored: Option<String>,
+    flag_dep_archive: Option<String>,
+    flag_env_file: Option<String>,
     arg_args: Vec<String>,
 }
 
@@ -42,8 +47,14 @@ Options:
     -v, --verbose ...       Use verbose output
     -q, --quiet             No output printed to stdout
     --color WHEN            Coloring: auto, always, never
+    --message-format FMT    Error format: human, json [default: human]
     --frozen                Require Cargo.lock and cache are up to date
     --locked                Require Cargo.lock is up to date
+    --offline               Only access the network as allowed by --allow-net
+    --allow-net SPEC ...    Endpoint to allow while offline, e.g. registry:NAME
+    --vendored DIR          Replace registries with the vendor directory DIR
+    --dep-archive FILE      Use the dependencies archived in FILE
+    --env-file FILE         Set the environment variables in FILE when running
 
 If neither `--bin` nor `--example` are given, then if the project only has one
 bin target it will be run. Otherwise `--bin` specifies the bin target to run,
@@ -53,6 +64,19 @@ and `--example` specifies the example target to run. At most one of `--bin` or
 All of the trailing arguments are passed to the binary to run. If you're passing
 arguments to both Cargo and the binary, the ones after `--` go to the binary,
 the ones before go to Cargo.
+
+A `.rs` file given as the first argument, or as `--manifest-path`, is run as a
+package of its own, whose manifest is in a ```cargo block of the doc comment at
+the top of the file:
+
+    //! ```cargo
+    //! [dependencies]
+    //! time = \"0.1\"
+    //! ```
+
+Its lockfile and build artifacts are kept under `$CARGO_HOME/scripts`, and
+relative `path` dependencies are relative to the directory of the file. With
+`--bin` or `--example`, the file is passed to the binary instead.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -61,8 +85,30 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    try!(config.configure_vendored(options.flag_vendored.as_ref()
+                                          .map(|s| &s[..])));
+    try!(config.configure_dep_archive(options.flag_dep_archive.as_ref()
+                                             .map(|s| &s[..])));
+    try!(config.configure_env_file(options.flag_env_file.as_ref()
+                                          .map(|s| &s[..])));
 
-    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    // `cargo run foo.rs` runs the single-file package `foo.rs`, unless a
+    // target to run is named, in which case it's an argument of the binary.
+    let mut args = options.arg_args;
+    let script = options.flag_manifest_path.is_none() &&
+                 options.flag_bin.is_none() &&
+                 options.flag_example.is_none() &&
+                 args.first().map(|first| {
+        ops::is_script(first) && config.cwd().join(first).is_file()
+    }).unwrap_or(false);
+    let manifest_path = if script {
+        Some(args.remove(0))
+    } else {
+        options.flag_manifest_path
+    };
+    let root = try!(ops::find_script_or_root_manifest(manifest_path, config));
 
     let (mut examples, mut bins) = (Vec::new(), Vec::new());
     if let Some(s) = options.flag_bin {
@@ -93,10 +139,16 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
         },
         target_rustdoc_args: None,
         target_rustc_args: None,
+        emit_ninja: None,
+        message_format: options.flag_message_format,
+        self_profile: None,
+        preserve_old_artifacts: false,
+        sbom: None,
+        shard: None,
     };
 
     let ws = try!(Workspace::new(&root, config));
-    match try!(ops::run(&ws, &compile_opts, &options.arg_args)) {
+    match try!(ops::run(&ws, &compile_opts, &args)) {
         None => Ok(None),
         Some(err) => {
             // If we never actually spawned the process then that sounds pretty
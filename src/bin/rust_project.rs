@@ -0,0 +1,96 @@
+use cargo::core::Workspace;
+use cargo::ops::{self, CompileOptions, MessageFormat, RustProject};
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_jobs: Option<u32>,
+    flag_features: Vec<String>,
+    flag_all_features: bool,
+    flag_no_default_features: bool,
+    flag_target: Option<String>,
+    flag_release: bool,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Build a workspace and describe it in rust-analyzer's rust-project.json format
+
+Usage:
+    cargo rust-project [options]
+
+Options:
+    -h, --help                   Print this message
+    -j N, --jobs N               Number of parallel jobs, defaults to # of CPUs
+    --features FEATURES          Space-separated list of features to also build
+    --all-features               Build all available features
+    --no-default-features        Do not build the `default` feature
+    --target TRIPLE              Build for the target triple
+    --release                    Build artifacts in release mode, with optimizations
+    --manifest-path PATH         Path to the manifest of the workspace
+    -v, --verbose ...            Use verbose output
+    -q, --quiet                  No output printed to stdout
+    --color WHEN                 Coloring: auto, always, never
+    --frozen                     Require Cargo.lock and cache are up to date
+    --locked                     Require Cargo.lock is up to date
+    --offline                    Only access the network as allowed by --allow-net
+    --allow-net SPEC ...         Endpoint to allow while offline, e.g. registry:NAME
+
+All members of the workspace are built, after which a description of the
+crate graph is printed to stdout. It lists the library of every package and
+the binaries of the members, each with the `cfg` values and features it is
+compiled with, its dependencies, and the environment it is compiled in,
+including the `OUT_DIR` populated by its build script. Libraries of
+procedural macros point at their compiled dynamic library.
+
+This lets rust-analyzer work with the workspace without running Cargo itself,
+for example by writing the output to `rust-project.json` at the root of the
+workspace.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<RustProject>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+    let members = ws.members().map(|pkg| {
+        format!("{}:{}", pkg.name(), pkg.version())
+    }).collect::<Vec<_>>();
+
+    let opts = CompileOptions {
+        config: config,
+        jobs: options.flag_jobs,
+        target: options.flag_target.as_ref().map(|t| &t[..]),
+        features: &options.flag_features,
+        all_features: options.flag_all_features,
+        no_default_features: options.flag_no_default_features,
+        spec: &members,
+        exec_engine: None,
+        mode: ops::CompileMode::Build,
+        release: options.flag_release,
+        filter: ops::CompileFilter::Everything,
+        target_rustdoc_args: None,
+        target_rustc_args: None,
+        emit_ninja: None,
+        message_format: MessageFormat::Human,
+        self_profile: None,
+        preserve_old_artifacts: false,
+        sbom: None,
+        shard: None,
+    };
+    let project = try!(ops::rust_project(&ws, &opts));
+    Ok(Some(project))
+}
@@ -12,6 +12,8 @@ pub struct Options {
     flag_limit: Option<u32>,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
     arg_query: Vec<String>,
 }
 
@@ -31,6 +33,8 @@ Options:
     --limit LIMIT            Limit the number of results (default: 10, max: 100)
     --frozen                 Require Cargo.lock and cache are up to date
     --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -39,6 +43,8 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
     let Options {
         flag_host: host,
         flag_limit: limit,
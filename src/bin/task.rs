@@ -0,0 +1,92 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliResult, CliError, Config, Human, human};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    arg_name: Option<String>,
+    arg_args: Vec<String>,
+    flag_list: bool,
+    flag_release: bool,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+}
+
+pub const USAGE: &'static str = "
+Run a task declared in the workspace's manifest
+
+Usage:
+    cargo task [options] <name> [--] [<args>...]
+    cargo task [options] --list
+
+Options:
+    -h, --help              Print this message
+    -l, --list              List the tasks of the workspace
+    --release               Build the binaries tasks need in release mode
+    --manifest-path PATH    Path to the manifest of the workspace
+    -v, --verbose ...       Use verbose output
+    -q, --quiet             No output printed to stdout
+    --color WHEN            Coloring: auto, always, never
+    --frozen                Require Cargo.lock and cache are up to date
+    --locked                Require Cargo.lock is up to date
+
+Tasks are declared in the `[workspace.tasks]` table of the root manifest of the
+workspace, either as a command or as a table:
+
+    [workspace.tasks]
+    fmt = \"rustfmt --write-mode=overwrite src/lib.rs\"
+    codegen = { command = [\"gen\", \"src/generated.rs\"], build = [\"gen\"] }
+    ci = { command = [\"cargo\", \"test\"], depends-on = [\"fmt\", \"codegen\"] }
+
+The tasks listed in `depends-on` are run first, each at most once. The binaries
+of the members listed in `build` are built before the task runs, and can be
+run by name from its command. A task may also set `env`, a table of additional
+environment variables, and `cwd`, the directory it's run in relative to the
+root of the workspace, which it's otherwise run in.
+
+Tasks are run with `CARGO` set to the path of cargo itself, along with
+`CARGO_WORKSPACE_DIR` and `CARGO_TARGET_DIR`. The trailing arguments are passed
+to the command of the task named.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+
+    let name = match options.arg_name {
+        Some(name) => name,
+        None => {
+            for (name, task) in try!(ops::tasks(&ws)) {
+                println!("{}: {}", name, task.command.join(" "));
+            }
+            return Ok(None)
+        }
+    };
+
+    match try!(ops::run_task(&ws, &name, &options.arg_args, options.flag_release)) {
+        None => Ok(None),
+        Some(err) => {
+            let exit = match err.exit.clone() {
+                Some(exit) => exit,
+                None => return Err(CliError::new(Box::new(Human(err)), 101)),
+            };
+            let exit_code = exit.code().unwrap_or(101);
+            Err(if options.flag_quiet == Some(true) {
+                CliError::code(exit_code)
+            } else {
+                CliError::new(human(format!("task `{}` failed: {}", name, err)),
+                              exit_code)
+            })
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use cargo::core::Workspace;
-use cargo::ops;
+use cargo::ops::{self, MessageFormat};
 use cargo::util::{CliResult, CliError, Human, human, Config};
 use cargo::util::important_paths::{find_root_manifest_for_wd};
 
@@ -13,6 +13,9 @@ pub struct Options {
     flag_no_default_features: bool,
     flag_no_run: bool,
     flag_package: Vec<String>,
+    flag_changed_since: Option<String>,
+    flag_exclude: Vec<String>,
+    flag_select: Vec<String>,
     flag_target: Option<String>,
     flag_lib: bool,
     flag_doc: bool,
@@ -23,10 +26,18 @@ pub struct Options {
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
+    flag_message_format: MessageFormat,
     flag_release: bool,
     flag_no_fail_fast: bool,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_vendored: Option<String>,
+    flag_dep_archive: Option<String>,
+    flag_env_file: Option<String>,
+    flag_shard: Option<String>,
+    flag_shard_tests: bool,
 }
 
 pub const USAGE: &'static str = "
@@ -45,6 +56,9 @@ Options:
     --bench NAME                 Test only the specified benchmark target
     --no-run                     Compile, but don't run tests
     -p SPEC, --package SPEC ...  Package to run tests for
+    --changed-since REV          Test only packages changed since REV
+    --exclude SPEC ...           Exclude packages from the test
+    --select EXPR ...            Test only members whose manifest matches EXPR
     -j N, --jobs N               Number of parallel jobs, defaults to # of CPUs
     --release                    Build artifacts in release mode, with optimizations
     --features FEATURES          Space-separated list of features to also build
@@ -55,9 +69,17 @@ Options:
     -v, --verbose ...            Use verbose output
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
+    --message-format FMT         Error format: human, json [default: human]
     --no-fail-fast               Run all tests regardless of failure
     --frozen                     Require Cargo.lock and cache are up to date
     --locked                     Require Cargo.lock is up to date
+    --offline                    Only access the network as allowed by --allow-net
+    --allow-net SPEC ...         Endpoint to allow while offline, e.g. registry:NAME
+    --vendored DIR               Replace registries with the vendor directory DIR
+    --dep-archive FILE           Use the dependencies archived in FILE
+    --env-file FILE              Set the environment variables in FILE when running
+    --shard K/N                  Build and run only the K-th of N parts of the tests
+    --shard-tests                Split individual tests rather than test targets
 
 All of the trailing arguments are passed to the test binaries generated for
 filtering tests and generally providing options configuring how they run. For
@@ -70,11 +92,29 @@ which indicates which package should be tested. If it is not given, then the
 current package is tested. For more information on SPEC and its format, see the
 `cargo help pkgid` command.
 
+With --changed-since only the workspace members with files which differ from
+the git revision REV, committed or not, are tested, along with the members which
+depend on them. Changes to Cargo.lock affect every member.
+
+SPEC may also be a glob pattern such as `net-*`, matched against the names of
+the workspace members, and a SPEC prefixed with `!` excludes the members it
+matches, as does --exclude. With --select only the members whose manifest
+passes the test EXPR are tested, e.g. `package.metadata.ci.tier == 1`; a key
+without a comparison must be present and not false.
+
+When run from the root of a workspace without --package, the members listed
+in the `test` key of the `workspace.default-members` table of its manifest are
+tested, if there are any.
+
 The --jobs argument affects the building of the test executable but does
 not affect how many jobs are used when running the tests.
 
 Compilation can be configured via the `test` profile in the manifest.
 
+The variables of the --env-file file, or of the `run.env-file` configuration
+key, are set for the test executables, but not when compiling them, nor for
+doc tests which rustdoc compiles.
+
 By default the rust test harness hides output from test execution to
 keep results readable. Test output can be recovered (e.g. for debugging)
 by passing `--nocapture` to the test binaries:
@@ -84,6 +124,25 @@ by passing `--nocapture` to the test binaries:
 To get the list of all options available for the test binaries use this:
 
   cargo test -- --help
+
+The names of test executables change as they are rebuilt. Each executable of
+a local package is also linked to
+`target/debug/test-bins/<package>/<kind>/<target>`, where `<kind>` is one of
+`lib`, `bin`, `test`, `example` or `bench`, which keeps its name, for use with
+debuggers and other tools.
+
+With `--message-format json`, each doctest is reported in a `doctest` message
+with its result, how long it ran and the diagnostics of compiling it, and the
+doctests of each target in a `doctest-suite` message. This needs a nightly
+toolchain, for libtest's JSON output; other toolchains run doctests as usual.
+
+With --shard K/N, the test targets are split in N parts, the same way on every
+machine, and only the K-th part, numbered from 1, is built and run, so that N
+machines running each part run every test once. The doctests of a library go
+along with the library. With --shard-tests as well, every test target is built
+and the tests listed by all of them are split instead, which balances the parts
+better when some targets have many more tests than others. Test targets with
+`harness = false` can't list their tests, and are still run whole by one part.
 ";
 
 pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
@@ -92,7 +151,37 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    try!(config.configure_vendored(options.flag_vendored.as_ref()
+                                          .map(|s| &s[..])));
+    try!(config.configure_dep_archive(options.flag_dep_archive.as_ref()
+                                             .map(|s| &s[..])));
+    try!(config.configure_env_file(options.flag_env_file.as_ref()
+                                          .map(|s| &s[..])));
     let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let spec = match options.flag_changed_since {
+        Some(ref rev) => {
+            let changed = try!(ops::changed_specs(&root, config, rev,
+                                                  &options.flag_package));
+            if changed.is_empty() {
+                return Ok(None)
+            }
+            changed
+        }
+        None => options.flag_package.clone(),
+    };
+    let spec = try!(ops::select_specs(&root, config, "test", &spec,
+                                      &options.flag_exclude,
+                                      &options.flag_select));
+
+    let shard = match options.flag_shard {
+        Some(ref shard) => Some(try!(ops::TestShard::parse(shard))),
+        None if options.flag_shard_tests => {
+            return Err(CliError::new(human("--shard-tests requires --shard"), 101))
+        }
+        None => None,
+    };
 
     let empty = Vec::new();
     let (mode, filter);
@@ -112,6 +201,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
         no_run: options.flag_no_run,
         no_fail_fast: options.flag_no_fail_fast,
         only_doc: options.flag_doc,
+        test_shard: if options.flag_shard_tests {shard} else {None},
         compile_opts: ops::CompileOptions {
             config: config,
             jobs: options.flag_jobs,
@@ -119,17 +209,23 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
             features: &options.flag_features,
             all_features: options.flag_all_features,
             no_default_features: options.flag_no_default_features,
-            spec: &options.flag_package,
+            spec: &spec,
             exec_engine: None,
             release: options.flag_release,
             mode: mode,
             filter: filter,
             target_rustdoc_args: None,
             target_rustc_args: None,
+            emit_ninja: None,
+            message_format: options.flag_message_format,
+            self_profile: None,
+            preserve_old_artifacts: false,
+            sbom: None,
+            shard: if options.flag_shard_tests {None} else {shard},
         },
     };
 
-    let ws = try!(Workspace::new(&root, config));
+    let ws = try!(Workspace::new_for_specs(&root, config, &spec));
     let err = try!(ops::run_tests(&ws, &ops, &options.arg_args));
     match err {
         None => Ok(None),
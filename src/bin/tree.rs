@@ -0,0 +1,84 @@
+use cargo::core::Workspace;
+use cargo::ops::{self, TreeOptions};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+use cargo::util::{CliResult, Config};
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_features: Vec<String>,
+    flag_all_features: bool,
+    flag_no_default_features: bool,
+    flag_target: Option<String>,
+    flag_why_feature: Option<String>,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+    flag_vendored: Option<String>,
+    flag_dep_archive: Option<String>,
+}
+
+pub const USAGE: &'static str = "
+Display the dependency graph of the workspace as a tree
+
+Usage:
+    cargo tree [options]
+
+Options:
+    -h, --help                 Print this message
+    --why-feature PKG/FEATURE  Explain why FEATURE of PKG is enabled
+    --features FEATURES        Space-separated list of features to activate
+    --all-features             Activate all available features
+    --no-default-features      Do not activate the `default` feature
+    --target TRIPLE            Show the graph as built for the target triple
+    --manifest-path PATH       Path to the manifest of the workspace
+    -v, --verbose ...          Use verbose output
+    -q, --quiet                No output printed to stdout
+    --color WHEN               Coloring: auto, always, never
+    --frozen                   Require Cargo.lock and cache are up to date
+    --locked                   Require Cargo.lock is up to date
+    --offline                  Only access the network as allowed by --allow-net
+    --allow-net SPEC ...       Endpoint to allow while offline, e.g. registry:NAME
+    --vendored DIR             Replace registries with the vendor directory DIR
+    --dep-archive FILE         Use the dependencies archived in FILE
+
+The graph holds the packages built for the host, or for TRIPLE with --target,
+with the features enabled as they are when building. Packages appearing more
+than once are only expanded the first time, and marked with `(*)` after that.
+
+With --why-feature the chain of causes of the feature is shown instead: the
+features listing it, the dependency declarations requesting it or the default
+features of the package, and the packages depending on those, down to the
+members of the workspace. For example `cargo tree --why-feature serde/std`.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    try!(config.configure_vendored(options.flag_vendored.as_ref()
+                                          .map(|s| &s[..])));
+    try!(config.configure_dep_archive(options.flag_dep_archive.as_ref()
+                                             .map(|s| &s[..])));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path,
+                                              config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+    let tree = try!(ops::tree(&ws, &TreeOptions {
+        config: config,
+        features: &options.flag_features,
+        all_features: options.flag_all_features,
+        no_default_features: options.flag_no_default_features,
+        target: options.flag_target.as_ref().map(|t| &t[..]),
+        why_feature: options.flag_why_feature.as_ref().map(|s| &s[..]),
+    }));
+    print!("{}", tree);
+    Ok(None)
+}
@@ -1,8 +1,9 @@
 use std::env;
+use std::path::Path;
 
 use cargo::core::Workspace;
-use cargo::ops;
-use cargo::util::{CliResult, Config};
+use cargo::ops::{self, MessageFormat};
+use cargo::util::{error_json, CliResult, Config};
 use cargo::util::important_paths::find_root_manifest_for_wd;
 
 #[derive(RustcDecodable)]
@@ -10,12 +11,21 @@ pub struct Options {
     flag_package: Vec<String>,
     flag_aggressive: bool,
     flag_precise: Option<String>,
+    flag_allow_yanked: bool,
+    flag_from_advisories: Option<String>,
+    flag_minimal_direct: bool,
+    flag_breaking: bool,
+    flag_dry_run: bool,
+    flag_diff: bool,
     flag_manifest_path: Option<String>,
+    flag_format: MessageFormat,
     flag_verbose: u32,
     flag_quiet: Option<bool>,
     flag_color: Option<String>,
     flag_frozen: bool,
     flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
 }
 
 pub const USAGE: &'static str = "
@@ -29,12 +39,24 @@ Options:
     -p SPEC, --package SPEC ...  Package to update
     --aggressive                 Force updating all dependencies of <name> as well
     --precise PRECISE            Update a single dependency to exactly PRECISE
+    --allow-yanked               Allow PRECISE to be a yanked version
+    --from-advisories FILE       Update just enough to avoid the versions
+                                 listed in the advisories in FILE
+    --minimal-direct             Update direct dependencies to the oldest
+                                 versions their requirements allow
+    --breaking                   Raise requirements in the workspace to allow
+                                 semver-incompatible versions
+    --dry-run                    Don't write the updated lock file
+    --diff                       Print the changes to the lock file to stdout
     --manifest-path PATH         Path to the crate's manifest
+    --format FMT                 Format of errors and --diff: human, json [default: human]
     -v, --verbose ...            Use verbose output
     -q, --quiet                  No output printed to stdout
     --color WHEN                 Coloring: auto, always, never
     --frozen                     Require Cargo.lock and cache are up to date
     --locked                     Require Cargo.lock is up to date
+    --offline                    Only access the network as allowed by --allow-net
+    --allow-net SPEC ...         Endpoint to allow while offline, e.g. registry:NAME
 
 This command requires that a `Cargo.lock` already exists as generated by
 `cargo build` or related commands.
@@ -51,9 +73,53 @@ being updated should be updated to. For example, if the package comes from a git
 repository, then PRECISE would be the exact revision that the repository should
 be updated to.
 
+A yanked version of a package from a registry is only locked with --precise if
+--allow-yanked is also passed, for example to reproduce an old build. The
+lockfile then records that the version was yanked, and every command reading
+it warns about it until the package is updated to another version.
+
 If SPEC is not given, then all dependencies will be re-resolved and
 updated.
 
+If FILE is given, then the lockfile is updated as little as possible such that
+no locked package is affected by the advisories it lists. Each advisory names a
+package and the ranges of its versions which are affected:
+
+    [[advisory]]
+    id = \"EXAMPLE-2016-0001\"
+    package = \"foo\"
+    versions = [\"< 1.2.3\"]
+
+If no update compatible with the requirements in the workspace avoids every
+advisory, then nothing is changed and the affected packages are reported.
+
+With --minimal-direct, the dependencies of the workspace members which are
+updated are locked to the oldest versions their requirements allow, rather than
+the newest, while their own dependencies are updated as usual. Building with
+the resulting lockfile checks that the lower bounds of the requirements in the
+workspace are accurate. Versions are only raised above the oldest as far as
+other requirements on the same packages demand.
+
+With --breaking, the requirements of the workspace members on the registry
+dependencies named by SPEC, or on all of them if no SPEC is given, are raised
+to allow the newest release of each dependency when they don't already, and
+those dependencies are then updated. A requirement is edited where it's
+declared, which is `[workspace.dependencies]` of the workspace root for one
+inherited with `workspace = true`. Requirements other than a plain version,
+optionally prefixed with `^` or `~`, are left alone.
+
+With --dry-run, the lockfile is left as it is, while the changes to it are still
+reported. With --diff, every package which would change is printed to stdout:
+its old and new versions, whether it's added or removed, and the features
+enabled on it which change. With `--format json` this is a JSON object with a
+`changes` array, each entry listing `name`, `source`, `old_version` and
+`new_version` (null for added and removed packages), `features_added` and
+`features_removed`.
+
+With `--format json`, when no version of a dependency can be selected, the
+conflicting requirements are also written to stdout as a JSON object, each with
+the chain of packages it comes from.
+
 For more information about package id specifications, see `cargo help pkgid`.
 ";
 
@@ -64,16 +130,33 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
                           &options.flag_color,
                           options.flag_frozen,
                           options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
     let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
 
     let update_opts = ops::UpdateOptions {
         aggressive: options.flag_aggressive,
         precise: options.flag_precise.as_ref().map(|s| &s[..]),
+        allow_yanked: options.flag_allow_yanked,
+        dry_run: options.flag_dry_run,
+        diff: if options.flag_diff {Some(options.flag_format)} else {None},
         to_update: &options.flag_package,
+        advisories: options.flag_from_advisories.as_ref().map(|s| {
+            Path::new(&s[..])
+        }),
+        minimal_direct: options.flag_minimal_direct,
+        breaking: options.flag_breaking,
         config: config,
     };
 
     let ws = try!(Workspace::new(&root, config));
-    try!(ops::update_lockfile(&ws, &update_opts));
+    if let Err(e) = ops::update_lockfile(&ws, &update_opts) {
+        if options.flag_format == MessageFormat::Json {
+            if let Some(json) = error_json(&*e) {
+                println!("{}", json);
+            }
+        }
+        return Err(e.into())
+    }
     Ok(None)
 }
@@ -0,0 +1,74 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_package: Vec<String>,
+    flag_breaking: bool,
+    flag_dry_run: bool,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Raise the version requirements on dependencies in the manifests of a workspace
+
+Usage:
+    cargo upgrade [options]
+
+Options:
+    -h, --help                   Print this message
+    -p NAME, --package NAME ...  Dependency to upgrade
+    --breaking                   Raise requirements to allow semver-incompatible
+                                 versions
+    --dry-run                    Don't write the upgraded manifests
+    --manifest-path PATH         Path to the crate's manifest
+    -v, --verbose ...            Use verbose output
+    -q, --quiet                  No output printed to stdout
+    --color WHEN                 Coloring: auto, always, never
+    --frozen                     Require Cargo.lock and cache are up to date
+    --locked                     Require Cargo.lock is up to date
+    --offline                    Only access the network as allowed by --allow-net
+    --allow-net SPEC ...         Endpoint to allow while offline, e.g. registry:NAME
+
+The requirements of the workspace members on the registry dependencies named by
+NAME, or on all of them if no NAME is given, are raised to start at the newest
+release they allow, keeping how many parts of the version each gives: `1.2`
+becomes `1.4` when 1.4.1 is released, while `0.3` stays as it is. With
+--breaking, requirements are instead raised to allow the newest release of each
+dependency when they don't already, as with `cargo update --breaking`.
+
+A requirement is edited where it's declared, which is `[workspace.dependencies]`
+of the workspace root for one inherited with `workspace = true`. Requirements
+other than a plain version, optionally prefixed with `^` or `~`, and those on
+dependencies replaced with `[replace]`, are left alone. The lockfile is then
+updated for the upgraded dependencies, unlike `cargo update`, which only ever
+changes the lockfile.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+
+    let ws = try!(Workspace::new(&root, config));
+    try!(ops::upgrade(&ws, &ops::UpgradeOptions {
+        to_upgrade: &options.flag_package,
+        breaking: options.flag_breaking,
+        dry_run: options.flag_dry_run,
+    }));
+    Ok(None)
+}
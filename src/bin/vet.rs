@@ -0,0 +1,61 @@
+use cargo::core::Workspace;
+use cargo::ops;
+use cargo::util::{CliResult, Config};
+use cargo::util::important_paths::find_root_manifest_for_wd;
+
+#[derive(RustcDecodable)]
+pub struct Options {
+    flag_exempt: bool,
+    flag_manifest_path: Option<String>,
+    flag_verbose: u32,
+    flag_quiet: Option<bool>,
+    flag_color: Option<String>,
+    flag_frozen: bool,
+    flag_locked: bool,
+    flag_offline: bool,
+    flag_allow_net: Vec<String>,
+}
+
+pub const USAGE: &'static str = "
+Check the dependencies against the supply-chain policy of the workspace
+
+Usage:
+    cargo vet [options]
+
+Options:
+    -h, --help               Print this message
+    --exempt                 Exempt the packages which aren't covered by the policy
+    --manifest-path PATH     Path to the manifest of the workspace
+    -v, --verbose ...        Use verbose output
+    -q, --quiet              No output printed to stdout
+    --color WHEN             Coloring: auto, always, never
+    --frozen                 Require Cargo.lock and cache are up to date
+    --locked                 Require Cargo.lock is up to date
+    --offline                Only access the network as allowed by --allow-net
+    --allow-net SPEC ...     Endpoint to allow while offline, e.g. registry:NAME
+
+The policy is the `supply-chain.toml` file at the root of the workspace. Every
+dependency which isn't a path dependency has to be covered by it: its version
+is listed in `[[audits.<name>]]` once it has been reviewed, or it was published
+by a user listed in `[[trusted.<name>]]`, or its version is exempted from
+review in `[[exemptions.<name>]]`. Builds and `cargo add` fail on dependencies
+which aren't covered.
+
+With --exempt, the dependencies which aren't covered are exempted, creating the
+policy if there's none yet. Exemptions which aren't needed anymore, because the
+version was audited or isn't used anymore, are reported.
+";
+
+pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
+    try!(config.configure(options.flag_verbose,
+                          options.flag_quiet,
+                          &options.flag_color,
+                          options.flag_frozen,
+                          options.flag_locked));
+    try!(config.configure_network(options.flag_offline,
+                                  &options.flag_allow_net));
+    let root = try!(find_root_manifest_for_wd(options.flag_manifest_path, config.cwd()));
+    let ws = try!(Workspace::new(&root, config));
+    try!(ops::vet(&ws, &ops::VetOptions { exempt: options.flag_exempt }));
+    Ok(None)
+}
@@ -26,6 +26,7 @@ pub struct DependencyInner {
     only_match_name: bool,
 
     optional: bool,
+    public: bool,
     default_features: bool,
     features: Vec<String>,
 
@@ -34,7 +35,7 @@ pub struct DependencyInner {
     platform: Option<Platform>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Platform {
     Name(String),
     Cfg(CfgExpr),
@@ -111,6 +112,7 @@ impl DependencyInner {
             kind: Kind::Normal,
             only_match_name: true,
             optional: false,
+            public: false,
             features: Vec::new(),
             default_features: true,
             specified_req: false,
@@ -153,6 +155,13 @@ impl DependencyInner {
         self
     }
 
+    /// Sets whether the types of the dependency are part of the public API
+    /// of the package depending on it.
+    pub fn set_public(mut self, public: bool) -> DependencyInner {
+        self.public = public;
+        self
+    }
+
     /// Set the source id for this dependency
     pub fn set_source_id(mut self, id: SourceId) -> DependencyInner {
         self.source_id = id;
@@ -190,6 +199,7 @@ impl DependencyInner {
         match self.kind { Kind::Build => true, _ => false }
     }
     pub fn is_optional(&self) -> bool { self.optional }
+    pub fn is_public(&self) -> bool { self.public }
     /// Returns true if the default features of the dependency are requested.
     pub fn uses_default_features(&self) -> bool { self.default_features }
     /// Returns the list of features that are requested by the dependency.
@@ -249,6 +259,7 @@ impl Dependency {
     pub fn is_transitive(&self) -> bool { self.inner.is_transitive() }
     pub fn is_build(&self) -> bool { self.inner.is_build() }
     pub fn is_optional(&self) -> bool { self.inner.is_optional() }
+    pub fn is_public(&self) -> bool { self.inner.is_public() }
 
     /// Returns true if the default features of the dependency are requested.
     pub fn uses_default_features(&self) -> bool {
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fmt;
 use std::path::{PathBuf, Path};
 
@@ -6,6 +7,7 @@ use rustc_serialize::{Encoder, Encodable};
 
 use core::{Dependency, PackageId, Summary, SourceId, PackageIdSpec};
 use core::WorkspaceConfig;
+use core::dependency::Platform;
 use core::package_id::Metadata;
 
 pub enum EitherManifest {
@@ -18,7 +20,7 @@ pub enum EitherManifest {
 pub struct Manifest {
     summary: Summary,
     targets: Vec<Target>,
-    links: Option<String>,
+    links: Vec<String>,
     warnings: Vec<String>,
     exclude: Vec<String>,
     include: Vec<String>,
@@ -27,6 +29,10 @@ pub struct Manifest {
     publish: bool,
     replace: Vec<(PackageIdSpec, Dependency)>,
     workspace: WorkspaceConfig,
+    system_dependencies: Vec<SystemDependency>,
+    feature_docs: BTreeMap<String, String>,
+    feature_metadata: BTreeMap<String, FeatureMetadata>,
+    prebuilt: BTreeMap<String, Prebuilt>,
 }
 
 #[derive(Clone, Debug)]
@@ -35,6 +41,64 @@ pub struct VirtualManifest {
     workspace: WorkspaceConfig,
 }
 
+/// A native library required by a package, declared in the
+/// `[system-dependencies]` table and located with `pkg-config` at build time.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SystemDependency {
+    /// The key of the dependency in the manifest, also used to find
+    /// overrides in `[target.$triple.$name]`.
+    pub name: String,
+    /// The name of the `pkg-config` package to look for.
+    pub pkg_config_name: String,
+    /// The minimum acceptable version of the library, if any.
+    pub version: Option<String>,
+    /// How the library should be linked, if there's a preference.
+    pub link: Option<LinkPreference>,
+}
+
+/// What the `[features.metadata]` table of a manifest says about a feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq, RustcEncodable)]
+pub struct FeatureMetadata {
+    /// A one-line description of the feature.
+    pub description: Option<String>,
+    /// One of `stable`, `unstable` or `deprecated`.
+    pub stability: Option<String>,
+    /// Where the feature is documented at length.
+    pub docs: Option<String>,
+}
+
+/// Binaries built ahead of time for a target, declared in the `[prebuilt]`
+/// table of a manifest, which `cargo install` may use instead of building the
+/// package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Prebuilt {
+    /// Where the `.tar.gz` archive of the binaries is downloaded from.
+    pub url: String,
+    /// The SHA-256 checksum of the archive, in hex.
+    pub sha256: String,
+}
+
+/// A program binaries are run through instead of directly, e.g. an emulator
+/// or a tool flashing them onto a device, given by `target.$triple.runner` in
+/// the configuration or by the `runner` key of a target in the manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Runner {
+    pub program: String,
+    /// Arguments passed before the path of the binary.
+    pub args: Vec<String>,
+    /// Environment variables the runner is run with.
+    pub env: Vec<(String, String)>,
+    /// The directory the runner is run in, relative to the root of the package
+    /// for runners of the manifest.
+    pub cwd: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkPreference {
+    Static,
+    Dynamic,
+}
+
 /// General metadata about a package which is just blindly uploaded to the
 /// registry.
 ///
@@ -54,6 +118,7 @@ pub struct ManifestMetadata {
     pub homepage: Option<String>,       // url
     pub repository: Option<String>,     // url
     pub documentation: Option<String>,  // url
+    pub rust_version: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -139,7 +204,7 @@ pub struct Profile {
     pub panic: Option<String>,
 }
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct Profiles {
     pub release: Profile,
     pub dev: Profile,
@@ -165,6 +230,8 @@ pub struct Target {
     doctest: bool,
     harness: bool, // whether to use the test harness (--test)
     for_host: bool,
+    platform: Option<Platform>,
+    runner: Option<Runner>,
 }
 
 #[derive(RustcEncodable)]
@@ -188,12 +255,13 @@ impl Manifest {
     pub fn new(summary: Summary, targets: Vec<Target>,
                exclude: Vec<String>,
                include: Vec<String>,
-               links: Option<String>,
+               links: Vec<String>,
                metadata: ManifestMetadata,
                profiles: Profiles,
                publish: bool,
                replace: Vec<(PackageIdSpec, Dependency)>,
-               workspace: WorkspaceConfig) -> Manifest {
+               workspace: WorkspaceConfig,
+               system_dependencies: Vec<SystemDependency>) -> Manifest {
         Manifest {
             summary: summary,
             targets: targets,
@@ -206,6 +274,10 @@ impl Manifest {
             publish: publish,
             replace: replace,
             workspace: workspace,
+            system_dependencies: system_dependencies,
+            feature_docs: BTreeMap::new(),
+            feature_metadata: BTreeMap::new(),
+            prebuilt: BTreeMap::new(),
         }
     }
 
@@ -222,14 +294,32 @@ impl Manifest {
     pub fn profiles(&self) -> &Profiles { &self.profiles }
     pub fn publish(&self) -> bool { self.publish }
     pub fn replace(&self) -> &[(PackageIdSpec, Dependency)] { &self.replace }
-    pub fn links(&self) -> Option<&str> {
-        self.links.as_ref().map(|s| &s[..])
+    pub fn links(&self) -> &[String] { &self.links }
+    pub fn system_dependencies(&self) -> &[SystemDependency] {
+        &self.system_dependencies
     }
 
     pub fn workspace_config(&self) -> &WorkspaceConfig {
         &self.workspace
     }
 
+    /// Documentation of the entries of `[features]`, taken from the `##`
+    /// comments preceding them.
+    pub fn feature_docs(&self) -> &BTreeMap<String, String> {
+        &self.feature_docs
+    }
+
+    /// The description, stability and documentation of features declared in
+    /// `[features.metadata]`.
+    pub fn feature_metadata(&self) -> &BTreeMap<String, FeatureMetadata> {
+        &self.feature_metadata
+    }
+
+    /// The prebuilt binaries of the package, by target triple.
+    pub fn prebuilt(&self) -> &BTreeMap<String, Prebuilt> {
+        &self.prebuilt
+    }
+
     pub fn add_warning(&mut self, s: String) {
         self.warnings.push(s)
     }
@@ -238,6 +328,19 @@ impl Manifest {
         self.summary = summary;
     }
 
+    pub fn set_feature_docs(&mut self, docs: BTreeMap<String, String>) {
+        self.feature_docs = docs;
+    }
+
+    pub fn set_feature_metadata(&mut self,
+                                metadata: BTreeMap<String, FeatureMetadata>) {
+        self.feature_metadata = metadata;
+    }
+
+    pub fn set_prebuilt(&mut self, prebuilt: BTreeMap<String, Prebuilt>) {
+        self.prebuilt = prebuilt;
+    }
+
     pub fn map_source(self, to_replace: &SourceId, replace_with: &SourceId)
                       -> Manifest {
         Manifest {
@@ -278,6 +381,8 @@ impl Target {
             for_host: false,
             tested: true,
             benched: true,
+            platform: None,
+            runner: None,
         }
     }
 
@@ -365,7 +470,9 @@ impl Target {
     pub fn harness(&self) -> bool { self.harness }
     pub fn documented(&self) -> bool { self.doc }
     pub fn for_host(&self) -> bool { self.for_host }
+    pub fn platform(&self) -> Option<&Platform> { self.platform.as_ref() }
     pub fn benched(&self) -> bool { self.benched }
+    pub fn runner(&self) -> Option<&Runner> { self.runner.as_ref() }
 
     pub fn doctested(&self) -> bool {
         self.doctest && match self.kind {
@@ -387,6 +494,14 @@ impl Target {
         }
     }
 
+    /// Whether this is a library of procedural macros.
+    pub fn is_rustc_macro(&self) -> bool {
+        match self.kind {
+            TargetKind::Lib(ref kinds) => kinds.contains(&LibKind::RustcMacro),
+            _ => false,
+        }
+    }
+
     pub fn linkable(&self) -> bool {
         match self.kind {
             TargetKind::Lib(ref kinds) => {
@@ -443,6 +558,10 @@ impl Target {
         self.for_host = for_host;
         self
     }
+    pub fn set_platform(&mut self, platform: Option<Platform>) -> &mut Target {
+        self.platform = platform;
+        self
+    }
     pub fn set_harness(&mut self, harness: bool) -> &mut Target {
         self.harness = harness;
         self
@@ -451,6 +570,10 @@ impl Target {
         self.doc = doc;
         self
     }
+    pub fn set_runner(&mut self, runner: Option<Runner>) -> &mut Target {
+        self.runner = runner;
+        self
+    }
 }
 
 impl fmt::Display for Target {
@@ -1,5 +1,5 @@
 use std::cell::{Ref, RefCell};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::hash;
 use std::path::{Path, PathBuf};
@@ -7,7 +7,7 @@ use std::path::{Path, PathBuf};
 use semver::Version;
 
 use core::{Dependency, Manifest, PackageId, SourceId, Target, TargetKind};
-use core::{Summary, Metadata, SourceMap};
+use core::{Summary, Metadata, SourceMap, FeatureMetadata};
 use ops;
 use util::{CargoResult, Config, LazyCell, ChainError, internal, human, lev_distance};
 use rustc_serialize::{Encoder,Encodable};
@@ -33,6 +33,8 @@ struct SerializedPackage<'a> {
     dependencies: &'a [Dependency],
     targets: &'a [Target],
     features: &'a HashMap<String, Vec<String>>,
+    feature_docs: &'a BTreeMap<String, String>,
+    feature_metadata: &'a BTreeMap<String, FeatureMetadata>,
     manifest_path: &'a str,
 }
 
@@ -49,6 +51,8 @@ impl Encodable for Package {
             dependencies: summary.dependencies(),
             targets: &self.manifest.targets(),
             features: summary.features(),
+            feature_docs: self.manifest.feature_docs(),
+            feature_metadata: self.manifest.feature_metadata(),
             manifest_path: &self.manifest_path.display().to_string(),
         }.encode(s)
     }
@@ -87,6 +91,10 @@ impl Package {
         self.targets().iter().any(|t| t.is_custom_build())
     }
 
+    pub fn has_system_dependencies(&self) -> bool {
+        !self.manifest().system_dependencies().is_empty()
+    }
+
     pub fn generate_metadata(&self) -> Metadata {
         self.package_id().generate_metadata()
     }
@@ -168,6 +176,37 @@ impl<'cfg> PackageSet<'cfg> {
         Ok(slot.borrow().unwrap())
     }
 
+    /// Downloads all the packages of the set which aren't yet, with each
+    /// source fetching its packages as efficiently as it can.
+    pub fn prefetch(&self, json_messages: bool) -> CargoResult<()> {
+        let mut by_source = HashMap::new();
+        for &(ref id, ref slot) in self.packages.iter() {
+            if slot.borrow().is_none() {
+                by_source.entry(id.source_id().clone())
+                         .or_insert(Vec::new()).push(id.clone());
+            }
+        }
+        let mut sources = self.sources.borrow_mut();
+        for (source_id, ids) in by_source {
+            let source = try!(sources.get_mut(&source_id).chain_error(|| {
+                internal(format!("couldn't find source for `{}`", ids[0]))
+            }));
+            try!(source.prefetch(&ids, json_messages));
+        }
+        Ok(())
+    }
+
+    /// Adds the packages of `other` which aren't in this set yet, along with
+    /// the sources they come from.
+    pub fn extend(&mut self, other: PackageSet<'cfg>) {
+        for (id, slot) in other.packages {
+            if !self.packages.iter().any(|p| p.0 == id) {
+                self.packages.push((id, slot));
+            }
+        }
+        self.sources.borrow_mut().extend(other.sources.into_inner());
+    }
+
     pub fn sources(&self) -> Ref<SourceMap<'cfg>> {
         self.sources.borrow()
     }
@@ -172,6 +172,15 @@ impl<'cfg> PackageRegistry<'cfg> {
         sub_vec.push((id, deps));
     }
 
+    /// Returns whether `pkg` has been yanked from its source, which must have
+    /// already been loaded.
+    pub fn is_yanked(&mut self, pkg: &PackageId) -> CargoResult<bool> {
+        match self.sources.get_mut(pkg.source_id()) {
+            Some(src) => src.is_yanked(pkg),
+            None => Ok(false),
+        }
+    }
+
     fn load(&mut self, source_id: &SourceId, kind: Kind) -> CargoResult<()> {
         (|| {
             let source = try!(self.source_config.load(source_id));
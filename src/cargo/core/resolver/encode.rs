@@ -349,7 +349,7 @@ fn encodable_resolve_node(id: &PackageId, resolve: &Resolve)
     }
 }
 
-fn encodable_package_id(id: &PackageId) -> EncodablePackageId {
+pub fn encodable_package_id(id: &PackageId) -> EncodablePackageId {
     let source = if id.source_id().is_path() {
         None
     } else {
@@ -27,7 +27,10 @@
 //! * Always try to activate the highest version crate first. The default
 //!   dependency in Cargo (e.g. when you write `foo = "0.1.2"`) is
 //!   semver-compatible, so selecting the highest version possible will allow us
-//!   to hopefully satisfy as many dependencies at once.
+//!   to hopefully satisfy as many dependencies at once. With
+//!   `VersionOrder::MinimalDirect` the lowest versions are tried first for the
+//!   dependencies of the packages being resolved, so their lower bounds can
+//!   be checked.
 //!
 //! Beyond that, what's implemented below is just a naive backtracking version
 //! which should in theory try all possible combinations of dependencies and
@@ -46,11 +49,13 @@
 //! over the place.
 
 use std::cmp::Ordering;
-use std::collections::{HashSet, HashMap, BinaryHeap, BTreeMap};
+use std::error::Error;
+use std::collections::{HashSet, HashMap, BinaryHeap, BTreeMap, VecDeque};
 use std::fmt;
 use std::ops::Range;
 use std::rc::Rc;
 
+use rustc_serialize::json;
 use semver;
 
 use core::{PackageId, Registry, SourceId, Summary, Dependency};
@@ -98,6 +103,16 @@ pub enum Method<'a> {
     },
 }
 
+/// Which versions of each dependency the resolver tries first.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VersionOrder {
+    /// The newest versions, for every dependency.
+    Newest,
+    /// The oldest versions for the direct dependencies of the packages being
+    /// resolved, and the newest for everything they depend on in turn.
+    MinimalDirect,
+}
+
 // Err(..) == standard transient error (e.g. I/O error)
 // Ok(Err(..)) == resolve error, but is human readable
 // Ok(Ok(..)) == success in resolving
@@ -108,12 +123,64 @@ type ResolveResult<'a> = CargoResult<CargoResult<Box<Context<'a>>>>;
 // (dependency info, candidates, features activated)
 type DepInfo = (Dependency, Vec<Candidate>, Vec<String>);
 
+/// The result of a resolution in a form which can be stored, used to reuse
+/// resolutions across invocations of Cargo.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct StoredResolve {
+    nodes: Vec<(PackageId, Vec<PackageId>)>,
+    replacements: Vec<(PackageId, PackageId)>,
+    features: Vec<(PackageId, Vec<String>)>,
+    checksums: Vec<(PackageId, Option<String>)>,
+}
+
+impl Resolve {
+    pub fn to_stored(&self) -> StoredResolve {
+        StoredResolve {
+            nodes: self.graph.iter().map(|id| {
+                (id.clone(), self.graph.edges(id).unwrap().cloned().collect())
+            }).collect(),
+            replacements: self.replacements.iter().map(|(a, b)| {
+                (a.clone(), b.clone())
+            }).collect(),
+            features: self.features.iter().map(|(id, features)| {
+                (id.clone(), features.iter().cloned().collect())
+            }).collect(),
+            checksums: self.checksums.iter().map(|(id, cksum)| {
+                (id.clone(), cksum.clone())
+            }).collect(),
+        }
+    }
+
+    pub fn from_stored(stored: StoredResolve) -> Resolve {
+        let mut graph = Graph::new();
+        for (id, deps) in stored.nodes {
+            graph.add(id, &deps);
+        }
+        Resolve {
+            graph: graph,
+            replacements: stored.replacements.into_iter().collect(),
+            features: stored.features.into_iter().map(|(id, features)| {
+                (id, features.into_iter().collect())
+            }).collect(),
+            checksums: stored.checksums.into_iter().collect(),
+            metadata: BTreeMap::new(),
+        }
+    }
+}
+
 #[derive(Clone)]
 struct Candidate {
     summary: Rc<Summary>,
     replace: Option<Rc<Summary>>,
 }
 
+const YANKED_PREFIX: &'static str = "yanked ";
+
+fn yanked_key(pkg: &PackageId) -> String {
+    let id = encode::encodable_package_id(pkg);
+    format!("{}{}", YANKED_PREFIX, id.to_string())
+}
+
 impl Resolve {
     pub fn merge_from(&mut self, previous: &Resolve) -> CargoResult<()> {
         // Given a previous instance of resolve, it should be forbidden to ever
@@ -134,12 +201,27 @@ impl Resolve {
                     continue
                 }
 
+                // Package ids don't tell revisions of a git repository apart,
+                // and another revision is expected to have other contents.
+                if id.source_id().is_git() && !self.graph.iter().any(|other| {
+                    other == id &&
+                        other.source_id().precise() == id.source_id().precise()
+                }) {
+                    continue
+                }
+
+                // Lock files written before checksums were calculated for git
+                // dependencies list none for them, so those just pick up the
+                // checksum of the current checkout.
+                if cksum.is_none() && id.source_id().is_git() {
+                    continue
+
                 // If the previous checksum wasn't calculated, the current
                 // checksum is `Some`. This may indicate that a source was
                 // erroneously replaced or was replaced with something that
                 // desires stronger checksum guarantees than can be afforded
                 // elsewhere.
-                if cksum.is_none() {
+                } else if cksum.is_none() {
                     bail!("\
 checksum for `{}` was not previously calculated, but a checksum could now \
 be calculated
@@ -171,6 +253,22 @@ this could be indicative of a few possible situations:
 unable to verify that `{0}` is the same as when the lockfile was generated
 ", id, id.source_id())
 
+                // For a git dependency the revision is the same, but the files
+                // checked out for it aren't.
+                } else if id.source_id().is_git() {
+                    bail!("\
+checksum for `{}` changed between lock files
+
+the files checked out at the revision listed in the lock file differ from when
+the lock file was generated, which could be indicative of a few possible
+situations:
+
+    * the checkout of the repository in the Cargo home directory was modified
+    * the lock file is corrupt
+
+unable to verify that `{0}` is the same as when the lockfile was generated
+", id);
+
                 // If the checksums aren't equal, and neither is None, then they
                 // must both be Some, in which case the checksum now differs.
                 // That's quite bad!
@@ -190,11 +288,121 @@ unable to verify that `{0}` is the same as when the lockfile was generated
             }
         }
 
-        // Be sure to just copy over any unknown metadata.
+        // Be sure to just copy over any unknown metadata, except for the
+        // yanked versions which are no longer locked.
         self.metadata = previous.metadata.clone();
+        let stale = self.metadata.keys().filter(|k| {
+            k.starts_with(YANKED_PREFIX) &&
+                !self.graph.iter().any(|id| **k == yanked_key(id))
+        }).cloned().collect::<Vec<_>>();
+        for key in stale {
+            self.metadata.remove(&key);
+        }
         Ok(())
     }
 
+    /// Records in the lockfile that `pkg` was locked even though it's been
+    /// yanked, with `cargo update --precise --allow-yanked`.
+    pub fn pin_yanked(&mut self, pkg: &PackageId) {
+        self.metadata.insert(yanked_key(pkg),
+                             "yanked, pinned with --allow-yanked".to_string());
+    }
+
+    /// Returns the packages recorded as locked even though they've been
+    /// yanked.
+    pub fn yanked_pins(&self) -> Vec<&PackageId> {
+        let mut pins = self.graph.iter().filter(|id| {
+            self.metadata.contains_key(&yanked_key(id))
+        }).collect::<Vec<_>>();
+        pins.sort();
+        pins
+    }
+
+    /// Joins the packages `kept` from this resolve with all of `other`, for
+    /// updating a lockfile without resolving everything again.
+    ///
+    /// The packages in `roots` depend on what's listed for them there along
+    /// with what they depend on in `other`, while the others kept depend on
+    /// whatever they did before, as long as that's kept too. Checksums are
+    /// taken from whichever resolve the package comes from, and those listed
+    /// in `checksums` take precedence.
+    pub fn splice(&self,
+                  kept: &HashSet<PackageId>,
+                  roots: &HashMap<PackageId, Vec<PackageId>>,
+                  other: Option<&Resolve>,
+                  checksums: HashMap<PackageId, Option<String>>) -> Resolve {
+        let mut resolve = Resolve {
+            graph: Graph::new(),
+            replacements: HashMap::new(),
+            features: HashMap::new(),
+            checksums: HashMap::new(),
+            metadata: self.metadata.clone(),
+        };
+        for id in kept.iter() {
+            let deps = match roots.get(id) {
+                Some(deps) => deps.clone(),
+                None => self.deps_not_replaced(id).filter(|d| {
+                    kept.contains(*d)
+                }).cloned().collect(),
+            };
+            resolve.graph.add(id.clone(), &deps);
+            if let Some(cksum) = self.checksums.get(id) {
+                resolve.checksums.insert(id.clone(), cksum.clone());
+            }
+        }
+        if let Some(other) = other {
+            for id in other.iter() {
+                let deps = other.deps_not_replaced(id).cloned().collect::<Vec<_>>();
+                if roots.contains_key(id) {
+                    for dep in deps {
+                        resolve.graph.link(id.clone(), dep);
+                    }
+                } else {
+                    resolve.graph.add(id.clone(), &deps);
+                }
+                if let Some(features) = other.features.get(id) {
+                    resolve.features.insert(id.clone(), features.clone());
+                }
+                if !resolve.checksums.contains_key(id) {
+                    if let Some(cksum) = other.checksums.get(id) {
+                        resolve.checksums.insert(id.clone(), cksum.clone());
+                    }
+                }
+            }
+        }
+        resolve.checksums.extend(checksums);
+        resolve
+    }
+
+    /// Whether each package resolved both here and in `other` has the same
+    /// dependencies, features and replacement in both, so that the two can be
+    /// combined with `extend` and built together.
+    pub fn agrees_with(&self, other: &Resolve) -> bool {
+        let theirs = other.graph.get_nodes();
+        self.graph.get_nodes().iter().all(|(id, deps)| {
+            match theirs.get(id) {
+                Some(their_deps) => {
+                    deps == their_deps &&
+                        self.features.get(id) == other.features.get(id) &&
+                        self.replacements.get(id) == other.replacements.get(id)
+                }
+                None => true,
+            }
+        })
+    }
+
+    /// Adds the packages resolved in `other`, which has to agree with this
+    /// resolve as `agrees_with` checks.
+    pub fn extend(&mut self, other: Resolve) {
+        for (id, deps) in other.graph.get_nodes() {
+            let deps = deps.iter().cloned().collect::<Vec<_>>();
+            self.graph.add(id.clone(), &deps);
+        }
+        self.replacements.extend(other.replacements);
+        self.features.extend(other.features);
+        self.checksums.extend(other.checksums);
+    }
+
     pub fn iter(&self) -> Nodes<PackageId> {
         self.graph.iter()
     }
@@ -219,6 +427,10 @@ unable to verify that `{0}` is the same as when the lockfile was generated
         self.features.get(pkg)
     }
 
+    pub fn checksums(&self) -> &HashMap<PackageId, Option<String>> {
+        &self.checksums
+    }
+
     pub fn query(&self, spec: &str) -> CargoResult<&PackageId> {
         PackageIdSpec::query_str(spec, self.iter())
     }
@@ -260,18 +472,28 @@ struct Context<'a> {
     resolve_features: HashMap<PackageId, HashSet<String>>,
     resolve_replacements: HashMap<PackageId, PackageId>,
     replacements: &'a [(PackageIdSpec, Dependency)],
+    /// The packages whose dependencies are tried oldest version first.
+    oldest_first: &'a HashSet<PackageId>,
 }
 
 /// Builds the list of all packages required to build the first argument.
 pub fn resolve(summaries: &[(Summary, Method)],
                replacements: &[(PackageIdSpec, Dependency)],
+               order: VersionOrder,
                registry: &mut Registry) -> CargoResult<Resolve> {
+    let oldest_first = match order {
+        VersionOrder::Newest => HashSet::new(),
+        VersionOrder::MinimalDirect => {
+            summaries.iter().map(|&(ref s, _)| s.package_id().clone()).collect()
+        }
+    };
     let cx = Context {
         resolve_graph: Graph::new(),
         resolve_features: HashMap::new(),
         resolve_replacements: HashMap::new(),
         activations: HashMap::new(),
         replacements: replacements,
+        oldest_first: &oldest_first,
     };
     let _p = profile::start(format!("resolving"));
     let cx = try!(activate_deps_loop(cx, registry, summaries));
@@ -290,6 +512,7 @@ pub fn resolve(summaries: &[(Summary, Method)],
     }
 
     try!(check_cycles(&resolve, &cx.activations));
+    try!(check_conflicts(&resolve, summaries, &cx.activations));
 
     trace!("resolved: {:?}", resolve);
     Ok(resolve)
@@ -597,38 +820,41 @@ fn activation_error(cx: &Context,
                     prev_active: &[Rc<Summary>],
                     candidates: &[Candidate]) -> Box<CargoError> {
     if candidates.len() > 0 {
-        let mut msg = format!("failed to select a version for `{}` \
-                               (required by `{}`):\n\
-                               all possible versions conflict with \
-                               previously selected versions of `{}`",
-                              dep.name(), parent.name(),
-                              dep.name());
-        'outer: for v in prev_active.iter() {
-            for node in cx.resolve_graph.iter() {
-                let edges = match cx.resolve_graph.edges(node) {
-                    Some(edges) => edges,
-                    None => continue,
-                };
-                for edge in edges {
-                    if edge != v.package_id() { continue }
-
-                    msg.push_str(&format!("\n  version {} in use by {}",
-                                          v.version(), edge));
-                    continue 'outer;
-                }
+        let summary = |id: &PackageId| {
+            cx.activations.values().flat_map(|v| v.iter())
+              .find(|s| s.package_id() == id).cloned()
+        };
+        let selected = prev_active.iter().map(|v| {
+            let mut parents = cx.resolve_graph.get_nodes().iter().filter(|&(_, deps)| {
+                deps.contains(v.package_id())
+            }).map(|(parent, _)| parent).collect::<Vec<_>>();
+            parents.sort();
+            SelectedVersion {
+                version: v.version().to_string(),
+                required_by: parents.into_iter().map(|parent| {
+                    let req = summary(parent).and_then(|s| {
+                        s.dependencies().iter().find(|d| {
+                            d.matches_id(v.package_id())
+                        }).map(|d| d.version_req().to_string())
+                    });
+                    Requirement {
+                        req: req.unwrap_or("*".to_string()),
+                        path: dependency_path(&cx.resolve_graph, parent),
+                    }
+                }).collect(),
             }
-            msg.push_str(&format!("\n  version {} in use by ??",
-                                  v.version()));
-        }
-
-        msg.push_str(&format!("\n  possible versions to select: {}",
-                              candidates.iter()
-                                        .map(|v| v.summary.version())
-                                        .map(|v| v.to_string())
-                                        .collect::<Vec<_>>()
-                                        .join(", ")));
-
-        return human(msg)
+        }).collect();
+        return Box::new(ResolveConflict {
+            name: dep.name().to_string(),
+            requirement: Requirement {
+                req: dep.version_req().to_string(),
+                path: dependency_path(&cx.resolve_graph, parent.package_id()),
+            },
+            selected: selected,
+            candidates: candidates.iter().map(|c| {
+                c.summary.version().to_string()
+            }).collect(),
+        })
     }
 
     // Once we're all the way down here, we're definitely lost in the
@@ -679,12 +905,100 @@ fn activation_error(cx: &Context,
     human(msg)
 }
 
-// Returns if `a` and `b` are compatible in the semver sense. This is a
-// commutative operation.
-//
-// Versions `a` and `b` are compatible if their left-most nonzero digit is the
-// same.
-fn compatible(a: &semver::Version, b: &semver::Version) -> bool {
+/// The chain of packages from one being resolved, which nothing depends on,
+/// down to `id`.
+fn dependency_path(graph: &Graph<PackageId>, id: &PackageId) -> Vec<PackageId> {
+    let mut path = vec![id.clone()];
+    loop {
+        let parent = graph.get_nodes().iter().filter(|&(node, deps)| {
+            deps.contains(&path[path.len() - 1]) && !path.contains(node)
+        }).map(|(node, _)| node).min().cloned();
+        match parent {
+            Some(parent) => path.push(parent),
+            None => break,
+        }
+    }
+    path.reverse();
+    path
+}
+
+/// Why no version of a dependency could be selected: every version matching
+/// its requirement is semver compatible with a version selected for other
+/// requirements before, and only one of those can be in the graph.
+#[derive(Debug, RustcEncodable)]
+pub struct ResolveConflict {
+    /// The name of the dependency.
+    name: String,
+    /// The requirement which couldn't be satisfied.
+    requirement: Requirement,
+    /// The versions of the dependency selected before, along with the
+    /// requirements they were selected for.
+    selected: Vec<SelectedVersion>,
+    /// The versions matching `requirement`.
+    candidates: Vec<String>,
+}
+
+/// A version requirement on a dependency, and how it came to be required.
+#[derive(Debug, RustcEncodable)]
+pub struct Requirement {
+    req: String,
+    /// The packages depending on one another from one being resolved down to
+    /// the one declaring the requirement.
+    path: Vec<PackageId>,
+}
+
+#[derive(Debug, RustcEncodable)]
+pub struct SelectedVersion {
+    version: String,
+    required_by: Vec<Requirement>,
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.path.iter().map(|id| format!("`{}`", id))
+                       .collect::<Vec<_>>();
+        write!(f, "`{}` by {}", self.req, path.join(" -> "))
+    }
+}
+
+impl fmt::Display for ResolveConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let declared_by = self.requirement.path.last().map(|id| id.name())
+                              .unwrap_or("");
+        try!(write!(f, "failed to select a version for `{}` (required by \
+                        `{}`):\nall possible versions conflict with \
+                        previously selected versions of `{}`",
+                    self.name, declared_by, self.name));
+        try!(write!(f, "\n  required {}", self.requirement));
+        for selected in self.selected.iter() {
+            try!(write!(f, "\n  version {} selected for", selected.version));
+            if selected.required_by.is_empty() {
+                try!(write!(f, " ??"));
+            }
+            for req in selected.required_by.iter() {
+                try!(write!(f, "\n    {}", req));
+            }
+        }
+        write!(f, "\n  possible versions to select: {}",
+               self.candidates.join(", "))
+    }
+}
+
+impl Error for ResolveConflict {
+    fn description(&self) -> &str { "failed to select a version" }
+}
+
+impl CargoError for ResolveConflict {
+    fn is_human(&self) -> bool { true }
+    fn to_json(&self) -> Option<String> { json::encode(self).ok() }
+}
+
+/// Returns if `a` and `b` are compatible in the semver sense. This is a
+/// commutative operation.
+///
+/// Versions `a` and `b` are compatible if their left-most nonzero digit is the
+/// same.
+pub fn compatible(a: &semver::Version, b: &semver::Version) -> bool {
     if a.major != b.major { return false }
     if a.major != 0 { return true }
     if a.minor != b.minor { return false }
@@ -824,12 +1138,18 @@ impl<'a> Context<'a> {
 
         // Next, transform all dependencies into a list of possible candidates
         // which can satisfy that dependency.
+        let oldest_first = self.oldest_first.contains(candidate.package_id());
         let mut deps = try!(deps.into_iter().map(|(dep, features)| {
             let mut candidates = try!(self.query(registry, &dep));
             // When we attempt versions for a package, we'll want to start at
-            // the maximum version and work our way down.
+            // the maximum version and work our way down, unless its lower
+            // bounds are being checked.
             candidates.sort_by(|a, b| {
-                b.summary.version().cmp(a.summary.version())
+                if oldest_first {
+                    a.summary.version().cmp(b.summary.version())
+                } else {
+                    b.summary.version().cmp(a.summary.version())
+                }
             });
             Ok((dep, candidates, features))
         }).collect::<CargoResult<Vec<DepInfo>>>());
@@ -1028,3 +1348,158 @@ fn check_cycles(resolve: &Resolve,
         Ok(())
     }
 }
+
+/// Checks that no package ends up with two features declared in its
+/// `[features.conflicts]` enabled, as the features of each package are the
+/// union of all those requested of it. The error explains how each of the
+/// two came to be enabled, starting from one of `summaries`.
+///
+/// Roots resolved with `Method::Everything` enable all of their features,
+/// conflicting or not, so nothing is checked then; the conflicts are enforced
+/// once the features actually asked for are resolved.
+fn check_conflicts(resolve: &Resolve,
+                   summaries: &[(Summary, Method)],
+                   activations: &HashMap<(String, SourceId), Vec<Rc<Summary>>>)
+                   -> CargoResult<()> {
+    let everything = summaries.iter().any(|&(_, method)| {
+        match method {
+            Method::Everything => true,
+            Method::Required { .. } => false,
+        }
+    });
+    if everything {
+        return Ok(())
+    }
+
+    let activated: HashMap<&PackageId, &Summary> = activations.values()
+        .flat_map(|v| v)
+        .map(|s| (s.package_id(), &**s))
+        .collect();
+
+    // Sort packages to produce user friendly deterministic errors.
+    let all_packages = activated.keys().cloned().collect::<BinaryHeap<_>>()
+                                .into_sorted_vec();
+    for id in all_packages {
+        let enabled = match resolve.features(id) {
+            Some(enabled) => enabled,
+            None => continue,
+        };
+        for &(ref a, ref b) in activated[id].conflicts() {
+            if enabled.contains(a) && enabled.contains(b) {
+                bail!("features `{}` and `{}` of `{}` conflict, but both are \
+                       enabled:\n  `{}` by {}\n  `{}` by {}", a, b, id,
+                      a, activation_path(resolve, summaries, &activated, id, a),
+                      b, activation_path(resolve, summaries, &activated, id, b))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Describes the shortest chain of features and dependencies from one of the
+/// packages being resolved which enables `feature` of `id`.
+fn activation_path(resolve: &Resolve,
+                   summaries: &[(Summary, Method)],
+                   activated: &HashMap<&PackageId, &Summary>,
+                   id: &PackageId,
+                   feature: &str) -> String {
+    let mut dependents: HashMap<&PackageId, Vec<&PackageId>> = HashMap::new();
+    for parent in resolve.iter() {
+        for dep in resolve.deps_not_replaced(parent) {
+            dependents.entry(dep).or_insert(Vec::new()).push(parent);
+        }
+    }
+    let is_enabled = |&(ref pkg, ref feature): &(PackageId, Option<String>)| {
+        feature.as_ref().map(|f| {
+            resolve.features(pkg).map(|e| e.contains(f)).unwrap_or(false)
+        }).unwrap_or(true)
+    };
+
+    // Nodes are a package, with the feature of it being enabled if any, and
+    // are searched from `id` back towards the roots. Each node found maps to
+    // the one it enables, which leads back to `id`.
+    let start = (id.clone(), Some(feature.to_string()));
+    let mut enables = HashMap::new();
+    enables.insert(start.clone(), None);
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(node) = queue.pop_front() {
+        let summary = match activated.get(&node.0) {
+            Some(summary) => *summary,
+            None => continue,
+        };
+        let root = summaries.iter().find(|&&(ref s, _)| {
+            s.package_id() == &node.0
+        });
+        if let Some(&(_, method)) = root {
+            let requested = match (method, &node.1) {
+                (_, &None) | (Method::Everything, _) => true,
+                (Method::Required { features, uses_default_features, .. },
+                 &Some(ref f)) => {
+                    features.contains(f) || (uses_default_features && f == "default")
+                }
+            };
+            if requested {
+                let mut path = Vec::new();
+                let mut cur = Some(node);
+                while let Some(node) = cur {
+                    path.push(match node.1 {
+                        Some(ref f) => format!("feature `{}` of `{}`", f, node.0),
+                        None => format!("`{}`", node.0),
+                    });
+                    cur = enables[&node].clone();
+                }
+                return path.join(" -> ")
+            }
+        }
+
+        let mut found = Vec::new();
+        if let Some(ref f) = node.1 {
+            for (other, list) in summary.features() {
+                if list.contains(f) {
+                    found.push((node.0.clone(), Some(other.clone())));
+                }
+            }
+        }
+        for &parent in dependents.get(&node.0).into_iter().flat_map(|p| p.iter()) {
+            let parent_summary = match activated.get(parent) {
+                Some(summary) => *summary,
+                None => continue,
+            };
+            for dep in parent_summary.dependencies() {
+                if !dep.matches_id(&node.0) {
+                    continue
+                }
+                // The parent needs the dependency itself, which for an
+                // optional one is the feature of the same name.
+                let through_dep = if dep.is_optional() {
+                    (parent.clone(), Some(dep.name().to_string()))
+                } else {
+                    (parent.clone(), None)
+                };
+                match node.1 {
+                    Some(ref f) => {
+                        if dep.features().contains(f) ||
+                           (f == "default" && dep.uses_default_features()) {
+                            found.push(through_dep);
+                        }
+                        let reexport = format!("{}/{}", dep.name(), f);
+                        for (other, list) in parent_summary.features() {
+                            if list.contains(&reexport) {
+                                found.push((parent.clone(), Some(other.clone())));
+                            }
+                        }
+                    }
+                    None => found.push(through_dep),
+                }
+            }
+        }
+        for next in found {
+            if is_enabled(&next) && !enables.contains_key(&next) {
+                enables.insert(next.clone(), Some(node.clone()));
+                queue.push_back(next);
+            }
+        }
+    }
+    "an unknown path".to_string()
+}
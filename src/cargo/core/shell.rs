@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::io::prelude::*;
 use std::io;
+use std::str::FromStr;
 
 use term::color::{Color, BLACK, RED, GREEN, YELLOW};
 use term::{self, Terminal, TerminfoTerminal, color, Attr};
@@ -9,13 +11,46 @@ use self::AdequateTerminal::{NoColor, Colored};
 use self::Verbosity::{Verbose, Quiet};
 use self::ColorConfig::{Auto, Always, Never};
 
-use util::errors::CargoResult;
+use util::errors::{CargoError, CargoResult};
 
-#[derive(Clone, Copy, PartialEq)]
+/// How much is printed, from least to most.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum Verbosity {
-    Verbose,
+    Quiet,
     Normal,
-    Quiet
+    Verbose,
+    /// Everything, including what's only useful to find out why cargo
+    /// behaves the way it does.
+    Trace,
+}
+
+/// The parts of cargo whose verbosity can be raised on their own, e.g. with
+/// `-v=fingerprint`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Subsystem {
+    /// Fetching packages from registries.
+    Download,
+    /// Running the compiler and build scripts.
+    Compile,
+    /// Moving the final artifacts out of the `deps` directory.
+    Uplift,
+    /// Deciding which units are up to date.
+    Fingerprint,
+}
+
+impl FromStr for Subsystem {
+    type Err = Box<CargoError>;
+
+    fn from_str(s: &str) -> CargoResult<Subsystem> {
+        match s {
+            "download" => Ok(Subsystem::Download),
+            "compile" => Ok(Subsystem::Compile),
+            "uplift" => Ok(Subsystem::Uplift),
+            "fingerprint" => Ok(Subsystem::Fingerprint),
+            _ => bail!("unknown subsystem `{}`, expected one of `download`, \
+                        `compile`, `uplift` or `fingerprint`", s),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -54,12 +89,19 @@ pub struct Shell {
 pub struct MultiShell {
     out: Shell,
     err: Shell,
-    verbosity: Verbosity
+    verbosity: Verbosity,
+    /// The subsystems whose verbosity was raised above `verbosity`.
+    raised: HashMap<Subsystem, Verbosity>,
 }
 
 impl MultiShell {
     pub fn new(out: Shell, err: Shell, verbosity: Verbosity) -> MultiShell {
-        MultiShell { out: out, err: err, verbosity: verbosity }
+        MultiShell {
+            out: out,
+            err: err,
+            verbosity: verbosity,
+            raised: HashMap::new(),
+        }
     }
 
     pub fn out(&mut self) -> &mut Shell {
@@ -87,21 +129,39 @@ impl MultiShell {
         }
     }
 
+    /// Prints a status message of `subsystem` if its verbosity is at least
+    /// `verbosity`.
+    pub fn status_at<T, U>(&mut self,
+                           subsystem: Subsystem,
+                           verbosity: Verbosity,
+                           status: T,
+                           message: U) -> CargoResult<()>
+        where T: fmt::Display, U: fmt::Display
+    {
+        if self.verbosity_of(subsystem) >= verbosity {
+            self.err().say_status(status, message, GREEN, true)
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn verbose<F>(&mut self, mut callback: F) -> CargoResult<()>
         where F: FnMut(&mut MultiShell) -> CargoResult<()>
     {
-        match self.verbosity {
-            Verbose => callback(self),
-            _ => Ok(())
+        if self.verbosity >= Verbose {
+            callback(self)
+        } else {
+            Ok(())
         }
     }
 
     pub fn concise<F>(&mut self, mut callback: F) -> CargoResult<()>
         where F: FnMut(&mut MultiShell) -> CargoResult<()>
     {
-        match self.verbosity {
-            Verbose => Ok(()),
-            _ => callback(self)
+        if self.verbosity >= Verbose {
+            Ok(())
+        } else {
+            callback(self)
         }
     }
 
@@ -120,6 +180,22 @@ impl MultiShell {
         self.verbosity = verbosity;
     }
 
+    /// Raises the verbosity of `subsystem` alone to `verbosity`, if that's
+    /// more than the verbosity of everything else.
+    pub fn raise_verbosity(&mut self, subsystem: Subsystem, verbosity: Verbosity) {
+        let level = self.raised.entry(subsystem).or_insert(verbosity);
+        if *level < verbosity {
+            *level = verbosity;
+        }
+    }
+
+    pub fn verbosity_of(&self, subsystem: Subsystem) -> Verbosity {
+        match self.raised.get(&subsystem) {
+            Some(&raised) if raised > self.verbosity => raised,
+            _ => self.verbosity,
+        }
+    }
+
     pub fn set_color_config(&mut self, color: Option<&str>) -> CargoResult<()> {
         let cfg = match color {
             Some("auto") => Auto,
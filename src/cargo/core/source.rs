@@ -29,6 +29,17 @@ pub trait Source: Registry {
     /// version specified.
     fn download(&mut self, package: &PackageId) -> CargoResult<Package>;
 
+    /// Downloads the packages `ids` ahead of them being asked for with
+    /// `download`, as efficiently as the source can, e.g. several at a time.
+    ///
+    /// This is only an optimization: packages which fail to be prefetched are
+    /// left for `download` to fetch and report errors about. With
+    /// `json_messages` the progress is reported in JSON messages.
+    fn prefetch(&mut self, _ids: &[PackageId], _json_messages: bool)
+                -> CargoResult<()> {
+        Ok(())
+    }
+
     /// Generates a unique string which represents the fingerprint of the
     /// current state of the source.
     ///
@@ -50,6 +61,12 @@ pub trait Source: Registry {
     fn verify(&self, _pkg: &PackageId) -> CargoResult<()> {
         Ok(())
     }
+
+    /// Returns whether the package specified has been yanked from this
+    /// source, for sources which support yanking.
+    fn is_yanked(&mut self, _pkg: &PackageId) -> CargoResult<bool> {
+        Ok(false)
+    }
 }
 
 impl<'a, T: Source + ?Sized + 'a> Source for Box<T> {
@@ -61,6 +78,11 @@ impl<'a, T: Source + ?Sized + 'a> Source for Box<T> {
         (**self).download(id)
     }
 
+    fn prefetch(&mut self, ids: &[PackageId], json_messages: bool)
+                -> CargoResult<()> {
+        (**self).prefetch(ids, json_messages)
+    }
+
     fn fingerprint(&self, pkg: &Package) -> CargoResult<String> {
         (**self).fingerprint(pkg)
     }
@@ -68,6 +90,10 @@ impl<'a, T: Source + ?Sized + 'a> Source for Box<T> {
     fn verify(&self, pkg: &PackageId) -> CargoResult<()> {
         (**self).verify(pkg)
     }
+
+    fn is_yanked(&mut self, pkg: &PackageId) -> CargoResult<bool> {
+        (**self).is_yanked(pkg)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -251,6 +277,9 @@ impl SourceId {
     pub fn is_registry(&self) -> bool {
         self.inner.kind == Kind::Registry || self.inner.kind == Kind::LocalRegistry
     }
+    pub fn is_local_registry(&self) -> bool {
+        self.inner.kind == Kind::LocalRegistry
+    }
 
     pub fn is_git(&self) -> bool {
         match self.inner.kind {
@@ -509,6 +538,13 @@ impl<'src> SourceMap<'src> {
         self.map.insert(id.clone(), source);
     }
 
+    /// Adds the sources of `other` for the ids this map has no source for.
+    pub fn extend(&mut self, other: SourceMap<'src>) {
+        for (id, source) in other.map {
+            self.map.entry(id).or_insert(source);
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.map.is_empty()
     }
@@ -15,7 +15,15 @@ pub struct Summary {
     package_id: PackageId,
     dependencies: Vec<Dependency>,
     features: HashMap<String, Vec<String>>,
+    /// Pairs of features which can't be enabled together.
+    conflicts: Vec<(String, String)>,
     checksum: Option<String>,
+    published: Option<u64>,
+    size: Option<u64>,
+    rust_version: Option<String>,
+    publisher: Option<String>,
+    /// One-line descriptions of features, by name.
+    feature_descriptions: HashMap<String, String>,
 }
 
 impl Summary {
@@ -61,7 +69,13 @@ impl Summary {
             package_id: pkg_id,
             dependencies: dependencies,
             features: features,
+            conflicts: Vec::new(),
             checksum: None,
+            published: None,
+            size: None,
+            rust_version: None,
+            publisher: None,
+            feature_descriptions: HashMap::new(),
         })
     }
 
@@ -71,10 +85,36 @@ impl Summary {
     pub fn source_id(&self) -> &SourceId { self.package_id.source_id() }
     pub fn dependencies(&self) -> &[Dependency] { &self.dependencies }
     pub fn features(&self) -> &HashMap<String, Vec<String>> { &self.features }
+    pub fn conflicts(&self) -> &[(String, String)] { &self.conflicts }
     pub fn checksum(&self) -> Option<&str> {
         self.checksum.as_ref().map(|s| &s[..])
     }
 
+    /// When this version was published, in seconds since the Unix epoch, if
+    /// its registry records it.
+    pub fn published(&self) -> Option<u64> { self.published }
+
+    /// The size in bytes of the compressed package its registry serves, if
+    /// the registry records it.
+    pub fn size(&self) -> Option<u64> { self.size }
+
+    /// The oldest version of Rust the package declares it builds with, if its
+    /// registry records it.
+    pub fn rust_version(&self) -> Option<&str> {
+        self.rust_version.as_ref().map(|s| &s[..])
+    }
+
+    /// One-line descriptions of the features which have one, if the
+    /// registry records them.
+    pub fn feature_descriptions(&self) -> &HashMap<String, String> {
+        &self.feature_descriptions
+    }
+
+    /// The user who published this version, if its registry records it.
+    pub fn publisher(&self) -> Option<&str> {
+        self.publisher.as_ref().map(|s| &s[..])
+    }
+
     pub fn override_id(mut self, id: PackageId) -> Summary {
         self.package_id = id;
         self
@@ -85,6 +125,38 @@ impl Summary {
         self
     }
 
+    pub fn set_conflicts(mut self, conflicts: Vec<(String, String)>) -> Summary {
+        self.conflicts = conflicts;
+        self
+    }
+
+    pub fn set_published(mut self, published: u64) -> Summary {
+        self.published = Some(published);
+        self
+    }
+
+    pub fn set_size(mut self, size: Option<u64>) -> Summary {
+        self.size = size;
+        self
+    }
+
+    pub fn set_rust_version(mut self, rust_version: Option<String>) -> Summary {
+        self.rust_version = rust_version;
+        self
+    }
+
+    pub fn set_feature_descriptions(mut self,
+                                    descriptions: HashMap<String, String>)
+                                    -> Summary {
+        self.feature_descriptions = descriptions;
+        self
+    }
+
+    pub fn set_publisher(mut self, publisher: Option<String>) -> Summary {
+        self.publisher = publisher;
+        self
+    }
+
     pub fn map_dependencies<F>(mut self, f: F) -> Summary
                                where F: FnMut(Dependency) -> Dependency {
         let deps = mem::replace(&mut self.dependencies, Vec::new());
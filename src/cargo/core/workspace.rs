@@ -1,13 +1,16 @@
 use std::collections::hash_map::{HashMap, Entry};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::slice;
 
+use toml;
+
 use core::{Package, VirtualManifest, EitherManifest, SourceId};
 use core::{PackageIdSpec, Dependency};
 use ops;
 use util::{Config, CargoResult, Filesystem, human};
 use util::paths;
+use util::toml as cargo_toml;
 
 /// The core abstraction in Cargo for working with a workspace of crates.
 ///
@@ -40,6 +43,10 @@ pub struct Workspace<'cfg> {
     // paths. The packages themselves can be looked up through the `packages`
     // set above.
     members: Vec<PathBuf>,
+
+    // Whether only the members a command asked for were loaded, see
+    // `Workspace::new_for_specs`.
+    partial: bool,
 }
 
 // Separate structure for tracking loaded packages (to avoid loading anything
@@ -94,6 +101,7 @@ impl<'cfg> Workspace<'cfg> {
             root_manifest: None,
             target_dir: target_dir,
             members: Vec::new(),
+            partial: false,
         };
         ws.root_manifest = try!(ws.find_root(manifest_path));
         try!(ws.find_members());
@@ -101,6 +109,46 @@ impl<'cfg> Workspace<'cfg> {
         Ok(ws)
     }
 
+    /// Like `new`, but for commands which only operate on the packages named
+    /// by `specs`, the `-p` flags.
+    ///
+    /// If the workspace lists its members in `workspace.members` and has a
+    /// lockfile, only the members named by `specs` and the members they
+    /// depend on according to the lockfile are loaded, along with the current
+    /// package. The manifests of the other members are only read far enough
+    /// to learn the names of their packages. Otherwise, or if a spec doesn't
+    /// name a member in the lockfile, the whole workspace is loaded.
+    ///
+    /// A partially loaded workspace never writes the lockfile, see
+    /// `is_partial`.
+    pub fn new_for_specs(manifest_path: &Path,
+                         config: &'cfg Config,
+                         specs: &[String]) -> CargoResult<Workspace<'cfg>> {
+        if specs.is_empty() {
+            return Workspace::new(manifest_path, config)
+        }
+        let target_dir = try!(config.target_dir());
+
+        let mut ws = Workspace {
+            config: config,
+            current_manifest: manifest_path.to_path_buf(),
+            packages: Packages {
+                config: config,
+                packages: HashMap::new(),
+            },
+            root_manifest: None,
+            target_dir: target_dir,
+            members: Vec::new(),
+            partial: false,
+        };
+        ws.root_manifest = try!(ws.find_root(manifest_path));
+        if !try!(ws.find_members_for_specs(specs)) {
+            try!(ws.find_members());
+        }
+        try!(ws.validate());
+        Ok(ws)
+    }
+
     /// Creates a "temporary workspace" from one package which only contains
     /// that package.
     ///
@@ -122,6 +170,7 @@ impl<'cfg> Workspace<'cfg> {
             root_manifest: None,
             target_dir: None,
             members: Vec::new(),
+            partial: false,
         };
         {
             let key = ws.current_manifest.parent().unwrap();
@@ -179,6 +228,12 @@ impl<'cfg> Workspace<'cfg> {
         })
     }
 
+    /// Places the build artifacts of this workspace in `target_dir` instead
+    /// of wherever the configuration says they go.
+    pub fn set_target_dir(&mut self, target_dir: Filesystem) {
+        self.target_dir = Some(target_dir);
+    }
+
     /// Returns the root [replace] section of this workspace.
     ///
     /// This may be from a virtual crate or an actual crate.
@@ -193,6 +248,13 @@ impl<'cfg> Workspace<'cfg> {
         }
     }
 
+    /// Returns whether only some of the members of this workspace were
+    /// loaded, in which case `members` doesn't list all of them and the
+    /// lockfile can't be written from a resolution of this workspace.
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+
     /// Returns an iterator over all packages in this workspace
     pub fn members<'a>(&'a self) -> Members<'a, 'cfg> {
         Members {
@@ -285,6 +347,71 @@ impl<'cfg> Workspace<'cfg> {
         self.find_path_deps(&root_manifest)
     }
 
+    /// Loads only the members named by `specs` and those they depend on,
+    /// as described in `new_for_specs`.
+    ///
+    /// Returns `false`, having loaded nothing, if the workspace has to be
+    /// loaded in full.
+    fn find_members_for_specs(&mut self, specs: &[String]) -> CargoResult<bool> {
+        let root_manifest = match self.root_manifest {
+            Some(ref path) => path.clone(),
+            None => return Ok(false),
+        };
+        let list = match *try!(self.packages.load(&root_manifest)).workspace_config() {
+            WorkspaceConfig::Root { members: Some(ref list) } => list.clone(),
+            _ => return Ok(false),
+        };
+        let root = root_manifest.parent().unwrap().to_path_buf();
+        let lockfile = root.join("Cargo.lock");
+        let path_deps = match try!(lockfile_path_deps(&lockfile, self.config)) {
+            Some(deps) => deps,
+            None => return Ok(false),
+        };
+
+        // The members needed are those named and, transitively, the path
+        // packages they depend on.
+        let mut wanted = HashSet::new();
+        let mut stack = Vec::new();
+        for spec in specs {
+            let name = try!(PackageIdSpec::parse(spec)).name().to_string();
+            if !path_deps.contains_key(&name) {
+                return Ok(false)
+            }
+            stack.push(name);
+        }
+        while let Some(name) = stack.pop() {
+            if wanted.insert(name.clone()) {
+                stack.extend(path_deps[&name].iter().cloned());
+            }
+        }
+
+        let mut found = Vec::new();
+        for path in list {
+            if found.len() == wanted.len() {
+                break
+            }
+            let manifest_path = root.join(path).join("Cargo.toml");
+            match try!(peek_package_name(&manifest_path, self.config)) {
+                Some(ref name) if wanted.contains(name) => found.push(manifest_path),
+                _ => {}
+            }
+        }
+        if found.len() < wanted.len() {
+            // Some of them aren't listed members, only following the path
+            // dependencies of every member finds them.
+            return Ok(false)
+        }
+
+        debug!("find_members_for_specs - loading {} members", found.len());
+        self.partial = true;
+        let current = self.current_manifest.clone();
+        try!(self.find_path_deps(&current));
+        for manifest_path in found {
+            try!(self.find_path_deps(&manifest_path));
+        }
+        Ok(true)
+    }
+
     fn find_path_deps(&mut self, manifest_path: &Path) -> CargoResult<()> {
         if self.members.iter().any(|p| p == manifest_path) {
             return Ok(())
@@ -346,6 +473,9 @@ impl<'cfg> Workspace<'cfg> {
         }
 
         match roots.len() {
+            // The root may not be among the members loaded, but it was found
+            // to be a root when it was located.
+            0 if self.partial => {}
             0 => {
                 bail!("`package.workspace` configuration points to a crate \
                        which is not configured with [workspace]: \n\
@@ -436,6 +566,54 @@ impl<'cfg> Workspace<'cfg> {
     }
 }
 
+/// Reads the lockfile at `path`, if there is one, returning the names of
+/// the path packages it lists along with those of the path packages each
+/// depends on.
+fn lockfile_path_deps(path: &Path, config: &Config)
+                      -> CargoResult<Option<HashMap<String, Vec<String>>>> {
+    let contents = match paths::read(path) {
+        Ok(contents) => contents,
+        Err(..) => return Ok(None),
+    };
+    let table = try!(cargo_toml::parse(&contents, path, config));
+    let mut packages = Vec::new();
+    if let Some(&toml::Value::Table(ref root)) = table.get("root") {
+        packages.push(root);
+    }
+    if let Some(&toml::Value::Array(ref list)) = table.get("package") {
+        packages.extend(list.iter().filter_map(|p| p.as_table()));
+    }
+
+    // Path packages are the ones without a source.
+    let mut names = HashMap::new();
+    for pkg in packages.iter().filter(|p| !p.contains_key("source")) {
+        if let Some(name) = pkg.get("name").and_then(|n| n.as_str()) {
+            names.insert(name.to_string(), *pkg);
+        }
+    }
+    Ok(Some(names.iter().map(|(name, pkg)| {
+        let deps = pkg.get("dependencies").and_then(|d| d.as_slice())
+                      .unwrap_or(&[]).iter().filter_map(|d| d.as_str())
+                      .filter(|d| !d.contains('('))
+                      .filter_map(|d| d.split(' ').next())
+                      .filter(|d| names.contains_key(*d))
+                      .map(|d| d.to_string()).collect();
+        (name.clone(), deps)
+    }).collect()))
+}
+
+/// Reads the name of the package whose manifest is at `manifest_path`
+/// without interpreting the rest of the manifest. Returns `None` for virtual
+/// manifests.
+fn peek_package_name(manifest_path: &Path, config: &Config)
+                     -> CargoResult<Option<String>> {
+    let contents = try!(paths::read(manifest_path));
+    let table = try!(cargo_toml::parse(&contents, manifest_path, config));
+    let package = table.get("package").or(table.get("project"));
+    Ok(package.and_then(|p| p.lookup("name")).and_then(|n| n.as_str())
+              .map(|n| n.to_string()))
+}
+
 impl<'cfg> Packages<'cfg> {
     fn get(&self, manifest_path: &Path) -> &MaybePackage {
         &self.packages[manifest_path.parent().unwrap()]
@@ -23,6 +23,7 @@ extern crate tempdir;
 extern crate term;
 extern crate toml;
 extern crate url;
+extern crate zstd;
 
 use std::env;
 use std::io;
@@ -156,7 +157,7 @@ pub fn handle_error(err: CliError, shell: &mut MultiShell) {
     // exit_code == 0 is non-fatal error, e.g. docopt version info
     let fatal = exit_code != 0;
 
-    let hide = unknown && shell.get_verbose() != Verbose;
+    let hide = unknown && shell.get_verbose() < Verbose;
 
     if let Some(error) = error {
         let _ignored_result = if hide {
@@ -184,12 +185,12 @@ fn handle_cause(mut cargo_err: &CargoError, shell: &mut MultiShell) -> bool {
             Some(cause) => cause,
             None => { err = cargo_err.cause(); break }
         };
-        if verbose != Verbose && !cargo_err.is_human() { return false }
+        if verbose < Verbose && !cargo_err.is_human() { return false }
         print(cargo_err.to_string(), shell);
     }
     loop {
         let cause = match err { Some(err) => err, None => return true };
-        if verbose != Verbose { return false }
+        if verbose < Verbose { return false }
         print(cause.to_string(), shell);
         err = cause.cause();
     }
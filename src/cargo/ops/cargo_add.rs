@@ -0,0 +1,240 @@
+//! Adding dependencies to the manifest of a package, for `cargo add`.
+//!
+//! The manifest is edited as text, like `cargo update --breaking` does, so its
+//! comments and formatting are kept: the dependency is declared on one line at
+//! the end of its table, or replaces the line already declaring it. The
+//! workspace is then resolved again to update the lockfile, and the manifest is
+//! restored if that fails, or if the supply-chain policy of the workspace
+//! doesn't allow the packages added.
+
+use std::fs;
+
+use toml;
+
+use core::dependency::Kind;
+use core::registry::PackageRegistry;
+use core::{Dependency, SourceId, Workspace};
+use ops;
+use util::{paths, CargoResult, ChainError, human};
+use util::toml as cargo_toml;
+
+use super::cargo_upgrade::{newest_version, read_manifest};
+
+pub struct AddOptions<'a> {
+    /// The crates to add, each a name optionally followed by `@` and a
+    /// version requirement.
+    pub crates: &'a [String],
+    pub kind: Kind,
+    pub features: &'a [String],
+    pub optional: bool,
+    pub path: Option<&'a str>,
+    pub git: Option<&'a str>,
+    pub branch: Option<&'a str>,
+    pub tag: Option<&'a str>,
+    pub rev: Option<&'a str>,
+}
+
+/// Declares the crates of `opts` as dependencies of the current package of
+/// `ws`, and updates the lockfile.
+pub fn add(ws: &Workspace, opts: &AddOptions) -> CargoResult<()> {
+    let config = ws.config();
+    let pkg = try!(ws.current());
+    let manifest_path = pkg.manifest_path();
+
+    if opts.crates.len() > 1 && (opts.path.is_some() || opts.git.is_some()) {
+        bail!("only one crate can be added with --path or --git")
+    }
+    if opts.path.is_some() && opts.git.is_some() {
+        bail!("--path and --git can't both be specified")
+    }
+    let refs = [opts.branch, opts.tag, opts.rev].iter()
+                                                .filter(|r| r.is_some())
+                                                .count();
+    if refs > 0 && opts.git.is_none() {
+        bail!("--branch, --tag and --rev can only be specified along with --git")
+    }
+    if refs > 1 {
+        bail!("only one of --branch, --tag and --rev can be specified")
+    }
+
+    let table = match opts.kind {
+        Kind::Normal => "dependencies",
+        Kind::Development => "dev-dependencies",
+        Kind::Build => "build-dependencies",
+    };
+    let root = try!(read_manifest(&ws.root().join("Cargo.toml"), config));
+    let mut registry = try!(PackageRegistry::new(config));
+
+    let original = try!(paths::read(manifest_path));
+    let mut contents = original.clone();
+    for krate in opts.crates.iter() {
+        let (name, req) = match krate.find('@') {
+            Some(at) => (&krate[..at], Some(&krate[at + 1..])),
+            None => (&krate[..], None),
+        };
+        if name.is_empty() ||
+           name.contains(|c: char| !c.is_alphanumeric() && c != '-' && c != '_') {
+            bail!("`{}` isn't a valid crate name", name)
+        }
+
+        let mut keys = Vec::new();
+        let described;
+        if let Some(path) = opts.path {
+            let path = cargo_toml::relative_path(pkg.root(), &config.cwd().join(path));
+            let path = path.to_string_lossy().replace("\\", "/");
+            described = format!("from {}", path);
+            keys.push(("path", toml::Value::String(path)));
+        } else if let Some(git) = opts.git {
+            described = format!("from {}", git);
+            keys.push(("git", toml::Value::String(git.to_string())));
+            let reference = [("branch", opts.branch), ("tag", opts.tag),
+                             ("rev", opts.rev)];
+            for &(key, value) in reference.iter() {
+                if let Some(value) = value {
+                    keys.push((key, toml::Value::String(value.to_string())));
+                }
+            }
+        } else if req.is_none() && inherits(&root, name) {
+            described = "from the workspace".to_string();
+            keys.push(("workspace", toml::Value::Boolean(true)));
+        } else {
+            described = String::new();
+        }
+
+        let version = match req {
+            Some(req) => Some(req.to_string()),
+            None if keys.is_empty() => {
+                let source = try!(SourceId::crates_io(config));
+                let any = try!(Dependency::parse(name, None, &source));
+                match try!(newest_version(&mut registry, &any)) {
+                    Some(v) => Some(format!("{}.{}.{}", v.major, v.minor, v.patch)),
+                    None => bail!("no crate named `{}` was found in `{}`",
+                                  name, source.url()),
+                }
+            }
+            None => None,
+        };
+        let described = match version {
+            Some(ref version) if described.is_empty() => format!("v{}", version),
+            Some(ref version) => format!("v{} {}", version, described),
+            None => described,
+        };
+        if let Some(version) = version {
+            keys.insert(0, ("version", toml::Value::String(version)));
+        }
+        if !opts.features.is_empty() {
+            let features = opts.features.iter().map(|f| {
+                toml::Value::String(f.clone())
+            }).collect();
+            keys.push(("features", toml::Value::Array(features)));
+        }
+        if opts.optional {
+            keys.push(("optional", toml::Value::Boolean(true)));
+        }
+
+        let value = match (keys.len(), keys.first()) {
+            (1, Some(&("version", ref version))) => version.to_string(),
+            _ => {
+                let keys = keys.iter().map(|&(key, ref value)| {
+                    format!("{} = {}", key, value)
+                }).collect::<Vec<_>>();
+                format!("{{ {} }}", keys.join(", "))
+            }
+        };
+        contents = try!(declare(&contents, table, name,
+                                &format!("{} = {}", name, value)).chain_error(|| {
+            human(format!("failed to add `{}` to `{}`", name,
+                          manifest_path.display()))
+        }));
+        try!(config.shell().status("Adding", format!("{} {} to {}", name,
+                                                     described, table)));
+    }
+    let lockfile = ws.root().join("Cargo.lock");
+    let locked = if lockfile.exists() {
+        Some(try!(paths::read(&lockfile)))
+    } else {
+        None
+    };
+    try!(paths::write(manifest_path, contents.as_bytes()));
+
+    // Make sure the dependencies can be resolved and are allowed by the
+    // supply-chain policy, and record them in the lockfile, leaving the
+    // manifest and the lockfile as they were otherwise.
+    let updated = Workspace::new(manifest_path, config).and_then(|ws| {
+        let mut registry = try!(PackageRegistry::new(config));
+        let resolve = try!(ops::resolve_ws(&mut registry, &ws));
+        ops::check_vetted(&ws, &resolve)
+    });
+    if let Err(e) = updated {
+        try!(paths::write(manifest_path, original.as_bytes()));
+        match locked {
+            Some(locked) => try!(paths::write(&lockfile, locked.as_bytes())),
+            None if lockfile.exists() => try!(fs::remove_file(&lockfile)),
+            None => {}
+        }
+        return Err(e)
+    }
+    Ok(())
+}
+
+/// Whether the root manifest `root` declares `name` in
+/// `[workspace.dependencies]`.
+fn inherits(root: &toml::Value, name: &str) -> bool {
+    root.lookup("workspace.dependencies")
+        .and_then(|t| t.as_table())
+        .map(|t| t.contains_key(name))
+        .unwrap_or(false)
+}
+
+/// Declares the dependency `name` with `line` in the table `table` of the
+/// manifest `contents`, replacing the line already declaring it, or appending
+/// it to the table, which is added to the end of the manifest if it's missing.
+fn declare(contents: &str, table: &str, name: &str, line: &str) -> CargoResult<String> {
+    let aliases = [table.to_string(), table.replace("-", "_")];
+    let mut lines = contents.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+    let mut in_table = false;
+    // The line after the header of the table or its last declaration.
+    let mut end = None;
+    for i in 0..lines.len() {
+        let trimmed = lines[i].trim().to_string();
+        if trimmed.starts_with('[') {
+            let header = trimmed.trim_matches(|c: char| c == '[' || c == ']').trim();
+            in_table = aliases.iter().any(|a| a == header);
+            if in_table {
+                end = Some(i + 1);
+            }
+            if aliases.iter().any(|a| header == format!("{}.{}", a, name)) {
+                bail!("`{}` is declared by its own `[{}]` table, which can't \
+                       be edited", name, header)
+            }
+        } else if in_table && !trimmed.is_empty() && !trimmed.starts_with('#') {
+            end = Some(i + 1);
+            if let Some(pos) = trimmed.find('=') {
+                let key = trimmed[..pos].trim()
+                                        .trim_matches(|c: char| c == '"' || c == '\'');
+                if key == name {
+                    let indent = lines[i].len() - lines[i].trim_left().len();
+                    lines[i] = format!("{}{}", &lines[i][..indent], line);
+                    return Ok(join(lines))
+                }
+            }
+        }
+    }
+    match end {
+        Some(end) => lines.insert(end, line.to_string()),
+        None => {
+            if lines.last().map(|l| !l.trim().is_empty()).unwrap_or(false) {
+                lines.push(String::new());
+            }
+            lines.push(format!("[{}]", table));
+            lines.push(line.to_string());
+        }
+    }
+    Ok(join(lines))
+}
+
+fn join(lines: Vec<String>) -> String {
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
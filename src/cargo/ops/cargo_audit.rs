@@ -0,0 +1,291 @@
+//! Checking the locked dependencies against security advisories.
+//!
+//! Advisories either come from a file listing the affected versions of each
+//! package, as passed to `cargo update --from-advisories`, or from local copies
+//! of advisory databases in the format of the RustSec database, configured in
+//! the `[audit]` table. When databases are configured, every build checks the
+//! registry packages it uses against them, and `cargo update` updates the
+//! affected packages along with those it was asked to.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::Decodable;
+use semver::VersionReq;
+use toml;
+
+use core::{PackageId, Resolve, Workspace};
+use util::{CargoResult, ChainError, Config, human, paths};
+use util::toml as cargo_toml;
+
+/// A file listing advisories against packages, used with
+/// `cargo update --from-advisories`.
+#[derive(RustcDecodable)]
+struct AdvisoryFile {
+    advisory: Option<Vec<TomlAdvisory>>,
+}
+
+#[derive(RustcDecodable)]
+struct TomlAdvisory {
+    id: Option<String>,
+    package: String,
+    versions: Vec<String>,
+}
+
+/// A set of versions of a package which must be avoided.
+pub struct Advisory {
+    pub id: String,
+    pub package: String,
+    /// The versions affected, unless they're also `unaffected`.
+    affected: Vec<VersionReq>,
+    unaffected: Vec<VersionReq>,
+    /// Describes the versions affected, e.g. "versions `< 0.1.1`".
+    pub versions: String,
+}
+
+impl Advisory {
+    pub fn affects(&self, pkg: &PackageId) -> bool {
+        pkg.name() == self.package &&
+            self.affected.iter().any(|req| req.matches(pkg.version())) &&
+            !self.unaffected.iter().any(|req| req.matches(pkg.version()))
+    }
+}
+
+/// What to do about locked packages affected by advisories.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AuditAction {
+    /// Print a warning for each of them.
+    Warn,
+    /// Fail the build.
+    Deny,
+}
+
+/// The advisory databases to check builds against.
+pub struct AuditPolicy {
+    pub databases: Vec<PathBuf>,
+    pub action: AuditAction,
+}
+
+impl AuditPolicy {
+    /// Reads the policy from the `audit.databases` and `audit.action`
+    /// configuration keys. Returns `None` if no database is configured.
+    pub fn from_config(config: &Config) -> CargoResult<Option<AuditPolicy>> {
+        let databases = match try!(config.get_list("audit.databases")) {
+            Some(list) => list.val,
+            None => return Ok(None),
+        };
+        // Like `paths`, each database is relative to the directory containing
+        // the `.cargo` directory it's configured in.
+        let databases = databases.iter().map(|&(ref s, ref p)| {
+            p.parent().unwrap().parent().unwrap().join(s)
+        }).collect::<Vec<_>>();
+        if databases.is_empty() {
+            return Ok(None)
+        }
+        let action = match try!(config.get_string("audit.action")) {
+            None => AuditAction::Warn,
+            Some(v) => match &v.val[..] {
+                "warn" => AuditAction::Warn,
+                "deny" => AuditAction::Deny,
+                s => bail!("invalid configuration for key `audit.action`, \
+                            expected `warn` or `deny` but found `{}` in {}",
+                           s, v.definition),
+            },
+        };
+        Ok(Some(AuditPolicy {
+            databases: databases,
+            action: action,
+        }))
+    }
+
+    /// Loads the advisories of every configured database.
+    pub fn advisories(&self, config: &Config) -> CargoResult<Vec<Advisory>> {
+        let mut ret = Vec::new();
+        for db in self.databases.iter() {
+            ret.extend(try!(load_database(db, config)));
+        }
+        Ok(ret)
+    }
+}
+
+/// Checks the registry packages of a build against the configured advisory
+/// databases, if any, warning about those affected or failing if the policy
+/// denies them.
+pub fn check_advisories(ws: &Workspace, resolve: &Resolve) -> CargoResult<()> {
+    let config = ws.config();
+    let policy = match try!(AuditPolicy::from_config(config)) {
+        Some(policy) => policy,
+        None => return Ok(()),
+    };
+    let advisories = try!(policy.advisories(config));
+    let affected = affected(resolve, &advisories);
+    if affected.is_empty() {
+        return Ok(())
+    }
+    match policy.action {
+        AuditAction::Warn => {
+            for &(pkg, advisory) in affected.iter() {
+                try!(config.shell().warn(format!("`{}` is affected by {} ({})",
+                                                 pkg, advisory.id,
+                                                 advisory.versions)));
+            }
+            Ok(())
+        }
+        AuditAction::Deny => {
+            let list = affected.iter().map(|&(pkg, advisory)| {
+                format!("  `{}` is affected by {} ({})", pkg, advisory.id,
+                        advisory.versions)
+            }).collect::<Vec<_>>();
+            bail!("locked packages are affected by advisories:\n{}\n\
+                   update them with `cargo update`, or set `audit.action` \
+                   to `warn` in the configuration", list.join("\n"))
+        }
+    }
+}
+
+/// The registry packages of `resolve` affected by any of `advisories`, along
+/// with the first advisory affecting each.
+pub fn affected<'a, 'b>(resolve: &'a Resolve, advisories: &'b [Advisory])
+                        -> Vec<(&'a PackageId, &'b Advisory)> {
+    let mut ret = resolve.iter().filter(|pkg| {
+        pkg.source_id().is_registry()
+    }).filter_map(|pkg| {
+        advisories.iter().find(|a| a.affects(pkg)).map(|a| (pkg, a))
+    }).collect::<Vec<_>>();
+    ret.sort_by(|a, b| a.0.cmp(b.0));
+    ret
+}
+
+/// Loads the advisories listed in the file at `path`, as
+/// `[[advisory]]` tables naming a package and the versions affected.
+pub fn load_advisories(path: &Path, config: &Config) -> CargoResult<Vec<Advisory>> {
+    let contents = try!(paths::read(path));
+    (|| {
+        let table = try!(cargo_toml::parse(&contents, path, config));
+        let mut d = toml::Decoder::new(toml::Value::Table(table));
+        let file: AdvisoryFile = try!(Decodable::decode(&mut d));
+        let mut ret = Vec::new();
+        for (i, advisory) in file.advisory.unwrap_or(Vec::new()).into_iter()
+                                 .enumerate() {
+            let affected = try!(parse_reqs(&advisory.versions, &advisory.package));
+            let id = advisory.id.map(|id| format!("`{}`", id)).unwrap_or_else(|| {
+                format!("advisory #{}", i + 1)
+            });
+            ret.push(Advisory {
+                id: id,
+                versions: format!("versions {}", quote(&advisory.versions)),
+                package: advisory.package,
+                affected: affected,
+                unaffected: Vec::new(),
+            });
+        }
+        Ok(ret)
+    }).chain_error(|| {
+        human(format!("failed to parse advisories at `{}`", path.display()))
+    })
+}
+
+/// Loads the advisories of a local copy of a database in the format of the
+/// RustSec database, which has a directory for each package in `crates`. Each
+/// advisory is a Markdown file starting with a block of TOML, or a plain TOML
+/// file in older copies. Informational and withdrawn advisories are skipped.
+fn load_database(db: &Path, config: &Config) -> CargoResult<Vec<Advisory>> {
+    let crates = db.join("crates");
+    let packages = try!(fs::read_dir(&crates).chain_error(|| {
+        human(format!("failed to read the advisory database at `{}`, which \
+                       should contain a `crates` directory", db.display()))
+    }));
+    let mut files = Vec::new();
+    for package in packages {
+        let package = try!(package);
+        if !package.path().is_dir() {
+            continue
+        }
+        for file in try!(fs::read_dir(package.path())) {
+            let path = try!(file).path();
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("md") | Some("toml") => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+
+    let mut ret = Vec::new();
+    for path in files {
+        let advisory = try!(load_rustsec(&path, config).chain_error(|| {
+            human(format!("failed to parse the advisory at `{}`", path.display()))
+        }));
+        ret.extend(advisory);
+    }
+    Ok(ret)
+}
+
+fn load_rustsec(path: &Path, config: &Config) -> CargoResult<Option<Advisory>> {
+    let contents = try!(paths::read(path));
+    let contents = if path.extension().and_then(|e| e.to_str()) == Some("md") {
+        let mut lines = contents.lines().skip_while(|l| l.trim().is_empty());
+        if lines.next().map(|l| l.trim()) != Some("```toml") {
+            bail!("expected the advisory to start with a ```toml block")
+        }
+        lines.take_while(|l| l.trim() != "```").collect::<Vec<_>>().join("\n")
+    } else {
+        contents
+    };
+    let table = toml::Value::Table(try!(cargo_toml::parse(&contents, path, config)));
+
+    let string = |key: &str| table.lookup(key).and_then(|v| v.as_str());
+    if string("advisory.informational").is_some() ||
+       table.lookup("advisory.withdrawn").is_some() {
+        return Ok(None)
+    }
+    let (id, package) = match (string("advisory.id"), string("advisory.package")) {
+        (Some(id), Some(package)) => (id.to_string(), package.to_string()),
+        _ => bail!("expected `advisory.id` and `advisory.package`"),
+    };
+    let list = |keys: &[&str]| -> CargoResult<Vec<String>> {
+        let value = match keys.iter().filter_map(|k| table.lookup(k)).next() {
+            Some(value) => value,
+            None => return Ok(Vec::new()),
+        };
+        let array = match value.as_slice() {
+            Some(array) => array,
+            None => bail!("expected `{}` to be an array", keys[0]),
+        };
+        array.iter().map(|v| {
+            v.as_str().map(|s| s.to_string()).chain_error(|| {
+                human(format!("expected `{}` to be an array of strings", keys[0]))
+            })
+        }).collect()
+    };
+    let patched = try!(list(&["versions.patched", "advisory.patched_versions"]));
+    let unaffected = try!(list(&["versions.unaffected",
+                                 "advisory.unaffected_versions"]));
+
+    let mut safe = patched.clone();
+    safe.extend(unaffected);
+    let versions = if patched.is_empty() {
+        "no patched versions".to_string()
+    } else {
+        format!("patched in {}", quote(&patched))
+    };
+    Ok(Some(Advisory {
+        id: format!("`{}`", id),
+        unaffected: try!(parse_reqs(&safe, &package)),
+        affected: vec![VersionReq::any()],
+        package: package,
+        versions: versions,
+    }))
+}
+
+fn parse_reqs(reqs: &[String], package: &str) -> CargoResult<Vec<VersionReq>> {
+    reqs.iter().map(|v| {
+        VersionReq::parse(v).chain_error(|| {
+            human(format!("invalid version range `{}` for `{}`", v, package))
+        })
+    }).collect()
+}
+
+fn quote(reqs: &[String]) -> String {
+    reqs.iter().map(|v| format!("`{}`", v)).collect::<Vec<_>>().join(", ")
+}
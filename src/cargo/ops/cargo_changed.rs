@@ -0,0 +1,183 @@
+//! Selecting the members of a workspace affected by the changes made since a
+//! git revision, for `--changed-since`.
+//!
+//! A member is affected if one of its files changed, or if it depends,
+//! directly or through other path dependencies, on a package one of whose
+//! files changed, whether or not that package is a member of the workspace.
+//! Changes to the lockfile or to the configuration of the workspace affect
+//! every member, as do changes to the `[replace]` and `[profile]` sections of
+//! the root manifest.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use git2;
+
+use core::{Package, Workspace};
+use ops;
+use util::{paths, CargoResult, ChainError, Config, human};
+use util::toml as cargo_toml;
+
+/// Selects the packages to build for `--changed-since REV`, which can't be
+/// combined with `--package`. Says so if none of them changed.
+pub fn changed_specs(manifest_path: &Path,
+                     config: &Config,
+                     rev: &str,
+                     packages: &[String]) -> CargoResult<Vec<String>> {
+    if !packages.is_empty() {
+        bail!("`--changed-since` cannot be used with `--package`")
+    }
+    let ws = try!(Workspace::new(manifest_path, config));
+    let changed = try!(changed_since(&ws, rev));
+    if changed.is_empty() {
+        try!(config.shell().status("Unchanged",
+                                   format!("no packages changed since `{}`", rev)));
+    }
+    Ok(changed)
+}
+
+/// Returns the names of the members of `ws` affected by the changes made
+/// since `rev`, including those not committed yet.
+pub fn changed_since(ws: &Workspace, rev: &str) -> CargoResult<Vec<String>> {
+    let files = try!(changed_files(ws.root(), rev).chain_error(|| {
+        human(format!("failed to find the files changed since `{}`", rev))
+    }));
+
+    let packages = try!(path_packages(ws));
+
+    // The directory of each package, most deeply nested first so that files
+    // are attributed to the innermost package containing them.
+    let mut roots = packages.iter().map(|pkg| {
+        (pkg.root().to_path_buf(), pkg.name().to_string())
+    }).collect::<Vec<_>>();
+    roots.sort_by(|a, b| b.0.components().count().cmp(&a.0.components().count()));
+
+    let root_manifest = ws.root().join("Cargo.toml");
+    let mut everything = vec![ws.root().join("Cargo.lock"),
+                              ws.root().join(".cargo")];
+    let is_virtual = !ws.members().any(|pkg| pkg.root() == ws.root());
+    if is_virtual {
+        // A virtual manifest only configures the workspace.
+        everything.push(root_manifest.clone());
+    }
+    let target_dir = ws.target_dir().into_path_unlocked();
+    let mut changed = BTreeSet::new();
+    for file in files.iter().filter(|f| !f.starts_with(&target_dir)) {
+        if everything.iter().any(|p| file.starts_with(p)) ||
+           (!is_virtual && *file == root_manifest &&
+            try!(workspace_sections_changed(ws, rev))) {
+            debug!("{} affects every member", file.display());
+            return Ok(ws.members().map(|pkg| pkg.name().to_string()).collect())
+        }
+        if let Some(&(_, ref name)) = roots.iter().find(|r| file.starts_with(&r.0)) {
+            changed.insert(name.clone());
+        }
+    }
+
+    // Packages are affected by changes to the path packages they depend on.
+    let mut dependents = HashMap::new();
+    for pkg in packages.iter() {
+        for dep in pkg.dependencies().iter().filter(|d| d.source_id().is_path()) {
+            dependents.entry(dep.name().to_string()).or_insert(Vec::new())
+                      .push(pkg.name().to_string());
+        }
+    }
+    let mut affected = BTreeSet::new();
+    let mut stack = changed.into_iter().collect::<Vec<_>>();
+    while let Some(name) = stack.pop() {
+        if affected.insert(name.clone()) {
+            if let Some(users) = dependents.get(&name) {
+                stack.extend(users.iter().cloned());
+            }
+        }
+    }
+    let members = ws.members().map(|pkg| pkg.name()).collect::<HashSet<_>>();
+    Ok(affected.into_iter().filter(|name| members.contains(&name[..])).collect())
+}
+
+/// The members of `ws` along with every package they depend on by path,
+/// directly or through other path dependencies.
+fn path_packages(ws: &Workspace) -> CargoResult<Vec<Package>> {
+    let mut packages = ws.members().cloned().collect::<Vec<_>>();
+    let mut seen = packages.iter().map(|pkg| {
+        pkg.root().to_path_buf()
+    }).collect::<HashSet<_>>();
+    let mut i = 0;
+    while i < packages.len() {
+        let deps = packages[i].dependencies().iter().filter(|d| {
+            d.source_id().is_path()
+        }).cloned().collect::<Vec<_>>();
+        for dep in deps {
+            let root = match dep.source_id().url().to_file_path() {
+                Ok(root) => root,
+                Err(..) => bail!("invalid path for dependency `{}`: {}",
+                                 dep.name(), dep.source_id().url()),
+            };
+            if !seen.insert(root.clone()) {
+                continue
+            }
+            let manifest = root.join("Cargo.toml");
+            let (pkg, _) = try!(ops::read_package(&manifest, dep.source_id(),
+                                                  ws.config()));
+            packages.push(pkg);
+        }
+        i += 1;
+    }
+    Ok(packages)
+}
+
+/// Whether the sections of the root manifest which apply to every member of
+/// the workspace differ from those at `rev`.
+fn workspace_sections_changed(ws: &Workspace, rev: &str) -> CargoResult<bool> {
+    let manifest = ws.root().join("Cargo.toml");
+    let old = match try!(contents_at(&manifest, rev)) {
+        Some(old) => try!(cargo_toml::parse(&old, &manifest, ws.config())),
+        None => return Ok(true),
+    };
+    let new = try!(paths::read(&manifest));
+    let new = try!(cargo_toml::parse(&new, &manifest, ws.config()));
+    Ok(["replace", "profile"].iter().any(|key| old.get(*key) != new.get(*key)))
+}
+
+/// The contents of `path` in the tree of `rev`, if it existed there.
+fn contents_at(path: &Path, rev: &str) -> CargoResult<Option<String>> {
+    let repo = try!(git2::Repository::discover(path.parent().unwrap()));
+    let relative = match repo.workdir().and_then(|dir| path.strip_prefix(dir).ok()) {
+        Some(relative) => relative.to_path_buf(),
+        None => return Ok(None),
+    };
+    let commit = try!(repo.revparse_single(&format!("{}^{{commit}}", rev)));
+    let tree = try!(try!(repo.find_commit(commit.id())).tree());
+    let entry = match tree.get_path(&relative) {
+        Ok(entry) => entry,
+        Err(..) => return Ok(None),
+    };
+    let blob = try!(repo.find_blob(entry.id()));
+    Ok(Some(String::from_utf8_lossy(blob.content()).into_owned()))
+}
+
+/// The files which differ between the tree of `rev` and the working
+/// directory, untracked ones included, as absolute paths.
+fn changed_files(path: &Path, rev: &str) -> CargoResult<Vec<PathBuf>> {
+    let repo = try!(git2::Repository::discover(path));
+    let workdir = match repo.workdir() {
+        Some(dir) => dir.to_path_buf(),
+        None => bail!("`{}` is in a bare git repository", path.display()),
+    };
+    let commit = try!(repo.revparse_single(&format!("{}^{{commit}}", rev)));
+    let tree = try!(try!(repo.find_commit(commit.id())).tree());
+
+    let mut opts = git2::DiffOptions::new();
+    opts.include_untracked(true).recurse_untracked_dirs(true);
+    let diff = try!(repo.diff_tree_to_workdir_with_index(Some(&tree),
+                                                         Some(&mut opts)));
+    let mut files = Vec::new();
+    for delta in diff.deltas() {
+        for file in [delta.old_file(), delta.new_file()].iter() {
+            if let Some(path) = file.path() {
+                files.push(workdir.join(path));
+            }
+        }
+    }
+    Ok(files)
+}
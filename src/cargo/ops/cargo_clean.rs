@@ -23,9 +23,24 @@ pub fn clean(ws: &Workspace, opts: &CleanOptions) -> CargoResult<()> {
     //
     // Note that we don't bother grabbing a lock here as we're just going to
     // blow it all away anyway.
+    let package_target_dirs = try!(ops::scrape_package_target_dirs(opts.config));
     if opts.spec.is_empty() {
         let target_dir = target_dir.into_path_unlocked();
-        return rm_rf(&target_dir);
+        for dir in package_target_dirs.values() {
+            try!(rm_rf(dir));
+        }
+        // Pinned artifacts are kept until they're unpinned.
+        let pinned = ops::pinned_dir(ws);
+        if fs::metadata(&pinned).is_err() {
+            return rm_rf(&target_dir);
+        }
+        for entry in try!(fs::read_dir(&target_dir)) {
+            let path = try!(entry).path();
+            if path != pinned {
+                try!(rm_rf(&path));
+            }
+        }
+        return Ok(())
     }
 
     let mut registry = try!(PackageRegistry::new(opts.config));
@@ -34,11 +49,21 @@ pub fn clean(ws: &Workspace, opts: &CleanOptions) -> CargoResult<()> {
 
     let profiles = try!(ws.current()).manifest().profiles();
     let host_triple = try!(opts.config.rustc()).host.clone();
+    // The configuration of the platforms is needed to find the outputs of
+    // their transforms.
+    let host = try!(ops::scrape_target_config(opts.config, &host_triple));
+    let target = match opts.target {
+        Some(triple) => try!(ops::scrape_target_config(opts.config, triple)),
+        None => host.clone(),
+    };
     let mut cx = try!(Context::new(ws, &resolve, &packages, opts.config,
                                    BuildConfig {
                                        host_triple: host_triple,
+                                       host: host,
                                        requested_target: opts.target.map(|s| s.to_owned()),
+                                       target: target,
                                        release: opts.release,
+                                       package_target_dirs: package_target_dirs,
                                        ..BuildConfig::default()
                                    },
                                    profiles));
@@ -81,6 +106,9 @@ pub fn clean(ws: &Workspace, opts: &CleanOptions) -> CargoResult<()> {
         for (filename, _) in try!(cx.target_filenames(&unit)) {
             try!(rm_rf(&root.join(&filename)));
         }
+        for (_, output) in cx.output_transforms(&unit) {
+            try!(rm_rf(&root.join(&output)));
+        }
     }
 
     Ok(())
@@ -22,17 +22,17 @@
 //!       previously compiled dependency
 //!
 
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use core::registry::PackageRegistry;
 use core::{Source, SourceId, PackageSet, Package, Target};
-use core::{Profile, TargetKind, Profiles, Workspace};
-use core::resolver::{Method, Resolve};
+use core::{Profile, TargetKind, Profiles, Workspace, Runner};
+use core::resolver::{Method, Resolve, VersionOrder};
 use ops::{self, BuildOutput, ExecEngine};
 use sources::PathSource;
-use util::config::Config;
+use util::config::{Config, ConfigValue, Definition};
 use util::{CargoResult, profile, human, ChainError};
 
 /// Contains information about how a package should be compiled.
@@ -64,6 +64,28 @@ pub struct CompileOptions<'a> {
     /// The specified target will be compiled with all the available arguments,
     /// note that this only accounts for the *final* invocation of rustc
     pub target_rustc_args: Option<&'a [String]>,
+    /// File to write a Ninja build file to instead of building anything
+    pub emit_ninja: Option<&'a Path>,
+    /// Format in which messages about the build are emitted
+    pub message_format: MessageFormat,
+    /// Directory to write a summary of rustc's self-profile of the selected
+    /// packages to
+    pub self_profile: Option<&'a Path>,
+    /// Whether to set aside the artifacts of a previous compiler instead of
+    /// overwriting them when the compiler changes
+    pub preserve_old_artifacts: bool,
+    /// Format of the software bill of materials to write next to the
+    /// artifacts, if any
+    pub sbom: Option<ops::SbomFormat>,
+    /// The shard of the selected targets to build, which are otherwise split
+    /// evenly between the shards
+    pub shard: Option<ops::TestShard>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, RustcDecodable)]
+pub enum MessageFormat {
+    Human,
+    Json,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -130,7 +152,8 @@ pub fn resolve_dependencies<'a>(ws: &Workspace<'a>,
 
     let resolved_with_overrides =
             try!(ops::resolve_with_previous(&mut registry, ws,
-                                            method, Some(&resolve), None));
+                                            method, VersionOrder::Newest,
+                                            Some(&resolve), None));
 
     let packages = ops::get_resolved_packages(&resolved_with_overrides,
                                               registry);
@@ -143,14 +166,15 @@ pub fn compile_ws<'a>(ws: &Workspace<'a>,
                       options: &CompileOptions<'a>)
                       -> CargoResult<ops::Compilation<'a>> {
     let root_package = try!(ws.current());
-    let CompileOptions { config, jobs, target, spec, features,
+    let CompileOptions { config, jobs, spec, features,
                          all_features, no_default_features,
                          release, mode,
-                         ref filter, ref exec_engine,
+                         ref filter,
                          ref target_rustdoc_args,
-                         ref target_rustc_args } = *options;
+                         ref target_rustc_args,
+                         self_profile,
+                         shard, .. } = *options;
 
-    let target = target.map(|s| s.to_string());
     let features = features.iter().flat_map(|s| {
         s.split(' ')
     }).map(|s| s.to_string()).collect::<Vec<String>>();
@@ -158,6 +182,10 @@ pub fn compile_ws<'a>(ws: &Workspace<'a>,
     if jobs == Some(0) {
         bail!("jobs must be at least 1")
     }
+    if self_profile.is_some() && !try!(config.rustc()).is_nightly() {
+        bail!("`--self-profile` requires a nightly compiler, as it passes \
+               `-Z time-passes` to rustc")
+    }
 
     let profiles = root_package.manifest().profiles();
     if spec.len() == 0 {
@@ -167,6 +195,9 @@ pub fn compile_ws<'a>(ws: &Workspace<'a>,
     let (packages, resolve_with_overrides) = {
         try!(resolve_dependencies(ws, source, features, all_features, no_default_features))
     };
+    try!(ops::check_staleness(ws, &resolve_with_overrides));
+    try!(ops::check_advisories(ws, &resolve_with_overrides));
+    try!(ops::check_vetted(ws, &resolve_with_overrides));
 
     let mut pkgids = Vec::new();
     if spec.len() > 0 {
@@ -232,16 +263,27 @@ pub fn compile_ws<'a>(ws: &Workspace<'a>,
         }
     }
 
-    let mut ret = {
-        let _p = profile::start("compiling");
-        let mut build_config = try!(scrape_build_config(config, jobs, target));
-        build_config.exec_engine = exec_engine.clone();
-        build_config.release = release;
-        build_config.test = mode == CompileMode::Test;
-        if let CompileMode::Doc { deps } = mode {
-            build_config.doc_all = deps;
+    if let Some(shard) = shard {
+        let mut keys = package_targets.iter().flat_map(|&(pkg, ref targets)| {
+            targets.iter().map(move |&(target, profile)| {
+                shard_key(pkg, target, profile)
+            })
+        }).collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        let keys = keys.into_iter().enumerate().filter(|&(i, _)| {
+            shard.contains(i)
+        }).map(|(_, key)| key).collect::<HashSet<_>>();
+        for &mut (pkg, ref mut targets) in package_targets.iter_mut() {
+            targets.retain(|&(target, profile)| {
+                keys.contains(&shard_key(pkg, target, profile))
+            });
         }
+    }
 
+    let mut ret = {
+        let _p = profile::start("compiling");
+        let build_config = try!(build_config_for(options));
         try!(ops::compile_targets(ws,
                                   &package_targets,
                                   &packages,
@@ -251,11 +293,118 @@ pub fn compile_ws<'a>(ws: &Workspace<'a>,
                                   profiles))
     };
 
-    ret.to_doc_test = to_builds.iter().map(|&p| p.clone()).collect();
+    ret.to_doc_test = to_builds.iter().filter(|&&p| {
+        // The library built without the test profile is there for the
+        // doctests, which are then run by the same shard.
+        shard.is_none() || package_targets.iter().any(|&(pkg, ref targets)| {
+            pkg.package_id() == p.package_id() &&
+                targets.iter().any(|&(t, profile)| t.is_lib() && !profile.test)
+        })
+    }).map(|&p| p.clone()).collect();
 
     Ok(ret)
 }
 
+/// Compiles the current package of each of `wss`, taken from the matching
+/// source of `sources`, in the target directory of the first workspace.
+///
+/// Every package is resolved on its own, as `compile_ws` would. Those whose
+/// resolves agree on the packages they have in common, and which have the
+/// same profiles, are then built together in one run, so that the units they
+/// share are built once and the others in parallel. The rest are built in
+/// runs of their own, one after the other.
+pub fn compile_many<'a>(wss: &[Workspace<'a>],
+                        sources: Vec<Box<Source + 'a>>,
+                        options: &CompileOptions<'a>)
+                        -> CargoResult<Vec<ops::Compilation<'a>>> {
+    let CompileOptions { config, jobs, features,
+                         all_features, no_default_features,
+                         release, mode, ref filter, .. } = *options;
+
+    let features = features.iter().flat_map(|s| {
+        s.split(' ')
+    }).map(|s| s.to_string()).collect::<Vec<String>>();
+
+    if jobs == Some(0) {
+        bail!("jobs must be at least 1")
+    }
+
+    let mut groups: Vec<(&Profiles, Vec<&Workspace>, PackageSet, Resolve)> = Vec::new();
+    for (ws, source) in wss.iter().zip(sources) {
+        let (packages, resolve) = try!(resolve_dependencies(ws, Some(source),
+                                                            features.clone(),
+                                                            all_features,
+                                                            no_default_features));
+        try!(ops::check_staleness(ws, &resolve));
+        try!(ops::check_advisories(ws, &resolve));
+        try!(ops::check_vetted(ws, &resolve));
+
+        let profiles = try!(ws.current()).manifest().profiles();
+        let group = groups.iter().position(|&(p, _, _, ref r)| {
+            p == profiles && r.agrees_with(&resolve)
+        });
+        match group {
+            Some(i) => {
+                let (_, ref mut members, ref mut set, ref mut merged) = groups[i];
+                members.push(ws);
+                set.extend(packages);
+                merged.extend(resolve);
+            }
+            None => groups.push((profiles, vec![ws], packages, resolve)),
+        }
+    }
+
+    let mut ret = Vec::new();
+    for (profiles, members, packages, resolve) in groups {
+        let mut build_config = try!(build_config_for(options));
+        let mut package_targets = Vec::new();
+        for ws in members.iter() {
+            let pkg = try!(packages.get(try!(ws.current()).package_id()));
+            let targets = try!(generate_targets(pkg, profiles, mode, filter,
+                                                release));
+            package_targets.push((pkg, targets));
+            build_config.primary_packages.push(pkg.package_id().clone());
+        }
+
+        let _p = profile::start("compiling");
+        ret.push(try!(ops::compile_targets(members[0],
+                                           &package_targets,
+                                           &packages,
+                                           &resolve,
+                                           config,
+                                           build_config,
+                                           profiles)));
+    }
+    Ok(ret)
+}
+
+/// The configuration of the build `options` ask for.
+fn build_config_for(options: &CompileOptions) -> CargoResult<ops::BuildConfig> {
+    let target = options.target.map(|s| s.to_string());
+    let mut build_config = try!(scrape_build_config(options.config,
+                                                    options.jobs,
+                                                    target));
+    build_config.exec_engine = options.exec_engine.clone();
+    build_config.release = options.release;
+    build_config.test = options.mode == CompileMode::Test;
+    build_config.json_messages = options.message_format == MessageFormat::Json;
+    build_config.emit_ninja = options.emit_ninja.map(|p| p.to_path_buf());
+    build_config.self_profile = options.self_profile.map(|p| p.to_path_buf());
+    build_config.preserve_old_artifacts = options.preserve_old_artifacts;
+    build_config.sbom = options.sbom;
+    if let CompileMode::Doc { deps } = options.mode {
+        build_config.doc_all = deps;
+    }
+    Ok(build_config)
+}
+
+/// Identifies a target of a package built with a profile, the same way on
+/// every machine sharding the build.
+fn shard_key(pkg: &Package, target: &Target, profile: &Profile) -> String {
+    format!("{} {:?} {} {}", pkg.package_id(), target.kind(), target.name(),
+            profile.test)
+}
+
 impl<'a> CompileFilter<'a> {
     pub fn new(lib_only: bool,
                bins: &'a [String],
@@ -430,6 +579,10 @@ fn add_overrides<'a>(registry: &mut PackageRegistry<'a>,
 /// * target.$target.ar
 /// * target.$target.linker
 /// * target.$target.libfoo.metadata
+/// * build.sandbox
+/// * build.sandbox-allow
+/// * build.script-cache
+/// * build.package-target-dir
 fn scrape_build_config(config: &Config,
                        jobs: Option<u32>,
                        target: Option<String>)
@@ -455,6 +608,9 @@ fn scrape_build_config(config: &Config,
         host_triple: try!(config.rustc()).host.clone(),
         requested_target: target.clone(),
         jobs: jobs,
+        sandbox: try!(ops::SandboxConfig::from_config(config)),
+        script_cache: try!(config.get_path("build.script-cache")).map(|v| v.val),
+        package_target_dirs: try!(scrape_package_target_dirs(config)),
         ..Default::default()
     };
     base.host = try!(scrape_target_config(config, &base.host_triple));
@@ -465,22 +621,54 @@ fn scrape_build_config(config: &Config,
     Ok(base)
 }
 
-fn scrape_target_config(config: &Config, triple: &str)
-                        -> CargoResult<ops::TargetConfig> {
+/// Reads the `[build.package-target-dir]` table, which maps the names of
+/// packages to directories their artifacts are placed in instead of the
+/// target directory.
+pub fn scrape_package_target_dirs(config: &Config)
+                                  -> CargoResult<HashMap<String, PathBuf>> {
+    let table = match try!(config.get_table("build.package-target-dir")) {
+        Some(table) => table.val,
+        None => return Ok(HashMap::new()),
+    };
+    let mut ret = HashMap::new();
+    for name in table.keys() {
+        let key = format!("build.package-target-dir.{}", name);
+        if let Some(dir) = try!(config.get_path(&key)) {
+            ret.insert(name.clone(), config.cwd().join(dir.val));
+        }
+    }
+    Ok(ret)
+}
+
+/// Reads the configuration of `[target.<triple>]` for the given triple.
+pub fn scrape_target_config(config: &Config, triple: &str)
+                            -> CargoResult<ops::TargetConfig> {
 
     let key = format!("target.{}", triple);
     let mut ret = ops::TargetConfig {
         ar: try!(config.get_path(&format!("{}.ar", key))).map(|v| v.val),
         linker: try!(config.get_path(&format!("{}.linker", key))).map(|v| v.val),
+        pkg_config: try!(config.get_path(&format!("{}.pkg-config", key)))
+                        .map(|v| v.val),
+        sysroot: try!(config.get_path(&format!("{}.sysroot", key))).map(|v| v.val),
         overrides: HashMap::new(),
+        transforms: try!(scrape_transforms(config, &key)),
+        runner: None,
     };
     let table = match try!(config.get_table(&key)) {
         Some(table) => table.val,
         None => return Ok(ret),
     };
     for (lib_name, value) in table {
-        if lib_name == "ar" || lib_name == "linker" || lib_name == "rustflags" {
-            continue
+        match &lib_name[..] {
+            "ar" | "linker" | "rustflags" | "pkg-config" | "sysroot" |
+            "transform" => continue,
+            "runner" => {
+                let key = format!("{}.runner", key);
+                ret.runner = Some(try!(scrape_runner(config, &key, &value)));
+                continue
+            }
+            _ => {}
         }
 
         let mut output = BuildOutput {
@@ -490,6 +678,9 @@ fn scrape_target_config(config: &Config, triple: &str)
             metadata: Vec::new(),
             rerun_if_changed: Vec::new(),
             warnings: Vec::new(),
+            declared_outputs: Vec::new(),
+            tracked_inputs: Vec::new(),
+            diagnostics: Vec::new(),
         };
         for (k, value) in try!(value.table(&lib_name)).0 {
             let key = format!("{}.{}", key, k);
@@ -530,3 +721,114 @@ fn scrape_target_config(config: &Config, triple: &str)
 
     Ok(ret)
 }
+
+/// Parses the `runner` of a target, either a command line, as a string split on
+/// whitespace or as an array, or a table with the `program`, its `args`, `env`
+/// and `cwd`, or naming one of Cargo's `preset` runners. Like other tools, a
+/// program with a path is relative to the directory containing `.cargo`, and so
+/// is the working directory.
+fn scrape_runner(config: &Config, key: &str, value: &ConfigValue)
+                 -> CargoResult<ops::TargetRunner> {
+    let definition = Definition::Path(value.definition_path().to_path_buf());
+    let root = definition.root(config);
+    let mut runner = Runner {
+        program: String::new(),
+        args: Vec::new(),
+        env: Vec::new(),
+        cwd: None,
+    };
+    let mut command: Vec<String> = match *value {
+        ConfigValue::String(ref s, _) => {
+            s.split_whitespace().map(|s| s.to_string()).collect()
+        }
+        ConfigValue::List(ref list, _) => {
+            list.iter().map(|v| v.0.clone()).collect()
+        }
+        ConfigValue::Table(ref table, _) if table.contains_key("preset") => {
+            let preset = try!(ops::parse_preset(key, table, root));
+            return Ok(ops::TargetRunner::Preset(preset))
+        }
+        ConfigValue::Table(ref table, _) => {
+            let program = match table.get("program") {
+                Some(program) => try!(program.string(&format!("{}.program", key))).0,
+                None => bail!("`{}.program` must be specified", key),
+            };
+            let mut command = vec![program.to_string()];
+            if let Some(args) = table.get("args") {
+                let args = try!(args.list(&format!("{}.args", key)));
+                command.extend(args.iter().map(|v| v.0.clone()));
+            }
+            if let Some(env) = table.get("env") {
+                let (env, _) = try!(env.table(&format!("{}.env", key)));
+                let mut names = env.keys().cloned().collect::<Vec<_>>();
+                names.sort();
+                for name in names {
+                    let key = format!("{}.env.{}", key, name);
+                    let val = try!(env[&name].string(&key)).0.to_string();
+                    runner.env.push((name, val));
+                }
+            }
+            if let Some(cwd) = table.get("cwd") {
+                let cwd = try!(cwd.string(&format!("{}.cwd", key))).0;
+                runner.cwd = Some(root.join(cwd));
+            }
+            command
+        }
+        _ => bail!("expected a string, an array or a table for `{}`, but found \
+                    a {} in {}", key, value.desc(),
+                   value.definition_path().display()),
+    };
+    if command.is_empty() || command[0].is_empty() {
+        bail!("the program of `{}` must not be empty", key)
+    }
+    runner.program = command.remove(0);
+    if runner.program.contains('/') ||
+       (cfg!(windows) && runner.program.contains('\\')) {
+        runner.program = root.join(&runner.program).display().to_string();
+    }
+    runner.args = command;
+    Ok(ops::TargetRunner::Command(runner))
+}
+
+fn scrape_transforms(config: &Config, key: &str)
+                     -> CargoResult<Vec<ops::OutputTransform>> {
+    let key = format!("{}.transform", key);
+    let table = match try!(config.get_table(&key)) {
+        Some(table) => table.val,
+        None => return Ok(Vec::new()),
+    };
+    let mut names = table.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+    let mut ret = Vec::new();
+    for name in names {
+        let key = format!("{}.{}", key, name);
+        let command = match try!(config.get_list(&format!("{}.command", key))) {
+            Some(list) => list.val.into_iter().map(|v| v.0).collect::<Vec<_>>(),
+            None => bail!("`{}.command` must be specified", key),
+        };
+        if command.is_empty() {
+            bail!("`{}.command` must not be empty", key)
+        }
+        let suffix = match try!(config.get_string(&format!("{}.suffix", key))) {
+            Some(suffix) => suffix.val,
+            None => bail!("`{}.suffix` must be specified", key),
+        };
+        let kinds = match try!(config.get_list(&format!("{}.kinds", key))) {
+            Some(list) => list.val.into_iter().map(|v| v.0).collect::<Vec<_>>(),
+            None => vec!["bin".to_string()],
+        };
+        for kind in kinds.iter() {
+            match &kind[..] {
+                "bin" | "example" | "test" | "bench" => {}
+                _ => bail!("unknown kind of target `{}` in `{}.kinds`", kind, key),
+            }
+        }
+        ret.push(ops::OutputTransform {
+            name: name,
+            command: command,
+            suffix: suffix,
+            kinds: kinds,
+        });
+    }
+    Ok(ret)
+}
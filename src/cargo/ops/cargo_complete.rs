@@ -0,0 +1,198 @@
+//! Shell completions which ask cargo for the values they complete.
+//!
+//! The scripts emitted by `cargo complete <shell>` complete the flags of each
+//! command from its `--help`, and the values of flags such as `--bin` or
+//! `--features` by running `cargo complete --values KIND`. Those are read
+//! from the cached output of `cargo metadata --no-deps`, so they reflect the
+//! workspace as it is without resolving it on every keystroke.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use rustc_serialize::json::Json;
+
+use ops::{self, OutputMetadataOptions};
+use util::{CargoResult, Config, internal};
+
+/// The kinds of values `cargo complete --values` knows about.
+pub const COMPLETION_KINDS: &'static [&'static str] = &[
+    "packages", "bins", "examples", "tests", "benches", "features", "profiles",
+];
+
+/// The completion script for `shell`.
+pub fn completion_script(shell: &str) -> CargoResult<&'static str> {
+    match shell {
+        "bash" => Ok(BASH),
+        "zsh" => Ok(ZSH),
+        _ => bail!("completions can't be generated for `{}`, only for `bash` \
+                    and `zsh`", shell),
+    }
+}
+
+/// The values of the given kind found in the workspace of `manifest`,
+/// sorted and without duplicates.
+pub fn completion_values(manifest: &Path,
+                         config: &Config,
+                         kind: &str) -> CargoResult<Vec<String>> {
+    let target_kind = match kind {
+        "bins" => Some("bin"),
+        "examples" => Some("example"),
+        "tests" => Some("test"),
+        "benches" => Some("bench"),
+        "packages" | "features" => None,
+        // The profiles are fixed rather than declared by the manifest.
+        "profiles" => return Ok(vec!["dev".to_string(), "release".to_string()]),
+        _ => bail!("unknown kind of value `{}`, expected one of `{}`", kind,
+                   COMPLETION_KINDS.join("`, `")),
+    };
+
+    let output = try!(ops::output_metadata_cached(manifest, config,
+                                                  &OutputMetadataOptions {
+        features: Vec::new(),
+        no_default_features: false,
+        all_features: false,
+        no_deps: true,
+        version: 1,
+        platforms: Vec::new(),
+    }));
+    let metadata = try!(Json::from_str(&output).map_err(|e| {
+        internal(format!("metadata isn't valid JSON: {}", e))
+    }));
+    let packages = metadata.find("packages").and_then(|p| p.as_array());
+
+    let mut values = BTreeSet::new();
+    for pkg in packages.into_iter().flat_map(|p| p.iter()) {
+        match kind {
+            "packages" => {
+                values.extend(pkg.find("name").and_then(|n| n.as_string()));
+            }
+            "features" => {
+                let features = pkg.find("features").and_then(|f| f.as_object());
+                values.extend(features.into_iter().flat_map(|f| f.keys())
+                                      .map(|s| &s[..]));
+            }
+            _ => {
+                let targets = pkg.find("targets").and_then(|t| t.as_array());
+                for target in targets.into_iter().flat_map(|t| t.iter()) {
+                    let kinds = target.find("kind").and_then(|k| k.as_array());
+                    if kinds.into_iter().flat_map(|k| k.iter())
+                            .any(|k| k.as_string() == target_kind) {
+                        values.extend(target.find("name")
+                                            .and_then(|n| n.as_string()));
+                    }
+                }
+            }
+        }
+    }
+    Ok(values.into_iter().map(|s| s.to_string()).collect())
+}
+
+const BASH: &'static str = r#"# bash completion for cargo, generated by `cargo complete bash`.
+# Load it with `source <(cargo complete bash)`.
+
+_cargo_complete_values()
+{
+	local i manifest=()
+	for (( i=2; i < COMP_CWORD; i++ )); do
+		if [[ ${COMP_WORDS[i]} == --manifest-path ]]; then
+			manifest=( --manifest-path "${COMP_WORDS[i+1]}" )
+		fi
+	done
+	cargo complete --values "$1" "${manifest[@]}" 2>/dev/null
+}
+
+_cargo_complete()
+{
+	local cur=${COMP_WORDS[COMP_CWORD]}
+	local prev=${COMP_WORDS[COMP_CWORD-1]}
+	local cmd=${COMP_WORDS[1]}
+	local kind
+
+	COMPREPLY=()
+	if [[ $COMP_CWORD -eq 1 ]]; then
+		COMPREPLY=( $( compgen -W "$(cargo --list 2>/dev/null | tail -n +2)" -- "$cur" ) )
+		return 0
+	fi
+
+	case "$prev" in
+		-p|--package|--exclude) kind=packages ;;
+		--bin) kind=bins ;;
+		--example) kind=examples ;;
+		--test) kind=tests ;;
+		--bench) kind=benches ;;
+		--features) kind=features ;;
+		--profile) kind=profiles ;;
+		--manifest-path)
+			COMPREPLY=( $( compgen -f -- "$cur" ) )
+			return 0
+			;;
+	esac
+
+	if [[ -n $kind ]]; then
+		COMPREPLY=( $( compgen -W "$(_cargo_complete_values $kind)" -- "$cur" ) )
+	elif [[ "$cur" == -* ]]; then
+		local flags=$(cargo "$cmd" --help 2>/dev/null | grep '^ *-' |
+		              sed 's/^ *//; s/  .*//' |
+		              grep -o -- '--\?[a-zA-Z][-a-zA-Z]*')
+		COMPREPLY=( $( compgen -W "$flags" -- "$cur" ) )
+	else
+		COMPREPLY=( $( compgen -f -- "$cur" ) )
+	fi
+	return 0
+}
+
+complete -F _cargo_complete cargo
+"#;
+
+const ZSH: &'static str = r#"#compdef cargo
+# zsh completion for cargo, generated by `cargo complete zsh`.
+# Load it with `source <(cargo complete zsh)`, or save it as `_cargo` in a
+# directory of $fpath.
+
+_cargo_complete_values() {
+    local -a manifest values
+    local i=${words[(i)--manifest-path]}
+    if (( i < CURRENT - 1 )); then
+        manifest=(--manifest-path ${words[i+1]})
+    fi
+    values=(${=$(cargo complete --values $1 $manifest 2>/dev/null)})
+    compadd -a values
+}
+
+_cargo() {
+    if (( CURRENT == 2 )); then
+        local -a commands
+        commands=(${=$(cargo --list 2>/dev/null | tail -n +2)})
+        compadd -a commands
+        return
+    fi
+
+    case ${words[CURRENT-1]} in
+        -p|--package|--exclude) _cargo_complete_values packages ;;
+        --bin) _cargo_complete_values bins ;;
+        --example) _cargo_complete_values examples ;;
+        --test) _cargo_complete_values tests ;;
+        --bench) _cargo_complete_values benches ;;
+        --features) _cargo_complete_values features ;;
+        --profile) _cargo_complete_values profiles ;;
+        --manifest-path) _files -g '*.toml' ;;
+        *)
+            if [[ ${words[CURRENT]} == -* ]]; then
+                local -a flags
+                flags=(${=$(cargo ${words[2]} --help 2>/dev/null |
+                            grep '^ *-' | sed 's/^ *//; s/  .*//' |
+                            grep -o -- '--\?[a-zA-Z][-a-zA-Z]*')})
+                compadd -a flags
+            else
+                _files
+            fi
+            ;;
+    esac
+}
+
+if [[ $funcstack[1] == _cargo ]]; then
+    _cargo "$@"
+else
+    compdef _cargo cargo
+fi
+"#;
@@ -0,0 +1,419 @@
+//! Obtaining the tokens of registries from credential providers.
+//!
+//! The `registry.credential-provider` key picks where the token used to talk
+//! to a registry comes from. By default it's `cargo:token`, the `registry.token`
+//! key of the configuration written by `cargo login`. With `cargo:keyring` it's
+//! kept in the keyring of the system instead, out of the configuration files.
+//! With `cargo:secret-key` there's no long-lived token at all: each request is
+//! authorized by a token made for it and signed with the private key
+//! `registry.secret-key`, whose public key was registered with the registry.
+//! Any other value is a command, given either as a string or as a list of the
+//! program and its arguments, which is run every time a token is needed, saved
+//! or revoked.
+//!
+//! The command is sent a single line of JSON on its stdin describing what it
+//! should do, and answers with a single line of JSON on its stdout. Its stderr
+//! is inherited so it can interact with the user:
+//!
+//! ```text
+//! -> {"v":1,"action":"get","registry":"https://github.com/rust-lang/crates.io-index","token":null}
+//! <- {"token":"...","expires":1700000000}
+//!
+//! -> {"v":1,"action":"login","registry":"...","token":"..."}
+//! <- {}
+//!
+//! -> {"v":1,"action":"logout","registry":"...","token":null}
+//! <- {"error":"no token is stored for this registry"}
+//! ```
+//!
+//! A token which comes with an expiry time, in seconds since the epoch, is
+//! reused until then by the same invocation of Cargo, and one without is
+//! reused until it exits.
+//!
+//! The signed tokens of `cargo:secret-key` are made of three parts in
+//! unpadded URL-safe base64, following `cargo.v1.`: a JSON payload of what the
+//! request is allowed to do, a JSON footer with the ID of the key, and the
+//! RSA signature of the SHA-256 digest of everything before it:
+//!
+//! ```text
+//! cargo.v1.<payload>.<footer>.<signature>
+//!
+//! payload: {"iat":1700000000,"url":"...","mutation":"publish","name":"foo","vers":"0.1.0","cksum":"..."}
+//! footer:  {"kid":"..."}
+//! ```
+//!
+//! `iat` is when the token was made, in seconds since the epoch, and a token
+//! for a request which doesn't change anything has no `mutation`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rustc_serialize::base64::{ToBase64, URL_SAFE};
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json::{self, Json};
+
+use core::SourceId;
+use util::{self, human, keyring, paths, CargoResult, ChainError, Config, Sha256};
+use util::signature;
+use util::config::{self, ConfigValue, Location};
+
+/// The service under which tokens are kept in the keyring, with the URL of
+/// their registry as the account.
+const KEYRING_SERVICE: &'static str = "cargo-registry";
+
+/// What a token is going to be used for.
+pub enum Operation<'a> {
+    /// Requests which don't change anything.
+    Read,
+    /// Publishing the version `vers` of `name`, whose `.crate` file has the
+    /// SHA-256 digest `cksum`.
+    Publish { name: &'a str, vers: &'a str, cksum: &'a str },
+    Yank { name: &'a str, vers: &'a str },
+    Unyank { name: &'a str, vers: &'a str },
+    Owners { name: &'a str },
+}
+
+/// Where the tokens of registries come from.
+pub enum CredentialProvider {
+    /// The `registry.token` key of the configuration.
+    Token,
+    /// The keyring of the system.
+    Keyring,
+    /// Tokens made for each request, signed with the private key at the path
+    /// with the ID.
+    SecretKey(PathBuf, String),
+    /// A command speaking the protocol described above.
+    Process(Vec<String>),
+}
+
+#[derive(RustcEncodable)]
+struct Request<'a> {
+    v: u32,
+    action: &'a str,
+    registry: &'a str,
+    token: Option<&'a str>,
+}
+
+#[derive(RustcDecodable)]
+struct Response {
+    token: Option<String>,
+    expires: Option<u64>,
+    error: Option<String>,
+}
+
+impl CredentialProvider {
+    pub fn from_config(config: &Config) -> CargoResult<CredentialProvider> {
+        let key = "registry.credential-provider";
+        let command = match config.get_string(key) {
+            Ok(Some(value)) => {
+                match &value.val[..] {
+                    "cargo:token" => return Ok(CredentialProvider::Token),
+                    "cargo:keyring" => return Ok(CredentialProvider::Keyring),
+                    "cargo:secret-key" => return secret_key(config),
+                    _ => {}
+                }
+                value.val.split_whitespace().map(|s| {
+                    (s.to_string(), value.definition.root(config).to_path_buf())
+                }).collect::<Vec<_>>()
+            }
+            Ok(None) => return Ok(CredentialProvider::Token),
+            Err(_) => {
+                let value = try!(config.get_list(key)).unwrap();
+                value.val.into_iter().map(|(s, path)| {
+                    (s, path.parent().unwrap().parent().unwrap().to_path_buf())
+                }).collect()
+            }
+        };
+        if command.is_empty() {
+            bail!("expected `{}` to name a program", key)
+        }
+        if command[0].0.starts_with("cargo:") {
+            bail!("unknown built-in credential provider `{}`, expected \
+                   `cargo:token`, `cargo:keyring` or `cargo:secret-key`",
+                  command[0].0)
+        }
+
+        // Programs given as a path are relative to where they're configured.
+        let mut command = command.into_iter();
+        let (program, root) = command.next().unwrap();
+        let program = if program.contains('/') ||
+                         (cfg!(windows) && program.contains('\\')) {
+            root.join(program).display().to_string()
+        } else {
+            program
+        };
+        let mut args = vec![program];
+        args.extend(command.map(|(arg, _)| arg));
+        Ok(CredentialProvider::Process(args))
+    }
+
+    /// The token to use with `registry` for `operation`, if there is one.
+    pub fn token(&self, config: &Config, registry: &SourceId,
+                 operation: &Operation) -> CargoResult<Option<String>> {
+        let args = match *self {
+            CredentialProvider::Token => {
+                let token = try!(config.get_string("registry.token"));
+                return Ok(token.map(|t| t.val))
+            }
+            CredentialProvider::Keyring => {
+                return keyring::get(KEYRING_SERVICE, &registry.url().to_string())
+            }
+            CredentialProvider::SecretKey(ref key, ref key_id) => {
+                return sign(key, key_id, registry, operation).map(Some)
+            }
+            CredentialProvider::Process(ref args) => args,
+        };
+
+        let key = registry.url().to_string();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                                   .map(|d| d.as_secs()).unwrap_or(0);
+        if let Some(&(ref token, expires)) = config.credential_cache().get(&key) {
+            if expires.map(|e| e > now).unwrap_or(true) {
+                return Ok(Some(token.clone()))
+            }
+        }
+
+        let response = try!(request(config, args, "get", registry, None));
+        match response.token {
+            Some(token) => {
+                config.credential_cache().insert(key, (token.clone(),
+                                                       response.expires));
+                Ok(Some(token))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Saves `token` as the token to use with `registry`.
+    pub fn login(&self, config: &Config, registry: &SourceId, token: &str)
+                 -> CargoResult<()> {
+        match *self {
+            CredentialProvider::Token => {
+                set_registry(config, true, vec![("token", token.to_string())])
+            }
+            CredentialProvider::Keyring => {
+                keyring::set(KEYRING_SERVICE, &registry.url().to_string(), token)
+            }
+            CredentialProvider::SecretKey(..) => {
+                bail!("the `cargo:secret-key` credential provider signs \
+                       requests instead of using tokens, run \
+                       `cargo login --generate-key` to make a new key")
+            }
+            CredentialProvider::Process(ref args) => {
+                try!(request(config, args, "login", registry, Some(token)));
+                Ok(())
+            }
+        }
+    }
+
+    /// Revokes the token of `registry`, forgetting it.
+    pub fn logout(&self, config: &Config, registry: &SourceId)
+                  -> CargoResult<()> {
+        config.credential_cache().remove(&registry.url().to_string());
+        match *self {
+            CredentialProvider::Token => {
+                if try!(config.get_string("registry.token")).is_none() {
+                    bail!("there's no token in the configuration to remove")
+                }
+                set_registry(config, true, Vec::new())
+            }
+            CredentialProvider::Keyring => {
+                let url = registry.url().to_string();
+                if !try!(keyring::delete(KEYRING_SERVICE, &url)) {
+                    bail!("there's no token of `{}` in the keyring to remove",
+                          url)
+                }
+                Ok(())
+            }
+            // The key itself is left alone, it may well be used elsewhere.
+            CredentialProvider::SecretKey(..) => {
+                set_registry(config, false, Vec::new())
+            }
+            CredentialProvider::Process(ref args) => {
+                try!(request(config, args, "logout", registry, None));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Makes a new key for `cargo:secret-key` and switches to it, returning its
+/// public key to register with the registry.
+pub fn generate_key(config: &Config, key_id: Option<String>)
+                    -> CargoResult<Vec<u8>> {
+    let (private, public) = try!(signature::generate());
+    let key_id = key_id.unwrap_or_else(|| {
+        let mut h = Sha256::new();
+        h.update(&public);
+        format!("sha256:{}", h.finish().to_hex())
+    });
+
+    try!(config.home().create_dir());
+    let path = config.home().join("registry-key.pem").into_path_unlocked();
+    try!(paths::write(&path, &private));
+    try!(restrict_permissions(&path));
+    try!(set_registry(config, false, vec![
+        ("credential-provider", "cargo:secret-key".to_string()),
+        ("secret-key", path.display().to_string()),
+        ("secret-key-id", key_id),
+    ]));
+    Ok(public)
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> CargoResult<()> {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    try!(fs::set_permissions(path, fs::Permissions::from_mode(0o600)));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> CargoResult<()> {
+    Ok(())
+}
+
+/// Writes the `[registry]` table of the global configuration with its index,
+/// its credential provider if `keep_provider`, and then `keys`.
+fn set_registry(config: &Config, keep_provider: bool, keys: Vec<(&str, String)>)
+                -> CargoResult<()> {
+    let mut map = HashMap::new();
+    let p = config.cwd().to_path_buf();
+    if let Some(index) = try!(config.get_string("registry.index")) {
+        map.insert("index".to_string(), ConfigValue::String(index.val, p.clone()));
+    }
+    let provider = if keep_provider {
+        try!(config.get_string("registry.credential-provider"))
+    } else {
+        None
+    };
+    if let Some(provider) = provider {
+        map.insert("credential-provider".to_string(),
+                   ConfigValue::String(provider.val, p.clone()));
+    }
+    for (key, value) in keys {
+        map.insert(key.to_string(), ConfigValue::String(value, p.clone()));
+    }
+    config::set_config(config, Location::Global, "registry",
+                       ConfigValue::Table(map, PathBuf::from(".")))
+}
+
+fn secret_key(config: &Config) -> CargoResult<CredentialProvider> {
+    let key = match try!(config.get_string("registry.secret-key")) {
+        Some(key) => key.definition.root(config).join(key.val),
+        None => bail!("the `cargo:secret-key` credential provider needs \
+                       `registry.secret-key` to be set, run \
+                       `cargo login --generate-key` to make a new key"),
+    };
+    let key_id = match try!(config.get_string("registry.secret-key-id")) {
+        Some(key_id) => key_id.val,
+        None => bail!("the `cargo:secret-key` credential provider needs \
+                       `registry.secret-key-id` to be set to the ID the \
+                       registry knows the key by"),
+    };
+    Ok(CredentialProvider::SecretKey(key, key_id))
+}
+
+/// Makes the signed token allowing `operation` on `registry`.
+fn sign(key: &Path, key_id: &str, registry: &SourceId,
+        operation: &Operation) -> CargoResult<String> {
+    let mut payload = BTreeMap::new();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                               .map(|d| d.as_secs()).unwrap_or(0);
+    payload.insert("iat".to_string(), Json::U64(now));
+    payload.insert("url".to_string(), Json::String(registry.url().to_string()));
+    {
+        let mut insert = |key: &str, value: &str| {
+            payload.insert(key.to_string(), Json::String(value.to_string()));
+        };
+        match *operation {
+            Operation::Read => {}
+            Operation::Publish { name, vers, cksum } => {
+                insert("mutation", "publish");
+                insert("name", name);
+                insert("vers", vers);
+                insert("cksum", cksum);
+            }
+            Operation::Yank { name, vers } => {
+                insert("mutation", "yank");
+                insert("name", name);
+                insert("vers", vers);
+            }
+            Operation::Unyank { name, vers } => {
+                insert("mutation", "unyank");
+                insert("name", name);
+                insert("vers", vers);
+            }
+            Operation::Owners { name } => {
+                insert("mutation", "owners");
+                insert("name", name);
+            }
+        }
+    }
+    let mut footer = BTreeMap::new();
+    footer.insert("kid".to_string(), Json::String(key_id.to_string()));
+
+    let payload = Json::Object(payload).to_string();
+    let footer = Json::Object(footer).to_string();
+    let message = format!("cargo.v1.{}.{}",
+                          payload.as_bytes().to_base64(URL_SAFE),
+                          footer.as_bytes().to_base64(URL_SAFE));
+    let mut h = Sha256::new();
+    h.update(message.as_bytes());
+    let private = try!(paths::read_bytes(key));
+    let sig = try!(signature::sign(&private, &h.finish()).chain_error(|| {
+        human(format!("failed to sign a request to `{}` with the key `{}`",
+                      registry.url(), key.display()))
+    }));
+    Ok(format!("{}.{}", message, sig.to_base64(URL_SAFE)))
+}
+
+fn request(config: &Config,
+           args: &[String],
+           action: &str,
+           registry: &SourceId,
+           token: Option<&str>) -> CargoResult<Response> {
+    let url = registry.url().to_string();
+    let request = try!(json::encode(&Request {
+        v: 1,
+        action: action,
+        registry: &url,
+        token: token,
+    }));
+
+    let mut cmd = util::process(&args[0]);
+    cmd.args(&args[1..]).cwd(config.cwd());
+    let mut child = try!(cmd.build_command()
+                            .stdin(Stdio::piped())
+                            .stdout(Stdio::piped())
+                            .spawn().chain_error(|| {
+        human(format!("failed to run the credential provider `{}`", args[0]))
+    }));
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        try!(writeln!(stdin, "{}", request).chain_error(|| {
+            human(format!("failed to send a request to the credential \
+                           provider `{}`", args[0]))
+        }));
+    }
+    let output = try!(child.wait_with_output());
+    if !output.status.success() {
+        bail!("the credential provider `{}` failed to {} the token of `{}` \
+               ({})", args[0], action, url, output.status)
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next().unwrap_or("");
+    let response = try!(json::decode::<Response>(line).chain_error(|| {
+        human(format!("the credential provider `{}` answered with invalid \
+                       JSON: `{}`", args[0], line))
+    }));
+    if let Some(ref error) = response.error {
+        bail!("the credential provider `{}` failed to {} the token of `{}`: {}",
+              args[0], action, url, error)
+    }
+    Ok(response)
+}
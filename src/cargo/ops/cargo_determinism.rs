@@ -0,0 +1,130 @@
+//! Checking whether a build is reproducible.
+//!
+//! The requested units are built once as usual and then a second time into a
+//! separate target directory, with a slightly different environment. Every
+//! artifact of the two builds is compared byte for byte, and for those which
+//! differ we try to guess at what leaked into the output.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+
+use core::{PackageId, Workspace};
+use ops::{self, CompileOptions};
+use util::{paths, CargoResult};
+
+/// Builds `ws` twice, the second time as `second`, and reports all artifacts
+/// which didn't come out identical.
+///
+/// `second` must be the same workspace as `ws`, just with a different target
+/// directory.
+pub fn check_determinism<'a>(ws: &Workspace<'a>,
+                             second: &Workspace<'a>,
+                             options: &CompileOptions<'a>) -> CargoResult<()> {
+    let config = options.config;
+    let first = try!(ops::compile(ws, options));
+    try!(config.shell().status("Rebuilding",
+                               format!("in `{}` to check determinism",
+                                       second.target_dir().display())));
+    let saved = vary_environment();
+    let other = ops::compile(second, options);
+    restore_environment(saved);
+    let other = try!(other);
+
+    let mut artifacts: Vec<(Option<&PackageId>, &Path)> = Vec::new();
+    for (id, libs) in first.libraries.iter() {
+        artifacts.extend(libs.iter().map(|&(_, ref path)| (Some(id), &**path)));
+    }
+    artifacts.extend(first.binaries.iter().map(|p| (None, &**p)));
+    artifacts.sort();
+    artifacts.dedup();
+
+    let mut differing = 0;
+    for &(id, path) in artifacts.iter() {
+        let relative = match path.strip_prefix(&first.root_output) {
+            Ok(relative) => relative,
+            Err(..) => continue,
+        };
+        let other_path = other.root_output.join(relative);
+        let a = try!(paths::read_bytes(path));
+        let b = try!(paths::read_bytes(&other_path));
+        if a == b {
+            continue
+        }
+        differing += 1;
+        let name = path.file_name().unwrap().to_string_lossy();
+        let msg = match id {
+            Some(id) => format!("`{}` of `{}` differs between builds", name, id),
+            None => format!("`{}` differs between builds", name),
+        };
+        try!(config.shell().warn(format!("{}\n  {}", msg,
+                                         likely_cause(&a, &b, &other.root_output))));
+    }
+
+    if differing > 0 {
+        bail!("{} of {} artifacts were not reproducible",
+              differing, artifacts.len())
+    }
+    try!(config.shell().status("Verified",
+                               format!("{} artifacts are reproducible",
+                                       artifacts.len())));
+    Ok(())
+}
+
+/// Changes the environment inherited by the compiler and build scripts, so
+/// outputs which depend on it differ between the builds: the variables are
+/// put in reverse order, the time zone changes and an extra variable is set.
+///
+/// Returns the original environment for `restore_environment`.
+fn vary_environment() -> Vec<(OsString, OsString)> {
+    let saved = env::vars_os().collect::<Vec<_>>();
+    for &(ref k, _) in saved.iter() {
+        env::remove_var(k);
+    }
+    for &(ref k, ref v) in saved.iter().rev() {
+        env::set_var(k, v);
+    }
+    let tz = match env::var("TZ") {
+        Ok(ref tz) if tz == "Pacific/Kiritimati" => "Etc/GMT+12",
+        _ => "Pacific/Kiritimati",
+    };
+    env::set_var("TZ", tz);
+    env::set_var("CARGO_DETERMINISM_CHECK", "1");
+    saved
+}
+
+fn restore_environment(saved: Vec<(OsString, OsString)>) {
+    for (k, _) in env::vars_os() {
+        env::remove_var(k);
+    }
+    for (k, v) in saved {
+        env::set_var(k, v);
+    }
+}
+
+/// Makes a guess at what caused `a` and `b` to differ, `b` having been built
+/// into `other_root`.
+fn likely_cause(a: &[u8], b: &[u8], other_root: &Path) -> &'static str {
+    let root = other_root.to_string_lossy();
+    if contains(b, root.as_bytes()) {
+        return "the path of the target directory is embedded in it"
+    }
+
+    // Timestamps tend to show up as a run of digits where only some of them
+    // changed between the builds.
+    let start = a.iter().zip(b).position(|(x, y)| x != y).unwrap_or(0);
+    let digits = |s: &[u8]| {
+        s.iter().skip(start).take(4).all(|c| (*c as char).is_digit(10))
+    };
+    if a.len() == b.len() && digits(a) && digits(b) {
+        return "a number changed between builds, which is often the current \
+                time being embedded by a build script or procedural macro"
+    }
+
+    "it may depend on the environment, randomness, or the iteration order of \
+     a hash map"
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty() && haystack.windows(needle.len()).any(|w| w == needle)
+}
@@ -0,0 +1,211 @@
+//! Diagnosing problems with the sources Cargo is configured to use and with
+//! what it keeps of them in `$CARGO_HOME`.
+//!
+//! Each problem found is reported as a warning along with how to fix it, and
+//! the command fails if there were any.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use git2;
+use rustc_serialize::hex::ToHex;
+use url::Url;
+
+use core::{SourceId, Workspace};
+use ops;
+use sources::SourceConfigMap;
+use sources::registry::downloaded_crate;
+use util::{network, CargoResult, Config, Sha256, human};
+
+struct Report<'a> {
+    config: &'a Config,
+    problems: usize,
+}
+
+impl<'a> Report<'a> {
+    fn check(&self, what: &str) -> CargoResult<()> {
+        self.config.shell().status("Checking", what)
+    }
+
+    fn problem(&mut self, problem: String, fix: String) -> CargoResult<()> {
+        self.problems += 1;
+        self.config.shell().warn(format!("{}\nhelp: {}", problem, fix))
+    }
+}
+
+/// Checks the configured sources, the downloaded packages the lockfile of
+/// `ws` refers to, if any, and the git repositories Cargo fetched.
+pub fn doctor(config: &Config, ws: Option<&Workspace>) -> CargoResult<()> {
+    let mut report = Report { config: config, problems: 0 };
+    let sources = try!(SourceConfigMap::new(config));
+    try!(check_sources(&mut report, &sources));
+    try!(check_registry_token(&mut report));
+    if let Some(ws) = ws {
+        try!(check_lockfile(&mut report, &sources, ws));
+    }
+    try!(check_git_db(&mut report));
+
+    match report.problems {
+        0 => config.shell().status("Finished", "no problems found"),
+        1 => bail!("found 1 problem"),
+        n => bail!("found {} problems", n),
+    }
+}
+
+fn check_sources(report: &mut Report, sources: &SourceConfigMap) -> CargoResult<()> {
+    for (name, id, replace_with) in sources.configured() {
+        try!(report.check(&format!("source `{}` ({})", name, id.url())));
+
+        // Replaced sources are never contacted, but what they're replaced
+        // with has to make sense.
+        if let Some(other) = replace_with {
+            if let Err(e) = sources.load(id) {
+                try!(report.problem(
+                    format!("source `{}` cannot be replaced with `{}`: {}",
+                            name, other, e),
+                    format!("correct `source.{}.replace-with` in the \
+                             configuration", name)));
+            }
+            continue
+        }
+
+        if let Err(e) = reachable(report.config, id) {
+            try!(report.problem(
+                format!("source `{}` at `{}` is not reachable: {}",
+                        name, id.url(), e),
+                if id.url().scheme() == "file" {
+                    format!("point `source.{}` at an existing location in \
+                             the configuration", name)
+                } else {
+                    "check the network connection and the `http.proxy` \
+                     configuration".to_string()
+                }));
+        }
+    }
+    Ok(())
+}
+
+fn reachable(config: &Config, id: &SourceId) -> CargoResult<()> {
+    let url = id.url();
+    match url.scheme() {
+        "file" => {
+            let path = try!(url.to_file_path().map_err(|()| {
+                human(format!("`{}` is not a valid path", url))
+            }));
+            if fs::metadata(&path).is_err() {
+                bail!("`{}` does not exist", path.display())
+            }
+            Ok(())
+        }
+        "http" | "https" => head(config, url),
+        // Other protocols are only spoken by git, which doesn't offer a
+        // cheap way of checking on a remote.
+        _ => Ok(()),
+    }
+}
+
+fn head(config: &Config, url: &Url) -> CargoResult<()> {
+    try!(network::check_allowed(config, url));
+    let mut handle = try!(ops::http_handle(config));
+    try!(handle.url(&url.to_string()));
+    try!(handle.nobody(true));
+    try!(handle.follow_location(true));
+    try!(handle.perform());
+    let code = try!(handle.response_code());
+    if code >= 400 {
+        bail!("got a {} response", code)
+    }
+    Ok(())
+}
+
+fn check_registry_token(report: &mut Report) -> CargoResult<()> {
+    let token = match try!(ops::registry_configuration(report.config)).token {
+        Some(token) => token,
+        None => return Ok(()),
+    };
+    try!(report.check("registry token"));
+    if token.is_empty() ||
+       token.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        try!(report.problem("the registry token in the configuration is \
+                             malformed".to_string(),
+                            "run `cargo login` again with the token from the \
+                             registry's website".to_string()));
+    }
+    Ok(())
+}
+
+fn check_lockfile(report: &mut Report,
+                  sources: &SourceConfigMap,
+                  ws: &Workspace) -> CargoResult<()> {
+    let resolve = match try!(ops::load_pkg_lockfile(ws)) {
+        Some(resolve) => resolve,
+        None => return Ok(()),
+    };
+    try!(report.check("downloaded packages against Cargo.lock"));
+
+    let mut checksums = resolve.checksums().iter().filter_map(|(id, cksum)| {
+        cksum.as_ref().map(|cksum| (id, cksum))
+    }).collect::<Vec<_>>();
+    checksums.sort();
+    for (id, cksum) in checksums {
+        let source_id = match sources.replacement(id.source_id()) {
+            Ok(source_id) => source_id,
+            // Reported along with the sources already
+            Err(..) => continue,
+        };
+        if !source_id.is_registry() {
+            continue
+        }
+        let (file, dir) = downloaded_crate(report.config, &source_id, id);
+        let mut contents = Vec::new();
+        match File::open(&file).and_then(|mut f| f.read_to_end(&mut contents)) {
+            Ok(..) => {}
+            // Not downloaded yet
+            Err(..) => continue,
+        }
+        let mut state = Sha256::new();
+        state.update(&contents);
+        if state.finish().to_hex() != *cksum {
+            try!(report.problem(
+                format!("the downloaded `{}` does not match the checksum of \
+                         `{}` in Cargo.lock", file.display(), id),
+                format!("remove `{}` and `{}` so that it is downloaded again",
+                        file.display(), dir.display())));
+        }
+    }
+    Ok(())
+}
+
+fn check_git_db(report: &mut Report) -> CargoResult<()> {
+    let db = report.config.git_path().join("db").into_path_unlocked();
+    let mut repos = match fs::read_dir(&db) {
+        Ok(entries) => try!(entries.map(|e| e.map(|e| e.path()))
+                                   .collect::<Result<Vec<_>, _>>()),
+        Err(..) => return Ok(()),
+    };
+    repos.sort();
+    try!(report.check(&format!("git repositories in `{}`", db.display())));
+    for repo in repos {
+        if let Err(e) = verify_git_repo(&repo) {
+            try!(report.problem(
+                format!("the git repository `{}` is corrupt: {}",
+                        repo.display(), e),
+                format!("remove `{}`, it will be fetched again when needed",
+                        repo.display())));
+        }
+    }
+    Ok(())
+}
+
+/// Makes sure the commit and tree each reference points to can be read.
+fn verify_git_repo(path: &Path) -> Result<(), git2::Error> {
+    let repo = try!(git2::Repository::open(path));
+    for reference in try!(repo.references()) {
+        let reference = try!(reference);
+        if let Some(oid) = reference.target() {
+            try!(try!(repo.find_commit(oid)).tree());
+        }
+    }
+    Ok(())
+}
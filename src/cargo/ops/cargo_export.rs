@@ -0,0 +1,371 @@
+//! Generating build rules for other build systems from the resolved graph.
+//!
+//! Each package which isn't local to the workspace gets a library rule named
+//! `$name-$version`, along with rules compiling and running its build
+//! scripts. Features are those activated by the resolve and platform-specific
+//! dependencies are turned into `select`s, so the rules always agree with how
+//! Cargo itself would build the package.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use core::{Package, PackageId, Target, Workspace};
+use core::dependency::Platform;
+use ops;
+use util::{self, CargoResult, CargoError};
+
+/// The build systems rules can be exported for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    /// `BUILD.bazel` rules for `rules_rust`.
+    Bazel,
+    /// `BUCK` rules for the Buck2 prelude.
+    Buck2,
+}
+
+impl FromStr for ExportFormat {
+    type Err = Box<CargoError>;
+
+    fn from_str(s: &str) -> CargoResult<ExportFormat> {
+        match s {
+            "bazel" => Ok(ExportFormat::Bazel),
+            "buck2" => Ok(ExportFormat::Buck2),
+            _ => bail!("unknown export format `{}`, expected `bazel` or \
+                        `buck2`", s),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExportFormat::Bazel => "bazel".fmt(f),
+            ExportFormat::Buck2 => "buck2".fmt(f),
+        }
+    }
+}
+
+pub struct ExportOptions<'a> {
+    pub format: ExportFormat,
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// The package containing the conditions platform-specific dependencies
+    /// are selected with.
+    pub platforms: &'a str,
+}
+
+/// The label of a dependency of a rule, only used on `platform` if given.
+type Dep<'a> = (String, Option<&'a Platform>);
+
+/// A package to generate rules for.
+struct Crate<'a> {
+    pkg: &'a Package,
+    lib: &'a Target,
+    features: Vec<String>,
+    deps: Vec<Dep<'a>>,
+    proc_macro_deps: Vec<Dep<'a>>,
+    build_deps: Vec<Dep<'a>>,
+}
+
+/// Resolves the workspace and returns the rules for all of the packages it
+/// depends on which aren't local.
+pub fn export(ws: &Workspace, opts: &ExportOptions) -> CargoResult<String> {
+    let (packages, resolve) = try!(ops::resolve_dependencies(ws,
+                                                             None,
+                                                             opts.features.clone(),
+                                                             opts.all_features,
+                                                             opts.no_default_features));
+
+    let mut pkgs = HashMap::new();
+    for id in packages.package_ids() {
+        pkgs.insert(id, try!(packages.get(id)));
+    }
+    let mut ids = pkgs.keys().cloned().filter(|id| {
+        !id.source_id().is_path()
+    }).collect::<Vec<_>>();
+    ids.sort();
+
+    let mut crates = Vec::new();
+    for id in ids {
+        let pkg = pkgs[id];
+        let lib = match pkg.targets().iter().find(|t| t.is_lib()) {
+            Some(lib) => lib,
+            None => continue,
+        };
+        let mut features = resolve.features(id).map(|f| {
+            f.iter().cloned().collect::<Vec<_>>()
+        }).unwrap_or(Vec::new());
+        features.sort();
+
+        let mut deps = Vec::new();
+        let mut proc_macro_deps = Vec::new();
+        let mut build_deps = Vec::new();
+        for locked in resolve.deps_not_replaced(id) {
+            let dep_id = resolve.replacement(locked).unwrap_or(locked);
+            let dep_lib = match pkgs.get(dep_id).and_then(|p| {
+                p.targets().iter().find(|t| t.is_lib())
+            }) {
+                Some(lib) => lib,
+                None => continue,
+            };
+            let used = pkg.dependencies().iter().filter(|d| {
+                d.matches_id(locked) && d.is_transitive() &&
+                    (!d.is_optional() || features.iter().any(|f| f == d.name()))
+            });
+            for d in used {
+                let dep = (format!(":{}", label(dep_id)), d.platform());
+                let list = if d.is_build() {
+                    &mut build_deps
+                } else if dep_lib.is_rustc_macro() {
+                    &mut proc_macro_deps
+                } else {
+                    &mut deps
+                };
+                if !list.contains(&dep) {
+                    list.push(dep);
+                }
+            }
+        }
+        for list in [&mut deps, &mut proc_macro_deps, &mut build_deps].iter_mut() {
+            list.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        crates.push(Crate {
+            pkg: pkg,
+            lib: lib,
+            features: features,
+            deps: deps,
+            proc_macro_deps: proc_macro_deps,
+            build_deps: build_deps,
+        });
+    }
+
+    // Direct dependencies of the workspace can also be referred to by name,
+    // as long as only one version of them is used.
+    let mut direct = BTreeMap::new();
+    for member in ws.members() {
+        for dep_id in resolve.deps(member.package_id()) {
+            if dep_id.source_id().is_path() {
+                continue
+            }
+            direct.entry(dep_id.name()).or_insert(Vec::new()).push(dep_id);
+        }
+    }
+
+    let mut out = String::new();
+    header(&mut out, opts);
+    for krate in crates.iter() {
+        match opts.format {
+            ExportFormat::Bazel => bazel(&mut out, krate, opts),
+            ExportFormat::Buck2 => buck2(&mut out, krate, opts),
+        }
+    }
+    for (name, ids) in direct.iter_mut() {
+        ids.sort();
+        ids.dedup();
+        if ids.len() == 1 {
+            alias(&mut out, name, &label(ids[0]), opts);
+        }
+    }
+    Ok(out)
+}
+
+fn label(id: &PackageId) -> String {
+    format!("{}-{}", id.name(), id.version())
+}
+
+/// The path of a target's source relative to the rules, which expect the
+/// sources of each package in a directory named after its rule.
+fn src_path(krate: &Crate, target: &Target) -> String {
+    let path = util::without_prefix(target.src_path(), krate.pkg.root())
+                    .unwrap_or(target.src_path());
+    let mut ret = label(krate.pkg.package_id());
+    for component in path.iter() {
+        ret.push('/');
+        ret.push_str(&component.to_string_lossy());
+    }
+    ret
+}
+
+fn header(out: &mut String, opts: &ExportOptions) {
+    out.push_str(&format!("\
+# Generated by `cargo export --format {}`, do not edit.
+#
+# The sources of each package are expected in a directory named after its
+# rule next to this file. Platform-specific dependencies are selected with
+# conditions in `{}` named after the platform in Cargo.toml.
+", opts.format, opts.platforms));
+    if opts.format == ExportFormat::Bazel {
+        out.push_str("
+load(\"@rules_rust//cargo:defs.bzl\", \"cargo_build_script\")
+load(\"@rules_rust//rust:defs.bzl\", \"rust_library\", \"rust_proc_macro\")
+");
+    }
+}
+
+fn bazel(out: &mut String, krate: &Crate, opts: &ExportOptions) {
+    let name = label(krate.pkg.package_id());
+    let srcs = format!("glob([\"{}/**/*.rs\"])", name);
+    let data = format!("glob([\"{}/**\"])", name);
+
+    let mut deps = krate.deps.clone();
+    for script in krate.pkg.targets().iter().filter(|t| t.is_custom_build()) {
+        let script_name = format!("{}-{}", name, script.name());
+        let mut attrs = vec![
+            ("name", string(&script_name)),
+            ("srcs", srcs.clone()),
+            ("crate_root", string(&src_path(krate, script))),
+            ("crate_features", list(&krate.features)),
+            ("deps", deps_list(&krate.build_deps, opts)),
+            ("data", data.clone()),
+            ("version", string(&krate.pkg.version().to_string())),
+        ];
+        if let Some(links) = krate.pkg.manifest().links().first() {
+            attrs.push(("links", string(links)));
+        }
+        rule(out, "cargo_build_script", &attrs);
+        deps.push((format!(":{}", script_name), script.platform()));
+    }
+
+    let kind = if krate.lib.is_rustc_macro() {
+        "rust_proc_macro"
+    } else {
+        "rust_library"
+    };
+    rule(out, kind, &[
+        ("name", string(&name)),
+        ("crate_name", string(&krate.lib.crate_name())),
+        ("srcs", srcs),
+        ("crate_root", string(&src_path(krate, krate.lib))),
+        ("crate_features", list(&krate.features)),
+        ("deps", deps_list(&deps, opts)),
+        ("proc_macro_deps", deps_list(&krate.proc_macro_deps, opts)),
+        ("compile_data", data),
+        ("version", string(&krate.pkg.version().to_string())),
+        ("visibility", list(&["//visibility:public".to_string()])),
+    ]);
+}
+
+fn buck2(out: &mut String, krate: &Crate, opts: &ExportOptions) {
+    let name = label(krate.pkg.package_id());
+    let srcs = format!("glob([\"{}/**\"])", name);
+
+    let mut env = Vec::new();
+    let mut rustc_flags = Vec::new();
+    let mut deps = krate.deps.clone();
+    deps.extend(krate.proc_macro_deps.iter().cloned());
+    for script in krate.pkg.targets().iter().filter(|t| t.is_custom_build()) {
+        let bin = format!("{}-{}", name, script.name());
+        let run = format!("{}-run", bin);
+        rule(out, "rust_binary", &[
+            ("name", string(&bin)),
+            ("srcs", srcs.clone()),
+            ("crate", string(&script.crate_name())),
+            ("crate_root", string(&src_path(krate, script))),
+            ("features", list(&krate.features)),
+            ("deps", deps_list(&krate.build_deps, opts)),
+        ]);
+        rule(out, "buildscript_run", &[
+            ("name", string(&run)),
+            ("package_name", string(krate.pkg.name())),
+            ("buildscript_rule", string(&format!(":{}", bin))),
+            ("features", list(&krate.features)),
+            ("version", string(&krate.pkg.version().to_string())),
+        ]);
+        // Only the first build script provides the library's `OUT_DIR`, as
+        // with Cargo.
+        if env.is_empty() {
+            env.push(format!("\"OUT_DIR\": \"$(location :{}[out_dir])\"", run));
+        }
+        rustc_flags.push(format!("@$(location :{}[rustc_flags])", run));
+    }
+
+    let mut attrs = vec![
+        ("name", string(&name)),
+        ("crate", string(&krate.lib.crate_name())),
+        ("srcs", srcs),
+        ("crate_root", string(&src_path(krate, krate.lib))),
+        ("features", list(&krate.features)),
+        ("deps", deps_list(&deps, opts)),
+    ];
+    if krate.lib.is_rustc_macro() {
+        attrs.push(("proc_macro", "True".to_string()));
+    }
+    if !env.is_empty() {
+        attrs.push(("env", format!("{{{}}}", env.join(", "))));
+        attrs.push(("rustc_flags", list(&rustc_flags)));
+    }
+    attrs.push(("visibility", list(&["PUBLIC".to_string()])));
+    rule(out, "rust_library", &attrs);
+}
+
+fn alias(out: &mut String, name: &str, actual: &str, opts: &ExportOptions) {
+    let visibility = match opts.format {
+        ExportFormat::Bazel => "//visibility:public",
+        ExportFormat::Buck2 => "PUBLIC",
+    };
+    rule(out, "alias", &[
+        ("name", string(name)),
+        ("actual", string(&format!(":{}", actual))),
+        ("visibility", list(&[visibility.to_string()])),
+    ]);
+}
+
+fn rule(out: &mut String, kind: &str, attrs: &[(&str, String)]) {
+    out.push_str(&format!("\n{}(\n", kind));
+    for &(key, ref value) in attrs {
+        out.push_str(&format!("    {} = {},\n", key, value));
+    }
+    out.push_str(")\n");
+}
+
+fn string(s: &str) -> String {
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+fn list(items: &[String]) -> String {
+    let items = items.iter().map(|s| string(s)).collect::<Vec<_>>();
+    format!("[{}]", items.join(", "))
+}
+
+/// A list of dependencies, where those only used on some platforms are
+/// added with a `select` on a condition named after the platform.
+fn deps_list(deps: &[Dep], opts: &ExportOptions) -> String {
+    let mut always = Vec::new();
+    let mut conditional = BTreeMap::new();
+    for &(ref dep, platform) in deps {
+        let dep = dep.clone();
+        match platform {
+            Some(platform) => {
+                conditional.entry(condition(platform, opts))
+                           .or_insert(Vec::new()).push(dep);
+            }
+            None => always.push(dep),
+        }
+    }
+    let mut ret = list(&always);
+    if !conditional.is_empty() {
+        let default = match opts.format {
+            ExportFormat::Bazel => "//conditions:default",
+            ExportFormat::Buck2 => "DEFAULT",
+        };
+        ret.push_str(" + select({\n");
+        for (condition, deps) in conditional.iter() {
+            ret.push_str(&format!("        {}: {},\n", string(condition),
+                                  list(deps)));
+        }
+        ret.push_str(&format!("        {}: [],\n    }})", string(default)));
+    }
+    ret
+}
+
+/// The label of the condition for `platform`, with everything but
+/// alphanumerics, `-` and `.` replaced by `_`, so `cfg(unix)` for example is
+/// selected by `$platforms:cfg_unix_`.
+fn condition(platform: &Platform, opts: &ExportOptions) -> String {
+    let name = platform.to_string().chars().map(|c| {
+        if c.is_alphanumeric() || c == '-' || c == '.' {c} else {'_'}
+    }).collect::<String>();
+    format!("{}:{}", opts.platforms, name)
+}
@@ -1,19 +1,38 @@
+use std::path::Path;
+
 use core::registry::PackageRegistry;
 use core::{PackageId, Resolve, PackageSet, Workspace};
-use ops;
+use ops::{self, MessageFormat};
+use sources::dep_archive;
 use util::CargoResult;
 
 /// Executes `cargo fetch`.
-pub fn fetch<'a>(ws: &Workspace<'a>) -> CargoResult<(Resolve, PackageSet<'a>)> {
+///
+/// All the packages are downloaded up front, so sources able to download
+/// several at once do, and with `MessageFormat::Json` their progress is
+/// reported in JSON messages.
+pub fn fetch<'a>(ws: &Workspace<'a>, message_format: MessageFormat)
+                 -> CargoResult<(Resolve, PackageSet<'a>)> {
     let mut registry = try!(PackageRegistry::new(ws.config()));
     let resolve = try!(ops::resolve_ws(&mut registry, ws));
     let packages = get_resolved_packages(&resolve, registry);
+    try!(packages.prefetch(message_format == MessageFormat::Json));
     for id in resolve.iter() {
         try!(packages.get(id));
     }
     Ok((resolve, packages))
 }
 
+/// Executes `cargo fetch --archive`, writing everything needed to build `ws`
+/// without the network into the archive `dst`.
+pub fn fetch_archive(ws: &Workspace, message_format: MessageFormat, dst: &Path)
+                     -> CargoResult<()> {
+    let (resolve, packages) = try!(fetch(ws, message_format));
+    let count = try!(dep_archive::write(ws.config(), &resolve, &packages, dst));
+    ws.config().shell().status("Archived", format!("{} packages into `{}`",
+                                                   count, dst.display()))
+}
+
 pub fn get_resolved_packages<'a>(resolve: &Resolve,
                                  registry: PackageRegistry<'a>)
                                  -> PackageSet<'a> {
@@ -1,24 +1,68 @@
-use std::collections::{BTreeMap, HashSet};
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use rustc_serialize::json;
+use term::color::BLACK;
 
 use core::PackageId;
 use core::registry::PackageRegistry;
 use core::{Resolve, SourceId, Workspace};
-use core::resolver::Method;
-use ops;
+use core::resolver::{Method, VersionOrder};
+use ops::{self, MessageFormat};
 use util::config::Config;
 use util::CargoResult;
 
+use super::cargo_audit::{self, AuditPolicy};
+
 pub struct UpdateOptions<'a> {
     pub config: &'a Config,
     pub to_update: &'a [String],
     pub precise: Option<&'a str>,
     pub aggressive: bool,
+    pub advisories: Option<&'a Path>,
+    /// Whether the direct dependencies of the workspace which are updated
+    /// are updated to the oldest versions they allow.
+    pub minimal_direct: bool,
+    /// Whether the requirements in the manifests of the workspace on the
+    /// packages updated are raised to allow their newest versions, even when
+    /// those are semver-incompatible with the requirements.
+    pub breaking: bool,
+    /// Whether `precise` may name a version which has been yanked.
+    pub allow_yanked: bool,
+    /// Whether the lockfile is left as it is, only reporting the changes.
+    pub dry_run: bool,
+    /// The format to print the changes to the lockfile in to stdout, if any.
+    pub diff: Option<MessageFormat>,
+}
+
+/// A package which changes in the lockfile, as printed by
+/// `cargo update --diff`. Packages which are added have no old version, and
+/// those removed no new version.
+#[derive(RustcEncodable)]
+struct LockChange {
+    name: String,
+    source: String,
+    old_version: Option<String>,
+    new_version: Option<String>,
+    features_added: Vec<String>,
+    features_removed: Vec<String>,
+}
+
+#[derive(RustcEncodable)]
+struct LockDiff {
+    changes: Vec<LockChange>,
 }
 
 pub fn generate_lockfile(ws: &Workspace) -> CargoResult<()> {
+    generate_lockfile_in_order(ws, VersionOrder::Newest)
+}
+
+fn generate_lockfile_in_order(ws: &Workspace,
+                              order: VersionOrder) -> CargoResult<()> {
     let mut registry = try!(PackageRegistry::new(ws.config()));
     let resolve = try!(ops::resolve_with_previous(&mut registry, ws,
-                                                  Method::Everything,
+                                                  Method::Everything, order,
                                                   None, None));
     try!(ops::write_pkg_lockfile(ws, &resolve));
     Ok(())
@@ -30,6 +74,35 @@ pub fn update_lockfile(ws: &Workspace, opts: &UpdateOptions)
     if opts.aggressive && opts.precise.is_some() {
         bail!("cannot specify both aggressive and precise simultaneously")
     }
+    if opts.advisories.is_some() &&
+       (opts.aggressive || opts.precise.is_some() || !opts.to_update.is_empty()) {
+        bail!("cannot specify packages to update along with --from-advisories")
+    }
+    if opts.minimal_direct && (opts.precise.is_some() || opts.advisories.is_some()) {
+        bail!("cannot update to minimal versions along with --precise or \
+               --from-advisories")
+    }
+    if opts.allow_yanked && opts.precise.is_none() {
+        bail!("--allow-yanked can only be specified along with --precise")
+    }
+    if opts.breaking && opts.dry_run {
+        bail!("cannot specify --breaking along with --dry-run, as the \
+               manifests are edited first")
+    }
+    if opts.breaking &&
+       (opts.aggressive || opts.precise.is_some() || opts.advisories.is_some() ||
+        opts.minimal_direct) {
+        bail!("cannot specify --breaking along with --aggressive, --precise, \
+               --from-advisories or --minimal-direct")
+    }
+    if opts.breaking {
+        return update_breaking(ws, opts)
+    }
+    let order = if opts.minimal_direct {
+        VersionOrder::MinimalDirect
+    } else {
+        VersionOrder::Newest
+    };
 
     if ws.members().is_empty() {
         bail!("you can't generate a lockfile for an empty workspace.")
@@ -37,8 +110,17 @@ pub fn update_lockfile(ws: &Workspace, opts: &UpdateOptions)
 
     let previous_resolve = match try!(ops::load_pkg_lockfile(ws)) {
         Some(resolve) => resolve,
-        None => return generate_lockfile(ws),
+        None if opts.advisories.is_some() => {
+            bail!("a Cargo.lock must exist to update from advisories")
+        }
+        None if opts.dry_run || opts.diff.is_some() => {
+            bail!("a Cargo.lock must exist to show the changes to it")
+        }
+        None => return generate_lockfile_in_order(ws, order),
     };
+    if let Some(path) = opts.advisories {
+        return update_from_advisories(ws, opts, &previous_resolve, path)
+    }
     let mut registry = try!(PackageRegistry::new(opts.config));
     let mut to_avoid = HashSet::new();
 
@@ -72,39 +154,51 @@ pub fn update_lockfile(ws: &Workspace, opts: &UpdateOptions)
             }
         }
         try!(registry.add_sources(&sources));
+
+        // The packages affected by the configured advisory databases are
+        // updated along with those asked for, to pick up patched versions.
+        if let Some(policy) = try!(AuditPolicy::from_config(opts.config)) {
+            let advisories = try!(policy.advisories(opts.config));
+            let affected = cargo_audit::affected(&previous_resolve, &advisories);
+            let sources = affected.iter().map(|&(pkg, _)| {
+                pkg.source_id().clone().with_precise(None)
+            }).collect::<Vec<_>>();
+            to_avoid.extend(affected.iter().map(|&(pkg, _)| pkg));
+            try!(registry.add_sources(&sources));
+        }
     }
 
-    let resolve = try!(ops::resolve_with_previous(&mut registry,
-                                                  ws,
-                                                  Method::Everything,
-                                                  Some(&previous_resolve),
-                                                  Some(&to_avoid)));
+    let mut resolve = try!(ops::resolve_with_previous(&mut registry,
+                                                      ws,
+                                                      Method::Everything,
+                                                      order,
+                                                      Some(&previous_resolve),
+                                                      Some(&to_avoid)));
 
-    // Summarize what is changing for the user.
-    let print_change = |status: &str, msg: String| {
-        opts.config.shell().status(status, msg)
-    };
-    for (removed, added) in compare_dependency_graphs(&previous_resolve, &resolve) {
-        if removed.len() == 1 && added.len() == 1 {
-            let msg = if removed[0].source_id().is_git() {
-                format!("{} -> #{}", removed[0],
-                        &added[0].source_id().precise().unwrap()[..8])
-            } else {
-                format!("{} -> v{}", removed[0], added[0].version())
-            };
-            try!(print_change("Updating", msg));
-        } else {
-            for package in removed.iter() {
-                try!(print_change("Removing", format!("{}", package)));
+    if let Some(precise) = opts.precise {
+        // Yanked versions are only selected when they're asked for precisely,
+        // and then only kept with --allow-yanked.
+        let selected = resolve.iter().filter(|id| {
+            opts.to_update.iter().any(|name| name == id.name()) &&
+                id.version().to_string() == precise
+        }).cloned().collect::<Vec<_>>();
+        for id in selected {
+            if !try!(registry.is_yanked(&id)) {
+                continue
             }
-            for package in added.iter() {
-                try!(print_change("Adding", format!("{}", package)));
+            if !opts.allow_yanked {
+                bail!("version {} of `{}` has been yanked, pass \
+                       --allow-yanked to lock it anyway",
+                      id.version(), id.name())
             }
+            try!(opts.config.shell().warn(format!(
+                "locking `{}` even though it has been yanked; this is \
+                 recorded in Cargo.lock, and reported until it's updated", id)));
+            resolve.pin_yanked(&id);
         }
     }
 
-    try!(ops::write_pkg_lockfile(&ws, &resolve));
-    return Ok(());
+    return finish_update(ws, opts, &previous_resolve, &resolve);
 
     fn fill_with_deps<'a>(resolve: &'a Resolve, dep: &'a PackageId,
                           set: &mut HashSet<&'a PackageId>,
@@ -117,66 +211,332 @@ pub fn update_lockfile(ws: &Workspace, opts: &UpdateOptions)
             fill_with_deps(resolve, dep, set, visited);
         }
     }
+}
 
-    fn compare_dependency_graphs<'a>(previous_resolve: &'a Resolve,
-                                     resolve: &'a Resolve) ->
-                                     Vec<(Vec<&'a PackageId>, Vec<&'a PackageId>)> {
-        fn key(dep: &PackageId) -> (&str, &SourceId) {
-            (dep.name(), dep.source_id())
-        }
-
-        // Removes all package ids in `b` from `a`. Note that this is somewhat
-        // more complicated because the equality for source ids does not take
-        // precise versions into account (e.g. git shas), but we want to take
-        // that into account here.
-        fn vec_subtract<'a>(a: &[&'a PackageId],
-                            b: &[&'a PackageId]) -> Vec<&'a PackageId> {
-            a.iter().filter(|a| {
-                // If this package id is not found in `b`, then it's definitely
-                // in the subtracted set
-                let i = match b.binary_search(a) {
-                    Ok(i) => i,
-                    Err(..) => return true,
-                };
-
-                // If we've found `a` in `b`, then we iterate over all instances
-                // (we know `b` is sorted) and see if they all have different
-                // precise versions. If so, then `a` isn't actually in `b` so
-                // we'll let it through.
-                //
-                // Note that we only check this for non-registry sources,
-                // however, as registries countain enough version information in
-                // the package id to disambiguate
-                if a.source_id().is_registry() {
-                    return false
+/// Raises the requirements on the packages to update which don't allow their
+/// newest versions, and then updates those packages in the lockfile.
+fn update_breaking(ws: &Workspace, opts: &UpdateOptions) -> CargoResult<()> {
+    if !opts.config.lock_update_allowed() {
+        let flag = if opts.config.network_allowed() {"--frozen"} else {"--locked"};
+        bail!("the manifests of the workspace need to be updated but {} was \
+               passed to prevent this", flag)
+    }
+    let upgraded = try!(ops::upgrade_requirements(ws, &ops::UpgradeOptions {
+        to_upgrade: opts.to_update,
+        breaking: true,
+        dry_run: false,
+    }));
+    if upgraded.is_empty() {
+        try!(opts.config.shell().status("Upgrading",
+                                        "no requirement excludes the newest \
+                                         version of its dependency"));
+        return Ok(())
+    }
+
+    // The workspace is loaded again to pick up the edited requirements.
+    let ws = try!(Workspace::new(&ws.root().join("Cargo.toml"), opts.config));
+    ops::update_lockfile(&ws, &UpdateOptions {
+        to_update: &upgraded,
+        breaking: false,
+        ..*opts
+    })
+}
+
+/// Updates the lock file just enough that no locked package is affected by
+/// any of the advisories listed in the file at `path`.
+///
+/// Affected packages are first updated on their own. If that isn't enough,
+/// because whatever depends on them only accepts affected versions, the
+/// packages depending on them are updated as well, and so on until either
+/// nothing is affected or there's nothing left which can be updated.
+fn update_from_advisories(ws: &Workspace,
+                          opts: &UpdateOptions,
+                          previous_resolve: &Resolve,
+                          path: &Path) -> CargoResult<()> {
+    let config = opts.config;
+    let advisories = try!(cargo_audit::load_advisories(path, config));
+    let affected = |resolve: &Resolve| {
+        resolve.iter().filter_map(|pkg| {
+            advisories.iter().find(|a| a.affects(pkg)).map(|a| (pkg.clone(), a))
+        }).collect::<Vec<_>>()
+    };
+
+    let mut to_unlock = affected(previous_resolve).into_iter().map(|(pkg, _)| {
+        pkg
+    }).collect::<HashSet<_>>();
+    if to_unlock.is_empty() {
+        try!(config.shell().status("Checked", "no locked packages are \
+                                               affected by advisories"));
+        return Ok(())
+    }
+    let members = ws.members().map(|p| p.package_id().clone())
+                    .collect::<HashSet<_>>();
+
+    loop {
+        let mut registry = try!(PackageRegistry::new(config));
+        let sources = to_unlock.iter().map(|pkg| {
+            pkg.source_id().clone().with_precise(None)
+        }).collect::<Vec<_>>();
+        try!(registry.add_sources(&sources));
+        let to_avoid = to_unlock.iter().collect::<HashSet<_>>();
+        let resolve = try!(ops::resolve_with_previous(&mut registry,
+                                                      ws,
+                                                      Method::Everything,
+                                                      VersionOrder::Newest,
+                                                      Some(previous_resolve),
+                                                      Some(&to_avoid)));
+
+        let remaining = affected(&resolve);
+        if remaining.is_empty() {
+            return finish_update(ws, opts, previous_resolve, &resolve)
+        }
+
+        // Widen the update to everything depending on what's still affected,
+        // excluding the workspace members themselves since their
+        // requirements can't be changed here.
+        let dependents = reverse_deps(&resolve);
+        let before = to_unlock.len();
+        for &(ref pkg, _) in remaining.iter() {
+            for parent in dependents.get(pkg).into_iter().flat_map(|v| v) {
+                if !members.contains(*parent) {
+                    to_unlock.insert((*parent).clone());
                 }
-                b[i..].iter().take_while(|b| a == b).all(|b| {
-                    a.source_id().precise() != b.source_id().precise()
-                })
-            }).cloned().collect()
+            }
+        }
+        if to_unlock.len() > before {
+            continue
         }
 
-        // Map (package name, package source) to (removed versions, added versions).
-        let mut changes = BTreeMap::new();
-        let empty = (Vec::new(), Vec::new());
-        for dep in previous_resolve.iter() {
-            changes.entry(key(dep)).or_insert(empty.clone()).0.push(dep);
+        let mut msg = String::from("no compatible update escapes all \
+                                    advisories:\n");
+        for &(ref pkg, advisory) in remaining.iter() {
+            let parents = dependents.get(pkg).into_iter().flat_map(|v| v)
+                                    .map(|p| format!("`{}`", p))
+                                    .collect::<Vec<_>>();
+            msg.push_str(&format!("\n  `{}` is affected by {} ({})",
+                                  pkg, advisory.id, advisory.versions));
+            if !parents.is_empty() {
+                msg.push_str(&format!(", and no version outside of that \
+                                       range is accepted by {}",
+                                      parents.join(", ")));
+            }
         }
-        for dep in resolve.iter() {
-            changes.entry(key(dep)).or_insert(empty.clone()).1.push(dep);
+        bail!("{}", msg)
+    }
+}
+
+fn reverse_deps(resolve: &Resolve) -> HashMap<&PackageId, Vec<&PackageId>> {
+    let mut ret = HashMap::new();
+    for pkg in resolve.iter() {
+        for dep in resolve.deps(pkg) {
+            ret.entry(dep).or_insert(Vec::new()).push(pkg);
         }
+    }
+    ret
+}
 
-        for (_, v) in changes.iter_mut() {
-            let (ref mut old, ref mut new) = *v;
-            old.sort();
-            new.sort();
-            let removed = vec_subtract(old, new);
-            let added = vec_subtract(new, old);
-            *old = removed;
-            *new = added;
+/// Reports the changes an update makes to the lockfile, and writes it unless
+/// it's a dry run.
+fn finish_update(ws: &Workspace,
+                 opts: &UpdateOptions,
+                 previous_resolve: &Resolve,
+                 resolve: &Resolve) -> CargoResult<()> {
+    try!(print_changes(opts.config, previous_resolve, resolve));
+    if let Some(format) = opts.diff {
+        // A loaded lockfile doesn't know which features are enabled, so it's
+        // resolved again as it is to find out.
+        let mut registry = try!(PackageRegistry::new(opts.config));
+        let locked = try!(ops::resolve_with_previous(&mut registry, ws,
+                                                     Method::Everything,
+                                                     VersionOrder::Newest,
+                                                     Some(previous_resolve),
+                                                     None));
+        try!(print_diff(opts.config, format, previous_resolve, &locked, resolve));
+    }
+    if opts.dry_run {
+        return opts.config.shell().warn("not writing Cargo.lock, as this is \
+                                         a dry run")
+    }
+    ops::write_pkg_lockfile(ws, resolve)
+}
+
+/// Prints the packages which change between `previous_resolve` and `resolve`
+/// to stdout, including those whose version stays the same but which have
+/// other features enabled. `locked` is `previous_resolve` resolved again,
+/// which knows the features enabled before.
+fn print_diff(config: &Config,
+              format: MessageFormat,
+              previous_resolve: &Resolve,
+              locked: &Resolve,
+              resolve: &Resolve) -> CargoResult<()> {
+    let mut changes = Vec::new();
+    for (removed, added) in compare_dependency_graphs(previous_resolve, resolve) {
+        if removed.len() == 1 && added.len() == 1 {
+            changes.push(lock_change(Some((locked, removed[0])),
+                                     Some((resolve, added[0]))));
+        } else {
+            changes.extend(removed.iter().map(|id| {
+                lock_change(Some((locked, *id)), None)
+            }));
+            changes.extend(added.iter().map(|id| {
+                lock_change(None, Some((resolve, *id)))
+            }));
+        }
+    }
+    for id in resolve.iter() {
+        let kept = previous_resolve.iter().any(|old| {
+            old == id && old.source_id().precise() == id.source_id().precise()
+        });
+        if !kept {
+            continue
+        }
+        let change = lock_change(Some((locked, id)), Some((resolve, id)));
+        if !change.features_added.is_empty() || !change.features_removed.is_empty() {
+            changes.push(change);
+        }
+    }
+    changes.sort_by(|a, b| (&a.name, &a.source).cmp(&(&b.name, &b.source)));
+
+    if format == MessageFormat::Json {
+        println!("{}", json::encode(&LockDiff { changes: changes }).unwrap());
+        return Ok(())
+    }
+    for change in changes.iter() {
+        let line = match (&change.old_version, &change.new_version) {
+            (&Some(ref old), &Some(ref new)) if old != new => {
+                format!("~ {} {} -> {}", change.name, old, new)
+            }
+            (&Some(ref v), &Some(_)) => format!("~ {} {}", change.name, v),
+            (&Some(ref v), &None) => format!("- {} {}", change.name, v),
+            (&None, &Some(ref v)) => format!("+ {} {}", change.name, v),
+            (&None, &None) => continue,
+        };
+        try!(config.shell().say(line, BLACK));
+        for feature in change.features_added.iter() {
+            try!(config.shell().say(format!("    + feature `{}`", feature), BLACK));
+        }
+        for feature in change.features_removed.iter() {
+            try!(config.shell().say(format!("    - feature `{}`", feature), BLACK));
+        }
+    }
+    Ok(())
+}
+
+/// Describes how a package changes from `old`, the resolve it was locked in
+/// before and its id there, to `new`.
+fn lock_change(old: Option<(&Resolve, &PackageId)>,
+               new: Option<(&Resolve, &PackageId)>) -> LockChange {
+    let features = |side: Option<(&Resolve, &PackageId)>| {
+        side.and_then(|(resolve, id)| resolve.features(id)).map(|f| {
+            f.iter().cloned().collect::<BTreeSet<_>>()
+        }).unwrap_or(BTreeSet::new())
+    };
+    let version = |id: &PackageId| {
+        match id.source_id().precise() {
+            Some(rev) if id.source_id().is_git() => {
+                format!("{}#{}", id.version(), &rev[..cmp::min(rev.len(), 8)])
+            }
+            _ => id.version().to_string(),
+        }
+    };
+    let before = features(old);
+    let after = features(new);
+    let id = new.or(old).unwrap().1;
+    LockChange {
+        name: id.name().to_string(),
+        source: id.source_id().with_precise(None).to_url(),
+        old_version: old.map(|(_, id)| version(id)),
+        new_version: new.map(|(_, id)| version(id)),
+        features_added: after.difference(&before).cloned().collect(),
+        features_removed: before.difference(&after).cloned().collect(),
+    }
+}
+
+fn print_changes(config: &Config, previous_resolve: &Resolve, resolve: &Resolve)
+                 -> CargoResult<()> {
+    let print_change = |status: &str, msg: String| {
+        config.shell().status(status, msg)
+    };
+    for (removed, added) in compare_dependency_graphs(previous_resolve, resolve) {
+        if removed.len() == 1 && added.len() == 1 {
+            let msg = if removed[0].source_id().is_git() {
+                format!("{} -> #{}", removed[0],
+                        &added[0].source_id().precise().unwrap()[..8])
+            } else {
+                format!("{} -> v{}", removed[0], added[0].version())
+            };
+            try!(print_change("Updating", msg));
+        } else {
+            for package in removed.iter() {
+                try!(print_change("Removing", format!("{}", package)));
+            }
+            for package in added.iter() {
+                try!(print_change("Adding", format!("{}", package)));
+            }
         }
-        debug!("{:#?}", changes);
+    }
 
-        changes.into_iter().map(|(_, v)| v).collect()
+    Ok(())
+}
+
+fn compare_dependency_graphs<'a>(previous_resolve: &'a Resolve,
+                                 resolve: &'a Resolve) ->
+                                 Vec<(Vec<&'a PackageId>, Vec<&'a PackageId>)> {
+    fn key(dep: &PackageId) -> (&str, &SourceId) {
+        (dep.name(), dep.source_id())
+    }
+
+    // Removes all package ids in `b` from `a`. Note that this is somewhat
+    // more complicated because the equality for source ids does not take
+    // precise versions into account (e.g. git shas), but we want to take
+    // that into account here.
+    fn vec_subtract<'a>(a: &[&'a PackageId],
+                        b: &[&'a PackageId]) -> Vec<&'a PackageId> {
+        a.iter().filter(|a| {
+            // If this package id is not found in `b`, then it's definitely
+            // in the subtracted set
+            let i = match b.binary_search(a) {
+                Ok(i) => i,
+                Err(..) => return true,
+            };
+
+            // If we've found `a` in `b`, then we iterate over all instances
+            // (we know `b` is sorted) and see if they all have different
+            // precise versions. If so, then `a` isn't actually in `b` so
+            // we'll let it through.
+            //
+            // Note that we only check this for non-registry sources,
+            // however, as registries countain enough version information in
+            // the package id to disambiguate
+            if a.source_id().is_registry() {
+                return false
+            }
+            b[i..].iter().take_while(|b| a == b).all(|b| {
+                a.source_id().precise() != b.source_id().precise()
+            })
+        }).cloned().collect()
+    }
+
+    // Map (package name, package source) to (removed versions, added versions).
+    let mut changes = BTreeMap::new();
+    let empty = (Vec::new(), Vec::new());
+    for dep in previous_resolve.iter() {
+        changes.entry(key(dep)).or_insert(empty.clone()).0.push(dep);
+    }
+    for dep in resolve.iter() {
+        changes.entry(key(dep)).or_insert(empty.clone()).1.push(dep);
     }
+
+    for (_, v) in changes.iter_mut() {
+        let (ref mut old, ref mut new) = *v;
+        old.sort();
+        new.sort();
+        let removed = vec_subtract(old, new);
+        let added = vec_subtract(new, old);
+        *old = removed;
+        *new = added;
+    }
+    debug!("{:#?}", changes);
+
+    changes.into_iter().map(|(_, v)| v).collect()
 }
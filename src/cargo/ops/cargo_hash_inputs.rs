@@ -0,0 +1,103 @@
+//! Hashing the inputs of a workspace for use as a cache key.
+//!
+//! The hash covers the root manifest, the lockfile, all configuration files
+//! and the manifests of every local package the selected packages depend on,
+//! optionally along with their source files. Paths are hashed relative to the
+//! package they belong to, so the same inputs hash the same wherever the
+//! workspace is checked out.
+
+use std::collections::{BTreeMap, HashSet};
+
+use rustc_serialize::hex::ToHex;
+
+use core::{Package, PackageId, PackageIdSpec, Workspace};
+use ops;
+use sources::PathSource;
+use util::{self, paths, human, CargoResult, Sha256};
+
+pub struct HashInputsOptions<'a> {
+    /// Packages whose inputs are hashed, all workspace members if empty.
+    pub spec: &'a [String],
+    /// Whether to also hash the source files of local packages.
+    pub sources: bool,
+}
+
+/// Returns a hex-encoded hash of the inputs of the selected packages.
+pub fn hash_inputs(ws: &Workspace, opts: &HashInputsOptions)
+                   -> CargoResult<String> {
+    let config = ws.config();
+    let resolve = match try!(ops::load_pkg_lockfile(ws)) {
+        Some(resolve) => resolve,
+        None => bail!("a Cargo.lock must exist for this command"),
+    };
+
+    // Every input is keyed by a name which doesn't depend on where the
+    // workspace lives, and hashed in order of those names.
+    let mut inputs = BTreeMap::new();
+    inputs.insert("Cargo.lock".to_string(), ws.root().join("Cargo.lock"));
+    inputs.insert("Cargo.toml".to_string(), ws.root().join("Cargo.toml"));
+    for (i, file) in try!(config.config_files()).into_iter().enumerate() {
+        inputs.insert(format!("config {}", i), file);
+    }
+
+    let roots = if opts.spec.is_empty() {
+        ws.members().map(|p| p.package_id()).collect::<Vec<_>>()
+    } else {
+        try!(opts.spec.iter().map(|spec| {
+            PackageIdSpec::query_str(spec, resolve.iter())
+        }).collect::<CargoResult<Vec<_>>>())
+    };
+
+    // Only local packages need to be looked at, everything else is pinned
+    // down by the lockfile already.
+    let mut visited = HashSet::new();
+    let mut todo = roots;
+    while let Some(id) = todo.pop() {
+        if !id.source_id().is_path() || !visited.insert(id) {
+            continue
+        }
+        todo.extend(resolve.deps(id));
+
+        let pkg = try!(local_package(ws, id));
+        let prefix = format!("{} {}", pkg.name(), pkg.version());
+        inputs.insert(format!("{}/Cargo.toml", prefix),
+                      pkg.manifest_path().to_path_buf());
+        if opts.sources {
+            let src = PathSource::new(pkg.root(), id.source_id(), config);
+            for file in try!(src.list_files(&pkg)) {
+                let relative = util::without_prefix(&file, pkg.root()).unwrap()
+                                    .to_string_lossy().replace("\\", "/");
+                inputs.insert(format!("{}/{}", prefix, relative), file);
+            }
+        }
+    }
+
+    let mut h = Sha256::new();
+    for (name, path) in inputs.iter() {
+        if !path.exists() {
+            continue
+        }
+        let contents = try!(paths::read_bytes(path));
+        try!(config.shell().verbose(|shell| {
+            shell.status("Hashing", name)
+        }));
+        h.update(name.as_bytes());
+        h.update(&[0]);
+        h.update(&(contents.len() as u64).to_string().as_bytes());
+        h.update(&[0]);
+        h.update(&contents);
+    }
+    Ok(h.finish().to_hex())
+}
+
+fn local_package(ws: &Workspace, id: &PackageId) -> CargoResult<Package> {
+    if let Some(pkg) = ws.members().find(|p| p.package_id() == id) {
+        return Ok(pkg.clone())
+    }
+    let root = try!(id.source_id().url().to_file_path().map_err(|()| {
+        human(format!("invalid path for `{}`", id))
+    }));
+    let (pkg, _) = try!(ops::read_package(&root.join("Cargo.toml"),
+                                          id.source_id(), ws.config()));
+    Ok(pkg)
+}
@@ -1,5 +1,5 @@
 use std::collections::btree_map::Entry;
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::ffi::OsString;
 use std::fs::{self, File};
@@ -7,15 +7,21 @@ use std::io::prelude::*;
 use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
 
+use curl;
+use flate2::read::GzDecoder;
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json;
+use semver::Version;
+use tar::Archive;
 use tempdir::TempDir;
 use toml;
 
 use core::{SourceId, Source, Package, Dependency, PackageIdSpec};
-use core::{PackageId, Workspace};
+use core::{PackageId, Prebuilt, Workspace};
 use ops::{self, CompileFilter};
 use sources::{GitSource, PathSource, SourceConfigMap};
-use util::{CargoResult, ChainError, Config, human, internal};
-use util::{Filesystem, FileLock};
+use util::{CargoResult, ChainError, Config, human, internal, network};
+use util::{paths, Filesystem, FileLock, Sha256, ToUrl};
 
 #[derive(RustcDecodable, RustcEncodable)]
 enum CrateListing {
@@ -28,6 +34,58 @@ struct CrateListingV1 {
     v1: BTreeMap<PackageId, BTreeSet<String>>,
 }
 
+/// How each installed package was installed, so it can be installed again
+/// the same way, here or on another machine with `--from-metadata`. It's kept
+/// in `.crates2.json`, next to `.crates.toml` which older versions of Cargo
+/// read too. The package IDs pin the exact version and source, down to the
+/// revision of git repositories.
+#[derive(RustcDecodable, RustcEncodable, Default)]
+struct CrateListingV2 {
+    installs: BTreeMap<PackageId, InstallInfo>,
+}
+
+#[derive(RustcDecodable, RustcEncodable, Clone, Default)]
+struct InstallInfo {
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool,
+    debug: bool,
+    /// The binaries and examples asked for with `--bin` and `--example`,
+    /// none meaning every binary.
+    bins: Vec<String>,
+    examples: Vec<String>,
+    /// The fields below weren't recorded by the first versions of this
+    /// listing, hence the options.
+    target: Option<String>,
+    /// The first line of `rustc -vV`.
+    rustc: Option<String>,
+    /// The binaries installed, as in `.crates.toml`.
+    binaries: Option<BTreeSet<String>>,
+}
+
+impl InstallInfo {
+    fn new(opts: &ops::CompileOptions) -> CargoResult<InstallInfo> {
+        let (bins, examples) = match opts.filter {
+            CompileFilter::Everything => (Vec::new(), Vec::new()),
+            CompileFilter::Only { bins, examples, .. } => {
+                (bins.to_vec(), examples.to_vec())
+            }
+        };
+        let rustc = try!(opts.config.rustc());
+        Ok(InstallInfo {
+            features: opts.features.to_vec(),
+            all_features: opts.all_features,
+            no_default_features: opts.no_default_features,
+            debug: !opts.release,
+            bins: bins,
+            examples: examples,
+            target: opts.target.map(|s| s.to_string()),
+            rustc: rustc.verbose_version.lines().next().map(|s| s.to_string()),
+            binaries: None,
+        })
+    }
+}
+
 struct Transaction {
     bins: Vec<PathBuf>,
 }
@@ -47,37 +105,49 @@ impl Drop for Transaction {
 }
 
 pub fn install(root: Option<&str>,
-               krate: Option<&str>,
+               krates: &[&str],
                source_id: &SourceId,
                vers: Option<&str>,
                opts: &ops::CompileOptions,
-               force: bool) -> CargoResult<()> {
+               force: bool,
+               prebuilt: bool) -> CargoResult<()> {
     let config = opts.config;
     let root = try!(resolve_root(root, config));
-    let map = try!(SourceConfigMap::new(config));
-    let (pkg, source) = if source_id.is_git() {
-        try!(select_pkg(GitSource::new(source_id, config), source_id,
-                        krate, vers, &mut |git| git.read_packages()))
-    } else if source_id.is_path() {
-        let path = source_id.url().to_file_path().ok()
-                            .expect("path sources must have a valid path");
-        let mut src = PathSource::new(&path, source_id, config);
-        try!(src.update().chain_error(|| {
-            human(format!("`{}` is not a crate root; specify a crate to \
-                           install from crates.io, or use --path or --git to \
-                           specify an alternate source", path.display()))
-        }));
-        try!(select_pkg(PathSource::new(&path, source_id, config),
-                        source_id, krate, vers,
-                        &mut |path| path.read_packages()))
+    let prebuilt = prebuilt ||
+        try!(config.get_bool("install.prebuilt")).map(|v| v.val).unwrap_or(false);
+    if krates.len() > 1 {
+        try!(install_many(&root, krates, source_id, vers, opts, force,
+                          prebuilt));
     } else {
-        try!(select_pkg(try!(map.load(source_id)),
-                        source_id, krate, vers,
-                        &mut |_| Err(human("must specify a crate to install from \
-                                            crates.io, or use --path or --git to \
-                                            specify alternate source"))))
-    };
+        try!(install_one(&root, krates.first().map(|s| *s), source_id, vers,
+                         opts, force, prebuilt));
+    }
+
+    // Print a warning that if this directory isn't in PATH that they won't be
+    // able to run these commands.
+    let dst = root.join("bin").into_path_unlocked();
+    let path = env::var_os("PATH").unwrap_or(OsString::new());
+    for path in env::split_paths(&path) {
+        if path == dst {
+            return Ok(())
+        }
+    }
 
+    try!(config.shell().warn(&format!("be sure to add `{}` to your PATH to be \
+                                       able to run the installed binaries",
+                                      dst.display())));
+    Ok(())
+}
+
+fn install_one(root: &Filesystem,
+               krate: Option<&str>,
+               source_id: &SourceId,
+               vers: Option<&str>,
+               opts: &ops::CompileOptions,
+               force: bool,
+               prebuilt: bool) -> CargoResult<()> {
+    let config = opts.config;
+    let (pkg, source) = try!(select_source_pkg(config, source_id, krate, vers));
 
     let mut td_opt = None;
     let overidden_target_dir = if source_id.is_path() {
@@ -97,22 +167,174 @@ pub fn install(root: Option<&str>,
     // We have to check this again afterwards, but may as well avoid building
     // anything if we're gonna throw it away anyway.
     {
-        let metadata = try!(metadata(config, &root));
+        let metadata = try!(metadata(config, root));
         let list = try!(read_crate_list(metadata.file()));
         let dst = metadata.parent().join("bin");
         try!(check_overwrites(&dst, pkg, &opts.filter, &list, force));
     }
 
-    let compile = try!(ops::compile_ws(&ws, Some(source), opts).chain_error(|| {
-        if let Some(td) = td_opt.take() {
-            // preserve the temporary directory, so the user can inspect it
-            td.into_path();
+    let prebuilt_dir = try!(TempDir::new("cargo-install-prebuilt"));
+    let prebuilt = if prebuilt && !source_id.is_path() {
+        try!(prebuilt_binaries(pkg, opts, prebuilt_dir.path()))
+    } else {
+        None
+    };
+    let compile;
+    let bins = match prebuilt {
+        Some(ref bins) => bins,
+        None => {
+            compile = try!(ops::compile_ws(&ws, Some(source), opts).chain_error(|| {
+                if let Some(td) = td_opt.take() {
+                    // preserve the temporary directory, so the user can inspect it
+                    td.into_path();
+                }
+
+                human(format!("failed to compile `{}`, intermediate artifacts can be \
+                               found at `{}`", pkg, ws.target_dir().display()))
+            }));
+            &compile.binaries
         }
+    };
+    try!(install_binaries(root, pkg, opts, bins, !source_id.is_path(), force));
 
-        human(format!("failed to compile `{}`, intermediate artifacts can be \
-                       found at `{}`", pkg, ws.target_dir().display()))
-    }));
-    let binaries: Vec<(&str, &Path)> = try!(compile.binaries.iter().map(|bin| {
+    if !source_id.is_path() {
+        // Don't bother grabbing a lock as we're going to blow it all away
+        // anyway.
+        let target_dir = ws.target_dir().into_path_unlocked();
+        if target_dir.exists() {
+            try!(fs::remove_dir_all(&target_dir));
+        }
+    }
+    Ok(())
+}
+
+/// Installs several crates from `source_id`. Each one is resolved on its own,
+/// as if it were installed alone, and they're then built concurrently in a
+/// shared target directory, the dependencies they have in common with the
+/// same features only being built once.
+fn install_many(root: &Filesystem,
+                krates: &[&str],
+                source_id: &SourceId,
+                vers: Option<&str>,
+                opts: &ops::CompileOptions,
+                force: bool,
+                prebuilt: bool) -> CargoResult<()> {
+    let config = opts.config;
+    if vers.is_some() {
+        bail!("--vers can't be used when installing several crates")
+    }
+    if let CompileFilter::Only { .. } = opts.filter {
+        bail!("--bin and --example can't be used when installing several crates")
+    }
+
+    let mut pkgs = Vec::new();
+    for krate in krates {
+        let (pkg, src) = try!(select_source_pkg(config, source_id, Some(*krate),
+                                                None));
+        if pkgs.iter().any(|&(ref p, _): &(Package, _)| {
+            p.package_id() == pkg.package_id()
+        }) {
+            continue
+        }
+        pkgs.push((pkg, src));
+    }
+
+    // Preflight checks, as with a single crate, and the crates can't
+    // overwrite each other's binaries either.
+    {
+        let metadata = try!(metadata(config, root));
+        let list = try!(read_crate_list(metadata.file()));
+        let dst = metadata.parent().join("bin");
+        let mut names = HashMap::new();
+        for &(ref pkg, _) in pkgs.iter() {
+            try!(check_overwrites(&dst, pkg, &opts.filter, &list, force));
+            for target in pkg.targets().iter().filter(|t| t.is_bin()) {
+                if let Some(other) = names.insert(target.name(), pkg.package_id()) {
+                    bail!("`{}` and `{}` both have a binary named `{}`",
+                          other, pkg.package_id(), target.name())
+                }
+            }
+        }
+    }
+
+    let mut td_opt = None;
+    let overidden_target_dir = if source_id.is_path() {
+        None
+    } else if let Ok(td) = TempDir::new("cargo-install") {
+        let p = td.path().to_owned();
+        td_opt = Some(td);
+        Some(Filesystem::new(p))
+    } else {
+        Some(Filesystem::new(config.cwd().join("target-install")))
+    };
+
+    let prebuilt_dir = try!(TempDir::new("cargo-install-prebuilt"));
+    let mut wss = Vec::new();
+    let mut sources = Vec::new();
+    for (pkg, source) in pkgs {
+        if prebuilt && !source_id.is_path() {
+            let dir = prebuilt_dir.path().join(pkg.name());
+            try!(fs::create_dir_all(&dir));
+            if let Some(bins) = try!(prebuilt_binaries(&pkg, opts, &dir)) {
+                try!(install_binaries(root, &pkg, opts, &bins, true, force));
+                continue
+            }
+        }
+
+        wss.push(try!(Workspace::one(pkg, config, overidden_target_dir.clone())));
+        sources.push(source);
+    }
+
+    if !wss.is_empty() {
+        let compiles = try!(ops::compile_many(&wss, sources, opts).chain_error(|| {
+            if let Some(td) = td_opt.take() {
+                // preserve the temporary directory, so the user can inspect it
+                td.into_path();
+            }
+
+            let names = wss.iter().filter_map(|ws| ws.current().ok()).map(|pkg| {
+                pkg.to_string()
+            }).collect::<Vec<_>>();
+            human(format!("failed to compile `{}`, intermediate artifacts can be \
+                           found at `{}`", names.join("`, `"),
+                          wss[0].target_dir().display()))
+        }));
+
+        // The crates can't have binaries of the same name, so each one's are
+        // told apart by their names.
+        for ws in wss.iter() {
+            let pkg = try!(ws.current());
+            let bins = compiles.iter().flat_map(|c| c.binaries.iter()).filter(|bin| {
+                let stem = bin.file_stem().and_then(|s| s.to_str());
+                pkg.targets().iter().any(|t| t.is_bin() && stem == Some(t.name()))
+            }).cloned().collect::<Vec<_>>();
+            try!(install_binaries(root, pkg, opts, &bins, !source_id.is_path(),
+                                  force));
+        }
+    }
+
+    if let Some(target_dir) = overidden_target_dir {
+        // Don't bother grabbing a lock as we're going to blow it all away
+        // anyway.
+        let target_dir = target_dir.into_path_unlocked();
+        if target_dir.exists() {
+            try!(fs::remove_dir_all(&target_dir));
+        }
+    }
+    Ok(())
+}
+
+/// Moves the binaries `bins` built or downloaded for `pkg` into the `bin`
+/// folder of `root` and records them, moving rather than copying them when
+/// they're `transient`.
+fn install_binaries(root: &Filesystem,
+                    pkg: &Package,
+                    opts: &ops::CompileOptions,
+                    bins: &[PathBuf],
+                    transient: bool,
+                    force: bool) -> CargoResult<()> {
+    let config = opts.config;
+    let binaries: Vec<(&str, &Path)> = try!(bins.iter().map(|bin| {
         let name = bin.file_name().unwrap();
         if let Some(s) = name.to_str() {
             Ok((s, bin.as_ref()))
@@ -121,7 +343,7 @@ pub fn install(root: Option<&str>,
         }
     }).collect::<CargoResult<_>>());
 
-    let metadata = try!(metadata(config, &root));
+    let metadata = try!(metadata(config, root));
     let mut list = try!(read_crate_list(metadata.file()));
     let dst = metadata.parent().join("bin");
     let duplicates = try!(check_overwrites(&dst, pkg, &opts.filter,
@@ -136,7 +358,7 @@ pub fn install(root: Option<&str>,
     for &(bin, src) in binaries.iter() {
         let dst = staging_dir.path().join(bin);
         // Try to move if `target_dir` is transient.
-        if !source_id.is_path() {
+        if transient {
             if fs::rename(src, &dst).is_ok() {
                 continue
             }
@@ -212,7 +434,15 @@ pub fn install(root: Option<&str>,
                .extend(to_install.iter().map(|s| s.to_string()));
     }
 
-    let write_result = write_crate_list(metadata.file(), list);
+    let mut v2 = try!(read_crate_list_v2(&metadata));
+    if result.is_ok() {
+        v2.installs.insert(pkg.package_id().clone(), try!(InstallInfo::new(opts)));
+    }
+    sync_crate_list_v2(&mut v2, &list);
+
+    let write_result = write_crate_list(metadata.file(), list).and_then(|()| {
+        write_crate_list_v2(&metadata, &v2)
+    });
     match write_result {
         // Replacement error (if any) isn't actually caused by write error
         // but this seems to be the only way to show both.
@@ -222,26 +452,129 @@ pub fn install(root: Option<&str>,
 
     // Reaching here means all actions have succeeded. Clean up.
     installed.success();
-    if !source_id.is_path() {
-        // Don't bother grabbing a lock as we're going to blow it all away
-        // anyway.
-        let target_dir = ws.target_dir().into_path_unlocked();
-        try!(fs::remove_dir_all(&target_dir));
+    Ok(())
+}
+
+/// Selects the package `krate`, or the only one there is, from `source_id`.
+fn select_source_pkg<'a>(config: &'a Config,
+                         source_id: &SourceId,
+                         krate: Option<&str>,
+                         vers: Option<&str>)
+                         -> CargoResult<(Package, Box<Source + 'a>)> {
+    let map = try!(SourceConfigMap::new(config));
+    if source_id.is_git() {
+        select_pkg(GitSource::new(source_id, config), source_id,
+                   krate, vers, &mut |git| git.read_packages())
+    } else if source_id.is_path() {
+        let path = source_id.url().to_file_path().ok()
+                            .expect("path sources must have a valid path");
+        let mut src = PathSource::new(&path, source_id, config);
+        try!(src.update().chain_error(|| {
+            human(format!("`{}` is not a crate root; specify a crate to \
+                           install from crates.io, or use --path or --git to \
+                           specify an alternate source", path.display()))
+        }));
+        select_pkg(PathSource::new(&path, source_id, config),
+                   source_id, krate, vers,
+                   &mut |path| path.read_packages())
+    } else {
+        select_pkg(try!(map.load(source_id)),
+                   source_id, krate, vers,
+                   &mut |_| Err(human("must specify a crate to install from \
+                                       crates.io, or use --path or --git to \
+                                       specify alternate source")))
     }
+}
 
-    // Print a warning that if this directory isn't in PATH that they won't be
-    // able to run these commands.
-    let path = env::var_os("PATH").unwrap_or(OsString::new());
-    for path in env::split_paths(&path) {
-        if path == dst {
-            return Ok(())
+/// Downloads the binaries which `pkg` declares were built ahead of time for
+/// the host into `dir`, as long as they're what would be built: the release
+/// binaries with the default features.
+///
+/// The archive of the binaries is only trusted through its checksum in the
+/// manifest, which is as trustworthy as the rest of the package: checked
+/// against the registry index, and against the signing keys of the registry
+/// if there are any. When the binaries can't be used they're built instead,
+/// after a warning, so `None` is returned.
+fn prebuilt_binaries(pkg: &Package, opts: &ops::CompileOptions, dir: &Path)
+                     -> CargoResult<Option<Vec<PathBuf>>> {
+    let config = opts.config;
+    if !opts.release || opts.target.is_some() || !opts.features.is_empty() ||
+       opts.all_features || opts.no_default_features {
+        return Ok(None)
+    }
+    let bins = match opts.filter {
+        CompileFilter::Everything => {
+            pkg.targets().iter().filter(|t| t.is_bin())
+               .map(|t| t.name().to_string()).collect::<Vec<_>>()
+        }
+        CompileFilter::Only { bins, examples, .. } if examples.is_empty() => {
+            bins.to_vec()
+        }
+        CompileFilter::Only { .. } => return Ok(None),
+    };
+    let host = &try!(config.rustc()).host;
+    let prebuilt = match pkg.manifest().prebuilt().get(host) {
+        Some(prebuilt) => prebuilt,
+        None => return Ok(None),
+    };
+
+    try!(config.shell().status("Downloading",
+                               format!("{} prebuilt for `{}`", pkg, host)));
+    match download_prebuilt(config, prebuilt, &bins, dir) {
+        Ok(paths) => Ok(Some(paths)),
+        Err(e) => {
+            try!(config.shell().warn(format!("the prebuilt binaries of `{}` \
+                                              can't be used, building them \
+                                              instead: {}", pkg, e)));
+            Ok(None)
         }
     }
+}
 
-    try!(config.shell().warn(&format!("be sure to add `{}` to your PATH to be \
-                                       able to run the installed binaries",
-                                      dst.display())));
-    Ok(())
+fn download_prebuilt(config: &Config,
+                     prebuilt: &Prebuilt,
+                     bins: &[String],
+                     dir: &Path) -> CargoResult<Vec<PathBuf>> {
+    let url = try!((&prebuilt.url[..]).to_url());
+    try!(network::check_allowed(config, &url));
+    let mut handle = try!(ops::http_handle(config));
+    try!(handle.get(true));
+    try!(handle.url(url.as_str()));
+    try!(handle.follow_location(true));
+    let body = try!(network::with_retry(config, || -> Result<_, curl::Error> {
+        let mut body = Vec::new();
+        {
+            let mut handle = handle.transfer();
+            try!(handle.write_function(|buf| {
+                body.extend_from_slice(buf);
+                Ok(buf.len())
+            }));
+            try!(handle.perform());
+        }
+        Ok(body)
+    }));
+    let code = try!(handle.response_code());
+    if code != 0 && code != 200 {
+        bail!("failed to get 200 response from `{}`, got {}", url, code)
+    }
+
+    let mut hash = Sha256::new();
+    hash.update(&body);
+    if hash.finish().to_hex() != prebuilt.sha256 {
+        bail!("the archive downloaded from `{}` doesn't match the `sha256` \
+               checksum of the manifest", url)
+    }
+    try!(Archive::new(try!(GzDecoder::new(&body[..]))).unpack(dir).chain_error(|| {
+        human(format!("failed to unpack the archive downloaded from `{}`", url))
+    }));
+    bins.iter().map(|bin| {
+        let path = dir.join(format!("{}{}", bin, env::consts::EXE_SUFFIX));
+        if !path.is_file() {
+            bail!("the archive downloaded from `{}` doesn't contain the \
+                   binary `{}`", url, bin)
+        }
+        Ok(path)
+    }).collect()
 }
 
 fn select_pkg<'a, T>(mut source: T,
@@ -405,10 +738,52 @@ fn write_crate_list(mut file: &File, listing: CrateListingV1) -> CargoResult<()>
     })
 }
 
-pub fn install_list(dst: Option<&str>, config: &Config) -> CargoResult<()> {
+/// Reads the `.crates2.json` file next to the `.crates.toml` file locked by
+/// `metadata`.
+fn read_crate_list_v2(metadata: &FileLock) -> CargoResult<CrateListingV2> {
+    let path = metadata.parent().join(".crates2.json");
+    if !path.exists() {
+        return Ok(CrateListingV2::default())
+    }
+    (|| -> CargoResult<_> {
+        Ok(try!(json::decode(&try!(paths::read(&path)))))
+    }).chain_error(|| {
+        human(format!("failed to parse crate metadata at `{}`", path.display()))
+    })
+}
+
+fn write_crate_list_v2(metadata: &FileLock, listing: &CrateListingV2)
+                       -> CargoResult<()> {
+    let path = metadata.parent().join(".crates2.json");
+    paths::write(&path, try!(json::encode(listing)).as_bytes())
+}
+
+/// Forgets how the packages which aren't installed anymore were installed.
+fn sync_crate_list_v2(v2: &mut CrateListingV2, v1: &CrateListingV1) {
+    let gone = v2.installs.keys().filter(|id| !v1.v1.contains_key(id))
+                 .cloned().collect::<Vec<_>>();
+    for id in gone {
+        v2.installs.remove(&id);
+    }
+    for (id, info) in v2.installs.iter_mut() {
+        info.binaries = v1.v1.get(id).cloned();
+    }
+}
+
+pub fn install_list(dst: Option<&str>, outdated: bool, config: &Config)
+                    -> CargoResult<()> {
     let dst = try!(resolve_root(dst, config));
-    let dst = try!(metadata(config, &dst));
-    let list = try!(read_crate_list(dst.file()));
+    let list = {
+        let metadata = try!(metadata(config, &dst));
+        try!(read_crate_list(metadata.file()))
+    };
+    if outdated {
+        for (id, version) in try!(outdated_installs(config, &list)) {
+            try!(writeln!(config.shell().out(), "{}: v{} is available",
+                          id, version));
+        }
+        return Ok(())
+    }
     let mut shell = config.shell();
     let out = shell.out();
     for (k, v) in list.v1.iter() {
@@ -420,6 +795,146 @@ pub fn install_list(dst: Option<&str>, config: &Config) -> CargoResult<()> {
     Ok(())
 }
 
+/// Installs the newest version of each installed package from a registry
+/// which has a newer one, as it was installed: with the same features,
+/// profile and binaries. The other options come from `opts`.
+pub fn install_upgrade_all(root: Option<&str>,
+                           opts: &ops::CompileOptions,
+                           prebuilt: bool) -> CargoResult<()> {
+    let config = opts.config;
+    let dst = try!(resolve_root(root, config));
+    let (list, v2) = {
+        let metadata = try!(metadata(config, &dst));
+        (try!(read_crate_list(metadata.file())),
+         try!(read_crate_list_v2(&metadata)))
+    };
+    let outdated = try!(outdated_installs(config, &list));
+    if outdated.is_empty() {
+        try!(config.shell().status("Finished",
+                                   "all installed packages are up to date"));
+        return Ok(())
+    }
+    for (id, version) in outdated {
+        // Packages installed before the way they were installed was recorded
+        // are installed again with the defaults.
+        let info = v2.installs.get(&id).cloned().unwrap_or_default();
+        try!(config.shell().status("Upgrading",
+                                   format!("{} to v{}", id, version)));
+        try!(reinstall(root, &id, &id.source_id().with_precise(None), &version,
+                       &info, opts, true, prebuilt));
+    }
+    Ok(())
+}
+
+/// Installs again the packages listed in the `.crates2.json` file `path`,
+/// usually from another machine, each with its exact version and source and
+/// the way it was installed there. The other options come from `opts`.
+pub fn install_from_metadata(root: Option<&str>,
+                             path: &Path,
+                             opts: &ops::CompileOptions,
+                             force: bool,
+                             prebuilt: bool) -> CargoResult<()> {
+    let config = opts.config;
+    let listing: CrateListingV2 = try!((|| -> CargoResult<_> {
+        Ok(try!(json::decode(&try!(paths::read(path)))))
+    }).chain_error(|| {
+        human(format!("failed to parse crate metadata at `{}`", path.display()))
+    }));
+    let rustc = try!(config.rustc());
+    let rustc = rustc.verbose_version.lines().next().unwrap_or("");
+    for (id, info) in listing.installs.iter() {
+        if let Some(ref installed_with) = info.rustc {
+            if installed_with != rustc {
+                try!(config.shell().warn(&format!("`{}` was installed with `{}`, \
+                                                   but `{}` is used now",
+                                                  id, installed_with, rustc)));
+            }
+        }
+        // Registries are queried for the exact version, while other sources
+        // are pinned by their precise revision.
+        let source_id = if id.source_id().is_registry() {
+            id.source_id().with_precise(None)
+        } else {
+            id.source_id().clone()
+        };
+        try!(reinstall(root, id, &source_id, id.version(), info, opts, force,
+                       prebuilt));
+    }
+    Ok(())
+}
+
+/// Installs `version` of the package `id` from `source_id` as described by
+/// `info`.
+fn reinstall(root: Option<&str>,
+             id: &PackageId,
+             source_id: &SourceId,
+             version: &Version,
+             info: &InstallInfo,
+             opts: &ops::CompileOptions,
+             force: bool,
+             prebuilt: bool) -> CargoResult<()> {
+    let filter = if info.bins.is_empty() && info.examples.is_empty() {
+        CompileFilter::Everything
+    } else {
+        CompileFilter::Only {
+            lib: false,
+            bins: &info.bins,
+            examples: &info.examples,
+            tests: &[],
+            benches: &[],
+        }
+    };
+    let opts = ops::CompileOptions {
+        config: opts.config,
+        jobs: opts.jobs,
+        target: info.target.as_ref().map(|s| &s[..]),
+        features: &info.features,
+        all_features: info.all_features,
+        no_default_features: info.no_default_features,
+        spec: &[],
+        exec_engine: None,
+        mode: ops::CompileMode::Build,
+        release: !info.debug,
+        filter: filter,
+        target_rustc_args: None,
+        emit_ninja: None,
+        message_format: opts.message_format,
+        self_profile: None,
+        preserve_old_artifacts: false,
+        sbom: None,
+        shard: None,
+        target_rustdoc_args: None,
+    };
+    install(root, &[id.name()], source_id, Some(&format!("={}", version)),
+            &opts, force, prebuilt)
+}
+
+/// The installed packages from registries with a newer version, along with
+/// the newest one. Pre-releases aren't considered.
+fn outdated_installs(config: &Config, list: &CrateListingV1)
+                     -> CargoResult<Vec<(PackageId, Version)>> {
+    let sources = try!(SourceConfigMap::new(config));
+    let mut loaded = HashMap::new();
+    let mut ret = Vec::new();
+    for id in list.v1.keys().filter(|id| id.source_id().is_registry()) {
+        let source_id = id.source_id().with_precise(None);
+        if !loaded.contains_key(&source_id) {
+            let mut source = try!(sources.load(&source_id));
+            try!(source.update());
+            loaded.insert(source_id.clone(), source);
+        }
+        let source = loaded.get_mut(&source_id).unwrap();
+        let dep = try!(Dependency::parse(id.name(), None, &source_id));
+        let newest = try!(source.query(&dep)).iter().map(|s| s.version())
+                         .filter(|v| v.pre.is_empty() && *v > id.version())
+                         .max().cloned();
+        if let Some(version) = newest {
+            ret.push((id.clone(), version));
+        }
+    }
+    Ok(ret)
+}
+
 pub fn uninstall(root: Option<&str>,
                  spec: &str,
                  bins: &[String],
@@ -471,7 +986,10 @@ pub fn uninstall(root: Option<&str>,
             installed.remove();
         }
     }
+    let mut v2 = try!(read_crate_list_v2(&crate_metadata));
+    sync_crate_list_v2(&mut v2, &metadata);
     try!(write_crate_list(crate_metadata.file(), metadata));
+    try!(write_crate_list_v2(&crate_metadata, &v2));
     for bin in to_remove {
         try!(config.shell().status("Removing", bin.display()));
         try!(fs::remove_file(bin));
@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::Path;
+
+use rustc_serialize::json;
+
+use core::Workspace;
+use ops::{self, MessageFormat};
+use sources::SourceConfigMap;
+use sources::registry::{crate_file, file_path, index_entry};
+use util::{CargoResult, ChainError, human};
+
+/// The part of an index entry telling which version it's about.
+#[derive(RustcDecodable)]
+struct IndexVersion {
+    vers: String,
+}
+
+/// Executes `cargo local-registry`, adding the packages from registries which
+/// `ws` depends on to the local registry `dst`, created if it doesn't exist.
+///
+/// The `.crate` files are copied as they were downloaded and the index entries
+/// as they are in the index of their registry, so the checksums in Cargo.lock
+/// still apply once the local registry replaces the registry.
+pub fn local_registry(ws: &Workspace, dst: &Path) -> CargoResult<()> {
+    let config = ws.config();
+    let (resolve, _packages) = try!(ops::fetch(ws, MessageFormat::Human));
+    let sources = try!(SourceConfigMap::new(config));
+    try!(fs::create_dir_all(dst.join("index")).chain_error(|| {
+        human(format!("failed to create the local registry `{}`",
+                      dst.display()))
+    }));
+
+    let mut ids = resolve.iter().collect::<Vec<_>>();
+    ids.sort();
+    let mut entries = BTreeMap::new();
+    for id in ids {
+        if id.source_id().is_git() {
+            try!(config.shell().warn(format!("`{}` comes from a git repository, \
+                                              it isn't added to the local \
+                                              registry", id)));
+            continue
+        }
+        if !id.source_id().is_registry() {
+            continue
+        }
+        let actual = try!(sources.replacement(id.source_id()));
+        if !actual.is_registry() {
+            bail!("can't add `{}` to the local registry, it comes from `{}` \
+                   which has no `.crate` files", id, actual)
+        }
+        let entry = try!(index_entry(config, &actual, id));
+        entries.entry(id.name()).or_insert(Vec::new())
+               .push((id.version().to_string(), entry));
+
+        let src = crate_file(config, &actual, id);
+        let file = dst.join(format!("{}-{}.crate", id.name(), id.version()));
+        if src != file {
+            try!(fs::copy(&src, &file).chain_error(|| {
+                human(format!("failed to copy `{}` to `{}`", src.display(),
+                              file.display()))
+            }));
+        }
+    }
+
+    let mut count = 0;
+    for (name, new) in entries {
+        let path = dst.join("index").join(file_path(name));
+        try!((|| -> CargoResult<()> {
+            try!(fs::create_dir_all(path.parent().unwrap()));
+            let mut contents = String::new();
+            if path.exists() {
+                try!(try!(File::open(&path)).read_to_string(&mut contents));
+            }
+            let mut have = HashSet::new();
+            for line in contents.lines().filter(|l| l.trim().len() > 0) {
+                let entry: IndexVersion = try!(json::decode(line));
+                have.insert(entry.vers);
+            }
+            if !contents.is_empty() && !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            for (vers, line) in new {
+                if have.insert(vers) {
+                    contents.push_str(&line);
+                    contents.push('\n');
+                }
+                count += 1;
+            }
+            try!(try!(File::create(&path)).write_all(contents.as_bytes()));
+            Ok(())
+        })().chain_error(|| {
+            human(format!("failed to update the index file `{}`",
+                          path.display()))
+        }));
+    }
+
+    config.shell().status("Added", format!("{} packages to the local registry \
+                                            `{}`", count, dst.display()))
+}
@@ -5,18 +5,22 @@ use std::collections::BTreeMap;
 
 use rustc_serialize::{Decodable, Decoder};
 
-use git2::Config as GitConfig;
+use git2::{self, Config as GitConfig};
 
+use tempdir::TempDir;
 use term::color::BLACK;
 
-use core::Workspace;
-use util::{GitRepo, HgRepo, CargoResult, human, ChainError, internal};
-use util::{Config, paths};
+use core::{GitReference, Workspace};
+use sources::git::{self, GitRemote};
+use util::{GitRepo, HgRepo, JjRepo, SaplingRepo, FossilRepo};
+use util::{CargoResult, human, ChainError, internal, discover_checkout};
+use util::{Config, ToUrl, paths, process};
+use util::toml as cargo_toml;
 
 use toml;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub enum VersionControl { Git, Hg, NoVcs }
+pub enum VersionControl { Git, Hg, Jj, Sapling, Fossil, NoVcs }
 
 pub struct NewOptions<'a> {
     pub version_control: Option<VersionControl>,
@@ -24,6 +28,12 @@ pub struct NewOptions<'a> {
     pub lib: bool,
     pub path: &'a str,
     pub name: Option<&'a str>,
+    /// A directory or the URL of a git repository to create the project
+    /// from, instead of Cargo's own template.
+    pub template: Option<&'a str>,
+    /// The branch, tag or revision of a template from git, instead of what
+    /// the repository's `HEAD` points to.
+    pub template_ref: Option<GitReference>,
 }
 
 struct SourceFileInformation {
@@ -45,6 +55,9 @@ impl Decodable for VersionControl {
         Ok(match &try!(d.read_str())[..] {
             "git" => VersionControl::Git,
             "hg" => VersionControl::Hg,
+            "jj" => VersionControl::Jj,
+            "sapling" => VersionControl::Sapling,
+            "fossil" => VersionControl::Fossil,
             "none" => VersionControl::NoVcs,
             n => {
                 let err = format!("could not decode '{}' as version control", n);
@@ -59,7 +72,8 @@ impl<'a> NewOptions<'a> {
            bin: bool,
            lib: bool,
            path: &'a str,
-           name: Option<&'a str>) -> NewOptions<'a> {
+           name: Option<&'a str>,
+           template: Option<&'a str>) -> NewOptions<'a> {
 
         // default to lib
         let is_lib = if !bin {
@@ -75,6 +89,8 @@ impl<'a> NewOptions<'a> {
             lib: is_lib,
             path: path,
             name: name,
+            template: template,
+            template_ref: None,
         }
     }
 }
@@ -82,9 +98,25 @@ impl<'a> NewOptions<'a> {
 struct CargoNewConfig {
     name: Option<String>,
     email: Option<String>,
+    license: Option<String>,
     version_control: Option<VersionControl>,
 }
 
+/// The optional `cargo-template.toml` at the root of a template.
+#[derive(RustcDecodable)]
+struct TomlTemplateManifest {
+    template: Option<TomlTemplate>,
+}
+
+#[derive(RustcDecodable, Default)]
+struct TomlTemplate {
+    /// The license substituted when `cargo-new.license` isn't set.
+    license: Option<String>,
+    /// Commands run in the new project, each one a program and its
+    /// arguments.
+    hooks: Option<Vec<Vec<String>>>,
+}
+
 fn get_name<'a>(path: &'a Path, opts: &'a NewOptions, config: &Config) -> CargoResult<&'a str> {
     if let Some(name) = opts.name {
         return Ok(name);
@@ -265,9 +297,21 @@ pub fn new(opts: NewOptions, config: &Config) -> CargoResult<()> {
         bail!("can't specify both lib and binary outputs");
     }
 
+    if opts.template.is_none() && opts.template_ref.is_some() {
+        bail!("--branch, --tag and --rev can only be used with --template");
+    }
+
     let name = try!(get_name(&path, &opts, config));
     try!(check_name(name));
 
+    if let Some(template) = opts.template {
+        return mk_from_template(config, template, opts.template_ref.as_ref(),
+                                &path, name, opts.version_control).chain_error(|| {
+            human(format!("Failed to create project `{}` at `{}`",
+                          name, path.display()))
+        })
+    }
+
     let mkopts = MkOptions {
         version_control: opts.version_control,
         path: &path,
@@ -312,26 +356,30 @@ pub fn init(opts: NewOptions, config: &Config) -> CargoResult<()> {
     let mut version_control = opts.version_control;
 
     if version_control == None {
-        let mut num_detected_vsces = 0;
-
-        if fs::metadata(&path.join(".git")).is_ok() {
-            version_control = Some(VersionControl::Git);
-            num_detected_vsces += 1;
-        }
-
-        if fs::metadata(&path.join(".hg")).is_ok() {
-            version_control = Some(VersionControl::Hg);
-            num_detected_vsces += 1;
+        // jj checkouts are usually colocated with a git repository, so a
+        // `.jj` directory takes precedence over `.git`.
+        let detected = [(".jj", VersionControl::Jj),
+                        (".git", VersionControl::Git),
+                        (".hg", VersionControl::Hg),
+                        (".sl", VersionControl::Sapling),
+                        (".fslckout", VersionControl::Fossil),
+                        ("_FOSSIL_", VersionControl::Fossil)];
+        let mut found = detected.iter().filter(|&&(dir, _)| {
+            fs::metadata(&path.join(dir)).is_ok()
+        }).collect::<Vec<_>>();
+        if found.first().map(|&&(_, vcs)| vcs) == Some(VersionControl::Jj) {
+            found.retain(|&&(_, vcs)| vcs != VersionControl::Git);
         }
 
         // if none exists, maybe create git, like in `cargo new`
 
-        if num_detected_vsces > 1 {
-            bail!("both .git and .hg directories found \
-                              and the ignore file can't be \
-                              filled in as a result, \
-                              specify --vcs to override detection");
+        if found.len() > 1 {
+            let dirs = found.iter().map(|&&(dir, _)| dir).collect::<Vec<_>>();
+            bail!("multiple version control directories found ({}) \
+                   and the ignore file can't be filled in as a result, \
+                   specify --vcs to override detection", dirs.join(", "));
         }
+        version_control = found.first().map(|&&(_, vcs)| vcs);
     }
 
     let mkopts = MkOptions {
@@ -363,7 +411,7 @@ fn strip_rust_affixes(name: &str) -> &str {
 }
 
 fn existing_vcs_repo(path: &Path, cwd: &Path) -> bool {
-    GitRepo::discover(path, cwd).is_ok() || HgRepo::discover(path, cwd).is_ok()
+    GitRepo::discover(path, cwd).is_ok() || discover_checkout(path).is_some()
 }
 
 fn mk(config: &Config, opts: &MkOptions) -> CargoResult<()> {
@@ -371,46 +419,11 @@ fn mk(config: &Config, opts: &MkOptions) -> CargoResult<()> {
     let name = opts.name;
     let cfg = try!(global_config(config));
     let mut ignore = "target\n".to_string();
-    let in_existing_vcs_repo = existing_vcs_repo(path.parent().unwrap(), config.cwd());
     if !opts.bin {
         ignore.push_str("Cargo.lock\n");
     }
-
-    let vcs = match (opts.version_control, cfg.version_control, in_existing_vcs_repo) {
-        (None, None, false) => VersionControl::Git,
-        (None, Some(option), false) => option,
-        (Some(option), _, _) => option,
-        (_, _, true) => VersionControl::NoVcs,
-    };
-
-    match vcs {
-        VersionControl::Git => {
-            if !fs::metadata(&path.join(".git")).is_ok() {
-                try!(GitRepo::init(path, config.cwd()));
-            }
-            try!(paths::append(&path.join(".gitignore"), ignore.as_bytes()));
-        },
-        VersionControl::Hg => {
-            if !fs::metadata(&path.join(".hg")).is_ok() {
-                try!(HgRepo::init(path, config.cwd()));
-            }
-            try!(paths::append(&path.join(".hgignore"), ignore.as_bytes()));
-        },
-        VersionControl::NoVcs => {
-            try!(fs::create_dir_all(path));
-        },
-    };
-
-    let (author_name, email) = try!(discover_author());
-    // Hoo boy, sure glad we've got exhaustivenes checking behind us.
-    let author = match (cfg.name, cfg.email, author_name, email) {
-        (Some(name), Some(email), _, _) |
-        (Some(name), None, _, Some(email)) |
-        (None, Some(email), name, _) |
-        (None, None, name, Some(email)) => format!("{} <{}>", name, email),
-        (Some(name), None, _, None) |
-        (None, None, name, None) => name,
-    };
+    try!(init_vcs(config, path, opts.version_control, &cfg, Some(&ignore[..])));
+    let author = try!(author(&cfg));
 
     let mut cargotoml_path_specifier = String::new();
 
@@ -490,6 +503,208 @@ mod tests {
     Ok(())
 }
 
+/// Sets up version control for the new project at `path`, adding `ignore`
+/// to the ignore file if there's one to add.
+fn init_vcs(config: &Config,
+            path: &Path,
+            version_control: Option<VersionControl>,
+            cfg: &CargoNewConfig,
+            ignore: Option<&str>) -> CargoResult<()> {
+    let in_existing_vcs_repo = existing_vcs_repo(path.parent().unwrap(), config.cwd());
+    let vcs = match (version_control, cfg.version_control, in_existing_vcs_repo) {
+        (None, None, false) => VersionControl::Git,
+        (None, Some(option), false) => option,
+        (Some(option), _, _) => option,
+        (_, _, true) => VersionControl::NoVcs,
+    };
+
+    match vcs {
+        VersionControl::Git => {
+            if !fs::metadata(&path.join(".git")).is_ok() {
+                try!(GitRepo::init(path, config.cwd()));
+            }
+            try!(append_ignore(&path.join(".gitignore"), ignore));
+        },
+        VersionControl::Hg => {
+            if !fs::metadata(&path.join(".hg")).is_ok() {
+                try!(HgRepo::init(path, config.cwd()));
+            }
+            try!(append_ignore(&path.join(".hgignore"), ignore));
+        },
+        VersionControl::Jj => {
+            if !fs::metadata(&path.join(".jj")).is_ok() {
+                try!(JjRepo::init(path, config.cwd()));
+            }
+            try!(append_ignore(&path.join(".gitignore"), ignore));
+        },
+        VersionControl::Sapling => {
+            if !fs::metadata(&path.join(".sl")).is_ok() {
+                try!(SaplingRepo::init(path, config.cwd()));
+            }
+            try!(append_ignore(&path.join(".gitignore"), ignore));
+        },
+        VersionControl::Fossil => {
+            if !fs::metadata(&path.join(".fslckout")).is_ok() &&
+               !fs::metadata(&path.join("_FOSSIL_")).is_ok() {
+                try!(FossilRepo::init(path, config.cwd()));
+            }
+            // fossil reads its versioned settings, like the ignore globs,
+            // from files in `.fossil-settings`
+            let settings = path.join(".fossil-settings");
+            try!(fs::create_dir_all(&settings));
+            try!(append_ignore(&settings.join("ignore-glob"), ignore));
+        },
+        VersionControl::NoVcs => {
+            try!(fs::create_dir_all(path));
+        },
+    };
+    Ok(())
+}
+
+fn append_ignore(file: &Path, ignore: Option<&str>) -> CargoResult<()> {
+    match ignore {
+        Some(ignore) => paths::append(file, ignore.as_bytes()),
+        None => Ok(()),
+    }
+}
+
+/// The author of new projects, as placed in the `authors` of Cargo.toml.
+fn author(cfg: &CargoNewConfig) -> CargoResult<String> {
+    let (author_name, email) = try!(discover_author());
+    // Hoo boy, sure glad we've got exhaustivenes checking behind us.
+    Ok(match (cfg.name.clone(), cfg.email.clone(), author_name, email) {
+        (Some(name), Some(email), _, _) |
+        (Some(name), None, _, Some(email)) |
+        (None, Some(email), name, _) |
+        (None, None, name, Some(email)) => format!("{} <{}>", name, email),
+        (Some(name), None, _, None) |
+        (None, None, name, None) => name,
+    })
+}
+
+/// Creates the project `name` at `path` out of `template`, a directory or the
+/// URL of a git repository. `reference` is what is checked out of the
+/// repository, by default the branch its `HEAD` points to.
+///
+/// The files of the template are copied, with `{{name}}`, `{{authors}}` and
+/// `{{license}}` replaced in their contents and paths, and then the hooks
+/// the template declares in its `cargo-template.toml` are run in the project.
+/// Ignore files are left to the template.
+fn mk_from_template(config: &Config,
+                    template: &str,
+                    reference: Option<&GitReference>,
+                    path: &Path,
+                    name: &str,
+                    version_control: Option<VersionControl>) -> CargoResult<()> {
+    let td = try!(TempDir::new("cargo-template"));
+    let dir = config.cwd().join(template);
+    let dir = if fs::metadata(&dir).map(|m| m.is_dir()).unwrap_or(false) {
+        if reference.is_some() {
+            bail!("--branch, --tag and --rev only apply to templates from git \
+                   repositories, and `{}` is a directory", template)
+        }
+        dir
+    } else {
+        let url = try!(template.to_url().chain_error(|| {
+            human(format!("template `{}` is neither a directory nor a URL",
+                          template))
+        }));
+        let remote = GitRemote::new(&url);
+        let db_path = td.path().join("db");
+        let db = try!(remote.checkout(&db_path, config));
+        let rev = match reference {
+            Some(reference) => try!(db.rev_for(reference)),
+            None => {
+                let repo = try!(git2::Repository::open(&db_path));
+                try!(git::fetch(&repo, &url.to_string(),
+                                "+HEAD:refs/remotes/origin/HEAD", config));
+                let head = "refs/remotes/origin/HEAD".to_string();
+                try!(db.rev_for(&GitReference::Rev(head)))
+            }
+        };
+        let checkout = td.path().join("checkout");
+        try!(db.copy_to(rev, &checkout, config));
+        checkout
+    };
+
+    let manifest = try!(read_template_manifest(&dir, config));
+    let cfg = try!(global_config(config));
+    let author = try!(author(&cfg));
+    let license = cfg.license.clone().or(manifest.license).unwrap_or(String::new());
+    let vars = [("{{name}}", name),
+                ("{{authors}}", &author[..]),
+                ("{{license}}", &license[..])];
+    try!(fs::create_dir_all(path));
+    try!(copy_template(&dir, path, &vars));
+    if fs::metadata(path.join("Cargo.toml")).is_err() {
+        bail!("template `{}` has no Cargo.toml", template)
+    }
+    try!(init_vcs(config, path, version_control, &cfg, None));
+
+    for hook in manifest.hooks.unwrap_or(Vec::new()) {
+        let mut cmd = process(&hook[0]);
+        cmd.args(&hook[1..]).cwd(path);
+        try!(cmd.exec().chain_error(|| {
+            human(format!("template hook `{}` failed", hook.join(" ")))
+        }));
+    }
+    Ok(())
+}
+
+fn read_template_manifest(dir: &Path, config: &Config) -> CargoResult<TomlTemplate> {
+    let file = dir.join("cargo-template.toml");
+    if fs::metadata(&file).is_err() {
+        return Ok(TomlTemplate::default())
+    }
+    let contents = try!(paths::read(&file));
+    let table = try!(cargo_toml::parse(&contents, &file, config));
+    let mut d = toml::Decoder::new(toml::Value::Table(table));
+    let manifest: TomlTemplateManifest = try!(Decodable::decode(&mut d).map_err(|e| {
+        human(format!("failed to parse `{}`: {}", file.display(), e))
+    }));
+    let template = manifest.template.unwrap_or_default();
+    if let Some(ref hooks) = template.hooks {
+        if hooks.iter().any(|h| h.is_empty()) {
+            bail!("the hooks of `{}` must each have a program to run",
+                  file.display())
+        }
+    }
+    Ok(template)
+}
+
+/// Copies the template at `src` to `dst`, replacing the variables in `vars`
+/// in the contents of the files which are UTF-8 and in their names.
+fn copy_template(src: &Path, dst: &Path, vars: &[(&str, &str)]) -> CargoResult<()> {
+    let substitute = |s: &str| {
+        vars.iter().fold(s.to_string(), |s, &(var, val)| s.replace(var, val))
+    };
+    for entry in try!(fs::read_dir(src)) {
+        let entry = try!(entry);
+        let file_name = entry.file_name();
+        match file_name.to_str() {
+            Some(".git") | Some("cargo-template.toml") => continue,
+            _ => {}
+        }
+        let to = match file_name.to_str() {
+            Some(s) => dst.join(substitute(s)),
+            None => dst.join(&file_name),
+        };
+        if try!(entry.file_type()).is_dir() {
+            try!(fs::create_dir_all(&to));
+            try!(copy_template(&entry.path(), &to, vars));
+            continue
+        }
+        let contents = try!(paths::read_bytes(&entry.path()));
+        let contents = match String::from_utf8(contents) {
+            Ok(s) => substitute(&s).into_bytes(),
+            Err(e) => e.into_bytes(),
+        };
+        try!(paths::write(&to, &contents));
+        try!(fs::set_permissions(&to, try!(entry.metadata()).permissions()));
+    }
+    Ok(())
+}
+
 fn get_environment_variable(variables: &[&str] ) -> Option<String>{
     variables.iter()
              .filter_map(|var| env::var(var).ok())
@@ -528,11 +743,15 @@ fn discover_author() -> CargoResult<(String, Option<String>)> {
 fn global_config(config: &Config) -> CargoResult<CargoNewConfig> {
     let name = try!(config.get_string("cargo-new.name")).map(|s| s.val);
     let email = try!(config.get_string("cargo-new.email")).map(|s| s.val);
+    let license = try!(config.get_string("cargo-new.license")).map(|s| s.val);
     let vcs = try!(config.get_string("cargo-new.vcs"));
 
     let vcs = match vcs.as_ref().map(|p| (&p.val[..], &p.definition)) {
         Some(("git", _)) => Some(VersionControl::Git),
         Some(("hg", _)) => Some(VersionControl::Hg),
+        Some(("jj", _)) => Some(VersionControl::Jj),
+        Some(("sapling", _)) => Some(VersionControl::Sapling),
+        Some(("fossil", _)) => Some(VersionControl::Fossil),
         Some(("none", _)) => Some(VersionControl::NoVcs),
         Some((s, p)) => {
             return Err(internal(format!("invalid configuration for key \
@@ -544,6 +763,7 @@ fn global_config(config: &Config) -> CargoResult<CargoNewConfig> {
     Ok(CargoNewConfig {
         name: name,
         email: email,
+        license: license,
         version_control: vcs,
     })
 }
@@ -1,18 +1,34 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::str::{self, FromStr};
+
 use rustc_serialize::{Encodable, Encoder};
+use rustc_serialize::json;
 
+use core::dependency::Kind;
 use core::resolver::Resolve;
-use core::{Package, PackageId, Workspace};
+use core::{Dependency, Package, PackageId, PackageSet, Registry, Summary, Workspace};
 use ops;
-use util::CargoResult;
+use sources::{RegistrySource, SourceConfigMap};
+use util::{paths, short_hash, CargoError, CargoResult, ChainError, Cfg, Config};
+use util::human;
 
 const VERSION: u32 = 1;
 
+#[derive(Hash)]
 pub struct OutputMetadataOptions {
     pub features: Vec<String>,
     pub no_default_features: bool,
     pub all_features: bool,
     pub no_deps: bool,
     pub version: u32,
+    /// Target triples for which to describe the dependency graph as it is
+    /// built for that platform.
+    pub platforms: Vec<String>,
 }
 
 /// Loads the manifest, resolves the dependencies of the project to the concrete
@@ -31,12 +47,129 @@ pub fn output_metadata(ws: &Workspace,
     }
 }
 
+/// Like `output_metadata`, but returns the serialized output of a previous
+/// run for the same manifest and options as long as none of the files it was
+/// derived from changed since.
+///
+/// The cache lives in `$CARGO_HOME/metadata-cache` and records the contents
+/// of the manifest of every path package, the lockfile and the configuration
+/// files individually, so a change to any one of them is enough to compute
+/// the metadata again. So is adding or removing one of the files targets are
+/// inferred from, such as those of `src/bin` or `tests`.
+pub fn output_metadata_cached(manifest: &Path,
+                              config: &Config,
+                              opt: &OutputMetadataOptions) -> CargoResult<String> {
+    let key = (manifest, opt, config.vendored(), config.dep_archive());
+    let file = config.home().join("metadata-cache")
+                     .into_path_unlocked()
+                     .join(short_hash(&key));
+    let config_files = try!(config.config_files());
+    let config_files = hash_files(config_files.iter().map(|p| &**p));
+
+    if let Some(entry) = load_cache(&file) {
+        let inputs = hash_files(entry.inputs.keys().map(Path::new));
+        let layouts = hash_layouts(entry.layouts.keys().map(Path::new));
+        if entry.config == config_files && entry.inputs == inputs &&
+           entry.layouts == layouts {
+            return Ok(entry.output)
+        }
+        debug!("metadata cache for {} is stale", manifest.display());
+    }
+
+    let ws = try!(Workspace::new(manifest, config));
+    let info = try!(output_metadata(&ws, opt));
+    let output = try!(json::encode(&info));
+
+    let mut inputs = vec![manifest.to_path_buf(),
+                          ws.root().join("Cargo.toml"),
+                          ws.root().join("Cargo.lock")];
+    let path_packages = info.packages.iter().filter(|pkg| {
+        pkg.package_id().source_id().is_path()
+    }).collect::<Vec<_>>();
+    inputs.extend(path_packages.iter().map(|pkg| pkg.manifest_path().to_path_buf()));
+    let entry = CacheEntry {
+        config: config_files,
+        inputs: hash_files(inputs.iter().map(|p| &**p)),
+        layouts: hash_layouts(path_packages.iter().map(|pkg| pkg.root())),
+        output: output,
+    };
+    // Failing to write the cache only makes the next run slower.
+    if let Err(e) = store_cache(&file, &entry) {
+        debug!("failed to write metadata cache {}: {}", file.display(), e);
+    }
+    Ok(entry.output)
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct CacheEntry {
+    /// The hashes of the configuration files which applied.
+    config: BTreeMap<String, String>,
+    /// The hashes of the manifests and of the lockfile the output was
+    /// computed from, where missing files are hashed as such.
+    inputs: BTreeMap<String, String>,
+    /// The hashes of the files targets are inferred from, by package root.
+    layouts: BTreeMap<String, String>,
+    output: String,
+}
+
+fn hash_files<'a, I>(files: I) -> BTreeMap<String, String>
+    where I: Iterator<Item=&'a Path>
+{
+    files.map(|file| {
+        let hash = match paths::read(file) {
+            Ok(contents) => short_hash(&contents),
+            Err(..) => "missing".to_string(),
+        };
+        (file.display().to_string(), hash)
+    }).collect()
+}
+
+/// Hashes the names of the files of each package which targets are inferred
+/// from when they aren't listed in its manifest.
+fn hash_layouts<'a, I>(roots: I) -> BTreeMap<String, String>
+    where I: Iterator<Item=&'a Path>
+{
+    roots.map(|root| {
+        let mut found = Vec::new();
+        for file in ["src/lib.rs", "src/main.rs", "build.rs"].iter() {
+            if root.join(file).exists() {
+                found.push(file.to_string());
+            }
+        }
+        for dir in ["src/bin", "examples", "tests", "benches"].iter() {
+            if let Ok(entries) = fs::read_dir(root.join(dir)) {
+                found.extend(entries.filter_map(|e| e.ok()).map(|e| {
+                    format!("{}/{}", dir, e.file_name().to_string_lossy())
+                }));
+            }
+        }
+        found.sort();
+        (root.display().to_string(), short_hash(&found))
+    }).collect()
+}
+
+fn load_cache(file: &Path) -> Option<CacheEntry> {
+    paths::read(file).ok().and_then(|contents| json::decode(&contents).ok())
+}
+
+fn store_cache(file: &Path, entry: &CacheEntry) -> CargoResult<()> {
+    // Written aside and moved into place, so concurrent runs never see a
+    // partially written cache.
+    let tmp = PathBuf::from(format!("{}.tmp", file.display()));
+    try!(fs::create_dir_all(file.parent().unwrap()));
+    try!(paths::write(&tmp, try!(json::encode(entry)).as_bytes()));
+    try!(fs::rename(&tmp, file));
+    Ok(())
+}
+
 fn metadata_no_deps(ws: &Workspace,
                     _opt: &OutputMetadataOptions) -> CargoResult<ExportInfo> {
     Ok(ExportInfo {
         packages: ws.members().cloned().collect(),
         workspace_members: ws.members().map(|pkg| pkg.package_id().clone()).collect(),
         resolve: None,
+        platforms: None,
+        downloads: None,
         version: VERSION,
     })
 }
@@ -50,6 +183,22 @@ fn metadata_full(ws: &Workspace,
                                               opt.no_default_features));
     let (packages, resolve) = deps;
 
+    let platforms = if opt.platforms.is_empty() {
+        None
+    } else {
+        let host = try!(ws.config().rustc()).host.clone();
+        let host_cfg = try!(target_cfg(ws, &host));
+        let mut platforms = Vec::new();
+        for triple in opt.platforms.iter() {
+            let cfg = try!(target_cfg(ws, triple));
+            platforms.push(try!(platform_graph(ws, opt, &packages, &resolve,
+                                               (triple, &cfg[..]),
+                                               (&host, &host_cfg[..]))));
+        }
+        Some(platforms)
+    };
+
+    let downloads = try!(downloads(ws.config(), &resolve));
     let packages = try!(packages.package_ids()
                                 .map(|i| packages.get(i).map(|p| p.clone()))
                                 .collect());
@@ -61,18 +210,583 @@ fn metadata_full(ws: &Workspace,
             resolve: resolve,
             root: ws.current_opt().map(|pkg| pkg.package_id().clone()),
         }),
+        platforms: platforms,
+        downloads: Some(downloads),
         version: VERSION,
     })
 }
 
+/// What it takes to fetch one of the resolved packages.
+#[derive(RustcEncodable)]
+pub struct PackageDownload {
+    id: PackageId,
+    /// The checksum of the compressed package, as recorded in the lockfile.
+    checksum: Option<String>,
+    /// The size in bytes of the compressed package, if its registry records
+    /// it.
+    size: Option<u64>,
+    /// Where the package is downloaded from: the package itself for remote
+    /// registries, or the repository or registry it comes from otherwise.
+    url: String,
+}
+
+/// Describes the download of every resolved package which doesn't come from
+/// a path. Only the local copy of each registry's index is consulted.
+fn downloads(config: &Config, resolve: &Resolve) -> CargoResult<Vec<PackageDownload>> {
+    let sources = try!(SourceConfigMap::new(config));
+    let mut loaded = HashMap::new();
+    let mut dl = HashMap::new();
+
+    let mut ids = resolve.iter().filter(|id| {
+        !id.source_id().is_path()
+    }).collect::<Vec<_>>();
+    ids.sort();
+
+    let mut downloads = Vec::new();
+    for id in ids {
+        let source_id = id.source_id().with_precise(None);
+        let mut size = None;
+        let mut url = source_id.url().to_string();
+        if source_id.is_registry() {
+            if !loaded.contains_key(&source_id) {
+                let source = try!(sources.load(&source_id));
+                loaded.insert(source_id.clone(), source);
+                // Local registries have no configuration, and a remote
+                // registry's may not be around when its source is replaced.
+                let config = RegistrySource::remote(&source_id, config)
+                                            .config().ok().and_then(|c| c);
+                dl.insert(source_id.clone(), config.map(|c| c.dl));
+            }
+            let source = loaded.get_mut(&source_id).unwrap();
+            let dep = try!(Dependency::parse(id.name(), None, &source_id));
+            size = try!(source.query(&dep)).iter()
+                                           .find(|s| s.version() == id.version())
+                                           .and_then(|s| s.size());
+            if let Some(&Some(ref base)) = dl.get(&source_id) {
+                url = format!("{}/{}/{}/download", base.trim_right_matches('/'),
+                              id.name(), id.version());
+            }
+        }
+        downloads.push(PackageDownload {
+            id: id.clone(),
+            checksum: resolve.checksums().get(id).and_then(|c| c.clone()),
+            size: size,
+            url: url,
+        });
+    }
+    Ok(downloads)
+}
+
+/// The dependency graph as built for one platform.
+#[derive(RustcEncodable)]
+pub struct PlatformGraph {
+    target: String,
+    nodes: Vec<PlatformNode>,
+}
+
+#[derive(RustcEncodable)]
+struct PlatformNode {
+    id: PackageId,
+    features: Vec<String>,
+    dependencies: Vec<PackageId>,
+}
+
+/// Asks rustc for the `cfg` values of the platform `triple`.
+pub fn target_cfg(ws: &Workspace, triple: &str) -> CargoResult<Vec<Cfg>> {
+    let mut process = try!(ws.config().rustc()).process();
+    process.arg("-")
+           .arg("--crate-name").arg("_")
+           .arg("--crate-type").arg("lib")
+           .arg("--print=cfg")
+           .arg("--target").arg(triple)
+           .env_remove("RUST_LOG");
+    let output = try!(process.exec_with_output().chain_error(|| {
+        human(format!("failed to learn about the target `{}` from rustc",
+                      triple))
+    }));
+    let output = str::from_utf8(&output.stdout).unwrap();
+    output.lines().map(|line| line.parse()).collect()
+}
+
+/// Walks the resolved graph from the members of the workspace, leaving out
+/// dependencies which aren't used on the given platform, to find which
+/// packages are built for it along with their features.
+///
+/// Build dependencies and everything they depend on are built for the host,
+/// so their platform-specific dependencies are matched against the host.
+/// Like when building, the features of a package are the union of those
+/// requested of it anywhere in the graph.
+fn platform_graph(ws: &Workspace,
+                  opt: &OutputMetadataOptions,
+                  packages: &PackageSet,
+                  resolve: &Resolve,
+                  target: (&str, &[Cfg]),
+                  host: (&str, &[Cfg])) -> CargoResult<PlatformGraph> {
+    // The features requested of each package, and whether its default ones
+    // are.
+    let mut requested: HashMap<PackageId, (BTreeSet<String>, bool)> = HashMap::new();
+    let mut edges: HashMap<PackageId, BTreeSet<PackageId>> = HashMap::new();
+    let mut visited: HashSet<(PackageId, bool)> = HashSet::new();
+    let mut queue = Vec::new();
+
+    let features = opt.features.iter().flat_map(|s| s.split(' '))
+                       .filter(|s| !s.is_empty())
+                       .map(|s| s.to_string()).collect::<BTreeSet<_>>();
+    for member in ws.members() {
+        let id = member.package_id();
+        let mut explicit = BTreeSet::new();
+        if opt.all_features {
+            explicit.extend(member.summary().features().keys().cloned());
+        } else if ws.current_opt().map(|p| p.package_id()) == Some(id) {
+            explicit.extend(features.iter().cloned());
+        }
+        requested.insert(id.clone(), (explicit, !opt.no_default_features));
+        queue.push((id.clone(), false));
+    }
+    let is_member = |id: &PackageId| {
+        ws.members().any(|m| m.package_id() == id)
+    };
+
+    // Enabling a feature may activate more dependencies, or more features of
+    // them, so packages are revisited until nothing changes.
+    while let Some((id, for_host)) = queue.pop() {
+        visited.insert((id.clone(), for_host));
+        let summary = try!(packages.get(&id)).summary().clone();
+        let (explicit, default) = requested[&id].clone();
+        let (enabled, dep_features) = enabled_features(&summary, &explicit,
+                                                       default);
+
+        let (triple, cfg) = if for_host { host } else { target };
+        for dep in summary.dependencies() {
+            if dep.kind() == Kind::Development && !is_member(&id) {
+                continue
+            }
+            let dep_for_host = for_host || dep.kind() == Kind::Build;
+            let (triple, cfg) = if dep.kind() == Kind::Build {
+                host
+            } else {
+                (triple, cfg)
+            };
+            if !dep.platform().map(|p| p.matches(triple, Some(cfg)))
+                                .unwrap_or(true) {
+                continue
+            }
+            if dep.is_optional() && !enabled.contains(dep.name()) {
+                continue
+            }
+            let dep_id = match resolve.deps_not_replaced(&id).find(|d| {
+                dep.matches_id(d)
+            }) {
+                Some(dep_id) => resolve.replacement(dep_id).unwrap_or(dep_id)
+                                       .clone(),
+                None => continue,
+            };
+            edges.entry(id.clone()).or_insert(BTreeSet::new())
+                 .insert(dep_id.clone());
+
+            let mut changed = !requested.contains_key(&dep_id);
+            {
+                let entry = requested.entry(dep_id.clone())
+                                     .or_insert((BTreeSet::new(), false));
+                for feature in dep.features().iter()
+                                  .chain(dep_features.get(dep.name())
+                                                     .into_iter()
+                                                     .flat_map(|f| f.iter())) {
+                    changed |= entry.0.insert(feature.clone());
+                }
+                if dep.uses_default_features() && !entry.1 {
+                    entry.1 = true;
+                    changed = true;
+                }
+            }
+            if changed || !visited.contains(&(dep_id.clone(), dep_for_host)) {
+                queue.push((dep_id, dep_for_host));
+            }
+        }
+    }
+
+    let mut ids = requested.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
+    let mut nodes = Vec::new();
+    for id in ids {
+        let summary = try!(packages.get(&id)).summary().clone();
+        let (ref explicit, default) = requested[&id];
+        let (enabled, _) = enabled_features(&summary, explicit, default);
+        nodes.push(PlatformNode {
+            features: enabled.into_iter().collect(),
+            dependencies: edges.remove(&id).map(|e| e.into_iter().collect())
+                               .unwrap_or(Vec::new()),
+            id: id,
+        });
+    }
+    Ok(PlatformGraph {
+        target: target.0.to_string(),
+        nodes: nodes,
+    })
+}
+
+/// Expands the features requested of a package into all of those which are
+/// enabled, which includes the names of the optional dependencies
+/// activated, along with the features of its dependencies enabled through
+/// `dep/feature`.
+fn enabled_features(summary: &Summary,
+                    explicit: &BTreeSet<String>,
+                    default: bool)
+                    -> (BTreeSet<String>, HashMap<String, Vec<String>>) {
+    let mut enabled = BTreeSet::new();
+    let mut deps = HashMap::new();
+    let mut stack = explicit.iter().cloned().collect::<Vec<_>>();
+    if default && summary.features().contains_key("default") {
+        stack.push("default".to_string());
+    }
+    while let Some(feature) = stack.pop() {
+        let mut parts = feature.splitn(2, '/');
+        let name = parts.next().unwrap().to_string();
+        if let Some(dep_feature) = parts.next() {
+            deps.entry(name.clone()).or_insert(Vec::new())
+                .push(dep_feature.to_string());
+        }
+        if enabled.insert(name.clone()) {
+            if let Some(implied) = summary.features().get(&name) {
+                stack.extend(implied.iter().cloned());
+            }
+        }
+    }
+    (enabled, deps)
+}
+
 #[derive(RustcEncodable)]
 pub struct ExportInfo {
     packages: Vec<Package>,
     workspace_members: Vec<PackageId>,
     resolve: Option<MetadataResolve>,
+    platforms: Option<Vec<PlatformGraph>>,
+    downloads: Option<Vec<PackageDownload>>,
+    version: u32,
+}
+
+/// The encodings `cargo metadata` can write its output in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MetadataFormat {
+    /// A single JSON object.
+    Json,
+    /// One JSON object per line: each package in its own `{"package": ..}`
+    /// object, followed by an object with the remaining fields.
+    Ndjson,
+    /// The same data as the JSON object, encoded as CBOR.
+    Cbor,
+}
+
+impl FromStr for MetadataFormat {
+    type Err = Box<CargoError>;
+
+    fn from_str(s: &str) -> CargoResult<MetadataFormat> {
+        match s {
+            "json" => Ok(MetadataFormat::Json),
+            "ndjson" => Ok(MetadataFormat::Ndjson),
+            "cbor" => Ok(MetadataFormat::Cbor),
+            _ => bail!("unknown metadata format `{}`, expected one of \
+                        `json`, `ndjson` or `cbor`", s),
+        }
+    }
+}
+
+impl fmt::Display for MetadataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MetadataFormat::Json => "json".fmt(f),
+            MetadataFormat::Ndjson => "ndjson".fmt(f),
+            MetadataFormat::Cbor => "cbor".fmt(f),
+        }
+    }
+}
+
+/// The fields of `ExportInfo` written after the packages in the `ndjson`
+/// format.
+#[derive(RustcEncodable)]
+struct ExportTrailer<'a> {
+    workspace_members: &'a [PackageId],
+    resolve: &'a Option<MetadataResolve>,
+    platforms: &'a Option<Vec<PlatformGraph>>,
+    downloads: &'a Option<Vec<PackageDownload>>,
     version: u32,
 }
 
+/// Writes `info` to `out` in the given format.
+///
+/// Every format is encoded straight into `out` as the data is walked, so the
+/// whole output is never held in memory at once.
+pub fn write_metadata(info: &ExportInfo,
+                      format: MetadataFormat,
+                      out: &mut Write) -> CargoResult<()> {
+    let mut out = io::BufWriter::new(out);
+    match format {
+        MetadataFormat::Json => {
+            try!(write_json(info, &mut out));
+            try!(out.write_all(b"\n"));
+        }
+        MetadataFormat::Ndjson => {
+            for pkg in info.packages.iter() {
+                try!(out.write_all(b"{\"package\":"));
+                try!(write_json(pkg, &mut out));
+                try!(out.write_all(b"}\n"));
+            }
+            let trailer = ExportTrailer {
+                workspace_members: &info.workspace_members,
+                resolve: &info.resolve,
+                platforms: &info.platforms,
+                downloads: &info.downloads,
+                version: info.version,
+            };
+            try!(write_json(&trailer, &mut out));
+            try!(out.write_all(b"\n"));
+        }
+        MetadataFormat::Cbor => {
+            try!(info.encode(&mut CborEncoder { out: &mut out }));
+        }
+    }
+    try!(out.flush());
+    Ok(())
+}
+
+/// Encodes `value` as JSON into `out`.
+fn write_json<T: Encodable>(value: &T, out: &mut Write) -> CargoResult<()> {
+    let mut writer = FmtWriter { out: out, error: None };
+    let res = {
+        let mut encoder = json::Encoder::new(&mut writer);
+        value.encode(&mut encoder)
+    };
+    if let Some(e) = writer.error {
+        return Err(e.into())
+    }
+    try!(res);
+    Ok(())
+}
+
+/// Adapts an `io::Write` to the `fmt::Write` the JSON encoder writes to,
+/// keeping the I/O error which made it fail.
+struct FmtWriter<'a> {
+    out: &'a mut Write,
+    error: Option<io::Error>,
+}
+
+impl<'a> fmt::Write for FmtWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.out.write_all(s.as_bytes()) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.error = Some(e);
+                Err(fmt::Error)
+            }
+        }
+    }
+}
+
+/// Encodes values as CBOR (RFC 7049) with the same structure as the JSON
+/// encoder gives them: structs and maps become maps, sequences and tuples
+/// arrays, `None` is null, and enums are encoded as their name or, if they
+/// have fields, as a `{"variant": .., "fields": [..]}` map.
+struct CborEncoder<'a> {
+    out: &'a mut Write,
+}
+
+impl<'a> CborEncoder<'a> {
+    fn header(&mut self, major: u8, n: u64) -> io::Result<()> {
+        let major = major << 5;
+        let (extra, len) = if n < 24 {
+            (n as u8, 0)
+        } else if n < 0x100 {
+            (24, 1)
+        } else if n < 0x10000 {
+            (25, 2)
+        } else if n < 0x100000000 {
+            (26, 4)
+        } else {
+            (27, 8)
+        };
+        let mut buf = vec![major | extra];
+        for i in (0..len).rev() {
+            buf.push((n >> (i * 8)) as u8);
+        }
+        self.out.write_all(&buf)
+    }
+
+    fn integer(&mut self, n: i64) -> io::Result<()> {
+        if n < 0 {
+            self.header(1, !(n as u64))
+        } else {
+            self.header(0, n as u64)
+        }
+    }
+}
+
+impl<'a> Encoder for CborEncoder<'a> {
+    type Error = io::Error;
+
+    fn emit_nil(&mut self) -> io::Result<()> { self.out.write_all(&[0xf6]) }
+    fn emit_usize(&mut self, v: usize) -> io::Result<()> { self.header(0, v as u64) }
+    fn emit_u64(&mut self, v: u64) -> io::Result<()> { self.header(0, v) }
+    fn emit_u32(&mut self, v: u32) -> io::Result<()> { self.header(0, v as u64) }
+    fn emit_u16(&mut self, v: u16) -> io::Result<()> { self.header(0, v as u64) }
+    fn emit_u8(&mut self, v: u8) -> io::Result<()> { self.header(0, v as u64) }
+    fn emit_isize(&mut self, v: isize) -> io::Result<()> { self.integer(v as i64) }
+    fn emit_i64(&mut self, v: i64) -> io::Result<()> { self.integer(v) }
+    fn emit_i32(&mut self, v: i32) -> io::Result<()> { self.integer(v as i64) }
+    fn emit_i16(&mut self, v: i16) -> io::Result<()> { self.integer(v as i64) }
+    fn emit_i8(&mut self, v: i8) -> io::Result<()> { self.integer(v as i64) }
+
+    fn emit_bool(&mut self, v: bool) -> io::Result<()> {
+        self.out.write_all(&[if v { 0xf5 } else { 0xf4 }])
+    }
+
+    fn emit_f64(&mut self, v: f64) -> io::Result<()> {
+        let bits: u64 = unsafe { mem::transmute(v) };
+        let mut buf = vec![0xfb];
+        for i in (0..8).rev() {
+            buf.push((bits >> (i * 8)) as u8);
+        }
+        self.out.write_all(&buf)
+    }
+
+    fn emit_f32(&mut self, v: f32) -> io::Result<()> { self.emit_f64(v as f64) }
+
+    fn emit_char(&mut self, v: char) -> io::Result<()> {
+        self.emit_str(&v.to_string())
+    }
+
+    fn emit_str(&mut self, v: &str) -> io::Result<()> {
+        try!(self.header(3, v.len() as u64));
+        self.out.write_all(v.as_bytes())
+    }
+
+    fn emit_enum<F>(&mut self, _name: &str, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        f(self)
+    }
+
+    fn emit_enum_variant<F>(&mut self, name: &str, _id: usize, len: usize, f: F)
+                            -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        if len == 0 {
+            return self.emit_str(name)
+        }
+        try!(self.header(5, 2));
+        try!(self.emit_str("variant"));
+        try!(self.emit_str(name));
+        try!(self.emit_str("fields"));
+        try!(self.header(4, len as u64));
+        f(self)
+    }
+
+    fn emit_enum_variant_arg<F>(&mut self, _idx: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        f(self)
+    }
+
+    fn emit_enum_struct_variant<F>(&mut self, name: &str, id: usize, len: usize,
+                                   f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        self.emit_enum_variant(name, id, len, f)
+    }
+
+    fn emit_enum_struct_variant_field<F>(&mut self, _name: &str, idx: usize,
+                                         f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        self.emit_enum_variant_arg(idx, f)
+    }
+
+    fn emit_struct<F>(&mut self, _name: &str, len: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        try!(self.header(5, len as u64));
+        f(self)
+    }
+
+    fn emit_struct_field<F>(&mut self, name: &str, _idx: usize, f: F)
+                            -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        try!(self.emit_str(name));
+        f(self)
+    }
+
+    fn emit_tuple<F>(&mut self, len: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        self.emit_seq(len, f)
+    }
+
+    fn emit_tuple_arg<F>(&mut self, idx: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_tuple_struct<F>(&mut self, _name: &str, len: usize, f: F)
+                            -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        self.emit_seq(len, f)
+    }
+
+    fn emit_tuple_struct_arg<F>(&mut self, idx: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_option<F>(&mut self, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        f(self)
+    }
+
+    fn emit_option_none(&mut self) -> io::Result<()> { self.emit_nil() }
+
+    fn emit_option_some<F>(&mut self, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        f(self)
+    }
+
+    fn emit_seq<F>(&mut self, len: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        try!(self.header(4, len as u64));
+        f(self)
+    }
+
+    fn emit_seq_elt<F>(&mut self, _idx: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        f(self)
+    }
+
+    fn emit_map<F>(&mut self, len: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        try!(self.header(5, len as u64));
+        f(self)
+    }
+
+    fn emit_map_elt_key<F>(&mut self, _idx: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        f(self)
+    }
+
+    fn emit_map_elt_val<F>(&mut self, _idx: usize, f: F) -> io::Result<()>
+        where F: FnOnce(&mut Self) -> io::Result<()>
+    {
+        f(self)
+    }
+}
+
 /// Newtype wrapper to provide a custom `Encodable` implementation.
 /// The one from lockfile does not fit because it uses a non-standard
 /// format for `PackageId`s
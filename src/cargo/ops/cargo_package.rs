@@ -1,18 +1,31 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs::{self, File};
-use std::io::SeekFrom;
+use std::io::{self, SeekFrom};
 use std::io::prelude::*;
 use std::path::{self, Path};
+use std::str::FromStr;
 
 use flate2::read::GzDecoder;
 use flate2::{GzBuilder, Compression};
 use git2;
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json;
 use tar::{Archive, Builder, Header};
+use zstd;
 
 use core::{SourceId, Package, PackageId, Workspace, Source};
 use sources::PathSource;
-use util::{self, CargoResult, human, internal, ChainError, Config, FileLock};
+use util::{self, CargoResult, CargoError, human, internal, ChainError, Config};
+use util::{FileLock, Sha256};
+use util::Checkout;
+use util::toml as cargo_toml;
 use ops;
 
+/// Name of the file in a package tarball which describes the version control
+/// state the package was created from.
+const VCS_INFO_FILE: &'static str = ".cargo_vcs_info.json";
+
 pub struct PackageOpts<'cfg> {
     pub config: &'cfg Config,
     pub list: bool,
@@ -20,6 +33,38 @@ pub struct PackageOpts<'cfg> {
     pub allow_dirty: bool,
     pub verify: bool,
     pub jobs: Option<u32>,
+    pub formats: Vec<ArchiveFormat>,
+}
+
+/// Forms a package can be produced in next to the canonical `.crate` file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArchiveFormat {
+    /// The same tarball as the `.crate` file, compressed with zstd.
+    TarZst,
+    /// The contents of the `.crate` file unpacked into a directory.
+    Dir,
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = Box<CargoError>;
+
+    fn from_str(s: &str) -> CargoResult<ArchiveFormat> {
+        match s {
+            "tar.zst" => Ok(ArchiveFormat::TarZst),
+            "dir" => Ok(ArchiveFormat::Dir),
+            _ => bail!("unknown package format `{}`, expected one of \
+                        `tar.zst` or `dir`", s),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArchiveFormat::TarZst => "tar.zst".fmt(f),
+            ArchiveFormat::Dir => "dir".fmt(f),
+        }
+    }
 }
 
 pub fn package(ws: &Workspace,
@@ -49,8 +94,10 @@ pub fn package(ws: &Workspace,
         return Ok(None)
     }
 
-    if !opts.allow_dirty {
-        try!(check_not_dirty(&pkg, &src));
+    let policy = try!(DirtyPolicy::from_config(config, opts.allow_dirty));
+    let vcs_info = try!(vcs_info(&pkg, &src, policy, config));
+    if let Some(ref info) = vcs_info {
+        try!(check_dirty(info, policy, config));
     }
 
     let filename = format!("{}-{}.crate", pkg.name(), pkg.version());
@@ -66,7 +113,7 @@ pub fn package(ws: &Workspace,
     // it exists.
     try!(config.shell().status("Packaging", pkg.package_id().to_string()));
     try!(dst.file().set_len(0));
-    try!(tar(ws, &src, dst.file(), &filename).chain_error(|| {
+    try!(tar(ws, &src, vcs_info.as_ref(), dst.file(), &filename).chain_error(|| {
         human("failed to prepare local package for uploading")
     }));
     if opts.verify {
@@ -76,16 +123,101 @@ pub fn package(ws: &Workspace,
         }))
     }
     try!(dst.seek(SeekFrom::Start(0)));
-    {
-        let src_path = dst.path();
-        let dst_path = dst.parent().join(&filename);
-        try!(fs::rename(&src_path, &dst_path).chain_error(|| {
-            human("failed to move temporary tarball into final location")
+    let crate_path = dst.parent().join(&filename);
+    try!(fs::rename(dst.path(), &crate_path).chain_error(|| {
+        human("failed to move temporary tarball into final location")
+    }));
+    try!(write_checksum(&crate_path));
+
+    for &format in opts.formats.iter() {
+        try!(export(ws, format, &crate_path).chain_error(|| {
+            human(format!("failed to produce the package as `{}`", format))
         }));
     }
     Ok(Some(dst))
 }
 
+/// Produces the package in `format` from the finished `.crate` file, so every
+/// format has exactly the same contents.
+fn export(ws: &Workspace, format: ArchiveFormat, crate_path: &Path)
+          -> CargoResult<()> {
+    let pkg = try!(ws.current());
+    let config = ws.config();
+    let name = format!("{}-{}", pkg.name(), pkg.version());
+    let dir = crate_path.parent().unwrap();
+    let mut tarball = try!(GzDecoder::new(try!(File::open(crate_path))));
+
+    try!(config.shell().status("Exporting",
+                               format!("{} as {}", pkg.package_id(), format)));
+    match format {
+        ArchiveFormat::TarZst => {
+            let dst = dir.join(format!("{}.tar.zst", name));
+            let file = try!(File::create(&dst));
+            let mut encoder = try!(zstd::stream::Encoder::new(file, 19));
+            try!(io::copy(&mut tarball, &mut encoder));
+            try!(encoder.finish());
+            try!(write_checksum(&dst));
+        }
+        ArchiveFormat::Dir => {
+            let root = dir.join("export");
+            let dst = root.join(&name);
+            if fs::metadata(&dst).is_ok() {
+                try!(fs::remove_dir_all(&dst));
+            }
+            try!(Archive::new(tarball).unpack(&root));
+
+            // The checksums are recorded in the same way as in a directory
+            // source, so the export can be used as one directly.
+            let mut files = BTreeMap::new();
+            let tarball = try!(GzDecoder::new(try!(File::open(crate_path))));
+            let mut archive = Archive::new(tarball);
+            for entry in try!(archive.entries()) {
+                let mut entry = try!(entry);
+                let path = try!(entry.path()).into_owned();
+                let path = match path.strip_prefix(&name) {
+                    Ok(path) => path.to_string_lossy().replace("\\", "/"),
+                    Err(..) => continue,
+                };
+                let mut contents = Vec::new();
+                try!(entry.read_to_end(&mut contents));
+                files.insert(path, sha256(&contents));
+            }
+            let package = try!(util::paths::read_bytes(crate_path));
+            let checksum = Checksum {
+                package: sha256(&package),
+                files: files,
+            };
+            try!(util::paths::write(&dst.join(".cargo-checksum.json"),
+                                    try!(json::encode(&checksum)).as_bytes()));
+        }
+    }
+    Ok(())
+}
+
+/// The `.cargo-checksum.json` of an exported directory.
+#[derive(RustcEncodable)]
+struct Checksum {
+    package: String,
+    files: BTreeMap<String, String>,
+}
+
+/// Writes the SHA-256 of `path` next to it as `<path>.sha256`, in the format
+/// understood by `sha256sum -c`.
+fn write_checksum(path: &Path) -> CargoResult<()> {
+    let contents = try!(util::paths::read_bytes(path));
+    let name = path.file_name().unwrap().to_string_lossy();
+    let mut dst = path.as_os_str().to_os_string();
+    dst.push(".sha256");
+    util::paths::write(Path::new(&dst),
+                       format!("{}  {}\n", sha256(&contents), name).as_bytes())
+}
+
+fn sha256(contents: &[u8]) -> String {
+    let mut h = Sha256::new();
+    h.update(contents);
+    h.finish().to_hex()
+}
+
 // check that the package has some piece of metadata that a human can
 // use to tell what the package is about.
 fn check_metadata(pkg: &Package, config: &Config) -> CargoResult<()> {
@@ -135,29 +267,144 @@ fn verify_dependencies(pkg: &Package) -> CargoResult<()> {
     Ok(())
 }
 
-fn check_not_dirty(p: &Package, src: &PathSource) -> CargoResult<()> {
-    if let Ok(repo) = git2::Repository::discover(p.root()) {
-        if let Some(workdir) = repo.workdir() {
-            debug!("found a git repo at {:?}, checking if index present",
-                   workdir);
-            let path = p.manifest_path();
-            let path = path.strip_prefix(workdir).unwrap_or(path);
-            if let Ok(status) = repo.status_file(path) {
-                if (status & git2::STATUS_IGNORED).is_empty() {
-                    debug!("Cargo.toml found in repo, checking if dirty");
-                    return git(p, src, &repo)
-                }
-            }
+/// How to treat uncommitted changes in the package being packaged.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum DirtyPolicy {
+    /// Refuse to package a dirty working directory.
+    Error,
+    /// Warn about the dirty files, but package them anyway.
+    Warn,
+    /// Silently package dirty files.
+    Allow,
+}
+
+impl DirtyPolicy {
+    /// Reads the policy from the `package.dirty` configuration key, with
+    /// `--allow-dirty` taking precedence over it.
+    fn from_config(config: &Config, allow_dirty: bool)
+                   -> CargoResult<DirtyPolicy> {
+        if allow_dirty {
+            return Ok(DirtyPolicy::Allow)
+        }
+        match try!(config.get_string("package.dirty")) {
+            None => Ok(DirtyPolicy::Error),
+            Some(v) => match &v.val[..] {
+                "error" => Ok(DirtyPolicy::Error),
+                "warn" => Ok(DirtyPolicy::Warn),
+                "allow" => Ok(DirtyPolicy::Allow),
+                s => bail!("invalid configuration for key `package.dirty`, \
+                            expected `error`, `warn` or `allow` but found \
+                            `{}` in {}", s, v.definition),
+            },
+        }
+    }
+}
+
+/// The state of the version control checkout a package was packaged from,
+/// recorded in the `.cargo_vcs_info.json` file of the tarball.
+#[derive(RustcEncodable)]
+struct VcsInfo {
+    vcs: String,
+    revision: Option<String>,
+    dirty: bool,
+    dirty_files: Vec<String>,
+}
+
+fn check_dirty(info: &VcsInfo, policy: DirtyPolicy, config: &Config)
+               -> CargoResult<()> {
+    if info.dirty_files.is_empty() {
+        return Ok(())
+    }
+    match policy {
+        DirtyPolicy::Allow => Ok(()),
+        DirtyPolicy::Warn => {
+            config.shell().warn(format!("{} dirty files found in the working \
+                                         directory:\n\n{}\n",
+                                        info.dirty_files.len(),
+                                        info.dirty_files.join("\n")))
+        }
+        DirtyPolicy::Error => {
+            bail!("{} dirty files found in the working directory:\n\n{}\n\n\
+                   to proceed despite this, pass the `--allow-dirty` flag",
+                  info.dirty_files.len(), info.dirty_files.join("\n"))
         }
     }
+}
+
+fn vcs_info(p: &Package,
+            src: &PathSource,
+            policy: DirtyPolicy,
+            config: &Config) -> CargoResult<Option<VcsInfo>> {
+    if let Some(repo) = git_repo(p, p.root()) {
+        return git(p, src, &repo).map(Some)
+    }
+
+    if let Some(checkout) = util::discover_checkout(p.root()) {
+        debug!("found a {} checkout at {:?}, checking if dirty",
+               checkout.tool(), checkout.root());
+        let err = match other(p, src, &*checkout) {
+            Ok(info) => return Ok(Some(info)),
+            Err(e) => e,
+        };
+        // Checkouts which keep their history in git can still be inspected
+        // through libgit2 when their own tool fails.
+        if let Some(repo) = git_repo(p, checkout.root()) {
+            debug!("`{}` failed, falling back to the git repository",
+                   checkout.tool());
+            return git(p, src, &repo).map(Some)
+        }
+        // Whether the files are dirty doesn't matter if they may be packaged
+        // anyway, so only the revision would be missing from the package.
+        if policy != DirtyPolicy::Allow {
+            return Err(err).chain_error(|| {
+                human(format!("failed to determine whether the {} checkout at \
+                               `{}` has uncommitted changes, pass \
+                               `--allow-dirty` to package it anyway",
+                              checkout.tool(), checkout.root().display()))
+            })
+        }
+        try!(config.shell().warn(format!("could not determine the state of \
+                                          the {} checkout at `{}`, its \
+                                          revision will not be recorded: {}",
+                                         checkout.tool(),
+                                         checkout.root().display(), err)));
+        return Ok(None)
+    }
 
     // No VCS recognized, we don't know if the directory is dirty or not, so we
     // have to assume that it's clean.
-    return Ok(());
+    return Ok(None);
+
+    /// Finds the git repository above `dir` which tracks the manifest of `p`.
+    fn git_repo(p: &Package, dir: &Path) -> Option<git2::Repository> {
+        let repo = match git2::Repository::discover(dir) {
+            Ok(repo) => repo,
+            Err(..) => return None,
+        };
+        let tracked = match repo.workdir() {
+            Some(workdir) => {
+                debug!("found a git repo at {:?}, checking if index present",
+                       workdir);
+                let path = p.manifest_path();
+                let path = path.strip_prefix(workdir).unwrap_or(path);
+                match repo.status_file(path) {
+                    Ok(status) => (status & git2::STATUS_IGNORED).is_empty(),
+                    Err(..) => false,
+                }
+            }
+            None => false,
+        };
+        if tracked {
+            debug!("Cargo.toml found in repo, checking if dirty");
+            Some(repo)
+        } else {
+            None
+        }
+    }
 
     fn git(p: &Package,
            src: &PathSource,
-           repo: &git2::Repository) -> CargoResult<()> {
+           repo: &git2::Repository) -> CargoResult<VcsInfo> {
         let workdir = repo.workdir().unwrap();
         let dirty = try!(src.list_files(p)).iter().filter(|file| {
             let relative = file.strip_prefix(workdir).unwrap();
@@ -169,18 +416,54 @@ fn check_not_dirty(p: &Package, src: &PathSource) -> CargoResult<()> {
         }).map(|path| {
             path.strip_prefix(p.root()).unwrap_or(path).display().to_string()
         }).collect::<Vec<_>>();
-        if dirty.is_empty() {
-            Ok(())
-        } else {
-            bail!("{} dirty files found in the working directory:\n\n{}\n\n\
-                   to proceed despite this, pass the `--allow-dirty` flag",
-                  dirty.len(), dirty.join("\n"))
-        }
+        let revision = repo.head().ok().and_then(|h| h.target());
+        Ok(VcsInfo {
+            vcs: "git".to_string(),
+            revision: revision.map(|oid| oid.to_string()),
+            dirty: !dirty.is_empty(),
+            dirty_files: dirty,
+        })
+    }
+
+    fn other(p: &Package,
+             src: &PathSource,
+             checkout: &Checkout) -> CargoResult<VcsInfo> {
+        let changed = try!(checkout.changed_files()).into_iter().map(|file| {
+            checkout.root().join(file)
+        }).collect::<HashSet<_>>();
+        let dirty = try!(src.list_files(p)).iter().filter(|file| {
+            changed.contains(*file)
+        }).map(|path| {
+            path.strip_prefix(p.root()).unwrap_or(path).display().to_string()
+        }).collect::<Vec<_>>();
+        Ok(VcsInfo {
+            vcs: checkout.tool().to_string(),
+            revision: checkout.revision().ok(),
+            dirty: !dirty.is_empty(),
+            dirty_files: dirty,
+        })
     }
 }
 
+/// The manifest of `pkg` with what it inherits from its workspace written out,
+/// if it inherits anything.
+fn inherited_manifest(pkg: &Package, config: &Config)
+                      -> CargoResult<Option<String>> {
+    let path = pkg.manifest_path();
+    let contents = try!(util::paths::read(path));
+    let mut manifest = try!(cargo_toml::parse(&contents, path, config));
+    if !try!(cargo_toml::inherit_from_workspace(&mut manifest, path,
+                                                pkg.root(), config)) {
+        return Ok(None)
+    }
+    Ok(Some(format!("# Values inherited from the workspace were written \
+                     out by `cargo package`.\n\n{}",
+                    ::toml::Value::Table(manifest))))
+}
+
 fn tar(ws: &Workspace,
        src: &PathSource,
+       vcs_info: Option<&VcsInfo>,
        dst: &File,
        filename: &str) -> CargoResult<()> {
     // Prepare the encoder and its header
@@ -200,15 +483,35 @@ fn tar(ws: &Workspace,
             human(format!("non-utf8 path in source directory: {}",
                           relative.display()))
         }));
-        let mut file = try!(File::open(file).chain_error(|| {
-            human(format!("failed to open for archiving: `{}`", file.display()))
-        }));
         try!(config.shell().verbose(|shell| {
             shell.status("Archiving", &relative)
         }));
         let path = format!("{}-{}{}{}", pkg.name(), pkg.version(),
                            path::MAIN_SEPARATOR, relative);
 
+        // What's inherited from the workspace is written out, as the package
+        // is built outside of it once published.
+        if file.as_path() == pkg.manifest_path() {
+            if let Some(contents) = try!(inherited_manifest(pkg, config)) {
+                let mut header = Header::new_ustar();
+                try!(header.set_path(&path).chain_error(|| {
+                    human(format!("failed to add to archive: `{}`", relative))
+                }));
+                header.set_mode(0o644);
+                header.set_size(contents.len() as u64);
+                header.set_cksum();
+                try!(ar.append(&header, contents.as_bytes()).chain_error(|| {
+                    internal(format!("could not archive source file `{}`",
+                                     relative))
+                }));
+                continue
+            }
+        }
+
+        let mut file = try!(File::open(file).chain_error(|| {
+            human(format!("failed to open for archiving: `{}`", file.display()))
+        }));
+
         // The tar::Builder type by default will build GNU archives, but
         // unfortunately we force it here to use UStar archives instead. The
         // UStar format has more limitations on the length of path name that it
@@ -241,6 +544,25 @@ fn tar(ws: &Workspace,
             internal(format!("could not archive source file `{}`", relative))
         }));
     }
+
+    // Record where the package came from so it can be traced back to the
+    // exact revision it was published from.
+    if let Some(info) = vcs_info {
+        let contents = try!(json::encode(info));
+        let path = format!("{}-{}{}{}", pkg.name(), pkg.version(),
+                           path::MAIN_SEPARATOR, VCS_INFO_FILE);
+        let mut header = Header::new_ustar();
+        try!(header.set_path(&path).chain_error(|| {
+            human(format!("failed to add to archive: `{}`", VCS_INFO_FILE))
+        }));
+        header.set_mode(0o644);
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        try!(ar.append(&header, contents.as_bytes()).chain_error(|| {
+            internal(format!("could not archive `{}`", VCS_INFO_FILE))
+        }));
+    }
+
     let encoder = try!(ar.into_inner());
     try!(encoder.finish());
     Ok(())
@@ -298,6 +620,12 @@ fn run_verify(ws: &Workspace, tar: &File, opts: &PackageOpts) -> CargoResult<()>
         mode: ops::CompileMode::Build,
         target_rustdoc_args: None,
         target_rustc_args: None,
+        emit_ninja: None,
+        message_format: ops::MessageFormat::Human,
+        self_profile: None,
+        preserve_old_artifacts: false,
+        sbom: None,
+        shard: None,
     }));
 
     Ok(())
@@ -0,0 +1,191 @@
+//! Pinning built artifacts so they outlive the target directory's upkeep.
+//!
+//! Pinned artifacts are copied to the `pinned` directory of the target
+//! directory, under `$name-$version/$profile` (prefixed by the target triple
+//! when one was given), where they stay until unpinned.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use core::{Package, Workspace};
+use core::registry::PackageRegistry;
+use ops::{self, Context, BuildConfig, Kind, Unit};
+use util::{CargoResult, ChainError, Config, human};
+
+pub struct PinOptions<'a> {
+    pub config: &'a Config,
+    /// Packages whose artifacts are pinned, the current package if empty.
+    pub spec: &'a [String],
+    pub target: Option<&'a str>,
+    pub release: bool,
+}
+
+/// Copies the artifacts last built for the selected packages into the
+/// `pinned` directory, replacing those pinned for them before.
+pub fn pin_artifacts(ws: &Workspace, opts: &PinOptions) -> CargoResult<()> {
+    let mut registry = try!(PackageRegistry::new(opts.config));
+    let resolve = try!(ops::resolve_ws(&mut registry, ws));
+    let packages = ops::get_resolved_packages(&resolve, registry);
+
+    let mut pkgs = Vec::new();
+    if opts.spec.is_empty() {
+        pkgs.push(try!(ws.current()));
+    }
+    for spec in opts.spec {
+        let pkgid = try!(resolve.query(spec));
+        pkgs.push(try!(packages.get(&pkgid)));
+    }
+
+    let profiles = try!(ws.current()).manifest().profiles();
+    let host_triple = try!(opts.config.rustc()).host.clone();
+    let host = try!(ops::scrape_target_config(opts.config, &host_triple));
+    let target = match opts.target {
+        Some(triple) => try!(ops::scrape_target_config(opts.config, triple)),
+        None => host.clone(),
+    };
+    let package_target_dirs = try!(ops::scrape_package_target_dirs(opts.config));
+    let mut cx = try!(Context::new(ws, &resolve, &packages, opts.config,
+                                   BuildConfig {
+                                       host_triple: host_triple,
+                                       host: host,
+                                       requested_target: opts.target.map(|s| s.to_owned()),
+                                       target: target,
+                                       release: opts.release,
+                                       package_target_dirs: package_target_dirs,
+                                       ..BuildConfig::default()
+                                   },
+                                   profiles));
+
+    let profile = if opts.release {&profiles.release} else {&profiles.dev};
+    let mut units = Vec::new();
+    for pkg in pkgs.iter() {
+        for target in pkg.targets() {
+            if target.is_lib() || target.is_bin() || target.is_example() {
+                units.push(Unit {
+                    pkg: *pkg,
+                    target: target,
+                    profile: profile,
+                    kind: Kind::Target,
+                });
+            }
+        }
+    }
+    try!(cx.probe_target_info(&units));
+
+    for pkg in pkgs.iter() {
+        let mut artifacts = Vec::new();
+        for unit in units.iter().filter(|u| u.pkg.package_id() == pkg.package_id()) {
+            let root = cx.out_dir(unit);
+            for (filename, _) in try!(cx.target_filenames(unit)) {
+                let src = root.join(&filename);
+                if fs::metadata(&src).is_ok() {
+                    artifacts.push((src, filename));
+                }
+            }
+        }
+        if artifacts.is_empty() {
+            bail!("no artifacts of `{}` built with the `{}` profile were \
+                   found, build them before pinning them", pkg,
+                  profile_name(opts.release))
+        }
+
+        let dst = pin_dir(ws, pkg, opts);
+        try!(remove_pin(&dst));
+        try!(fs::create_dir_all(&dst));
+        for &(ref src, ref filename) in artifacts.iter() {
+            // Copied rather than hard linked, as the compiler may write to the
+            // artifacts of later builds in place.
+            try!(fs::copy(src, dst.join(filename)).chain_error(|| {
+                human(format!("failed to pin `{}`", src.display()))
+            }));
+        }
+        try!(opts.config.shell().status("Pinned",
+                                        format!("{} artifact(s) of {}",
+                                                artifacts.len(), pkg)));
+    }
+    Ok(())
+}
+
+/// Removes the artifacts pinned for the selected packages.
+pub fn unpin_artifacts(ws: &Workspace, opts: &PinOptions) -> CargoResult<()> {
+    let mut pkgs = Vec::new();
+    if opts.spec.is_empty() {
+        pkgs.push(try!(ws.current()));
+    }
+    for spec in opts.spec {
+        match ws.members().find(|m| m.name() == &spec[..]) {
+            Some(member) => pkgs.push(member),
+            None => bail!("only the artifacts of members of the workspace \
+                           can be unpinned by name, `{}` isn't one", spec),
+        }
+    }
+    for pkg in pkgs {
+        let dir = pin_dir(ws, pkg, opts);
+        if fs::metadata(&dir).is_err() {
+            bail!("no artifacts of `{}` are pinned for the `{}` profile", pkg,
+                  profile_name(opts.release))
+        }
+        try!(remove_pin(&dir));
+        try!(opts.config.shell().status("Unpinned", pkg));
+    }
+    Ok(())
+}
+
+/// Lists the pinned artifacts, relative to the `pinned` directory.
+pub fn list_pinned(ws: &Workspace) -> CargoResult<Vec<String>> {
+    let root = pinned_dir(ws);
+    let mut files = Vec::new();
+    if fs::metadata(&root).is_ok() {
+        try!(walk(&root, &root, &mut files));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The directory of the target directory of `ws` holding pinned artifacts.
+pub fn pinned_dir(ws: &Workspace) -> PathBuf {
+    ws.target_dir().into_path_unlocked().join("pinned")
+}
+
+fn pin_dir(ws: &Workspace, pkg: &Package, opts: &PinOptions) -> PathBuf {
+    let mut dir = pinned_dir(ws).join(format!("{}-{}", pkg.name(), pkg.version()));
+    if let Some(triple) = opts.target {
+        dir.push(Path::new(triple).file_stem().unwrap());
+    }
+    dir.join(profile_name(opts.release))
+}
+
+fn profile_name(release: bool) -> &'static str {
+    if release {"release"} else {"dev"}
+}
+
+fn remove_pin(dir: &Path) -> CargoResult<()> {
+    if fs::metadata(dir).is_ok() {
+        try!(fs::remove_dir_all(dir).chain_error(|| {
+            human(format!("failed to unpin `{}`", dir.display()))
+        }));
+    }
+    // Drop the directories of the package left empty.
+    let mut cur = dir.parent();
+    while let Some(parent) = cur {
+        if parent.file_name().map(|n| n == "pinned").unwrap_or(true) ||
+           fs::remove_dir(parent).is_err() {
+            break
+        }
+        cur = parent.parent();
+    }
+    Ok(())
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) -> CargoResult<()> {
+    for entry in try!(fs::read_dir(dir)) {
+        let path = try!(entry).path();
+        if try!(fs::metadata(&path)).is_dir() {
+            try!(walk(root, &path, files));
+        } else {
+            let name = path.strip_prefix(root).unwrap();
+            files.push(name.to_string_lossy().replace("\\", "/"));
+        }
+    }
+    Ok(())
+}
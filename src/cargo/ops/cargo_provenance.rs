@@ -0,0 +1,276 @@
+//! Provenance attestations of published packages.
+//!
+//! With `cargo publish --provenance` a signed statement of how the `.crate`
+//! file was produced is uploaded along with it: who built it, from which
+//! revision of the sources, with which toolchain, and the digest of every file
+//! it contains. It's an [in-toto] statement with a [SLSA provenance] predicate,
+//! wrapped in a [DSSE] envelope, and is also kept next to the `.crate` file as
+//! `<name>-<version>.crate.intoto.json`.
+//!
+//! The identity of the builder and how the statement is signed are configured
+//! in the `[provenance]` table, with either an RSA private key in PEM format
+//! or a command which is given the data to sign and writes the signature:
+//!
+//! ```toml
+//! [provenance]
+//! builder = "https://ci.example.com/runners/linux-1"
+//! key = "keys/provenance.pem"
+//! # command = ["sign-blob", "--input", "{input}", "--output", "{output}"]
+//! ```
+//!
+//! [in-toto]: https://github.com/in-toto/attestation
+//! [SLSA provenance]: https://slsa.dev/provenance/v1
+//! [DSSE]: https://github.com/secure-systems-lab/dsse
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use rustc_serialize::base64::{ToBase64, STANDARD};
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json::Json;
+use tar::Archive;
+
+use core::{SourceId, Workspace};
+use util::{self, paths, human, CargoResult, ChainError, Config, Sha256, ToUrl};
+use util::signature;
+
+const PAYLOAD_TYPE: &'static str = "application/vnd.in-toto+json";
+const BUILD_TYPE: &'static str = "https://doc.crates.io/provenance/publish/v1";
+
+/// How the attestations of packages are produced, from the `[provenance]`
+/// table of the configuration.
+pub struct ProvenanceConfig {
+    builder: String,
+    signer: Signer,
+}
+
+enum Signer {
+    /// An RSA private key in PEM format.
+    Key(PathBuf),
+    /// A command and its arguments, where `{input}` and `{output}` are
+    /// replaced with the paths of the data to sign and of the signature.
+    Command(Vec<String>),
+}
+
+impl ProvenanceConfig {
+    pub fn from_config(config: &Config) -> CargoResult<ProvenanceConfig> {
+        let builder = match try!(config.get_string("provenance.builder")) {
+            Some(builder) => builder.val,
+            None => bail!("provenance attestations record who built the \
+                           package, set `provenance.builder` in the \
+                           configuration"),
+        };
+        let key = try!(config.get_string("provenance.key"));
+        let command = try!(config.get_list("provenance.command"));
+        let signer = match (key, command) {
+            (Some(key), None) => {
+                Signer::Key(key.definition.root(config).join(key.val))
+            }
+            (None, Some(command)) => {
+                if command.val.is_empty() {
+                    bail!("expected `provenance.command` in {} to name a \
+                           program", command.definition)
+                }
+                Signer::Command(command.val.into_iter().map(|(s, _)| s)
+                                       .collect())
+            }
+            (Some(..), Some(..)) => {
+                bail!("only one of `provenance.key` and `provenance.command` \
+                       may be set")
+            }
+            (None, None) => {
+                bail!("provenance attestations are signed, set \
+                       `provenance.key` or `provenance.command` in the \
+                       configuration")
+            }
+        };
+        Ok(ProvenanceConfig {
+            builder: builder,
+            signer: signer,
+        })
+    }
+
+    /// Produces the signed attestation of `crate_path`, the package of the
+    /// current package of `ws` about to be published to `registry`.
+    pub fn attest(&self, ws: &Workspace, crate_path: &Path,
+                  registry: &SourceId) -> CargoResult<Vec<u8>> {
+        let pkg = try!(ws.current());
+        let config = ws.config();
+        try!(config.shell().status("Attesting", pkg.package_id().to_string()));
+
+        let statement = try!(self.statement(ws, crate_path, registry));
+        let payload = statement.to_string();
+
+        // The signature covers the type of the payload as well, in the
+        // pre-authentication encoding of DSSE.
+        let mut pae = format!("DSSEv1 {} {} {} ", PAYLOAD_TYPE.len(),
+                              PAYLOAD_TYPE, payload.len()).into_bytes();
+        pae.extend_from_slice(payload.as_bytes());
+        let sig = try!(self.sign(crate_path, &pae).chain_error(|| {
+            human("failed to sign the provenance attestation")
+        }));
+
+        let envelope = object(vec![
+            ("payloadType", string(PAYLOAD_TYPE)),
+            ("payload", string(payload.as_bytes().to_base64(STANDARD))),
+            ("signatures", Json::Array(vec![object(vec![
+                ("sig", string(sig.to_base64(STANDARD))),
+            ])])),
+        ]);
+        let contents = format!("{}\n", envelope).into_bytes();
+        try!(paths::write(&sibling(crate_path, ".intoto.json"), &contents));
+        Ok(contents)
+    }
+
+    fn statement(&self, ws: &Workspace, crate_path: &Path,
+                 registry: &SourceId) -> CargoResult<Json> {
+        let pkg = try!(ws.current());
+        let config = ws.config();
+        let name = format!("{}-{}", pkg.name(), pkg.version());
+
+        // The files are read back from the `.crate` file, so the digests are
+        // those of exactly what is published.
+        let mut files = BTreeMap::new();
+        let mut vcs_info = None;
+        let tarball = try!(GzDecoder::new(try!(File::open(crate_path))));
+        let mut archive = Archive::new(tarball);
+        for entry in try!(archive.entries()) {
+            let mut entry = try!(entry);
+            let path = try!(entry.path()).into_owned();
+            let path = match path.strip_prefix(&name) {
+                Ok(path) => path.to_string_lossy().replace("\\", "/"),
+                Err(..) => continue,
+            };
+            let mut contents = Vec::new();
+            try!(entry.read_to_end(&mut contents));
+            if path == ".cargo_vcs_info.json" {
+                vcs_info = Json::from_str(&String::from_utf8_lossy(&contents))
+                                .ok();
+            }
+            files.insert(path, sha256(&contents));
+        }
+
+        let mut dependencies = Vec::new();
+        if let Some(info) = vcs_info {
+            let vcs = info.find("vcs").and_then(|v| v.as_string());
+            let revision = info.find("revision").and_then(|v| v.as_string());
+            if let (Some(vcs), Some(revision)) = (vcs, revision) {
+                let uri = match pkg.manifest().metadata().repository {
+                    Some(ref repository) => repository.clone(),
+                    None => try!(pkg.root().to_url()).to_string(),
+                };
+                let dirty = info.find("dirty").and_then(|v| v.as_boolean())
+                                .unwrap_or(false);
+                let key = format!("{}Commit", vcs);
+                dependencies.push(object(vec![
+                    ("uri", string(format!("{}+{}", vcs, uri))),
+                    ("digest", object(vec![
+                        (&key[..], string(revision)),
+                    ])),
+                    ("annotations", object(vec![
+                        ("dirty", Json::Boolean(dirty)),
+                    ])),
+                ]));
+            }
+        }
+        for (path, digest) in files {
+            dependencies.push(object(vec![
+                ("name", string(path)),
+                ("digest", object(vec![("sha256", string(digest))])),
+            ]));
+        }
+
+        let filename = format!("{}.crate", name);
+        let contents = try!(paths::read_bytes(crate_path));
+        let rustc = try!(config.rustc()).verbose_version.lines().next()
+                                        .unwrap_or("").to_string();
+        Ok(object(vec![
+            ("_type", string("https://in-toto.io/Statement/v1")),
+            ("subject", Json::Array(vec![object(vec![
+                ("name", string(filename)),
+                ("digest", object(vec![("sha256", string(sha256(&contents)))])),
+            ])])),
+            ("predicateType", string("https://slsa.dev/provenance/v1")),
+            ("predicate", object(vec![
+                ("buildDefinition", object(vec![
+                    ("buildType", string(BUILD_TYPE)),
+                    ("externalParameters", object(vec![
+                        ("package", string(pkg.name())),
+                        ("version", string(pkg.version())),
+                        ("registry", string(registry.url())),
+                    ])),
+                    ("internalParameters", object(vec![
+                        ("cargo", string(::version())),
+                        ("rustc", string(rustc)),
+                    ])),
+                    ("resolvedDependencies", Json::Array(dependencies)),
+                ])),
+                ("runDetails", object(vec![
+                    ("builder", object(vec![
+                        ("id", string(&self.builder)),
+                    ])),
+                ])),
+            ])),
+        ]))
+    }
+
+    fn sign(&self, crate_path: &Path, data: &[u8]) -> CargoResult<Vec<u8>> {
+        match self.signer {
+            Signer::Key(ref key) => {
+                let key = try!(paths::read_bytes(key));
+                signature::sign(&key, &digest(data))
+            }
+            Signer::Command(ref command) => {
+                let input = sibling(crate_path, ".intoto.pae");
+                let output = sibling(crate_path, ".intoto.sig");
+                try!(paths::write(&input, data));
+                let _ = fs::remove_file(&output);
+
+                let replace = |arg: &String| {
+                    arg.replace("{input}", &input.to_string_lossy())
+                       .replace("{output}", &output.to_string_lossy())
+                };
+                let mut cmd = util::process(replace(&command[0]));
+                for arg in command[1..].iter() {
+                    cmd.arg(replace(arg));
+                }
+                try!(cmd.exec_with_output());
+                let sig = try!(paths::read_bytes(&output).chain_error(|| {
+                    human(format!("`{}` didn't write a signature to `{}`",
+                                  command[0], output.display()))
+                }));
+                let _ = fs::remove_file(&input);
+                let _ = fs::remove_file(&output);
+                Ok(sig)
+            }
+        }
+    }
+}
+
+/// `path` with `suffix` appended to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut dst = path.as_os_str().to_os_string();
+    dst.push(suffix);
+    PathBuf::from(dst)
+}
+
+fn digest(data: &[u8]) -> Vec<u8> {
+    let mut h = Sha256::new();
+    h.update(data);
+    h.finish().to_vec()
+}
+
+fn sha256(data: &[u8]) -> String {
+    digest(data).to_hex()
+}
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn string<S: ToString>(s: S) -> Json {
+    Json::String(s.to_string())
+}
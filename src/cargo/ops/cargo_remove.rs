@@ -0,0 +1,198 @@
+//! Removing dependencies from the manifest of a package, for `cargo remove`.
+//!
+//! Like `cargo add`, the manifest is edited as text to keep its comments and
+//! formatting. Besides the declaration of the dependency, its mentions in
+//! `[features]` are removed once no table declares it anymore, and the
+//! lockfile is updated by resolving the workspace again, which keeps every
+//! package still used locked as it was.
+
+use core::dependency::Kind;
+use core::registry::PackageRegistry;
+use core::Workspace;
+use ops;
+use util::{paths, CargoResult};
+
+pub struct RemoveOptions<'a> {
+    pub dependencies: &'a [String],
+    pub kind: Kind,
+    /// The platform of `[target.<target>.dependencies]` to remove from.
+    pub target: Option<&'a str>,
+}
+
+/// Removes the dependencies of `opts` from the manifest of the current package
+/// of `ws`, and updates the lockfile.
+pub fn remove(ws: &Workspace, opts: &RemoveOptions) -> CargoResult<()> {
+    let config = ws.config();
+    let pkg = try!(ws.current());
+    let manifest_path = pkg.manifest_path();
+
+    let table = match opts.kind {
+        Kind::Normal => "dependencies",
+        Kind::Development => "dev-dependencies",
+        Kind::Build => "build-dependencies",
+    };
+    let shown = match opts.target {
+        Some(target) => format!("target.{}.{}", target, table),
+        None => table.to_string(),
+    };
+
+    let original = try!(paths::read(manifest_path));
+    let mut lines = original.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+    for name in opts.dependencies.iter() {
+        if !undeclare(&mut lines, table, opts.target, name) {
+            bail!("the dependency `{}` could not be found in `{}` of `{}`",
+                  name, shown, manifest_path.display())
+        }
+        try!(config.shell().status("Removing", format!("{} from {}", name, shown)));
+
+        // Features may still use a dependency declared by another table.
+        let declared = pkg.dependencies().iter().any(|d| {
+            d.name() == *name &&
+                (d.kind() != opts.kind ||
+                 d.platform().map(|p| p.to_string()) != opts.target.map(|t| {
+                     t.trim_matches(|c: char| c == '"' || c == '\'').to_string()
+                 }))
+        });
+        if !declared {
+            remove_from_features(&mut lines, name);
+        }
+    }
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    try!(paths::write(manifest_path, contents.as_bytes()));
+
+    let updated = Workspace::new(manifest_path, config).and_then(|ws| {
+        let mut registry = try!(PackageRegistry::new(config));
+        ops::resolve_ws(&mut registry, &ws).map(|_| ())
+    });
+    if let Err(e) = updated {
+        try!(paths::write(manifest_path, original.as_bytes()));
+        return Err(e)
+    }
+    Ok(())
+}
+
+/// The name of the table a header line opens, if it's one.
+fn header(line: &str) -> Option<String> {
+    let line = line.trim();
+    if !line.starts_with('[') {
+        return None
+    }
+    let header = line.trim_matches(|c: char| c == '[' || c == ']').trim();
+    // Quoted keys such as `'cfg(unix)'` are compared without their quotes.
+    Some(header.chars().filter(|&c| c != '"' && c != '\'').collect())
+}
+
+/// Removes the declaration of `name` from `table`, either a line of the table
+/// or the `[table.name]` table itself. Returns whether it was declared.
+fn undeclare(lines: &mut Vec<String>, table: &str, target: Option<&str>,
+             name: &str) -> bool {
+    let target = target.map(|t| {
+        t.chars().filter(|&c| c != '"' && c != '\'').collect::<String>()
+    });
+    let tables = [table.to_string(), table.replace("-", "_")].iter().map(|t| {
+        match target {
+            Some(ref target) => format!("target.{}.{}", target, t),
+            None => t.clone(),
+        }
+    }).collect::<Vec<_>>();
+
+    let mut in_table = false;
+    for i in 0..lines.len() {
+        if let Some(opened) = header(&lines[i]) {
+            in_table = tables.contains(&opened);
+            if tables.iter().any(|t| opened == format!("{}.{}", t, name)) {
+                // Up to the next table, leaving the blank lines before it, and
+                // with the blank line separating it from the previous one.
+                let mut end = i + 1;
+                while end < lines.len() && header(&lines[end]).is_none() {
+                    end += 1;
+                }
+                while end > i + 1 && lines[end - 1].trim().is_empty() {
+                    end -= 1;
+                }
+                let start = if i > 0 && lines[i - 1].trim().is_empty() {i - 1} else {i};
+                lines.drain(start..end);
+                return true
+            }
+            continue
+        }
+        let trimmed = lines[i].trim().to_string();
+        if !in_table || trimmed.starts_with('#') {
+            continue
+        }
+        if let Some(pos) = trimmed.find('=') {
+            let key = trimmed[..pos].trim()
+                                    .trim_matches(|c: char| c == '"' || c == '\'');
+            if key == name {
+                lines.remove(i);
+                return true
+            }
+        }
+    }
+    false
+}
+
+/// Removes `name` and `name/feature` from the features in `[features]`, and
+/// the lines of arrays left empty by it.
+fn remove_from_features(lines: &mut Vec<String>, name: &str) {
+    let mut in_features = false;
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some(opened) = header(&lines[i]) {
+            in_features = opened == "features";
+        } else if in_features {
+            let edited = remove_strings(&lines[i], |s| {
+                s == name || s.starts_with(&format!("{}/", name))
+            });
+            if edited.trim().is_empty() && !lines[i].trim().is_empty() {
+                lines.remove(i);
+                continue
+            }
+            lines[i] = edited;
+        }
+        i += 1;
+    }
+}
+
+/// Removes the quoted strings matching `remove` from a line of an array,
+/// along with the comma separating each from the next or previous element.
+fn remove_strings<F>(line: &str, remove: F) -> String
+    where F: Fn(&str) -> bool
+{
+    let mut out = line.to_string();
+    let mut from = match out.find('=') {
+        Some(pos) => pos + 1,
+        None => 0,
+    };
+    loop {
+        let start = match out[from..].find(|c: char| c == '"' || c == '\'') {
+            Some(start) => from + start,
+            None => return out,
+        };
+        let quote = out[start..].chars().next().unwrap();
+        let end = match out[start + 1..].find(quote) {
+            Some(end) => start + 1 + end + 1,
+            None => return out,
+        };
+        if !remove(&out[start + 1..end - 1]) {
+            from = end;
+            continue
+        }
+        let (start, end) = {
+            let rest = out[end..].trim_left();
+            let before = out[..start].trim_right();
+            if rest.starts_with(',') {
+                let comma = out.len() - rest.len();
+                let next = &out[comma + 1..];
+                (start, comma + 1 + next.len() - next.trim_left().len())
+            } else if before.ends_with(',') {
+                (before.len() - 1, end)
+            } else {
+                (start, end)
+            }
+        };
+        out = format!("{}{}", &out[..start], &out[end..]);
+        from = start;
+    }
+}
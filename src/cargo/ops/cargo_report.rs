@@ -0,0 +1,365 @@
+//! Flagging locked dependencies which have fallen behind the registry they
+//! come from, either because the version used was published long ago or
+//! because newer major versions of the package have since been released.
+//!
+//! The policy is configured in the `[staleness]` table. When a threshold is
+//! configured it is checked on every build, and `cargo report staleness`
+//! evaluates it on demand. `cargo outdated` lists every dependency with a
+//! newer release instead, regardless of the policy.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use semver::Version;
+
+use core::{Dependency, PackageId, Registry, Source, SourceId, Workspace};
+use core::registry::PackageRegistry;
+use core::resolver::Resolve;
+use ops;
+use sources::SourceConfigMap;
+use util::{CargoResult, Config};
+
+const SECONDS_PER_YEAR: u64 = 31_557_600;
+
+/// What to do about dependencies which break the staleness policy.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StalenessAction {
+    /// Print a warning for each of them.
+    Warn,
+    /// Fail the build.
+    Deny,
+}
+
+/// The thresholds past which a locked dependency is considered stale.
+pub struct StalenessPolicy {
+    /// The age, in years, of the oldest version allowed.
+    pub max_age: Option<u64>,
+    /// How many major versions a dependency may fall behind the newest one
+    /// in the registry.
+    pub max_major_behind: Option<u64>,
+    pub action: StalenessAction,
+}
+
+impl StalenessPolicy {
+    /// Reads the policy from the `staleness.max-age`,
+    /// `staleness.max-major-behind` and `staleness.action` configuration
+    /// keys. Returns `None` if no threshold is configured.
+    pub fn from_config(config: &Config) -> CargoResult<Option<StalenessPolicy>> {
+        let max_age = try!(non_negative(config, "staleness.max-age"));
+        let max_major_behind = try!(non_negative(config,
+                                                 "staleness.max-major-behind"));
+        if max_age.is_none() && max_major_behind.is_none() {
+            return Ok(None)
+        }
+        let action = match try!(config.get_string("staleness.action")) {
+            None => StalenessAction::Warn,
+            Some(v) => match &v.val[..] {
+                "warn" => StalenessAction::Warn,
+                "deny" => StalenessAction::Deny,
+                s => bail!("invalid configuration for key `staleness.action`, \
+                            expected `warn` or `deny` but found `{}` in {}",
+                           s, v.definition),
+            },
+        };
+        Ok(Some(StalenessPolicy {
+            max_age: max_age,
+            max_major_behind: max_major_behind,
+            action: action,
+        }))
+    }
+}
+
+fn non_negative(config: &Config, key: &str) -> CargoResult<Option<u64>> {
+    match try!(config.get_i64(key)) {
+        Some(v) => {
+            if v.val < 0 {
+                bail!("{} must not be negative, but found {} in {}",
+                      key, v.val, v.definition)
+            }
+            Ok(Some(v.val as u64))
+        }
+        None => Ok(None),
+    }
+}
+
+/// A locked dependency which breaks the staleness policy.
+pub struct StaleDependency {
+    pub id: PackageId,
+    /// How many whole years ago the locked version was published, if the
+    /// registry records it.
+    pub age: Option<u64>,
+    /// The newest version in the registry, if it's semver incompatible with
+    /// the locked one.
+    pub latest: Option<Version>,
+    /// How many major versions were released after the locked one.
+    pub majors_behind: u64,
+}
+
+impl StaleDependency {
+    /// Describes why the dependency is stale, e.g. "published 4 years ago,
+    /// 2 major versions behind 3.0.0".
+    pub fn describe(&self) -> String {
+        let mut reasons = Vec::new();
+        if let Some(age) = self.age {
+            reasons.push(format!("published {} year{} ago", age,
+                                 if age == 1 { "" } else { "s" }));
+        }
+        if let Some(ref latest) = self.latest {
+            reasons.push(format!("{} major version{} behind {}",
+                                 self.majors_behind,
+                                 if self.majors_behind == 1 { "" } else { "s" },
+                                 latest));
+        }
+        reasons.join(", ")
+    }
+}
+
+/// Options for `cargo report staleness`, overriding the configured policy.
+pub struct StalenessOptions {
+    pub max_age: Option<u64>,
+    pub max_major_behind: Option<u64>,
+}
+
+/// Checks the dependencies of a build against the configured policy, if any,
+/// warning about those which are stale or failing if it denies them.
+pub fn check_staleness(ws: &Workspace, resolve: &Resolve) -> CargoResult<()> {
+    let policy = match try!(StalenessPolicy::from_config(ws.config())) {
+        Some(policy) => policy,
+        None => return Ok(()),
+    };
+    let stale = try!(stale_dependencies(ws.config(), resolve, &policy));
+    enforce(ws.config(), &policy, &stale)
+}
+
+/// Finds the locked dependencies of the workspace which break the policy,
+/// returning them along with the policy they were checked against.
+pub fn report_staleness(ws: &Workspace, opts: &StalenessOptions)
+                        -> CargoResult<(StalenessPolicy, Vec<StaleDependency>)> {
+    let config = ws.config();
+    let mut policy = try!(StalenessPolicy::from_config(config)).unwrap_or(
+        StalenessPolicy {
+            max_age: None,
+            max_major_behind: None,
+            action: StalenessAction::Warn,
+        });
+    policy.max_age = opts.max_age.or(policy.max_age);
+    policy.max_major_behind = opts.max_major_behind.or(policy.max_major_behind);
+    if policy.max_age.is_none() && policy.max_major_behind.is_none() {
+        bail!("no staleness policy to report on, configure \
+               `staleness.max-age` or `staleness.max-major-behind`, or pass \
+               `--max-age` or `--max-major-behind`")
+    }
+
+    let mut registry = try!(PackageRegistry::new(config));
+    let resolve = try!(ops::resolve_ws(&mut registry, ws));
+    let stale = try!(stale_dependencies(config, &resolve, &policy));
+    Ok((policy, stale))
+}
+
+fn enforce(config: &Config,
+           policy: &StalenessPolicy,
+           stale: &[StaleDependency]) -> CargoResult<()> {
+    if stale.is_empty() {
+        return Ok(())
+    }
+    match policy.action {
+        StalenessAction::Warn => {
+            for dep in stale {
+                try!(config.shell().warn(format!("`{}` is stale: {}",
+                                                 dep.id, dep.describe())));
+            }
+            Ok(())
+        }
+        StalenessAction::Deny => {
+            let list = stale.iter().map(|dep| {
+                format!("  {}: {}", dep.id, dep.describe())
+            }).collect::<Vec<_>>();
+            bail!("the staleness policy denies these dependencies:\n{}\n\
+                   update them or relax `staleness` in the configuration",
+                  list.join("\n"))
+        }
+    }
+}
+
+/// Compares every registry package in `resolve` with what its registry's
+/// index offers. Only the local copy of the index is consulted.
+fn stale_dependencies(config: &Config,
+                      resolve: &Resolve,
+                      policy: &StalenessPolicy) -> CargoResult<Vec<StaleDependency>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                               .map(|d| d.as_secs()).unwrap_or(0);
+    let sources = try!(SourceConfigMap::new(config));
+    let mut loaded = HashMap::new();
+
+    let mut ids = resolve.iter().filter(|id| {
+        id.source_id().is_registry()
+    }).collect::<Vec<_>>();
+    ids.sort();
+
+    let mut stale = Vec::new();
+    for id in ids {
+        let source_id = id.source_id().with_precise(None);
+        if !loaded.contains_key(&source_id) {
+            let source = try!(sources.load(&source_id));
+            loaded.insert(source_id.clone(), source);
+        }
+        let source = loaded.get_mut(&source_id).unwrap();
+        let dep = try!(Dependency::parse(id.name(), None, &source_id));
+        let summaries = try!(source.query(&dep));
+
+        let age = summaries.iter().find(|s| s.version() == id.version())
+                           .and_then(|s| s.published())
+                           .map(|published| now.saturating_sub(published));
+        let age = age.and_then(|age| {
+            match policy.max_age {
+                Some(max) if age > max * SECONDS_PER_YEAR => {
+                    Some(age / SECONDS_PER_YEAR)
+                }
+                _ => None,
+            }
+        });
+
+        // Every release which isn't semver compatible with its predecessors
+        // counts as a major version.
+        let newer = summaries.iter().map(|s| s.version())
+                             .filter(|v| v.pre.is_empty() &&
+                                         major(v) > major(id.version()))
+                             .collect::<Vec<_>>();
+        let majors_behind = newer.iter().map(|v| major(v))
+                                 .collect::<BTreeSet<_>>().len() as u64;
+        let latest = match policy.max_major_behind {
+            Some(max) if majors_behind > max => {
+                newer.iter().max().map(|v| (*v).clone())
+            }
+            _ => None,
+        };
+
+        if age.is_some() || latest.is_some() {
+            stale.push(StaleDependency {
+                id: id.clone(),
+                age: age,
+                latest: latest,
+                majors_behind: majors_behind,
+            });
+        }
+    }
+    Ok(stale)
+}
+
+/// The part of a version which semver compatibility is decided on.
+fn major(v: &Version) -> (u64, u64, u64) {
+    match (v.major, v.minor) {
+        (0, 0) => (0, 0, v.patch),
+        (0, minor) => (0, minor, 0),
+        (major, _) => (major, 0, 0),
+    }
+}
+
+/// A locked dependency of a workspace member for which newer versions were
+/// released.
+pub struct OutdatedDependency {
+    pub id: PackageId,
+    /// Whether the member depends on it itself, rather than through other
+    /// packages.
+    pub direct: bool,
+    /// The newest release semver compatible with the locked version, if it's
+    /// newer than it.
+    pub compatible: Option<Version>,
+    /// The newest release, if it isn't semver compatible with the locked
+    /// version.
+    pub latest: Option<Version>,
+}
+
+pub struct OutdatedOptions {
+    /// Only look at the dependencies the members declare themselves.
+    pub direct_only: bool,
+}
+
+/// Finds the outdated registry dependencies of each member of the workspace,
+/// sorted by member and then by dependency.
+///
+/// The dependencies are taken from the workspace's own resolve, so packages
+/// replaced with `[replace]` are left out, as the version locked for them
+/// isn't the one used. The index of each registry is updated first.
+pub fn report_outdated(ws: &Workspace, opts: &OutdatedOptions)
+                       -> CargoResult<Vec<(PackageId, Vec<OutdatedDependency>)>> {
+    let config = ws.config();
+    let mut registry = try!(PackageRegistry::new(config));
+    let resolve = try!(ops::resolve_ws(&mut registry, ws));
+    let sources = try!(SourceConfigMap::new(config));
+    let mut loaded = HashMap::new();
+    let mut newer = HashMap::new();
+
+    let mut members = ws.members().map(|m| m.package_id().clone())
+                        .collect::<Vec<_>>();
+    members.sort();
+    let mut report = Vec::new();
+    for member in members {
+        // The packages reachable from the member.
+        let direct = resolve.deps(&member).collect::<HashSet<_>>();
+        let mut reached = Vec::new();
+        let mut visited = HashSet::new();
+        let mut todo = direct.iter().cloned().collect::<Vec<_>>();
+        while let Some(id) = todo.pop() {
+            if !visited.insert(id) {
+                continue
+            }
+            reached.push(id);
+            if !opts.direct_only {
+                todo.extend(resolve.deps(id));
+            }
+        }
+
+        let mut outdated = Vec::new();
+        for &id in reached.iter() {
+            if !id.source_id().is_registry() ||
+               resolve.replacements().contains_key(id) {
+                continue
+            }
+            if !newer.contains_key(id) {
+                let versions = try!(newer_versions(&sources, &mut loaded, id));
+                newer.insert(id.clone(), versions);
+            }
+            if let Some(&(ref compatible, ref latest)) = newer.get(id) {
+                if compatible.is_some() || latest.is_some() {
+                    outdated.push(OutdatedDependency {
+                        id: id.clone(),
+                        direct: direct.contains(&id),
+                        compatible: compatible.clone(),
+                        latest: latest.clone(),
+                    });
+                }
+            }
+        }
+        outdated.sort_by(|a, b| a.id.cmp(&b.id));
+        report.push((member, outdated));
+    }
+    Ok(report)
+}
+
+/// The newest compatible and incompatible releases of `id`, as in
+/// `OutdatedDependency`.
+fn newer_versions<'cfg>(sources: &SourceConfigMap<'cfg>,
+                        loaded: &mut HashMap<SourceId, Box<Source + 'cfg>>,
+                        id: &PackageId)
+                        -> CargoResult<(Option<Version>, Option<Version>)> {
+    let source_id = id.source_id().with_precise(None);
+    if !loaded.contains_key(&source_id) {
+        let mut source = try!(sources.load(&source_id));
+        try!(source.update());
+        loaded.insert(source_id.clone(), source);
+    }
+    let source = loaded.get_mut(&source_id).unwrap();
+    let dep = try!(Dependency::parse(id.name(), None, &source_id));
+    let summaries = try!(source.query(&dep));
+    let versions = summaries.iter().map(|s| s.version())
+                            .filter(|v| v.pre.is_empty() && *v > id.version())
+                            .collect::<Vec<_>>();
+    let compatible = versions.iter().filter(|v| major(v) == major(id.version()))
+                             .max().map(|v| (*v).clone());
+    let latest = versions.iter().max().map(|v| (*v).clone())
+                         .and_then(|v| {
+        if major(&v) != major(id.version()) {Some(v)} else {None}
+    });
+    Ok((compatible, latest))
+}
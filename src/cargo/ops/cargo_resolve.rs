@@ -0,0 +1,125 @@
+//! Recording the packages the resolution of a workspace considers, and
+//! replaying that resolution to measure the performance of the resolver.
+//!
+//! A recording holds the summaries of the members of the workspace along with
+//! those of every candidate the registry offered the resolver, so replaying
+//! it involves neither the network nor the filesystem. Recordings of large
+//! graphs make regressions in the resolver measurable.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rustc_serialize::json;
+
+use core::{Dependency, PackageIdSpec, Registry, Summary, Workspace};
+use core::registry::PackageRegistry;
+use core::resolver::{self, Method, VersionOrder};
+use ops::resolve::{Recorder, RecordedDependency, RecordedSummary};
+use util::{paths, CargoResult, ChainError, human};
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct RecordedGraph {
+    roots: Vec<RecordedSummary>,
+    replace: Vec<(String, RecordedDependency)>,
+    packages: Vec<RecordedSummary>,
+}
+
+/// The outcome of replaying a recorded resolution.
+pub struct ResolveBench {
+    /// The number of packages in the resolution.
+    pub packages: usize,
+    /// The number of packages the resolver had to choose from.
+    pub candidates: usize,
+    /// How long each resolution took.
+    pub times: Vec<Duration>,
+}
+
+/// Resolves the workspace from scratch, ignoring any lockfile, and writes
+/// down everything the resolver was offered to `out`.
+pub fn record_resolve(ws: &Workspace, out: &Path) -> CargoResult<()> {
+    let mut registry = try!(PackageRegistry::new(ws.config()));
+    let mut roots = Vec::new();
+    for member in ws.members() {
+        try!(registry.add_sources(&[member.package_id().source_id()
+                                          .clone()]));
+        roots.push((member.summary().clone(), Method::Everything));
+    }
+    let replace = ws.root_replace();
+
+    let queries = {
+        let mut recorder = Recorder::new(&mut registry);
+        try!(resolver::resolve(&roots, replace, VersionOrder::Newest,
+                               &mut recorder));
+        recorder.queries
+    };
+    let mut packages = BTreeMap::new();
+    for summary in queries.into_iter().flat_map(|(_, answer)| answer) {
+        packages.entry(summary.package_id().clone()).or_insert_with(|| {
+            RecordedSummary::new(&summary)
+        });
+    }
+
+    let graph = RecordedGraph {
+        roots: roots.iter().map(|&(ref s, _)| RecordedSummary::new(s)).collect(),
+        replace: replace.iter().map(|&(ref spec, ref dep)| {
+            (spec.to_string(), RecordedDependency::new(dep))
+        }).collect(),
+        packages: packages.into_iter().map(|(_, s)| s).collect(),
+    };
+    try!(paths::write(out, try!(json::encode(&graph)).as_bytes()));
+    ws.config().shell().status("Recorded",
+                               format!("{} candidates to `{}`",
+                                       graph.packages.len(), out.display()))
+}
+
+/// Resolves the graph recorded in `file` as many times as `iterations`.
+pub fn bench_resolve(file: &Path, iterations: u32) -> CargoResult<ResolveBench> {
+    let contents = try!(paths::read(file));
+    let graph = try!(json::decode::<RecordedGraph>(&contents).chain_error(|| {
+        human(format!("`{}` is not a recorded resolution", file.display()))
+    }));
+
+    let mut roots = Vec::new();
+    for root in graph.roots.iter() {
+        roots.push((try!(root.to_summary()), Method::Everything));
+    }
+    let mut replace = Vec::new();
+    for &(ref spec, ref dep) in graph.replace.iter() {
+        replace.push((try!(PackageIdSpec::parse(spec)), try!(dep.to_dependency())));
+    }
+    let mut registry = Replay { summaries: HashMap::new() };
+    for package in graph.packages.iter() {
+        registry.summaries.entry(package.package_id().name().to_string())
+                .or_insert(Vec::new())
+                .push(try!(package.to_summary()));
+    }
+
+    let mut ret = ResolveBench {
+        packages: 0,
+        candidates: graph.packages.len(),
+        times: Vec::new(),
+    };
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let resolve = try!(resolver::resolve(&roots, &replace,
+                                             VersionOrder::Newest,
+                                             &mut registry));
+        ret.times.push(start.elapsed());
+        ret.packages = resolve.iter().count();
+    }
+    Ok(ret)
+}
+
+/// Answers queries with the recorded summaries.
+struct Replay {
+    summaries: HashMap<String, Vec<Summary>>,
+}
+
+impl Registry for Replay {
+    fn query(&mut self, dep: &Dependency) -> CargoResult<Vec<Summary>> {
+        Ok(self.summaries.get(dep.name()).map(|summaries| {
+            summaries.iter().filter(|s| dep.matches(s)).cloned().collect()
+        }).unwrap_or(Vec::new()))
+    }
+}
@@ -10,13 +10,13 @@ pub fn run(ws: &Workspace,
     let config = ws.config();
     let root = try!(ws.current());
 
-    let mut bins = root.manifest().targets().iter().filter(|a| {
+    let bins = root.manifest().targets().iter().filter(|a| {
         !a.is_lib() && !a.is_custom_build() && match options.filter {
             CompileFilter::Everything => a.is_bin(),
             CompileFilter::Only { .. } => options.filter.matches(a),
         }
-    });
-    if bins.next().is_none() {
+    }).collect::<Vec<_>>();
+    if bins.is_empty() {
         match options.filter {
             CompileFilter::Everything => {
                 bail!("a bin target must be available for `cargo run`")
@@ -26,7 +26,7 @@ pub fn run(ws: &Workspace,
             }
         }
     }
-    if bins.next().is_some() {
+    if bins.len() > 1 {
         match options.filter {
             CompileFilter::Everything => {
                 bail!("`cargo run` requires that a project only have one \
@@ -48,9 +48,12 @@ pub fn run(ws: &Workspace,
         Some(path) => path.to_path_buf(),
         None => exe.to_path_buf(),
     };
-    let mut process = try!(compile.target_process(exe, &root))
+    let mut process = try!(compile.runner_process(&exe, args, &root, bins[0],
+                                                  config.cwd()))
                                   .into_process_builder();
-    process.args(args).cwd(config.cwd());
+    for (key, value) in try!(config.run_env()) {
+        process.env(&key, &value);
+    }
 
     try!(config.shell().status("Running", process.to_string()));
     Ok(process.exec().err())
@@ -0,0 +1,191 @@
+//! Describing the crate graph of a workspace in the `rust-project.json`
+//! format understood by rust-analyzer.
+//!
+//! The workspace is built first, so the `OUT_DIR` of each package has been
+//! filled in by its build script and the procedural macros rust-analyzer
+//! needs to load have been compiled. The description then includes
+//! everything rust-analyzer would otherwise have to learn by running Cargo
+//! itself.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rustc_serialize::{Encodable, Encoder};
+
+use core::{Package, PackageId, Target, Workspace};
+use ops::{self, CompileOptions};
+use util::{CargoResult, Cfg};
+
+/// Cargo predates editions, so every crate is of the first one.
+const EDITION: &'static str = "2015";
+
+#[derive(RustcEncodable)]
+pub struct RustProject {
+    sysroot: Option<String>,
+    sysroot_src: Option<String>,
+    crates: Vec<Crate>,
+}
+
+#[derive(RustcEncodable)]
+struct Crate {
+    display_name: String,
+    root_module: String,
+    edition: &'static str,
+    version: String,
+    deps: Vec<Dep>,
+    is_workspace_member: bool,
+    cfg: Vec<String>,
+    target: String,
+    env: BTreeMap<String, String>,
+    is_proc_macro: bool,
+    proc_macro_dylib_path: Option<String>,
+}
+
+/// A dependency on the crate at index `krate` of the list of crates.
+struct Dep {
+    krate: usize,
+    name: String,
+}
+
+impl Encodable for Dep {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("Dep", 2, |s| {
+            try!(s.emit_struct_field("crate", 0, |s| self.krate.encode(s)));
+            s.emit_struct_field("name", 1, |s| self.name.encode(s))
+        })
+    }
+}
+
+/// Builds the packages selected by `options` and describes them along with
+/// all of their dependencies.
+pub fn rust_project<'a>(ws: &Workspace<'a>, options: &CompileOptions<'a>)
+                    -> CargoResult<RustProject> {
+    let compilation = try!(ops::compile_ws(ws, None, options));
+
+    let features = options.features.iter().flat_map(|s| {
+        s.split(' ')
+    }).map(|s| s.to_string()).collect::<Vec<String>>();
+    let (packages, resolve) = try!(ops::resolve_dependencies(ws,
+                                                             None,
+                                                             features,
+                                                             options.all_features,
+                                                             options.no_default_features));
+
+    let mut ids = packages.package_ids().collect::<Vec<_>>();
+    ids.sort();
+    let mut pkgs = HashMap::new();
+    for &id in ids.iter() {
+        pkgs.insert(id, try!(packages.get(id)));
+    }
+    let is_member = |id: &PackageId| {
+        ws.members().any(|m| m.package_id() == id)
+    };
+
+    // Libraries come first so binaries can refer to them, followed by the
+    // binaries of the members of the workspace.
+    let mut roots: Vec<(&Package, &Target)> = Vec::new();
+    for &id in ids.iter() {
+        let pkg = pkgs[id];
+        if let Some(lib) = pkg.targets().iter().find(|t| t.is_lib()) {
+            roots.push((pkg, lib));
+        }
+    }
+    let libs = roots.iter().enumerate().map(|(i, &(pkg, _))| {
+        (pkg.package_id(), i)
+    }).collect::<HashMap<_, _>>();
+    for &id in ids.iter().filter(|id| is_member(id)) {
+        let pkg = pkgs[id];
+        roots.extend(pkg.targets().iter().filter(|t| t.is_bin()).map(|t| {
+            (pkg, t)
+        }));
+    }
+
+    let target_cfg = compilation.target_cfg.as_ref().map(|cfg| &cfg[..]);
+    let host_cfg = compilation.host_cfg.as_ref().map(|cfg| &cfg[..]);
+    let mut crates = Vec::new();
+    for &(pkg, target) in roots.iter() {
+        let id = pkg.package_id();
+        let member = is_member(id);
+        // Procedural macros are built for the host, as `Kind::Host` units.
+        let (triple, platform_cfg) = if target.for_host() {
+            (&compilation.host_triple, host_cfg)
+        } else {
+            (&compilation.target_triple, target_cfg)
+        };
+        let mut features = resolve.features(id).map(|f| {
+            f.iter().cloned().collect::<Vec<_>>()
+        }).unwrap_or(Vec::new());
+        features.sort();
+
+        let mut deps = Vec::new();
+        if target.is_bin() {
+            if let Some(&krate) = libs.get(id) {
+                deps.push(Dep { krate: krate, name: roots[krate].1.crate_name() });
+            }
+        }
+        for dep_id in resolve.deps(id) {
+            let krate = match libs.get(dep_id) {
+                Some(&krate) => krate,
+                None => continue,
+            };
+            let used = pkg.dependencies().iter().any(|d| {
+                d.name() == dep_id.name() && !d.is_build() &&
+                    (d.is_transitive() || member) &&
+                    d.platform().map(|p| {
+                        p.matches(triple, platform_cfg)
+                    }).unwrap_or(true) &&
+                    (!d.is_optional() || features.iter().any(|f| f == d.name()))
+            });
+            if used {
+                deps.push(Dep { krate: krate, name: roots[krate].1.crate_name() });
+            }
+        }
+
+        let mut cfg = features.iter().map(|f| {
+            format!("feature=\"{}\"", f)
+        }).collect::<Vec<_>>();
+        cfg.extend(platform_cfg.unwrap_or(&[]).iter().map(|c| {
+            match *c {
+                Cfg::Name(ref name) => name.clone(),
+                Cfg::KeyPair(ref k, ref v) => format!("{}=\"{}\"", k, v),
+            }
+        }));
+
+        let mut env = BTreeMap::new();
+        env.insert("CARGO_PKG_NAME".to_string(), pkg.name().to_string());
+        env.insert("CARGO_PKG_VERSION".to_string(), pkg.version().to_string());
+        env.insert("CARGO_MANIFEST_DIR".to_string(),
+                   pkg.root().display().to_string());
+        if let Some(out_dir) = compilation.out_dirs.get(id) {
+            env.insert("OUT_DIR".to_string(), out_dir.display().to_string());
+        }
+
+        crates.push(Crate {
+            display_name: target.crate_name(),
+            root_module: target.src_path().display().to_string(),
+            edition: EDITION,
+            version: pkg.version().to_string(),
+            deps: deps,
+            is_workspace_member: member,
+            cfg: cfg,
+            target: triple.clone(),
+            env: env,
+            is_proc_macro: target.is_rustc_macro(),
+            proc_macro_dylib_path: if target.is_rustc_macro() {
+                compilation.rustc_macros.get(id).map(|p| {
+                    p.display().to_string()
+                })
+            } else {
+                None
+            },
+        });
+    }
+
+    let sysroot = compilation.sysroot.as_ref();
+    Ok(RustProject {
+        sysroot: sysroot.map(|p| p.display().to_string()),
+        sysroot_src: sysroot.map(|p| {
+            p.join("lib/rustlib/src/rust/src").display().to_string()
+        }),
+        crates: crates,
+    })
+}
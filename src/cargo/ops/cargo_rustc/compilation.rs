@@ -1,12 +1,21 @@
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use semver::Version;
 
-use core::{PackageId, Package, Target};
-use util::{self, CargoResult, Config};
+use core::{PackageId, Package, Target, Runner};
+use util::{self, CargoResult, Cfg, Config};
 
-use super::{CommandType, CommandPrototype};
+use super::{CommandType, CommandPrototype, Preset, TargetRunner};
+
+/// How the doctests of a target are run when cross-compiling.
+pub enum DoctestRunner {
+    /// Through the runner, as rustdoc's `--runtool`, or directly if its
+    /// program is empty, in its environment.
+    Runner(Runner),
+    /// They can't be run, for the reason given.
+    Unavailable(String),
+}
 
 /// A structure returning the result of a compilation.
 pub struct Compilation<'cfg> {
@@ -17,7 +26,7 @@ pub struct Compilation<'cfg> {
     pub libraries: HashMap<PackageId, Vec<(Target, PathBuf)>>,
 
     /// An array of all tests created during this compilation.
-    pub tests: Vec<(Package, String, PathBuf)>,
+    pub tests: Vec<(Package, Target, PathBuf)>,
 
     /// An array of all binaries created.
     pub binaries: Vec<PathBuf>,
@@ -35,6 +44,10 @@ pub struct Compilation<'cfg> {
     /// Output directory for rust dependencies
     pub deps_output: PathBuf,
 
+    /// Output directory for the dependencies of the host, such as procedural
+    /// macros, when cross-compiling.
+    pub host_deps_output: PathBuf,
+
     /// Extra environment variables that were passed to compilations and should
     /// be passed to future invocations of programs.
     pub extra_env: HashMap<PackageId, Vec<(String, String)>>,
@@ -44,6 +57,30 @@ pub struct Compilation<'cfg> {
     /// Features enabled during this compilation.
     pub cfgs: HashSet<String>,
 
+    /// The platform compiled for, and the `cfg` values rustc reported for it.
+    pub target_triple: String,
+    pub target_cfg: Option<Vec<Cfg>>,
+
+    /// The same for the host, which plugins and build scripts are built for.
+    pub host_triple: String,
+    pub host_cfg: Option<Vec<Cfg>>,
+
+    /// The sysroot of the compiler used for the target.
+    pub sysroot: Option<PathBuf>,
+
+    /// The linker configured for the target, if any.
+    pub target_linker: Option<PathBuf>,
+
+    /// What binaries built for the target are run through, unless their
+    /// target has a runner of its own.
+    pub runner: Option<TargetRunner>,
+
+    /// The `OUT_DIR` of every package compiled whose build script ran.
+    pub out_dirs: HashMap<PackageId, PathBuf>,
+
+    /// The dynamic library of every compiled package of procedural macros.
+    pub rustc_macros: HashMap<PackageId, PathBuf>,
+
     config: &'cfg Config,
 }
 
@@ -54,11 +91,21 @@ impl<'cfg> Compilation<'cfg> {
             native_dirs: HashSet::new(),  // TODO: deprecated, remove
             root_output: PathBuf::from("/"),
             deps_output: PathBuf::from("/"),
+            host_deps_output: PathBuf::from("/"),
             tests: Vec::new(),
             binaries: Vec::new(),
             extra_env: HashMap::new(),
             to_doc_test: Vec::new(),
             cfgs: HashSet::new(),
+            target_triple: String::new(),
+            target_cfg: None,
+            host_triple: String::new(),
+            host_cfg: None,
+            sysroot: None,
+            target_linker: None,
+            runner: None,
+            out_dirs: HashMap::new(),
+            rustc_macros: HashMap::new(),
             config: config,
         }
     }
@@ -86,6 +133,79 @@ impl<'cfg> Compilation<'cfg> {
         self.process(CommandType::Host(cmd.as_ref().to_os_string()), pkg)
     }
 
+    /// Prepares a process running the binary `exe` built for `target` with the
+    /// arguments `args`, through the runner of the target or else the one of
+    /// the platform compiled for, if any, in the working directory `cwd` unless
+    /// the runner has one.
+    pub fn runner_process(&self, exe: &Path, args: &[String], pkg: &Package,
+                          target: &Target, cwd: &Path)
+                          -> CargoResult<CommandPrototype> {
+        // Presets are given the binary and its arguments, while other runners
+        // are followed by them.
+        let (runner, exe) = match (target.runner(), self.runner.as_ref()) {
+            (Some(runner), _) => (runner.clone(), Some(cwd.join(exe))),
+            (None, Some(&TargetRunner::Command(ref runner))) => {
+                (runner.clone(), Some(cwd.join(exe)))
+            }
+            (None, Some(&TargetRunner::Preset(ref preset))) => {
+                let exe = cwd.join(exe);
+                (try!(preset.command(&self.target_triple, &exe, args)), None)
+            }
+            (None, Some(&TargetRunner::Unavailable(ref why))) => bail!("{}", why),
+            (None, None) => {
+                let mut cmd = try!(self.target_process(exe, pkg));
+                cmd.args(args).cwd(cwd);
+                return Ok(cmd)
+            }
+        };
+        if runner.program.is_empty() {
+            bail!("the runner of `{}` must not be empty", target.name())
+        }
+        let mut cmd = try!(self.target_process(runner_program(&runner, pkg), pkg));
+        cmd.args(&runner.args);
+        if let Some(exe) = exe {
+            cmd.arg(exe).args(args);
+        }
+        for &(ref key, ref value) in runner.env.iter() {
+            cmd.env(key, value);
+        }
+        match runner.cwd {
+            Some(ref dir) => cmd.cwd(pkg.root().join(dir)),
+            None => cmd.cwd(cwd),
+        };
+        Ok(cmd)
+    }
+
+    /// How the doctests of `target` are run when cross-compiling.
+    pub fn doctest_runner(&self, pkg: &Package, target: &Target)
+                          -> CargoResult<DoctestRunner> {
+        let mut runner = match (target.runner(), self.runner.as_ref()) {
+            (Some(runner), _) => runner.clone(),
+            (None, Some(&TargetRunner::Command(ref runner))) => runner.clone(),
+            (None, Some(&TargetRunner::Preset(Preset::QemuSystem(..)))) => {
+                return Ok(DoctestRunner::Unavailable(format!("\
+the `qemu-system` runner of `{}` can only boot binaries it's given",
+                                                             self.target_triple)))
+            }
+            (None, Some(&TargetRunner::Preset(ref preset))) => {
+                try!(preset.runtool(&self.target_triple))
+            }
+            (None, Some(&TargetRunner::Unavailable(ref why))) => {
+                return Ok(DoctestRunner::Unavailable(why.clone()))
+            }
+            (None, None) => Runner {
+                program: String::new(),
+                args: Vec::new(),
+                env: Vec::new(),
+                cwd: None,
+            },
+        };
+        if !runner.program.is_empty() {
+            runner.program = runner_program(&runner, pkg).display().to_string();
+        }
+        Ok(DoctestRunner::Runner(runner))
+    }
+
     /// Prepares a new process with an appropriate environment to run against
     /// the artifacts produced by the build process.
     ///
@@ -143,6 +263,17 @@ impl<'cfg> Compilation<'cfg> {
     }
 }
 
+/// The program of `runner`, which is relative to the root of `pkg` if it has a
+/// path.
+fn runner_program(runner: &Runner, pkg: &Package) -> PathBuf {
+    if runner.program.contains('/') ||
+       (cfg!(windows) && runner.program.contains('\\')) {
+        pkg.root().join(&runner.program)
+    } else {
+        PathBuf::from(&runner.program)
+    }
+}
+
 fn pre_version_component(v: &Version) -> String {
     if v.pre.is_empty() {
         return String::new();
@@ -7,11 +7,12 @@ use std::sync::Arc;
 
 use core::{Package, PackageId, PackageSet, Resolve, Target, Profile};
 use core::{TargetKind, Profiles, Metadata, Dependency, Workspace};
-use core::dependency::Kind as DepKind;
+use core::dependency::{Kind as DepKind, Platform};
 use util::{CargoResult, ChainError, internal, Config, profile, Cfg, human};
+use util::Filesystem;
 
-use super::TargetConfig;
-use super::custom_build::{BuildState, BuildScripts};
+use super::{TargetConfig, OutputTransform};
+use super::custom_build::{BuildState, BuildScripts, BuildDeps};
 use super::fingerprint::Fingerprint;
 use super::layout::{Layout, LayoutProxy};
 use super::links::Links;
@@ -33,7 +34,7 @@ pub struct Context<'a, 'cfg: 'a> {
     pub compilation: Compilation<'cfg>,
     pub packages: &'a PackageSet<'cfg>,
     pub build_state: Arc<BuildState>,
-    pub build_explicit_deps: HashMap<Unit<'a>, (PathBuf, Vec<String>)>,
+    pub build_explicit_deps: HashMap<Unit<'a>, BuildDeps>,
     pub exec_engine: Arc<Box<ExecEngine>>,
     pub fingerprints: HashMap<Unit<'a>, Arc<Fingerprint>>,
     pub compiled: HashSet<Unit<'a>>,
@@ -41,9 +42,15 @@ pub struct Context<'a, 'cfg: 'a> {
     pub build_scripts: HashMap<Unit<'a>, Arc<BuildScripts>>,
     pub links: Links<'a>,
     pub used_in_plugin: HashSet<Unit<'a>>,
+    pub self_profiled: HashSet<Unit<'a>>,
 
     host: Layout,
     target: Option<Layout>,
+    // The host and target layouts of the directories configured in
+    // `build.package-target-dir`, and for each package placed in one of
+    // them, which one.
+    relocated: Vec<(Layout, Option<Layout>)>,
+    relocated_packages: HashMap<String, usize>,
     target_info: TargetInfo,
     host_info: TargetInfo,
     profiles: &'a Profiles,
@@ -53,6 +60,7 @@ pub struct Context<'a, 'cfg: 'a> {
 struct TargetInfo {
     crate_types: HashMap<String, Option<(String, String)>>,
     cfg: Option<Vec<Cfg>>,
+    sysroot: Option<PathBuf>,
 }
 
 impl<'a, 'cfg> Context<'a, 'cfg> {
@@ -72,6 +80,41 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
             None => None,
         };
 
+        // Packages sharing a directory share its layouts, which are locked
+        // while they're in use.
+        let mut relocated = Vec::new();
+        let mut relocated_dirs = Vec::new();
+        let mut relocated_packages = HashMap::new();
+        let mut names = build_config.package_target_dirs.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let dir = &build_config.package_target_dirs[name];
+            if *dir == ws.target_dir().into_path_unlocked() {
+                continue
+            }
+            let index = match relocated_dirs.iter().position(|d| d == dir) {
+                Some(index) => index,
+                None => {
+                    let target_dir = Filesystem::new(dir.clone());
+                    let host = try!(Layout::in_target_dir(config,
+                                                          target_dir.clone(),
+                                                          None, &dest));
+                    let target = match build_config.requested_target.as_ref() {
+                        Some(target) => {
+                            Some(try!(Layout::in_target_dir(config, target_dir,
+                                                            Some(&target),
+                                                            &dest)))
+                        }
+                        None => None,
+                    };
+                    relocated.push((host, target));
+                    relocated_dirs.push(dir.clone());
+                    relocated.len() - 1
+                }
+            };
+            relocated_packages.insert(name.clone(), index);
+        }
+
         let engine = build_config.exec_engine.as_ref().cloned().unwrap_or({
             Arc::new(Box::new(ProcessEngine))
         });
@@ -79,6 +122,8 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         Ok(Context {
             host: host_layout,
             target: target_layout,
+            relocated: relocated,
+            relocated_packages: relocated_packages,
             resolve: resolve,
             current_package: current_package,
             packages: packages,
@@ -94,8 +139,9 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
             compiled: HashSet::new(),
             build_scripts: HashMap::new(),
             build_explicit_deps: HashMap::new(),
-            links: Links::new(),
+            links: try!(Links::new(config)),
             used_in_plugin: HashSet::new(),
+            self_profiled: HashSet::new(),
         })
     }
 
@@ -115,10 +161,21 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
             }
             None => {}
         }
+        for &mut (ref mut host, ref mut target) in self.relocated.iter_mut() {
+            try!(host.prepare().chain_error(|| {
+                internal(format!("couldn't prepare build directories"))
+            }));
+            if let Some(ref mut target) = *target {
+                try!(target.prepare().chain_error(|| {
+                    internal(format!("couldn't prepare build directories"))
+                }));
+            }
+        }
 
         let layout = self.target.as_ref().unwrap_or(&self.host);
         self.compilation.root_output = layout.dest().to_path_buf();
         self.compilation.deps_output = layout.deps().to_path_buf();
+        self.compilation.host_deps_output = self.host.deps().to_path_buf();
         Ok(())
     }
 
@@ -185,7 +242,7 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         }
 
         let mut with_cfg = process.clone();
-        with_cfg.arg("--print=cfg");
+        with_cfg.arg("--print=sysroot").arg("--print=cfg");
 
         let mut has_cfg = true;
         let output = try!(with_cfg.exec_with_output().or_else(|_| {
@@ -225,10 +282,15 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
                        Some((prefix.to_string(), suffix.to_string())));
         }
 
-        let cfg = if has_cfg {
-            Some(try!(lines.map(Cfg::from_str).collect()))
+        let (sysroot, cfg) = if has_cfg {
+            let sysroot = match lines.next() {
+                Some(line) => PathBuf::from(line.trim()),
+                None => bail!("output of --print=sysroot missing when learning \
+                               about target-specific information from rustc"),
+            };
+            (Some(sysroot), Some(try!(lines.map(Cfg::from_str).collect())))
         } else {
-            None
+            (None, None)
         };
 
         let info = match kind {
@@ -237,6 +299,7 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         };
         info.crate_types = map;
         info.cfg = cfg;
+        info.sysroot = sysroot;
         Ok(())
     }
 
@@ -277,13 +340,50 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
 
     /// Returns the appropriate directory layout for either a plugin or not.
     pub fn layout(&self, unit: &Unit) -> LayoutProxy {
-        let primary = unit.pkg.package_id() == &self.current_package;
-        match unit.kind {
-            Kind::Host => LayoutProxy::new(&self.host, primary),
-            Kind::Target => LayoutProxy::new(self.target.as_ref()
-                                                 .unwrap_or(&self.host),
-                                             primary),
+        let primary = self.is_primary(unit.pkg.package_id());
+        let dest = match unit.kind {
+            Kind::Host => &self.host,
+            Kind::Target => self.target.as_ref().unwrap_or(&self.host),
+        };
+        match self.relocated_packages.get(unit.pkg.name()) {
+            Some(&index) => {
+                let (ref host, ref target) = self.relocated[index];
+                let root = match unit.kind {
+                    Kind::Host => host,
+                    Kind::Target => target.as_ref().unwrap_or(host),
+                };
+                LayoutProxy::relocated(root, dest, primary)
+            }
+            None => LayoutProxy::new(dest, primary),
+        }
+    }
+
+    /// Returns the `deps` directories of every layout units of the given kind
+    /// may be built in.
+    pub fn deps_dirs(&self, kind: Kind) -> Vec<&Path> {
+        let mut dirs = vec![match kind {
+            Kind::Host => self.host.deps(),
+            Kind::Target => self.target.as_ref().unwrap_or(&self.host).deps(),
+        }];
+        dirs.extend(self.relocated.iter().map(|&(ref host, ref target)| {
+            match kind {
+                Kind::Host => host.deps(),
+                Kind::Target => target.as_ref().unwrap_or(host).deps(),
+            }
+        }));
+        dirs
+    }
+
+    /// All layouts output is placed in, which includes the target's if it's
+    /// different from the host's, and those of `build.package-target-dir`.
+    pub fn layouts(&self) -> Vec<&Layout> {
+        let mut layouts = vec![&self.host];
+        layouts.extend(self.target.as_ref());
+        for &(ref host, ref target) in self.relocated.iter() {
+            layouts.push(host);
+            layouts.extend(target.as_ref());
         }
+        layouts
     }
 
     /// Returns the path for plugin/dylib dependencies
@@ -318,6 +418,27 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
 
     /// Get the metadata for a target in a specific profile
     pub fn target_metadata(&self, unit: &Unit) -> Option<Metadata> {
+        let mut metadata = self.profile_metadata(unit);
+        // Extra flags from `cargo rustc -p` only apply to this one build of
+        // the dependency, so its artifacts are kept apart from the ones
+        // everything else links against and are reused afterwards.
+        if self.separates_rustc_args(unit) {
+            if let Some(ref mut metadata) = metadata {
+                metadata.mix(unit.profile.rustc_args.as_ref().unwrap());
+            }
+        }
+        metadata
+    }
+
+    /// Whether the extra flags `cargo rustc` passes for `unit` are part of
+    /// the names of its artifacts. This is only the case for dependencies
+    /// from outside of the workspace, path packages are rebuilt in place.
+    pub fn separates_rustc_args(&self, unit: &Unit) -> bool {
+        unit.profile.rustc_args.is_some() &&
+            !unit.pkg.package_id().source_id().is_path()
+    }
+
+    fn profile_metadata(&self, unit: &Unit) -> Option<Metadata> {
         let metadata = unit.target.metadata();
         if unit.target.is_lib() && unit.profile.test {
             // Libs and their tests are built in parallel, so we need to make
@@ -376,6 +497,36 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         }
     }
 
+    /// The `cfg` values rustc reported for the platform of `kind`, if it
+    /// supports printing them.
+    pub fn cfg(&self, kind: Kind) -> Option<&[Cfg]> {
+        self.info(kind).cfg.as_ref().map(|cfg| &cfg[..])
+    }
+
+    /// The sysroot of the compiler used for `kind`.
+    pub fn sysroot(&self, kind: Kind) -> Option<&Path> {
+        self.info(kind).sysroot.as_ref().map(|p| &**p)
+    }
+
+    /// The directory in the sysroot holding the standard library for the
+    /// platform of `kind`.
+    pub fn sysroot_target_libdir(&self, kind: Kind) -> Option<PathBuf> {
+        let triple = match kind {
+            Kind::Host => self.host_triple(),
+            Kind::Target => self.target_triple(),
+        };
+        self.sysroot(kind).map(|sysroot| {
+            sysroot.join("lib").join("rustlib").join(triple).join("lib")
+        })
+    }
+
+    fn info(&self, kind: Kind) -> &TargetInfo {
+        match kind {
+            Kind::Host => &self.host_info,
+            Kind::Target => &self.target_info,
+        }
+    }
+
     /// Return the filenames that the given target for the given profile will
     /// generate, along with whether you can link against that file (e.g. it's a
     /// library).
@@ -441,6 +592,20 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         Ok(ret)
     }
 
+    /// Return the transforms configured for the platform of the given unit
+    /// which apply to it, along with the filename of the output each one
+    /// produces.
+    pub fn output_transforms(&self, unit: &Unit)
+                             -> Vec<(&OutputTransform, String)> {
+        if unit.profile.doc || unit.target.is_custom_build() {
+            return Vec::new()
+        }
+        let stem = self.file_stem(unit);
+        self.target_config(unit.kind).transforms.iter().filter(|t| {
+            t.applies_to(unit.target)
+        }).map(|t| (t, format!("{}{}", stem, t.suffix))).collect()
+    }
+
     /// For a package, return all targets which are registered as dependencies
     /// for that package.
     pub fn dep_targets(&self, unit: &Unit<'a>) -> CargoResult<Vec<Unit<'a>>> {
@@ -565,9 +730,9 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
             ..*unit
         };
         let deps = try!(self.dep_targets(&tmp));
-        Ok(deps.iter().filter_map(|unit| {
-            if !unit.target.linkable() || unit.pkg.manifest().links().is_none() {
-                return None
+        Ok(deps.iter().flat_map(|unit| {
+            if !unit.target.linkable() || unit.pkg.manifest().links().is_empty() {
+                return Vec::new()
             }
             self.dep_build_script(unit)
         }).chain(Some(Unit {
@@ -629,22 +794,22 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         Ok(ret)
     }
 
-    /// If a build script is scheduled to be run for the package specified by
-    /// `unit`, this function will return the unit to run that build script.
+    /// Returns the units to run each of the build scripts of the package
+    /// specified by `unit`.
     ///
     /// Overriding a build script simply means that the running of the build
     /// script itself doesn't have any dependencies, so even in that case a unit
-    /// of work is still returned. `None` is only returned if the package has no
-    /// build script.
-    fn dep_build_script(&self, unit: &Unit<'a>) -> Option<Unit<'a>> {
-        unit.pkg.targets().iter().find(|t| t.is_custom_build()).map(|t| {
+    /// of work is still returned. The list is only empty if the package has
+    /// no build scripts.
+    fn dep_build_script(&self, unit: &Unit<'a>) -> Vec<Unit<'a>> {
+        self.active_build_scripts(unit.pkg, unit.kind).into_iter().map(|t| {
             Unit {
                 pkg: unit.pkg,
                 target: t,
                 profile: &self.profiles.custom_build,
                 kind: unit.kind,
             }
-        })
+        }).collect()
     }
 
     fn maybe_lib(&self, unit: &Unit<'a>) -> Option<Unit<'a>> {
@@ -658,10 +823,24 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         })
     }
 
+    /// Returns the build scripts of `pkg` which apply when building for
+    /// `kind`, skipping those declared for other platforms.
+    pub fn active_build_scripts(&self, pkg: &'a Package, kind: Kind)
+                                -> Vec<&'a Target> {
+        pkg.targets().iter().filter(|t| {
+            t.is_custom_build() && self.platform_activated(t.platform(), kind)
+        }).collect()
+    }
+
     fn dep_platform_activated(&self, dep: &Dependency, kind: Kind) -> bool {
-        // If this dependency is only available for certain platforms,
-        // make sure we're only enabling it for that platform.
-        let platform = match dep.platform() {
+        self.platform_activated(dep.platform(), kind)
+    }
+
+    fn platform_activated(&self, platform: Option<&Platform>, kind: Kind)
+                          -> bool {
+        // If this dependency or build script is only available for certain
+        // platforms, make sure we're only enabling it for that platform.
+        let platform = match platform {
             Some(p) => p,
             None => return true,
         };
@@ -688,7 +867,7 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
     }
 
     /// Get the target configuration for a particular host or target
-    fn target_config(&self, kind: Kind) -> &TargetConfig {
+    pub fn target_config(&self, kind: Kind) -> &TargetConfig {
         match kind {
             Kind::Host => &self.build_config.host,
             Kind::Target => &self.build_config.target,
@@ -721,14 +900,31 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         env_args(self.config, &self.build_config, unit.kind, "RUSTFLAGS")
     }
 
+    /// Extra flags for rustc to profile the compilation of `unit`, if it was
+    /// selected for that.
+    pub fn self_profile_args(&self, unit: &Unit) -> Vec<String> {
+        if self.self_profiled.contains(unit) {
+            vec!["-Z".to_string(), "time-passes".to_string()]
+        } else {
+            Vec::new()
+        }
+    }
+
     pub fn rustdocflags_args(&self, unit: &Unit) -> CargoResult<Vec<String>> {
         env_args(self.config, &self.build_config, unit.kind, "RUSTDOCFLAGS")
     }
 
     pub fn show_warnings(&self, pkg: &PackageId) -> bool {
-        pkg == &self.current_package || pkg.source_id().is_path() ||
+        self.is_primary(pkg) || pkg.source_id().is_path() ||
             self.config.extra_verbose()
     }
+
+    /// Whether `pkg` is the current package, or one built alongside it as if
+    /// it were.
+    pub fn is_primary(&self, pkg: &PackageId) -> bool {
+        pkg == &self.current_package ||
+            self.build_config.primary_packages.contains(pkg)
+    }
 }
 
 // Acquire extra flags to pass to the compiler from the
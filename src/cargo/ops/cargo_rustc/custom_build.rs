@@ -1,23 +1,55 @@
-use std::collections::{HashMap, BTreeSet, HashSet};
+use std::collections::{HashMap, BTreeMap, BTreeSet, HashSet};
 use std::fs;
 use std::path::{PathBuf, Path};
 use std::str;
 use std::sync::{Mutex, Arc};
 use std::process::{Stdio, Output};
 
+use rustc_serialize::json;
+
 use core::PackageId;
+use core::shell::{Subsystem, Verbosity};
 use util::{CargoResult, Human};
 use util::{internal, ChainError, profile, paths};
-use util::{Freshness, ProcessBuilder, read2};
+use util::{Freshness, ProcessBuilder, read2, machine_message};
 use util::errors::{process_error, ProcessError};
 
 use super::job::Work;
 use super::job_queue::JobState;
+use super::sandbox;
+use super::script_protocol::{self, Diagnostic, DiagnosticLevel};
+use super::script_protocol::{BuildScriptMessage, BuildScriptProgress};
+use super::script_protocol::ScriptMessage;
+use super::script_cache::ScriptCache;
 use super::{fingerprint, Kind, Context, Unit};
-use super::CommandType;
+use super::{CommandPrototype, CommandType};
+
+/// The contents of the `target-info.json` file passed to build scripts.
+///
+/// Fields may be added over time, but existing ones won't change their
+/// meaning without bumping `version`.
+#[derive(RustcEncodable)]
+struct TargetInfoFile<'a> {
+    version: u32,
+    target: &'a str,
+    host: &'a str,
+    cfg: Vec<String>,
+    sysroot: Option<String>,
+    sysroot_target_libdir: Option<String>,
+    features: Vec<&'a str>,
+    profile: ProfileInfo<'a>,
+}
+
+#[derive(RustcEncodable)]
+struct ProfileInfo<'a> {
+    name: &'a str,
+    opt_level: &'a str,
+    debuginfo: bool,
+    debug_assertions: bool,
+}
 
 /// Contains the parsed output of a custom build script.
-#[derive(Clone, Debug, Hash)]
+#[derive(Clone, Debug, Hash, Default)]
 pub struct BuildOutput {
     /// Paths to pass to rustc with the `-L` flag
     pub library_paths: Vec<PathBuf>,
@@ -31,13 +63,40 @@ pub struct BuildOutput {
     pub rerun_if_changed: Vec<String>,
     /// Warnings generated by this build,
     pub warnings: Vec<String>,
+    /// Files (relative to `OUT_DIR`) that make up the complete output of this
+    /// build script.
+    pub declared_outputs: Vec<PathBuf>,
+    /// Files which are the complete set of inputs read by this build script.
+    pub tracked_inputs: Vec<String>,
+    /// Structured diagnostics reported through protocol version 2.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Information about a previous run of a build script that's used to determine
+/// whether it needs to be run again.
+pub struct BuildDeps {
+    /// Location of the file the script's stdout was saved to.
+    pub build_script_output: PathBuf,
+    /// The script's `OUT_DIR`.
+    pub out_dir: PathBuf,
+    /// Paths which, when modified, cause the script to be rerun.
+    pub rerun_if_changed: Vec<String>,
+    /// Files that the previous run declared it produced in `OUT_DIR`.
+    pub declared_outputs: Vec<PathBuf>,
 }
 
 pub type BuildMap = HashMap<(PackageId, Kind), BuildOutput>;
 
 pub struct BuildState {
+    /// The combined output of all build scripts of each package.
     pub outputs: Mutex<BuildMap>,
+    /// The output of each individual build script, keyed by the name of its
+    /// target.
+    pub scripts: Mutex<HashMap<(PackageId, Kind), BTreeMap<String, BuildOutput>>>,
     overrides: HashMap<(String, Kind), BuildOutput>,
+    /// Link directives found for `[system-dependencies]`, which are merged
+    /// into the output of a package's build script.
+    system: Mutex<BuildMap>,
 }
 
 #[derive(Default)]
@@ -85,12 +144,50 @@ pub fn prepare<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     Ok((work_dirty.then(dirty), work_fresh.then(fresh), freshness))
 }
 
-fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
-                        -> CargoResult<(Work, Work)> {
+/// Renders the `target-info.json` file for the build script `unit`, which is
+/// run for `target_triple`.
+fn target_info(cx: &Context, unit: &Unit, target_triple: &str)
+               -> CargoResult<String> {
+    let profile = cx.lib_profile(unit.pkg.package_id());
+    let display = |p: &Path| p.display().to_string();
+    let mut features = match cx.resolve.features(unit.pkg.package_id()) {
+        Some(features) => features.iter().map(|s| &s[..]).collect(),
+        None => Vec::new(),
+    };
+    features.sort();
+    let info = TargetInfoFile {
+        version: 1,
+        target: target_triple,
+        host: cx.host_triple(),
+        cfg: cx.cfg(unit.kind).unwrap_or(&[]).iter().map(|c| {
+            c.to_string()
+        }).collect(),
+        sysroot: cx.sysroot(unit.kind).map(&display),
+        sysroot_target_libdir: cx.sysroot_target_libdir(unit.kind).map(|p| {
+            display(&p)
+        }),
+        features: features,
+        profile: ProfileInfo {
+            name: if cx.build_config.release {"release"} else {"debug"},
+            opt_level: &profile.opt_level,
+            debuginfo: profile.debuginfo,
+            debug_assertions: profile.debug_assertions,
+        },
+    };
+    json::encode(&info).chain_error(|| {
+        internal("failed to encode target info for build script")
+    })
+}
+
+/// Prepares the command running the build script `unit`, with all of the
+/// environment variables known before its dependencies have been built, and
+/// returns it along with the contents of its `target-info.json` file.
+pub fn script_process(cx: &Context, unit: &Unit)
+                      -> CargoResult<(CommandPrototype, String)> {
     let host_unit = Unit { kind: Kind::Host, ..*unit };
     let (script_output, build_output) = {
         (cx.layout(&host_unit).build(unit.pkg),
-         cx.layout(unit).build_out(unit.pkg))
+         cx.layout(unit).build_script_out(unit.pkg, unit.target))
     };
 
     // Building the command to execute
@@ -103,23 +200,29 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     let profile = cx.lib_profile(unit.pkg.package_id());
     let to_exec = to_exec.into_os_string();
     let mut p = try!(super::process(CommandType::Host(to_exec), unit.pkg, cx));
+    let target_triple = script_target(cx, unit);
     p.env("OUT_DIR", &build_output)
      .env("CARGO_MANIFEST_DIR", unit.pkg.root())
      .env("NUM_JOBS", &cx.jobs().to_string())
-     .env("TARGET", &match unit.kind {
-         Kind::Host => cx.host_triple(),
-         Kind::Target => cx.target_triple(),
-     })
+     .env("TARGET", &target_triple)
      .env("DEBUG", &profile.debuginfo.to_string())
      .env("OPT_LEVEL", &profile.opt_level)
      .env("PROFILE", if cx.build_config.release {"release"} else {"debug"})
      .env("HOST", cx.host_triple())
+     .env("CARGO_BUILD_SCRIPT_PROTOCOL",
+          &script_protocol::PROTOCOL_VERSION.to_string())
      .env("RUSTC", &try!(cx.config.rustc()).path)
      .env("RUSTDOC", &*try!(cx.config.rustdoc()));
 
-     if let Some(links) = unit.pkg.manifest().links(){
-        p.env("CARGO_MANIFEST_LINKS", links);
-     }
+    if !unit.pkg.manifest().links().is_empty() {
+        p.env("CARGO_MANIFEST_LINKS", unit.pkg.manifest().links().join(","));
+    }
+
+    // Everything we know about the platform being built for is also written
+    // to a file in `OUT_DIR`, which is simpler for scripts to consume than
+    // all of the individual environment variables.
+    let target_info = try!(target_info(cx, unit, &target_triple));
+    p.env("CARGO_TARGET_INFO", &build_output.join("target-info.json"));
 
     // Be sure to pass along all enabled features for this package, this is the
     // last piece of statically known information that we have.
@@ -128,6 +231,23 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
             p.env(&format!("CARGO_FEATURE_{}", super::envify(feat)), "1");
         }
     }
+    Ok((p, target_info))
+}
+
+fn script_target(cx: &Context, unit: &Unit) -> String {
+    match unit.kind {
+        Kind::Host => cx.host_triple(),
+        Kind::Target => cx.target_triple(),
+    }.to_string()
+}
+
+fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
+                        -> CargoResult<(Work, Work)> {
+    let host_unit = Unit { kind: Kind::Host, ..*unit };
+    let build_output = cx.layout(unit).build_script_out(unit.pkg, unit.target);
+    let target_triple = script_target(cx, unit);
+    let target_info_file = build_output.join("target-info.json");
+    let (mut p, target_info) = try!(script_process(cx, unit));
 
     // Gather the set of native dependencies that this package has along with
     // some other variables to close over.
@@ -135,32 +255,50 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     // This information will be used at build-time later on to figure out which
     // sorts of variables need to be discovered at that time.
     let lib_deps = {
-        try!(cx.dep_run_custom_build(unit)).iter().filter_map(|unit| {
-            if unit.profile.run_custom_build {
-                Some((unit.pkg.manifest().links().unwrap().to_string(),
-                      unit.pkg.package_id().clone()))
-            } else {
-                None
+        let mut lib_deps = Vec::new();
+        for dep in try!(cx.dep_run_custom_build(unit)) {
+            if !dep.profile.run_custom_build {
+                continue
+            }
+            let links = dep.pkg.manifest().links();
+            for lib in links.iter().filter(|l| cx.links.provides(dep.pkg, l)) {
+                lib_deps.push((lib.clone(), links.to_vec(),
+                               dep.pkg.package_id().clone()));
             }
-        }).collect::<Vec<_>>()
+        }
+        lib_deps
     };
     let pkg_name = unit.pkg.to_string();
     let build_state = cx.build_state.clone();
     let id = unit.pkg.package_id().clone();
     let output_file = build_output.parent().unwrap().join("output");
+    let script_name = unit.target.name().to_string();
     let all = (id.clone(), pkg_name.clone(), build_state.clone(),
-               output_file.clone());
+               output_file.clone(), script_name.clone());
     let build_scripts = super::load_build_deps(cx, unit);
     let kind = unit.kind;
+    let sandboxed = cx.build_config.sandbox.applies_to(unit.pkg.name());
+    let rustc_version = try!(cx.config.rustc()).verbose_version.clone();
+    let script_cache = cx.build_config.script_cache.as_ref().map(|p| {
+        ScriptCache::new(p, &rustc_version)
+    });
+    let script_exe = PathBuf::from(p.get_program());
+    let pkg_root = unit.pkg.root().to_path_buf();
+    let json_messages = cx.build_config.json_messages;
 
     // Check to see if the build script as already run, and if it has keep
     // track of whether it has told us about some explicit dependencies
     let prev_output = BuildOutput::parse_file(&output_file, &pkg_name).ok();
-    let rerun_if_changed = match prev_output {
-        Some(ref prev) => prev.rerun_if_changed.clone(),
-        None => Vec::new(),
+    let deps = match prev_output {
+        Some(ref prev) => BuildDeps::new(&output_file, &build_output, prev),
+        None => BuildDeps {
+            build_script_output: output_file.clone(),
+            out_dir: build_output.clone(),
+            rerun_if_changed: Vec::new(),
+            declared_outputs: Vec::new(),
+        },
     };
-    cx.build_explicit_deps.insert(*unit, (output_file.clone(), rerun_if_changed));
+    cx.build_explicit_deps.insert(*unit, deps);
 
     try!(fs::create_dir_all(&cx.layout(&host_unit).build(unit.pkg)));
     try!(fs::create_dir_all(&cx.layout(unit).build(unit.pkg)));
@@ -176,7 +314,7 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
         // If we have an old build directory, then just move it into place,
         // otherwise create it!
         if fs::metadata(&build_output).is_err() {
-            try!(fs::create_dir(&build_output).chain_error(|| {
+            try!(fs::create_dir_all(&build_output).chain_error(|| {
                 internal("failed to create script output directory for \
                           build command")
             }));
@@ -188,7 +326,7 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
         // native dynamic libraries.
         {
             let build_state = build_state.outputs.lock().unwrap();
-            for (name, id) in lib_deps {
+            for (name, links, id) in lib_deps {
                 let key = (id.clone(), kind);
                 let state = try!(build_state.get(&key).chain_error(|| {
                     internal(format!("failed to locate build state for env \
@@ -196,6 +334,18 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
                 }));
                 let data = &state.metadata;
                 for &(ref key, ref value) in data.iter() {
+                    // Packages linking several libraries can scope metadata
+                    // to one of them as `cargo:$lib.$key=value`, everything
+                    // else is passed along for all of them.
+                    let key = match key.find('.') {
+                        Some(i) if links.iter().any(|l| *l == key[..i]) => {
+                            if key[..i] != name[..] {
+                                continue
+                            }
+                            &key[i + 1..]
+                        }
+                        _ => &key[..],
+                    };
                     p.env(&format!("DEP_{}_{}", super::envify(&name),
                                    super::envify(key)), value);
                 }
@@ -206,10 +356,53 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
             }
         }
 
-        // And now finally, run the build command itself!
+        // If the script has previously declared all of its inputs and outputs
+        // then we may be able to restore its results from the cache instead of
+        // running it again.
+        let env = p.get_envs().iter().filter_map(|(k, v)| {
+            v.as_ref().and_then(|v| v.to_str()).map(|v| {
+                (k.clone(), v.to_string())
+            })
+        }).collect::<Vec<_>>();
+        // The target description isn't one of the script's declared outputs,
+        // so it's written out even when the rest is restored from the cache.
+        try!(paths::write(&target_info_file, target_info.as_bytes()));
+        if let Some(ref cache) = script_cache {
+            let restored = try!(cache.restore(&id, &target_triple, &script_exe,
+                                              &env, &pkg_root, &build_output));
+            if let Some(stdout) = restored {
+                try!(paths::write(&output_file, &stdout));
+                let parsed_output = try!(BuildOutput::parse(&stdout, &pkg_name));
+                build_state.insert(id, kind, &script_name, parsed_output);
+                return Ok(())
+            }
+        }
+
+        // And now finally, run the build command itself! If requested, the
+        // script is wrapped up in a sandbox that only lets it write to
+        // `OUT_DIR` and doesn't give it network access.
         state.running(&p);
-        let cmd = p.into_process_builder();
-        let output = try!(stream_output(state, &cmd).map_err(|mut e| {
+        let mut cmd = p.into_process_builder();
+        if sandboxed {
+            cmd = try!(sandbox::wrap(&cmd, &build_output));
+        }
+        let mut protocol = 1;
+        let output = try!(stream_output(state, &cmd, &mut |line| {
+            let message = match script_protocol::progress_message(line,
+                                                                  &mut protocol) {
+                Some(message) => message,
+                None => return,
+            };
+            if json_messages {
+                machine_message::emit(&BuildScriptProgress {
+                    package_id: &id,
+                    message: &message,
+                });
+            } else {
+                state.status(Subsystem::Compile, Verbosity::Verbose, "Progress",
+                             format!("{}: {}", pkg_name, message));
+            }
+        }).map_err(|mut e| {
             e.desc = format!("failed to run custom build command for `{}`\n{}",
                              pkg_name, e.desc);
             Human(e)
@@ -224,7 +417,26 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
         // state informing what variables were discovered via our script as
         // well.
         let parsed_output = try!(BuildOutput::parse(&output.stdout, &pkg_name));
-        build_state.insert(id, kind, parsed_output);
+        if json_messages {
+            // Errors abort the build before the diagnostics are forwarded
+            // along with warnings, so forward them here instead.
+            for d in parsed_output.diagnostics.iter() {
+                if d.level == DiagnosticLevel::Error {
+                    machine_message::emit(&BuildScriptMessage {
+                        package_id: &id,
+                        message: d,
+                    });
+                }
+            }
+        }
+        try!(parsed_output.check_diagnostics(&pkg_name));
+        try!(parsed_output.verify_declared_outputs(&build_output, &pkg_name));
+        if let Some(ref cache) = script_cache {
+            try!(cache.store(&id, &target_triple, &script_exe, &env,
+                             &pkg_root, &build_output, &output.stdout,
+                             &parsed_output));
+        }
+        build_state.insert(id, kind, &script_name, parsed_output);
         Ok(())
     });
 
@@ -232,12 +444,12 @@ fn build_work<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     // itself to run when we actually end up just discarding what we calculated
     // above.
     let fresh = Work::new(move |_tx| {
-        let (id, pkg_name, build_state, output_file) = all;
+        let (id, pkg_name, build_state, output_file, script_name) = all;
         let output = match prev_output {
             Some(output) => output,
             None => try!(BuildOutput::parse_file(&output_file, &pkg_name)),
         };
-        build_state.insert(id, kind, output);
+        build_state.insert(id, kind, &script_name, output);
         Ok(())
     });
 
@@ -254,28 +466,90 @@ impl BuildState {
         }
         BuildState {
             outputs: Mutex::new(HashMap::new()),
+            scripts: Mutex::new(HashMap::new()),
             overrides: overrides,
+            system: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records the output of the build script `script` of a package, and
+    /// recombines it with the output of its other build scripts.
+    fn insert(&self, id: PackageId, kind: Kind, script: &str,
+              output: BuildOutput) {
+        let key = (id, kind);
+        let mut merged = BuildOutput::default();
+        {
+            let mut scripts = self.scripts.lock().unwrap();
+            let scripts = scripts.entry(key.clone()).or_insert(BTreeMap::new());
+            scripts.insert(script.to_string(), output);
+            for output in scripts.values() {
+                merged.merge(output);
+            }
+        }
+        if let Some(system) = self.system.lock().unwrap().get(&key) {
+            merged.library_paths.extend(system.library_paths.iter().cloned());
+            merged.library_links.extend(system.library_links.iter().cloned());
         }
+        self.outputs.lock().unwrap().insert(key, merged);
     }
 
-    fn insert(&self, id: PackageId, kind: Kind, output: BuildOutput) {
-        self.outputs.lock().unwrap().insert((id, kind), output);
+    /// Records the link directives for the system dependencies of a package.
+    ///
+    /// Packages without a build script for this platform have nothing
+    /// further to wait for, so these become their output directly.
+    pub fn insert_system(&self, id: &PackageId, kind: Kind, has_scripts: bool,
+                         output: BuildOutput) {
+        let key = (id.clone(), kind);
+        if !has_scripts {
+            self.outputs.lock().unwrap().insert(key.clone(), output.clone());
+        }
+        self.system.lock().unwrap().insert(key, output);
     }
 
+    /// Checks whether the build script of `unit` is overridden, which is
+    /// only the case when every library it links to has an override. The
+    /// overrides are then merged together into the script's output.
     fn has_override(&self, unit: &Unit) -> bool {
-        let key = unit.pkg.manifest().links().map(|l| (l.to_string(), unit.kind));
-        match key.and_then(|k| self.overrides.get(&k)) {
-            Some(output) => {
-                self.insert(unit.pkg.package_id().clone(), unit.kind,
-                            output.clone());
-                true
+        let links = unit.pkg.manifest().links();
+        if links.is_empty() {
+            return false
+        }
+        let mut merged = BuildOutput::default();
+        for lib in links {
+            let output = match self.overrides.get(&(lib.to_string(), unit.kind)) {
+                Some(output) => output,
+                None => return false,
+            };
+            merged.library_paths.extend(output.library_paths.iter().cloned());
+            merged.library_links.extend(output.library_links.iter().cloned());
+            merged.cfgs.extend(output.cfgs.iter().cloned());
+            if links.len() == 1 {
+                merged.metadata.extend(output.metadata.iter().cloned());
+            } else {
+                merged.metadata.extend(output.metadata.iter().map(|&(ref k, ref v)| {
+                    (format!("{}.{}", lib, k), v.clone())
+                }));
             }
-            None => false,
         }
+        self.insert(unit.pkg.package_id().clone(), unit.kind, "", merged);
+        true
     }
 }
 
 impl BuildOutput {
+    /// Adds everything `other` printed to this output.
+    pub fn merge(&mut self, other: &BuildOutput) {
+        self.library_paths.extend(other.library_paths.iter().cloned());
+        self.library_links.extend(other.library_links.iter().cloned());
+        self.cfgs.extend(other.cfgs.iter().cloned());
+        self.metadata.extend(other.metadata.iter().cloned());
+        self.rerun_if_changed.extend(other.rerun_if_changed.iter().cloned());
+        self.warnings.extend(other.warnings.iter().cloned());
+        self.declared_outputs.extend(other.declared_outputs.iter().cloned());
+        self.tracked_inputs.extend(other.tracked_inputs.iter().cloned());
+        self.diagnostics.extend(other.diagnostics.iter().cloned());
+    }
+
     pub fn parse_file(path: &Path, pkg_name: &str) -> CargoResult<BuildOutput> {
         let contents = try!(paths::read_bytes(path));
         BuildOutput::parse(&contents, pkg_name)
@@ -290,6 +564,10 @@ impl BuildOutput {
         let mut metadata = Vec::new();
         let mut rerun_if_changed = Vec::new();
         let mut warnings = Vec::new();
+        let mut declared_outputs = Vec::new();
+        let mut tracked_inputs = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut protocol = 1;
         let whence = format!("build script of `{}`", pkg_name);
 
         for line in input.split(|b| *b == b'\n') {
@@ -297,27 +575,48 @@ impl BuildOutput {
                 Ok(line) => line.trim(),
                 Err(..) => continue,
             };
-            let mut iter = line.splitn(2, ':');
-            if iter.next() != Some("cargo") {
-                // skip this line since it doesn't start with "cargo:"
-                continue;
-            }
-            let data = match iter.next() {
-                Some(val) => val,
-                None => continue
-            };
-
-            // getting the `key=value` part of the line
-            let mut iter = data.splitn(2, '=');
-            let key = iter.next();
-            let value = iter.next();
-            let (key, value) = match (key, value) {
-                (Some(a), Some(b)) => (a, b.trim_right()),
-                // line started with `cargo:` but didn't match `key=value`
-                _ => bail!("Wrong output in {}: `{}`", whence, line),
+            let (key, value) = if script_protocol::is_message(line, protocol) {
+                match try!(script_protocol::parse_message(line, &whence)) {
+                    ScriptMessage::Diagnostic(d) => {
+                        diagnostics.push(d);
+                        continue
+                    }
+                    ScriptMessage::Progress(..) => continue,
+                    ScriptMessage::Metadata(key, value) => {
+                        metadata.push((key, value));
+                        continue
+                    }
+                    ScriptMessage::Directive(key, value) => (key, value),
+                }
+            } else {
+                let mut iter = line.splitn(2, ':');
+                if iter.next() != Some("cargo") {
+                    // skip this line since it doesn't start with "cargo:"
+                    continue;
+                }
+                let data = match iter.next() {
+                    Some(val) => val,
+                    None => continue
+                };
+                // Directives may also be written as `cargo::key=value`
+                let data = if data.starts_with(':') {&data[1..]} else {data};
+
+                // getting the `key=value` part of the line
+                let mut iter = data.splitn(2, '=');
+                let key = iter.next();
+                let value = iter.next();
+                match (key, value) {
+                    (Some(a), Some(b)) => (a.to_string(), b.trim_right().to_string()),
+                    // line started with `cargo:` but didn't match `key=value`
+                    _ => bail!("Wrong output in {}: `{}`", whence, line),
+                }
             };
+            let value = &value[..];
 
-            match key {
+            match &key[..] {
+                "protocol" => {
+                    protocol = try!(script_protocol::parse_version(value, &whence));
+                }
                 "rustc-flags" => {
                     let (libs, links) = try!(
                         BuildOutput::parse_rustc_flags(value, &whence)
@@ -330,6 +629,16 @@ impl BuildOutput {
                 "rustc-cfg" => cfgs.push(value.to_string()),
                 "warning" => warnings.push(value.to_string()),
                 "rerun-if-changed" => rerun_if_changed.push(value.to_string()),
+                "declares-output" => {
+                    let path = PathBuf::from(value);
+                    if path.is_absolute() ||
+                       path.components().any(|c| c.as_os_str() == "..") {
+                        bail!("declared output must be a path relative to \
+                               OUT_DIR in {}: `{}`", whence, value)
+                    }
+                    declared_outputs.push(path);
+                }
+                "tracked-input" => tracked_inputs.push(value.to_string()),
                 _ => metadata.push((key.to_string(), value.to_string())),
             }
         }
@@ -341,9 +650,42 @@ impl BuildOutput {
             metadata: metadata,
             rerun_if_changed: rerun_if_changed,
             warnings: warnings,
+            declared_outputs: declared_outputs,
+            tracked_inputs: tracked_inputs,
+            diagnostics: diagnostics,
         })
     }
 
+    /// Returns whether this script told us about all of its inputs and outputs
+    /// ahead of time, meaning its results can be cached.
+    pub fn is_cacheable(&self) -> bool {
+        !self.declared_outputs.is_empty() && !self.tracked_inputs.is_empty()
+    }
+
+    /// Fails the build if the script reported any error diagnostics.
+    pub fn check_diagnostics(&self, pkg_name: &str) -> CargoResult<()> {
+        let errors = self.diagnostics.iter().filter(|d| {
+            d.level == DiagnosticLevel::Error
+        }).map(|d| d.to_string()).collect::<Vec<_>>();
+        if !errors.is_empty() {
+            bail!("build script of `{}` reported errors:\n\n{}", pkg_name,
+                  errors.join("\n\n"))
+        }
+        Ok(())
+    }
+
+    /// Checks that every output the script declared was actually produced.
+    pub fn verify_declared_outputs(&self, out_dir: &Path, pkg_name: &str)
+                                   -> CargoResult<()> {
+        for file in self.declared_outputs.iter() {
+            if fs::metadata(out_dir.join(file)).is_err() {
+                bail!("build script of `{}` declared output `{}` but did \
+                       not create it in OUT_DIR", pkg_name, file.display())
+            }
+        }
+        Ok(())
+    }
+
     pub fn parse_rustc_flags(value: &str, whence: &str)
                              -> CargoResult<(Vec<PathBuf>, Vec<String>)> {
         let value = value.trim();
@@ -376,6 +718,21 @@ impl BuildOutput {
     }
 }
 
+impl BuildDeps {
+    pub fn new(output_file: &Path, out_dir: &Path, output: &BuildOutput)
+               -> BuildDeps {
+        BuildDeps {
+            build_script_output: output_file.to_path_buf(),
+            out_dir: out_dir.to_path_buf(),
+            rerun_if_changed: output.rerun_if_changed.iter()
+                                    .chain(output.tracked_inputs.iter())
+                                    .cloned()
+                                    .collect(),
+            declared_outputs: output.declared_outputs.clone(),
+        }
+    }
+}
+
 /// Compute the `build_scripts` map in the `Context` which tracks what build
 /// scripts each package depends on.
 ///
@@ -411,7 +768,9 @@ pub fn build_map<'b, 'cfg>(cx: &mut Context<'b, 'cfg>,
 
         let mut ret = BuildScripts::default();
 
-        if !unit.target.is_custom_build() && unit.pkg.has_custom_build() {
+        if !unit.target.is_custom_build() &&
+           (!cx.active_build_scripts(unit.pkg, unit.kind).is_empty() ||
+            unit.pkg.has_system_dependencies()) {
             add_to_link(&mut ret, unit.pkg.package_id(), unit.kind);
         }
         for unit in try!(cx.dep_targets(unit)).iter() {
@@ -444,7 +803,11 @@ pub fn build_map<'b, 'cfg>(cx: &mut Context<'b, 'cfg>,
     }
 }
 
-fn stream_output(state: &JobState, cmd: &ProcessBuilder)
+/// Runs `cmd`, forwarding its output to `state` and passing each complete line
+/// of its stdout to `on_line` as it arrives.
+fn stream_output(state: &JobState,
+                 cmd: &ProcessBuilder,
+                 on_line: &mut FnMut(&str))
                  -> Result<Output, ProcessError> {
     let mut stdout = Vec::new();
     let mut stderr = Vec::new();
@@ -473,6 +836,9 @@ fn stream_output(state: &JobState, cmd: &ProcessBuilder)
             dst.extend(data);
             let s = String::from_utf8_lossy(&dst[start..]);
             if is_out {
+                for line in s.lines() {
+                    on_line(line);
+                }
                 state.stdout(&s);
             } else {
                 state.stderr(&s);
@@ -76,6 +76,7 @@ impl CommandPrototype {
         self
     }
 
+    pub fn get_program(&self) -> &OsString { self.builder.get_program() }
     pub fn get_args(&self) -> &[OsString] { self.builder.get_args() }
     pub fn get_cwd(&self) -> Option<&Path> { self.builder.get_cwd() }
 
@@ -9,8 +9,10 @@ use filetime::FileTime;
 use rustc_serialize::{json, Encodable, Decodable, Encoder, Decoder};
 
 use core::{Package, TargetKind};
+use core::shell::{Subsystem, Verbosity};
 use util;
-use util::{CargoResult, Fresh, Dirty, Freshness, internal, profile, ChainError};
+use util::{CargoResult, Config, Fresh, Dirty, Freshness, internal, profile, ChainError};
+use util::human;
 use util::paths;
 
 use super::job::Work;
@@ -49,13 +51,17 @@ pub fn prepare_target<'a, 'cfg>(cx: &mut Context<'a, 'cfg>,
     let _p = profile::start(format!("fingerprint: {} / {}",
                                     unit.pkg.package_id(), unit.target.name()));
     let new = dir(cx, unit);
-    let loc = new.join(&filename(unit));
+    let loc = new.join(&filename(cx, unit));
 
     debug!("fingerprint at: {}", loc.display());
 
     let fingerprint = try!(calculate(cx, unit));
     let compare = compare_old_fingerprint(&loc, &*fingerprint);
     log_compare(unit, &compare);
+    // A unit which was never built has no previous fingerprint to explain.
+    if loc.exists() {
+        try!(explain_compare(cx.config, unit, &compare));
+    }
 
     // If our comparison failed (e.g. we're going to trigger a rebuild of this
     // crate), then we also ensure the source of the crate passes all
@@ -368,7 +374,12 @@ fn calculate<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     let extra_flags = if unit.profile.doc {
         try!(cx.rustdocflags_args(unit))
     } else {
-        try!(cx.rustflags_args(unit))
+        let mut flags = try!(cx.rustflags_args(unit));
+        flags.extend(cx.self_profile_args(unit));
+        flags.extend(cx.output_transforms(unit).into_iter().map(|(t, _)| {
+            format!("transform.{}={}{}", t.name, t.command.join(" "), t.suffix)
+        }));
+        flags
     };
     let fingerprint = Arc::new(Fingerprint {
         rustc: util::hash_u64(&try!(cx.config.rustc()).verbose_version),
@@ -416,7 +427,7 @@ pub fn prepare_build_cmd<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     let _p = profile::start(format!("fingerprint build cmd: {}",
                                     unit.pkg.package_id()));
     let new = dir(cx, unit);
-    let loc = new.join("build");
+    let loc = new.join(build_cmd_filename(unit));
 
     debug!("fingerprint at: {}", loc.display());
 
@@ -433,14 +444,17 @@ pub fn prepare_build_cmd<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
                 (LocalFingerprint::Precalculated(s), None)
             }
             None => {
-                let &(ref output, ref deps) = &cx.build_explicit_deps[unit];
+                let deps = &cx.build_explicit_deps[unit];
+                let output = &deps.build_script_output;
 
-                let local = if deps.is_empty() {
+                let local = if deps.rerun_if_changed.is_empty() {
                     let s = try!(pkg_fingerprint(cx, unit.pkg));
                     LocalFingerprint::Precalculated(s)
                 } else {
-                    let deps = deps.iter().map(|p| unit.pkg.root().join(p));
-                    let mtime = mtime_if_fresh(output, deps);
+                    let paths = deps.rerun_if_changed.iter().map(|p| {
+                        unit.pkg.root().join(p)
+                    });
+                    let mtime = mtime_if_fresh(output, paths);
                     let mtime = MtimeSlot(Mutex::new(mtime));
                     LocalFingerprint::MtimeBased(mtime, output.clone())
                 };
@@ -450,6 +464,15 @@ pub fn prepare_build_cmd<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
         }
     };
 
+    // If the previous run of the build script declared the files it produces,
+    // then it must be rerun if any of them have since gone missing.
+    let missing_outputs = match cx.build_explicit_deps.get(unit) {
+        Some(deps) => deps.declared_outputs.iter().any(|file| {
+            fs::metadata(deps.out_dir.join(file)).is_err()
+        }),
+        None => false,
+    };
+
     let mut fingerprint = Fingerprint {
         rustc: 0,
         target: 0,
@@ -470,15 +493,18 @@ pub fn prepare_build_cmd<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
     // script then prints `rerun-if-changed`, however, we need to record what's
     // necessary for that fingerprint.
     //
-    // Hence, if there were some `rerun-if-changed` directives forcibly change
-    // the kind of fingerprint over to the `MtimeBased` variant where the
-    // relevant mtime is the output path of the build script.
+    // Hence, if there were some `rerun-if-changed` (or `tracked-input`)
+    // directives forcibly change the kind of fingerprint over to the
+    // `MtimeBased` variant where the relevant mtime is the output path of the
+    // build script.
     let state = cx.build_state.clone();
     let key = (unit.pkg.package_id().clone(), unit.kind);
     let write_fingerprint = Work::new(move |_| {
         if let Some(output_path) = output_path {
             let outputs = state.outputs.lock().unwrap();
-            if !outputs[&key].rerun_if_changed.is_empty() {
+            let output = &outputs[&key];
+            if !output.rerun_if_changed.is_empty() ||
+               !output.tracked_inputs.is_empty() {
                 let slot = MtimeSlot(Mutex::new(None));
                 fingerprint.local = LocalFingerprint::MtimeBased(slot,
                                                                  output_path);
@@ -488,7 +514,8 @@ pub fn prepare_build_cmd<'a, 'cfg>(cx: &mut Context<'a, 'cfg>, unit: &Unit<'a>)
         write_fingerprint(&loc, &fingerprint)
     });
 
-    Ok((if compare.is_ok() {Fresh} else {Dirty}, write_fingerprint, Work::noop()))
+    let fresh = compare.is_ok() && !missing_outputs;
+    Ok((if fresh {Fresh} else {Dirty}, write_fingerprint, Work::noop()))
 }
 
 fn write_fingerprint(loc: &Path, fingerprint: &Fingerprint) -> CargoResult<()> {
@@ -500,6 +527,24 @@ fn write_fingerprint(loc: &Path, fingerprint: &Fingerprint) -> CargoResult<()> {
     Ok(())
 }
 
+/// Removes the fingerprint of a unit whose work was aborted, so that whatever
+/// it left behind is rebuilt by the next build.
+pub fn invalidate(cx: &Context, unit: &Unit) -> CargoResult<()> {
+    let loc = if unit.profile.run_custom_build {
+        dir(cx, unit).join(build_cmd_filename(unit))
+    } else {
+        dir(cx, unit).join(&filename(cx, unit))
+    };
+    for path in [loc.with_extension("json"), loc].iter() {
+        if fs::metadata(path).is_ok() {
+            try!(fs::remove_file(path).chain_error(|| {
+                human(format!("failed to remove fingerprint `{}`", path.display()))
+            }));
+        }
+    }
+    Ok(())
+}
+
 /// Prepare work for when a package starts to build
 pub fn prepare_init(cx: &mut Context, unit: &Unit) -> CargoResult<()> {
     let new1 = dir(cx, unit);
@@ -521,7 +566,7 @@ pub fn dir(cx: &Context, unit: &Unit) -> PathBuf {
 
 /// Returns the (old, new) location for the dep info file of a target.
 pub fn dep_info_loc(cx: &Context, unit: &Unit) -> PathBuf {
-    dir(cx, unit).join(&format!("dep-{}", filename(unit)))
+    dir(cx, unit).join(&format!("dep-{}", filename(cx, unit)))
 }
 
 fn compare_old_fingerprint(loc: &Path, new_fingerprint: &Fingerprint)
@@ -557,6 +602,29 @@ fn log_compare(unit: &Unit, compare: &CargoResult<()>) {
     }
 }
 
+/// Says why a unit is about to be rebuilt, at `-vv=fingerprint`.
+fn explain_compare(config: &Config,
+                   unit: &Unit,
+                   compare: &CargoResult<()>) -> CargoResult<()> {
+    let mut e = match *compare {
+        Ok(..) => return Ok(()),
+        Err(ref e) => &**e,
+    };
+    let mut reasons = vec![e.to_string()];
+    while let Some(cause) = e.cargo_cause() {
+        reasons.push(cause.to_string());
+        e = cause;
+    }
+    let mut e = e.cause();
+    while let Some(cause) = e {
+        reasons.push(cause.to_string());
+        e = cause.cause();
+    }
+    config.shell().status_at(Subsystem::Fingerprint, Verbosity::Trace, "Dirty",
+                             format!("{} ({}): {}", unit.pkg, unit.target.name(),
+                                     reasons.join(": ")))
+}
+
 fn dep_info_mtime_if_fresh(dep_info: &Path) -> CargoResult<Option<FileTime>> {
     macro_rules! fs_try {
         ($e:expr) => (match $e { Ok(e) => e, Err(..) => return Ok(None) })
@@ -642,7 +710,7 @@ fn mtime_if_fresh<I>(output: &Path, paths: I) -> Option<FileTime>
     }
 }
 
-fn filename(unit: &Unit) -> String {
+fn filename(cx: &Context, unit: &Unit) -> String {
     let kind = match *unit.target.kind() {
         TargetKind::Lib(..) => "lib",
         TargetKind::Bin => "bin",
@@ -658,7 +726,29 @@ fn filename(unit: &Unit) -> String {
     } else {
         ""
     };
-    format!("{}{}-{}", flavor, kind, unit.target.name())
+    // Units given extra flags by `cargo rustc` may be built alongside the
+    // same target without them, in which case they need a fingerprint of
+    // their own. Otherwise they share the artifacts, and so the fingerprint.
+    let flags = match cx.target_metadata(unit) {
+        Some(metadata) if cx.separates_rustc_args(unit) => {
+            metadata.extra_filename
+        }
+        _ => String::new(),
+    };
+    format!("{}{}-{}{}", flavor, kind, unit.target.name(), flags)
+}
+
+/// The name of the fingerprint of running a build script. Packages with
+/// several build scripts keep one for each of them.
+fn build_cmd_filename(unit: &Unit) -> String {
+    let scripts = unit.pkg.targets().iter().filter(|t| {
+        t.is_custom_build()
+    }).count();
+    if scripts > 1 {
+        format!("build-{}", unit.target.name())
+    } else {
+        "build".to_string()
+    }
 }
 
 // The dep-info files emitted by the compiler all have their listed paths
@@ -2,18 +2,23 @@ use std::collections::HashSet;
 use std::collections::hash_map::HashMap;
 use std::fmt;
 use std::io::Write;
-use std::sync::mpsc::{channel, Sender, Receiver};
+use std::sync::mpsc::{channel, Sender, Receiver, RecvTimeoutError};
+use std::time::Duration;
 
 use crossbeam::{self, Scope};
 use term::color::YELLOW;
 
 use core::{PackageId, Target, Profile};
+use core::shell::{Subsystem, Verbosity};
 use util::{Config, DependencyQueue, Fresh, Dirty, Freshness};
 use util::{CargoResult, profile, internal};
+use util::{job, machine_message};
 
 use super::{Context, Kind, Unit};
+use super::fingerprint;
 use super::job::Job;
 use super::engine::CommandPrototype;
+use super::script_protocol::BuildScriptMessage;
 
 /// A management structure of the entire dependency graph to compile.
 ///
@@ -30,6 +35,7 @@ pub struct JobQueue<'a> {
     compiled: HashSet<&'a PackageId>,
     documented: HashSet<&'a PackageId>,
     counts: HashMap<&'a PackageId, usize>,
+    total: usize,
     is_release: bool,
     is_doc_all: bool,
 }
@@ -58,6 +64,7 @@ pub struct JobState<'a> {
 
 enum Message {
     Run(String),
+    Status(Subsystem, Verbosity, &'static str, String),
     Stdout(String),
     Stderr(String),
     Finish(CargoResult<()>),
@@ -68,6 +75,17 @@ impl<'a> JobState<'a> {
         let _ = self.tx.send((self.key, Message::Run(cmd.to_string())));
     }
 
+    /// Prints a status message of `subsystem` if its verbosity is at least
+    /// `verbosity`.
+    pub fn status(&self,
+                  subsystem: Subsystem,
+                  verbosity: Verbosity,
+                  status: &'static str,
+                  message: String) {
+        let _ = self.tx.send((self.key, Message::Status(subsystem, verbosity,
+                                                        status, message)));
+    }
+
     pub fn stdout(&self, out: &str) {
         let _ = self.tx.send((self.key, Message::Stdout(out.to_string())));
     }
@@ -90,6 +108,7 @@ impl<'a> JobQueue<'a> {
             compiled: HashSet::new(),
             documented: HashSet::new(),
             counts: HashMap::new(),
+            total: 0,
             is_release: cx.build_config.release,
             is_doc_all: cx.build_config.doc_all,
         }
@@ -104,6 +123,7 @@ impl<'a> JobQueue<'a> {
         let deps = try!(key.dependencies(cx));
         self.queue.queue(Fresh, key, Vec::new(), &deps).push((job, fresh));
         *self.counts.entry(key.pkg).or_insert(0) += 1;
+        self.total += 1;
         Ok(())
     }
 
@@ -136,10 +156,27 @@ impl<'a> JobQueue<'a> {
         // After a job has finished we update our internal state if it was
         // successful and otherwise wait for pending work to finish if it failed
         // and then immediately return.
+        //
+        // Ctrl-C is handled the same way as a failure: nothing more is
+        // started, and the jobs running are waited for. Those which fail
+        // because they were interrupted too have their fingerprints removed,
+        // so the next build knows they have to run again.
+        let _interrupts = job::catch_interrupts();
+        let mut interrupted = false;
+        let mut finished = 0;
+        let mut aborted = Vec::new();
         let mut error = None;
         let start_time = Instant::now();
         loop {
-            while error.is_none() && self.active < self.jobs {
+            if !interrupted && job::interrupted() {
+                interrupted = true;
+                if self.active > 0 {
+                    try!(cx.config.shell().say(
+                                "Interrupted, waiting for running jobs to \
+                                 finish...", YELLOW));
+                }
+            }
+            while error.is_none() && !interrupted && self.active < self.jobs {
                 if !queue.is_empty() {
                     let (key, job, fresh) = queue.remove(0);
                     try!(self.run(key, fresh, job, cx.config, scope));
@@ -162,11 +199,23 @@ impl<'a> JobQueue<'a> {
                 break
             }
 
-            let (key, msg) = self.rx.recv().unwrap();
+            // Messages are waited for a little at a time so that Ctrl-C is
+            // noticed while jobs are running.
+            let (key, msg) = match self.rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => unreachable!(),
+            };
 
             match msg {
                 Message::Run(cmd) => {
-                    try!(cx.config.shell().verbose(|c| c.status("Running", &cmd)));
+                    try!(cx.config.shell().status_at(Subsystem::Compile,
+                                                     Verbosity::Verbose,
+                                                     "Running", &cmd));
+                }
+                Message::Status(subsystem, verbosity, status, message) => {
+                    try!(cx.config.shell().status_at(subsystem, verbosity,
+                                                     status, message));
                 }
                 Message::Stdout(out) => {
                     if cx.config.extra_verbose() {
@@ -182,7 +231,20 @@ impl<'a> JobQueue<'a> {
                     info!("end: {:?}", key);
                     self.active -= 1;
                     match result {
-                        Ok(()) => try!(self.finish(key, cx)),
+                        Ok(()) => {
+                            try!(self.finish(key, cx));
+                            finished += 1;
+                        }
+                        Err(..) if job::interrupted() => {
+                            let unit = Unit {
+                                pkg: try!(cx.get_package(key.pkg)),
+                                target: key.target,
+                                profile: key.profile,
+                                kind: key.kind,
+                            };
+                            try!(fingerprint::invalidate(cx, &unit));
+                            aborted.push(key);
+                        }
                         Err(e) => {
                             if self.active > 0 {
                                 try!(cx.config.shell().say(
@@ -198,6 +260,15 @@ impl<'a> JobQueue<'a> {
             }
         }
 
+        if interrupted {
+            for key in aborted.iter() {
+                try!(cx.config.shell().status("Aborted", key.describe()));
+            }
+            bail!("build interrupted: {} jobs finished, {} aborted and {} not \
+                   started", finished, aborted.len(),
+                  self.total - finished - aborted.len())
+        }
+
         let build_type = if self.is_release { "release" } else { "debug" };
         let profile = cx.lib_profile(&cx.current_package);
         let mut opt_type = String::from(if profile.opt_level == "0" { "unoptimized" }
@@ -255,11 +326,24 @@ impl<'a> JobQueue<'a> {
 
     fn finish(&mut self, key: Key<'a>, cx: &mut Context) -> CargoResult<()> {
         if key.profile.run_custom_build && cx.show_warnings(key.pkg) {
-            let output = cx.build_state.outputs.lock().unwrap();
-            if let Some(output) = output.get(&(key.pkg.clone(), key.kind)) {
+            let scripts = cx.build_state.scripts.lock().unwrap();
+            let output = scripts.get(&(key.pkg.clone(), key.kind)).and_then(|s| {
+                s.get(key.target.name())
+            });
+            if let Some(output) = output {
                 for warning in output.warnings.iter() {
                     try!(cx.config.shell().warn(warning));
                 }
+                for diagnostic in output.diagnostics.iter() {
+                    if cx.build_config.json_messages {
+                        machine_message::emit(&BuildScriptMessage {
+                            package_id: key.pkg,
+                            message: diagnostic,
+                        });
+                    } else {
+                        try!(cx.config.shell().warn(diagnostic));
+                    }
+                }
             }
         }
         let state = self.pending.get_mut(&key).unwrap();
@@ -294,15 +378,21 @@ impl<'a> JobQueue<'a> {
             Dirty => {
                 if key.profile.doc {
                     self.documented.insert(key.pkg);
-                    try!(config.shell().status("Documenting", key.pkg));
+                    try!(config.shell().status_at(Subsystem::Compile,
+                                                  Verbosity::Normal,
+                                                  "Documenting", key.pkg));
                 } else {
                     self.compiled.insert(key.pkg);
-                    try!(config.shell().status("Compiling", key.pkg));
+                    try!(config.shell().status_at(Subsystem::Compile,
+                                                  Verbosity::Normal,
+                                                  "Compiling", key.pkg));
                 }
             }
             Fresh if self.counts[key.pkg] == 0 => {
                 self.compiled.insert(key.pkg);
-                try!(config.shell().verbose(|c| c.status("Fresh", key.pkg)));
+                try!(config.shell().status_at(Subsystem::Compile,
+                                              Verbosity::Verbose,
+                                              "Fresh", key.pkg));
             }
             Fresh => {}
         }
@@ -320,6 +410,17 @@ impl<'a> Key<'a> {
         }
     }
 
+    /// The package and target of the job, as shown to the user.
+    fn describe(&self) -> String {
+        if self.profile.run_custom_build {
+            format!("{} (running the build script)", self.pkg)
+        } else if self.profile.doc {
+            format!("{} (documenting `{}`)", self.pkg, self.target.name())
+        } else {
+            format!("{} (`{}`)", self.pkg, self.target.name())
+        }
+    }
+
     fn dependencies<'cfg>(&self, cx: &Context<'a, 'cfg>)
                           -> CargoResult<Vec<Key<'a>>> {
         let unit = Unit {
@@ -28,6 +28,11 @@
 //!             # is placed.
 //!             out/
 //!
+//!             # Packages with several build scripts instead have a directory
+//!             # named after each script, each with its own `out` directory.
+//!             build-script-$name/
+//!                 out/
+//!
 //!     # This is the location at which the output of all old custom build
 //!     # commands are rooted
 //!     native/
@@ -43,13 +48,35 @@
 //!     # Hidden directory that holds all of the fingerprint files for all
 //!     # packages
 //!     .fingerprint/
+//!
+//!     # The verbose version of the compiler which last built here
+//!     .rustc-version
+//!
+//!     # Artifacts of other compilers set aside with
+//!     # `--preserve-old-artifacts`, each containing its own `deps`,
+//!     # `build`, `native`, `examples` and `.fingerprint` directories
+//!     toolchains/
+//!         $hash1/
+//!         $hash2/
+//!
+//!     # Timings reported by rustc for units built with `--self-profile`
+//!     self-profile/
+//!
+//!     # Links to the most recently built test executables of each package,
+//!     # under names which don't change across rebuilds.
+//!     test-bins/
+//!         $pkg1/
+//!             lib/
+//!                 $target1
+//!             test/
+//!                 $target2
 //! ```
 
 use std::fs;
 use std::io;
 use std::path::{PathBuf, Path};
 
-use core::{Package, Workspace};
+use core::{Package, Target, Workspace};
 use util::{Config, FileLock, CargoResult, Filesystem};
 use util::hex::short_hash;
 use super::Unit;
@@ -61,19 +88,34 @@ pub struct Layout {
     build: PathBuf,
     fingerprint: PathBuf,
     examples: PathBuf,
+    self_profile: PathBuf,
+    test_bins: PathBuf,
+    toolchains: PathBuf,
     _lock: FileLock,
 }
 
 pub struct LayoutProxy<'a> {
     root: &'a Layout,
     primary: bool,
+    // The layout the rest of the build uses, which `root` differs from for
+    // packages whose artifacts are placed in a directory of their own.
+    dest: &'a Layout,
 }
 
 impl Layout {
     pub fn new(ws: &Workspace,
                triple: Option<&str>,
                dest: &str) -> CargoResult<Layout> {
-        let mut path = ws.target_dir();
+        Layout::in_target_dir(ws.config(), ws.target_dir(), triple, dest)
+    }
+
+    /// Creates the layout of a build in `target_dir` rather than in the
+    /// target directory of the workspace.
+    pub fn in_target_dir(config: &Config,
+                         target_dir: Filesystem,
+                         triple: Option<&str>,
+                         dest: &str) -> CargoResult<Layout> {
+        let mut path = target_dir;
         // Flexible target specifications often point at filenames, so interpret
         // the target triple as a Path and then just use the file stem as the
         // component for the directory name.
@@ -81,7 +123,7 @@ impl Layout {
             path.push(Path::new(triple).file_stem().unwrap());
         }
         path.push(dest);
-        Layout::at(ws.config(), path)
+        Layout::at(config, path)
     }
 
     pub fn at(config: &Config, root: Filesystem) -> CargoResult<Layout> {
@@ -97,6 +139,9 @@ impl Layout {
             build: root.join("build"),
             fingerprint: root.join(".fingerprint"),
             examples: root.join("examples"),
+            self_profile: root.join("self-profile"),
+            test_bins: root.join("test-bins"),
+            toolchains: root.join("toolchains"),
             root: root,
             _lock: lock,
         })
@@ -127,6 +172,47 @@ impl Layout {
     pub fn deps(&self) -> &Path { &self.deps }
     pub fn examples(&self) -> &Path { &self.examples }
     pub fn root(&self) -> &Path { &self.root }
+    pub fn self_profile(&self) -> &Path { &self.self_profile }
+
+    pub fn test_bins(&self, package: &Package) -> PathBuf {
+        self.test_bins.join(package.name())
+    }
+
+    pub fn rustc_version(&self) -> PathBuf {
+        self.root.join(".rustc-version")
+    }
+
+    /// Moves the artifacts in this layout to `toolchains/$old` and brings
+    /// back those previously set aside in `toolchains/$new`, returning
+    /// whether there were any.
+    pub fn swap_toolchain(&self, old: &str, new: &str) -> io::Result<bool> {
+        let dirs = [&self.deps, &self.native, &self.build, &self.fingerprint,
+                    &self.examples];
+
+        let saved = self.toolchains.join(old);
+        if fs::metadata(&saved).is_ok() {
+            try!(fs::remove_dir_all(&saved));
+        }
+        try!(fs::create_dir_all(&saved));
+        for dir in dirs.iter() {
+            if fs::metadata(dir).is_ok() {
+                try!(fs::rename(dir, saved.join(dir.file_name().unwrap())));
+            }
+        }
+
+        let restore = self.toolchains.join(new);
+        if fs::metadata(&restore).is_err() {
+            return Ok(false)
+        }
+        for dir in dirs.iter() {
+            let src = restore.join(dir.file_name().unwrap());
+            if fs::metadata(&src).is_ok() {
+                try!(fs::rename(&src, dir));
+            }
+        }
+        try!(fs::remove_dir_all(&restore));
+        Ok(true)
+    }
 
     pub fn fingerprint(&self, package: &Package) -> PathBuf {
         self.fingerprint.join(&self.pkg_dir(package))
@@ -136,8 +222,26 @@ impl Layout {
         self.build.join(&self.pkg_dir(package))
     }
 
+    /// The `OUT_DIR` a package is compiled with, which is that of its first
+    /// build script.
     pub fn build_out(&self, package: &Package) -> PathBuf {
-        self.build(package).join("out")
+        match package.targets().iter().find(|t| t.is_custom_build()) {
+            Some(script) => self.build_script_out(package, script),
+            None => self.build(package).join("out"),
+        }
+    }
+
+    /// The `OUT_DIR` of one build script of a package. Packages with several
+    /// build scripts give each of them a directory of their own.
+    pub fn build_script_out(&self, package: &Package, script: &Target) -> PathBuf {
+        let scripts = package.targets().iter().filter(|t| {
+            t.is_custom_build()
+        }).count();
+        if scripts > 1 {
+            self.build(package).join(script.name()).join("out")
+        } else {
+            self.build(package).join("out")
+        }
     }
 
     fn pkg_dir(&self, pkg: &Package) -> String {
@@ -150,12 +254,31 @@ impl<'a> LayoutProxy<'a> {
         LayoutProxy {
             root: root,
             primary: primary,
+            dest: root,
+        }
+    }
+
+    /// A proxy for a package whose artifacts are placed in `root` instead of
+    /// `dest`. All of its outputs are built in the `deps` directory of
+    /// `root`, from where those of the primary package are uplifted to
+    /// `dest`.
+    pub fn relocated(root: &'a Layout, dest: &'a Layout, primary: bool)
+                     -> LayoutProxy<'a> {
+        LayoutProxy {
+            root: root,
+            primary: primary,
+            dest: dest,
         }
     }
 
     pub fn root(&self) -> &'a Path {
-        if self.primary {self.root.dest()} else {self.root.deps()}
+        let relocated = self.root as *const Layout != self.dest as *const Layout;
+        if self.primary && !relocated {self.root.dest()} else {self.root.deps()}
     }
+
+    /// The directory the outputs of the primary package are uplifted to from
+    /// `deps`.
+    pub fn uplift_dir(&self) -> &'a Path { self.dest.dest() }
     pub fn deps(&self) -> &'a Path { self.root.deps() }
 
     pub fn examples(&self) -> &'a Path { self.root.examples() }
@@ -164,6 +287,12 @@ impl<'a> LayoutProxy<'a> {
 
     pub fn build_out(&self, pkg: &Package) -> PathBuf { self.root.build_out(pkg) }
 
+    pub fn build_script_out(&self, pkg: &Package, script: &Target) -> PathBuf {
+        self.root.build_script_out(pkg, script)
+    }
+
+    pub fn test_bins(&self, pkg: &Package) -> PathBuf { self.root.test_bins(pkg) }
+
     pub fn proxy(&self) -> &'a Layout { self.root }
 
     pub fn out_dir(&self, unit: &Unit) -> PathBuf {
@@ -181,6 +310,6 @@ impl<'a> LayoutProxy<'a> {
     pub fn doc_root(&self) -> PathBuf {
         // the "root" directory ends in 'debug' or 'release', and we want it to
         // end in 'doc' instead
-        self.root.root().parent().unwrap().join("doc")
+        self.dest.root().parent().unwrap().join("doc")
     }
 }
@@ -1,19 +1,40 @@
 use std::collections::{HashMap, HashSet};
 
-use core::PackageId;
-use util::CargoResult;
+use core::{Package, PackageId};
+use util::{CargoResult, Config};
 use super::Unit;
 
 pub struct Links<'a> {
     validated: HashSet<&'a PackageId>,
     links: HashMap<String, &'a PackageId>,
+    owners: HashMap<String, String>,
 }
 
 impl<'a> Links<'a> {
-    pub fn new() -> Links<'a> {
-        Links {
+    pub fn new(config: &Config) -> CargoResult<Links<'a>> {
+        // The `[links]` table in the configuration picks which package
+        // provides a native library when several of them claim to link it.
+        let mut owners = HashMap::new();
+        if let Some(table) = try!(config.get_table("links")) {
+            for (lib, value) in table.val.iter() {
+                let (name, _) = try!(value.string(&format!("links.{}", lib)));
+                owners.insert(lib.clone(), name.to_string());
+            }
+        }
+        Ok(Links {
             validated: HashSet::new(),
             links: HashMap::new(),
+            owners: owners,
+        })
+    }
+
+    /// Returns whether `pkg` provides the native library `lib`, which is
+    /// only not the case if the configuration has handed `lib` to some
+    /// other package.
+    pub fn provides(&self, pkg: &Package, lib: &str) -> bool {
+        match self.owners.get(lib) {
+            Some(owner) => owner == pkg.name(),
+            None => true,
         }
     }
 
@@ -21,29 +42,36 @@ impl<'a> Links<'a> {
         if !self.validated.insert(unit.pkg.package_id()) {
             return Ok(())
         }
-        let lib = match unit.pkg.manifest().links() {
-            Some(lib) => lib,
-            None => return Ok(()),
-        };
-        if let Some(prev) = self.links.get(lib) {
-            let pkg = unit.pkg.package_id();
-            if prev.name() == pkg.name() && prev.source_id() == pkg.source_id() {
-                bail!("native library `{}` is being linked to by more \
-                       than one version of the same package, but it can \
-                       only be linked once; try updating or pinning your \
-                       dependencies to ensure that this package only shows \
-                       up once\n\n  {}\n  {}", lib, prev, pkg)
-            } else {
-                bail!("native library `{}` is being linked to by more than \
-                       one package, and can only be linked to by one \
-                       package\n\n  {}\n  {}", lib, prev, pkg)
+        for lib in unit.pkg.manifest().links() {
+            if !self.provides(unit.pkg, lib) {
+                continue
             }
+            if let Some(prev) = self.links.get(lib) {
+                let pkg = unit.pkg.package_id();
+                if prev.name() == pkg.name() &&
+                   prev.source_id() == pkg.source_id() {
+                    bail!("native library `{}` is being linked to by more \
+                           than one version of the same package, but it can \
+                           only be linked once; try updating or pinning your \
+                           dependencies to ensure that this package only \
+                           shows up once\n\n  {}\n  {}", lib, prev, pkg)
+                } else {
+                    bail!("native library `{}` is being linked to by more \
+                           than one package, and can only be linked to by \
+                           one package\n\n  {}\n  {}\n\nif one of these \
+                           packages provides the library for both, it can \
+                           be chosen by adding the following to \
+                           .cargo/config:\n\n  [links]\n  {} = \"{}\"",
+                          lib, prev, pkg, lib, prev.name())
+                }
+            }
+            if !unit.pkg.manifest().targets().iter().any(|t| t.is_custom_build()) {
+                bail!("package `{}` specifies that it links to `{}` but does \
+                       not have a custom build script",
+                      unit.pkg.package_id(), lib)
+            }
+            self.links.insert(lib.to_string(), unit.pkg.package_id());
         }
-        if !unit.pkg.manifest().targets().iter().any(|t| t.is_custom_build()) {
-            bail!("package `{}` specifies that it links to `{}` but does not \
-                   have a custom build script", unit.pkg.package_id(), lib)
-        }
-        self.links.insert(lib.to_string(), unit.pkg.package_id());
         Ok(())
     }
 }
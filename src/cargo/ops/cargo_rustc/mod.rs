@@ -2,23 +2,29 @@ use std::collections::HashMap;
 use std::env;
 use std::ffi::{OsStr, OsString};
 use std::fs;
+use std::io::{self, Write};
 use std::path::{self, PathBuf};
 use std::sync::Arc;
 
+use rustc_serialize::json;
+
 use core::{Package, PackageId, PackageSet, Target, Resolve};
-use core::{Profile, Profiles, Workspace};
-use core::shell::ColorConfig;
-use util::{self, CargoResult, human};
+use core::{Profile, Profiles, TargetKind, Workspace};
+use core::shell::{ColorConfig, Subsystem, Verbosity};
+use util::{self, CargoResult, human, machine_message};
 use util::{Config, internal, ChainError, profile, join_paths, short_hash};
 
 use self::job::{Job, Work};
 use self::job_queue::JobQueue;
 
-pub use self::compilation::Compilation;
+pub use self::compilation::{Compilation, DoctestRunner};
 pub use self::context::{Context, Unit};
 pub use self::engine::{CommandPrototype, CommandType, ExecEngine, ProcessEngine};
 pub use self::layout::{Layout, LayoutProxy};
 pub use self::custom_build::{BuildOutput, BuildMap, BuildScripts};
+pub use self::sandbox::SandboxConfig;
+pub use self::runner::{TargetRunner, Preset, QemuSystem, parse_preset};
+pub use self::sbom::SbomFormat;
 
 mod context;
 mod compilation;
@@ -29,6 +35,16 @@ mod job;
 mod job_queue;
 mod layout;
 mod links;
+mod ninja;
+mod public_deps;
+mod runner;
+mod sandbox;
+mod sbom;
+mod script_cache;
+mod script_protocol;
+mod self_profile;
+mod system_deps;
+mod toolchain;
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, PartialOrd, Ord)]
 pub enum Kind { Host, Target }
@@ -44,13 +60,62 @@ pub struct BuildConfig {
     pub release: bool,
     pub test: bool,
     pub doc_all: bool,
+    pub sandbox: SandboxConfig,
+    pub script_cache: Option<PathBuf>,
+    pub json_messages: bool,
+    pub self_profile: Option<PathBuf>,
+    pub preserve_old_artifacts: bool,
+    /// Where to write a Ninja build file for the units instead of building
+    /// them.
+    pub emit_ninja: Option<PathBuf>,
+    pub sbom: Option<SbomFormat>,
+    /// Directories the artifacts of some packages are placed in instead of
+    /// the target directory, by package name.
+    pub package_target_dirs: HashMap<String, PathBuf>,
+    /// Packages built as the current one is, with their outputs placed at
+    /// the top of the target directory, when several are built together.
+    pub primary_packages: Vec<PackageId>,
 }
 
 #[derive(Clone, Default)]
 pub struct TargetConfig {
     pub ar: Option<PathBuf>,
     pub linker: Option<PathBuf>,
+    pub pkg_config: Option<PathBuf>,
+    pub sysroot: Option<PathBuf>,
     pub overrides: HashMap<String, BuildOutput>,
+    pub transforms: Vec<OutputTransform>,
+    /// What binaries built for the target are run through, if anything.
+    pub runner: Option<TargetRunner>,
+}
+
+/// An extra output derived from a linked artifact by running a tool on it,
+/// as configured in `[target.<triple>.transform.<name>]`.
+#[derive(Clone, Debug)]
+pub struct OutputTransform {
+    pub name: String,
+    /// The tool and its arguments, where `{input}` and `{output}` are
+    /// replaced with the paths of the artifact and of the derived output.
+    pub command: Vec<String>,
+    /// Appended to the file stem of the artifact to name the output.
+    pub suffix: String,
+    /// The kinds of targets (`bin`, `example`, `test`, `bench`) whose
+    /// artifacts are transformed.
+    pub kinds: Vec<String>,
+}
+
+impl OutputTransform {
+    pub fn applies_to(&self, target: &Target) -> bool {
+        self.kinds.iter().any(|kind| {
+            match &kind[..] {
+                "bin" => target.is_bin(),
+                "example" => target.is_example(),
+                "test" => target.is_test(),
+                "bench" => target.is_bench(),
+                _ => false,
+            }
+        })
+    }
 }
 
 pub type PackagesToBuild<'a> = [(&'a Package, Vec<(&'a Target,&'a Profile)>)];
@@ -87,10 +152,23 @@ pub fn compile_targets<'a, 'cfg: 'a>(ws: &Workspace<'cfg>,
 
     let mut queue = JobQueue::new(&cx);
 
+    if cx.build_config.self_profile.is_some() {
+        let profiled = units.iter().filter(|u| !u.profile.doc);
+        cx.self_profiled.extend(profiled.cloned());
+    }
+
+    let rollover = try!(toolchain::rollover(&cx));
     try!(cx.prepare());
     try!(cx.probe_target_info(&units));
     try!(cx.build_used_in_plugin_map(&units));
     try!(custom_build::build_map(&mut cx, &units));
+    try!(system_deps::probe_all(&cx));
+    try!(public_deps::check(&cx, &units));
+
+    if let Some(dst) = cx.build_config.emit_ninja.clone() {
+        try!(ninja::emit(&cx, &units, &dst));
+        return Ok(cx.compilation)
+    }
 
     for unit in units.iter() {
         // Build up a list of pending jobs, each of which represent
@@ -100,13 +178,59 @@ pub fn compile_targets<'a, 'cfg: 'a>(ws: &Workspace<'cfg>,
         // parallelism.
         try!(compile(&mut cx, &mut queue, unit));
     }
+    if let Some(ref rollover) = rollover {
+        try!(toolchain::notify(&cx, rollover));
+    }
+
+    // Profiles left over from a previous build would otherwise be taken for
+    // those of units which are fresh this time around.
+    for unit in cx.self_profiled.iter() {
+        try!(self_profile::clear(&cx, unit));
+    }
 
     // Now that we've figured out everything that we're going to do, do it!
     try!(queue.execute(&mut cx));
 
+    if let Some(ref dst) = cx.build_config.self_profile {
+        let profiled = units.iter().filter(|u| {
+            cx.self_profiled.contains(u) &&
+                self_profile::profile_path(&cx, u).exists()
+        }).cloned().collect::<Vec<_>>();
+        try!(self_profile::summarize(&cx, &profiled, dst));
+    }
+    if let Some(format) = cx.build_config.sbom {
+        try!(sbom::write(&cx, &units, format));
+    }
+
+    cx.compilation.target_triple = cx.target_triple().to_string();
+    cx.compilation.target_cfg = cx.cfg(Kind::Target).map(|cfg| cfg.to_vec());
+    cx.compilation.host_triple = cx.host_triple().to_string();
+    cx.compilation.host_cfg = cx.cfg(Kind::Host).map(|cfg| cfg.to_vec());
+    cx.compilation.sysroot = cx.sysroot(Kind::Target).map(|p| p.to_path_buf());
+    cx.compilation.target_linker = cx.linker(Kind::Target).map(|p| p.to_path_buf());
+    cx.compilation.runner = runner::target_runner(cx.host_triple(),
+                                                  cx.target_triple(),
+                                                  &cx.build_config.target);
+    let compiled = cx.compiled.iter().cloned().collect::<Vec<_>>();
+    for unit in compiled.iter().filter(|u| u.target.is_lib() && !u.profile.doc) {
+        let pkgid = unit.pkg.package_id().clone();
+        if let Some(script) = cx.active_build_scripts(unit.pkg, unit.kind).first() {
+            let out_dir = cx.layout(unit).build_script_out(unit.pkg, script);
+            cx.compilation.out_dirs.insert(pkgid.clone(), out_dir);
+        }
+        if unit.target.is_rustc_macro() && !unit.profile.test {
+            if let Some((filename, _)) = try!(cx.target_filenames(unit)).pop() {
+                let dst = cx.out_dir(unit).join(filename);
+                cx.compilation.rustc_macros.insert(pkgid, dst);
+            }
+        }
+    }
+
     for unit in units.iter() {
-        let out_dir = cx.layout(unit).build_out(unit.pkg)
-                        .display().to_string();
+        let out_dir = match cx.active_build_scripts(unit.pkg, unit.kind).first() {
+            Some(script) => cx.layout(unit).build_script_out(unit.pkg, script),
+            None => cx.layout(unit).build_out(unit.pkg),
+        }.display().to_string();
         cx.compilation.extra_env.entry(unit.pkg.package_id().clone())
           .or_insert(Vec::new())
           .push(("OUT_DIR".to_string(), out_dir));
@@ -115,7 +239,7 @@ pub fn compile_targets<'a, 'cfg: 'a>(ws: &Workspace<'cfg>,
             let dst = cx.out_dir(unit).join(filename);
             if unit.profile.test {
                 cx.compilation.tests.push((unit.pkg.clone(),
-                                           unit.target.name().to_string(),
+                                           unit.target.clone(),
                                            dst));
             } else if unit.target.is_bin() || unit.target.is_example() {
                 cx.compilation.binaries.push(dst);
@@ -186,7 +310,8 @@ fn compile<'a, 'cfg: 'a>(cx: &mut Context<'a, 'cfg>,
         } else {
             try!(rustc(cx, unit))
         };
-        let dirty = work.then(dirty);
+        let dirty = work.then(dirty).then(try!(link_test_bin(cx, unit)));
+        let fresh = fresh.then(try!(link_test_bin(cx, unit)));
         (dirty, fresh, freshness)
     };
     try!(jobs.enqueue(cx, unit, Job::new(dirty, fresh), freshness));
@@ -199,24 +324,133 @@ fn compile<'a, 'cfg: 'a>(cx: &mut Context<'a, 'cfg>,
     Ok(())
 }
 
-fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
-    let crate_types = unit.target.rustc_crate_types();
-    let mut rustc = try!(prepare_rustc(cx, crate_types, unit));
+/// Announces where the executable of a test unit was linked to in
+/// `--message-format=json`.
+#[derive(RustcEncodable)]
+struct TestBinaryMessage {
+    package_id: PackageId,
+    target: String,
+    executable: PathBuf,
+    link: PathBuf,
+}
 
-    let name = unit.pkg.name().to_string();
-    if !cx.show_warnings(unit.pkg.package_id()) {
-        if try!(cx.config.rustc()).cap_lints {
-            rustc.arg("--cap-lints").arg("allow");
-        } else {
-            rustc.arg("-Awarnings");
+impl machine_message::Message for TestBinaryMessage {
+    fn reason(&self) -> &str {
+        "test-binary"
+    }
+}
+
+/// Links the executable of a test unit of a local package to
+/// `test-bins/$pkg/$target`, which unlike the executable itself keeps its
+/// name across rebuilds so debuggers and IDEs can be pointed at it.
+fn link_test_bin(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
+    if !unit.profile.test || !unit.pkg.package_id().source_id().is_path() {
+        return Ok(Work::noop())
+    }
+    let filenames = try!(cx.target_filenames(unit));
+    let src = cx.out_dir(unit).join(&filenames[0].0);
+    let mut name = unit.target.name().to_string();
+    if let Some(ext) = src.extension().and_then(|e| e.to_str()) {
+        name.push('.');
+        name.push_str(ext);
+    }
+    // A test and a bench of the same package may share a name, so each kind
+    // of target is linked into a directory of its own.
+    let kind = match *unit.target.kind() {
+        TargetKind::Lib(..) => "lib",
+        TargetKind::Bin => "bin",
+        TargetKind::Test => "test",
+        TargetKind::Example => "example",
+        TargetKind::Bench => "bench",
+        TargetKind::CustomBuild => "build-script",
+    };
+    let dst = cx.layout(unit).test_bins(unit.pkg).join(kind).join(name);
+    let json_messages = cx.build_config.json_messages;
+    let id = unit.pkg.package_id().clone();
+    let target = unit.target.name().to_string();
+
+    Ok(Work::new(move |_| {
+        // This may have been a `cargo rustc` command which changes the
+        // output, so the source may not actually exist.
+        if !src.exists() {
+            return Ok(())
+        }
+        try!(fs::create_dir_all(dst.parent().unwrap()));
+        if fs::symlink_metadata(&dst).is_ok() {
+            try!(fs::remove_file(&dst).chain_error(|| {
+                human(format!("failed to remove: {}", dst.display()))
+            }));
+        }
+        // Hard links keep working when the target directory is moved, but
+        // aren't supported everywhere, so fall back to a copy.
+        if fs::hard_link(&src, &dst).is_err() {
+            try!(fs::copy(&src, &dst).chain_error(|| {
+                human(format!("failed to link `{}` to `{}`",
+                              src.display(), dst.display()))
+            }));
+        }
+        if json_messages {
+            machine_message::emit(&TestBinaryMessage {
+                package_id: id,
+                target: target,
+                executable: src,
+                link: dst,
+            });
+        }
+        Ok(())
+    }))
+}
+
+/// A diagnostic of the compiler in `--message-format=json`.
+#[derive(RustcEncodable)]
+struct CompilerMessage<'a> {
+    package_id: &'a PackageId,
+    target: &'a Target,
+    message: json::Json,
+}
+
+impl<'a> machine_message::Message for CompilerMessage<'a> {
+    fn reason(&self) -> &str {
+        "compiler-message"
+    }
+}
+
+/// Re-emits the diagnostics rustc printed with `--error-format json` as
+/// machine messages. Anything else it printed is passed through to stderr.
+fn forward_compiler_messages(id: &PackageId, target: &Target, stderr: &str) {
+    for line in stderr.lines() {
+        match line.parse::<json::Json>() {
+            Ok(message) => {
+                machine_message::emit(&CompilerMessage {
+                    package_id: id,
+                    target: target,
+                    message: message,
+                });
+            }
+            Err(..) => {
+                let _ = writeln!(io::stderr(), "{}", line);
+            }
         }
     }
+}
+
+fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
+    let mut rustc = try!(rustc_command(cx, unit));
+
+    let name = unit.pkg.name().to_string();
     let has_custom_args = unit.profile.rustc_args.is_some();
     let exec_engine = cx.exec_engine.clone();
 
     let filenames = try!(cx.target_filenames(unit));
     let root = cx.out_dir(unit);
 
+    // Tools configured for the platform derive extra outputs from the
+    // artifact once it has been linked.
+    let transforms = try!(transform_commands(cx, unit));
+    let outputs = filenames.iter().map(|&(ref f, _)| f.clone()).chain({
+        transforms.iter().map(|&(_, ref output, _)| output.clone())
+    }).collect::<Vec<_>>();
+
     // Prepare the native lib state (extra -L and -l flags)
     let build_state = cx.build_state.clone();
     let current_id = unit.pkg.package_id().clone();
@@ -229,7 +463,8 @@ fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
     let do_rename = unit.target.allows_underscores() && !unit.profile.test;
     let real_name = unit.target.name().to_string();
     let crate_name = unit.target.crate_name();
-    let move_outputs_up = unit.pkg.package_id() == &cx.current_package;
+    let move_outputs_up = cx.is_primary(unit.pkg.package_id());
+    let uplift_dir = cx.layout(unit).uplift_dir().to_path_buf();
 
     let rustc_dep_info_loc = if do_rename {
         root.join(&crate_name)
@@ -239,7 +474,22 @@ fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
     let dep_info_loc = fingerprint::dep_info_loc(cx, unit);
     let cwd = cx.config.cwd().to_path_buf();
 
-    rustc.args(&try!(cx.rustflags_args(unit)));
+    // With `--message-format json` the compiler's diagnostics are forwarded
+    // as machine messages.
+    let json_messages = cx.build_config.json_messages;
+    if json_messages {
+        rustc.arg("--error-format").arg("json");
+    }
+    let package_id = unit.pkg.package_id().clone();
+    let target = unit.target.clone();
+
+    // Profiled units have their timings captured from rustc's output.
+    let profile_path = if cx.self_profiled.contains(unit) {
+        rustc.args(&cx.self_profile_args(unit));
+        Some(self_profile::profile_path(cx, unit))
+    } else {
+        None
+    };
 
     return Ok(Work::new(move |state| {
         // Only at runtime have we discovered what the extra -L and -l
@@ -256,7 +506,7 @@ fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
 
         // FIXME(rust-lang/rust#18913): we probably shouldn't have to do
         //                              this manually
-        for &(ref filename, _linkable) in filenames.iter() {
+        for filename in outputs.iter() {
             let dst = root.join(filename);
             if fs::metadata(&dst).is_ok() {
                 try!(fs::remove_file(&dst).chain_error(|| {
@@ -266,9 +516,34 @@ fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
         }
 
         state.running(&rustc);
-        try!(exec_engine.exec(rustc).chain_error(|| {
-            human(format!("Could not compile `{}`.", name))
-        }));
+        if json_messages || profile_path.is_some() {
+            let result = exec_engine.exec_with_output(rustc);
+            {
+                let output = match result {
+                    Ok(ref output) => Some(output),
+                    Err(ref e) => e.output.as_ref(),
+                };
+                if let Some(output) = output {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    if json_messages {
+                        forward_compiler_messages(&package_id, &target, &stderr);
+                    } else {
+                        state.stderr(&stderr);
+                    }
+                }
+            }
+            let output = try!(result.chain_error(|| {
+                human(format!("Could not compile `{}`.", name))
+            }));
+            if let Some(ref profile_path) = profile_path {
+                try!(fs::create_dir_all(profile_path.parent().unwrap()));
+                try!(util::paths::write(profile_path, &output.stdout));
+            }
+        } else {
+            try!(exec_engine.exec(rustc).chain_error(|| {
+                human(format!("Could not compile `{}`.", name))
+            }));
+        }
 
         if do_rename && real_name != crate_name {
             let dst = root.join(&filenames[0].0);
@@ -290,12 +565,20 @@ fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
             try!(fingerprint::append_current_dir(&dep_info_loc, &cwd));
         }
 
+        for (transform, _, cmd) in transforms {
+            state.running(&cmd);
+            try!(exec_engine.exec(cmd).chain_error(|| {
+                human(format!("failed to run the `{}` transform for `{}`",
+                              transform, name))
+            }));
+        }
+
         // If we're a "root crate", e.g. the target of this compilation, then we
         // hard link our outputs out of the `deps` directory into the directory
         // above. This means that `cargo build` will produce binaries in
         // `target/debug` which one probably expects.
         if move_outputs_up {
-            for &(ref filename, _linkable) in filenames.iter() {
+            for filename in outputs.iter() {
                 let src = root.join(filename);
                 // This may have been a `cargo rustc` command which changes the
                 // output, so the source may not actually exist.
@@ -310,17 +593,22 @@ fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
                 if !src_dir.ends_with("deps") {
                     continue
                 }
-                let dst = src_dir.parent().unwrap()
-                                 .join(src.file_name().unwrap());
+                let dst = uplift_dir.join(src.file_name().unwrap());
                 if dst.exists() {
                     try!(fs::remove_file(&dst).chain_error(|| {
                         human(format!("failed to remove: {}", dst.display()))
                     }));
                 }
-                try!(fs::hard_link(&src, &dst).chain_error(|| {
-                    human(format!("failed to link `{}` to `{}`",
-                                  src.display(), dst.display()))
-                }));
+                state.status(Subsystem::Uplift, Verbosity::Trace, "Uplifting",
+                             format!("`{}` to `{}`", src.display(), dst.display()));
+                // Packages built in a `build.package-target-dir` may be on
+                // another filesystem, which they can't be hard linked from.
+                if fs::hard_link(&src, &dst).is_err() {
+                    try!(fs::copy(&src, &dst).chain_error(|| {
+                        human(format!("failed to link `{}` to `{}`",
+                                      src.display(), dst.display()))
+                    }));
+                }
             }
         }
 
@@ -357,6 +645,43 @@ fn rustc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
     }
 }
 
+/// The commands of the transforms applied to the artifact of `unit`, along
+/// with their names and the file names of their outputs.
+fn transform_commands(cx: &Context, unit: &Unit)
+                      -> CargoResult<Vec<(String, String, CommandPrototype)>> {
+    let filenames = try!(cx.target_filenames(unit));
+    let root = cx.out_dir(unit);
+    let mut transforms = Vec::new();
+    for (transform, output) in cx.output_transforms(unit) {
+        let input = root.join(&filenames[0].0).display().to_string();
+        let output_path = root.join(&output).display().to_string();
+        let tool = CommandType::Host(From::from(&transform.command[0]));
+        let mut cmd = try!(process(tool, unit.pkg, cx));
+        for arg in transform.command[1..].iter() {
+            cmd.arg(&arg.replace("{input}", &input)
+                        .replace("{output}", &output_path));
+        }
+        transforms.push((transform.name.clone(), output, cmd));
+    }
+    Ok(transforms)
+}
+
+/// The rustc invocation compiling `unit`, without the flags coming from the
+/// output of build scripts.
+fn rustc_command(cx: &Context, unit: &Unit) -> CargoResult<CommandPrototype> {
+    let crate_types = unit.target.rustc_crate_types();
+    let mut rustc = try!(prepare_rustc(cx, crate_types, unit));
+    if !cx.show_warnings(unit.pkg.package_id()) {
+        if try!(cx.config.rustc()).cap_lints {
+            rustc.arg("--cap-lints").arg("allow");
+        } else {
+            rustc.arg("-Awarnings");
+        }
+    }
+    rustc.args(&try!(cx.rustflags_args(unit)));
+    Ok(rustc)
+}
+
 fn load_build_deps(cx: &Context, unit: &Unit) -> Option<Arc<BuildScripts>> {
     cx.build_scripts.get(unit).cloned()
 }
@@ -427,9 +752,7 @@ fn rustdoc(cx: &mut Context, unit: &Unit) -> CargoResult<Work> {
 
     try!(build_deps_args(&mut rustdoc, cx, unit));
 
-    if unit.pkg.has_custom_build() {
-        rustdoc.env("OUT_DIR", &cx.layout(unit).build_out(unit.pkg));
-    }
+    set_out_dirs(&mut rustdoc, cx, unit);
 
     rustdoc.args(&try!(cx.rustdocflags_args(unit)));
 
@@ -504,7 +827,7 @@ fn build_base_args(cx: &Context,
     let prefer_dynamic = (unit.target.for_host() &&
                           !unit.target.is_custom_build()) ||
                          (crate_types.contains(&"dylib") &&
-                          unit.pkg.package_id() != &cx.current_package);
+                          !cx.is_primary(unit.pkg.package_id()));
     if prefer_dynamic {
         cmd.arg("-C").arg("prefer-dynamic");
     }
@@ -604,6 +927,31 @@ fn build_plugin_args(cmd: &mut CommandPrototype, cx: &Context, unit: &Unit) {
     opt(cmd, "-C", "linker=", cx.linker(unit.kind).map(|s| s.as_ref()));
 }
 
+/// Points `OUT_DIR` at the output of the build script of `unit`'s package,
+/// if it has one for the platform being built for. Packages with several
+/// build scripts also get `OUT_DIR_$NAME` for each of them, `$NAME` being the
+/// file name of the script.
+fn set_out_dirs(cmd: &mut CommandPrototype, cx: &Context, unit: &Unit) {
+    let scripts = cx.active_build_scripts(unit.pkg, unit.kind);
+    let layout = cx.layout(unit);
+    match scripts.first() {
+        Some(script) => {
+            cmd.env("OUT_DIR", &layout.build_script_out(unit.pkg, script));
+        }
+        None => return,
+    }
+    let all = unit.pkg.targets().iter().filter(|t| {
+        t.is_custom_build()
+    }).count();
+    if all > 1 {
+        for script in scripts {
+            let name = &script.name()["build-script-".len()..];
+            cmd.env(&format!("OUT_DIR_{}", envify(name)),
+                    &layout.build_script_out(unit.pkg, script));
+        }
+    }
+}
+
 fn build_deps_args(cmd: &mut CommandPrototype, cx: &Context, unit: &Unit)
                    -> CargoResult<()> {
     let layout = cx.layout(unit);
@@ -612,11 +960,17 @@ fn build_deps_args(cmd: &mut CommandPrototype, cx: &Context, unit: &Unit)
         deps.push(layout.deps());
         deps
     });
-
-    if unit.pkg.has_custom_build() {
-        cmd.env("OUT_DIR", &layout.build_out(unit.pkg));
+    // Dependencies may also have been built in a `build.package-target-dir`.
+    for dir in cx.deps_dirs(unit.kind) {
+        if dir != layout.deps() {
+            let mut deps = OsString::from("dependency=");
+            deps.push(dir);
+            cmd.arg("-L").arg(&deps);
+        }
     }
 
+    set_out_dirs(cmd, cx, unit);
+
     for unit in try!(cx.dep_targets(unit)).iter() {
         if unit.target.linkable() && !unit.profile.doc {
             try!(link_to(cmd, cx, unit));
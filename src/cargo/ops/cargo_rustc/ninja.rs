@@ -0,0 +1,408 @@
+//! Lowers the units of a build into a Ninja build file for `cargo build
+//! --emit-ninja`, so that builds planned by Cargo can be run by Ninja, or by
+//! a remote execution backend reading its files, without Cargo.
+//!
+//! Every unit becomes a build statement running the rustc invocation Cargo
+//! itself would, and build scripts are run by Ninja as well. Of the output of
+//! a build script the `rustc-link-search`, `rustc-link-lib`, `rustc-flags`
+//! and `rustc-cfg` directives are applied, through files of arguments which
+//! rustc reads with `@file`. The metadata of packages with a `links` key is
+//! written to files of `DEP_*` variables, which the build scripts depending
+//! on them source before running. Only directives written as `cargo:` lines
+//! are understood, not those of protocol v2 messages. Commands are run by a
+//! POSIX shell.
+
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use core::{Package, Target};
+use util::{CargoResult, paths};
+
+use super::{custom_build, CommandPrototype, Context, Kind, Unit};
+
+const RULES: &'static str = "\
+ninja_required_version = 1.10
+
+rule rustc
+  command = $cmd
+  description = Compiling $name
+  depfile = $depfile
+
+rule build_script
+  command = $cmd
+  description = Running the build script of $name
+
+rule script_args
+  command = awk -v search=$search -v cfg=$cfg -v link=$link '$
+    BEGIN { printf \"\" > search; printf \"\" > cfg; printf \"\" > link } $
+    { i = index($$0, \"=\"); k = substr($$0, 1, i - 1); v = substr($$0, i + 1) } $
+    k == \"cargo:rustc-link-search\" { print \"-L\" > search; print v > search } $
+    k == \"cargo:rustc-cfg\" { print \"--cfg\" > cfg; print v > cfg } $
+    k == \"cargo:rustc-link-lib\" { print \"-l\" > link; print v > link } $
+    k == \"cargo:rustc-flags\" { n = split(v, f, \" \"); for (j = 1; j < n; j += 2) { $
+      if (f[j] == \"-L\") { print \"-L\" > search; print f[j + 1] > search } $
+      if (f[j] == \"-l\") { print \"-l\" > link; print f[j + 1] > link } } }' $in
+  description = Reading the output of $name
+
+rule dep_env
+  command = awk -v lib=$lib -v links=$links -v out=$file '$
+    BEGIN { n = split(links, l, \",\"); for (j = 1; j <= n; j++) own[l[j]] = 1; $
+            n = split(\"warning rerun-if-changed declares-output tracked-input protocol\", $
+                      l, \" \"); for (j = 1; j <= n; j++) skip[l[j]] = 1; $
+            printf \"\" > out; env = toupper(lib); gsub(/-/, \"_\", env) } $
+    !sub(/^cargo::?/, \"\") { next } $
+    { i = index($$0, \"=\"); k = substr($$0, 1, i - 1); v = substr($$0, i + 1) } $
+    i == 0 || k ~ /^rustc-/ || (k in skip) { next } $
+    (d = index(k, \".\")) && (substr(k, 1, d - 1) in own) { $
+      if (substr(k, 1, d - 1) != lib) next; k = substr(k, d + 1) } $
+    { k = toupper(k); gsub(/-/, \"_\", k); sub(/[ \\t\\r]+$$/, \"\", v); $
+      gsub(/\\047/, \"\\047\\\"\\047\\\"\\047\", v); $
+      print \"export DEP_\" env \"_\" k \"=\\047\" v \"\\047\" > out }' $in
+  description = Reading the metadata of $name for DEP_ variables
+
+rule transform
+  command = $cmd
+  description = Running the $name transform of $in
+
+rule uplift
+  command = ln -f $in $out 2>/dev/null || cp -f $in $out
+  description = Uplifting $out
+";
+
+/// Writes a Ninja build file building `units` to `dst`.
+pub fn emit<'a, 'cfg>(cx: &Context<'a, 'cfg>,
+                      units: &[Unit<'a>],
+                      dst: &Path) -> CargoResult<()> {
+    let mut ninja = Ninja {
+        out: String::new(),
+        seen: HashSet::new(),
+        statements: 0,
+    };
+    let mut defaults = Vec::new();
+    for unit in units.iter() {
+        defaults.extend(try!(ninja.unit(cx, unit)));
+    }
+
+    let mut contents = format!("# Generated by `cargo build --emit-ninja` for {}.\n\n{}",
+                               cx.current_package, RULES);
+    contents.push_str(&ninja.out);
+    contents.push_str("\ndefault");
+    for path in defaults.iter() {
+        contents.push(' ');
+        contents.push_str(&escape_path(path));
+    }
+    contents.push('\n');
+    try!(paths::write(dst, contents.as_bytes()));
+    try!(cx.config.shell().status("Emitted",
+                                  format!("{} build statements to `{}`",
+                                          ninja.statements, dst.display())));
+    Ok(())
+}
+
+struct Ninja<'a> {
+    out: String,
+    seen: HashSet<Unit<'a>>,
+    statements: usize,
+}
+
+/// The argument files holding the flags of the output of a build script.
+struct ScriptArgs {
+    search: PathBuf,
+    cfg: PathBuf,
+    link: PathBuf,
+}
+
+impl<'a> Ninja<'a> {
+    /// Adds the build statements of `unit` and of its dependencies, and
+    /// returns the files `unit` is finally built to.
+    fn unit<'cfg>(&mut self, cx: &Context<'a, 'cfg>, unit: &Unit<'a>)
+                  -> CargoResult<Vec<PathBuf>> {
+        let deps = try!(cx.dep_targets(unit));
+        if self.seen.insert(*unit) {
+            let mut inputs = Vec::new();
+            for dep in deps.iter() {
+                inputs.extend(try!(self.unit(cx, dep)));
+            }
+            if unit.profile.run_custom_build {
+                try!(self.build_script(cx, unit, inputs));
+            } else if unit.profile.doc {
+                bail!("documentation can't be built from a Ninja file")
+            } else {
+                try!(self.rustc(cx, unit, inputs));
+            }
+        }
+        outputs(cx, unit)
+    }
+
+    fn rustc<'cfg>(&mut self, cx: &Context<'a, 'cfg>, unit: &Unit<'a>,
+                   mut inputs: Vec<PathBuf>) -> CargoResult<()> {
+        let mut rustc = try!(super::rustc_command(cx, unit));
+        let root = cx.out_dir(unit);
+        try!(fs::create_dir_all(&root));
+
+        // The flags from build scripts are only known once they've run, so
+        // rustc reads them from the files they're extracted to.
+        if let Some(scripts) = cx.build_scripts.get(unit) {
+            let pass_l_flag = unit.target.is_lib() ||
+                              !unit.pkg.targets().iter().any(|t| t.is_lib());
+            for &(ref id, kind) in scripts.to_link.iter() {
+                let pkg = try!(cx.packages.get(id));
+                for script in cx.active_build_scripts(pkg, kind) {
+                    let args = script_args(cx, pkg, script, kind);
+                    rustc.arg(arg_file(&args.search));
+                    inputs.push(args.search);
+                    if id == unit.pkg.package_id() {
+                        rustc.arg(arg_file(&args.cfg));
+                        inputs.push(args.cfg);
+                        if pass_l_flag {
+                            rustc.arg(arg_file(&args.link));
+                            inputs.push(args.link);
+                        }
+                    }
+                }
+            }
+        }
+
+        let filenames = try!(cx.target_filenames(unit));
+        let do_rename = unit.target.allows_underscores() && !unit.profile.test;
+        let real_name = unit.target.name().to_string();
+        let crate_name = unit.target.crate_name();
+        let depfile = if do_rename {
+            root.join(&crate_name)
+        } else {
+            root.join(&cx.file_stem(unit))
+        }.with_extension("d");
+
+        let mut cmd = command(&rustc);
+        if do_rename && real_name != crate_name {
+            let dst = root.join(&filenames[0].0);
+            let src = dst.with_file_name(dst.file_name().unwrap()
+                                            .to_str().unwrap()
+                                            .replace(&real_name, &crate_name));
+            cmd.push_str(&format!(" && mv -f {} {}", quote(&src), quote(&dst)));
+        }
+
+        let mut outputs = filenames.iter().map(|&(ref f, _)| {
+            root.join(f)
+        }).collect::<Vec<_>>();
+        let src = unit.pkg.root().join(unit.target.src_path());
+        self.build(&outputs, "rustc", &[src], &inputs, &[
+            ("cmd", cmd),
+            ("depfile", depfile.display().to_string()),
+            ("name", format!("{} ({})", unit.pkg, unit.target.name())),
+        ]);
+
+        for (name, output, transform) in try!(super::transform_commands(cx, unit)) {
+            let output = root.join(output);
+            self.build(&[output.clone()], "transform", &[outputs[0].clone()], &[], &[
+                ("cmd", command(&transform)),
+                ("name", name),
+            ]);
+            outputs.push(output);
+        }
+
+        // The outputs of the package being built are hard linked out of the
+        // `deps` directory, as Cargo does.
+        if uplifted(cx, unit) {
+            for src in outputs.iter() {
+                let dst = cx.layout(unit).uplift_dir().join(src.file_name().unwrap());
+                self.build(&[dst], "uplift", &[src.clone()], &[], &[]);
+            }
+        }
+        Ok(())
+    }
+
+    fn build_script<'cfg>(&mut self, cx: &Context<'a, 'cfg>, unit: &Unit<'a>,
+                          inputs: Vec<PathBuf>) -> CargoResult<()> {
+        let (script, target_info) = try!(custom_build::script_process(cx, unit));
+        let out_dir = cx.layout(unit).build_script_out(unit.pkg, unit.target);
+        try!(fs::create_dir_all(&out_dir));
+        try!(paths::write(&out_dir.join("target-info.json"),
+                          target_info.as_bytes()));
+
+        // The metadata of the packages this one links against is only known
+        // once their build scripts have run, so it's sourced from the files
+        // it was extracted to.
+        let mut sourced = String::new();
+        for dep in try!(cx.dep_run_custom_build(unit)) {
+            if !dep.profile.run_custom_build {
+                continue
+            }
+            for (_, file) in dep_env_files(cx, &dep) {
+                sourced.push_str(&format!(". {} && ", quote(&file)));
+            }
+        }
+
+        let output = out_dir.parent().unwrap().join("output");
+        let cmd = format!("{}{} > {}", sourced, command(&script), quote(&output));
+        let name = unit.pkg.to_string();
+        self.build(&[output.clone()], "build_script", &inputs, &[], &[
+            ("cmd", cmd),
+            ("name", name.clone()),
+        ]);
+
+        let links = unit.pkg.manifest().links().join(",");
+        for (lib, file) in dep_env_files(cx, unit) {
+            self.build(&[file.clone()], "dep_env", &[output.clone()], &[], &[
+                ("lib", quote(&lib)),
+                ("links", quote(&links)),
+                ("file", quote(&file)),
+                ("name", name.clone()),
+            ]);
+        }
+
+        let args = script_args(cx, unit.pkg, unit.target, unit.kind);
+        let files = vec![args.search.clone(), args.cfg.clone(), args.link.clone()];
+        self.build(&files, "script_args", &[output], &[], &[
+            ("search", quote(&args.search)),
+            ("cfg", quote(&args.cfg)),
+            ("link", quote(&args.link)),
+            ("name", name),
+        ]);
+        Ok(())
+    }
+
+    fn build(&mut self,
+             outputs: &[PathBuf],
+             rule: &str,
+             inputs: &[PathBuf],
+             implicit: &[PathBuf],
+             vars: &[(&str, String)]) {
+        let list = |paths: &[PathBuf]| {
+            paths.iter().map(|p| escape_path(p)).collect::<Vec<_>>().join(" ")
+        };
+        write!(self.out, "\nbuild {}: {} {}", list(outputs), rule,
+               list(inputs)).unwrap();
+        if !implicit.is_empty() {
+            write!(self.out, " | {}", list(implicit)).unwrap();
+        }
+        self.out.push('\n');
+        for &(name, ref value) in vars.iter() {
+            writeln!(self.out, "  {} = {}", name, value.replace("$", "$$")).unwrap();
+        }
+        self.statements += 1;
+    }
+}
+
+/// The files `unit` is finally built to, which are its artifacts and their
+/// transforms or, for a run of a build script, the arguments and `DEP_*`
+/// variables extracted from its output.
+fn outputs<'a, 'cfg>(cx: &Context<'a, 'cfg>, unit: &Unit<'a>)
+                     -> CargoResult<Vec<PathBuf>> {
+    if unit.profile.run_custom_build {
+        let args = script_args(cx, unit.pkg, unit.target, unit.kind);
+        let mut outputs = vec![args.search, args.cfg, args.link];
+        outputs.extend(dep_env_files(cx, unit).into_iter().map(|(_, f)| f));
+        return Ok(outputs)
+    }
+    let root = cx.out_dir(unit);
+    let filenames = try!(cx.target_filenames(unit)).into_iter().map(|(f, _)| f);
+    let transformed = cx.output_transforms(unit).into_iter().map(|(_, f)| f);
+    Ok(filenames.chain(transformed).map(|f| {
+        let src = root.join(f);
+        if uplifted(cx, unit) {
+            cx.layout(unit).uplift_dir().join(src.file_name().unwrap())
+        } else {
+            src
+        }
+    }).collect())
+}
+
+fn uplifted(cx: &Context, unit: &Unit) -> bool {
+    cx.is_primary(unit.pkg.package_id()) && cx.out_dir(unit).ends_with("deps")
+}
+
+fn script_args<'a, 'cfg>(cx: &Context<'a, 'cfg>,
+                         pkg: &'a Package,
+                         script: &'a Target,
+                         kind: Kind) -> ScriptArgs {
+    let unit = Unit {
+        pkg: pkg,
+        target: script,
+        profile: cx.build_script_profile(pkg.package_id()),
+        kind: kind,
+    };
+    let dir = cx.layout(&unit).build_script_out(pkg, script);
+    let dir = dir.parent().unwrap();
+    ScriptArgs {
+        search: dir.join("search-args"),
+        cfg: dir.join("cfg-args"),
+        link: dir.join("link-args"),
+    }
+}
+
+/// The files of `DEP_*` variables extracted from the output of the build
+/// script run by `unit`, one for each library it provides.
+fn dep_env_files(cx: &Context, unit: &Unit) -> Vec<(String, PathBuf)> {
+    let dir = cx.layout(unit).build_script_out(unit.pkg, unit.target);
+    let dir = dir.parent().unwrap();
+    unit.pkg.manifest().links().iter().filter(|lib| {
+        cx.links.provides(unit.pkg, lib)
+    }).map(|lib| {
+        (lib.clone(), dir.join(format!("dep-env-{}", lib)))
+    }).collect()
+}
+
+fn arg_file(path: &Path) -> OsString {
+    let mut arg = OsString::from("@");
+    arg.push(path);
+    arg
+}
+
+/// Renders `cmd` as a shell command, run in its working directory with its
+/// environment variables set.
+fn command(cmd: &CommandPrototype) -> String {
+    let mut ret = String::new();
+    if let Some(cwd) = cmd.get_cwd() {
+        ret.push_str(&format!("cd {} && ", quote(cwd)));
+    }
+    ret.push_str("env");
+    let mut envs = cmd.get_envs().iter().collect::<Vec<_>>();
+    envs.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, value) in envs {
+        match *value {
+            Some(ref value) => {
+                let mut var = OsString::from(format!("{}=", key));
+                var.push(value);
+                ret.push(' ');
+                ret.push_str(&quote(&var));
+            }
+            None => ret.push_str(&format!(" -u {}", quote(key))),
+        }
+    }
+    ret.push(' ');
+    ret.push_str(&quote(cmd.get_program()));
+    for arg in cmd.get_args().iter() {
+        ret.push(' ');
+        ret.push_str(&quote(arg));
+    }
+    ret
+}
+
+/// Quotes `s` for a POSIX shell.
+fn quote<S: AsRef<OsStr> + ?Sized>(s: &S) -> String {
+    let s = s.as_ref().to_string_lossy();
+    let plain = !s.is_empty() && s.chars().all(|c| {
+        c.is_alphanumeric() || "-_=/.,:+@%".contains(c)
+    });
+    if plain {
+        s.into_owned()
+    } else {
+        format!("'{}'", s.replace("'", "'\\''"))
+    }
+}
+
+/// Escapes a path in a `build` line of a Ninja file.
+fn escape_path(path: &Path) -> String {
+    let mut ret = String::new();
+    for c in path.to_string_lossy().chars() {
+        if c == '$' || c == ' ' || c == ':' {
+            ret.push('$');
+        }
+        ret.push(c);
+    }
+    ret
+}
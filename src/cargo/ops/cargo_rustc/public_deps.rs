@@ -0,0 +1,91 @@
+//! Checking the dependencies declared `public = true`.
+//!
+//! The types of a public dependency are part of the API of the package
+//! depending on it, so whatever uses that package can be handed them as well.
+//! When a package can see two semver-incompatible versions of one crate this
+//! way, the types of one can't be used where those of the other are expected,
+//! which tends to surface as baffling type errors long after the versions
+//! diverged. Local packages are warned about it before anything is built.
+
+use std::collections::HashSet;
+
+use core::PackageId;
+use util::CargoResult;
+
+use super::{Context, Unit};
+
+/// Warns about each local package among `units` and their dependencies which
+/// can see incompatible versions of a crate.
+pub fn check<'a, 'cfg>(cx: &Context<'a, 'cfg>, units: &[Unit<'a>]) -> CargoResult<()> {
+    let mut visited = HashSet::new();
+    let mut reported = HashSet::new();
+    let mut todo = units.to_vec();
+    while let Some(unit) = todo.pop() {
+        if !visited.insert(unit) {
+            continue
+        }
+        todo.extend(try!(cx.dep_targets(&unit)));
+        if !unit.pkg.package_id().source_id().is_path() ||
+           unit.profile.run_custom_build || unit.profile.doc {
+            continue
+        }
+
+        let mut seen = Vec::new();
+        try!(visible(cx, &unit, false, &mut Vec::new(), &mut seen));
+        for (i, &(a, ref a_path)) in seen.iter().enumerate() {
+            for &(b, ref b_path) in seen[i + 1..].iter() {
+                if a.name() != b.name() ||
+                   !reported.insert((unit.pkg.package_id(), a.name())) {
+                    continue
+                }
+                try!(cx.config.shell().warn(format!(
+                    "`{}` can use the types of both `{}` and `{}`, which \
+                     aren't interchangeable:\n  `{}` {}\n  `{}` {}\n\
+                     a dependency declared `public` exposes its types to \
+                     whatever depends on the package declaring it",
+                    unit.pkg, a, b, a, describe(a_path), b, describe(b_path))));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Collects the libraries `unit` can use the types of, along with the
+/// packages each is reached through: its own dependencies, and those they
+/// depend on publicly in turn. With `public_only` only the public
+/// dependencies of `unit` are followed.
+fn visible<'a, 'cfg>(cx: &Context<'a, 'cfg>,
+                     unit: &Unit<'a>,
+                     public_only: bool,
+                     path: &mut Vec<&'a PackageId>,
+                     seen: &mut Vec<(&'a PackageId, Vec<&'a PackageId>)>)
+                     -> CargoResult<()> {
+    for dep in try!(cx.dep_targets(unit)) {
+        if !dep.target.is_lib() || dep.profile.run_custom_build ||
+           dep.pkg.package_id() == unit.pkg.package_id() {
+            continue
+        }
+        if public_only && !unit.pkg.dependencies().iter().any(|d| {
+            d.name() == dep.pkg.name() && d.is_public()
+        }) {
+            continue
+        }
+        let id = dep.pkg.package_id();
+        if seen.iter().any(|&(other, _)| other == id) {
+            continue
+        }
+        seen.push((id, path.clone()));
+        path.push(id);
+        try!(visible(cx, &dep, true, path, seen));
+        path.pop();
+    }
+    Ok(())
+}
+
+fn describe(path: &[&PackageId]) -> String {
+    if path.is_empty() {
+        return "directly".to_string()
+    }
+    let path = path.iter().map(|id| format!("`{}`", id)).collect::<Vec<_>>();
+    format!("through {}", path.join(" and "))
+}
@@ -0,0 +1,398 @@
+//! Runners Cargo sets up itself for binaries the machine can't run directly:
+//! QEMU's user-mode and system emulators, and wasmtime.
+//!
+//! A preset is picked with the `preset` key of `target.$triple.runner`. When
+//! cross-compiling to a Linux or WASI target without a runner configured, the
+//! `qemu-user` or `wasmtime` preset is used, so that the tests of such targets
+//! run out of the box on a machine with the emulator installed, or with a
+//! binfmt_misc handler registered for QEMU.
+
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use core::Runner;
+use util::{CargoResult, paths};
+use util::config::ConfigValue;
+
+use super::TargetConfig;
+
+/// What binaries built for a target are run through, unless their target has
+/// a runner of its own in the manifest.
+#[derive(Clone, Debug)]
+pub enum TargetRunner {
+    /// A command line given in the configuration.
+    Command(Runner),
+    /// A runner Cargo sets up itself.
+    Preset(Preset),
+    /// Nothing can run the binaries, for the reason given.
+    Unavailable(String),
+}
+
+#[derive(Clone, Debug)]
+pub enum Preset {
+    /// `qemu-$arch`, QEMU's user-mode emulator, or the binfmt_misc handler the
+    /// system registered for it, loading the libraries of the target from
+    /// `sysroot`.
+    QemuUser { sysroot: Option<PathBuf> },
+    /// `qemu-system-$arch`, booting either the binary itself, its arguments
+    /// given through semihosting, or a kernel and initrd which run it.
+    QemuSystem(QemuSystem),
+    /// `wasmtime`, with the working directory preopened.
+    Wasmtime { args: Vec<String> },
+}
+
+#[derive(Clone, Debug)]
+pub struct QemuSystem {
+    pub machine: String,
+    pub cpu: Option<String>,
+    pub memory: Option<String>,
+    /// The kernel booted instead of the binary. Its initrd is expected to
+    /// mount the 9p filesystem tagged `cargo`, holding the binary, and run
+    /// the binary named by the `opt/cargo/exe` fw_cfg entry with the
+    /// arguments of `opt/cargo/args`, one per line.
+    pub kernel: Option<PathBuf>,
+    pub initrd: Option<PathBuf>,
+    /// The command line of the kernel.
+    pub append: Option<String>,
+    /// Further arguments of QEMU.
+    pub args: Vec<String>,
+}
+
+/// Parses the preset runner of the table `key`, relative paths being
+/// relative to `root`.
+pub fn parse_preset(key: &str,
+                    table: &HashMap<String, ConfigValue>,
+                    root: &Path) -> CargoResult<Preset> {
+    let string = |name: &str| -> CargoResult<Option<String>> {
+        match table.get(name) {
+            Some(value) => {
+                let key = format!("{}.{}", key, name);
+                Ok(Some(try!(value.string(&key)).0.to_string()))
+            }
+            None => Ok(None),
+        }
+    };
+    let path = |name: &str| -> CargoResult<Option<PathBuf>> {
+        Ok(try!(string(name)).map(|p| root.join(p)))
+    };
+    let args = match table.get("args") {
+        Some(value) => {
+            let args = try!(value.list(&format!("{}.args", key)));
+            args.iter().map(|v| v.0.clone()).collect()
+        }
+        None => Vec::new(),
+    };
+    let preset = try!(string("preset")).unwrap_or(String::new());
+    match &preset[..] {
+        "qemu-user" => Ok(Preset::QemuUser { sysroot: try!(path("sysroot")) }),
+        "qemu-system" => {
+            let machine = match try!(string("machine")) {
+                Some(machine) => machine,
+                None => bail!("`{}.machine` must be specified for the \
+                               `qemu-system` runner", key),
+            };
+            Ok(Preset::QemuSystem(QemuSystem {
+                machine: machine,
+                cpu: try!(string("cpu")),
+                memory: try!(string("memory")),
+                kernel: try!(path("kernel")),
+                initrd: try!(path("initrd")),
+                append: try!(string("append")),
+                args: args,
+            }))
+        }
+        "wasmtime" => Ok(Preset::Wasmtime { args: args }),
+        _ => bail!("unknown runner preset `{}` in `{}.preset`, expected \
+                    `qemu-user`, `qemu-system` or `wasmtime`", preset, key),
+    }
+}
+
+/// The runner of binaries built for `target` on `host`, given the
+/// configuration of the target.
+pub fn target_runner(host: &str, target: &str, config: &TargetConfig)
+                     -> Option<TargetRunner> {
+    let runner = match config.runner {
+        Some(ref runner) => runner.clone(),
+        None => match detect(host, target) {
+            Some(runner) => runner,
+            None => return None,
+        },
+    };
+    // The sysroot of the target is also where QEMU finds its libraries.
+    Some(match runner {
+        TargetRunner::Preset(Preset::QemuUser { sysroot: None }) => {
+            TargetRunner::Preset(Preset::QemuUser { sysroot: config.sysroot.clone() })
+        }
+        runner => runner,
+    })
+}
+
+fn detect(host: &str, target: &str) -> Option<TargetRunner> {
+    let same_arch = arch(host) == arch(target) ||
+                    (arch(host) == "x86_64" && qemu_arch(target) == "i386");
+    if host == target || (same_arch && os(host) == os(target)) {
+        None
+    } else if os(target) == "linux" {
+        Some(TargetRunner::Preset(Preset::QemuUser { sysroot: None }))
+    } else if target.contains("-wasi") {
+        Some(TargetRunner::Preset(Preset::Wasmtime { args: Vec::new() }))
+    } else if target.contains("-none") || target == "wasm32-unknown-unknown" {
+        Some(TargetRunner::Unavailable(format!("\
+cannot run binaries built for `{0}` on `{1}`
+
+help: configure what runs them in `target.{0}.runner`, for example \
+QEMU with `{{ preset = \"qemu-system\", machine = \"...\" }}`", target, host)))
+    } else {
+        None
+    }
+}
+
+impl Preset {
+    /// The command running the binary `exe`, built for `triple`, with the
+    /// arguments `args`.
+    pub fn command(&self, triple: &str, exe: &Path, args: &[String])
+                   -> CargoResult<Runner> {
+        let exe = exe.display().to_string();
+        let mut runner = match *self {
+            Preset::QemuSystem(ref qemu) => return qemu_system(qemu, triple, exe, args),
+            _ => try!(self.runtool(triple)),
+        };
+        if runner.program.is_empty() {
+            runner.program = exe;
+        } else {
+            runner.args.push(exe);
+        }
+        runner.args.extend(args.iter().cloned());
+        Ok(runner)
+    }
+
+    /// The runner binaries built for `triple` are given to, followed by their
+    /// arguments, as rustdoc's `--runtool` does. Its program is empty if they
+    /// are run directly, through a binfmt_misc handler. The `qemu-system`
+    /// preset doesn't run binaries this way, and so has no such runner.
+    pub fn runtool(&self, triple: &str) -> CargoResult<Runner> {
+        let mut runner = Runner {
+            program: String::new(),
+            args: Vec::new(),
+            env: Vec::new(),
+            cwd: None,
+        };
+        match *self {
+            Preset::QemuUser { ref sysroot } => {
+                let arch = qemu_arch(triple);
+                let emulator = format!("qemu-{}", arch);
+                let sysroot = sysroot.clone().or_else(|| default_sysroot(triple));
+                if let Some(sysroot) = sysroot {
+                    runner.env.push(("QEMU_LD_PREFIX".to_string(),
+                                     sysroot.display().to_string()));
+                }
+                if find_program(&emulator) {
+                    runner.program = emulator;
+                } else if !binfmt_registered(arch) {
+                    bail!("cannot run binaries built for `{0}`: QEMU's emulator \
+                           `{1}` is not in PATH, and no binfmt_misc handler is \
+                           registered for it\n\n\
+                           help: install QEMU's user-mode emulators, e.g. the \
+                           `qemu-user` package, or configure what runs them in \
+                           `target.{0}.runner`", triple, emulator)
+                }
+            }
+            Preset::Wasmtime { ref args } => {
+                runner.program = "wasmtime".to_string();
+                if !find_program(&runner.program) {
+                    bail!("cannot run binaries built for `{}`: `wasmtime` is \
+                           not in PATH\n\n\
+                           help: install it from https://wasmtime.dev", triple)
+                }
+                runner.args.push("run".to_string());
+                runner.args.push("--dir=.".to_string());
+                runner.args.extend(args.iter().cloned());
+            }
+            Preset::QemuSystem(..) => {
+                bail!("the `qemu-system` runner of `{}` can only boot binaries \
+                       it's given, and so can't run doctests", triple)
+            }
+        }
+        Ok(runner)
+    }
+}
+
+fn qemu_system(qemu: &QemuSystem, triple: &str, exe: String, args: &[String])
+               -> CargoResult<Runner> {
+    let program = format!("qemu-system-{}", qemu_arch(triple));
+    if !find_program(&program) {
+        bail!("cannot run binaries built for `{}`: QEMU's emulator `{}` is not \
+               in PATH\n\n\
+               help: install QEMU's system emulators, e.g. the `qemu-system` \
+               package", triple, program)
+    }
+    let mut qemu_args = vec!["-nographic".to_string(),
+                             "-machine".to_string(), qemu.machine.clone()];
+    if let Some(ref cpu) = qemu.cpu {
+        qemu_args.push("-cpu".to_string());
+        qemu_args.push(cpu.clone());
+    }
+    if let Some(ref memory) = qemu.memory {
+        qemu_args.push("-m".to_string());
+        qemu_args.push(memory.clone());
+    }
+    match qemu.kernel {
+        Some(ref kernel) => {
+            let exe = Path::new(&exe);
+            let dir = exe.parent().unwrap_or(Path::new("."));
+            let name = exe.file_name().map(|s| s.to_string_lossy())
+                          .unwrap_or(String::new().into());
+            qemu_args.push("-kernel".to_string());
+            qemu_args.push(kernel.display().to_string());
+            if let Some(ref initrd) = qemu.initrd {
+                qemu_args.push("-initrd".to_string());
+                qemu_args.push(initrd.display().to_string());
+            }
+            if let Some(ref append) = qemu.append {
+                qemu_args.push("-append".to_string());
+                qemu_args.push(append.clone());
+            }
+            qemu_args.push("-virtfs".to_string());
+            qemu_args.push(format!("local,path={},mount_tag=cargo,security_model=none",
+                                   escape(&dir.display().to_string())));
+            qemu_args.push("-fw_cfg".to_string());
+            qemu_args.push(format!("name=opt/cargo/exe,string={}", escape(&name)));
+            if !args.is_empty() {
+                qemu_args.push("-fw_cfg".to_string());
+                qemu_args.push(format!("name=opt/cargo/args,string={}",
+                                       escape(&args.join("\n"))));
+            }
+        }
+        None => {
+            let mut semihosting = "enable=on,target=native".to_string();
+            for arg in Some(&exe).into_iter().chain(args) {
+                semihosting.push_str(",arg=");
+                semihosting.push_str(&escape(arg));
+            }
+            qemu_args.push("-semihosting-config".to_string());
+            qemu_args.push(semihosting);
+            qemu_args.push("-kernel".to_string());
+            qemu_args.push(exe);
+        }
+    }
+    qemu_args.extend(qemu.args.iter().cloned());
+    Ok(Runner { program: program, args: qemu_args, env: Vec::new(), cwd: None })
+}
+
+fn arch(triple: &str) -> &str {
+    triple.split('-').next().unwrap_or("")
+}
+
+fn os(triple: &str) -> &str {
+    triple.split('-').nth(2).unwrap_or("")
+}
+
+/// The name QEMU gives the architecture of `triple`.
+fn qemu_arch(triple: &str) -> &str {
+    match arch(triple) {
+        "i386" | "i586" | "i686" => "i386",
+        "powerpc" => "ppc",
+        "powerpc64" => "ppc64",
+        "powerpc64le" => "ppc64le",
+        a if a.starts_with("arm") || a.starts_with("thumb") => "arm",
+        a if a.starts_with("riscv32") => "riscv32",
+        a if a.starts_with("riscv64") => "riscv64",
+        a => a,
+    }
+}
+
+/// Where Debian and its derivatives install the libraries of `triple` for
+/// cross-compiling, e.g. `/usr/aarch64-linux-gnu`, if they're installed.
+fn default_sysroot(triple: &str) -> Option<PathBuf> {
+    let parts = triple.split('-').collect::<Vec<_>>();
+    if parts.len() != 4 {
+        return None
+    }
+    let arch = match qemu_arch(triple) {
+        "arm" => "arm",
+        "i386" => "i686",
+        "riscv64" => "riscv64",
+        _ => parts[0],
+    };
+    let dir = PathBuf::from(format!("/usr/{}-linux-{}", arch, parts[3]));
+    if dir.is_dir() { Some(dir) } else { None }
+}
+
+fn find_program(name: &str) -> bool {
+    let path = match env::var_os("PATH") {
+        Some(path) => path,
+        None => return false,
+    };
+    let name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+    env::split_paths(&path).any(|dir| dir.join(&name).is_file())
+}
+
+fn binfmt_registered(arch: &str) -> bool {
+    let handler = Path::new("/proc/sys/fs/binfmt_misc").join(format!("qemu-{}", arch));
+    paths::read(&handler).map(|contents| contents.starts_with("enabled"))
+                         .unwrap_or(false)
+}
+
+/// Escapes the commas of a value of a QEMU option.
+fn escape(s: &str) -> String {
+    s.replace(",", ",,")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{detect, Preset, TargetRunner};
+
+    const HOST: &'static str = "x86_64-unknown-linux-gnu";
+
+    fn detected(target: &str) -> &'static str {
+        match detect(HOST, target) {
+            None => "none",
+            Some(TargetRunner::Preset(Preset::QemuUser { .. })) => "qemu-user",
+            Some(TargetRunner::Preset(Preset::Wasmtime { .. })) => "wasmtime",
+            Some(TargetRunner::Unavailable(..)) => "unavailable",
+            Some(runner) => panic!("unexpected runner {:?}", runner),
+        }
+    }
+
+    #[test]
+    fn native_targets() {
+        assert_eq!(detected(HOST), "none");
+        assert_eq!(detected("x86_64-unknown-linux-musl"), "none");
+        assert_eq!(detected("i686-unknown-linux-gnu"), "none");
+        assert_eq!(detected("i586-unknown-linux-gnu"), "none");
+    }
+
+    #[test]
+    fn linux_targets() {
+        assert_eq!(detected("aarch64-unknown-linux-gnu"), "qemu-user");
+        assert_eq!(detected("armv7-unknown-linux-gnueabihf"), "qemu-user");
+        assert_eq!(detected("powerpc64le-unknown-linux-gnu"), "qemu-user");
+        assert!(detect("aarch64-unknown-linux-gnu", HOST).is_some());
+    }
+
+    #[test]
+    fn wasm_targets() {
+        assert_eq!(detected("wasm32-wasi"), "wasmtime");
+        assert_eq!(detected("wasm32-wasip1"), "wasmtime");
+        assert_eq!(detected("wasm32-unknown-unknown"), "unavailable");
+    }
+
+    #[test]
+    fn bare_metal_targets() {
+        assert_eq!(detected("thumbv7m-none-eabi"), "unavailable");
+        assert_eq!(detected("riscv32imac-unknown-none-elf"), "unavailable");
+        match detect(HOST, "thumbv7m-none-eabi") {
+            Some(TargetRunner::Unavailable(msg)) => {
+                assert!(msg.contains("`target.thumbv7m-none-eabi.runner`"), "{}", msg);
+            }
+            runner => panic!("unexpected runner {:?}", runner),
+        }
+    }
+
+    #[test]
+    fn other_targets_run_directly() {
+        assert_eq!(detected("x86_64-pc-windows-gnu"), "none");
+        assert_eq!(detected("x86_64-apple-darwin"), "none");
+    }
+}
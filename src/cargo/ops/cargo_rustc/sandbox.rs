@@ -0,0 +1,115 @@
+//! Support for running build scripts inside of a platform sandbox.
+//!
+//! When `build.sandbox` is enabled the execution of each build script is
+//! wrapped in a sandbox which blocks network access and only allows writes to
+//! the script's `OUT_DIR` (plus temporary directories). Packages listed in
+//! `build.sandbox-allow` are run without any restrictions.
+//!
+//! On Linux this is implemented with `bwrap` (bubblewrap) which sets up fresh
+//! user/network/mount namespaces, and on macOS with `sandbox-exec` and a
+//! generated profile. Other platforms are not supported.
+
+#[cfg(target_os = "linux")]
+use std::env;
+use std::path::Path;
+
+use util::{CargoResult, Config, ProcessBuilder};
+
+/// Configuration for sandboxing build scripts, loaded from `[build]`.
+#[derive(Clone, Default)]
+pub struct SandboxConfig {
+    /// Whether build scripts should be sandboxed at all.
+    pub enabled: bool,
+    /// Names of packages whose build scripts are exempt from the sandbox.
+    pub allow: Vec<String>,
+}
+
+impl SandboxConfig {
+    pub fn from_config(config: &Config) -> CargoResult<SandboxConfig> {
+        let enabled = try!(config.get_bool("build.sandbox"))
+                          .map(|v| v.val).unwrap_or(false);
+        let allow = match try!(config.get_list("build.sandbox-allow")) {
+            Some(list) => list.val.into_iter().map(|v| v.0).collect(),
+            None => Vec::new(),
+        };
+        Ok(SandboxConfig { enabled: enabled, allow: allow })
+    }
+
+    /// Returns whether the build script of the package `name` should be run
+    /// inside of a sandbox.
+    pub fn applies_to(&self, name: &str) -> bool {
+        self.enabled && !self.allow.iter().any(|p| p == name)
+    }
+}
+
+/// Wraps `cmd` so that it executes inside a sandbox which can only write to
+/// `out_dir`.
+pub fn wrap(cmd: &ProcessBuilder, out_dir: &Path) -> CargoResult<ProcessBuilder> {
+    let mut wrapped = try!(sandbox_process(out_dir));
+    wrapped.arg(cmd.get_program()).args(cmd.get_args());
+    if let Some(cwd) = cmd.get_cwd() {
+        wrapped.cwd(cwd);
+    }
+    for (k, v) in cmd.get_envs() {
+        match *v {
+            Some(ref v) => { wrapped.env(k, v); }
+            None => { wrapped.env_remove(k); }
+        }
+    }
+    Ok(wrapped)
+}
+
+#[cfg(target_os = "linux")]
+fn sandbox_process(out_dir: &Path) -> CargoResult<ProcessBuilder> {
+    if !find_program("bwrap") {
+        bail!("`build.sandbox` is enabled but `bwrap` could not be found in \
+               PATH, install bubblewrap or disable `build.sandbox`")
+    }
+    let mut p = ::util::process("bwrap");
+    p.arg("--unshare-net")
+     .arg("--die-with-parent")
+     .arg("--ro-bind").arg("/").arg("/")
+     .arg("--dev").arg("/dev")
+     .arg("--proc").arg("/proc")
+     .arg("--tmpfs").arg("/tmp")
+     .arg("--bind").arg(out_dir).arg(out_dir)
+     .arg("--");
+    Ok(p)
+}
+
+#[cfg(target_os = "linux")]
+fn find_program(name: &str) -> bool {
+    match env::var_os("PATH") {
+        Some(path) => env::split_paths(&path).any(|dir| dir.join(name).is_file()),
+        None => false,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn sandbox_process(out_dir: &Path) -> CargoResult<ProcessBuilder> {
+    let out_dir = match out_dir.to_str() {
+        Some(s) => s,
+        None => bail!("cannot sandbox a build script whose OUT_DIR is not \
+                       valid unicode: {}", out_dir.display()),
+    };
+    let profile = format!("\
+(version 1)
+(allow default)
+(deny network*)
+(deny file-write*)
+(allow file-write*
+    (subpath \"{}\")
+    (subpath \"/private/tmp\")
+    (subpath \"/private/var/folders\")
+    (literal \"/dev/null\"))
+", out_dir.replace("\\", "\\\\").replace("\"", "\\\""));
+    let mut p = ::util::process("sandbox-exec");
+    p.arg("-p").arg(profile);
+    Ok(p)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn sandbox_process(_out_dir: &Path) -> CargoResult<ProcessBuilder> {
+    bail!("sandboxing build scripts is not supported on this platform, \
+           consider disabling `build.sandbox`")
+}
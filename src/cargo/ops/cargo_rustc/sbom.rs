@@ -0,0 +1,343 @@
+//! Writing a software bill of materials (SBOM) of a build.
+//!
+//! With `--sbom`, every package which was built gets a document listing the
+//! packages compiled into it, derived from the graph of units rather than from
+//! the lockfile so that only what was actually built is listed. Each package
+//! is recorded with its exact version, checksum, enabled features, license and
+//! the triples it was compiled for, along with the toolchain used. The document
+//! is placed next to the artifacts, in the CycloneDX 1.4 or SPDX 2.3 JSON
+//! format.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rustc_serialize::json::{self, Json};
+
+use core::{Package, PackageId};
+use util::{paths, short_hash, CargoResult};
+use super::{Context, Kind, Unit};
+
+#[derive(Clone, Copy, PartialEq, Debug, RustcDecodable)]
+pub enum SbomFormat {
+    CycloneDx,
+    Spdx,
+}
+
+impl SbomFormat {
+    fn extension(&self) -> &'static str {
+        match *self {
+            SbomFormat::CycloneDx => "cdx.json",
+            SbomFormat::Spdx => "spdx.json",
+        }
+    }
+}
+
+/// A package compiled into the artifacts the document is about.
+struct Component<'a> {
+    pkg: &'a Package,
+    triples: BTreeSet<String>,
+    deps: BTreeSet<&'a PackageId>,
+}
+
+/// The toolchain the document was produced with.
+struct Toolchain {
+    cargo: String,
+    rustc: String,
+}
+
+/// Writes a document for each package of `units` in `format`.
+pub fn write<'a, 'cfg>(cx: &Context<'a, 'cfg>,
+                       units: &[Unit<'a>],
+                       format: SbomFormat) -> CargoResult<()> {
+    let cargo = ::version();
+    let toolchain = Toolchain {
+        cargo: cargo.split_whitespace().nth(1).unwrap_or("").to_string(),
+        rustc: try!(cx.config.rustc()).verbose_version.lines().next()
+                                      .unwrap_or("").to_string(),
+    };
+
+    let mut roots = BTreeMap::new();
+    for unit in units.iter().filter(|u| !u.profile.doc) {
+        roots.entry(unit.pkg.package_id()).or_insert(Vec::new()).push(*unit);
+    }
+    for (id, units) in roots {
+        let components = try!(collect(cx, &units));
+        let doc = match format {
+            SbomFormat::CycloneDx => cyclonedx(cx, id, &components, &toolchain),
+            SbomFormat::Spdx => spdx(cx, id, &components, &toolchain),
+        };
+        let dst = cx.layout(&units[0]).uplift_dir()
+                    .join(format!("{}.{}", id.name(), format.extension()));
+        let contents = format!("{}\n", json::as_pretty_json(&doc));
+        try!(paths::write(&dst, contents.as_bytes()));
+        try!(cx.config.shell().status("Generated",
+                                      format!("SBOM of {} at `{}`", id,
+                                              dst.display())));
+    }
+    Ok(())
+}
+
+/// Walks the units `roots` depend on, grouping them by package.
+fn collect<'a, 'cfg>(cx: &Context<'a, 'cfg>, roots: &[Unit<'a>])
+                     -> CargoResult<BTreeMap<&'a PackageId, Component<'a>>> {
+    let mut components = BTreeMap::new();
+    let mut visited = HashSet::new();
+    let mut queue = roots.to_vec();
+    while let Some(unit) = queue.pop() {
+        if !visited.insert(unit) {
+            continue
+        }
+        let id = unit.pkg.package_id();
+        let triple = match unit.kind {
+            Kind::Host => cx.host_triple(),
+            Kind::Target => cx.target_triple(),
+        };
+        let deps = try!(cx.dep_targets(&unit));
+
+        let component = components.entry(id).or_insert_with(|| {
+            Component {
+                pkg: unit.pkg,
+                triples: BTreeSet::new(),
+                deps: BTreeSet::new(),
+            }
+        });
+        component.triples.insert(triple.to_string());
+        for dep in deps {
+            if dep.pkg.package_id() != id {
+                component.deps.insert(dep.pkg.package_id());
+            }
+            queue.push(dep);
+        }
+    }
+    Ok(components)
+}
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn string<S: ToString>(s: S) -> Json {
+    Json::String(s.to_string())
+}
+
+fn reference(id: &PackageId) -> String {
+    format!("{} {} ({})", id.name(), id.version(), id.source_id().to_url())
+}
+
+/// The package URL of packages from registries.
+fn purl(id: &PackageId) -> Option<String> {
+    let source_id = id.source_id();
+    if !source_id.is_registry() {
+        None
+    } else if source_id.is_default_registry() {
+        Some(format!("pkg:cargo/{}@{}", id.name(), id.version()))
+    } else {
+        Some(format!("pkg:cargo/{}@{}?repository_url={}", id.name(),
+                     id.version(), source_id.url()))
+    }
+}
+
+fn checksum(cx: &Context, id: &PackageId) -> Option<String> {
+    cx.resolve.checksums().get(id).and_then(|c| c.clone())
+}
+
+fn features(cx: &Context, id: &PackageId) -> Vec<String> {
+    let mut features = cx.resolve.features(id).map(|f| {
+        f.iter().cloned().collect::<Vec<_>>()
+    }).unwrap_or(Vec::new());
+    features.sort();
+    features
+}
+
+fn cyclonedx(cx: &Context,
+             root: &PackageId,
+             components: &BTreeMap<&PackageId, Component>,
+             toolchain: &Toolchain) -> Json {
+    let component = |c: &Component| {
+        let id = c.pkg.package_id();
+        let kind = if c.pkg.targets().iter().any(|t| t.is_bin()) && id == root {
+            "application"
+        } else {
+            "library"
+        };
+        let mut fields = vec![
+            ("type", string(kind)),
+            ("bom-ref", string(reference(id))),
+            ("name", string(id.name())),
+            ("version", string(id.version())),
+        ];
+        if let Some(checksum) = checksum(cx, id) {
+            fields.push(("hashes", Json::Array(vec![object(vec![
+                ("alg", string("SHA-256")),
+                ("content", string(checksum)),
+            ])])));
+        }
+        if let Some(ref license) = c.pkg.manifest().metadata().license {
+            fields.push(("licenses", Json::Array(vec![object(vec![
+                ("expression", string(license)),
+            ])])));
+        }
+        if let Some(purl) = purl(id) {
+            fields.push(("purl", string(purl)));
+        }
+        let mut properties = vec![
+            object(vec![("name", string("cargo:source")),
+                        ("value", string(id.source_id().to_url()))]),
+        ];
+        for feature in features(cx, id) {
+            properties.push(object(vec![("name", string("cargo:feature")),
+                                        ("value", string(feature))]));
+        }
+        for triple in c.triples.iter() {
+            properties.push(object(vec![("name", string("cargo:target")),
+                                        ("value", string(triple))]));
+        }
+        fields.push(("properties", Json::Array(properties)));
+        object(fields)
+    };
+
+    let dependencies = components.values().map(|c| {
+        object(vec![
+            ("ref", string(reference(c.pkg.package_id()))),
+            ("dependsOn", Json::Array(c.deps.iter().map(|d| {
+                string(reference(d))
+            }).collect())),
+        ])
+    }).collect();
+
+    object(vec![
+        ("bomFormat", string("CycloneDX")),
+        ("specVersion", string("1.4")),
+        ("version", Json::U64(1)),
+        ("metadata", object(vec![
+            ("timestamp", string(created())),
+            ("tools", Json::Array(vec![object(vec![
+                ("name", string("cargo")),
+                ("version", string(&toolchain.cargo)),
+            ])])),
+            ("component", component(&components[root])),
+            ("properties", Json::Array(vec![object(vec![
+                ("name", string("cargo:rustc")),
+                ("value", string(&toolchain.rustc)),
+            ])])),
+        ])),
+        ("components", Json::Array(components.values().filter(|c| {
+            c.pkg.package_id() != root
+        }).map(|c| component(c)).collect())),
+        ("dependencies", Json::Array(dependencies)),
+    ])
+}
+
+fn spdx(cx: &Context,
+        root: &PackageId,
+        components: &BTreeMap<&PackageId, Component>,
+        toolchain: &Toolchain) -> Json {
+    // Identifiers may only contain letters, numbers, `.` and `-`, and are
+    // numbered as packages of different sources may share a name and version.
+    let ids = components.keys().enumerate().map(|(i, id)| {
+        let name = format!("{}-{}", id.name(), id.version()).chars().map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '-' }
+        }).collect::<String>();
+        (*id, format!("SPDXRef-Package-{}-{}", i, name))
+    }).collect::<BTreeMap<_, _>>();
+
+    let packages = components.values().map(|c| {
+        let id = c.pkg.package_id();
+        let mut fields = vec![
+            ("SPDXID", string(&ids[id])),
+            ("name", string(id.name())),
+            ("versionInfo", string(id.version())),
+            ("downloadLocation", string("NOASSERTION")),
+            ("filesAnalyzed", Json::Boolean(false)),
+            ("licenseConcluded", string("NOASSERTION")),
+            ("licenseDeclared",
+             string(c.pkg.manifest().metadata().license.as_ref()
+                     .map(|l| &l[..]).unwrap_or("NOASSERTION"))),
+            ("copyrightText", string("NOASSERTION")),
+        ];
+        if let Some(checksum) = checksum(cx, id) {
+            fields.push(("checksums", Json::Array(vec![object(vec![
+                ("algorithm", string("SHA256")),
+                ("checksumValue", string(checksum)),
+            ])])));
+        }
+        if let Some(purl) = purl(id) {
+            fields.push(("externalRefs", Json::Array(vec![object(vec![
+                ("referenceCategory", string("PACKAGE-MANAGER")),
+                ("referenceType", string("purl")),
+                ("referenceLocator", string(purl)),
+            ])])));
+        }
+        let features = features(cx, id);
+        let triples = c.triples.iter().cloned().collect::<Vec<_>>();
+        fields.push(("comment", string(format!(
+            "source: {}; features: {}; targets: {}",
+            id.source_id().to_url(),
+            if features.is_empty() { "none".to_string() } else { features.join(", ") },
+            triples.join(", ")))));
+        object(fields)
+    }).collect();
+
+    let mut relationships = vec![object(vec![
+        ("spdxElementId", string("SPDXRef-DOCUMENT")),
+        ("relationshipType", string("DESCRIBES")),
+        ("relatedSpdxElement", string(&ids[root])),
+    ])];
+    for c in components.values() {
+        for dep in c.deps.iter() {
+            relationships.push(object(vec![
+                ("spdxElementId", string(&ids[c.pkg.package_id()])),
+                ("relationshipType", string("DEPENDS_ON")),
+                ("relatedSpdxElement", string(&ids[*dep])),
+            ]));
+        }
+    }
+
+    let created = created();
+    let name = format!("{}-{}", root.name(), root.version());
+    object(vec![
+        ("spdxVersion", string("SPDX-2.3")),
+        ("dataLicense", string("CC0-1.0")),
+        ("SPDXID", string("SPDXRef-DOCUMENT")),
+        ("name", string(&name)),
+        ("documentNamespace",
+         string(format!("https://spdx.org/spdxdocs/{}-{}", name,
+                        short_hash(&(reference(root), &created))))),
+        ("creationInfo", object(vec![
+            ("created", string(&created)),
+            ("creators", Json::Array(vec![
+                string(format!("Tool: cargo-{}", toolchain.cargo)),
+            ])),
+            ("comment", string(format!("Compiled with {}", toolchain.rustc))),
+        ])),
+        ("packages", Json::Array(packages)),
+        ("relationships", Json::Array(relationships)),
+    ])
+}
+
+/// When the document was created, as an RFC 3339 timestamp in UTC. This is
+/// `SOURCE_DATE_EPOCH` if it's set, so that the document is reproducible.
+fn created() -> String {
+    let secs = env::var("SOURCE_DATE_EPOCH").ok()
+                   .and_then(|s| s.trim().parse().ok())
+                   .unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH)
+                         .map(|d| d.as_secs()).unwrap_or(0)
+    });
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // The inverse of the conversion of dates in the registry index, with
+    // years starting in March so that the leap day comes last.
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day,
+            rem / 3600, rem % 3600 / 60, rem % 60)
+}
@@ -0,0 +1,178 @@
+//! A cache of build script results, configured through `build.script-cache`.
+//!
+//! Build scripts which declare both their complete set of inputs
+//! (`tracked-input`) and outputs (`declares-output`) can have their results
+//! stored and later restored instead of running the script again. Entries are
+//! keyed by the package, the target, the compiled build script, the version of
+//! rustc, the relevant environment the script was run with and the contents of
+//! every tracked input.
+//!
+//! The cache is laid out as:
+//!
+//! ```notrust
+//! $cache/$pkg-$hash/inputs          # tracked inputs of the last stored run
+//! $cache/$pkg-$hash/$key/output     # stdout of the build script
+//! $cache/$pkg-$hash/$key/out/...    # declared outputs, relative to OUT_DIR
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::hex::ToHex;
+
+use core::PackageId;
+use util::{CargoResult, ChainError, Sha256, human, paths, short_hash};
+use super::BuildOutput;
+
+pub struct ScriptCache {
+    root: PathBuf,
+    rustc: String,
+}
+
+impl ScriptCache {
+    pub fn new(root: &Path, rustc: &str) -> ScriptCache {
+        ScriptCache { root: root.to_path_buf(), rustc: rustc.to_string() }
+    }
+
+    /// Attempts to restore a previously cached run of the build script
+    /// `script` into `out_dir`, returning the script's original stdout on a
+    /// hit.
+    pub fn restore(&self,
+                   id: &PackageId,
+                   target: &str,
+                   script: &Path,
+                   env: &[(String, String)],
+                   pkg_root: &Path,
+                   out_dir: &Path) -> CargoResult<Option<Vec<u8>>> {
+        let bucket = self.bucket(id, target);
+        let inputs = match paths::read(&bucket.join("inputs")) {
+            Ok(inputs) => inputs,
+            Err(..) => return Ok(None),
+        };
+        let inputs = inputs.lines().map(|s| s.to_string()).collect::<Vec<_>>();
+        let key = match try!(self.cache_key(script, env, pkg_root, &inputs)) {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let entry = bucket.join(&key);
+        let output = match paths::read_bytes(&entry.join("output")) {
+            Ok(output) => output,
+            Err(..) => return Ok(None),
+        };
+        try!(copy_dir(&entry.join("out"), out_dir).chain_error(|| {
+            human(format!("failed to restore cached build script output \
+                           from `{}`", entry.display()))
+        }));
+        Ok(Some(output))
+    }
+
+    /// Stores the results of a build script run in the cache.
+    ///
+    /// Nothing is stored if the script did not declare its inputs and outputs.
+    pub fn store(&self,
+                 id: &PackageId,
+                 target: &str,
+                 script: &Path,
+                 env: &[(String, String)],
+                 pkg_root: &Path,
+                 out_dir: &Path,
+                 stdout: &[u8],
+                 output: &BuildOutput) -> CargoResult<()> {
+        if !output.is_cacheable() {
+            return Ok(())
+        }
+        let inputs = &output.tracked_inputs;
+        let key = match try!(self.cache_key(script, env, pkg_root, inputs)) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let bucket = self.bucket(id, target);
+        let entry = bucket.join(&key);
+        let _ = fs::remove_dir_all(&entry);
+        try!(fs::create_dir_all(&entry));
+        for file in output.declared_outputs.iter() {
+            let src = out_dir.join(file);
+            let dst = entry.join("out").join(file);
+            try!(fs::create_dir_all(dst.parent().unwrap()));
+            try!(fs::copy(&src, &dst).chain_error(|| {
+                human(format!("failed to cache `{}`", src.display()))
+            }));
+        }
+        try!(paths::write(&entry.join("output"), stdout));
+        try!(paths::write(&bucket.join("inputs"),
+                          output.tracked_inputs.join("\n").as_bytes()));
+        Ok(())
+    }
+
+    fn bucket(&self, id: &PackageId, target: &str) -> PathBuf {
+        self.root.join(format!("{}-{}", id.name(), short_hash(&(id, target))))
+    }
+
+    fn cache_key(&self,
+                 script: &Path,
+                 env: &[(String, String)],
+                 pkg_root: &Path,
+                 inputs: &[String]) -> CargoResult<Option<String>> {
+        let mut env = env.iter().filter(|&&(ref k, _)| is_cache_relevant(k))
+                         .collect::<Vec<_>>();
+        env.sort();
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.rustc.as_bytes());
+        hasher.update(&[0]);
+        // A rebuilt script may well print something else, so the compiled
+        // script itself is part of the key.
+        let script = match paths::read_bytes(script) {
+            Ok(script) => script,
+            Err(..) => return Ok(None),
+        };
+        hasher.update(&script);
+        hasher.update(&[0]);
+        for &&(ref k, ref v) in env.iter() {
+            hasher.update(k.as_bytes());
+            hasher.update(&[0]);
+            hasher.update(v.as_bytes());
+            hasher.update(&[0]);
+        }
+        for input in inputs {
+            let contents = match paths::read_bytes(&pkg_root.join(input)) {
+                Ok(contents) => contents,
+                // An input that's gone missing can't be hashed, so there's no
+                // way to find a matching entry.
+                Err(..) => return Ok(None),
+            };
+            hasher.update(input.as_bytes());
+            hasher.update(&[0]);
+            hasher.update(&contents);
+        }
+        Ok(Some(hasher.finish().to_hex()))
+    }
+}
+
+/// Returns whether an environment variable passed to a build script should
+/// influence its cache key.
+///
+/// Variables which are absolute paths on the local machine (like `OUT_DIR`)
+/// are excluded so entries can be shared between checkouts.
+pub fn is_cache_relevant(var: &str) -> bool {
+    match var {
+        "TARGET" | "HOST" | "PROFILE" | "OPT_LEVEL" | "DEBUG" |
+        "CARGO_MANIFEST_LINKS" => true,
+        _ => var.starts_with("CARGO_FEATURE_") || var.starts_with("DEP_"),
+    }
+}
+
+fn copy_dir(src: &Path, dst: &Path) -> CargoResult<()> {
+    try!(fs::create_dir_all(dst));
+    for entry in try!(fs::read_dir(src)) {
+        let entry = try!(entry);
+        let path = entry.path();
+        let dst = dst.join(entry.file_name());
+        if try!(entry.file_type()).is_dir() {
+            try!(copy_dir(&path, &dst));
+        } else {
+            try!(fs::copy(&path, &dst));
+        }
+    }
+    Ok(())
+}
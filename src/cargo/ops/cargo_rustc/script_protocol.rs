@@ -0,0 +1,213 @@
+//! Version 2 of the protocol build scripts use to talk to Cargo.
+//!
+//! Cargo advertises the newest protocol it understands to build scripts
+//! through the `CARGO_BUILD_SCRIPT_PROTOCOL` environment variable. A script
+//! opts in to version 2 by printing `cargo:protocol=2`, after which any line
+//! of its stdout starting with `{` is interpreted as a JSON message. The
+//! `kind` field of each message is one of:
+//!
+//! * `diagnostic` - a warning or error with optional file/line spans and notes
+//! * `progress` - a free-form progress message
+//! * `metadata` - a `key`/`value` pair passed to dependents, like `cargo:k=v`
+//! * `directive` - any other `key`/`value` directive, like `cargo:key=value`
+//!
+//! The classic `cargo:key=value` lines continue to work alongside messages.
+
+use std::fmt;
+
+use rustc_serialize::{json, Encodable, Encoder};
+
+use core::PackageId;
+use util::{CargoResult, ChainError, human};
+use util::machine_message::Message;
+
+/// The newest version of the protocol understood by this Cargo.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// A structured diagnostic emitted by a build script.
+#[derive(Clone, Debug, Hash, RustcEncodable)]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    pub message: String,
+    pub spans: Vec<DiagnosticSpan>,
+    pub notes: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq)]
+pub enum DiagnosticLevel {
+    Warning,
+    Error,
+}
+
+impl Encodable for DiagnosticLevel {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        match *self {
+            DiagnosticLevel::Warning => "warning",
+            DiagnosticLevel::Error => "error",
+        }.encode(s)
+    }
+}
+
+#[derive(Clone, Debug, Hash, RustcEncodable, RustcDecodable)]
+pub struct DiagnosticSpan {
+    pub file: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub label: Option<String>,
+}
+
+/// A parsed protocol message.
+pub enum ScriptMessage {
+    Diagnostic(Diagnostic),
+    Progress(String),
+    Metadata(String, String),
+    Directive(String, String),
+}
+
+#[derive(RustcDecodable)]
+struct RawMessage {
+    kind: String,
+    level: Option<String>,
+    message: Option<String>,
+    spans: Option<Vec<DiagnosticSpan>>,
+    notes: Option<Vec<String>>,
+    key: Option<String>,
+    value: Option<String>,
+}
+
+/// Returns whether `line` should be parsed as a message when the script is
+/// speaking protocol `version`.
+pub fn is_message(line: &str, version: u32) -> bool {
+    version >= 2 && line.starts_with('{')
+}
+
+/// Parses the protocol version requested by a `cargo:protocol=N` directive.
+pub fn parse_version(value: &str, whence: &str) -> CargoResult<u32> {
+    let version = try!(value.parse::<u32>().chain_error(|| {
+        human(format!("invalid protocol version in {}: `{}`", whence, value))
+    }));
+    if version == 0 || version > PROTOCOL_VERSION {
+        bail!("{} requested protocol version {}, but this version of Cargo \
+               only supports up to version {}", whence, version,
+              PROTOCOL_VERSION)
+    }
+    Ok(version)
+}
+
+/// Follows the stdout of a running build script for `progress` messages, so
+/// they can be shown while the script is still running.
+///
+/// `protocol` tracks the version the script selected so far. Malformed lines
+/// are ignored here, they're reported once the script has finished.
+pub fn progress_message(line: &str, protocol: &mut u32) -> Option<String> {
+    let line = line.trim();
+    for prefix in ["cargo:protocol=", "cargo::protocol="].iter() {
+        if line.starts_with(prefix) {
+            *protocol = line[prefix.len()..].parse().unwrap_or(*protocol);
+            return None
+        }
+    }
+    if !is_message(line, *protocol) {
+        return None
+    }
+    match parse_message(line, "") {
+        Ok(ScriptMessage::Progress(message)) => Some(message),
+        _ => None,
+    }
+}
+
+/// Parses a single JSON message line.
+pub fn parse_message(line: &str, whence: &str) -> CargoResult<ScriptMessage> {
+    let raw: RawMessage = try!(json::decode(line).chain_error(|| {
+        human(format!("malformed message in {}: `{}`", whence, line))
+    }));
+    let missing = |field: &str| {
+        human(format!("message of kind `{}` in {} is missing the `{}` field",
+                      raw.kind, whence, field))
+    };
+    let msg = match &raw.kind[..] {
+        "diagnostic" => {
+            let level = match raw.level.as_ref().map(|s| &s[..]) {
+                Some("warning") | None => DiagnosticLevel::Warning,
+                Some("error") => DiagnosticLevel::Error,
+                Some(other) => bail!("unknown diagnostic level `{}` in {}",
+                                     other, whence),
+            };
+            ScriptMessage::Diagnostic(Diagnostic {
+                level: level,
+                message: try!(raw.message.clone().ok_or_else(|| {
+                    missing("message")
+                })),
+                spans: raw.spans.clone().unwrap_or(Vec::new()),
+                notes: raw.notes.clone().unwrap_or(Vec::new()),
+            })
+        }
+        "progress" => {
+            let message = try!(raw.message.clone().ok_or_else(|| {
+                missing("message")
+            }));
+            ScriptMessage::Progress(message)
+        }
+        "metadata" | "directive" => {
+            let key = try!(raw.key.clone().ok_or_else(|| missing("key")));
+            let value = try!(raw.value.clone().ok_or_else(|| missing("value")));
+            if raw.kind == "metadata" {
+                ScriptMessage::Metadata(key, value)
+            } else {
+                ScriptMessage::Directive(key, value)
+            }
+        }
+        other => bail!("unknown message kind `{}` in {}", other, whence),
+    };
+    Ok(msg)
+}
+
+impl fmt::Display for Diagnostic {
+    /// Renders the diagnostic similarly to rustc, minus the leading level
+    /// which is printed by the shell.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", self.message));
+        for span in self.spans.iter() {
+            try!(write!(f, "\n  --> {}", span.file));
+            if let Some(line) = span.line {
+                try!(write!(f, ":{}", line));
+                if let Some(column) = span.column {
+                    try!(write!(f, ":{}", column));
+                }
+            }
+            if let Some(ref label) = span.label {
+                try!(write!(f, "\n   | {}", label));
+            }
+        }
+        for note in self.notes.iter() {
+            try!(write!(f, "\n   = note: {}", note));
+        }
+        Ok(())
+    }
+}
+
+/// A diagnostic forwarded from a build script in `--message-format=json`.
+#[derive(RustcEncodable)]
+pub struct BuildScriptMessage<'a> {
+    pub package_id: &'a PackageId,
+    pub message: &'a Diagnostic,
+}
+
+impl<'a> Message for BuildScriptMessage<'a> {
+    fn reason(&self) -> &str {
+        "build-script-message"
+    }
+}
+
+/// A progress message of a running build script in `--message-format=json`.
+#[derive(RustcEncodable)]
+pub struct BuildScriptProgress<'a> {
+    pub package_id: &'a PackageId,
+    pub message: &'a str,
+}
+
+impl<'a> Message for BuildScriptProgress<'a> {
+    fn reason(&self) -> &str {
+        "build-script-progress"
+    }
+}
@@ -0,0 +1,78 @@
+//! Collecting and summarizing compiler self-profiles.
+//!
+//! Units selected for profiling are compiled with `-Z time-passes`, and the
+//! timings printed by rustc are kept in the `self-profile` directory of the
+//! layout, one file per unit. Once the build has finished all of them are
+//! merged into a single summary listing the slowest passes of each crate.
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use util::{paths, CargoResult};
+use super::{Context, Unit};
+
+/// How many of the slowest passes of each crate are listed in the summary.
+const TOP_PASSES: usize = 10;
+
+/// The file the profile of `unit` is stored in.
+pub fn profile_path(cx: &Context, unit: &Unit) -> PathBuf {
+    cx.layout(unit).proxy().self_profile()
+      .join(format!("{}.txt", cx.file_stem(unit)))
+}
+
+/// Removes the profile of `unit` kept from a previous build, if any.
+pub fn clear(cx: &Context, unit: &Unit) -> CargoResult<()> {
+    let path = profile_path(cx, unit);
+    if path.exists() {
+        try!(fs::remove_file(&path));
+    }
+    Ok(())
+}
+
+/// Writes a summary of the profiles of `units` to `summary.txt` in `dst`.
+///
+/// Only units which were compiled by this build have a profile, fresh ones
+/// are expected to have been left out by the caller.
+pub fn summarize(cx: &Context, units: &[Unit], dst: &Path) -> CargoResult<()> {
+    let mut summary = String::new();
+    for unit in units {
+        let contents = try!(paths::read(&profile_path(cx, unit)));
+        let mut passes = parse(&contents);
+        passes.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+        writeln!(summary, "{} ({})", unit.pkg, unit.target.name()).unwrap();
+        for &(time, ref pass) in passes.iter().take(TOP_PASSES) {
+            writeln!(summary, "  {:>9.3}s  {}", time, pass).unwrap();
+        }
+        summary.push('\n');
+    }
+
+    try!(fs::create_dir_all(dst));
+    let file = dst.join("summary.txt");
+    try!(paths::write(&file, summary.as_bytes()));
+    try!(cx.config.shell().status("Profiled",
+                                  format!("{} units, summary written to `{}`",
+                                          units.len(), file.display())));
+    Ok(())
+}
+
+/// Parses the output of `-Z time-passes`, in which each line looks like
+/// `time: 0.012; rss: 40MB\tparsing`, nested passes being indented.
+fn parse(contents: &str) -> Vec<(f64, String)> {
+    contents.lines().filter_map(|line| {
+        let line = line.trim_left();
+        if !line.starts_with("time:") {
+            return None
+        }
+        let rest = line["time:".len()..].trim_left();
+        let end = rest.find(|c: char| c == ';' || c.is_whitespace())
+                      .unwrap_or(rest.len());
+        let time = match rest[..end].parse() {
+            Ok(time) => time,
+            Err(..) => return None,
+        };
+        rest.find('\t').map(|i| (time, rest[i + 1..].trim().to_string()))
+    }).collect()
+}
@@ -0,0 +1,138 @@
+//! Locating the native libraries declared in `[system-dependencies]`.
+//!
+//! Each system dependency is looked up with `pkg-config` once for every kind
+//! (host or target) its package is built for, and the resulting `-L` and `-l`
+//! flags are handed to rustc exactly as if a build script had printed them.
+//!
+//! Libraries can be overridden per target in the same way as `links` keys,
+//! with a `[target.$triple.$name]` table in the configuration. When
+//! cross-compiling, `target.$triple.sysroot` must point at the sysroot to
+//! search since the host's libraries are of no use.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use core::{LinkPreference, Package, SystemDependency};
+use util::{self, CargoResult, ChainError, human};
+
+use super::{BuildOutput, Context, Kind};
+
+/// Probes the system dependencies of every package which is going to be
+/// linked as part of this build.
+pub fn probe_all(cx: &Context) -> CargoResult<()> {
+    let mut todo = HashSet::new();
+    for scripts in cx.build_scripts.values() {
+        todo.extend(scripts.to_link.iter().cloned());
+    }
+    for (id, kind) in todo {
+        let pkg = try!(cx.get_package(&id));
+        if !pkg.has_system_dependencies() {
+            continue
+        }
+        let output = try!(probe(cx, pkg, kind).chain_error(|| {
+            human(format!("failed to locate the system dependencies of `{}`",
+                          pkg))
+        }));
+        let has_scripts = !cx.active_build_scripts(pkg, kind).is_empty();
+        cx.build_state.insert_system(&id, kind, has_scripts, output);
+    }
+    Ok(())
+}
+
+fn probe(cx: &Context, pkg: &Package, kind: Kind) -> CargoResult<BuildOutput> {
+    let mut output = BuildOutput::default();
+    let config = cx.target_config(kind);
+    let triple = match kind {
+        Kind::Host => cx.host_triple(),
+        Kind::Target => cx.target_triple(),
+    };
+
+    for dep in pkg.manifest().system_dependencies() {
+        if let Some(o) = config.overrides.get(&dep.name) {
+            output.library_paths.extend(o.library_paths.iter().cloned());
+            output.library_links.extend(o.library_links.iter().cloned());
+            continue
+        }
+
+        let cross = triple != cx.host_triple();
+        if cross && config.sysroot.is_none() {
+            bail!("cannot locate system dependency `{}` when cross compiling \
+                   to `{}`\n\nset `target.{}.sysroot` to the sysroot to \
+                   search, or override it with `[target.{}.{}]`",
+                  dep.name, triple, triple, triple, dep.name)
+        }
+        let pkg_config = config.pkg_config.clone()
+                               .unwrap_or(PathBuf::from("pkg-config"));
+        let flags = try!(pkg_config_libs(&pkg_config,
+                                         config.sysroot.as_ref().map(|p| &**p),
+                                         dep).chain_error(|| {
+            let version = match dep.version {
+                Some(ref v) => format!(" (version >= {})", v),
+                None => String::new(),
+            };
+            human(format!("could not find system library `{}`{} for `{}` \
+                           with `{}`\n\nto use a library which isn't known \
+                           to pkg-config, override it with `[target.{}.{}]`",
+                          dep.pkg_config_name, version, triple,
+                          pkg_config.display(), triple, dep.name))
+        }));
+        parse_libs(&flags, dep, &mut output);
+    }
+    Ok(output)
+}
+
+fn pkg_config_libs(pkg_config: &Path,
+                   sysroot: Option<&Path>,
+                   dep: &SystemDependency) -> CargoResult<String> {
+    let mut cmd = util::process(pkg_config);
+    if let Some(sysroot) = sysroot {
+        let dirs = [sysroot.join("usr/lib/pkgconfig"),
+                    sysroot.join("usr/share/pkgconfig")];
+        cmd.env("PKG_CONFIG_SYSROOT_DIR", sysroot)
+           .env("PKG_CONFIG_LIBDIR",
+                &try!(util::join_paths(&dirs, "PKG_CONFIG_LIBDIR")));
+    }
+    if let Some(ref version) = dep.version {
+        try!(cmd.clone().arg(format!("--atleast-version={}", version))
+                .arg(&dep.pkg_config_name).exec_with_output());
+    }
+    cmd.arg("--libs");
+    if dep.link == Some(LinkPreference::Static) {
+        cmd.arg("--static");
+    }
+    let output = try!(cmd.arg(&dep.pkg_config_name).exec_with_output());
+    match String::from_utf8(output.stdout) {
+        Ok(s) => Ok(s),
+        Err(..) => bail!("pkg-config printed output which was not utf-8"),
+    }
+}
+
+fn parse_libs(flags: &str, dep: &SystemDependency, output: &mut BuildOutput) {
+    let paths = flags.split_whitespace().filter(|f| f.starts_with("-L"))
+                     .map(|f| PathBuf::from(&f[2..]))
+                     .collect::<Vec<_>>();
+    for flag in flags.split_whitespace() {
+        if !flag.starts_with("-l") {
+            continue
+        }
+        let name = &flag[2..];
+        // `--static` also lists libraries like `m` and `pthread` which only
+        // exist as shared libraries, so only ask for static linking when a
+        // static archive was actually found.
+        let kind = match dep.link {
+            Some(LinkPreference::Static) => {
+                let archive = format!("lib{}.a", name);
+                if paths.iter().any(|p| fs::metadata(p.join(&archive)).is_ok()) {
+                    "static="
+                } else {
+                    ""
+                }
+            }
+            Some(LinkPreference::Dynamic) => "dylib=",
+            None => "",
+        };
+        output.library_links.push(format!("{}{}", kind, name));
+    }
+    output.library_paths.extend(paths);
+}
@@ -0,0 +1,87 @@
+//! Noticing when the compiler used for a build directory changes.
+//!
+//! The verbose version of rustc is recorded in `.rustc-version` at the root of
+//! each layout. Every fingerprint includes the compiler version, so once it
+//! changes all units are rebuilt, which is pointed out before the build
+//! starts. With `--preserve-old-artifacts` the artifacts of the previous
+//! compiler are set aside in the `toolchains` directory of the layout rather
+//! than overwritten, and brought back when that compiler is used again.
+
+use std::fs;
+
+use util::{paths, short_hash, CargoResult, ChainError, human};
+use super::Context;
+
+/// A change of the compiler since the last build.
+pub struct Rollover {
+    previous: String,
+    current: String,
+    /// Where the artifacts of the previous compiler were moved to, if they
+    /// were preserved.
+    preserved: Option<String>,
+    /// Whether artifacts of the current compiler were brought back.
+    restored: bool,
+}
+
+/// Compares the compiler with the one which last built in each layout,
+/// swapping out the artifacts of the previous compiler if requested, and
+/// records the current one.
+pub fn rollover(cx: &Context) -> CargoResult<Option<Rollover>> {
+    let current = try!(cx.config.rustc()).verbose_version.clone();
+    let mut ret = None;
+    for layout in cx.layouts() {
+        let file = layout.rustc_version();
+        if fs::metadata(&file).is_ok() {
+            let previous = try!(paths::read(&file));
+            if previous != current {
+                let mut rollover = Rollover {
+                    previous: previous,
+                    current: current.clone(),
+                    preserved: None,
+                    restored: false,
+                };
+                if cx.build_config.preserve_old_artifacts {
+                    let old = short_hash(&rollover.previous);
+                    let new = short_hash(&current);
+                    rollover.restored = try!(layout.swap_toolchain(&old, &new)
+                                                   .chain_error(|| {
+                        human(format!("failed to set aside the artifacts in \
+                                       `{}`", layout.dest().display()))
+                    }));
+                    rollover.preserved = Some(old);
+                }
+                ret = Some(rollover);
+            }
+        }
+        try!(paths::write(&file, current.as_bytes()));
+    }
+    Ok(ret)
+}
+
+/// Warns about the compiler having changed, along with how much will be
+/// rebuilt because of it.
+pub fn notify(cx: &Context, rollover: &Rollover) -> CargoResult<()> {
+    let first_line = |s: &str| s.lines().next().unwrap_or("").to_string();
+    let mut msg = format!("the compiler changed since the last build\n  \
+                           previous: {}\n  current:  {}\n",
+                          first_line(&rollover.previous),
+                          first_line(&rollover.current));
+    if rollover.restored {
+        msg.push_str("artifacts previously built with the current compiler \
+                      were restored");
+    } else {
+        msg.push_str(&format!("all {} units will be rebuilt",
+                              cx.compiled.len()));
+    }
+    match rollover.preserved {
+        Some(ref hash) => {
+            msg.push_str(&format!(", those of the previous compiler were \
+                                   moved to `toolchains/{}`", hash));
+        }
+        None => {
+            msg.push_str(", pass --preserve-old-artifacts to keep those of \
+                          the previous compiler");
+        }
+    }
+    cx.config.shell().warn(msg)
+}
@@ -0,0 +1,174 @@
+//! Single-file packages: a `.rs` file which is a package on its own, with its
+//! manifest, if it needs one, embedded in its leading doc comment:
+//!
+//! ```text
+//! //! ```cargo
+//! //! [dependencies]
+//! //! time = "0.1"
+//! //! ```
+//!
+//! fn main() {}
+//! ```
+//!
+//! A shebang line may come before the doc comment. The package Cargo actually
+//! builds is generated under `$CARGO_HOME/scripts`, one per script, and holds
+//! the lockfile and the target directory of the script, so they're kept from
+//! one run to the next.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use util::important_paths::find_root_manifest_for_wd;
+use util::toml as cargo_toml;
+use util::{CargoResult, ChainError, Config, human, paths, short_hash};
+
+/// Whether the manifest path `path` is a single-file package rather than a
+/// `Cargo.toml`.
+pub fn is_script(path: &str) -> bool {
+    path.ends_with(".rs")
+}
+
+/// Like `find_root_manifest_for_wd`, but `manifest_path` may be the path of a
+/// single-file package as well, in which case the manifest generated for it
+/// is returned.
+pub fn find_script_or_root_manifest(manifest_path: Option<String>,
+                                    config: &Config) -> CargoResult<PathBuf> {
+    match manifest_path {
+        Some(ref path) if is_script(path) => {
+            script_manifest(&config.cwd().join(path), config)
+        }
+        manifest_path => find_root_manifest_for_wd(manifest_path, config.cwd()),
+    }
+}
+
+/// Generates the package of the single-file package at `script`, returning
+/// the path of its manifest.
+pub fn script_manifest(script: &Path, config: &Config) -> CargoResult<PathBuf> {
+    let script = try!(fs::canonicalize(script).chain_error(|| {
+        human(format!("script `{}` does not exist", script.display()))
+    }));
+    let contents = try!(paths::read(&script));
+    let name = try!(script.file_stem().and_then(|s| s.to_str()).chain_error(|| {
+        human(format!("cannot run a script with a non-unicode name: `{}`",
+                      script.display()))
+    }));
+    let name = name.chars().map(|c| {
+        if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }
+    }).collect::<String>();
+
+    let mut root = match embedded_manifest(&contents) {
+        Some(manifest) => try!(cargo_toml::parse(&manifest, &script, config)),
+        None => BTreeMap::new(),
+    };
+    for key in ["lib", "bin", "example", "test", "bench"].iter() {
+        if root.contains_key(*key) {
+            bail!("the manifest of script `{}` can't declare targets, the \
+                   script is its only binary", script.display())
+        }
+    }
+    {
+        let package = root.entry("package".to_string()).or_insert_with(|| {
+            toml::Value::Table(BTreeMap::new())
+        });
+        let package = match *package {
+            toml::Value::Table(ref mut package) => package,
+            _ => bail!("`package` in the manifest of script `{}` must be a table",
+                       script.display()),
+        };
+        package.entry("name".to_string())
+               .or_insert_with(|| toml::Value::String(name.clone()));
+        package.entry("version".to_string())
+               .or_insert_with(|| toml::Value::String("0.0.0".to_string()));
+        package.entry("authors".to_string())
+               .or_insert_with(|| toml::Value::Array(Vec::new()));
+    }
+    // The manifest is generated elsewhere, so relative paths of dependencies
+    // are made relative to the script's directory.
+    let script_dir = script.parent().unwrap();
+    for (key, value) in root.iter_mut() {
+        match &key[..] {
+            "target" => {
+                if let toml::Value::Table(ref mut targets) = *value {
+                    for target in targets.values_mut() {
+                        if let toml::Value::Table(ref mut target) = *target {
+                            absolutize_paths(target, script_dir);
+                        }
+                    }
+                }
+            }
+            "replace" => {
+                if let toml::Value::Table(ref mut replace) = *value {
+                    absolutize_dep_paths(replace, script_dir);
+                }
+            }
+            _ => {}
+        }
+    }
+    absolutize_paths(&mut root, script_dir);
+
+    let mut bin = BTreeMap::new();
+    bin.insert("name".to_string(), toml::Value::String(name.clone()));
+    bin.insert("path".to_string(),
+               toml::Value::String(script.display().to_string()));
+    root.insert("bin".to_string(), toml::Value::Array(vec![toml::Value::Table(bin)]));
+    // The package is a workspace of its own, whatever is above it.
+    root.entry("workspace".to_string())
+        .or_insert_with(|| toml::Value::Table(BTreeMap::new()));
+
+    let dir = config.home().join("scripts")
+                    .join(format!("{}-{}", name, short_hash(&script)))
+                    .into_path_unlocked();
+    try!(fs::create_dir_all(&dir));
+    let manifest = dir.join("Cargo.toml");
+    let new_contents = toml::Value::Table(root).to_string();
+    let old_contents = paths::read(&manifest).unwrap_or(String::new());
+    if old_contents != new_contents {
+        try!(paths::write(&manifest, new_contents.as_bytes()));
+    }
+    Ok(manifest)
+}
+
+/// Makes the relative `path`s of the dependencies in the dependency tables of
+/// `table` relative to `dir`.
+fn absolutize_paths(table: &mut toml::Table, dir: &Path) {
+    for key in ["dependencies", "dev-dependencies", "dev_dependencies",
+                "build-dependencies", "build_dependencies"].iter() {
+        if let Some(&mut toml::Value::Table(ref mut deps)) = table.get_mut(*key) {
+            absolutize_dep_paths(deps, dir);
+        }
+    }
+}
+
+fn absolutize_dep_paths(deps: &mut toml::Table, dir: &Path) {
+    for dep in deps.values_mut() {
+        if let toml::Value::Table(ref mut dep) = *dep {
+            if let Some(&mut toml::Value::String(ref mut path)) = dep.get_mut("path") {
+                *path = dir.join(&path[..]).display().to_string();
+            }
+        }
+    }
+}
+
+/// The manifest in the ```` ```cargo ```` block of the doc comment which
+/// starts `contents`, after a shebang line if there's one.
+fn embedded_manifest(contents: &str) -> Option<String> {
+    let mut lines = contents.lines().map(|line| line.trim_left()).skip_while(|line| {
+        (line.starts_with("#!") && !line.starts_with("#![")) ||
+            line.trim().is_empty()
+    }).take_while(|line| line.starts_with("//!")).map(|line| {
+        let line = &line[3..];
+        if line.starts_with(' ') { &line[1..] } else { line }
+    });
+    if !lines.any(|line| line.trim() == "```cargo") {
+        return None
+    }
+    let mut manifest = String::new();
+    for line in lines.take_while(|line| line.trim() != "```") {
+        manifest.push_str(line);
+        manifest.push('\n');
+    }
+    Some(manifest)
+}
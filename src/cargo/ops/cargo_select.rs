@@ -0,0 +1,217 @@
+//! Choosing the packages to build from patterns rather than explicit lists.
+//!
+//! `-p` accepts glob patterns matched against the names of the members of the
+//! workspace, and patterns prefixed with `!` exclude the members they match,
+//! as do those passed to `--exclude`. `--select` keeps only the members whose
+//! manifest passes a test such as `package.metadata.ci.tier == 1`.
+//!
+//! Without `-p`, the root of a workspace can name the members each command
+//! builds by default:
+//!
+//! ```toml
+//! [workspace.default-members]
+//! build = ["core", "util-*"]
+//! test = ["*"]
+//! ```
+
+use std::path::Path;
+
+use glob::Pattern;
+use toml;
+
+use core::{Package, Workspace};
+use util::{paths, CargoResult, ChainError, Config, human};
+use util::toml as cargo_toml;
+
+/// Expands the package patterns, exclusions and selections passed to a
+/// command into the names of the packages to build. Without packages, those
+/// in `workspace.default-members` for `command` are used.
+///
+/// Specs which aren't patterns are passed through as they are when nothing
+/// else asks for the members of the workspace, so they don't have to all be
+/// loaded.
+pub fn select_specs(manifest_path: &Path,
+                    config: &Config,
+                    command: &str,
+                    packages: &[String],
+                    exclude: &[String],
+                    select: &[String]) -> CargoResult<Vec<String>> {
+    let defaults;
+    let packages = if packages.is_empty() {
+        defaults = try!(default_members(manifest_path, config, command));
+        &defaults[..]
+    } else {
+        packages
+    };
+    let (excluded, included): (Vec<&String>, Vec<&String>) =
+        packages.iter().partition(|p| p.starts_with('!'));
+    if excluded.is_empty() && exclude.is_empty() && select.is_empty() &&
+       !included.iter().any(|p| is_pattern(p)) {
+        return Ok(packages.to_vec())
+    }
+
+    let mut excluded = excluded.into_iter().map(|p| &p[1..]).collect::<Vec<_>>();
+    excluded.extend(exclude.iter().map(|p| &p[..]));
+    let excluded = try!(excluded.iter().map(|p| pattern(p))
+                                .collect::<CargoResult<Vec<_>>>());
+    let selectors = try!(select.iter().map(|s| Selector::parse(s))
+                               .collect::<CargoResult<Vec<_>>>());
+
+    let ws = try!(Workspace::new(manifest_path, config));
+    let mut specs = Vec::new();
+    let mut members = Vec::new();
+    if included.is_empty() {
+        members.extend(ws.members());
+    }
+    for spec in included {
+        if is_pattern(spec) {
+            let pattern = try!(pattern(spec));
+            let matched = ws.members().filter(|m| pattern.matches(m.name()))
+                                      .collect::<Vec<_>>();
+            if matched.is_empty() {
+                bail!("no members of the workspace match `{}`", spec)
+            }
+            members.extend(matched);
+        } else {
+            match ws.members().find(|m| m.name() == &spec[..]) {
+                Some(member) => members.push(member),
+                // Not a member, so it's left for the build to resolve.
+                None => specs.push(spec.clone()),
+            }
+        }
+    }
+
+    for member in members {
+        if excluded.iter().any(|p| p.matches(member.name())) {
+            continue
+        }
+        let mut selected = true;
+        for selector in selectors.iter() {
+            selected = selected && try!(selector.matches(member, config));
+        }
+        if selected && !specs.iter().any(|s| s == member.name()) {
+            specs.push(member.name().to_string());
+        }
+    }
+    if specs.is_empty() {
+        bail!("no packages are left to build once the exclusions and \
+               selections are applied")
+    }
+    specs.sort();
+    Ok(specs)
+}
+
+/// The packages `command` builds by default according to the manifest at
+/// `manifest_path`, which only the root of a workspace may declare.
+fn default_members(manifest_path: &Path,
+                   config: &Config,
+                   command: &str) -> CargoResult<Vec<String>> {
+    let contents = try!(paths::read(manifest_path));
+    let manifest = toml::Value::Table(try!(cargo_toml::parse(&contents,
+                                                            manifest_path,
+                                                            config)));
+    let table = match manifest.lookup("workspace.default-members") {
+        Some(&toml::Value::Table(ref table)) => table,
+        Some(..) => bail!("`workspace.default-members` of `{}` must be a table",
+                          manifest_path.display()),
+        None => return Ok(Vec::new()),
+    };
+    let members = match table.get(command) {
+        Some(members) => members,
+        None => return Ok(Vec::new()),
+    };
+    let members = try!(members.as_slice().and_then(|array| {
+        array.iter().map(|v| v.as_str().map(|s| s.to_string()))
+             .collect::<Option<Vec<_>>>()
+    }).chain_error(|| {
+        human(format!("`workspace.default-members.{}` of `{}` must be an \
+                       array of strings", command, manifest_path.display()))
+    }));
+    if members.is_empty() {
+        bail!("`workspace.default-members.{}` of `{}` can't be empty",
+              command, manifest_path.display())
+    }
+    Ok(members)
+}
+
+fn is_pattern(spec: &str) -> bool {
+    spec.contains(|c: char| c == '*' || c == '?' || c == '[')
+}
+
+fn pattern(spec: &str) -> CargoResult<Pattern> {
+    Pattern::new(spec).map_err(|e| {
+        human(format!("invalid package pattern `{}`: {}", spec, e))
+    })
+}
+
+/// A test of one value of the manifest of a package.
+struct Selector {
+    /// The dotted path of the key, e.g. `package.metadata.ci.tier`.
+    key: String,
+    /// The value the key must have, or must not have. Without one the key
+    /// must merely be present and not `false`.
+    test: Option<(bool, toml::Value)>,
+}
+
+impl Selector {
+    fn parse(s: &str) -> CargoResult<Selector> {
+        let (key, test) = match s.find("==").or(s.find("!=")) {
+            Some(i) => {
+                let equal = &s[i..i + 2] == "==";
+                let value = try!(parse_value(s[i + 2..].trim()).ok_or_else(|| {
+                    human(format!("invalid value in `{}`, strings must be \
+                                   quoted", s))
+                }));
+                (s[..i].trim(), Some((equal, value)))
+            }
+            None => (s.trim(), None),
+        };
+        if key.is_empty() || key.split('.').any(|k| k.is_empty()) {
+            bail!("invalid selection `{}`, expected a key such as \
+                   `package.metadata.ci.tier`, optionally followed by \
+                   `== VALUE` or `!= VALUE`", s)
+        }
+        Ok(Selector { key: key.to_string(), test: test })
+    }
+
+    fn matches(&self, pkg: &Package, config: &Config) -> CargoResult<bool> {
+        let contents = try!(paths::read(pkg.manifest_path()));
+        let manifest = toml::Value::Table(try!(cargo_toml::parse(&contents,
+                                                                pkg.manifest_path(),
+                                                                config)));
+        // `[project]` is an older name of `[package]`.
+        let alias = if self.key.starts_with("package.") {
+            Some(format!("project.{}", &self.key[8..]))
+        } else {
+            None
+        };
+        let value = manifest.lookup(&self.key).or_else(|| {
+            alias.as_ref().and_then(|key| manifest.lookup(key))
+        });
+        Ok(match (value, &self.test) {
+            (Some(value), &Some((equal, ref expected))) => {
+                (value == expected) == equal
+            }
+            (None, &Some((equal, _))) => !equal,
+            (Some(&toml::Value::Boolean(b)), &None) => b,
+            (Some(..), &None) => true,
+            (None, &None) => false,
+        })
+    }
+}
+
+fn parse_value(s: &str) -> Option<toml::Value> {
+    if s.len() >= 2 && (s.starts_with('"') && s.ends_with('"') ||
+                        s.starts_with('\'') && s.ends_with('\'')) {
+        return Some(toml::Value::String(s[1..s.len() - 1].to_string()))
+    }
+    match s {
+        "true" => return Some(toml::Value::Boolean(true)),
+        "false" => return Some(toml::Value::Boolean(false)),
+        _ => {}
+    }
+    if let Ok(n) = s.parse() {
+        return Some(toml::Value::Integer(n))
+    }
+    s.parse().ok().map(toml::Value::Float)
+}
@@ -0,0 +1,241 @@
+//! Running the tasks declared in the `[workspace.tasks]` table of the root
+//! manifest of a workspace.
+//!
+//! ```toml
+//! [workspace.tasks]
+//! fmt = "rustfmt --write-mode=overwrite src/lib.rs"
+//! codegen = { command = ["gen", "src/generated.rs"], build = ["gen"] }
+//! ci = { command = ["cargo", "test"], depends-on = ["fmt", "codegen"] }
+//! ```
+//!
+//! A task is a command, given as a string split on whitespace or as an array,
+//! or a table with the `command` and optionally the tasks it depends on, the
+//! packages whose binaries are built for it, additional environment variables
+//! and the directory it's run in, relative to the root of the workspace.
+
+use std::collections::BTreeMap;
+use std::env;
+
+use toml;
+
+use core::Workspace;
+use ops;
+use util::{paths, process, CargoResult, ChainError, ProcessError, human};
+use util::toml as cargo_toml;
+
+pub struct Task {
+    /// The program to run followed by its arguments.
+    pub command: Vec<String>,
+    /// Tasks run before this one.
+    pub depends_on: Vec<String>,
+    /// Packages whose binaries are built before running the task, and can be
+    /// run by name from it.
+    pub build: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub cwd: Option<String>,
+}
+
+/// Reads the tasks of the workspace.
+pub fn tasks(ws: &Workspace) -> CargoResult<BTreeMap<String, Task>> {
+    let file = ws.root().join("Cargo.toml");
+    let contents = try!(paths::read(&file));
+    let manifest = toml::Value::Table(try!(cargo_toml::parse(&contents, &file,
+                                                             ws.config())));
+    let table = match manifest.lookup("workspace.tasks") {
+        Some(&toml::Value::Table(ref table)) => table,
+        Some(..) => bail!("`workspace.tasks` of `{}` must be a table",
+                          file.display()),
+        None => return Ok(BTreeMap::new()),
+    };
+    let mut tasks = BTreeMap::new();
+    for (name, value) in table.iter() {
+        let task = try!(parse_task(value).chain_error(|| {
+            human(format!("invalid task `{}` in `{}`", name, file.display()))
+        }));
+        tasks.insert(name.clone(), task);
+    }
+    Ok(tasks)
+}
+
+/// Runs the task `name` after the tasks it depends on, passing `args` to its
+/// command. Returns the error of the first command which failed, if any.
+pub fn run_task(ws: &Workspace,
+                name: &str,
+                args: &[String],
+                release: bool) -> CargoResult<Option<ProcessError>> {
+    let tasks = try!(tasks(ws));
+    let mut order = Vec::new();
+    try!(schedule(&tasks, name, &mut Vec::new(), &mut order));
+
+    for task_name in order {
+        let task = &tasks[&task_name];
+        let mut process = process(&task.command[0]);
+        process.args(&task.command[1..]);
+        if task_name == name {
+            process.args(args);
+        }
+
+        let mut search_path = Vec::new();
+        for pkg in task.build.iter() {
+            // Each package is built as the current one, which the root of a
+            // virtual workspace can't be.
+            let member = match ws.members().find(|m| m.name() == &pkg[..]) {
+                Some(member) => member,
+                None => bail!("task `{}` builds `{}`, which isn't a member of \
+                               the workspace", task_name, pkg),
+            };
+            let member_ws = try!(Workspace::new(member.manifest_path(),
+                                                ws.config()));
+            let compile = try!(ops::compile(&member_ws, &ops::CompileOptions {
+                config: ws.config(),
+                jobs: None,
+                target: None,
+                features: &[],
+                all_features: false,
+                no_default_features: false,
+                spec: &[],
+                exec_engine: None,
+                release: release,
+                mode: ops::CompileMode::Build,
+                filter: ops::CompileFilter::Everything,
+                target_rustdoc_args: None,
+                target_rustc_args: None,
+                emit_ninja: None,
+                message_format: ops::MessageFormat::Human,
+                self_profile: None,
+                preserve_old_artifacts: false,
+                sbom: None,
+                shard: None,
+            }));
+            for binary in compile.binaries.iter() {
+                let dir = binary.parent().unwrap().to_path_buf();
+                if !search_path.contains(&dir) {
+                    search_path.push(dir);
+                }
+            }
+        }
+        if let Some(path) = env::var_os("PATH") {
+            search_path.extend(env::split_paths(&path));
+        }
+
+        process.cwd(ws.root().join(task.cwd.as_ref().map(|s| &s[..]).unwrap_or("")))
+               .env("PATH", &try!(paths::join_paths(&search_path, "PATH")))
+               .env("CARGO_WORKSPACE_DIR", ws.root())
+               .env("CARGO_TARGET_DIR", ws.target_dir().into_path_unlocked());
+        if let Ok(cargo) = env::current_exe() {
+            process.env("CARGO", cargo);
+        }
+        for (key, value) in task.env.iter() {
+            process.env(key, value);
+        }
+
+        try!(ws.config().shell().status("Running",
+                                        format!("task `{}`: {}", task_name,
+                                                process)));
+        if let Err(e) = process.exec() {
+            return Ok(Some(e))
+        }
+    }
+    Ok(None)
+}
+
+/// Appends the tasks to run for `name` to `order`, those it depends on first.
+fn schedule(tasks: &BTreeMap<String, Task>,
+            name: &str,
+            visiting: &mut Vec<String>,
+            order: &mut Vec<String>) -> CargoResult<()> {
+    if order.iter().any(|n| n == name) {
+        return Ok(())
+    }
+    if visiting.iter().any(|n| n == name) {
+        bail!("task `{}` depends on itself through `{}`", name,
+              visiting.join("` -> `"))
+    }
+    let task = match tasks.get(name) {
+        Some(task) => task,
+        None if visiting.is_empty() => {
+            bail!("no task named `{}` in `workspace.tasks`", name)
+        }
+        None => {
+            bail!("task `{}` depends on `{}`, which isn't in \
+                   `workspace.tasks`", visiting[visiting.len() - 1], name)
+        }
+    };
+    visiting.push(name.to_string());
+    for dep in task.depends_on.iter() {
+        try!(schedule(tasks, dep, visiting, order));
+    }
+    visiting.pop();
+    order.push(name.to_string());
+    Ok(())
+}
+
+fn parse_task(value: &toml::Value) -> CargoResult<Task> {
+    let mut task = Task {
+        command: Vec::new(),
+        depends_on: Vec::new(),
+        build: Vec::new(),
+        env: BTreeMap::new(),
+        cwd: None,
+    };
+    let table = match *value {
+        toml::Value::Table(ref table) => table,
+        _ => {
+            task.command = try!(command(value));
+            return Ok(task)
+        }
+    };
+    for (key, value) in table.iter() {
+        match &key[..] {
+            "command" => task.command = try!(command(value)),
+            "depends-on" => task.depends_on = try!(strings(key, value)),
+            "build" => task.build = try!(strings(key, value)),
+            "cwd" => {
+                task.cwd = Some(try!(value.as_str().chain_error(|| {
+                    human("`cwd` must be a string")
+                })).to_string());
+            }
+            "env" => {
+                let env = try!(value.as_table().chain_error(|| {
+                    human("`env` must be a table")
+                }));
+                for (name, value) in env.iter() {
+                    let value = try!(value.as_str().chain_error(|| {
+                        human(format!("`env.{}` must be a string", name))
+                    }));
+                    task.env.insert(name.clone(), value.to_string());
+                }
+            }
+            _ => bail!("unknown key `{}`, expected one of `command`, \
+                        `depends-on`, `build`, `env` or `cwd`", key),
+        }
+    }
+    if task.command.is_empty() {
+        bail!("a task needs a `command`")
+    }
+    Ok(task)
+}
+
+fn command(value: &toml::Value) -> CargoResult<Vec<String>> {
+    let command = match *value {
+        toml::Value::String(ref s) => {
+            s.split_whitespace().map(|s| s.to_string()).collect()
+        }
+        _ => try!(strings("command", value)),
+    };
+    if command.is_empty() {
+        bail!("the command of a task can't be empty")
+    }
+    Ok(command)
+}
+
+fn strings(key: &str, value: &toml::Value) -> CargoResult<Vec<String>> {
+    let array = try!(value.as_slice().chain_error(|| {
+        human(format!("`{}` must be an array of strings", key))
+    }));
+    array.iter().map(|v| {
+        v.as_str().map(|s| s.to_string()).chain_error(|| {
+            human(format!("`{}` must be an array of strings", key))
+        })
+    }).collect()
+}
@@ -1,14 +1,54 @@
 use std::ffi::{OsString, OsStr};
+use std::io::Write;
+use std::process::Output;
 
-use ops::{self, ExecEngine, ProcessEngine, Compilation};
-use util::{self, CargoResult, CargoTestError, ProcessError};
-use core::Workspace;
+use rustc_serialize::json::Json;
+
+use ops::{self, ExecEngine, ProcessEngine, Compilation, DoctestRunner};
+use ops::MessageFormat;
+use util::{self, CargoResult, CargoTestError, Config, ProcessError};
+use util::machine_message::{self, Message};
+use core::{Package, PackageId, Target, Workspace};
 
 pub struct TestOptions<'a> {
     pub compile_opts: ops::CompileOptions<'a>,
     pub no_run: bool,
     pub no_fail_fast: bool,
     pub only_doc: bool,
+    /// The shard of the individual tests, listed by the test binaries, which
+    /// is run rather than whole binaries, as `compile_opts.shard` does.
+    pub test_shard: Option<TestShard>,
+}
+
+/// One of the parts tests are split in, to run them on several machines
+/// with `--shard K/N`. Tests are split in the same way on every machine, from
+/// their sorted list, so that every test is run by exactly one shard.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TestShard {
+    /// The number of the shard, from 0.
+    pub index: usize,
+    pub count: usize,
+}
+
+impl TestShard {
+    /// Parses a shard given as `K/N`, numbered from 1.
+    pub fn parse(s: &str) -> CargoResult<TestShard> {
+        let mut parts = s.splitn(2, '/');
+        let index = parts.next().and_then(|k| k.parse::<usize>().ok());
+        let count = parts.next().and_then(|n| n.parse::<usize>().ok());
+        match (index, count) {
+            (Some(index), Some(count)) if index >= 1 && index <= count => {
+                Ok(TestShard { index: index - 1, count: count })
+            }
+            _ => bail!("invalid shard `{}`, expected `K/N` where K is between \
+                        1 and N", s),
+        }
+    }
+
+    /// Whether the item at `position` of a sorted list is in this shard.
+    pub fn contains(&self, position: usize) -> bool {
+        position % self.count == self.index
+    }
 }
 
 pub fn run_tests(ws: &Workspace,
@@ -69,7 +109,7 @@ fn compile_tests<'a>(ws: &Workspace<'a>,
                      -> CargoResult<Compilation<'a>> {
     let mut compilation = try!(ops::compile(ws, &options.compile_opts));
     compilation.tests.sort_by(|a, b| {
-        (a.0.package_id(), &a.1).cmp(&(b.0.package_id(), &b.1))
+        (a.0.package_id(), a.1.name()).cmp(&(b.0.package_id(), b.1.name()))
     });
     Ok(compilation)
 }
@@ -83,14 +123,47 @@ fn run_unit_tests(options: &TestOptions,
     let cwd = options.compile_opts.config.cwd();
 
     let mut errors = Vec::new();
+    let env = try!(config.run_env());
+    let shards = match options.test_shard {
+        Some(shard) => Some(try!(shard_tests(test_args, compilation, &env, shard))),
+        None => None,
+    };
+
+    // The tests of a shard are given by name, in as many runs of each binary
+    // as needed to keep its command line short enough.
+    let mut runs = Vec::new();
+    for (i, test) in compilation.tests.iter().enumerate() {
+        match shards {
+            Some(ref shards) => {
+                match shards[i] {
+                    // Without any name, the binary would run all of its tests,
+                    // so it isn't run at all.
+                    ShardRun::Tests(ref names) => {
+                        for batch in batches(names, MAX_TEST_NAMES_LEN) {
+                            let mut args = test_args.to_vec();
+                            args.push("--exact".to_string());
+                            args.extend(batch.iter().cloned());
+                            runs.push((test, args));
+                        }
+                    }
+                    ShardRun::Whole => runs.push((test, test_args.to_vec())),
+                    ShardRun::Nothing => {}
+                }
+            }
+            None => runs.push((test, test_args.to_vec())),
+        }
+    }
 
-    for &(ref pkg, _, ref exe) in &compilation.tests {
+    for (&(ref pkg, ref target, ref exe), args) in runs {
         let to_display = match util::without_prefix(exe, &cwd) {
             Some(path) => path,
             None => &**exe,
         };
-        let mut cmd = try!(compilation.target_process(exe, pkg));
-        cmd.args(test_args);
+        let mut cmd = try!(compilation.runner_process(exe, &args, pkg,
+                                                      target, pkg.root()));
+        for &(ref key, ref value) in env.iter() {
+            cmd.env(key, value);
+        }
         try!(config.shell().concise(|shell| {
             shell.status("Running", to_display.display().to_string())
         }));
@@ -108,6 +181,101 @@ fn run_unit_tests(options: &TestOptions,
     Ok(errors)
 }
 
+/// The most bytes of test names given to a test binary at once, well below the
+/// limits of command lines, Windows' being the lowest at 32K characters.
+const MAX_TEST_NAMES_LEN: usize = 16 * 1024;
+
+/// Splits `names` in batches of at most `max_len` bytes, with at least one
+/// name in each.
+fn batches(names: &[String], max_len: usize) -> Vec<&[String]> {
+    let mut ret = Vec::new();
+    let (mut start, mut len) = (0, 0);
+    for (i, name) in names.iter().enumerate() {
+        if i > start && len + name.len() + 1 > max_len {
+            ret.push(&names[start..i]);
+            start = i;
+            len = 0;
+        }
+        len += name.len() + 1;
+    }
+    if start < names.len() {
+        ret.push(&names[start..]);
+    }
+    ret
+}
+
+/// What a shard runs of a test binary.
+enum ShardRun {
+    /// The tests of these names.
+    Tests(Vec<String>),
+    /// All of it, for binaries without the test harness.
+    Whole,
+    Nothing,
+}
+
+/// Lists the tests of every test binary, with `--list`, and returns what of
+/// each binary is in `shard`.
+///
+/// Binaries without the test harness can neither list their tests nor run
+/// some of them, so each of them is run whole by one of the shards instead,
+/// as a whole test target would be.
+fn shard_tests(test_args: &[String],
+               compilation: &Compilation,
+               env: &[(String, String)],
+               shard: TestShard) -> CargoResult<Vec<ShardRun>> {
+    let mut list_args = test_args.to_vec();
+    list_args.push("--list".to_string());
+    list_args.push("--format".to_string());
+    list_args.push("terse".to_string());
+
+    let mut tests = Vec::new();
+    for &(ref pkg, ref target, ref exe) in compilation.tests.iter() {
+        if !target.harness() {
+            tests.push(None);
+            continue
+        }
+        let mut cmd = try!(compilation.runner_process(exe, &list_args, pkg,
+                                                      target, pkg.root()));
+        for &(ref key, ref value) in env.iter() {
+            cmd.env(key, value);
+        }
+        let output = try!(ExecEngine::exec_with_output(&ProcessEngine, cmd));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut names = stdout.lines().filter_map(|line| {
+            if line.ends_with(": test") {
+                Some(line[..line.len() - 6].to_string())
+            } else if line.ends_with(": benchmark") {
+                Some(line[..line.len() - 11].to_string())
+            } else {
+                None
+            }
+        }).collect::<Vec<_>>();
+        names.sort();
+        tests.push(Some(names));
+    }
+
+    let mut position = 0;
+    let mut whole = 0;
+    Ok(tests.into_iter().map(|names| {
+        match names {
+            Some(names) => {
+                ShardRun::Tests(names.into_iter().filter(|_| {
+                    position += 1;
+                    shard.contains(position - 1)
+                }).collect())
+            }
+            None => {
+                whole += 1;
+                if shard.contains(whole - 1) {
+                    ShardRun::Whole
+                } else {
+                    ShardRun::Nothing
+                }
+            }
+        }
+    }).collect())
+}
+
 fn run_doc_tests(options: &TestOptions,
                  test_args: &[String],
                  compilation: &Compilation)
@@ -115,26 +283,91 @@ fn run_doc_tests(options: &TestOptions,
     let mut errors = Vec::new();
     let config = options.compile_opts.config;
 
-    // We don't build/rust doctests if target != host
-    if let Some(target) = options.compile_opts.target {
-        if try!(config.rustc()).host != target {
-            return Ok(errors);
-        }
-    }
+    // When cross-compiling, doctests are built for the target too, and run
+    // through its runner.
+    let host = &try!(config.rustc()).host;
+    let nightly = try!(config.rustc()).is_nightly();
+    // libtest only reports in JSON on nightly, so doctests run as usual on
+    // other toolchains.
+    let json_messages = nightly &&
+        options.compile_opts.message_format == MessageFormat::Json;
+    let cross_target = options.compile_opts.target.and_then(|target| {
+        if target != host { Some(target) } else { None }
+    });
 
-    let libs = compilation.to_doc_test.iter().map(|package| {
-        (package, package.targets().iter().filter(|t| t.doctested())
-                         .map(|t| (t.src_path(), t.name(), t.crate_name())))
+    // When sharding individual tests, the doctests of each package are only
+    // run by one of the shards.
+    let mut packages = compilation.to_doc_test.iter().collect::<Vec<_>>();
+    if let Some(shard) = options.test_shard {
+        packages.sort_by(|a, b| a.package_id().cmp(b.package_id()));
+        packages = packages.into_iter().enumerate().filter(|&(i, _)| {
+            shard.contains(i)
+        }).map(|(_, package)| package).collect();
+    }
+    let libs = packages.into_iter().map(|package| {
+        (package, package.targets().iter().filter(|t| t.doctested()))
     });
 
     for (package, tests) in libs {
-        for (lib, name, crate_name) in tests {
-            try!(config.shell().status("Doc-tests", name));
+        for target in tests {
+            // Doctests which can't be run for the target are skipped, as
+            // rustdoc only runs them through a runner on nightly.
+            let runner = match cross_target {
+                Some(_) => {
+                    let skip = match try!(compilation.doctest_runner(package,
+                                                                     target)) {
+                        DoctestRunner::Runner(ref runner)
+                            if !runner.program.is_empty() && !nightly => {
+                            Err("running them through a runner needs a nightly \
+                                 toolchain".to_string())
+                        }
+                        DoctestRunner::Runner(runner) => Ok(Some(runner)),
+                        DoctestRunner::Unavailable(why) => Err(why),
+                    };
+                    match skip {
+                        Ok(runner) => runner,
+                        Err(why) => {
+                            try!(config.shell().warn(format!(
+                                "skipping the doctests of `{}`: {}",
+                                target.name(), why)));
+                            continue
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            try!(config.shell().status("Doc-tests", target.name()));
             let mut p = try!(compilation.rustdoc_process(package));
-            p.arg("--test").arg(lib)
-             .arg("--crate-name").arg(&crate_name);
+            p.arg("--test").arg(target.src_path())
+             .arg("--crate-name").arg(&target.crate_name());
 
-            for &rust_dep in &[&compilation.deps_output] {
+            let mut rust_deps = vec![&compilation.deps_output];
+            if let Some(triple) = cross_target {
+                p.arg("--target").arg(triple);
+                if let Some(ref linker) = compilation.target_linker {
+                    let mut arg = OsString::from("linker=");
+                    arg.push(linker);
+                    p.arg("-C").arg(arg);
+                }
+                rust_deps.push(&compilation.host_deps_output);
+            }
+            if let Some(runner) = runner {
+                if !runner.program.is_empty() {
+                    p.arg("-Z").arg("unstable-options")
+                     .arg("--runtool").arg(&runner.program);
+                    for arg in runner.args.iter() {
+                        p.arg("--runtool-arg").arg(arg);
+                    }
+                }
+                for &(ref key, ref value) in runner.env.iter() {
+                    p.env(key, value);
+                }
+                if let Some(ref cwd) = runner.cwd {
+                    p.cwd(package.root().join(cwd));
+                }
+            }
+            for rust_dep in rust_deps {
                 let mut arg = OsString::from("dependency=");
                 arg.push(rust_dep);
                 p.arg("-L").arg(arg);
@@ -174,10 +407,40 @@ fn run_doc_tests(options: &TestOptions,
                 }
             }
 
+            if json_messages {
+                // libtest reports the doctests in JSON, and rustdoc the errors
+                // compiling them, which are turned into messages of Cargo.
+                p.arg("--error-format").arg("json")
+                 .arg("--test-args")
+                 .arg("-Z unstable-options --format json --report-time");
+            }
+
             try!(config.shell().verbose(|shell| {
                 shell.status("Running", p.to_string())
             }));
-            if let Err(e) = ExecEngine::exec(&ProcessEngine, p) {
+            let result = if json_messages {
+                let result = ExecEngine::exec_with_output(&ProcessEngine, p);
+                let output = match result {
+                    Ok(ref output) => Some(output),
+                    Err(ref e) => e.output.as_ref(),
+                };
+                if let Some(output) = output {
+                    try!(emit_doc_test_messages(config, package, target, output));
+                }
+                // The output was just reported, so it's left out of the error.
+                // Without output rustdoc didn't run, and the error says why.
+                result.map(|_| ()).map_err(|e| {
+                    if e.output.is_none() {
+                        return e
+                    }
+                    util::process_error(&format!("doctests of `{}` failed",
+                                                 target.name()),
+                                        None, e.exit.as_ref(), None)
+                })
+            } else {
+                ExecEngine::exec(&ProcessEngine, p)
+            };
+            if let Err(e) = result {
                 errors.push(e);
                 if !options.no_fail_fast {
                     return Ok(errors);
@@ -187,3 +450,159 @@ fn run_doc_tests(options: &TestOptions,
     }
     Ok(errors)
 }
+
+/// The result of a doctest in `--message-format=json`, with the diagnostics
+/// of compiling it.
+#[derive(RustcEncodable)]
+struct DocTestMessage<'a> {
+    package_id: &'a PackageId,
+    target: &'a str,
+    name: String,
+    /// `ok`, `failed` or `ignored`.
+    event: String,
+    /// How long the doctest ran, in seconds.
+    exec_time: Option<f64>,
+    diagnostics: Vec<Json>,
+    /// What the doctest printed, other than diagnostics.
+    output: Option<String>,
+}
+
+impl<'a> Message for DocTestMessage<'a> {
+    fn reason(&self) -> &str {
+        "doctest"
+    }
+}
+
+/// The summary of the doctests of a target in `--message-format=json`.
+#[derive(RustcEncodable)]
+struct DocTestSuiteMessage<'a> {
+    package_id: &'a PackageId,
+    target: &'a str,
+    /// `ok` or `failed`.
+    event: String,
+    passed: u64,
+    failed: u64,
+    ignored: u64,
+    filtered_out: u64,
+    exec_time: Option<f64>,
+}
+
+impl<'a> Message for DocTestSuiteMessage<'a> {
+    fn reason(&self) -> &str {
+        "doctest-suite"
+    }
+}
+
+/// Turns the events libtest printed in JSON for the doctests of `target` into
+/// messages, passing anything else rustdoc printed on to stderr.
+fn emit_doc_test_messages(config: &Config, pkg: &Package, target: &Target,
+                          output: &Output) -> CargoResult<()> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut other = String::from_utf8_lossy(&output.stderr).into_owned();
+    for line in stdout.lines() {
+        let event = match Json::from_str(line) {
+            Ok(Json::Object(event)) => event,
+            _ => {
+                other.push_str(line);
+                other.push('\n');
+                continue
+            }
+        };
+        let string = |key: &str| event.get(key).and_then(|v| v.as_string());
+        let number = |key: &str| event.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let kind = string("event").unwrap_or("").to_string();
+        match (string("type"), &kind[..]) {
+            (Some("test"), "started") => {}
+            (Some("test"), _) => {
+                let (diagnostics, output) = split_diagnostics(string("stdout"));
+                machine_message::emit(&DocTestMessage {
+                    package_id: pkg.package_id(),
+                    target: target.name(),
+                    name: string("name").unwrap_or("").to_string(),
+                    event: kind.clone(),
+                    exec_time: exec_time(event.get("exec_time")),
+                    diagnostics: diagnostics,
+                    output: output,
+                });
+            }
+            (Some("suite"), "started") => {}
+            (Some("suite"), _) => {
+                machine_message::emit(&DocTestSuiteMessage {
+                    package_id: pkg.package_id(),
+                    target: target.name(),
+                    event: kind.clone(),
+                    passed: number("passed"),
+                    failed: number("failed"),
+                    ignored: number("ignored"),
+                    filtered_out: number("filtered_out"),
+                    exec_time: exec_time(event.get("exec_time")),
+                });
+            }
+            _ => {}
+        }
+    }
+    if !other.trim().is_empty() {
+        try!(write!(config.shell().err(), "{}", other));
+    }
+    Ok(())
+}
+
+/// Separates the diagnostics rustdoc printed in JSON when compiling a doctest
+/// from the rest of its output.
+fn split_diagnostics(stdout: Option<&str>) -> (Vec<Json>, Option<String>) {
+    let mut diagnostics = Vec::new();
+    let mut output = String::new();
+    for line in stdout.unwrap_or("").lines() {
+        match Json::from_str(line) {
+            Ok(diagnostic @ Json::Object(..)) => {
+                if diagnostic.find("level").is_some() {
+                    diagnostics.push(diagnostic);
+                    continue
+                }
+                output.push_str(line);
+            }
+            _ => output.push_str(line),
+        }
+        output.push('\n');
+    }
+    let output = if output.trim().is_empty() { None } else { Some(output) };
+    (diagnostics, output)
+}
+
+/// The `exec_time` of libtest, a number of seconds, or a string such as
+/// `"0.01s"` in older versions.
+fn exec_time(time: Option<&Json>) -> Option<f64> {
+    match time {
+        Some(&Json::String(ref s)) => s.trim_right_matches('s').parse().ok(),
+        Some(time) => time.as_f64(),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::batches;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn no_batches_without_names() {
+        assert!(batches(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn batches_are_bounded() {
+        let names = names(&["a", "bb", "ccc", "dddd", "e"]);
+        let batches = batches(&names, 7);
+        assert_eq!(batches, vec![&names[0..2], &names[2..3], &names[3..5]]);
+    }
+
+    #[test]
+    fn long_names_are_alone() {
+        let names = names(&["a", "a_very_long_name", "b"]);
+        let batches = batches(&names, 4);
+        assert_eq!(batches, vec![&names[0..1], &names[1..2], &names[2..3]]);
+    }
+}
@@ -0,0 +1,416 @@
+//! Showing the dependency graph as it's built for one platform, and why the
+//! features in it are enabled.
+//!
+//! `cargo tree --why-feature bar/std` walks back from the feature `std` of
+//! `bar` to the members of the workspace: through the features listing it,
+//! the dependency declarations requesting it, with or without their default
+//! features, and the packages declaring those. Every cause gets a branch of
+//! its own, so a feature enabled from several places shows all of them.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use core::dependency::Kind;
+use core::resolver::Resolve;
+use core::{PackageId, PackageSet, Summary, Workspace};
+use ops;
+use util::{CargoResult, Cfg, Config};
+
+use super::cargo_output_metadata::target_cfg;
+
+pub struct TreeOptions<'a> {
+    pub config: &'a Config,
+    pub features: &'a [String],
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// The platform the graph is built for, the host if `None`.
+    pub target: Option<&'a str>,
+    /// A feature to explain rather than printing the graph, as
+    /// `package/feature`.
+    pub why_feature: Option<&'a str>,
+}
+
+/// Why a feature of a package is enabled.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Cause {
+    /// Passed with `--features` or `--all-features`.
+    CommandLine,
+    /// The `default` feature of a member of the workspace built with its
+    /// default features.
+    Member,
+    /// Listed by another feature of the same package.
+    Feature(String),
+    /// Requested by a package depending on this one, by the declaration of
+    /// the dependency or, along with the name of the feature listing it, as
+    /// `dep/feature` in its `[features]`.
+    Dependency(PackageId, Option<String>),
+}
+
+/// The packages built for one platform, with why each of them and each of
+/// their features are.
+struct Graph {
+    target: String,
+    members: Vec<PackageId>,
+    features: HashMap<PackageId, BTreeMap<String, BTreeSet<Cause>>>,
+    dependencies: HashMap<PackageId, BTreeSet<PackageId>>,
+    /// The packages depending on each package, along with the name of the
+    /// optional dependency if that's how it's activated.
+    dependents: HashMap<PackageId, BTreeSet<(PackageId, Option<String>)>>,
+}
+
+/// A line of the output, and those nested under it.
+struct Node {
+    label: String,
+    children: Vec<Node>,
+}
+
+/// Renders the dependency graph of the workspace, or the causes of the
+/// feature `opts.why_feature`, as a tree.
+pub fn tree(ws: &Workspace, opts: &TreeOptions) -> CargoResult<String> {
+    let (packages, resolve) = try!(ops::resolve_dependencies(ws,
+                                                             None,
+                                                             opts.features.to_vec(),
+                                                             opts.all_features,
+                                                             opts.no_default_features));
+    let host = try!(opts.config.rustc()).host.clone();
+    let host_cfg = try!(target_cfg(ws, &host));
+    let triple = opts.target.unwrap_or(&host);
+    let cfg = try!(target_cfg(ws, triple));
+    let graph = try!(build_graph(ws, opts, &packages, &resolve,
+                                 (triple, &cfg[..]),
+                                 (&host, &host_cfg[..])));
+
+    let roots = match opts.why_feature {
+        Some(spec) => {
+            let mut parts = spec.rsplitn(2, '/');
+            let feature = parts.next().unwrap();
+            let pkg = match parts.next() {
+                Some(pkg) if !feature.is_empty() => pkg,
+                _ => bail!("`--why-feature` expects `PACKAGE/FEATURE`, found \
+                            `{}`", spec),
+            };
+            let id = try!(resolve.query(pkg)).clone();
+            let enabled = match graph.features.get(&id) {
+                Some(enabled) => enabled,
+                None => bail!("`{}` isn't built for `{}`", id, graph.target),
+            };
+            if !enabled.contains_key(feature) {
+                bail!("feature `{}` of `{}` isn't enabled when building for \
+                       `{}`", feature, id, graph.target)
+            }
+            vec![Node {
+                label: format!("feature \"{}\" of {}, for {}", feature, id,
+                               graph.target),
+                children: feature_causes(&graph, &id, feature,
+                                         &mut HashSet::new()),
+            }]
+        }
+        None => {
+            let mut seen = HashSet::new();
+            graph.members.iter().map(|id| {
+                dependency_node(&graph, id, &mut seen)
+            }).collect()
+        }
+    };
+
+    let mut out = String::new();
+    for root in roots {
+        out.push_str(&root.label);
+        out.push('\n');
+        render(&root, "", &mut out);
+    }
+    Ok(out)
+}
+
+/// Walks the resolved graph from the members of the workspace like
+/// `cargo metadata --filter-platform` does, recording why each package and
+/// each feature ends up in it along the way.
+fn build_graph(ws: &Workspace,
+               opts: &TreeOptions,
+               packages: &PackageSet,
+               resolve: &Resolve,
+               target: (&str, &[Cfg]),
+               host: (&str, &[Cfg])) -> CargoResult<Graph> {
+    let mut requested = HashMap::new();
+    let mut dependencies = HashMap::new();
+    let mut dependents = HashMap::new();
+    let mut visited = HashSet::new();
+    let mut queue = Vec::new();
+
+    let mut members = ws.members().map(|m| m.package_id().clone())
+                        .collect::<Vec<_>>();
+    members.sort();
+    let features = opts.features.iter().flat_map(|s| s.split(' '))
+                       .filter(|s| !s.is_empty()).collect::<Vec<_>>();
+    for member in ws.members() {
+        let id = member.package_id();
+        let summary = member.summary();
+        let mut explicit = BTreeMap::new();
+        if opts.all_features {
+            for feature in summary.features().keys() {
+                request(&mut explicit, feature, Cause::CommandLine);
+            }
+        } else if ws.current_opt().map(|p| p.package_id()) == Some(id) {
+            for feature in features.iter() {
+                request(&mut explicit, feature, Cause::CommandLine);
+            }
+        }
+        if !opts.no_default_features && summary.features().contains_key("default") {
+            request(&mut explicit, "default", Cause::Member);
+        }
+        requested.insert(id.clone(), explicit);
+        queue.push((id.clone(), false));
+    }
+
+    // Enabling a feature may activate more dependencies, or more features of
+    // them, so packages are revisited until nothing changes.
+    while let Some((id, for_host)) = queue.pop() {
+        visited.insert((id.clone(), for_host));
+        let summary = try!(packages.get(&id)).summary().clone();
+        let (enabled, dep_features) = expand(&id, &summary, &requested[&id]);
+
+        let (triple, cfg) = if for_host { host } else { target };
+        for dep in summary.dependencies() {
+            if dep.kind() == Kind::Development && !members.contains(&id) {
+                continue
+            }
+            let dep_for_host = for_host || dep.kind() == Kind::Build;
+            let (triple, cfg) = if dep.kind() == Kind::Build {
+                host
+            } else {
+                (triple, cfg)
+            };
+            if !dep.platform().map(|p| p.matches(triple, Some(cfg)))
+                                .unwrap_or(true) {
+                continue
+            }
+            if dep.is_optional() && !enabled.contains_key(dep.name()) {
+                continue
+            }
+            let dep_id = match resolve.deps_not_replaced(&id).find(|d| {
+                dep.matches_id(d)
+            }) {
+                Some(dep_id) => resolve.replacement(dep_id).unwrap_or(dep_id)
+                                       .clone(),
+                None => continue,
+            };
+            let optional = if dep.is_optional() {
+                Some(dep.name().to_string())
+            } else {
+                None
+            };
+            dependencies.entry(id.clone()).or_insert(BTreeSet::new())
+                        .insert(dep_id.clone());
+            dependents.entry(dep_id.clone()).or_insert(BTreeSet::new())
+                      .insert((id.clone(), optional));
+
+            let has_default = try!(packages.get(&dep_id)).summary().features()
+                                                         .contains_key("default");
+            let mut changed = !requested.contains_key(&dep_id);
+            {
+                let entry = requested.entry(dep_id.clone())
+                                     .or_insert(BTreeMap::new());
+                for feature in dep.features() {
+                    changed |= request(entry, feature,
+                                       Cause::Dependency(id.clone(), None));
+                }
+                for &(ref name, ref feature, ref cause) in dep_features.iter() {
+                    if name == dep.name() {
+                        changed |= request(entry, feature, cause.clone());
+                    }
+                }
+                if dep.uses_default_features() && has_default {
+                    changed |= request(entry, "default",
+                                       Cause::Dependency(id.clone(), None));
+                }
+            }
+            if changed || !visited.contains(&(dep_id.clone(), dep_for_host)) {
+                queue.push((dep_id, dep_for_host));
+            }
+        }
+    }
+
+    let mut features = HashMap::new();
+    for (id, explicit) in requested.iter() {
+        let summary = try!(packages.get(id)).summary().clone();
+        features.insert(id.clone(), expand(id, &summary, explicit).0);
+    }
+    Ok(Graph {
+        target: target.0.to_string(),
+        members: members,
+        features: features,
+        dependencies: dependencies,
+        dependents: dependents,
+    })
+}
+
+/// Records that `feature` is requested because of `cause`, returning whether
+/// that wasn't known yet.
+fn request(requested: &mut BTreeMap<String, BTreeSet<Cause>>,
+           feature: &str,
+           cause: Cause) -> bool {
+    requested.entry(feature.to_string()).or_insert(BTreeSet::new())
+             .insert(cause)
+}
+
+/// Expands the features requested of the package `id` into all of those
+/// which are enabled along with their causes, and the features requested of
+/// its dependencies through `dep/feature` as `(dep, feature, cause)`.
+fn expand(id: &PackageId,
+          summary: &Summary,
+          requested: &BTreeMap<String, BTreeSet<Cause>>)
+          -> (BTreeMap<String, BTreeSet<Cause>>, Vec<(String, String, Cause)>) {
+    let mut enabled: BTreeMap<String, BTreeSet<Cause>> = BTreeMap::new();
+    let mut dep_features = Vec::new();
+    let mut stack = Vec::new();
+    for (feature, causes) in requested.iter() {
+        stack.extend(causes.iter().map(|c| (feature.clone(), c.clone())));
+    }
+    while let Some((feature, cause)) = stack.pop() {
+        let mut parts = feature.splitn(2, '/');
+        let name = parts.next().unwrap().to_string();
+        if let Some(dep_feature) = parts.next() {
+            let dep_cause = match cause {
+                Cause::Feature(ref by) => {
+                    Cause::Dependency(id.clone(), Some(by.clone()))
+                }
+                Cause::CommandLine => Cause::CommandLine,
+                _ => Cause::Dependency(id.clone(), None),
+            };
+            dep_features.push((name.clone(), dep_feature.to_string(),
+                               dep_cause));
+        }
+        let causes = enabled.entry(name.clone()).or_insert(BTreeSet::new());
+        let first = causes.is_empty();
+        causes.insert(cause);
+        if first {
+            if let Some(implied) = summary.features().get(&name) {
+                stack.extend(implied.iter().map(|f| {
+                    (f.clone(), Cause::Feature(name.clone()))
+                }));
+            }
+        }
+    }
+    (enabled, dep_features)
+}
+
+/// The subtree of the dependencies of `id`, which is only expanded the first
+/// time the package is seen.
+fn dependency_node(graph: &Graph,
+                   id: &PackageId,
+                   seen: &mut HashSet<PackageId>) -> Node {
+    if !seen.insert(id.clone()) {
+        return Node { label: format!("{} (*)", id), children: Vec::new() }
+    }
+    let children = graph.dependencies.get(id).map(|deps| {
+        deps.iter().map(|dep| dependency_node(graph, dep, seen)).collect()
+    }).unwrap_or(Vec::new());
+    Node { label: id.to_string(), children: children }
+}
+
+/// Why the feature `feature` of `id` is enabled. Each feature and package is
+/// only explained the first time it comes up, which also cuts cycles short.
+fn feature_causes(graph: &Graph,
+                  id: &PackageId,
+                  feature: &str,
+                  seen: &mut HashSet<(PackageId, Option<String>)>) -> Vec<Node> {
+    seen.insert((id.clone(), Some(feature.to_string())));
+    let causes = match graph.features.get(id).and_then(|f| f.get(feature)) {
+        Some(causes) => causes,
+        None => return Vec::new(),
+    };
+    causes.iter().map(|cause| {
+        match *cause {
+            Cause::CommandLine => leaf("requested on the command line".to_string()),
+            Cause::Member => {
+                leaf(format!("{} is a member of the workspace, built with its \
+                              default features", id))
+            }
+            Cause::Feature(ref by) => {
+                feature_node(graph, id, by, format!("enabled by feature \"{}\" \
+                                                     of {}", by, id), seen)
+            }
+            Cause::Dependency(ref from, Some(ref by)) => {
+                feature_node(graph, from, by, format!("enabled by feature \
+                                                       \"{}\" of {}", by, from),
+                             seen)
+            }
+            Cause::Dependency(ref from, None) => {
+                let label = if feature == "default" {
+                    format!("requested by {}, which depends on `{}` with its \
+                             default features", from, id.name())
+                } else {
+                    format!("requested by {} in its dependency on `{}`", from,
+                            id.name())
+                };
+                package_node(graph, from, label, seen)
+            }
+        }
+    }).collect()
+}
+
+/// Why `id` is built: being a member of the workspace or depended on.
+fn package_causes(graph: &Graph,
+                  id: &PackageId,
+                  seen: &mut HashSet<(PackageId, Option<String>)>) -> Vec<Node> {
+    seen.insert((id.clone(), None));
+    if graph.members.contains(id) {
+        return vec![leaf(format!("{} is a member of the workspace", id))]
+    }
+    let dependents = match graph.dependents.get(id) {
+        Some(dependents) => dependents,
+        None => return Vec::new(),
+    };
+    dependents.iter().map(|&(ref parent, ref optional)| {
+        match *optional {
+            Some(ref name) => {
+                feature_node(graph, parent, name,
+                             format!("{} depends on `{}` as the optional \
+                                      dependency enabled by feature \"{}\"",
+                                     parent, id.name(), name), seen)
+            }
+            None => {
+                package_node(graph, parent,
+                             format!("{} depends on `{}`", parent, id.name()),
+                             seen)
+            }
+        }
+    }).collect()
+}
+
+fn feature_node(graph: &Graph,
+                id: &PackageId,
+                feature: &str,
+                label: String,
+                seen: &mut HashSet<(PackageId, Option<String>)>) -> Node {
+    if seen.contains(&(id.clone(), Some(feature.to_string()))) {
+        return leaf(format!("{} (*)", label))
+    }
+    Node { label: label, children: feature_causes(graph, id, feature, seen) }
+}
+
+fn package_node(graph: &Graph,
+                id: &PackageId,
+                label: String,
+                seen: &mut HashSet<(PackageId, Option<String>)>) -> Node {
+    if seen.contains(&(id.clone(), None)) {
+        return leaf(format!("{} (*)", label))
+    }
+    Node { label: label, children: package_causes(graph, id, seen) }
+}
+
+fn leaf(label: String) -> Node {
+    Node { label: label, children: Vec::new() }
+}
+
+/// Appends the lines of the children of `node`, each prefixed by `prefix`.
+fn render(node: &Node, prefix: &str, out: &mut String) {
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == node.children.len();
+        out.push_str(prefix);
+        out.push_str(if last {"└── "} else {"├── "});
+        out.push_str(&child.label);
+        out.push('\n');
+        render(child, &format!("{}{}", prefix, if last {"    "} else {"│   "}),
+               out);
+    }
+}
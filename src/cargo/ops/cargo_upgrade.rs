@@ -0,0 +1,359 @@
+//! Raising the version requirements of dependencies, for `cargo upgrade` and
+//! `cargo update --breaking`.
+//!
+//! Requirements are rewritten where they're declared: in the manifests of the
+//! members of the workspace, or in `[workspace.dependencies]` of its root for
+//! dependencies inherited with `workspace = true`. The manifests are edited as
+//! text, so their comments and formatting are kept.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use semver::Version;
+use toml;
+
+use core::registry::PackageRegistry;
+use core::{Dependency, Registry, Workspace};
+use ops::{self, UpdateOptions};
+use util::{paths, CargoResult, Config};
+use util::toml as cargo_toml;
+
+/// The tables of a manifest which declare dependencies.
+const DEPENDENCY_TABLES: &'static [&'static str] = &[
+    "dependencies",
+    "dev-dependencies",
+    "dev_dependencies",
+    "build-dependencies",
+    "build_dependencies",
+];
+
+pub struct UpgradeOptions<'a> {
+    /// The names of the dependencies to upgrade, or all of them if empty.
+    pub to_upgrade: &'a [String],
+    /// Whether to raise requirements past the newest version they allow,
+    /// rather than to it.
+    pub breaking: bool,
+    pub dry_run: bool,
+}
+
+/// A requirement to rewrite in a manifest.
+struct Upgrade {
+    manifest: PathBuf,
+    name: String,
+    from: String,
+    to: String,
+}
+
+/// Executes `cargo upgrade`: raises the requirements of `opts`, and then
+/// updates the lockfile for the upgraded dependencies.
+pub fn upgrade(ws: &Workspace, opts: &UpgradeOptions) -> CargoResult<()> {
+    let config = ws.config();
+    if !config.lock_update_allowed() && !opts.dry_run {
+        let flag = if config.network_allowed() {"--frozen"} else {"--locked"};
+        bail!("the manifests of the workspace need to be updated but {} was \
+               passed to prevent this", flag)
+    }
+    let upgraded = try!(upgrade_requirements(ws, opts));
+    if upgraded.is_empty() {
+        let reason = if opts.breaking {
+            "no requirement excludes the newest version of its dependency"
+        } else {
+            "every requirement already starts at the newest version it allows"
+        };
+        return config.shell().status("Upgrading", reason)
+    }
+    if opts.dry_run {
+        return config.shell().warn("not writing the manifests, as this is a \
+                                    dry run")
+    }
+
+    // The workspace is loaded again to pick up the edited requirements.
+    let ws = try!(Workspace::new(&ws.root().join("Cargo.toml"), config));
+    ops::update_lockfile(&ws, &UpdateOptions {
+        aggressive: false,
+        precise: None,
+        allow_yanked: false,
+        dry_run: false,
+        diff: None,
+        to_update: &upgraded,
+        advisories: None,
+        minimal_direct: false,
+        breaking: false,
+        config: config,
+    })
+}
+
+/// Raises the requirements on the registry dependencies of the members of
+/// `ws` named in `opts`, or all of them, to start at the newest version they
+/// allow, or with `breaking` to allow the newest version of the dependency
+/// when they don't. Returns the names of those upgraded.
+///
+/// Dependencies replaced with `[replace]` are left alone, as the requirement
+/// has to keep allowing the replacement.
+pub fn upgrade_requirements(ws: &Workspace,
+                            opts: &UpgradeOptions) -> CargoResult<Vec<String>> {
+    let config = ws.config();
+    let specs = opts.to_upgrade;
+    let mut registry = try!(PackageRegistry::new(config));
+    let root_manifest = ws.root().join("Cargo.toml");
+    let mut upgrades: Vec<Upgrade> = Vec::new();
+
+    for member in ws.members() {
+        let manifest = try!(read_manifest(member.manifest_path(), config));
+        for dep in member.dependencies() {
+            if !dep.source_id().is_registry() ||
+               (!specs.is_empty() && !specs.iter().any(|s| s == dep.name())) {
+                continue
+            }
+            if ws.root_replace().iter().any(|&(ref spec, _)| spec.name() == dep.name()) {
+                if !specs.is_empty() {
+                    try!(config.shell().warn(format!(
+                        "not upgrading `{}`, as it's replaced with `[replace]`",
+                        dep.name())));
+                }
+                continue
+            }
+            let newest = if opts.breaking {
+                try!(newest_version(&mut registry, dep))
+            } else {
+                try!(newest_matching(&mut registry, dep))
+            };
+            let newest = match newest {
+                Some(newest) => newest,
+                None => continue,
+            };
+            if opts.breaking && dep.version_req().matches(&newest) {
+                continue
+            }
+
+            // Inherited requirements are declared by the root instead.
+            let inherited = declarations(&manifest, dep.name()).iter().any(|d| {
+                d.lookup("workspace").and_then(|w| w.as_bool()) == Some(true)
+            });
+            let (path, from) = if inherited {
+                let root = try!(read_manifest(&root_manifest, config));
+                let decl = root.lookup("workspace.dependencies")
+                               .and_then(|t| t.as_table())
+                               .and_then(|t| t.get(dep.name()));
+                (root_manifest.clone(), decl.and_then(requirement))
+            } else {
+                let from = declarations(&manifest, dep.name()).into_iter()
+                                                              .filter_map(requirement)
+                                                              .next();
+                (member.manifest_path().to_path_buf(), from)
+            };
+            let from = match from {
+                Some(from) => from,
+                None => continue,
+            };
+            let to = match raise(&from, &newest) {
+                Some(ref to) if to == from.trim() => continue,
+                Some(to) => to,
+                None if !opts.breaking => continue,
+                None => {
+                    try!(config.shell().warn(format!(
+                        "not upgrading `{}`, as its requirement `{}` in `{}` \
+                         isn't a plain version", dep.name(), from,
+                        path.display())));
+                    continue
+                }
+            };
+            if !upgrades.iter().any(|u| u.manifest == path && u.name == dep.name()) {
+                upgrades.push(Upgrade {
+                    manifest: path,
+                    name: dep.name().to_string(),
+                    from: from,
+                    to: to,
+                });
+            }
+        }
+    }
+
+    let mut upgraded = BTreeSet::new();
+    for upgrade in upgrades.iter() {
+        if !opts.dry_run {
+            let contents = try!(paths::read(&upgrade.manifest));
+            let edited = rewrite_requirement(&contents, &upgrade.name,
+                                             &upgrade.from, &upgrade.to);
+            try!(paths::write(&upgrade.manifest, edited.as_bytes()));
+        }
+        let shown = upgrade.manifest.strip_prefix(ws.root())
+                           .unwrap_or(&upgrade.manifest);
+        try!(config.shell().status("Upgrading",
+                                   format!("{} {} -> {} in {}", upgrade.name,
+                                           upgrade.from, upgrade.to,
+                                           shown.display())));
+        upgraded.insert(upgrade.name.clone());
+    }
+    Ok(upgraded.into_iter().collect())
+}
+
+pub fn read_manifest(path: &Path, config: &Config) -> CargoResult<toml::Value> {
+    let contents = try!(paths::read(path));
+    Ok(toml::Value::Table(try!(cargo_toml::parse(&contents, path, config))))
+}
+
+/// The newest version of the dependency which isn't a pre-release.
+pub fn newest_version(registry: &mut PackageRegistry, dep: &Dependency)
+                      -> CargoResult<Option<Version>> {
+    let any = try!(Dependency::parse(dep.name(), None, dep.source_id()));
+    let summaries = try!(registry.query(&any));
+    Ok(summaries.iter().map(|s| s.version())
+                .filter(|v| v.pre.is_empty())
+                .max().cloned())
+}
+
+/// The newest version allowed by the requirement of the dependency which
+/// isn't a pre-release.
+fn newest_matching(registry: &mut PackageRegistry, dep: &Dependency)
+                   -> CargoResult<Option<Version>> {
+    let summaries = try!(registry.query(dep));
+    Ok(summaries.iter().map(|s| s.version())
+                .filter(|v| v.pre.is_empty())
+                .max().cloned())
+}
+
+/// The declarations of the dependency `name` in every dependency table of
+/// `manifest`, including the platform-specific ones.
+fn declarations<'a>(manifest: &'a toml::Value, name: &str) -> Vec<&'a toml::Value> {
+    let mut tables = vec![manifest];
+    if let Some(targets) = manifest.lookup("target").and_then(|t| t.as_table()) {
+        tables.extend(targets.values());
+    }
+    let mut found = Vec::new();
+    for table in tables {
+        for key in DEPENDENCY_TABLES {
+            let decl = table.as_table().and_then(|t| t.get(*key))
+                            .and_then(|t| t.as_table())
+                            .and_then(|t| t.get(name));
+            found.extend(decl);
+        }
+    }
+    found
+}
+
+/// The version requirement of a declaration, either the declaration itself or
+/// its `version` key.
+fn requirement(decl: &toml::Value) -> Option<String> {
+    match *decl {
+        toml::Value::String(ref req) => Some(req.clone()),
+        _ => decl.lookup("version").and_then(|v| v.as_str())
+                 .map(|s| s.to_string()),
+    }
+}
+
+/// Raises `req` to allow `version`, keeping its operator and how many parts of
+/// the version it gives: `0.1` becomes `0.3` and `^1.2.3` becomes `^2.0.1`.
+/// Requirements with several comparators or other operators than `^` and `~`
+/// aren't raised.
+fn raise(req: &str, version: &Version) -> Option<String> {
+    let req = req.trim();
+    let (op, rest) = if req.starts_with('^') || req.starts_with('~') {
+        (&req[..1], req[1..].trim())
+    } else {
+        ("", req)
+    };
+    if rest.is_empty() || rest.contains(|c: char| !c.is_digit(10) && c != '.') {
+        return None
+    }
+    let raised = match rest.split('.').count() {
+        1 => version.major.to_string(),
+        2 => format!("{}.{}", version.major, version.minor),
+        _ => format!("{}.{}.{}", version.major, version.minor, version.patch),
+    };
+    Some(format!("{}{}", op, raised))
+}
+
+/// Replaces the requirement `from` of the dependency `name` by `to` in the
+/// manifest `contents`, wherever the dependency is declared with it: by its
+/// line in a table of dependencies, whether the requirement is the value or
+/// the `version` of an inline table, or in a `[dependencies.name]` table of its
+/// own. The tables of `[workspace]` and of each `[target]` are included.
+fn rewrite_requirement(contents: &str, name: &str, from: &str, to: &str) -> String {
+    let mut out = String::new();
+    // Whether the current table declares dependencies, or is the table of
+    // the dependency itself as in `[dependencies.name]`.
+    let mut in_deps = false;
+    let mut in_dep = false;
+    for line in contents.lines() {
+        let mut edited = line.to_string();
+        if let Some(header) = header(line) {
+            let own = format!(".{}", name);
+            in_deps = is_dependency_table(&header);
+            in_dep = header.ends_with(&own) &&
+                     is_dependency_table(&header[..header.len() - own.len()]);
+        } else if let Some(pos) = line.find('=') {
+            let key = line[..pos].trim().trim_matches(|c: char| c == '"' || c == '\'');
+            if in_deps && key == name {
+                edited = if line[pos + 1..].trim_left().starts_with('{') {
+                    replace_inline_version(line, pos + 1, from, to)
+                } else {
+                    replace_quoted(line, pos + 1, from, to)
+                };
+            } else if in_dep && key == "version" {
+                edited = replace_quoted(line, pos + 1, from, to);
+            }
+        }
+        out.push_str(&edited);
+        out.push('\n');
+    }
+    out
+}
+
+/// The name of the table a header line opens, if it's one, with quoted keys
+/// such as `'cfg(unix)'` unquoted.
+fn header(line: &str) -> Option<String> {
+    let line = line.trim();
+    if !line.starts_with('[') {
+        return None
+    }
+    let header = line.trim_matches(|c: char| c == '[' || c == ']').trim();
+    Some(header.chars().filter(|&c| c != '"' && c != '\'').collect())
+}
+
+/// Whether `table` is one of `DEPENDENCY_TABLES`, either at the top level or
+/// in `[workspace]` or a `[target]`.
+fn is_dependency_table(table: &str) -> bool {
+    DEPENDENCY_TABLES.iter().any(|d| {
+        table == *d || table == format!("workspace.{}", d) ||
+            (table.starts_with("target.") && table.ends_with(&format!(".{}", d)))
+    })
+}
+
+/// Replaces the string `from` by `to` if it's the value starting at `at` in
+/// `line`, keeping its quotes.
+fn replace_quoted(line: &str, at: usize, from: &str, to: &str) -> String {
+    let value = line[at..].trim_left();
+    let start = line.len() - value.len();
+    for quote in ["\"", "'"].iter() {
+        let quoted = format!("{}{}{}", quote, from, quote);
+        if value.starts_with(&quoted) {
+            return format!("{}{}{}{}{}", &line[..start], quote, to, quote,
+                           &line[start + quoted.len()..])
+        }
+    }
+    line.to_string()
+}
+
+/// Replaces the `version` `from` by `to` in the inline table starting at `at`
+/// in `line`.
+fn replace_inline_version(line: &str, at: usize, from: &str, to: &str) -> String {
+    let mut search = at;
+    while let Some(found) = line[search..].find("version") {
+        let key = search + found;
+        search = key + "version".len();
+        let before = line[..key].trim_right_matches(|c: char| c == '"' || c == '\'');
+        if !before.ends_with(|c: char| c == '{' || c == ',' || c.is_whitespace()) {
+            continue
+        }
+        let rest = line[search..].trim_left_matches(|c: char| c == '"' || c == '\'')
+                                 .trim_left();
+        if rest.starts_with('=') {
+            let edited = replace_quoted(line, line.len() - rest.len() + 1, from, to);
+            if edited != line {
+                return edited
+            }
+        }
+    }
+    line.to_string()
+}
@@ -0,0 +1,234 @@
+//! Enforcing a supply-chain policy on the packages a workspace depends on.
+//!
+//! The policy is the `supply-chain.toml` file at the root of the workspace,
+//! and only applies if it exists. Every package which isn't a path dependency
+//! must be covered by it, either because its version was audited, because it
+//! was published by someone trusted with the package, or because its version
+//! is exempted until it's reviewed:
+//!
+//! ```toml
+//! [[audits.log]]
+//! version = "0.3.6"
+//! who = "Jane Doe <jane@example.com>"
+//! notes = "No unsafe code, no build script."
+//!
+//! [[trusted.libc]]
+//! publisher = "alexcrichton"
+//!
+//! [[exemptions.rand]]
+//! version = "0.3.14"
+//! ```
+//!
+//! Builds and `cargo add` refuse packages which aren't covered, and
+//! `cargo vet --exempt` records exemptions for all of those currently used, to
+//! adopt a policy in an existing workspace.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use toml;
+
+use core::{Dependency, PackageId, Registry, Resolve, Workspace};
+use core::registry::PackageRegistry;
+use ops;
+use sources::SourceConfigMap;
+use util::{paths, CargoResult, Config};
+use util::toml as cargo_toml;
+
+pub const POLICY_FILE: &'static str = "supply-chain.toml";
+
+/// The packages trusted by the supply-chain policy of a workspace.
+pub struct VetPolicy {
+    path: PathBuf,
+    /// The versions audited of each package.
+    audits: HashMap<String, Vec<String>>,
+    /// The publishers trusted with each package.
+    trusted: HashMap<String, Vec<String>>,
+    /// The versions of each package used without having been audited.
+    exemptions: HashMap<String, Vec<String>>,
+}
+
+impl VetPolicy {
+    /// Reads the policy of `ws`, returning `None` if it has none.
+    pub fn load(ws: &Workspace) -> CargoResult<Option<VetPolicy>> {
+        let path = ws.root().join(POLICY_FILE);
+        if !path.exists() {
+            return Ok(None)
+        }
+        let contents = try!(paths::read(&path));
+        let table = toml::Value::Table(try!(cargo_toml::parse(&contents, &path,
+                                                              ws.config())));
+        Ok(Some(VetPolicy {
+            audits: try!(entries(&table, "audits", "version")),
+            trusted: try!(entries(&table, "trusted", "publisher")),
+            exemptions: try!(entries(&table, "exemptions", "version")),
+            path: path,
+        }))
+    }
+}
+
+fn listed(list: &HashMap<String, Vec<String>>, name: &str, value: &str) -> bool {
+    list.get(name).map(|l| l.iter().any(|v| v == value)).unwrap_or(false)
+}
+
+/// Reads the `key` of every table in the arrays of the table `table`, which
+/// are named after the package they're about.
+fn entries(policy: &toml::Value, table: &str, key: &str)
+           -> CargoResult<HashMap<String, Vec<String>>> {
+    let mut ret = HashMap::new();
+    let packages = match policy.lookup(table) {
+        Some(&toml::Value::Table(ref packages)) => packages,
+        Some(_) => bail!("expected `{}` to be a table in `{}`", table, POLICY_FILE),
+        None => return Ok(ret),
+    };
+    for (name, list) in packages.iter() {
+        let list = match list.as_slice() {
+            Some(list) => list,
+            None => bail!("expected `{}.{}` to be an array of tables, as in \
+                           `[[{}.{}]]`", table, name, table, name),
+        };
+        let mut values = Vec::new();
+        for entry in list.iter() {
+            match entry.lookup(key).and_then(|v| v.as_str()) {
+                Some(value) => values.push(value.to_string()),
+                None => bail!("expected every entry of `{}.{}` to have a \
+                               `{}` string", table, name, key),
+            }
+        }
+        ret.insert(name.clone(), values);
+    }
+    Ok(ret)
+}
+
+pub struct VetOptions {
+    /// Whether to exempt the packages which aren't covered by the policy.
+    pub exempt: bool,
+}
+
+/// Fails if `resolve` has packages which aren't covered by the policy of
+/// `ws`, if it has one.
+pub fn check_vetted(ws: &Workspace, resolve: &Resolve) -> CargoResult<()> {
+    let policy = match try!(VetPolicy::load(ws)) {
+        Some(policy) => policy,
+        None => return Ok(()),
+    };
+    let unvetted = try!(unvetted(ws.config(), resolve, &policy));
+    if unvetted.is_empty() {
+        return Ok(())
+    }
+    let list = unvetted.iter().map(|id| format!("  {}", id)).collect::<Vec<_>>();
+    bail!("these packages aren't audited, trusted or exempted by the policy in \
+           `{}`:\n{}\nreview them and record an audit for each, or exempt them \
+           with `cargo vet --exempt`", policy.path.display(), list.join("\n"))
+}
+
+/// Checks the dependencies of the workspace against its policy for
+/// `cargo vet`, exempting those which aren't covered if asked to.
+pub fn vet(ws: &Workspace, opts: &VetOptions) -> CargoResult<()> {
+    let config = ws.config();
+    let mut registry = try!(PackageRegistry::new(config));
+    let resolve = try!(ops::resolve_ws(&mut registry, ws));
+    let path = ws.root().join(POLICY_FILE);
+
+    let policy = match try!(VetPolicy::load(ws)) {
+        Some(policy) => policy,
+        None if opts.exempt => VetPolicy {
+            path: path.clone(),
+            audits: HashMap::new(),
+            trusted: HashMap::new(),
+            exemptions: HashMap::new(),
+        },
+        None => bail!("there's no supply-chain policy in `{}`, adopt one by \
+                       exempting the current dependencies with \
+                       `cargo vet --exempt`", path.display()),
+    };
+    let unvetted = try!(unvetted(config, &resolve, &policy));
+
+    // Exemptions are meant to go away as packages are reviewed or updated.
+    let mut names = policy.exemptions.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        for version in policy.exemptions[name].iter() {
+            let used = resolve.iter().any(|id| {
+                id.name() == *name && id.version().to_string() == *version
+            });
+            if !used || listed(&policy.audits, name, version) {
+                try!(config.shell().warn(format!(
+                    "the exemption of `{} {}` isn't needed anymore and can be \
+                     removed", name, version)));
+            }
+        }
+    }
+
+    if unvetted.is_empty() {
+        return config.shell().status("Vetted", "every dependency is covered \
+                                                by the supply-chain policy")
+    }
+    if !opts.exempt {
+        let list = unvetted.iter().map(|id| format!("  {}", id))
+                           .collect::<Vec<_>>();
+        bail!("these packages aren't audited, trusted or exempted:\n{}\n\
+               review them and record an audit for each, or exempt them with \
+               `cargo vet --exempt`", list.join("\n"))
+    }
+
+    let mut contents = if path.exists() {
+        try!(paths::read(&path))
+    } else {
+        String::new()
+    };
+    for id in unvetted.iter() {
+        if !contents.is_empty() && !contents.ends_with("\n\n") {
+            contents.push_str(if contents.ends_with('\n') { "\n" } else { "\n\n" });
+        }
+        contents.push_str(&format!("[[exemptions.{}]]\nversion = \"{}\"\n",
+                                   id.name(), id.version()));
+        try!(config.shell().status("Exempting", id));
+    }
+    paths::write(&path, contents.as_bytes())
+}
+
+/// The packages of `resolve` which aren't path dependencies and aren't
+/// covered by `policy`.
+fn unvetted(config: &Config, resolve: &Resolve, policy: &VetPolicy)
+            -> CargoResult<Vec<PackageId>> {
+    let sources = try!(SourceConfigMap::new(config));
+    let mut loaded = HashMap::new();
+
+    let mut ids = resolve.iter().filter(|id| {
+        !id.source_id().is_path()
+    }).collect::<Vec<_>>();
+    ids.sort();
+
+    let mut ret = Vec::new();
+    for id in ids {
+        let version = id.version().to_string();
+        if listed(&policy.audits, id.name(), &version) ||
+           listed(&policy.exemptions, id.name(), &version) {
+            continue
+        }
+
+        // Only registries record who published a package.
+        if policy.trusted.contains_key(id.name()) && id.source_id().is_registry() {
+            let source_id = id.source_id().with_precise(None);
+            if !loaded.contains_key(&source_id) {
+                let source = try!(sources.load(&source_id));
+                loaded.insert(source_id.clone(), source);
+            }
+            let source = loaded.get_mut(&source_id).unwrap();
+            let req = format!("={}", version);
+            let dep = try!(Dependency::parse(id.name(), Some(&req), &source_id));
+            let summaries = try!(source.query(&dep));
+            let trusted = summaries.iter().find(|s| s.version() == id.version())
+                                   .and_then(|s| s.publisher())
+                                   .map(|p| listed(&policy.trusted,
+                                                          id.name(), p))
+                                   .unwrap_or(false);
+            if trusted {
+                continue
+            }
+        }
+        ret.push(id.clone());
+    }
+    Ok(ret)
+}
@@ -21,15 +21,22 @@ pub fn load_pkg_lockfile(ws: &Workspace) -> CargoResult<Option<Resolve>> {
         human(format!("failed to read file: {}", f.path().display()))
     }));
 
-    (|| {
+    let resolve = try!((|| {
         let table = try!(cargo_toml::parse(&s, f.path(), ws.config()));
         let table = toml::Value::Table(table);
         let mut d = toml::Decoder::new(table);
         let v: resolver::EncodableResolve = try!(Decodable::decode(&mut d));
-        Ok(Some(try!(v.into_resolve(ws))))
+        v.into_resolve(ws)
     }).chain_error(|| {
         human(format!("failed to parse lock file at: {}", f.path().display()))
-    })
+    }));
+
+    for pkg in resolve.yanked_pins() {
+        try!(ws.config().shell().warn(format!(
+            "`{}` is locked to a version which has been yanked, as pinned \
+             with `cargo update --precise --allow-yanked`", pkg)));
+    }
+    Ok(Some(resolve))
 }
 
 pub fn write_pkg_lockfile(ws: &Workspace, resolve: &Resolve) -> CargoResult<()> {
@@ -1,43 +1,97 @@
+pub use self::cargo_add::{add, AddOptions};
+pub use self::cargo_audit::{check_advisories, AuditAction, AuditPolicy};
+pub use self::cargo_changed::{changed_since, changed_specs};
 pub use self::cargo_clean::{clean, CleanOptions};
-pub use self::cargo_compile::{compile, compile_ws, resolve_dependencies, CompileOptions};
-pub use self::cargo_compile::{CompileFilter, CompileMode};
+pub use self::cargo_complete::{completion_script, completion_values, COMPLETION_KINDS};
+pub use self::cargo_compile::{compile, compile_ws, compile_many, resolve_dependencies};
+pub use self::cargo_compile::CompileOptions;
+pub use self::cargo_compile::{CompileFilter, CompileMode, MessageFormat};
+pub use self::cargo_compile::{scrape_target_config, scrape_package_target_dirs};
+pub use self::cargo_determinism::check_determinism;
+pub use self::cargo_doctor::doctor;
+pub use self::cargo_remove::{remove, RemoveOptions};
 pub use self::cargo_read_manifest::{read_manifest,read_package,read_packages};
 pub use self::cargo_rustc::{compile_targets, Compilation, Layout, Kind, Unit};
-pub use self::cargo_rustc::{Context, LayoutProxy};
-pub use self::cargo_rustc::{BuildOutput, BuildConfig, TargetConfig};
+pub use self::cargo_rustc::{Context, DoctestRunner, LayoutProxy};
+pub use self::cargo_rustc::{BuildOutput, BuildConfig, TargetConfig, SandboxConfig};
+pub use self::cargo_rustc::{OutputTransform, SbomFormat};
+pub use self::cargo_rustc::{TargetRunner, Preset, QemuSystem, parse_preset};
 pub use self::cargo_rustc::{CommandType, CommandPrototype, ExecEngine, ProcessEngine};
 pub use self::cargo_run::run;
-pub use self::cargo_install::{install, install_list, uninstall};
+pub use self::cargo_script::{find_script_or_root_manifest, is_script, script_manifest};
+pub use self::cargo_select::select_specs;
+pub use self::cargo_rust_project::{rust_project, RustProject};
+pub use self::cargo_install::{install, install_list, install_upgrade_all};
+pub use self::cargo_install::{install_from_metadata, uninstall};
 pub use self::cargo_new::{new, init, NewOptions, VersionControl};
 pub use self::cargo_doc::{doc, DocOptions};
 pub use self::cargo_generate_lockfile::{generate_lockfile};
 pub use self::cargo_generate_lockfile::{update_lockfile};
 pub use self::cargo_generate_lockfile::UpdateOptions;
 pub use self::lockfile::{load_pkg_lockfile, write_pkg_lockfile};
-pub use self::cargo_test::{run_tests, run_benches, TestOptions};
-pub use self::cargo_package::{package, PackageOpts};
+pub use self::cargo_task::{run_task, tasks, Task};
+pub use self::cargo_test::{run_tests, run_benches, TestOptions, TestShard};
+pub use self::cargo_tree::{tree, TreeOptions};
+pub use self::cargo_upgrade::{upgrade, upgrade_requirements, UpgradeOptions};
+pub use self::cargo_vet::{check_vetted, vet, VetOptions, VetPolicy};
+pub use self::cargo_package::{package, PackageOpts, ArchiveFormat};
 pub use self::registry::{publish, registry_configuration, RegistryConfig};
-pub use self::registry::{registry_login, search, http_proxy_exists, http_handle};
+pub use self::registry::{registry_login, registry_logout, search};
+pub use self::registry::registry_generate_key;
+pub use self::registry::{http_proxy_exists, http_handle};
 pub use self::registry::{modify_owners, yank, OwnersOptions, PublishOpts};
-pub use self::cargo_fetch::{fetch, get_resolved_packages};
+pub use self::registry::{info, InfoOptions};
+pub use self::cargo_export::{export, ExportOptions, ExportFormat};
+pub use self::cargo_fetch::{fetch, fetch_archive, get_resolved_packages};
+pub use self::cargo_local_registry::local_registry;
+pub use self::cargo_hash_inputs::{hash_inputs, HashInputsOptions};
+pub use self::cargo_pin::{pin_artifacts, unpin_artifacts, list_pinned, pinned_dir};
+pub use self::cargo_pin::PinOptions;
 pub use self::cargo_pkgid::pkgid;
+pub use self::cargo_report::{check_staleness, report_staleness, StalenessOptions};
+pub use self::cargo_report::{StaleDependency, StalenessAction, StalenessPolicy};
+pub use self::cargo_report::{report_outdated, OutdatedDependency, OutdatedOptions};
+pub use self::cargo_resolve::{record_resolve, bench_resolve, ResolveBench};
 pub use self::resolve::{resolve_ws, resolve_with_previous};
 pub use self::cargo_output_metadata::{output_metadata, OutputMetadataOptions, ExportInfo};
+pub use self::cargo_output_metadata::{output_metadata_cached, write_metadata, MetadataFormat};
 
+mod cargo_add;
+mod cargo_audit;
+mod cargo_changed;
 mod cargo_clean;
 mod cargo_compile;
+mod cargo_complete;
+mod cargo_credential;
+mod cargo_determinism;
+mod cargo_doctor;
 mod cargo_doc;
+mod cargo_export;
 mod cargo_fetch;
 mod cargo_generate_lockfile;
+mod cargo_hash_inputs;
 mod cargo_install;
+mod cargo_local_registry;
 mod cargo_new;
 mod cargo_output_metadata;
 mod cargo_package;
+mod cargo_pin;
 mod cargo_pkgid;
+mod cargo_provenance;
 mod cargo_read_manifest;
+mod cargo_remove;
+mod cargo_report;
+mod cargo_resolve;
 mod cargo_run;
+mod cargo_script;
+mod cargo_rust_project;
 mod cargo_rustc;
+mod cargo_select;
+mod cargo_task;
 mod cargo_test;
+mod cargo_tree;
+mod cargo_upgrade;
+mod cargo_vet;
 mod lockfile;
 mod registry;
 mod resolve;
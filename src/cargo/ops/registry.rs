@@ -1,13 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs::{self, File};
 use std::iter::repeat;
-use std::path::PathBuf;
 use std::time::Duration;
 
 use curl::easy::Easy;
 use git2;
 use registry::{Registry, NewCrate, NewCrateDependency};
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json;
+use semver::VersionReq;
 use term::color::BLACK;
 
 use url::percent_encoding::{percent_encode, QUERY_ENCODE_SET};
@@ -18,11 +20,13 @@ use core::dependency::Kind;
 use core::manifest::ManifestMetadata;
 use ops;
 use sources::{RegistrySource};
-use util::config;
+use util::network;
 use util::paths;
-use util::{CargoResult, human, ChainError, ToUrl};
-use util::config::{Config, ConfigValue, Location};
+use util::{CargoResult, human, ChainError, Sha256, ToUrl};
+use util::config::Config;
 use util::important_paths::find_root_manifest_for_wd;
+use super::cargo_credential::{self, CredentialProvider, Operation};
+use super::cargo_provenance::ProvenanceConfig;
 
 pub struct RegistryConfig {
     pub index: Option<String>,
@@ -37,6 +41,8 @@ pub struct PublishOpts<'cfg> {
     pub allow_dirty: bool,
     pub jobs: Option<u32>,
     pub dry_run: bool,
+    /// Whether to upload a signed provenance attestation with the package.
+    pub provenance: bool,
 }
 
 pub fn publish(ws: &Workspace, opts: &PublishOpts) -> CargoResult<()> {
@@ -47,10 +53,13 @@ pub fn publish(ws: &Workspace, opts: &PublishOpts) -> CargoResult<()> {
                `{}` is marked as unpublishable", pkg.name());
     }
 
-    let (mut registry, reg_id) = try!(registry(opts.config,
-                                               opts.token.clone(),
-                                               opts.index.clone()));
+    let reg_id = try!(registry_id(opts.config, opts.index.clone()));
     try!(verify_dependencies(&pkg, &reg_id));
+    let provenance = if opts.provenance {
+        Some(try!(ProvenanceConfig::from_config(opts.config)))
+    } else {
+        None
+    };
 
     // Prepare a tarball, with a non-surpressable warning if metadata
     // is missing since this is being put online.
@@ -61,12 +70,51 @@ pub fn publish(ws: &Workspace, opts: &PublishOpts) -> CargoResult<()> {
         check_metadata: true,
         allow_dirty: opts.allow_dirty,
         jobs: opts.jobs,
+        formats: Vec::new(),
     })).unwrap();
+    let attestation = match provenance {
+        Some(ref provenance) => {
+            let filename = format!("{}-{}.crate", pkg.name(), pkg.version());
+            let crate_path = tarball.parent().join(filename);
+            Some(try!(provenance.attest(ws, &crate_path, &reg_id)))
+        }
+        None => None,
+    };
+
+    // The token may only allow publishing exactly this `.crate` file, so it's
+    // asked for once the file exists.
+    let cksum = {
+        let mut h = Sha256::new();
+        h.update(&try!(paths::read_bytes(tarball.path())));
+        h.finish().to_hex()
+    };
+    let (mut registry, _) = try!(registry(opts.config,
+                                          opts.token.clone(),
+                                          opts.index.clone(),
+                                          &Operation::Publish {
+        name: pkg.name(),
+        vers: &pkg.version().to_string(),
+        cksum: &cksum,
+    }));
 
     // Upload said tarball to the specified destination
     try!(opts.config.shell().status("Uploading", pkg.package_id().to_string()));
     try!(transmit(opts.config, &pkg, tarball.file(), &mut registry, opts.dry_run));
 
+    // The attestation is only uploaded once the registry has the version it's
+    // about.
+    if let Some(attestation) = attestation {
+        if !opts.dry_run {
+            try!(opts.config.shell().status("Uploading", format!(
+                "provenance attestation of {}", pkg.package_id())));
+            try!(registry.publish_attestation(pkg.name(),
+                                              &pkg.version().to_string(),
+                                              &attestation).map_err(|e| {
+                human(e.to_string())
+            }));
+        }
+    }
+
     Ok(())
 }
 
@@ -106,12 +154,14 @@ fn transmit(config: &Config,
                 Kind::Build => "build",
                 Kind::Development => "dev",
             }.to_string(),
+            public: dep.is_public(),
         }
     }).collect::<Vec<NewCrateDependency>>();
     let manifest = pkg.manifest();
     let ManifestMetadata {
         ref authors, ref description, ref homepage, ref documentation,
         ref keywords, ref readme, ref repository, ref license, ref license_file,
+        ref rust_version,
     } = *manifest.metadata();
     let readme = match *readme {
         Some(ref readme) => Some(try!(paths::read(&pkg.root().join(readme)))),
@@ -126,6 +176,20 @@ fn transmit(config: &Config,
         None => {}
     }
 
+    // A feature is described by `[features.metadata]`, or otherwise by the
+    // first line of its `##` comments.
+    let mut feature_descriptions = HashMap::new();
+    for (feature, doc) in manifest.feature_docs().iter() {
+        if let Some(line) = doc.lines().map(|l| l.trim()).find(|l| !l.is_empty()) {
+            feature_descriptions.insert(feature.clone(), line.to_string());
+        }
+    }
+    for (feature, meta) in manifest.feature_metadata().iter() {
+        if let Some(ref description) = meta.description {
+            feature_descriptions.insert(feature.clone(), description.clone());
+        }
+    }
+
     // Do not upload if performing a dry run
     if dry_run {
         try!(config.shell().warn("aborting upload due to dry run"));
@@ -137,6 +201,9 @@ fn transmit(config: &Config,
         vers: pkg.version().to_string(),
         deps: deps,
         features: pkg.summary().features().clone(),
+        conflicts: pkg.summary().conflicts().to_vec(),
+        feature_descriptions: feature_descriptions,
+        rust_version: rust_version.clone(),
         authors: authors.clone(),
         description: description.clone(),
         homepage: homepage.clone(),
@@ -159,16 +226,16 @@ pub fn registry_configuration(config: &Config) -> CargoResult<RegistryConfig> {
 
 pub fn registry(config: &Config,
                 token: Option<String>,
-                index: Option<String>) -> CargoResult<(Registry, SourceId)> {
-    // Parse all configuration options
-    let RegistryConfig {
-        token: token_config,
-        index: _index_config,
-    } = try!(registry_configuration(config));
-    let token = token.or(token_config);
-    let sid = match index {
-        Some(index) => SourceId::for_registry(&try!(index.to_url())),
-        None => try!(SourceId::crates_io(config)),
+                index: Option<String>,
+                operation: &Operation) -> CargoResult<(Registry, SourceId)> {
+    let sid = try!(registry_id(config, index));
+    try!(network::check_allowed(config, sid.url()));
+    let token = match token {
+        Some(token) => Some(token),
+        None => {
+            let provider = try!(CredentialProvider::from_config(config));
+            try!(provider.token(config, &sid, operation))
+        }
     };
     let api_host = {
         let mut src = RegistrySource::remote(&sid, config);
@@ -181,6 +248,14 @@ pub fn registry(config: &Config,
     Ok((Registry::new_handle(api_host, token, handle), sid))
 }
 
+/// The registry at `index`, or crates.io.
+fn registry_id(config: &Config, index: Option<String>) -> CargoResult<SourceId> {
+    match index {
+        Some(index) => Ok(SourceId::for_registry(&try!(index.to_url()))),
+        None => SourceId::crates_io(config),
+    }
+}
+
 /// Create a new HTTP handle with appropriate global configuration for cargo.
 pub fn http_handle(config: &Config) -> CargoResult<Easy> {
     if !config.network_allowed() {
@@ -258,19 +333,25 @@ pub fn http_timeout(config: &Config) -> CargoResult<Option<i64>> {
 }
 
 pub fn registry_login(config: &Config, token: String) -> CargoResult<()> {
-    let RegistryConfig { index, token: _ } = try!(registry_configuration(config));
-    let mut map = HashMap::new();
-    let p = config.cwd().to_path_buf();
-    match index {
-        Some(index) => {
-            map.insert("index".to_string(), ConfigValue::String(index, p.clone()));
-        }
-        None => {}
-    }
-    map.insert("token".to_string(), ConfigValue::String(token, p));
+    let provider = try!(CredentialProvider::from_config(config));
+    provider.login(config, &try!(default_registry(config)), &token)
+}
+
+/// Makes a new key to sign the requests made to the registry with, returning
+/// its public key.
+pub fn registry_generate_key(config: &Config, key_id: Option<String>)
+                             -> CargoResult<Vec<u8>> {
+    cargo_credential::generate_key(config, key_id)
+}
+
+pub fn registry_logout(config: &Config) -> CargoResult<()> {
+    let provider = try!(CredentialProvider::from_config(config));
+    provider.logout(config, &try!(default_registry(config)))
+}
 
-    config::set_config(config, Location::Global, "registry",
-                       ConfigValue::Table(map, PathBuf::from(".")))
+/// The registry configured with `registry.index`, or crates.io.
+fn default_registry(config: &Config) -> CargoResult<SourceId> {
+    registry_id(config, try!(registry_configuration(config)).index)
 }
 
 pub struct OwnersOptions {
@@ -293,7 +374,8 @@ pub fn modify_owners(config: &Config, opts: &OwnersOptions) -> CargoResult<()> {
     };
 
     let (mut registry, _) = try!(registry(config, opts.token.clone(),
-                                          opts.index.clone()));
+                                          opts.index.clone(),
+                                          &Operation::Owners { name: &name }));
 
     match opts.to_add {
         Some(ref v) => {
@@ -356,7 +438,12 @@ pub fn yank(config: &Config,
         None => bail!("a version must be specified to yank")
     };
 
-    let (mut registry, _) = try!(registry(config, token, index));
+    let operation = if undo {
+        Operation::Unyank { name: &name, vers: &version }
+    } else {
+        Operation::Yank { name: &name, vers: &version }
+    };
+    let (mut registry, _) = try!(registry(config, token, index, &operation));
 
     if undo {
         try!(config.shell().status("Unyank", format!("{}:{}", name, version)));
@@ -385,7 +472,7 @@ pub fn search(query: &str,
         }
     }
 
-    let (mut registry, _) = try!(registry(config, None, index));
+    let (mut registry, _) = try!(registry(config, None, index, &Operation::Read));
     let (crates, total_crates) = try!(registry.search(query, limit).map_err(|e| {
         human(format!("failed to retrieve search results from the registry: {}", e))
     }));
@@ -434,3 +521,219 @@ pub fn search(query: &str,
 
     Ok(())
 }
+
+pub struct InfoOptions<'a> {
+    /// The name of the crate, optionally followed by `@` and a version
+    /// requirement selecting the version to describe.
+    pub krate: &'a str,
+    pub index: Option<String>,
+    pub format: ops::MessageFormat,
+}
+
+#[derive(RustcEncodable)]
+struct CrateInfo {
+    name: String,
+    version: String,
+    versions: Vec<VersionInfo>,
+    features: BTreeMap<String, Vec<String>>,
+    feature_descriptions: BTreeMap<String, String>,
+    dependencies: Vec<DependencyInfo>,
+    owners: Option<Vec<String>>,
+    locked: Vec<String>,
+}
+
+#[derive(RustcEncodable)]
+struct VersionInfo {
+    num: String,
+    yanked: bool,
+    rust_version: Option<String>,
+    published: Option<u64>,
+}
+
+#[derive(RustcEncodable)]
+struct DependencyInfo {
+    name: String,
+    req: String,
+    kind: Kind,
+    optional: bool,
+    target: Option<String>,
+}
+
+/// Describes a crate of the registry: its versions, and the features,
+/// dependencies and owners of the newest one, or of the newest one matching
+/// the requirement given. The versions of the crate locked by the lockfile of
+/// `ws` are listed as well.
+///
+/// Everything but the owners comes from the index. The owners are asked from
+/// the API of the registry, and are left out with a warning if that fails.
+pub fn info(config: &Config,
+            opts: &InfoOptions,
+            ws: Option<&Workspace>) -> CargoResult<()> {
+    let (name, req) = match opts.krate.find('@') {
+        Some(at) => (&opts.krate[..at], Some(&opts.krate[at + 1..])),
+        None => (opts.krate, None),
+    };
+    let req = match req {
+        Some(req) => Some(try!(VersionReq::parse(req).map_err(|e| {
+            human(format!("invalid version requirement `{}`: {}", req, e))
+        }))),
+        None => None,
+    };
+    let sid = match opts.index {
+        Some(ref index) => SourceId::for_registry(&try!(index.to_url())),
+        None => try!(SourceId::crates_io(config)),
+    };
+    let mut src = RegistrySource::remote(&sid, config);
+    try!(src.update().chain_error(|| {
+        human(format!("failed to update {}", sid))
+    }));
+    let mut versions = try!(src.versions(name));
+    if versions.is_empty() {
+        bail!("no crate named `{}` was found in `{}`", name, sid.url())
+    }
+    versions.sort_by(|a, b| b.0.version().cmp(a.0.version()));
+
+    // Yanked versions and pre-releases are only described when nothing else
+    // matches.
+    let matching = versions.iter().filter(|&&(ref s, _)| {
+        req.as_ref().map(|r| r.matches(s.version())).unwrap_or(true)
+    }).collect::<Vec<_>>();
+    let summary = match matching.iter().find(|&&&(ref s, yanked)| {
+        !yanked && s.version().pre.is_empty()
+    }).or(matching.first()) {
+        Some(&&(ref summary, _)) => summary,
+        None => bail!("no version of `{}` matches `{}`", name,
+                      req.as_ref().unwrap()),
+    };
+
+    let owners = match list_owners(config, &sid, &src, name) {
+        Ok(owners) => Some(owners),
+        Err(e) => {
+            try!(config.shell().warn(format!("the owners of `{}` couldn't be \
+                                              listed: {}", name, e)));
+            None
+        }
+    };
+    let mut locked = Vec::new();
+    if let Some(ws) = ws {
+        if let Some(resolve) = try!(ops::load_pkg_lockfile(ws)) {
+            locked.extend(resolve.iter().filter(|id| {
+                id.name() == name && *id.source_id() == sid
+            }).map(|id| id.version().to_string()));
+        }
+    }
+
+    let mut dependencies = summary.dependencies().iter().map(|d| {
+        DependencyInfo {
+            name: d.name().to_string(),
+            req: d.version_req().to_string(),
+            kind: d.kind(),
+            optional: d.is_optional(),
+            target: d.platform().map(|p| p.to_string()),
+        }
+    }).collect::<Vec<_>>();
+    dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+    let info = CrateInfo {
+        name: name.to_string(),
+        version: summary.version().to_string(),
+        versions: versions.iter().map(|&(ref s, yanked)| {
+            VersionInfo {
+                num: s.version().to_string(),
+                yanked: yanked,
+                rust_version: s.rust_version().map(|s| s.to_string()),
+                published: s.published(),
+            }
+        }).collect(),
+        features: summary.features().iter().map(|(k, v)| {
+            (k.clone(), v.clone())
+        }).collect(),
+        feature_descriptions: summary.feature_descriptions().iter().map(|(k, v)| {
+            (k.clone(), v.clone())
+        }).collect(),
+        dependencies: dependencies,
+        owners: owners,
+        locked: locked,
+    };
+
+    if opts.format == ops::MessageFormat::Json {
+        println!("{}", json::encode(&info).unwrap());
+        return Ok(())
+    }
+    println!("{} {}", info.name, info.version);
+    println!("versions:");
+    for version in info.versions.iter() {
+        let mut notes = Vec::new();
+        if let Some(ref rust_version) = version.rust_version {
+            notes.push(format!("rust {}", rust_version));
+        }
+        if version.yanked {
+            notes.push("yanked".to_string());
+        }
+        println!("    {}{}", version.num, if notes.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", notes.join(", "))
+        });
+    }
+    if !info.features.is_empty() {
+        println!("features:");
+        for (feature, enables) in info.features.iter() {
+            let enables = enables.iter().map(|f| format!("\"{}\"", f))
+                                 .collect::<Vec<_>>();
+            match info.feature_descriptions.get(feature) {
+                Some(description) => {
+                    println!("    {} = [{}]  # {}", feature, enables.join(", "),
+                             description)
+                }
+                None => println!("    {} = [{}]", feature, enables.join(", ")),
+            }
+        }
+    }
+    if !info.dependencies.is_empty() {
+        println!("dependencies:");
+        for dep in info.dependencies.iter() {
+            let mut notes = Vec::new();
+            match dep.kind {
+                Kind::Normal => {}
+                Kind::Development => notes.push("dev".to_string()),
+                Kind::Build => notes.push("build".to_string()),
+            }
+            if dep.optional {
+                notes.push("optional".to_string());
+            }
+            if let Some(ref target) = dep.target {
+                notes.push(format!("for {}", target));
+            }
+            println!("    {} = \"{}\"{}", dep.name, dep.req, if notes.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", notes.join(", "))
+            });
+        }
+    }
+    if let Some(ref owners) = info.owners {
+        println!("owners: {}", owners.join(", "));
+    }
+    if !info.locked.is_empty() {
+        println!("locked: {}", info.locked.join(", "));
+    }
+    Ok(())
+}
+
+fn list_owners(config: &Config,
+               sid: &SourceId,
+               src: &RegistrySource,
+               name: &str) -> CargoResult<Vec<String>> {
+    let api = match try!(src.config()) {
+        Some(cfg) => cfg.api,
+        None => bail!("the registry has no API"),
+    };
+    try!(network::check_allowed(config, &try!(api.to_url())));
+    let provider = try!(CredentialProvider::from_config(config));
+    let token = try!(provider.token(config, sid, &Operation::Read));
+    let mut registry = Registry::new_handle(api, token, try!(http_handle(config)));
+    let owners = try!(registry.list_owners(name).map_err(|e| {
+        human(format!("{}", e))
+    }));
+    Ok(owners.into_iter().map(|o| o.login).collect())
+}
@@ -1,10 +1,16 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use core::{PackageId, SourceId, Workspace};
+use rustc_serialize::json;
+
+use core::{Dependency, DependencyInner, PackageId, PackageIdSpec, Registry};
+use core::{SourceId, Summary, Workspace};
+use core::dependency::Kind;
 use core::registry::PackageRegistry;
-use core::resolver::{self, Resolve, Method};
+use core::resolver::{self, Resolve, Method, StoredResolve, VersionOrder};
 use ops;
-use util::CargoResult;
+use util::{paths, short_hash, CargoResult, Config};
 
 /// Resolve all dependencies for the specified `package` using the previous
 /// lockfile as a guide if present.
@@ -14,9 +20,30 @@ use util::CargoResult;
 pub fn resolve_ws(registry: &mut PackageRegistry, ws: &Workspace)
                    -> CargoResult<Resolve> {
     let prev = try!(ops::load_pkg_lockfile(ws));
-    let resolve = try!(resolve_with_previous(registry, ws,
-                                             Method::Everything,
-                                             prev.as_ref(), None));
+    let incremental = match prev {
+        Some(ref prev) if !ws.is_partial() => {
+            try!(resolve_incrementally(registry, ws, prev))
+        }
+        _ => None,
+    };
+    let resolve = match incremental {
+        Some(resolve) => resolve,
+        None => try!(resolve_with_previous(registry, ws,
+                                           Method::Everything,
+                                           VersionOrder::Newest,
+                                           prev.as_ref(), None)),
+    };
+
+    // A partially loaded workspace only resolves some of its members, which
+    // the lockfile has to cover already as it isn't written.
+    if ws.is_partial() {
+        let locked = prev.iter().flat_map(|r| r.iter()).collect::<HashSet<_>>();
+        if let Some(id) = resolve.iter().find(|id| !locked.contains(id)) {
+            bail!("the lockfile needs to be updated for `{}`, run `cargo \
+                   fetch` to update it", id)
+        }
+        return Ok(resolve)
+    }
 
     // Avoid writing a lockfile if we are `cargo install`ing a non local package.
     if ws.current_opt().map(|pkg| pkg.package_id().source_id().is_path()).unwrap_or(true) {
@@ -25,6 +52,204 @@ pub fn resolve_ws(registry: &mut PackageRegistry, ws: &Workspace)
     Ok(resolve)
 }
 
+/// Brings the previous resolve of a workspace up to date with edits to the
+/// dependencies of its members without resolving everything again.
+///
+/// Only the dependencies of members which the previous resolve no longer
+/// satisfies, because their requirement changed or because they ask for
+/// features needing packages which aren't locked, are resolved, with every
+/// package still reachable through the other dependencies locked. The result
+/// is joined with those packages, which are left exactly as they were.
+///
+/// Returns `None` when the dependencies of the members are still those
+/// locked, or when the change can't be confined like this, in which case the
+/// whole workspace has to be resolved.
+fn resolve_incrementally(registry: &mut PackageRegistry,
+                         ws: &Workspace,
+                         previous: &Resolve) -> CargoResult<Option<Resolve>> {
+    if !ws.root_replace().is_empty() || !previous.replacements().is_empty() {
+        return Ok(None)
+    }
+    let members = ws.members().map(|m| m.package_id().clone())
+                    .collect::<HashSet<_>>();
+    // The manifests of path dependencies outside of the workspace may have
+    // been edited as well.
+    if previous.iter().any(|id| id.source_id().is_path() && !members.contains(id)) {
+        return Ok(None)
+    }
+
+    // Most of the time the manifests weren't edited, which is told from the
+    // lockfile alone, before querying anything: each dependency is still
+    // locked to a package it matches, and each package locked is still
+    // depended on. Edits to the features asked of a dependency aren't told
+    // apart from no edit, so they're left to the full resolve.
+    let mut edited = false;
+    for member in ws.members() {
+        let id = member.package_id();
+        if !previous.iter().any(|p| p == id) {
+            return Ok(None)
+        }
+        let locked = previous.deps_not_replaced(id).collect::<Vec<_>>();
+        edited = edited || member.dependencies().iter().any(|dep| {
+            !locked.iter().any(|lock| dep.matches_id(lock))
+        }) || locked.iter().any(|lock| {
+            !member.dependencies().iter().any(|dep| dep.matches_id(lock))
+        });
+    }
+    if !edited {
+        return Ok(None)
+    }
+
+    let mut roots = HashMap::new();
+    let mut changed = Vec::new();
+    let mut dirty = false;
+    for member in ws.members() {
+        let id = member.package_id();
+        try!(registry.add_sources(&[id.source_id().clone()]));
+
+        let locked = previous.deps_not_replaced(id).map(|d| {
+            (d.name(), d)
+        }).collect::<HashMap<_, _>>();
+        let mut kept = Vec::new();
+        let mut unsatisfied = Vec::new();
+        for dep in member.dependencies() {
+            let lock = match locked.get(dep.name()) {
+                Some(&lock) if dep.matches_id(lock) => Some(lock),
+                _ => None,
+            };
+            let satisfied = match lock {
+                Some(lock) => try!(features_locked(registry, previous, lock, dep)),
+                None => false,
+            };
+            if satisfied {
+                kept.push(lock.unwrap().clone());
+            } else {
+                // Optional dependencies are activated when resolving
+                // everything, so they're just required here.
+                unsatisfied.push(dep.clone_inner().set_optional(false)
+                                    .into_dependency());
+            }
+        }
+        dirty = dirty || !unsatisfied.is_empty() ||
+                locked.values().any(|lock| !kept.contains(*lock));
+        if !unsatisfied.is_empty() {
+            changed.push((try!(Summary::new(id.clone(), unsatisfied,
+                                            HashMap::new())),
+                          Method::Everything));
+        }
+        roots.insert(id.clone(), kept);
+    }
+    if !dirty {
+        return Ok(None)
+    }
+
+    // Everything reachable from the members through what they still depend
+    // on is kept as it is.
+    let mut kept = members.clone();
+    let mut todo = roots.values().flat_map(|deps| deps.iter()).cloned()
+                        .collect::<Vec<_>>();
+    while let Some(id) = todo.pop() {
+        if kept.insert(id.clone()) {
+            todo.extend(previous.deps_not_replaced(&id).cloned());
+        }
+    }
+    let sources = kept.iter().map(|id| id.source_id().clone())
+                      .collect::<HashSet<_>>().into_iter().collect::<Vec<_>>();
+    try!(registry.add_sources(&sources));
+
+    let partial = if changed.is_empty() {
+        None
+    } else {
+        for id in kept.iter().filter(|id| !members.contains(*id)) {
+            let deps = previous.deps_not_replaced(id).filter(|d| {
+                kept.contains(*d)
+            }).cloned().collect();
+            registry.register_lock(id.clone(), deps);
+        }
+        // Failing here doesn't mean resolving everything fails too, which
+        // also reports the error properly if it does.
+        let resolve = match resolver::resolve(&changed, &[],
+                                              VersionOrder::Newest,
+                                              registry) {
+            Ok(resolve) => resolve,
+            Err(..) => return Ok(None),
+        };
+        // The graph may only hold one version of a package among those which
+        // are semver compatible.
+        for id in resolve.iter().filter(|id| !kept.contains(*id)) {
+            if kept.iter().any(|k| {
+                k.name() == id.name() && k.source_id() == id.source_id() &&
+                    resolver::compatible(k.version(), id.version())
+            }) {
+                return Ok(None)
+            }
+        }
+        Some(resolve)
+    };
+
+    // Git checkouts are checksummed again, as resolving would.
+    let mut checksums = HashMap::new();
+    for id in kept.iter().filter(|id| id.source_id().is_git()) {
+        let dep = try!(Dependency::parse(id.name(), None, id.source_id()))
+                      .lock_to(id);
+        let summary = try!(registry.query(&dep)).into_iter().find(|s| {
+            s.package_id() == id
+        });
+        checksums.insert(id.clone(), summary.and_then(|s| {
+            s.checksum().map(|c| c.to_string())
+        }));
+    }
+
+    let mut resolve = previous.splice(&kept, &roots, partial.as_ref(), checksums);
+    try!(resolve.merge_from(previous));
+    Ok(Some(resolve))
+}
+
+/// Returns whether the features `dep` asks of the locked package `id` only
+/// enable dependencies which `id` is already locked with, following features
+/// of its dependencies such as `bar/feature` as well.
+fn features_locked(registry: &mut PackageRegistry,
+                   previous: &Resolve,
+                   id: &PackageId,
+                   dep: &Dependency) -> CargoResult<bool> {
+    let mut features = dep.features().to_vec();
+    if dep.uses_default_features() {
+        features.push("default".to_string());
+    }
+    let mut todo = vec![(id.clone(), features)];
+    let mut seen = HashSet::new();
+    while let Some((id, mut features)) = todo.pop() {
+        let query = try!(Dependency::parse(id.name(), None, id.source_id()))
+                        .lock_to(&id);
+        let summary = match try!(registry.query(&query)).into_iter().find(|s| {
+            s.package_id() == &id
+        }) {
+            Some(summary) => summary,
+            None => return Ok(false),
+        };
+        let locked = previous.deps_not_replaced(&id).collect::<Vec<_>>();
+        while let Some(feature) = features.pop() {
+            if !seen.insert((id.clone(), feature.clone())) {
+                continue
+            }
+            let mut parts = feature.splitn(2, '/');
+            let name = parts.next().unwrap();
+            if let Some(sub) = parts.next() {
+                match locked.iter().find(|d| d.name() == name) {
+                    Some(d) => todo.push(((**d).clone(), vec![sub.to_string()])),
+                    None => return Ok(false),
+                }
+            } else if let Some(implied) = summary.features().get(name) {
+                features.extend(implied.iter().cloned());
+            } else if summary.dependencies().iter().any(|d| d.name() == name) &&
+                      !locked.iter().any(|d| d.name() == name) {
+                return Ok(false)
+            }
+        }
+    }
+    Ok(true)
+}
+
 /// Resolve all dependencies for a package using an optional previous instance
 /// of resolve to guide the resolution process.
 ///
@@ -34,9 +259,13 @@ pub fn resolve_ws(registry: &mut PackageRegistry, ws: &Workspace)
 ///
 /// The previous resolve normally comes from a lockfile. This function does not
 /// read or write lockfiles from the filesystem.
+///
+/// `order` is which versions of the dependencies which aren't locked are
+/// tried first.
 pub fn resolve_with_previous<'a>(registry: &mut PackageRegistry,
                                  ws: &Workspace,
                                  method: Method,
+                                 order: VersionOrder,
                                  previous: Option<&'a Resolve>,
                                  to_avoid: Option<&HashSet<&'a PackageId>>)
                                  -> CargoResult<Resolve> {
@@ -147,7 +376,8 @@ pub fn resolve_with_previous<'a>(registry: &mut PackageRegistry,
         None => root_replace.to_vec(),
     };
 
-    let mut resolved = try!(resolver::resolve(&summaries, &replace, registry));
+    let mut resolved = try!(resolve_cached(ws.config(), &summaries, &replace,
+                                           order, registry));
     if let Some(previous) = previous {
         try!(resolved.merge_from(previous));
     }
@@ -163,3 +393,244 @@ pub fn resolve_with_previous<'a>(registry: &mut PackageRegistry,
         }
     }
 }
+
+/// Resolves the dependencies of `summaries`, reusing the resolution of the
+/// same requirements by a previous invocation of Cargo as long as the
+/// registry still answers each query the resolver made then the same way.
+///
+/// Resolutions are stored in `$CARGO_HOME/resolve-cache`, keyed by the
+/// requirements. The answers to the queries amount to a snapshot of the
+/// indices involved, as well as of the locked and overridden packages. The
+/// cache is only used when enabled with `build.resolve-cache`.
+fn resolve_cached(config: &Config,
+                  summaries: &[(Summary, Method)],
+                  replace: &[(PackageIdSpec, Dependency)],
+                  order: VersionOrder,
+                  registry: &mut PackageRegistry) -> CargoResult<Resolve> {
+    let enabled = try!(config.get_bool("build.resolve-cache"))
+                        .map(|v| v.val).unwrap_or(false);
+    if !enabled {
+        return resolver::resolve(summaries, replace, order, registry)
+    }
+
+    let mut requirements = vec![format!("{:?}", order)];
+    for &(ref summary, method) in summaries.iter() {
+        requirements.push(try!(json::encode(&RecordedSummary::new(summary))));
+        requirements.push(match method {
+            Method::Everything => "everything".to_string(),
+            Method::Required { dev_deps, features, uses_default_features } => {
+                format!("required {} {} {:?}", dev_deps, uses_default_features,
+                        features)
+            }
+        });
+    }
+    for &(ref spec, ref dep) in replace.iter() {
+        requirements.push(spec.to_string());
+        requirements.push(try!(json::encode(&RecordedDependency::new(dep))));
+    }
+    let file = config.home().join("resolve-cache").into_path_unlocked()
+                     .join(short_hash(&requirements));
+
+    if let Some(entry) = load_cache(&file) {
+        let mut fresh = true;
+        for &(ref dep, ref answer) in entry.queries.iter() {
+            let dep = match dep.to_dependency() {
+                Ok(dep) => dep,
+                Err(..) => { fresh = false; break }
+            };
+            if try!(hash_answer(&try!(registry.query(&dep)))) != *answer {
+                fresh = false;
+                break
+            }
+        }
+        if fresh {
+            return Ok(Resolve::from_stored(entry.resolve))
+        }
+        debug!("resolve cache {} is stale", file.display());
+    }
+
+    let (resolve, queries) = {
+        let mut recorder = Recorder::new(registry);
+        let resolve = try!(resolver::resolve(summaries, replace, order,
+                                             &mut recorder));
+        (resolve, recorder.queries)
+    };
+    let mut entry = CacheEntry {
+        queries: Vec::new(),
+        resolve: resolve.to_stored(),
+    };
+    for (dep, answer) in queries {
+        entry.queries.push((dep, try!(hash_answer(&answer))));
+    }
+    // Failing to write the cache only makes the next resolution slower.
+    if let Err(e) = store_cache(&file, &entry) {
+        debug!("failed to write resolve cache {}: {}", file.display(), e);
+    }
+    Ok(resolve)
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct CacheEntry {
+    /// Every distinct query made by the resolver along with the hash of the
+    /// summaries it was answered with.
+    queries: Vec<(RecordedDependency, String)>,
+    resolve: StoredResolve,
+}
+
+fn hash_answer(summaries: &[Summary]) -> CargoResult<String> {
+    let mut encoded = Vec::new();
+    for summary in summaries {
+        encoded.push(try!(json::encode(&RecordedSummary::new(summary))));
+    }
+    encoded.sort();
+    Ok(short_hash(&encoded))
+}
+
+fn load_cache(file: &Path) -> Option<CacheEntry> {
+    paths::read(file).ok().and_then(|contents| json::decode(&contents).ok())
+}
+
+fn store_cache(file: &Path, entry: &CacheEntry) -> CargoResult<()> {
+    let tmp = PathBuf::from(format!("{}.tmp", file.display()));
+    try!(fs::create_dir_all(file.parent().unwrap()));
+    try!(paths::write(&tmp, try!(json::encode(entry)).as_bytes()));
+    try!(fs::rename(&tmp, file));
+    Ok(())
+}
+
+/// Passes queries on to a registry, keeping each distinct one along with the
+/// summaries it was answered with.
+pub struct Recorder<'a> {
+    registry: &'a mut Registry,
+    seen: HashSet<String>,
+    pub queries: Vec<(RecordedDependency, Vec<Summary>)>,
+}
+
+impl<'a> Recorder<'a> {
+    pub fn new(registry: &'a mut Registry) -> Recorder<'a> {
+        Recorder {
+            registry: registry,
+            seen: HashSet::new(),
+            queries: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Registry for Recorder<'a> {
+    fn query(&mut self, dep: &Dependency) -> CargoResult<Vec<Summary>> {
+        let ret = try!(self.registry.query(dep));
+        let recorded = RecordedDependency::new(dep);
+        if self.seen.insert(try!(json::encode(&recorded))) {
+            self.queries.push((recorded, ret.clone()));
+        }
+        Ok(ret)
+    }
+
+    fn supports_checksums(&self) -> bool {
+        self.registry.supports_checksums()
+    }
+}
+
+/// A dependency in a form which can be stored and turned back into the
+/// dependency it was made from.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct RecordedDependency {
+    name: String,
+    source: String,
+    precise: Option<String>,
+    req: Option<String>,
+    kind: String,
+    optional: bool,
+    default_features: bool,
+    features: Vec<String>,
+    target: Option<String>,
+}
+
+impl RecordedDependency {
+    pub fn new(dep: &Dependency) -> RecordedDependency {
+        RecordedDependency {
+            name: dep.name().to_string(),
+            source: dep.source_id().to_url(),
+            precise: dep.source_id().precise().map(|s| s.to_string()),
+            req: if dep.specified_req() {
+                Some(dep.version_req().to_string())
+            } else {
+                None
+            },
+            kind: match dep.kind() {
+                Kind::Normal => "normal",
+                Kind::Development => "dev",
+                Kind::Build => "build",
+            }.to_string(),
+            optional: dep.is_optional(),
+            default_features: dep.uses_default_features(),
+            features: dep.features().to_vec(),
+            target: dep.platform().map(|p| p.to_string()),
+        }
+    }
+
+    pub fn to_dependency(&self) -> CargoResult<Dependency> {
+        let source_id = try!(SourceId::from_url(&self.source))
+                            .with_precise(self.precise.clone());
+        let req = self.req.as_ref().map(|s| &s[..]);
+        let dep = try!(DependencyInner::parse(&self.name, req, &source_id));
+        let kind = match &self.kind[..] {
+            "dev" => Kind::Development,
+            "build" => Kind::Build,
+            _ => Kind::Normal,
+        };
+        let platform = match self.target {
+            Some(ref target) => Some(try!(target.parse())),
+            None => None,
+        };
+        Ok(dep.set_optional(self.optional)
+              .set_default_features(self.default_features)
+              .set_features(self.features.clone())
+              .set_platform(platform)
+              .set_kind(kind)
+              .into_dependency())
+    }
+}
+
+/// A summary in a form which can be stored and turned back into the summary
+/// it was made from.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct RecordedSummary {
+    id: PackageId,
+    dependencies: Vec<RecordedDependency>,
+    features: BTreeMap<String, Vec<String>>,
+    checksum: Option<String>,
+}
+
+impl RecordedSummary {
+    pub fn new(summary: &Summary) -> RecordedSummary {
+        RecordedSummary {
+            id: summary.package_id().clone(),
+            dependencies: summary.dependencies().iter()
+                                 .map(RecordedDependency::new).collect(),
+            features: summary.features().iter().map(|(k, v)| {
+                (k.clone(), v.clone())
+            }).collect(),
+            checksum: summary.checksum().map(|s| s.to_string()),
+        }
+    }
+
+    pub fn package_id(&self) -> &PackageId {
+        &self.id
+    }
+
+    pub fn to_summary(&self) -> CargoResult<Summary> {
+        let mut deps = Vec::new();
+        for dep in self.dependencies.iter() {
+            deps.push(try!(dep.to_dependency()));
+        }
+        let features = self.features.iter().map(|(k, v)| {
+            (k.clone(), v.clone())
+        }).collect();
+        let summary = try!(Summary::new(self.id.clone(), deps, features));
+        Ok(match self.checksum {
+            Some(ref cksum) => summary.set_checksum(cksum.clone()),
+            None => summary,
+        })
+    }
+}
@@ -17,6 +17,9 @@ use util::config::ConfigValue;
 pub struct SourceConfigMap<'cfg> {
     cfgs: HashMap<String, SourceConfig>,
     id2name: HashMap<SourceId, String>,
+    // Name of the source every git repository is replaced with, by
+    // `--dep-archive`.
+    git_replacement: Option<String>,
     config: &'cfg Config,
 }
 
@@ -26,6 +29,7 @@ pub struct SourceConfigMap<'cfg> {
 /// [source.crates-io]
 /// registry = 'https://github.com/rust-lang/crates.io-index'
 /// replace-with = 'foo'    # optional
+/// signing-keys = ['keys/crates-io.pem']    # optional
 /// ```
 struct SourceConfig {
     // id this source corresponds to, inferred from the various defined keys in
@@ -36,6 +40,10 @@ struct SourceConfig {
     // is a tuple of (name, path) where path is where this configuration key was
     // defined (the literal `.cargo/config` file).
     replace_with: Option<(String, PathBuf)>,
+
+    // Public keys the packages of this registry have to be signed with, if
+    // its signatures are checked.
+    signing_keys: Option<Vec<PathBuf>>,
 }
 
 impl<'cfg> SourceConfigMap<'cfg> {
@@ -46,6 +54,13 @@ impl<'cfg> SourceConfigMap<'cfg> {
                 try!(base.add_config(key, value));
             }
         }
+        if let Some(dir) = config.vendored() {
+            try!(base.add_vendored("--vendored", &dir));
+        }
+        if let Some(dir) = config.dep_archive() {
+            try!(base.add_vendored("--dep-archive", &dir));
+            base.git_replacement = Some("--dep-archive".to_string());
+        }
         Ok(base)
     }
 
@@ -53,21 +68,83 @@ impl<'cfg> SourceConfigMap<'cfg> {
         let mut base = SourceConfigMap {
             cfgs: HashMap::new(),
             id2name: HashMap::new(),
+            git_replacement: None,
             config: config,
         };
         base.add("crates-io", SourceConfig {
             id: try!(SourceId::crates_io(config)),
             replace_with: None,
+            signing_keys: None,
         });
         Ok(base)
     }
 
+    /// The configured sources by name, along with the name of the source each
+    /// one is replaced with, if any.
+    pub fn configured(&self) -> Vec<(&str, &SourceId, Option<&str>)> {
+        let mut ret = self.cfgs.iter().map(|(name, cfg)| {
+            (&name[..], &cfg.id, cfg.replace_with.as_ref().map(|r| &r.0[..]))
+        }).collect::<Vec<_>>();
+        ret.sort_by(|a, b| a.0.cmp(b.0));
+        ret
+    }
+
+    /// The public keys configured for the registry `id`, which the packages
+    /// it serves have to be signed with, if its signatures are checked.
+    pub fn signing_keys(&self, id: &SourceId) -> Option<&[PathBuf]> {
+        self.id2name.get(id).and_then(|name| self.cfgs.get(name))
+            .and_then(|cfg| cfg.signing_keys.as_ref())
+            .map(|keys| &keys[..])
+    }
+
+    /// The source which is actually used in place of `id`.
+    pub fn replacement(&self, id: &SourceId) -> CargoResult<SourceId> {
+        Ok(match try!(self.follow(id)) {
+            Some((_, new_id)) => new_id,
+            None => id.clone(),
+        })
+    }
+
     pub fn load(&self, id: &SourceId) -> CargoResult<Box<Source + 'cfg>> {
         debug!("loading: {}", id);
-        let mut name = match self.id2name.get(id) {
-            Some(name) => name,
+        let (name, new_id) = match try!(self.follow(id)) {
+            Some(replacement) => replacement,
             None => return Ok(id.load(self.config)),
         };
+        let new_src = new_id.load(self.config);
+        // The checksums of packages from git repositories are in the lockfile
+        // all the same, even though git sources don't support checksums.
+        let orig_name = match self.id2name.get(id) {
+            Some(name) => name,
+            None => {
+                return Ok(Box::new(ReplacedSource::new(id, &new_id, new_src)))
+            }
+        };
+        let old_src = id.load(self.config);
+        if new_src.supports_checksums() != old_src.supports_checksums() {
+            let (supports, no_support) = if new_src.supports_checksums() {
+                (name, orig_name)
+            } else {
+                (orig_name, name)
+            };
+            bail!("\
+cannot replace `{orig}` with `{name}`, the source `{supports}` supports \
+checksums, but `{no_support}` does not
+
+a lock file compatible with `{orig}` cannot be generated in this situation
+", orig = orig_name, name = name, supports = supports, no_support = no_support);
+        }
+        Ok(Box::new(ReplacedSource::new(id, &new_id, new_src)))
+    }
+
+    /// Follows the `replace-with` keys starting at the source `id`, returning
+    /// the name and id of the source it is eventually replaced with.
+    fn follow(&self, id: &SourceId) -> CargoResult<Option<(&str, SourceId)>> {
+        let mut name = match (self.id2name.get(id), &self.git_replacement) {
+            (Some(name), _) => name,
+            (None, &Some(ref name)) if id.is_git() => name,
+            (None, _) => return Ok(None),
+        };
         let mut path = Path::new("/");
         let orig_name = name;
         let new_id;
@@ -84,7 +161,7 @@ impl<'cfg> SourceConfigMap<'cfg> {
                     name = s;
                     path = p;
                 }
-                None if *id == cfg.id => return Ok(id.load(self.config)),
+                None if *id == cfg.id => return Ok(None),
                 None => {
                     new_id = cfg.id.with_precise(id.precise()
                                                  .map(|s| s.to_string()));
@@ -98,22 +175,7 @@ impl<'cfg> SourceConfigMap<'cfg> {
                        (configuration in `{}`)", name, path.display())
             }
         }
-        let new_src = new_id.load(self.config);
-        let old_src = id.load(self.config);
-        if new_src.supports_checksums() != old_src.supports_checksums() {
-            let (supports, no_support) = if new_src.supports_checksums() {
-                (name, orig_name)
-            } else {
-                (orig_name, name)
-            };
-            bail!("\
-cannot replace `{orig}` with `{name}`, the source `{supports}` supports \
-checksums, but `{no_support}` does not
-
-a lock file compatible with `{orig}` cannot be generated in this situation
-", orig = orig_name, name = name, supports = supports, no_support = no_support);
-        }
-        Ok(Box::new(ReplacedSource::new(id, &new_id, new_src)))
+        Ok(Some((&name[..], new_id)))
     }
 
     fn add(&mut self, name: &str, cfg: SourceConfig) {
@@ -121,6 +183,22 @@ a lock file compatible with `{orig}` cannot be generated in this situation
         self.cfgs.insert(name.to_string(), cfg);
     }
 
+    /// Replaces every registry with the directory source `dir`, as asked for
+    /// by the flag `name`, whatever they were configured to be replaced with.
+    fn add_vendored(&mut self, name: &str, dir: &Path) -> CargoResult<()> {
+        for cfg in self.cfgs.values_mut() {
+            if cfg.id.is_registry() {
+                cfg.replace_with = Some((name.to_string(), PathBuf::from(name)));
+            }
+        }
+        self.add(name, SourceConfig {
+            id: try!(SourceId::for_directory(dir)),
+            replace_with: None,
+            signing_keys: None,
+        });
+        Ok(())
+    }
+
     fn add_config(&mut self, name: &str, cfg: &ConfigValue) -> CargoResult<()> {
         let (table, _path) = try!(cfg.table(&format!("source.{}", name)));
         let mut srcs = Vec::new();
@@ -147,6 +225,10 @@ a lock file compatible with `{orig}` cannot be generated in this situation
             srcs.push(try!(SourceId::for_directory(&path)));
         }
 
+        // crates.io is known without its URL, e.g. to only configure its keys.
+        if srcs.is_empty() && name == "crates-io" {
+            srcs.push(try!(SourceId::crates_io(self.config)));
+        }
         let mut srcs = srcs.into_iter();
         let src = try!(srcs.next().chain_error(|| {
             human(format!("no source URL specified for `source.{}`, need \
@@ -165,9 +247,20 @@ a lock file compatible with `{orig}` cannot be generated in this situation
             replace_with = Some((s.to_string(), path.to_path_buf()));
         }
 
+        let mut signing_keys = None;
+        if let Some(val) = table.get("signing-keys") {
+            let list = try!(val.list(&format!("source.{}.signing-keys", name)));
+            // Like the other paths, keys are relative to the directory
+            // containing the `.cargo` directory they're configured in.
+            signing_keys = Some(list.iter().map(|&(ref s, ref path)| {
+                path.parent().unwrap().parent().unwrap().join(s)
+            }).collect());
+        }
+
         self.add(name, SourceConfig {
             id: src,
             replace_with: replace_with,
+            signing_keys: signing_keys,
         });
 
         return Ok(());
@@ -0,0 +1,231 @@
+//! Archives of the packages a workspace depends on, to build it without the
+//! network: made by `cargo fetch --archive` and used with `--dep-archive`.
+//!
+//! An archive is a `.tar.gz` file with:
+//!
+//! * `crates/<name>-<version>.crate`, the `.crate` file of each package from a
+//!   registry, as it was downloaded;
+//! * `git/<name>-<version>/...`, the files of each package from a git
+//!   repository.
+//!
+//! Nothing in an archive is trusted. It's unpacked into a directory source in
+//! `$CARGO_HOME/dep-archives`, replacing every registry and git repository,
+//! and the checksums of its packages are computed from their contents: the
+//! checksum of the `.crate` file for those from registries, and the same as
+//! git sources compute for the others. So a package which differs from the
+//! one in the lockfile is refused like any other, and the files unpacked are
+//! checked against their checksums before each build. A `.crate` file with
+//! anything besides the directory of its package is refused.
+
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::{Compression, GzBuilder};
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json;
+use tar::{Archive, Builder};
+
+use core::{PackageSet, Resolve};
+use sources::git::package_checksum;
+use sources::registry::crate_file;
+use sources::{PathSource, SourceConfigMap};
+use util::{CargoResult, ChainError, Config, Sha256, human};
+
+/// The `.cargo-checksum.json` file of a directory source.
+#[derive(RustcEncodable)]
+struct Checksum {
+    package: String,
+    files: BTreeMap<String, String>,
+}
+
+/// Writes the archive of the packages of `resolve` to `dst`, returning how
+/// many there are. Packages from paths aren't archived, they're part of what's
+/// built.
+pub fn write(config: &Config, resolve: &Resolve, packages: &PackageSet,
+             dst: &Path) -> CargoResult<usize> {
+    let sources = try!(SourceConfigMap::new(config));
+    let file = try!(File::create(dst).chain_error(|| {
+        human(format!("failed to create `{}`", dst.display()))
+    }));
+    let encoder = GzBuilder::new().write(file, Compression::Default);
+    let mut ar = Builder::new(encoder);
+
+    let mut ids = resolve.iter().filter(|id| !id.source_id().is_path())
+                         .collect::<Vec<_>>();
+    ids.sort();
+    let mut names = HashSet::new();
+    for id in ids.iter() {
+        let name = format!("{}-{}", id.name(), id.version());
+        if id.source_id().is_git() {
+            if !names.insert(format!("git/{}", name)) {
+                bail!("can't archive two packages named `{}` from git \
+                       repositories", name)
+            }
+            let pkg = try!(packages.get(id));
+            let src = PathSource::new(pkg.root(), id.source_id(), config);
+            for file in try!(src.list_files(pkg)) {
+                let rel = file.strip_prefix(pkg.root()).unwrap();
+                let path = format!("git/{}/{}", name,
+                                   rel.to_string_lossy().replace('\\', "/"));
+                try!(append(&mut ar, &path, &file));
+            }
+            continue
+        }
+
+        let actual = try!(sources.replacement(id.source_id()));
+        if !actual.is_registry() {
+            bail!("can't archive `{}`, it comes from `{}` which has no \
+                   `.crate` files", id, actual)
+        }
+        if !names.insert(format!("crates/{}", name)) {
+            bail!("can't archive two packages named `{}` from registries", name)
+        }
+        try!(packages.get(id));
+        try!(append(&mut ar, &format!("crates/{}.crate", name),
+                    &crate_file(config, &actual, id)));
+    }
+
+    let encoder = try!(ar.into_inner());
+    try!(encoder.finish());
+    Ok(ids.len())
+}
+
+fn append<W: Write>(ar: &mut Builder<W>, path: &str, file: &Path)
+                    -> CargoResult<()> {
+    (|| -> CargoResult<()> {
+        try!(ar.append_file(path, &mut try!(File::open(file))));
+        Ok(())
+    })().chain_error(|| {
+        human(format!("failed to archive `{}`", file.display()))
+    })
+}
+
+/// Unpacks the archive `file` into a directory source, unless it already is,
+/// and returns the directory.
+pub fn unpack(config: &Config, file: &Path) -> CargoResult<PathBuf> {
+    let mut hash = Sha256::new();
+    try!(hash_file(file, &mut hash).chain_error(|| {
+        human(format!("failed to read the dependency archive `{}`",
+                      file.display()))
+    }));
+    let root = config.home().join("dep-archives")
+                     .join(&hash.finish().to_hex()[..16])
+                     .into_path_unlocked();
+    let vendor = root.join("vendor");
+    if root.join(".cargo-ok").exists() {
+        return Ok(vendor)
+    }
+
+    try!(config.shell().status("Unpacking", file.display()));
+    try!((|| -> CargoResult<()> {
+        if root.exists() {
+            try!(fs::remove_dir_all(&root));
+        }
+        let raw = root.join("archive");
+        try!(fs::create_dir_all(&raw));
+        try!(fs::create_dir_all(&vendor));
+        try!(Archive::new(try!(GzDecoder::new(try!(File::open(file)))))
+                 .unpack(&raw));
+
+        for entry in try!(read_dir(&raw.join("crates"))) {
+            let stem = match entry.file_name().and_then(|s| s.to_str()) {
+                Some(s) if s.ends_with(".crate") => s[..s.len() - 6].to_string(),
+                _ => bail!("unexpected file in the archive: {}", entry.display()),
+            };
+            let mut hash = Sha256::new();
+            try!(hash_file(&entry, &mut hash));
+            // Each one is unpacked on its own so that it can't write into the
+            // directory of another package.
+            let tmp = root.join("tmp");
+            try!(fs::create_dir_all(&tmp));
+            try!(Archive::new(try!(GzDecoder::new(try!(File::open(&entry)))))
+                     .unpack(&tmp));
+            for unpacked in try!(read_dir(&tmp)) {
+                if unpacked.file_name() != Some(OsStr::new(&stem)) {
+                    bail!("`{}` contains `{}` which is outside of `{}/`",
+                          entry.display(),
+                          unpacked.file_name().unwrap().to_string_lossy(),
+                          stem)
+                }
+            }
+            let dir = vendor.join(&stem);
+            if !tmp.join(&stem).join("Cargo.toml").exists() {
+                bail!("`{}` doesn't contain the package `{}`",
+                      entry.display(), stem)
+            }
+            try!(fs::rename(tmp.join(&stem), &dir));
+            try!(fs::remove_dir_all(&tmp));
+            try!(write_checksum(&dir, hash.finish().to_hex()));
+        }
+
+        for entry in try!(read_dir(&raw.join("git"))) {
+            let name = entry.file_name().unwrap().to_string_lossy().into_owned();
+            let dir = vendor.join(format!("git-{}", name));
+            try!(fs::rename(&entry, &dir));
+            let list = try!(files(&dir));
+            try!(write_checksum(&dir, try!(package_checksum(&dir, &list))));
+        }
+
+        try!(fs::remove_dir_all(&raw));
+        try!(File::create(root.join(".cargo-ok")));
+        Ok(())
+    })().chain_error(|| {
+        human(format!("failed to unpack the dependency archive `{}`",
+                      file.display()))
+    }));
+    Ok(vendor)
+}
+
+fn hash_file(file: &Path, hash: &mut Sha256) -> CargoResult<()> {
+    let mut f = try!(File::open(file));
+    let mut buf = [0; 16 * 1024];
+    loop {
+        match try!(f.read(&mut buf)) {
+            0 => return Ok(()),
+            n => hash.update(&buf[..n]),
+        }
+    }
+}
+
+/// The entries of `dir`, if it exists.
+fn read_dir(dir: &Path) -> CargoResult<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new())
+    }
+    let mut ret = Vec::new();
+    for entry in try!(fs::read_dir(dir)) {
+        ret.push(try!(entry).path());
+    }
+    Ok(ret)
+}
+
+/// All the files under `dir`.
+fn files(dir: &Path) -> CargoResult<Vec<PathBuf>> {
+    let mut ret = Vec::new();
+    for entry in try!(read_dir(dir)) {
+        if entry.is_dir() {
+            ret.extend(try!(files(&entry)));
+        } else {
+            ret.push(entry);
+        }
+    }
+    Ok(ret)
+}
+
+fn write_checksum(dir: &Path, package: String) -> CargoResult<()> {
+    let mut cksum = Checksum { package: package, files: BTreeMap::new() };
+    for file in try!(files(dir)) {
+        let mut hash = Sha256::new();
+        try!(hash_file(&file, &mut hash));
+        let rel = file.strip_prefix(dir).unwrap();
+        cksum.files.insert(rel.to_string_lossy().replace('\\', "/"),
+                           hash.finish().to_hex());
+    }
+    let mut f = try!(File::create(dir.join(".cargo-checksum.json")));
+    try!(f.write_all(try!(json::encode(&cksum)).as_bytes()));
+    Ok(())
+}
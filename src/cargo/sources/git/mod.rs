@@ -1,4 +1,5 @@
 pub use self::utils::{GitRemote, GitDatabase, GitCheckout, GitRevision, fetch};
-pub use self::source::{GitSource, canonicalize_url};
+pub use self::source::{GitSource, canonicalize_url, package_checksum};
 mod utils;
 mod source;
+mod ssh;
@@ -1,14 +1,21 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Debug, Formatter};
+use std::fs::{self, File};
 use std::hash::{Hash, Hasher, SipHasher};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 
+use filetime::FileTime;
+use rustc_serialize::hex::{FromHex, ToHex};
+use rustc_serialize::json;
 use url::Url;
 
 use core::source::{Source, SourceId};
 use core::GitReference;
 use core::{Package, PackageId, Summary, Registry, Dependency};
-use util::{CargoResult, Config, to_hex};
+use util::{paths, CargoResult, ChainError, Config, Sha256, human, to_hex};
 use sources::PathSource;
-use sources::git::utils::{GitRemote, GitRevision};
+use sources::git::utils::{self, GitRemote, GitRevision};
 
 /* TODO: Refactor GitSource to delegate to a PathSource
  */
@@ -20,6 +27,21 @@ pub struct GitSource<'cfg> {
     rev: Option<GitRevision>,
     ident: String,
     config: &'cfg Config,
+    // The checksums of the contents of the packages checked out, recorded in
+    // the lockfile so a checkout which was modified since it was locked is
+    // noticed.
+    checksums: HashMap<PackageId, String>,
+    // The hashes of the files of the checkout, kept apart from it in
+    // `file_hashes_path` so files which haven't changed since aren't read on
+    // every resolve, unless `net.git-checksum-cache` is false.
+    file_hashes: Option<FileHashes>,
+    file_hashes_path: Option<PathBuf>,
+    // With `net.git-sparse-checkout`, the checkout only has the manifests of
+    // the packages until they're used, and these are the packages whose files
+    // are checked out.
+    sparse: bool,
+    checkout_path: PathBuf,
+    materialized: HashSet<PackageId>,
 }
 
 impl<'cfg> GitSource<'cfg> {
@@ -43,6 +65,12 @@ impl<'cfg> GitSource<'cfg> {
             rev: None,
             ident: ident,
             config: config,
+            checksums: HashMap::new(),
+            file_hashes: None,
+            file_hashes_path: None,
+            sparse: false,
+            checkout_path: PathBuf::new(),
+            materialized: HashSet::new(),
         }
     }
 
@@ -54,6 +82,184 @@ impl<'cfg> GitSource<'cfg> {
         }
         self.path_source.as_mut().unwrap().read_packages()
     }
+
+    /// Checks out the files of the package `id` in a sparse checkout, along
+    /// with those of the packages of the repository it depends on by path,
+    /// and reads the packages again now that their targets can be found.
+    fn materialize(&mut self, id: &PackageId) -> CargoResult<()> {
+        if !self.sparse || self.materialized.contains(id) {
+            return Ok(())
+        }
+        let packages = try!(self.path_source.as_ref()
+                                .expect("BUG: update() must be called first")
+                                .read_packages());
+        let mut dirs = Vec::new();
+        let mut todo = vec![id.clone()];
+        while let Some(id) = todo.pop() {
+            if !self.materialized.insert(id.clone()) {
+                continue
+            }
+            let pkg = match packages.iter().find(|p| p.package_id() == &id) {
+                Some(pkg) => pkg,
+                None => continue,
+            };
+            dirs.push(pkg.root().strip_prefix(&self.checkout_path).unwrap()
+                         .to_path_buf());
+            dirs.extend(outside_paths(pkg, &self.checkout_path));
+            for dep in pkg.dependencies() {
+                if dep.source_id().url() != self.source_id.url() {
+                    continue
+                }
+                let found = packages.iter().find(|p| p.name() == dep.name());
+                if let Some(found) = found {
+                    todo.push(found.package_id().clone());
+                }
+            }
+        }
+        try!(utils::checkout_paths(&self.checkout_path, &dirs));
+
+        let rev = self.rev.as_ref().unwrap().to_string();
+        let source_id = self.source_id.with_precise(Some(rev));
+        let mut path_source = PathSource::new_recursive(&self.checkout_path,
+                                                        &source_id,
+                                                        self.config);
+        try!(path_source.update());
+        self.path_source = Some(path_source);
+        Ok(())
+    }
+
+    /// Returns the checksum of the files of the package `id` in the
+    /// checkout: a SHA-256 over the path and the SHA-256 of each file.
+    fn checksum(&mut self, id: &PackageId) -> CargoResult<String> {
+        if let Some(cksum) = self.checksums.get(id) {
+            return Ok(cksum.clone())
+        }
+        if self.file_hashes.is_none() {
+            let hashes = self.file_hashes_path.as_ref().and_then(|cache| {
+                paths::read(cache).ok()
+            }).and_then(|s| json::decode(&s).ok());
+            self.file_hashes = Some(hashes.unwrap_or(HashMap::new()));
+        }
+        let cksum = {
+            let src = self.path_source.as_mut()
+                          .expect("BUG: update() must be called before query()");
+            let hashes = self.file_hashes.as_mut().unwrap();
+            let pkg = try!(src.download(id));
+            let files = try!(src.list_files(&pkg));
+            match self.file_hashes_path {
+                Some(ref cache) => {
+                    let cksum = try!(checksum_files(pkg.root(), &files, hashes));
+                    // The cache only saves time, so failing to write it isn't
+                    // an error.
+                    let _ = fs::create_dir_all(cache.parent().unwrap());
+                    let _ = paths::write(cache, json::encode(hashes).unwrap()
+                                                    .as_bytes());
+                    cksum
+                }
+                None => try!(package_checksum(pkg.root(), &files)),
+            }
+        };
+        self.checksums.insert(id.clone(), cksum.clone());
+        Ok(cksum)
+    }
+}
+
+/// The paths, relative to `checkout`, of what `pkg` refers to outside of its
+/// own directory with `../`: the directories of its targets and build script,
+/// its readme and license file, and the `include` patterns up to their first
+/// glob. Checking out the package's directory alone would leave them out.
+fn outside_paths(pkg: &Package, checkout: &Path) -> Vec<PathBuf> {
+    let root = pkg.root();
+    let manifest = pkg.manifest();
+    let mut ret = Vec::new();
+    for target in pkg.targets() {
+        if let Some(dir) = target.src_path().parent() {
+            ret.push(dir.to_path_buf());
+        }
+    }
+    let metadata = manifest.metadata();
+    for file in metadata.readme.iter().chain(metadata.license_file.iter()) {
+        ret.push(root.join(file));
+    }
+    for pattern in manifest.include() {
+        let plain = Path::new(pattern).components().take_while(|c| {
+            !c.as_os_str().to_string_lossy().contains(|ch| "*?[".contains(ch))
+        }).collect::<PathBuf>();
+        ret.push(root.join(plain));
+    }
+    ret.into_iter().map(|p| paths::normalize_path(&p)).filter(|p| {
+        !p.starts_with(root)
+    }).filter_map(|p| {
+        p.strip_prefix(checkout).ok().map(|p| p.to_path_buf())
+    }).collect()
+}
+
+/// The hashes of files by their path, along with the mtime and size they had
+/// when hashed: `(seconds, nanoseconds, size, SHA-256)`.
+type FileHashes = HashMap<String, (u64, u32, u64, String)>;
+
+/// The checksum of a package from a git repository, out of its `files` in
+/// `root`: a SHA-256 over the path and the SHA-256 of each file.
+///
+/// It's compared with the one in the lock file to notice files changed in the
+/// checkout in the Cargo home directory. As a revision names the contents of
+/// the repository, it doesn't tell anything about the repository itself: its
+/// contents are trusted when the lock file is generated.
+pub fn package_checksum(root: &Path, files: &[PathBuf]) -> CargoResult<String> {
+    checksum_files(root, files, &mut HashMap::new())
+}
+
+/// Calculates `package_checksum`, but doesn't read the files whose mtime and
+/// size are those recorded in `hashes` again, and records the others. A file
+/// changed without changing either of them isn't noticed.
+fn checksum_files(root: &Path,
+                  files: &[PathBuf],
+                  hashes: &mut FileHashes) -> CargoResult<String> {
+    let mut files = files.iter().map(|file| {
+        (file.strip_prefix(root).unwrap().to_path_buf(), file)
+    }).collect::<Vec<_>>();
+    files.sort();
+
+    let mut h = Sha256::new();
+    for (rel, file) in files {
+        let file_hash = try!(file_hash(file, hashes).chain_error(|| {
+            human(format!("failed to calculate checksum of: {}",
+                          file.display()))
+        }));
+        h.update(rel.to_string_lossy().replace('\\', "/").as_bytes());
+        h.update(&[0]);
+        h.update(&file_hash);
+    }
+    Ok(h.finish().to_hex())
+}
+
+/// The SHA-256 of `file`, from `hashes` if it has the same mtime and size as
+/// when it was recorded there.
+fn file_hash(file: &Path, hashes: &mut FileHashes) -> CargoResult<Vec<u8>> {
+    let meta = try!(fs::metadata(file));
+    let mtime = FileTime::from_last_modification_time(&meta);
+    let stamp = (mtime.seconds_relative_to_1970(), mtime.nanoseconds(), meta.len());
+    let key = file.to_string_lossy().into_owned();
+    if let Some(&(secs, nanos, len, ref hash)) = hashes.get(&key) {
+        if (secs, nanos, len) == stamp {
+            if let Ok(hash) = hash.from_hex() {
+                return Ok(hash)
+            }
+        }
+    }
+
+    let mut h = Sha256::new();
+    let mut buf = [0; 16 * 1024];
+    let mut f = try!(File::open(file));
+    loop {
+        match try!(f.read(&mut buf)) {
+            0 => break,
+            n => h.update(&buf[..n]),
+        }
+    }
+    let hash = h.finish();
+    hashes.insert(key, (stamp.0, stamp.1, stamp.2, hash.to_hex()));
+    Ok(hash.to_vec())
 }
 
 fn ident(url: &Url) -> String {
@@ -118,9 +324,18 @@ impl<'cfg> Debug for GitSource<'cfg> {
 
 impl<'cfg> Registry for GitSource<'cfg> {
     fn query(&mut self, dep: &Dependency) -> CargoResult<Vec<Summary>> {
-        let src = self.path_source.as_mut()
-                      .expect("BUG: update() must be called before query()");
-        src.query(dep)
+        let summaries = {
+            let src = self.path_source.as_mut()
+                          .expect("BUG: update() must be called before query()");
+            try!(src.query(dep))
+        };
+        let mut ret = Vec::new();
+        for summary in summaries {
+            try!(self.materialize(summary.package_id()));
+            let cksum = try!(self.checksum(summary.package_id()));
+            ret.push(summary.set_checksum(cksum));
+        }
+        Ok(ret)
     }
 }
 
@@ -143,27 +358,80 @@ impl<'cfg> Source for GitSource<'cfg> {
         // databaes already has that revision. If it does, we just load a
         // database pinned at that revision, and if we don't we issue an update
         // to try to find the revision.
+        //
+        // Tags and full commit hashes only need the one commit, which is
+        // fetched without its history into a database of its own unless
+        // `net.git-shallow` is false. A full database which already has the
+        // revision is still preferred.
+        let shallow = try!(self.config.get_bool("net.git-shallow"))
+                                      .map(|v| v.val).unwrap_or(true);
+        let refspec = if shallow {
+            utils::shallow_refspec(&self.reference)
+        } else {
+            None
+        };
+        let shallow_db_path = lock.parent().join("db")
+                                  .join(format!("{}-shallow", self.ident));
+        let locked = self.source_id.precise().is_some();
         let actual_rev = self.remote.rev_for(&db_path, &self.reference);
-        let should_update = actual_rev.is_err() ||
-                            self.source_id.precise().is_none();
+        let shallow_rev = self.remote.rev_for(&shallow_db_path, &self.reference);
 
-        let (repo, actual_rev) = if should_update {
+        let (repo, actual_rev) = if locked && actual_rev.is_ok() {
+            (try!(self.remote.db_at(&db_path)), actual_rev.unwrap())
+        } else if locked && refspec.is_some() && shallow_rev.is_ok() {
+            (try!(self.remote.db_at(&shallow_db_path)), shallow_rev.unwrap())
+        } else {
             try!(self.config.shell().status("Updating",
                 format!("git repository `{}`", self.remote.url())));
 
             trace!("updating git source `{:?}`", self.remote);
 
-            let repo = try!(self.remote.checkout(&db_path, &self.config));
+            let shallow_repo = match refspec {
+                Some(ref refspec) => {
+                    match self.remote.checkout_shallow(&shallow_db_path, refspec,
+                                                       &self.config) {
+                        Ok(repo) => Some(repo),
+                        Err(e) => {
+                            try!(self.config.shell().warn(format!(
+                                "failed to fetch `{}` without its history, \
+                                 fetching all of it instead: {}",
+                                self.remote.url(), e)));
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+            let repo = match shallow_repo {
+                Some(repo) => repo,
+                None => try!(self.remote.checkout(&db_path, &self.config)),
+            };
             let rev = try!(repo.rev_for(&self.reference));
             (repo, rev)
-        } else {
-            (try!(self.remote.db_at(&db_path)), actual_rev.unwrap())
         };
 
         // Copy the database to the checkout location. After this we could drop
         // the lock on the database as we no longer needed it, but we leave it
         // in scope so the destructors here won't tamper with too much.
-        try!(repo.copy_to(actual_rev.clone(), &checkout_path, &self.config));
+        //
+        // A sparse checkout of a large repository only has the manifests to
+        // start with, see `materialize`.
+        self.sparse = try!(self.config.get_bool("net.git-sparse-checkout"))
+                               .map(|v| v.val).unwrap_or(false);
+        if self.sparse {
+            try!(repo.copy_sparse_to(actual_rev.clone(), &checkout_path));
+        } else {
+            try!(repo.copy_to(actual_rev.clone(), &checkout_path, &self.config));
+        }
+        self.checkout_path = checkout_path.clone();
+        let cache_hashes = try!(self.config.get_bool("net.git-checksum-cache"))
+                                      .map(|v| v.val).unwrap_or(true);
+        self.file_hashes_path = if cache_hashes {
+            Some(lock.parent().join("file-hashes").join(&self.ident)
+                     .join(reference_path))
+        } else {
+            None
+        };
 
         let source_id = self.source_id.with_precise(Some(actual_rev.to_string()));
         let path_source = PathSource::new_recursive(&checkout_path,
@@ -181,6 +449,7 @@ impl<'cfg> Source for GitSource<'cfg> {
     fn download(&mut self, id: &PackageId) -> CargoResult<Package> {
         trace!("getting packages for package id `{}` from `{:?}`", id,
                self.remote);
+        try!(self.materialize(id));
         self.path_source.as_mut()
                         .expect("BUG: update() must be called before get()")
                         .download(id)
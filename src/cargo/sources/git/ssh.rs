@@ -0,0 +1,541 @@
+//! What OpenSSH would do for `ssh://` URLs which libssh2 leaves to us: the
+//! options of the host in `~/.ssh/config`, and checking the key of the host
+//! against `~/.ssh/known_hosts`.
+//!
+//! Only the options making a difference to fetching a repository are read:
+//! `HostName`, `User`, `Port`, `IdentityFile`, `UserKnownHostsFile` and
+//! `StrictHostKeyChecking`, in `Host` sections. `Match` sections never apply.
+//!
+//! libssh2 only gives the SHA-1 hash of the key of the host, so the key itself
+//! is recorded for `accept-new` by asking the host for it again with
+//! `ssh-keyscan`, and keeping the one with that hash.
+
+use std::ascii::AsciiExt;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::base64::FromBase64;
+use url::Url;
+
+use util::process;
+
+/// The options of the host an `ssh://` URL points to.
+pub struct Host {
+    /// The URL with the host, user and port of the configuration.
+    pub url: Url,
+    pub hostname: String,
+    pub port: u16,
+    /// Keys tried after those of ssh-agent.
+    pub identities: Vec<PathBuf>,
+    known_hosts: Vec<PathBuf>,
+    strict: bool,
+}
+
+/// Looks up the options of the host of `url` in `~/.ssh/config`.
+pub fn host(url: &Url) -> Host {
+    let alias = url.host_str().unwrap_or("").to_string();
+    let home = env::home_dir().unwrap_or(PathBuf::new());
+    let config = read(&home.join(".ssh/config")).unwrap_or(String::new());
+    host_from(url, &alias, &home, &config)
+}
+
+fn host_from(url: &Url, alias: &str, home: &Path, config: &str) -> Host {
+    let options = options(config, alias);
+    let first = |key: &str| {
+        options.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| v.clone())
+    };
+    let expand = |value: &str| {
+        let value = value.replace("%h", alias)
+                         .replace("%d", &home.display().to_string());
+        if value.starts_with("~/") {
+            home.join(&value[2..])
+        } else {
+            PathBuf::from(value)
+        }
+    };
+
+    let hostname = first("hostname").map(|h| h.replace("%h", alias))
+                                    .unwrap_or(alias.to_string());
+    let port = url.port()
+                  .or(first("port").and_then(|p| p.parse().ok()))
+                  .unwrap_or(22);
+    let mut rewritten = url.clone();
+    let _ = rewritten.set_host(Some(&hostname));
+    if port != 22 {
+        let _ = rewritten.set_port(Some(port));
+    }
+    if url.username().is_empty() {
+        if let Some(user) = first("user") {
+            let _ = rewritten.set_username(&user);
+        }
+    }
+
+    let mut identities = options.iter().filter(|&&(ref k, _)| k == "identityfile")
+                                .map(|&(_, ref v)| expand(v))
+                                .collect::<Vec<_>>();
+    if identities.is_empty() {
+        for name in ["id_ed25519", "id_ecdsa", "id_rsa"].iter() {
+            identities.push(home.join(".ssh").join(name));
+        }
+    }
+    identities.retain(|p| p.is_file());
+
+    let known_hosts = match first("userknownhostsfile") {
+        Some(files) => files.split_whitespace().map(|f| expand(f)).collect(),
+        None => vec![home.join(".ssh/known_hosts"),
+                     home.join(".ssh/known_hosts2")],
+    };
+    // Hosts which aren't known are refused with `ask`, the default, as
+    // OpenSSH does when it can't ask. Their keys are recorded otherwise.
+    let strict = match first("stricthostkeychecking") {
+        Some(ref v) => v != "no" && v != "off" && v != "accept-new",
+        None => true,
+    };
+
+    Host {
+        url: rewritten,
+        hostname: hostname,
+        port: port,
+        identities: identities,
+        known_hosts: known_hosts,
+        strict: strict,
+    }
+}
+
+/// The options applying to `alias`, with lowercase keys, in the order they're
+/// found: the first value of an option is the one used.
+fn options(config: &str, alias: &str) -> Vec<(String, String)> {
+    let mut ret = Vec::new();
+    let mut applies = true;
+    for line in config.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let split = line.find(|c: char| c.is_whitespace() || c == '=')
+                        .unwrap_or(line.len());
+        let key = line[..split].to_lowercase();
+        let value = line[split..].trim_left_matches(|c: char| {
+            c.is_whitespace() || c == '='
+        }).trim_matches('"');
+        match &key[..] {
+            "host" => {
+                let patterns = value.split_whitespace().collect::<Vec<_>>();
+                applies = matches_list(&patterns, alias);
+            }
+            "match" => applies = false,
+            _ if applies => ret.push((key, value.to_string())),
+            _ => {}
+        }
+    }
+    ret
+}
+
+/// Whether `name` matches one of `patterns` and none of those negated with a
+/// `!`.
+fn matches_list(patterns: &[&str], name: &str) -> bool {
+    let mut found = false;
+    for pattern in patterns {
+        if pattern.starts_with('!') {
+            if matches(&pattern[1..], name) {
+                return false
+            }
+        } else if matches(pattern, name) {
+            found = true;
+        }
+    }
+    found
+}
+
+/// Matches `name` against a pattern where `*` stands for any characters and
+/// `?` for one, ignoring case.
+fn matches(pattern: &str, name: &str) -> bool {
+    fn go(p: &[u8], n: &[u8]) -> bool {
+        match (p.first(), n.first()) {
+            (None, None) => true,
+            (Some(&b'*'), _) => go(&p[1..], n) || (!n.is_empty() && go(p, &n[1..])),
+            (Some(&b'?'), Some(_)) => go(&p[1..], &n[1..]),
+            (Some(a), Some(b)) => {
+                a.to_ascii_lowercase() == b.to_ascii_lowercase() &&
+                    go(&p[1..], &n[1..])
+            }
+            _ => false,
+        }
+    }
+    go(pattern.as_bytes(), name.as_bytes())
+}
+
+impl Host {
+    /// Checks the key of the host, of which libssh2 gives the SHA-1 hash,
+    /// against the known hosts files, returning why it's refused if it is.
+    /// If the key was added to the known hosts, says so.
+    pub fn check_host_key(&self, sha1: &[u8]) -> Result<Option<String>, String> {
+        let mut contents = String::new();
+        for file in self.known_hosts.iter() {
+            if let Some(s) = read(file) {
+                contents.push_str(&s);
+                contents.push('\n');
+            }
+        }
+        let files = self.known_hosts.iter()
+                        .map(|f| f.display().to_string())
+                        .collect::<Vec<_>>().join(", ");
+        match known_host_key(&contents, &self.hostname, self.port, sha1) {
+            KnownHost::Matches => Ok(None),
+            KnownHost::Revoked => {
+                Err(format!("the SSH host key of `{}` is marked as revoked in \
+                             {}", self.hostname, files))
+            }
+            KnownHost::Differs => {
+                Err(format!("the SSH host key of `{}` doesn't match those \
+                             in {}, someone may be impersonating it, or its \
+                             key was changed (only keys of type {} can be \
+                             checked, so one of those types has to be listed \
+                             for the host)", self.hostname, files,
+                            HOST_KEY_TYPES.join(", ")))
+            }
+            KnownHost::Unknown if !self.strict => {
+                self.add_host_key(sha1).map(Some).map_err(|e| {
+                    format!("the SSH host key of `{}` isn't known, and \
+                             couldn't be added to the known hosts: {}",
+                            self.hostname, e)
+                })
+            }
+            KnownHost::Unknown => {
+                Err(format!("the SSH host key of `{}` isn't known, add it to \
+                             {} (e.g. with `ssh-keyscan -t {} {}`), or set \
+                             `StrictHostKeyChecking accept-new` for the host \
+                             in ~/.ssh/config", self.hostname, files,
+                            HOST_KEY_TYPES.join(","), self.hostname))
+            }
+        }
+    }
+
+    /// Appends the key of the host with the hash `sha1` to the first known
+    /// hosts file.
+    fn add_host_key(&self, sha1: &[u8]) -> Result<String, String> {
+        let output = try!(process("ssh-keyscan")
+            .arg("-p").arg(&self.port.to_string())
+            .arg("-t").arg(&HOST_KEY_TYPES.join(","))
+            .arg(&self.hostname)
+            .exec_with_output()
+            .map_err(|e| e.to_string()));
+        let scanned = String::from_utf8_lossy(&output.stdout);
+        let name = known_hosts_name(&self.hostname, self.port);
+        let line = match key_line(&scanned, &name, sha1) {
+            Some(line) => line,
+            None => {
+                return Err(format!("`ssh-keyscan` didn't return the key the \
+                                    host presented"))
+            }
+        };
+        let file = match self.known_hosts.first() {
+            Some(file) => file,
+            None => return Err(format!("no known hosts file is configured")),
+        };
+        if let Some(parent) = file.parent() {
+            try!(fs::create_dir_all(parent).map_err(|e| e.to_string()));
+        }
+        try!(OpenOptions::new().create(true).append(true).open(file)
+                               .and_then(|mut f| writeln!(f, "{}", line))
+                               .map_err(|e| {
+            format!("failed to write to `{}`: {}", file.display(), e)
+        }));
+        Ok(format!("added the SSH host key of `{}` to {}", self.hostname,
+                   file.display()))
+    }
+}
+
+/// The types of host keys the libssh2 Cargo is built with negotiates, most
+/// preferred first.
+const HOST_KEY_TYPES: &'static [&'static str] = &["ssh-rsa", "ssh-dss"];
+
+/// The name of a host in the known hosts files.
+fn known_hosts_name(hostname: &str, port: u16) -> String {
+    if port == 22 {
+        hostname.to_string()
+    } else {
+        format!("[{}]:{}", hostname, port)
+    }
+}
+
+/// The known hosts line for `name` of the key with the hash `sha1` among
+/// those printed by `ssh-keyscan`.
+fn key_line(scanned: &str, name: &str, sha1: &[u8]) -> Option<String> {
+    scanned.lines().filter(|line| !line.starts_with('#')).filter_map(|line| {
+        let mut fields = line.split_whitespace().skip(1);
+        match (fields.next(), fields.next()) {
+            (Some(kind), Some(key)) => Some((kind, key)),
+            _ => None,
+        }
+    }).find(|&(_, key)| {
+        key.from_base64().map(|key| hash::sha1(&key) == sha1).unwrap_or(false)
+    }).map(|(kind, key)| format!("{} {} {}", name, kind, key))
+}
+
+#[derive(PartialEq, Debug)]
+enum KnownHost {
+    Matches,
+    Differs,
+    Revoked,
+    Unknown,
+}
+
+/// Looks the key of the host up in the known hosts files `contents`, of which
+/// only the SHA-1 hash is known.
+///
+/// As with OpenSSH, a host for which only other keys are known, whatever their
+/// type, is taken to have changed its key.
+fn known_host_key(contents: &str, hostname: &str, port: u16, sha1: &[u8])
+                  -> KnownHost {
+    let name = known_hosts_name(hostname, port);
+    let mut ret = KnownHost::Unknown;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let mut fields = line.split_whitespace();
+        let mut hosts = fields.next().unwrap_or("");
+        let marker = if hosts.starts_with('@') {
+            let marker = hosts;
+            hosts = fields.next().unwrap_or("");
+            Some(marker)
+        } else {
+            None
+        };
+        let key = match (fields.next(), fields.next()) {
+            (Some(_), Some(key)) => key,
+            _ => continue,
+        };
+        if !host_matches(hosts, &name) {
+            continue
+        }
+        let same = match key.from_base64() {
+            Ok(key) => hash::sha1(&key) == sha1,
+            Err(..) => continue,
+        };
+        match marker {
+            Some("@revoked") if same => return KnownHost::Revoked,
+            Some(..) => {}
+            None if same => ret = KnownHost::Matches,
+            None if ret == KnownHost::Unknown => ret = KnownHost::Differs,
+            None => {}
+        }
+    }
+    ret
+}
+
+/// Whether the hosts field of a known hosts line lists `name`, either as a
+/// list of patterns or hashed as `|1|<salt>|<HMAC-SHA1 of the name>`.
+fn host_matches(hosts: &str, name: &str) -> bool {
+    if hosts.starts_with("|1|") {
+        let mut parts = hosts[3..].split('|');
+        return match (parts.next().map(|s| s.from_base64()),
+                      parts.next().map(|s| s.from_base64())) {
+            (Some(Ok(salt)), Some(Ok(hash))) => {
+                hash::hmac_sha1(&salt, name.as_bytes()) == hash
+            }
+            _ => false,
+        }
+    }
+    matches_list(&hosts.split(',').collect::<Vec<_>>(), name)
+}
+
+fn read(path: &Path) -> Option<String> {
+    let mut contents = String::new();
+    match File::open(path).and_then(|mut f| f.read_to_string(&mut contents)) {
+        Ok(..) => Some(contents),
+        Err(..) => None,
+    }
+}
+
+mod hash {
+    pub use self::imp::sha1;
+
+    pub fn hmac_sha1(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut key = if key.len() > 64 { sha1(key) } else { key.to_vec() };
+        key.resize(64, 0);
+        let mut inner = key.iter().map(|b| b ^ 0x36).collect::<Vec<_>>();
+        inner.extend_from_slice(data);
+        let mut outer = key.iter().map(|b| b ^ 0x5c).collect::<Vec<_>>();
+        outer.extend_from_slice(&sha1(&inner));
+        sha1(&outer)
+    }
+
+    #[cfg(not(windows))]
+    mod imp {
+        extern crate openssl;
+
+        use self::openssl::crypto::hash::{hash, Type};
+
+        pub fn sha1(data: &[u8]) -> Vec<u8> {
+            hash(Type::SHA1, data)
+        }
+    }
+
+    #[cfg(windows)]
+    mod imp {
+        extern crate winapi;
+        extern crate advapi32;
+
+        use std::ptr;
+
+        use self::winapi::{DWORD, PROV_RSA_AES, CRYPT_SILENT};
+        use self::winapi::{CRYPT_VERIFYCONTEXT, CALG_SHA1, HP_HASHVAL};
+        use self::advapi32::{CryptAcquireContextW, CryptCreateHash};
+        use self::advapi32::{CryptDestroyHash, CryptGetHashParam};
+        use self::advapi32::{CryptHashData, CryptReleaseContext};
+
+        pub fn sha1(data: &[u8]) -> Vec<u8> {
+            let mut ret = vec![0u8; 20];
+            let mut len = ret.len() as DWORD;
+            unsafe {
+                let mut hcp = 0;
+                let mut hash = 0;
+                assert!(CryptAcquireContextW(&mut hcp, ptr::null(), ptr::null(),
+                                             PROV_RSA_AES,
+                                             CRYPT_VERIFYCONTEXT |
+                                                 CRYPT_SILENT) != 0);
+                assert!(CryptCreateHash(hcp, CALG_SHA1, 0, 0, &mut hash) != 0);
+                assert!(CryptHashData(hash, data.as_ptr() as *mut _,
+                                      data.len() as DWORD, 0) != 0);
+                assert!(CryptGetHashParam(hash, HP_HASHVAL, ret.as_mut_ptr(),
+                                          &mut len, 0) != 0);
+                CryptDestroyHash(hash);
+                CryptReleaseContext(hcp, 0);
+            }
+            ret
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use rustc_serialize::base64::{ToBase64, STANDARD};
+    use url::Url;
+
+    use super::{host_from, known_host_key, key_line, hash, KnownHost};
+
+    const CONFIG: &'static str = "
+# Comments are skipped
+Host work
+    HostName git.example.com
+    Port 2222
+    User builder
+    StrictHostKeyChecking yes
+
+Host *.example.com !secret.example.com
+    User someone
+    StrictHostKeyChecking=no
+
+Host *
+    User fallback
+    HostName ignored.example.com
+    StrictHostKeyChecking accept-new
+";
+
+    fn host(url: &str) -> super::Host {
+        let url = Url::parse(url).unwrap();
+        let alias = url.host_str().unwrap().to_string();
+        host_from(&url, &alias, Path::new("/home/me"), CONFIG)
+    }
+
+    #[test]
+    fn aliases_are_resolved() {
+        let host = host("ssh://work/org/repo");
+        assert_eq!(host.url.as_str(),
+                   "ssh://builder@git.example.com:2222/org/repo");
+        assert_eq!(host.port, 2222);
+        assert!(host.strict);
+    }
+
+    #[test]
+    fn first_value_wins_and_negations_exclude() {
+        let host = host("ssh://other.example.com/repo");
+        assert_eq!(host.url.as_str(), "ssh://someone@ignored.example.com/repo");
+        assert!(!host.strict);
+
+        let host = super::host_from(&Url::parse("ssh://git@secret.example.com/r")
+                                        .unwrap(),
+                                    "secret.example.com",
+                                    Path::new("/home/me"), CONFIG);
+        assert_eq!(host.url.as_str(), "ssh://git@ignored.example.com/r");
+        assert!(!host.strict);
+    }
+
+    #[test]
+    fn known_hosts() {
+        let key = b"not really a key";
+        let other = b"another key";
+        let sha1 = hash::sha1(key);
+        let salt = b"saltsaltsaltsaltsalt";
+        let hashed = hash::hmac_sha1(salt, b"[hashed.example.com]:2222");
+        let contents = format!("\
+git.example.com,other.example.com ssh-ed25519 {key}
+changed.example.com ssh-rsa {other}
+changed.example.com ssh-ed25519 {other}
+other-type.example.com ssh-ed25519 {other}
+|1|{salt}|{hashed} ssh-ed25519 {key}
+@revoked * ssh-ed25519 {other}
+",
+            key = key.to_base64(STANDARD),
+            other = other.to_base64(STANDARD),
+            salt = salt.to_base64(STANDARD),
+            hashed = hashed.to_base64(STANDARD));
+
+        assert_eq!(known_host_key(&contents, "other.example.com", 22, &sha1),
+                   KnownHost::Matches);
+        assert_eq!(known_host_key(&contents, "changed.example.com", 22, &sha1),
+                   KnownHost::Differs);
+        // Whatever the type of the key known, the host could be impersonated.
+        assert_eq!(known_host_key(&contents, "other-type.example.com", 22, &sha1),
+                   KnownHost::Differs);
+        assert_eq!(known_host_key(&contents, "hashed.example.com", 2222, &sha1),
+                   KnownHost::Matches);
+        assert_eq!(known_host_key(&contents, "hashed.example.com", 22, &sha1),
+                   KnownHost::Unknown);
+        assert_eq!(known_host_key(&contents, "new.example.com", 22,
+                                  &hash::sha1(other)),
+                   KnownHost::Revoked);
+    }
+
+    #[test]
+    fn unknown_hosts_refused_by_default() {
+        let url = Url::parse("ssh://git@example.com/repo").unwrap();
+        assert!(host_from(&url, "example.com", Path::new("/home/me"), "").strict);
+        let host = host("ssh://secret.example.com/repo");
+        assert!(!host.strict);
+    }
+
+    #[test]
+    fn scanned_keys() {
+        let key = b"not really a key";
+        let scanned = format!("\
+# example.com:2222 SSH-2.0-OpenSSH_7.2
+[example.com]:2222 ssh-rsa {other}
+[example.com]:2222 ssh-dss {key}
+",
+            key = key.to_base64(STANDARD),
+            other = b"another key".to_base64(STANDARD));
+
+        assert_eq!(key_line(&scanned, "[example.com]:2222", &hash::sha1(key)),
+                   Some(format!("[example.com]:2222 ssh-dss {}",
+                                key.to_base64(STANDARD))));
+        assert_eq!(key_line(&scanned, "[example.com]:2222",
+                            &hash::sha1(b"unknown")),
+                   None);
+    }
+
+    #[test]
+    fn hmac_sha1() {
+        // RFC 2202, test case 2.
+        assert_eq!(hash::hmac_sha1(b"Jefe", b"what do ya want for nothing?"),
+                   vec![0xef, 0xfc, 0xdf, 0x6a, 0xe5, 0xeb, 0x2f, 0xa2, 0xd2, 0x74,
+                        0x16, 0xd5, 0xf1, 0x84, 0xdf, 0x9c, 0x25, 0x9a, 0x7c, 0x79]);
+    }
+}
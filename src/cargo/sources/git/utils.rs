@@ -1,6 +1,7 @@
 use std::env;
 use std::fmt;
 use std::fs::{self, File};
+use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 
 use rustc_serialize::{Encodable, Encoder};
@@ -8,7 +9,9 @@ use url::Url;
 use git2::{self, ObjectType};
 
 use core::GitReference;
+use sources::git::ssh;
 use util::{CargoResult, ChainError, human, ToUrl, internal, Config, network};
+use util::process;
 
 #[derive(PartialEq, Clone, Debug)]
 pub struct GitRevision(git2::Oid);
@@ -131,6 +134,33 @@ impl GitRemote {
         })
     }
 
+    /// Fetches only the commit `refspec` names, without its history, into
+    /// a database at `into`. libgit2 can't make shallow fetches, so this is
+    /// done by `git` itself.
+    pub fn checkout_shallow(&self, into: &Path, refspec: &str,
+                            cargo_config: &Config) -> CargoResult<GitDatabase> {
+        let repo = match git2::Repository::open(into) {
+            Ok(repo) => repo,
+            Err(..) => {
+                if fs::metadata(&into).is_ok() {
+                    try!(fs::remove_dir_all(into));
+                }
+                try!(fs::create_dir_all(into));
+                try!(git2::Repository::init_bare(into))
+            }
+        };
+        try!(fetch_shallow(into, &self.url.to_string(), refspec,
+                           cargo_config).chain_error(|| {
+            human(format!("failed to fetch into {}", into.display()))
+        }));
+
+        Ok(GitDatabase {
+            remote: self.clone(),
+            path: into.to_path_buf(),
+            repo: repo,
+        })
+    }
+
     pub fn db_at(&self, db_path: &Path) -> CargoResult<GitDatabase> {
         let repo = try!(git2::Repository::open(db_path));
         Ok(GitDatabase {
@@ -169,8 +199,13 @@ impl GitDatabase {
         let checkout = match git2::Repository::open(dest) {
             Ok(repo) => {
                 let checkout = GitCheckout::new(dest, self, rev, repo);
-                if !checkout.is_fresh() {
-                    try!(checkout.fetch(&cargo_config));
+                if !checkout.is_fresh() || checkout.is_sparse() {
+                    if self.is_shallow() {
+                        try!(GitCheckout::borrow_objects(&checkout.repo,
+                                                         self.path()));
+                    } else {
+                        try!(checkout.fetch(&cargo_config));
+                    }
                     try!(checkout.reset());
                     assert!(checkout.is_fresh());
                 }
@@ -184,6 +219,38 @@ impl GitDatabase {
         Ok(checkout)
     }
 
+    /// Like `copy_to`, but only the manifests of the packages in the revision
+    /// and what's needed to read them are checked out, the rest waiting for
+    /// `checkout_paths`.
+    pub fn copy_sparse_to(&self, rev: GitRevision, dest: &Path)
+                          -> CargoResult<()> {
+        if let Ok(repo) = git2::Repository::open(dest) {
+            let checkout = GitCheckout::new(dest, self, rev.clone(), repo);
+            if checkout.is_sparse() && checkout.is_fresh() {
+                return Ok(())
+            }
+        }
+        let repo = try!(GitCheckout::init_repo(self.path(), dest));
+        let checkout = GitCheckout::new(dest, self, rev, repo);
+        let commit = try!(checkout.repo.find_commit(checkout.revision.0));
+        try!(checkout.repo.set_head_detached(commit.id()));
+
+        let mut manifests = Vec::new();
+        try!(find_manifests(&checkout.repo, &try!(commit.tree()),
+                            Path::new(""), &mut manifests));
+        let mut paths = Vec::new();
+        for dir in manifests {
+            for file in ["Cargo.toml", "build.rs", "src/lib.rs", "src/main.rs",
+                         "src/bin"].iter() {
+                paths.push(dir.join(file));
+            }
+        }
+        try!(File::create(checkout.repo.path().join("cargo-sparse")));
+        try!(checkout_paths(dest, &paths));
+        try!(File::create(dest.join(".cargo-ok")));
+        Ok(())
+    }
+
     pub fn rev_for(&self, reference: &GitReference) -> CargoResult<GitRevision> {
         let id = match *reference {
             GitReference::Tag(ref s) => {
@@ -219,6 +286,72 @@ impl GitDatabase {
         try!(self.repo.revparse_single(reference));
         Ok(())
     }
+
+    /// Whether this database was fetched without history, in which case
+    /// checkouts borrow its objects instead of cloning it, as libgit2 stumbles
+    /// over the missing parents.
+    fn is_shallow(&self) -> bool {
+        fs::metadata(self.path.join("shallow")).is_ok()
+    }
+}
+
+/// Collects the directories of `tree` with a `Cargo.toml`, relative to the
+/// root of the repository.
+fn find_manifests(repo: &git2::Repository, tree: &git2::Tree, dir: &Path,
+                  ret: &mut Vec<PathBuf>) -> CargoResult<()> {
+    for entry in tree.iter() {
+        let name = match entry.name() {
+            Some(name) => name,
+            None => continue,
+        };
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                let subtree = try!(repo.find_tree(entry.id()));
+                try!(find_manifests(repo, &subtree, &dir.join(name), ret));
+            }
+            Some(ObjectType::Blob) if name == "Cargo.toml" => {
+                ret.push(dir.to_path_buf());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Checks out `paths` of the revision a sparse checkout at `dest` is at,
+/// relative to its root, along with everything under those which are
+/// directories.
+pub fn checkout_paths(dest: &Path, paths: &[PathBuf]) -> CargoResult<()> {
+    let repo = try!(git2::Repository::open(dest));
+    let tree = try!(repo.revparse_single("HEAD^{tree}"));
+    let mut opts = git2::build::CheckoutBuilder::new();
+    opts.force();
+    // The root of the repository is everything, which no paths at all stand
+    // for.
+    if !paths.iter().any(|p| p.as_os_str().is_empty()) {
+        for path in paths {
+            opts.path(path.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    try!(repo.checkout_tree(&tree, Some(&mut opts)).chain_error(|| {
+        human(format!("failed to check out files in {}", dest.display()))
+    }));
+    Ok(())
+}
+
+/// The refspec fetching only what `reference` points to, if it can be fetched
+/// on its own: branches move, and abbreviated hashes can't be asked for.
+pub fn shallow_refspec(reference: &GitReference) -> Option<String> {
+    match *reference {
+        GitReference::Tag(ref s) => {
+            Some(format!("+refs/tags/{0}:refs/tags/{0}", s))
+        }
+        GitReference::Rev(ref s) if s.len() == 40 &&
+                                    s.chars().all(|c| c.is_digit(16)) => {
+            Some(format!("+{0}:refs/cargo/{0}", s))
+        }
+        _ => None,
+    }
 }
 
 impl<'a> GitCheckout<'a> {
@@ -238,7 +371,11 @@ impl<'a> GitCheckout<'a> {
                   revision: GitRevision)
                   -> CargoResult<GitCheckout<'a>>
     {
-        let repo = try!(GitCheckout::clone_repo(database.path(), into));
+        let repo = if database.is_shallow() {
+            try!(GitCheckout::init_repo(database.path(), into))
+        } else {
+            try!(GitCheckout::clone_repo(database.path(), into))
+        };
         let checkout = GitCheckout::new(into, database, revision, repo);
         try!(checkout.reset());
         Ok(checkout)
@@ -266,6 +403,37 @@ impl<'a> GitCheckout<'a> {
         Ok(repo)
     }
 
+    /// Makes an empty repository at `into` which finds its objects in the
+    /// database at `source`.
+    fn init_repo(source: &Path, into: &Path) -> CargoResult<git2::Repository> {
+        if fs::metadata(&into).is_ok() {
+            try!(fs::remove_dir_all(into).chain_error(|| {
+                human(format!("Couldn't rmdir {}", into.display()))
+            }));
+        }
+        try!(fs::create_dir_all(&into).chain_error(|| {
+            human(format!("Couldn't mkdir {}", into.display()))
+        }));
+        let repo = try!(git2::Repository::init(into));
+        try!(GitCheckout::borrow_objects(&repo, source));
+        Ok(repo)
+    }
+
+    fn borrow_objects(repo: &git2::Repository, source: &Path)
+                      -> CargoResult<()> {
+        let info = repo.path().join("objects/info");
+        try!(fs::create_dir_all(&info));
+        let mut file = try!(File::create(info.join("alternates")));
+        try!(writeln!(file, "{}", source.join("objects").display()));
+        Ok(())
+    }
+
+    /// Whether only some of the files of the revision are checked out, by
+    /// `GitDatabase::copy_sparse_to`.
+    fn is_sparse(&self) -> bool {
+        fs::metadata(self.repo.path().join("cargo-sparse")).is_ok()
+    }
+
     fn is_fresh(&self) -> bool {
         match self.repo.revparse_single("HEAD") {
             Ok(ref head) if head.id() == self.revision.0 => {
@@ -299,6 +467,7 @@ impl<'a> GitCheckout<'a> {
         info!("reset {} to {}", self.repo.path().display(), self.revision);
         let object = try!(self.repo.find_object(self.revision.0, None));
         try!(self.repo.reset(&object, git2::ResetType::Hard, None));
+        let _ = fs::remove_file(self.repo.path().join("cargo-sparse"));
         try!(File::create(ok_file));
         Ok(())
     }
@@ -387,7 +556,10 @@ impl<'a> GitCheckout<'a> {
 /// credentials until we give it a reason to not do so. To ensure we don't
 /// just sit here looping forever we keep track of authentications we've
 /// attempted and we don't try the same ones again.
-fn with_authentication<T, F>(url: &str, cfg: &git2::Config, mut f: F)
+///
+/// Keys in `identities` are tried after ssh-agent, with the same usernames.
+fn with_authentication<T, F>(url: &str, cfg: &git2::Config,
+                             identities: &[PathBuf], mut f: F)
                              -> CargoResult<T>
     where F: FnMut(&mut git2::Credentials) -> CargoResult<T>
 {
@@ -399,6 +571,7 @@ fn with_authentication<T, F>(url: &str, cfg: &git2::Config, mut f: F)
     let mut ssh_agent_attempts = Vec::new();
     let mut any_attempts = false;
     let mut tried_sshkey = false;
+    let mut key_attempts = Vec::new();
 
     let mut res = f(&mut |url, username, allowed| {
         any_attempts = true;
@@ -427,9 +600,8 @@ fn with_authentication<T, F>(url: &str, cfg: &git2::Config, mut f: F)
         }
 
         // An "SSH_KEY" authentication indicates that we need some sort of SSH
-        // authentication. This can currently either come from the ssh-agent
-        // process or from a raw in-memory SSH key. Cargo only supports using
-        // ssh-agent currently.
+        // authentication. This can either come from the ssh-agent process or
+        // from the key files of `identities`, which are tried in that order.
         //
         // If we get called with this then the only way that should be possible
         // is if a username is specified in the URL itself (e.g. `username` is
@@ -445,6 +617,11 @@ fn with_authentication<T, F>(url: &str, cfg: &git2::Config, mut f: F)
             ssh_agent_attempts.push(username.to_string());
             return git2::Cred::ssh_key_from_agent(&username)
         }
+        if allowed.contains(git2::SSH_KEY) && key_attempts.len() < identities.len() {
+            let key = &identities[key_attempts.len()];
+            key_attempts.push(key.display().to_string());
+            return git2::Cred::ssh_key(username.unwrap(), None, key, None)
+        }
 
         // Sometimes libgit2 will ask for a username/password in plaintext. This
         // is where Cargo would have an interactive prompt if we supported it,
@@ -506,23 +683,30 @@ fn with_authentication<T, F>(url: &str, cfg: &git2::Config, mut f: F)
                         ssh_agent_attempts.push(s.to_string());
                         return git2::Cred::ssh_key_from_agent(&s)
                     }
+                    if let Some(key) = identities.get(attempts - 2) {
+                        if !key_attempts.contains(&key.display().to_string()) {
+                            key_attempts.push(key.display().to_string());
+                        }
+                        return git2::Cred::ssh_key(&s, None, key, None)
+                    }
                 }
                 Err(git2::Error::from_str("no authentication available"))
             });
 
-            // If we made two attempts then that means:
+            // If we made two attempts more than there are key files then that
+            // means:
             //
             // 1. A username was requested, we returned `s`.
             // 2. An ssh key was requested, we returned to look up `s` in the
-            //    ssh agent.
-            // 3. For whatever reason that lookup failed, so we were asked again
+            //    ssh agent, and then each of the key files.
+            // 3. For whatever reason those failed, so we were asked again
             //    for another mode of authentication.
             //
-            // Essentially, if `attempts == 2` then in theory the only error was
-            // that this username failed to authenticate (e.g. no other network
-            // errors happened). Otherwise something else is funny so we bail
-            // out.
-            if attempts != 2 {
+            // Essentially, if `attempts == 2 + identities.len()` then in theory
+            // the only error was that this username failed to authenticate
+            // (e.g. no other network errors happened). Otherwise something else
+            // is funny so we bail out.
+            if attempts != 2 + identities.len() {
                 break
             }
         }
@@ -546,6 +730,11 @@ fn with_authentication<T, F>(url: &str, cfg: &git2::Config, mut f: F)
             msg.push_str(&format!("\nattempted ssh-agent authentication, but \
                                    none of the usernames {} succeeded", names));
         }
+        if key_attempts.len() > 0 {
+            msg.push_str(&format!("\nattempted authentication with the keys \
+                                   {}, but none of them succeeded",
+                                  key_attempts.join(", ")));
+        }
         if let Some(failed_cred_helper) = cred_helper_bad {
             if failed_cred_helper {
                 msg.push_str("\nattempted to find username/password via \
@@ -568,10 +757,53 @@ pub fn fetch(repo: &git2::Repository,
         bail!("attempting to update a git repository, but --frozen \
                was specified")
     }
-
-    with_authentication(url, &try!(repo.config()), |f| {
+    let parsed = try!(url.to_url());
+    try!(network::check_allowed(config, &parsed));
+
+    // libssh2 knows nothing of OpenSSH's configuration, which is applied here
+    // instead: the URL is rewritten to the host it names, and the key of the
+    // host is checked against the known hosts.
+    let host = if parsed.scheme() == "ssh" {
+        Some(ssh::host(&parsed))
+    } else {
+        None
+    };
+    let url = match host {
+        Some(ref host) => host.url.to_string(),
+        None => url.to_string(),
+    };
+    let identities = match host {
+        Some(ref host) => host.identities.clone(),
+        None => Vec::new(),
+    };
+
+    let mut host_key_error = None;
+    let mut host_key_added = None;
+    let res = with_authentication(&url, &try!(repo.config()), &identities, |f| {
         let mut cb = git2::RemoteCallbacks::new();
         cb.credentials(f);
+        if let Some(ref host) = host {
+            let host_key_error = &mut host_key_error;
+            let host_key_added = &mut host_key_added;
+            cb.certificate_check(move |cert, _hostname| {
+                let sha1 = match cert.as_hostkey().and_then(|k| k.hash_sha1()) {
+                    Some(sha1) => sha1,
+                    None => return true,
+                };
+                match host.check_host_key(sha1) {
+                    Ok(added) => {
+                        if added.is_some() {
+                            *host_key_added = added;
+                        }
+                        true
+                    }
+                    Err(e) => {
+                        *host_key_error = Some(e);
+                        false
+                    }
+                }
+            });
+        }
 
         // Create a local anonymous remote in the repository to fetch the url
         let mut remote = try!(repo.remote_anonymous(&url));
@@ -583,5 +815,30 @@ pub fn fetch(repo: &git2::Repository,
             remote.fetch(&[refspec], Some(&mut opts), None)
         }));
         Ok(())
-    })
+    });
+    if let Some(msg) = host_key_added {
+        try!(config.shell().warn(msg));
+    }
+    match host_key_error {
+        Some(e) => Err(human(e)),
+        None => res,
+    }
+}
+
+/// Fetches `refspec` from `url` into the repository at `path` with
+/// `git fetch --depth 1`.
+fn fetch_shallow(path: &Path, url: &str, refspec: &str, config: &Config)
+                 -> CargoResult<()> {
+    if !config.network_allowed() {
+        bail!("attempting to update a git repository, but --frozen \
+               was specified")
+    }
+    try!(network::check_allowed(config, &try!(url.to_url())));
+
+    let mut cmd = process("git");
+    cmd.arg("--git-dir").arg(path)
+       .arg("fetch").arg("--depth=1").arg("--no-tags")
+       .arg(url).arg(refspec);
+    try!(cmd.exec_with_output());
+    Ok(())
 }
@@ -6,6 +6,7 @@ pub use self::registry::{RegistrySource, CRATES_IO};
 pub use self::replaced::ReplacedSource;
 
 pub mod config;
+pub mod dep_archive;
 pub mod directory;
 pub mod git;
 pub mod path;
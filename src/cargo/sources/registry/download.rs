@@ -0,0 +1,330 @@
+//! Downloading `.crate` files: resumable transfers retried with backoff, and
+//! many of them at once.
+//!
+//! A transfer is written to `<name>-<version>.crate.part` next to where the
+//! `.crate` file is cached, and picks up from the end of it when it's tried
+//! again, be it after a spurious network error or in a later invocation of
+//! Cargo. The `.part` file only replaces the `.crate` file once its checksum
+//! is verified.
+
+use std::cell::Cell;
+use std::cmp;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam;
+use curl::easy::Easy;
+use rustc_serialize::hex::ToHex;
+
+use core::PackageId;
+use core::shell::{Subsystem, Verbosity};
+use ops;
+use util::errors::NetworkError;
+use util::machine_message::{self, Message};
+use util::{CargoResult, Config, Sha256, ToUrl, human};
+
+/// How many downloads run at once when `net.download-jobs` isn't set.
+const DEFAULT_JOBS: i64 = 8;
+
+/// A `.crate` file to download.
+pub struct Job {
+    pub pkg: PackageId,
+    pub url: String,
+    pub part: PathBuf,
+    pub checksum: String,
+}
+
+/// The progress of downloads, reported with `--message-format json`.
+#[derive(RustcEncodable)]
+struct DownloadMessage<'a> {
+    package_id: &'a PackageId,
+    /// `started`, `retrying`, `finished` or `failed`.
+    state: &'a str,
+    url: &'a str,
+    /// The number of the attempt when `retrying`.
+    attempt: Option<u32>,
+    /// The size of the `.crate` file when `finished`.
+    bytes: Option<u64>,
+    error: Option<&'a str>,
+}
+
+impl<'a> Message for DownloadMessage<'a> {
+    fn reason(&self) -> &str {
+        "download-progress"
+    }
+}
+
+enum Event {
+    Started(usize),
+    Retrying(usize, u32, String),
+    Finished(usize, u64),
+    Failed(usize, String),
+}
+
+/// The URL of the `.crate` file of `pkg` under the download endpoint `dl`.
+pub fn crate_url(dl: &str, pkg: &PackageId) -> CargoResult<String> {
+    let mut url = try!(dl.to_url());
+    url.path_segments_mut().unwrap()
+        .push(pkg.name())
+        .push(&pkg.version().to_string())
+        .push("download");
+    Ok(url.to_string())
+}
+
+/// The path the `.crate` file at `path` is downloaded to.
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_os_string();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Downloads all of `jobs`, several at a time, returning whether each of them
+/// was downloaded and its checksum verified.
+pub fn parallel(config: &Config, jobs: Vec<Job>, json_messages: bool)
+                -> CargoResult<Vec<bool>> {
+    let workers = match try!(config.get_i64("net.download-jobs")) {
+        Some(v) if v.val < 1 => {
+            bail!("net.download-jobs must be at least 1, but found {} in {}",
+                  v.val, v.definition)
+        }
+        Some(v) => v.val,
+        None => DEFAULT_JOBS,
+    };
+    let workers = cmp::min(workers as usize, jobs.len());
+    let retries = try!(config.net_retry()) as u32;
+
+    // Handles are configured here, as the configuration can't be shared with
+    // the workers.
+    let mut handles = Vec::new();
+    for _ in 0..workers {
+        handles.push(try!(ops::http_handle(config)));
+    }
+
+    let mut done = vec![false; jobs.len()];
+    let queue = Mutex::new((0..jobs.len()).rev().collect::<Vec<_>>());
+    let (tx, rx) = channel();
+    let mut result = Ok(());
+    crossbeam::scope(|scope| {
+        for mut handle in handles {
+            let tx = tx.clone();
+            let queue = &queue;
+            let jobs = &jobs;
+            scope.spawn(move || {
+                loop {
+                    let i = match queue.lock().unwrap().pop() {
+                        Some(i) => i,
+                        None => break,
+                    };
+                    let job = &jobs[i];
+                    tx.send(Event::Started(i)).unwrap();
+                    let res = transfer(&mut handle, &job.url, &job.part, retries,
+                                       &mut |attempt, err| {
+                        tx.send(Event::Retrying(i, attempt, err.to_string()))
+                          .unwrap();
+                    }).and_then(|()| verify(&job.part, &job.checksum));
+                    match res {
+                        Ok(bytes) => tx.send(Event::Finished(i, bytes)).unwrap(),
+                        Err(e) => tx.send(Event::Failed(i, e.to_string())).unwrap(),
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        for event in rx {
+            if result.is_err() {
+                continue
+            }
+            result = report(config, &jobs, &event, retries, json_messages);
+            match event {
+                Event::Finished(i, _) => done[i] = true,
+                _ => {}
+            }
+        }
+    });
+    try!(result);
+    Ok(done)
+}
+
+fn report(config: &Config, jobs: &[Job], event: &Event, retries: u32,
+          json_messages: bool) -> CargoResult<()> {
+    let (i, state, attempt, bytes, error) = match *event {
+        Event::Started(i) => (i, "started", None, None, None),
+        Event::Retrying(i, attempt, ref e) => {
+            (i, "retrying", Some(attempt), None, Some(&e[..]))
+        }
+        Event::Finished(i, bytes) => (i, "finished", None, Some(bytes), None),
+        Event::Failed(i, ref e) => (i, "failed", None, None, Some(&e[..])),
+    };
+    let job = &jobs[i];
+    if json_messages {
+        machine_message::emit(&DownloadMessage {
+            package_id: &job.pkg,
+            state: state,
+            url: &job.url,
+            attempt: attempt,
+            bytes: bytes,
+            error: error,
+        });
+    }
+    match *event {
+        Event::Started(..) => {
+            config.shell().status_at(Subsystem::Download, Verbosity::Normal,
+                                     "Downloading", &job.pkg)
+        }
+        Event::Retrying(_, attempt, ref e) => {
+            config.shell().warn(retry_warning(&job.pkg, retries - attempt, e))
+        }
+        // Failures are reported when the package is downloaded again on its
+        // own.
+        Event::Finished(..) | Event::Failed(..) => Ok(()),
+    }
+}
+
+/// Downloads `url` to the file `part`, continuing from where a previous
+/// attempt stopped. Spurious failures are retried up to `retries` times,
+/// waiting longer each time, and `on_retry` is told about each.
+pub fn transfer(handle: &mut Easy, url: &str, part: &Path, retries: u32,
+                on_retry: &mut FnMut(u32, &str)) -> CargoResult<()> {
+    let mut attempt = 0;
+    loop {
+        let err = match try!(transfer_once(handle, url, part)) {
+            None => return Ok(()),
+            Some(err) => err,
+        };
+        if attempt >= retries {
+            bail!("{}", err)
+        }
+        attempt += 1;
+        on_retry(attempt, &err);
+        thread::sleep(backoff(attempt));
+    }
+}
+
+/// The warning about a spurious error downloading `pkg`, which is tried
+/// again `remaining` more times at most.
+pub fn retry_warning(pkg: &PackageId, remaining: u32, err: &str) -> String {
+    format!("spurious network error downloading `{}` ({} tries remaining): {}",
+            pkg, remaining, err)
+}
+
+/// Half a second before the first retry, doubling each time up to ten
+/// seconds.
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(cmp::min(250u64 << cmp::min(attempt, 16), 10_000))
+}
+
+/// One attempt at downloading `url` to `part`, which is a spurious error if
+/// it may well work when tried again.
+fn transfer_once(handle: &mut Easy, url: &str, part: &Path)
+                 -> CargoResult<Option<String>> {
+    let offset = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+    try!(handle.get(true));
+    try!(handle.url(url));
+    try!(handle.follow_location(true));
+    try!(handle.resume_from(offset));
+
+    // What's received is written to `part` as it arrives, so an interrupted
+    // transfer keeps what it got. The status of the response, known by then,
+    // tells whether it's the rest of the file or all of it.
+    let status = Cell::new(0);
+    let mut file = None;
+    let mut error = None;
+    let res = {
+        let mut handle = handle.transfer();
+        try!(handle.header_function(|header| {
+            if let Some(code) = status_code(header) {
+                status.set(code);
+            }
+            true
+        }));
+        try!(handle.write_function(|buf| {
+            if file.is_none() {
+                // The server sent everything, even if asked for only the
+                // rest. Other protocols than HTTP have no status, and resume
+                // like a 206.
+                let opened = if status.get() == 200 {
+                    File::create(part)
+                } else {
+                    OpenOptions::new().append(true).create(true).open(part)
+                };
+                match opened {
+                    Ok(f) => file = Some(f),
+                    Err(e) => {
+                        error = Some(e);
+                        return Ok(0)
+                    }
+                }
+            }
+            match file.as_mut().unwrap().write_all(buf) {
+                Ok(()) => Ok(buf.len()),
+                Err(e) => {
+                    error = Some(e);
+                    Ok(0)
+                }
+            }
+        }));
+        handle.perform()
+    };
+    drop(file);
+    if let Some(e) = error {
+        return Err(human(format!("failed to write `{}`: {}", part.display(), e)))
+    }
+    let code = try!(handle.response_code());
+    try!(handle.resume_from(0));
+
+    // Error pages don't belong in the `.crate` file.
+    match code {
+        0 | 200 | 206 => {}
+        _ => {
+            if let Ok(file) = OpenOptions::new().write(true).open(part) {
+                try!(file.set_len(offset));
+            }
+        }
+    }
+
+    if let Err(e) = res {
+        if e.maybe_spurious() {
+            return Ok(Some(e.to_string()))
+        }
+        return Err(e.into())
+    }
+    match code {
+        0 | 200 | 206 => Ok(None),
+        // There's nothing left to send, the checksum tells whether that's
+        // because it's complete.
+        416 if offset > 0 => Ok(None),
+        429 | 500...599 => {
+            Ok(Some(format!("got {} response from `{}`", code, url)))
+        }
+        _ => bail!("failed to get 200 response from `{}`, got {}", url, code),
+    }
+}
+
+/// The status code of an HTTP response, if `header` is its status line.
+fn status_code(header: &[u8]) -> Option<u32> {
+    let line = String::from_utf8_lossy(header);
+    if !line.starts_with("HTTP/") {
+        return None
+    }
+    line.split_whitespace().nth(1).and_then(|code| code.parse().ok())
+}
+
+/// Checks the checksum of the downloaded file `part`, removing it if it's
+/// wrong so it's downloaded from scratch next time, and returns its size.
+pub fn verify(part: &Path, checksum: &str) -> CargoResult<u64> {
+    let mut contents = Vec::new();
+    try!(try!(File::open(part)).read_to_end(&mut contents));
+    let mut state = Sha256::new();
+    state.update(&contents);
+    if state.finish().to_hex() != checksum {
+        let _ = fs::remove_file(part);
+        return Err(human("checksum mismatch"))
+    }
+    Ok(contents.len() as u64)
+}
@@ -0,0 +1,165 @@
+//! The record of what a package's `.crate` file was unpacked to, kept in the
+//! `.cargo-ok` file of the unpacked directory, which is used to notice when
+//! the directory was damaged afterwards.
+//!
+//! The unpacked files are also shared between packages: each one is a hard
+//! link to a file of `$CARGO_HOME/registry/files` named after its checksum, so
+//! the files which don't change from one version of a package to the next, or
+//! which several packages contain, only take space once. A shared file which
+//! was modified through one of its links damages every package linking to it;
+//! they're all unpacked again, and the modified file is then replaced in the
+//! store.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json;
+
+use util::{paths, CargoResult, Sha256};
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct Extraction {
+    /// Each file unpacked, relative to the directory of the package.
+    files: BTreeMap<String, ExtractedFile>,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct ExtractedFile {
+    size: u64,
+    cksum: String,
+}
+
+impl Extraction {
+    /// Records the files just unpacked into `dir`.
+    pub fn record(dir: &Path) -> CargoResult<Extraction> {
+        let mut files = BTreeMap::new();
+        try!(walk(dir, dir, &mut files));
+        Ok(Extraction { files: files })
+    }
+
+    pub fn store(&self, ok: &Path) -> CargoResult<()> {
+        paths::write(ok, try!(json::encode(self)).as_bytes())
+    }
+
+    /// Replaces the files unpacked into `dir` with hard links to the same
+    /// files in `store`, adding to `store` the files it doesn't have yet.
+    ///
+    /// Files which can't be linked, e.g. because `store` is on another file
+    /// system, are left as they are.
+    pub fn share(&self, dir: &Path, store: &Path) -> CargoResult<()> {
+        for (path, file) in self.files.iter() {
+            let path = dir.join(path);
+            let meta = try!(fs::metadata(&path));
+            let name = if is_executable(&meta) {
+                format!("{}-x", file.cksum)
+            } else {
+                file.cksum.clone()
+            };
+            let shared = store.join(&name[..2]).join(&name);
+            try!(fs::create_dir_all(shared.parent().unwrap()));
+
+            let intact = match fs::metadata(&shared) {
+                Ok(meta) => {
+                    meta.len() == file.size &&
+                        try!(cksum(&shared)) == file.cksum
+                }
+                Err(..) => false,
+            };
+            if intact {
+                let mut tmp = path.clone().into_os_string();
+                tmp.push(".cargo-share");
+                let tmp = PathBuf::from(tmp);
+                if fs::hard_link(&shared, &tmp).is_ok() {
+                    try!(fs::rename(&tmp, &path));
+                }
+            } else {
+                // Either it's not there yet or it was modified since.
+                let _ = fs::remove_file(&shared);
+                let _ = fs::hard_link(&path, &shared);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the files unpacked into `dir` are still there as they were
+    /// recorded in `ok`. Files are only hashed again if they were modified
+    /// since being unpacked, and files added since are ignored.
+    ///
+    /// `.cargo-ok` files written before checksums were recorded are empty,
+    /// and the directories they mark are trusted.
+    pub fn verify(dir: &Path, ok: &Path) -> CargoResult<bool> {
+        let contents = try!(paths::read(ok));
+        if contents.is_empty() {
+            return Ok(true)
+        }
+        let extraction: Extraction = match json::decode(&contents) {
+            Ok(extraction) => extraction,
+            Err(..) => return Ok(false),
+        };
+        let unpacked = try!(try!(fs::metadata(ok)).modified());
+        for (path, file) in extraction.files.iter() {
+            let path = dir.join(path);
+            let meta = match fs::metadata(&path) {
+                Ok(meta) => meta,
+                Err(..) => return Ok(false),
+            };
+            if meta.len() != file.size {
+                return Ok(false)
+            }
+            if try!(meta.modified()) > unpacked &&
+               try!(cksum(&path)) != file.cksum {
+                return Ok(false)
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn walk(root: &Path,
+        dir: &Path,
+        files: &mut BTreeMap<String, ExtractedFile>) -> CargoResult<()> {
+    for entry in try!(fs::read_dir(dir)) {
+        let path = try!(entry).path();
+        let meta = try!(fs::symlink_metadata(&path));
+        if meta.is_dir() {
+            try!(walk(root, &path, files));
+        } else if meta.is_file() {
+            let name = path.strip_prefix(root).unwrap();
+            files.insert(name.to_string_lossy().replace("\\", "/"),
+                         ExtractedFile {
+                             size: meta.len(),
+                             cksum: try!(cksum(&path)),
+                         });
+        }
+    }
+    Ok(())
+}
+
+/// Files which only differ by their mode aren't the same file.
+#[cfg(unix)]
+fn is_executable(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+fn cksum(path: &Path) -> CargoResult<String> {
+    let mut state = Sha256::new();
+    let mut file = try!(File::open(path));
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let n = try!(file.read(&mut buf));
+        if n == 0 {
+            break
+        }
+        state.update(&buf[..n]);
+    }
+    Ok(state.finish().to_hex())
+}
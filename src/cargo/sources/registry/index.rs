@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::io::prelude::*;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use rustc_serialize::json;
 
@@ -15,6 +15,7 @@ pub struct RegistryIndex<'cfg> {
     path: Filesystem,
     cache: HashMap<String, Vec<(Summary, bool)>>,
     hashes: HashMap<(String, String), String>, // (name, vers) => cksum
+    signatures: HashMap<(String, String), String>, // (name, vers) => sig
     config: &'cfg Config,
     locked: bool,
 }
@@ -29,6 +30,7 @@ impl<'cfg> RegistryIndex<'cfg> {
             path: path.clone(),
             cache: HashMap::new(),
             hashes: HashMap::new(),
+            signatures: HashMap::new(),
             config: config,
             locked: locked,
         }
@@ -47,6 +49,13 @@ impl<'cfg> RegistryIndex<'cfg> {
         }).map(|s| s.clone())
     }
 
+    /// Return the signature listed for a specified PackageId, if it's signed.
+    pub fn signature(&mut self, pkg: &PackageId) -> CargoResult<Option<String>> {
+        try!(self.hash(pkg));
+        let key = (pkg.name().to_string(), pkg.version().to_string());
+        Ok(self.signatures.get(&key).cloned())
+    }
+
     /// Parse the on-disk metadata for the package provided
     ///
     /// Returns a list of pairs of (summary, yanked) for the package name
@@ -78,19 +87,7 @@ impl<'cfg> RegistryIndex<'cfg> {
             (self.path.clone().into_path_unlocked(), None)
         };
 
-        let fs_name = name.chars().flat_map(|c| {
-            c.to_lowercase()
-        }).collect::<String>();
-
-        // see module comment for why this is structured the way it is
-        let path = match fs_name.len() {
-            1 => path.join("1").join(&fs_name),
-            2 => path.join("2").join(&fs_name),
-            3 => path.join("3").join(&fs_name[..1]).join(&fs_name),
-            _ => path.join(&fs_name[0..2])
-                     .join(&fs_name[2..4])
-                     .join(&fs_name),
-        };
+        let path = path.join(file_path(name));
         match File::open(&path) {
             Ok(mut f) => {
                 let mut contents = String::new();
@@ -115,7 +112,8 @@ impl<'cfg> RegistryIndex<'cfg> {
     fn parse_registry_package(&mut self, line: &str)
                               -> CargoResult<(Summary, bool)> {
         let RegistryPackage {
-            name, vers, cksum, deps, features, yanked
+            name, vers, cksum, deps, features, conflicts, yanked, pubtime, size,
+            rust_version, feature_descriptions, publisher, sig
         } = try!(json::decode::<RegistryPackage>(line));
         let pkgid = try!(PackageId::new(&name, &vers, &self.source_id));
         let deps: CargoResult<Vec<Dependency>> = deps.into_iter().map(|dep| {
@@ -123,7 +121,19 @@ impl<'cfg> RegistryIndex<'cfg> {
         }).collect();
         let deps = try!(deps);
         let summary = try!(Summary::new(pkgid, deps, features));
-        let summary = summary.set_checksum(cksum.clone());
+        let descriptions = feature_descriptions.unwrap_or(HashMap::new());
+        let mut summary = summary.set_checksum(cksum.clone()).set_size(size)
+                                 .set_conflicts(conflicts.unwrap_or(Vec::new()))
+                                 .set_rust_version(rust_version)
+                                 .set_feature_descriptions(descriptions)
+                                 .set_publisher(publisher);
+        // A malformed timestamp only means the age of the version is unknown.
+        if let Some(published) = pubtime.as_ref().and_then(|t| parse_pubtime(t)) {
+            summary = summary.set_published(published);
+        }
+        if let Some(sig) = sig {
+            self.signatures.insert((name.clone(), vers.clone()), sig);
+        }
         self.hashes.insert((name, vers), cksum);
         Ok((summary, yanked.unwrap_or(false)))
     }
@@ -132,7 +142,8 @@ impl<'cfg> RegistryIndex<'cfg> {
     fn parse_registry_dependency(&self, dep: RegistryDependency)
                                  -> CargoResult<Dependency> {
         let RegistryDependency {
-            name, req, features, optional, default_features, target, kind
+            name, req, features, optional, default_features, target, kind,
+            public
         } = dep;
 
         let dep = try!(DependencyInner::parse(&name, Some(&req),
@@ -156,6 +167,7 @@ impl<'cfg> RegistryIndex<'cfg> {
         let features = features.into_iter().filter(|s| !s.is_empty()).collect();
 
         Ok(dep.set_optional(optional)
+              .set_public(public.unwrap_or(false))
               .set_default_features(default_features)
               .set_features(features)
               .set_platform(platform)
@@ -164,29 +176,64 @@ impl<'cfg> RegistryIndex<'cfg> {
     }
 }
 
+/// Parses an RFC 3339 timestamp in UTC, `YYYY-MM-DDTHH:MM:SSZ`, into seconds
+/// since the Unix epoch. Fractions of a second are ignored.
+fn parse_pubtime(s: &str) -> Option<u64> {
+    let b = s.as_bytes();
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || b[10] != b'T' ||
+       b[13] != b':' || b[16] != b':' || b[b.len() - 1] != b'Z' {
+        return None
+    }
+    let num = |from: usize, to: usize| {
+        b[from..to].iter().fold(Some(0), |n, &c| {
+            match (n, c) {
+                (Some(n), b'0'...b'9') => Some(n * 10 + (c - b'0') as u64),
+                _ => None,
+            }
+        })
+    };
+    let fields = [num(0, 4), num(5, 7), num(8, 10),
+                  num(11, 13), num(14, 16), num(17, 19)];
+    if fields.iter().any(|f| f.is_none()) {
+        return None
+    }
+    let fields = fields.iter().map(|f| f.unwrap()).collect::<Vec<_>>();
+    let (year, month, day) = (fields[0], fields[1], fields[2]);
+    if year < 1970 || month < 1 || month > 12 || day < 1 || day > 31 {
+        return None
+    }
+    // Days since the epoch of the civil date, with years starting in March
+    // so that the leap day comes last.
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let days = 365 * y + y / 4 - y / 100 + y / 400 + (153 * m + 2) / 5 + day - 1;
+    Some((days - 719468) * 86400 + fields[3] * 3600 + fields[4] * 60 + fields[5])
+}
+
 impl<'cfg> Registry for RegistryIndex<'cfg> {
     fn query(&mut self, dep: &Dependency) -> CargoResult<Vec<Summary>> {
-        let mut summaries = {
-            let summaries = try!(self.summaries(dep.name()));
-            summaries.iter().filter(|&&(_, yanked)| {
-                dep.source_id().precise().is_some() || !yanked
-            }).map(|s| s.0.clone()).collect::<Vec<_>>()
-        };
-
         // Handle `cargo update --precise` here. If specified, our own source
         // will have a precise version listed of the form `<pkg>=<req>` where
         // `<pkg>` is the name of a crate on this source and `<req>` is the
         // version requested (agument to `--precise`).
-        summaries.retain(|s| {
-            match self.source_id.precise() {
-                Some(p) if p.starts_with(dep.name()) &&
-                           p[dep.name().len()..].starts_with('=') => {
-                    let vers = &p[dep.name().len() + 1..];
-                    s.version().to_string() == vers
-                }
-                _ => true,
+        let precise = match self.source_id.precise() {
+            Some(p) if p.starts_with(dep.name()) &&
+                       p[dep.name().len()..].starts_with('=') => {
+                Some(p[dep.name().len() + 1..].to_string())
             }
-        });
+            _ => None,
+        };
+
+        // Yanked versions are only picked when locked, or when asked for
+        // precisely. Whether the latter is allowed is up to `cargo update`.
+        let summaries = {
+            let summaries = try!(self.summaries(dep.name()));
+            summaries.iter().filter(|&&(ref s, yanked)| {
+                match precise {
+                    Some(ref p) => s.version().to_string() == *p,
+                    None => dep.source_id().precise().is_some() || !yanked,
+                }
+            }).map(|s| s.0.clone()).collect::<Vec<_>>()
+        };
         summaries.query(dep)
     }
 
@@ -194,3 +241,21 @@ impl<'cfg> Registry for RegistryIndex<'cfg> {
         true
     }
 }
+
+/// The path of the file of the package `name` in an index.
+pub fn file_path(name: &str) -> PathBuf {
+    let fs_name = name.chars().flat_map(|c| {
+        c.to_lowercase()
+    }).collect::<String>();
+
+    // see module comment for why this is structured the way it is
+    let mut path = PathBuf::new();
+    match fs_name.len() {
+        1 => path.push("1"),
+        2 => path.push("2"),
+        3 => path.push(Path::new("3").join(&fs_name[..1])),
+        _ => path.push(Path::new(&fs_name[0..2]).join(&fs_name[2..4])),
+    }
+    path.push(&fs_name);
+    path
+}
@@ -5,6 +5,7 @@ use std::path::Path;
 use rustc_serialize::hex::ToHex;
 
 use core::PackageId;
+use core::shell::{Subsystem, Verbosity};
 use sources::registry::{RegistryData, RegistryConfig};
 use util::{Config, CargoResult, ChainError, human, Sha256, Filesystem};
 use util::FileLock;
@@ -71,7 +72,9 @@ impl<'cfg> RegistryData for LocalRegistry<'cfg> {
             return Ok(crate_file)
         }
 
-        try!(self.config.shell().status("Unpacking", pkg));
+        try!(self.config.shell().status_at(Subsystem::Download,
+                                           Verbosity::Normal,
+                                           "Unpacking", pkg));
 
         // We don't actually need to download anything per-se, we just need to
         // verify the checksum matches the .crate file itself.
@@ -126,6 +126,20 @@
 //! already been downloaded and unpacked. This caching allows us to only
 //! download a package when absolutely necessary.
 //!
+//! The checksum of each tarball is listed in the index, so it only protects
+//! against a tampered download as long as the index itself can be trusted.
+//! Registries may additionally sign their tarballs, listing the signature of
+//! each next to its checksum. When public keys are configured for a registry
+//! with the `signing-keys` of its `[source]` table, a tarball is only used if
+//! its signature verifies with one of those keys.
+//!
+//! The default registry may also have mirrors, listed in `registry.mirrors`,
+//! which are fetched from in order before the registry itself and whose
+//! downloads are tried in the same order. Each is fetched into its own remote
+//! of the index repository, and the checksums of packages downloaded through
+//! a mirror are checked against those of the index of the registry itself,
+//! as of when it was last fetched.
+//!
 //! # Filesystem Hierarchy
 //!
 //! Overall, the `$HOME/.cargo` looks like this when talking about the registry:
@@ -145,31 +159,42 @@
 //!         ...
 //!
 //!     # This folder is a cache for all downloaded tarballs from a registry.
-//!     # Once downloaded and verified, a tarball never changes.
+//!     # Once downloaded and verified, a tarball never changes. Until then it
+//!     # is downloaded to a `.part` file next to it, which an interrupted
+//!     # download is resumed from.
 //!     cache/
 //!         registry1-<hash>/<pkg>-<version>.crate
 //!         ...
 //!
 //!     # Location in which all tarballs are unpacked. Each tarball is known to
 //!     # be frozen after downloading, so transitively this folder is also
-//!     # frozen once its unpacked (it's never unpacked again)
+//!     # frozen once its unpacked. The `.cargo-ok` file of each package
+//!     # records the checksums of its files, and a package whose files were
+//!     # modified or removed since is unpacked again.
 //!     src/
 //!         registry1-<hash>/<pkg>-<version>/...
 //!         ...
 //! ```
 
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::prelude::*;
 use std::path::{PathBuf, Path};
 
 use flate2::read::GzDecoder;
+use rustc_serialize::hex::FromHex;
+use rustc_serialize::json;
 use tar::Archive;
 
 use core::{Source, SourceId, PackageId, Package, Summary, Registry};
 use core::dependency::Dependency;
-use sources::PathSource;
-use util::{CargoResult, Config, internal, ChainError, FileLock, Filesystem};
-use util::hex;
+use sources::{PathSource, SourceConfigMap};
+use util::{CargoResult, Config, internal, human, ChainError, FileLock, Filesystem};
+use util::{paths, hex, signature, LazyCell, Sha256};
+
+use self::extracted::Extraction;
+
+pub use self::index::file_path;
 
 const INDEX_LOCK: &'static str = ".cargo-index-lock";
 pub static CRATES_IO: &'static str = "https://github.com/rust-lang/crates.io-index";
@@ -182,6 +207,8 @@ pub struct RegistrySource<'cfg> {
     ops: Box<RegistryData + 'cfg>,
     index: index::RegistryIndex<'cfg>,
     index_locked: bool,
+    /// The public keys the packages have to be signed with, if configured.
+    signing_keys: LazyCell<Option<Vec<Vec<u8>>>>,
 }
 
 #[derive(RustcDecodable)]
@@ -202,8 +229,25 @@ struct RegistryPackage {
     vers: String,
     deps: Vec<RegistryDependency>,
     features: HashMap<String, Vec<String>>,
+    /// Pairs of features which can't be enabled together.
+    conflicts: Option<Vec<(String, String)>>,
     cksum: String,
     yanked: Option<bool>,
+    /// When the version was published, as an RFC 3339 timestamp in UTC such
+    /// as `2016-09-02T18:30:00Z`. Older registries don't record it.
+    pubtime: Option<String>,
+    /// The size in bytes of the `.crate` file, if the registry records it.
+    size: Option<u64>,
+    /// The `rust-version` declared by the manifest of the package.
+    rust_version: Option<String>,
+    /// One-line descriptions of features, by name.
+    feature_descriptions: Option<HashMap<String, String>>,
+    /// The login of the user who published the version, if the registry
+    /// records it.
+    publisher: Option<String>,
+    /// The signature of the `.crate` file, hex encoded, if the registry signs
+    /// the packages it serves.
+    sig: Option<String>,
 }
 
 #[derive(RustcDecodable)]
@@ -215,6 +259,7 @@ struct RegistryDependency {
     default_features: bool,
     target: Option<String>,
     kind: Option<String>,
+    public: Option<bool>,
 }
 
 pub trait RegistryData {
@@ -224,8 +269,17 @@ pub trait RegistryData {
     fn download(&mut self,
                 pkg: &PackageId,
                 checksum: &str) -> CargoResult<FileLock>;
+
+    /// Downloads `pkgs`, with their checksums, ahead of `download`.
+    fn prefetch(&mut self,
+                _pkgs: &[(PackageId, String)],
+                _json_messages: bool) -> CargoResult<()> {
+        Ok(())
+    }
 }
 
+mod download;
+mod extracted;
 mod index;
 mod remote;
 mod local;
@@ -236,6 +290,71 @@ fn short_name(id: &SourceId) -> String {
     format!("{}-{}", ident, hash)
 }
 
+/// The `.crate` file of `pkg` once downloaded from the remote registry
+/// `source_id`, along with the directory it is unpacked to.
+pub fn downloaded_crate(config: &Config,
+                        source_id: &SourceId,
+                        pkg: &PackageId) -> (PathBuf, PathBuf) {
+    let name = short_name(source_id);
+    let dir = format!("{}-{}", pkg.name(), pkg.version());
+    (config.registry_cache_path().join(&name).into_path_unlocked()
+           .join(format!("{}.crate", dir)),
+     config.registry_source_path().join(&name).into_path_unlocked().join(dir))
+}
+
+/// The `.crate` file of `pkg` from the registry `source_id`: local registries
+/// keep their `.crate` files, remote ones have them downloaded.
+pub fn crate_file(config: &Config,
+                  source_id: &SourceId,
+                  pkg: &PackageId) -> PathBuf {
+    match local_root(source_id) {
+        Some(root) => {
+            root.join(format!("{}-{}.crate", pkg.name(), pkg.version()))
+        }
+        None => downloaded_crate(config, source_id, pkg).0,
+    }
+}
+
+/// The line of the index of the registry `source_id` which describes `pkg`,
+/// as it is in the index.
+pub fn index_entry(config: &Config,
+                   source_id: &SourceId,
+                   pkg: &PackageId) -> CargoResult<String> {
+    let index = match local_root(source_id) {
+        Some(root) => root.join("index"),
+        None => {
+            config.registry_index_path().join(short_name(source_id))
+                  .into_path_unlocked()
+        }
+    };
+    let path = index.join(file_path(pkg.name()));
+    let mut contents = String::new();
+    try!(File::open(&path).and_then(|mut f| f.read_to_string(&mut contents))
+                          .chain_error(|| {
+        human(format!("failed to read the index of `{}` at `{}`",
+                      source_id, path.display()))
+    }));
+    let version = pkg.version().to_string();
+    for line in contents.lines().filter(|l| l.trim().len() > 0) {
+        let entry: RegistryPackage = try!(json::decode(line).chain_error(|| {
+            internal(format!("failed to parse registry's information for: {}",
+                             pkg.name()))
+        }));
+        if entry.vers == version {
+            return Ok(line.to_string())
+        }
+    }
+    bail!("the index of `{}` has no entry for `{}`", source_id, pkg)
+}
+
+fn local_root(source_id: &SourceId) -> Option<PathBuf> {
+    if source_id.is_local_registry() {
+        source_id.url().to_file_path().ok()
+    } else {
+        None
+    }
+}
+
 impl<'cfg> RegistrySource<'cfg> {
     pub fn remote(source_id: &SourceId,
                   config: &'cfg Config) -> RegistrySource<'cfg> {
@@ -268,6 +387,7 @@ impl<'cfg> RegistrySource<'cfg> {
                                              index_locked),
             index_locked: index_locked,
             ops: ops,
+            signing_keys: LazyCell::new(),
         }
     }
 
@@ -278,10 +398,17 @@ impl<'cfg> RegistrySource<'cfg> {
         self.ops.config()
     }
 
+    /// Every version of the package `name` in the index, along with whether
+    /// it was yanked.
+    pub fn versions(&mut self, name: &str) -> CargoResult<Vec<(Summary, bool)>> {
+        Ok(try!(self.index.summaries(name)).clone())
+    }
+
     /// Unpacks a downloaded package into a location where it's ready to be
     /// compiled.
     ///
-    /// No action is taken if the source looks like it's already unpacked.
+    /// No action is taken if the source is already unpacked, unless it was
+    /// damaged since, in which case it's unpacked again.
     fn unpack_package(&self,
                       pkg: &PackageId,
                       tarball: &FileLock)
@@ -295,16 +422,88 @@ impl<'cfg> RegistrySource<'cfg> {
         let dst = dst.into_path_unlocked();
         let ok = dst.join(".cargo-ok");
         if ok.exists() {
-            return Ok(dst)
+            if try!(Extraction::verify(&dst, &ok)) {
+                return Ok(dst)
+            }
+            try!(self.config.shell().warn(format!(
+                "the unpacked source of `{}` in `{}` was modified or is \
+                 incomplete, unpacking it again", pkg, dst.display())));
+            try!(fs::remove_dir_all(&dst));
+            try!(fs::create_dir_all(&dst));
         }
 
         let gz = try!(GzDecoder::new(tarball.file()));
         let mut tar = Archive::new(gz);
         try!(tar.unpack(dst.parent().unwrap()));
-        try!(File::create(&ok));
+        let extraction = try!(Extraction::record(&dst));
+        let share = try!(self.config.get_bool("registry.share-files"))
+                        .map(|v| v.val).unwrap_or(true);
+        if share {
+            let store = self.config.registry_files_path().into_path_unlocked();
+            try!(extraction.share(&dst, &store).chain_error(|| {
+                human(format!("failed to share the unpacked files of `{}`",
+                              pkg))
+            }));
+        }
+        try!(extraction.store(&ok));
         Ok(dst)
     }
 
+    /// Checks the signature of the downloaded `.crate` file of `pkg`, if
+    /// signing keys are configured for this registry.
+    ///
+    /// A package without a signature is only accepted with a warning, and not
+    /// at all when the lockfile is required to be up to date, so builds which
+    /// pass `--locked` fail closed.
+    fn verify_signature(&mut self, pkg: &PackageId, tarball: &FileLock)
+                        -> CargoResult<()> {
+        let config = self.config;
+        let source_id = self.source_id.clone();
+        let keys = try!(self.signing_keys.get_or_try_init(|| -> CargoResult<_> {
+            let sources = try!(SourceConfigMap::new(config));
+            let files = match sources.signing_keys(&source_id) {
+                Some(files) => files,
+                None => return Ok(None),
+            };
+            let mut keys = Vec::new();
+            for file in files {
+                keys.push(try!(paths::read_bytes(file).chain_error(|| {
+                    human(format!("failed to read the signing key `{}`",
+                                  file.display()))
+                })));
+            }
+            Ok(Some(keys))
+        }));
+        let keys = match *keys {
+            Some(ref keys) => keys,
+            None => return Ok(()),
+        };
+
+        let sig = match try!(self.index.signature(pkg)) {
+            Some(sig) => sig,
+            None if config.lock_update_allowed() => {
+                return config.shell().warn(format!(
+                    "`{}` isn't signed by its registry, builds with --locked \
+                     will refuse it", pkg))
+            }
+            None => bail!("`{}` isn't signed by its registry, but signing \
+                           keys are configured for it", pkg),
+        };
+        let sig = try!(sig.from_hex().map_err(|e| {
+            human(format!("invalid signature for `{}`: {}", pkg, e))
+        }));
+        let mut state = Sha256::new();
+        state.update(&try!(paths::read_bytes(tarball.path())));
+        let digest = state.finish();
+        for key in keys.iter() {
+            if try!(signature::verify(key, &digest, &sig)) {
+                return Ok(())
+            }
+        }
+        bail!("failed to verify the signature of `{}` with the signing keys \
+               configured for its registry", pkg)
+    }
+
     fn do_update(&mut self) -> CargoResult<()> {
         try!(self.ops.update_index());
         let path = self.ops.index_path();
@@ -354,6 +553,7 @@ impl<'cfg> Source for RegistrySource<'cfg> {
     fn download(&mut self, package: &PackageId) -> CargoResult<Package> {
         let hash = try!(self.index.hash(package));
         let path = try!(self.ops.download(package, &hash));
+        try!(self.verify_signature(package, &path));
         let path = try!(self.unpack_package(package, &path).chain_error(|| {
             internal(format!("failed to unpack package `{}`", package))
         }));
@@ -362,7 +562,23 @@ impl<'cfg> Source for RegistrySource<'cfg> {
         src.download(package)
     }
 
+    fn prefetch(&mut self, ids: &[PackageId], json_messages: bool)
+                -> CargoResult<()> {
+        let mut pkgs = Vec::new();
+        for id in ids {
+            pkgs.push((id.clone(), try!(self.index.hash(id))));
+        }
+        self.ops.prefetch(&pkgs, json_messages)
+    }
+
     fn fingerprint(&self, pkg: &Package) -> CargoResult<String> {
         Ok(pkg.package_id().version().to_string())
     }
+
+    fn is_yanked(&mut self, pkg: &PackageId) -> CargoResult<bool> {
+        let summaries = try!(self.index.summaries(pkg.name()));
+        Ok(summaries.iter().any(|&(ref s, yanked)| {
+            yanked && s.version() == pkg.version()
+        }))
+    }
 }
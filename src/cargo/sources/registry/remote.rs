@@ -1,21 +1,26 @@
+use std::fs;
 use std::io::SeekFrom;
 use std::io::prelude::*;
 use std::path::Path;
 
 use curl::easy::{Easy, List};
 use git2;
-use rustc_serialize::hex::ToHex;
-use rustc_serialize::json;
+use rustc_serialize::json::{self, Json};
 use url::Url;
 
 use core::{PackageId, SourceId};
+use core::shell::{Subsystem, Verbosity};
 use ops;
 use sources::git;
 use sources::registry::{RegistryData, RegistryConfig, INDEX_LOCK};
+use sources::registry::{download, index};
+use util::hex;
 use util::network;
 use util::paths;
 use util::{FileLock, Filesystem};
-use util::{Config, CargoResult, ChainError, human, Sha256, ToUrl};
+use util::{Config, CargoResult, ChainError, human};
+
+const DOWNLOAD_LOCK: &'static str = ".package-download-lock";
 
 pub struct RemoteRegistry<'cfg> {
     index_path: Filesystem,
@@ -36,6 +41,178 @@ impl<'cfg> RemoteRegistry<'cfg> {
             handle: None,
         }
     }
+
+    /// The index repositories this registry is updated from, in order, with
+    /// the names of their remotes: the mirrors configured in
+    /// `registry.mirrors` if it's the default registry, and then itself.
+    fn sources(&self) -> CargoResult<Vec<(String, String)>> {
+        let mut sources = Vec::new();
+        if self.source_id == try!(SourceId::crates_io(self.config)) {
+            if let Some(mirrors) = try!(self.config.get_list("registry.mirrors")) {
+                for (url, _) in mirrors.val {
+                    let remote = format!("mirror-{}", hex::short_hash(&url));
+                    sources.push((url, remote));
+                }
+            }
+        }
+        sources.push((self.source_id.url().to_string(), "origin".to_string()));
+        Ok(sources)
+    }
+
+    fn verify_mirrors(&self) -> CargoResult<bool> {
+        Ok(try!(self.config.get_bool("registry.verify-mirrors"))
+               .map(|v| v.val).unwrap_or(true))
+    }
+
+    /// The download endpoints of all the sources of the index fetched so far.
+    fn mirror_dls(&self) -> CargoResult<Vec<String>> {
+        let sources = try!(self.sources());
+        if sources.len() == 1 {
+            return Ok(Vec::new())
+        }
+        let path = self.index_path.clone().into_path_unlocked();
+        let repo = try!(git2::Repository::open(&path));
+        let mut dls = Vec::new();
+        for (_, remote) in sources {
+            if let Some(config) = remote_config(&repo, &remote) {
+                dls.push(config.dl);
+            }
+        }
+        Ok(dls)
+    }
+
+    /// Checks that `checksum`, the checksum of `pkg` in the index, is also its
+    /// checksum in the index of the registry itself when it's from a mirror.
+    /// A version which the index of the registry doesn't have, once it was
+    /// fetched, isn't accepted from a mirror either.
+    fn verify_canonical(&self, pkg: &PackageId, checksum: &str)
+                        -> CargoResult<()> {
+        match try!(self.canonical_checksum(pkg)) {
+            Canonical::Unchecked => {}
+            Canonical::Missing => {
+                bail!("`{}` is in the index of a mirror but not in the index \
+                       of `{}`", pkg, self.source_id.url())
+            }
+            Canonical::Checksum(ref canonical) if canonical != checksum => {
+                bail!("the checksum of `{}` in the index of a mirror doesn't \
+                       match the one in the index of `{}`",
+                      pkg, self.source_id.url())
+            }
+            Canonical::Checksum(..) => {}
+        }
+        Ok(())
+    }
+
+    /// The checksum of `pkg` in the index of the registry itself, if mirrors
+    /// are configured and checked and it was fetched.
+    fn canonical_checksum(&self, pkg: &PackageId) -> CargoResult<Canonical> {
+        if try!(self.sources()).len() == 1 || !try!(self.verify_mirrors()) {
+            return Ok(Canonical::Unchecked)
+        }
+        let path = self.index_path.clone().into_path_unlocked();
+        let repo = try!(git2::Repository::open(&path));
+        if repo.refname_to_id("refs/remotes/origin/master").is_err() {
+            return Ok(Canonical::Unchecked)
+        }
+        let contents = match read_remote_file(&repo, "origin",
+                                              &index::file_path(pkg.name())) {
+            Some(contents) => contents,
+            None => return Ok(Canonical::Missing),
+        };
+        let vers = pkg.version().to_string();
+        for line in String::from_utf8_lossy(&contents).lines() {
+            let entry = match Json::from_str(line) {
+                Ok(entry) => entry,
+                Err(..) => continue,
+            };
+            if entry.find("vers").and_then(|v| v.as_string()) != Some(&vers[..]) {
+                continue
+            }
+            return Ok(match entry.find("cksum").and_then(|v| v.as_string()) {
+                Some(cksum) => Canonical::Checksum(cksum.to_string()),
+                None => Canonical::Missing,
+            })
+        }
+        Ok(Canonical::Missing)
+    }
+
+    /// Downloads the `.crate` file of `pkg` from the endpoint `dl` to `part`.
+    fn fetch_crate(&mut self, pkg: &PackageId, dl: &str, part: &Path)
+                   -> CargoResult<()> {
+        let url = try!(download::crate_url(dl, pkg));
+        try!(self.config.shell().status_at(Subsystem::Download,
+                                           Verbosity::Trace,
+                                           "Fetching", &url));
+
+        let config = self.config;
+        let retries = try!(config.net_retry()) as u32;
+        let handle = match self.handle {
+            Some(ref mut handle) => handle,
+            None => {
+                self.handle = Some(try!(ops::http_handle(self.config)));
+                self.handle.as_mut().unwrap()
+            }
+        };
+        download::transfer(handle, &url, part, retries, &mut |attempt, err| {
+            let msg = download::retry_warning(pkg, retries - attempt, err);
+            let _ = config.shell().warn(msg);
+        })
+    }
+
+    /// Moves the downloaded and verified `part` into the cache file `dst`.
+    fn store(&self, dst: &mut FileLock, part: &Path) -> CargoResult<()> {
+        try!(dst.write_all(&try!(paths::read_bytes(part))));
+        try!(dst.seek(SeekFrom::Start(0)));
+        try!(fs::remove_file(part));
+        Ok(())
+    }
+}
+
+/// What the index of a registry itself says about a package from a mirror.
+enum Canonical {
+    /// It wasn't checked, as no mirror is used, the checks are turned off or
+    /// the index of the registry was never fetched.
+    Unchecked,
+    /// The index of the registry doesn't have the version.
+    Missing,
+    Checksum(String),
+}
+
+/// The `config.json` of the index as last fetched from the remote `remote`.
+fn remote_config(repo: &git2::Repository, remote: &str)
+                 -> Option<RegistryConfig> {
+    read_remote_file(repo, remote, Path::new("config.json")).and_then(|c| {
+        json::decode(&String::from_utf8_lossy(&c)).ok()
+    })
+}
+
+/// The contents of the file at `path` in the index as last fetched from the
+/// remote `remote`, if any.
+fn read_remote_file(repo: &git2::Repository, remote: &str, path: &Path)
+                    -> Option<Vec<u8>> {
+    let reference = format!("refs/remotes/{}/master", remote);
+    let tree = repo.refname_to_id(&reference)
+                   .and_then(|oid| repo.find_commit(oid))
+                   .and_then(|commit| commit.tree());
+    let mut tree = match tree {
+        Ok(tree) => tree,
+        Err(..) => return None,
+    };
+    let mut components = path.iter().peekable();
+    while let Some(component) = components.next() {
+        let id = match component.to_str().and_then(|c| tree.get_name(c)) {
+            Some(entry) => entry.id(),
+            None => return None,
+        };
+        if components.peek().is_none() {
+            return repo.find_blob(id).ok().map(|blob| blob.content().to_vec())
+        }
+        tree = match repo.find_tree(id) {
+            Ok(tree) => tree,
+            Err(..) => return None,
+        };
+    }
+    None
 }
 
 impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
@@ -49,7 +226,16 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
                                                 "the registry index"));
         let path = lock.path().parent().unwrap();
         let contents = try!(paths::read(&path.join("config.json")));
-        let config = try!(json::decode(&contents));
+        let mut config: RegistryConfig = try!(json::decode(&contents));
+
+        // Mirrors only serve the index and downloads, the registry itself is
+        // still the one published to.
+        if try!(self.sources()).len() > 1 {
+            let repo = try!(git2::Repository::open(path));
+            if let Some(canonical) = remote_config(&repo, "origin") {
+                config.api = canonical.api;
+            }
+        }
         Ok(Some(config))
     }
 
@@ -61,6 +247,7 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
         // This way if there's a problem the error gets printed before we even
         // hit the index, which may not actually read this configuration.
         try!(ops::http_handle(self.config));
+        try!(network::check_allowed(self.config, self.source_id.url()));
 
         // Then we actually update the index
         try!(self.index_path.create_dir());
@@ -77,7 +264,9 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
             git2::Repository::init(path)
         }));
 
-        if self.source_id.url().host_str() == Some("github.com") {
+        let sources = try!(self.sources());
+        if sources.len() == 1 &&
+           self.source_id.url().host_str() == Some("github.com") {
             if let Ok(oid) = repo.refname_to_id("refs/heads/master") {
                 let handle = match self.handle {
                     Some(ref mut handle) => handle,
@@ -95,17 +284,46 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
             }
         }
 
-        // git fetch origin
-        let url = self.source_id.url().to_string();
-        let refspec = "refs/heads/*:refs/remotes/origin/*";
+        // git fetch, from the first source which can be fetched
+        let mut fetched = None;
+        for (i, &(ref url, ref remote)) in sources.iter().enumerate() {
+            let refspec = format!("refs/heads/*:refs/remotes/{}/*", remote);
+            match git::fetch(&repo, url, &refspec, &self.config) {
+                Ok(()) => {
+                    fetched = Some(remote);
+                    break
+                }
+                Err(e) => {
+                    if i == sources.len() - 1 {
+                        return Err(e).chain_error(|| {
+                            human(format!("failed to fetch `{}`", url))
+                        })
+                    }
+                    try!(self.config.shell().warn(format!(
+                        "failed to fetch `{}`, trying `{}` instead: {}",
+                        url, sources[i + 1].0, e)));
+                }
+            }
+        }
+        let fetched = fetched.unwrap();
 
-        try!(git::fetch(&repo, &url, refspec, &self.config).chain_error(|| {
-            human(format!("failed to fetch `{}`", url))
-        }));
+        // The checksums of packages downloaded through a mirror are checked
+        // against the index of the registry itself, so it's kept up to date
+        // too where it can be reached.
+        if &fetched[..] != "origin" && try!(self.verify_mirrors()) {
+            let url = self.source_id.url().to_string();
+            let refspec = "refs/heads/*:refs/remotes/origin/*";
+            if let Err(e) = git::fetch(&repo, &url, refspec, &self.config) {
+                try!(self.config.shell().warn(format!(
+                    "failed to fetch `{}`, the checksums of packages from its \
+                     mirrors are checked against its index as of its last \
+                     update: {}", url, e)));
+            }
+        }
 
-        // git reset --hard origin/master
-        let reference = "refs/remotes/origin/master";
-        let oid = try!(repo.refname_to_id(reference));
+        // git reset --hard <source>/master
+        let reference = format!("refs/remotes/{}/master", fetched);
+        let oid = try!(repo.refname_to_id(&reference));
         trace!("[{}] updating to rev {}", self.source_id, oid);
         let object = try!(repo.find_object(oid, None));
         try!(repo.reset(&object, git2::ResetType::Hard, None));
@@ -116,60 +334,112 @@ impl<'cfg> RegistryData for RemoteRegistry<'cfg> {
                 -> CargoResult<FileLock> {
         let filename = format!("{}-{}.crate", pkg.name(), pkg.version());
         let path = Path::new(&filename);
+        let dst = try!(self.cache_path.open_rw(path, self.config, &filename));
+        if try!(dst.file().metadata()).len() > 0 {
+            return Ok(dst)
+        }
+        drop(dst);
+
+        // The `.part` file is only written while holding the download lock,
+        // which is taken before the lock on the `.crate` file as in
+        // `prefetch`. Another invocation of Cargo may have downloaded it in
+        // the meantime.
+        let _lock = try!(self.cache_path.open_rw(Path::new(DOWNLOAD_LOCK),
+                                                 self.config,
+                                                 "the package cache"));
         let mut dst = try!(self.cache_path.open_rw(path, self.config, &filename));
-        let meta = try!(dst.file().metadata());
-        if meta.len() > 0 {
+        if try!(dst.file().metadata()).len() > 0 {
             return Ok(dst)
         }
-        try!(self.config.shell().status("Downloading", pkg));
+        try!(network::check_allowed(self.config, self.source_id.url()));
+        try!(self.config.shell().status_at(Subsystem::Download,
+                                           Verbosity::Normal,
+                                           "Downloading", pkg));
 
-        let config = try!(self.config()).unwrap();
-        let mut url = try!(config.dl.to_url());
-        url.path_segments_mut().unwrap()
-            .push(pkg.name())
-            .push(&pkg.version().to_string())
-            .push("download");
+        // Packages are downloaded from where the index was fetched from and,
+        // failing that, from the other mirrors of the registry and the
+        // registry itself.
+        let mut dls = vec![try!(self.config()).unwrap().dl];
+        for dl in try!(self.mirror_dls()) {
+            if !dls.contains(&dl) {
+                dls.push(dl);
+            }
+        }
 
-        let handle = match self.handle {
-            Some(ref mut handle) => handle,
-            None => {
-                self.handle = Some(try!(ops::http_handle(self.config)));
-                self.handle.as_mut().unwrap()
+        let part = download::part_path(dst.path());
+        for (i, dl) in dls.iter().enumerate() {
+            match self.fetch_crate(pkg, dl, &part) {
+                Ok(()) => break,
+                Err(e) => {
+                    if i == dls.len() - 1 {
+                        return Err(e)
+                    }
+                    try!(self.config.shell().warn(format!(
+                        "failed to download `{}` from `{}`, trying `{}` \
+                         instead: {}", pkg, dl, dls[i + 1], e)));
+                }
             }
-        };
-        // TODO: don't download into memory, but ensure that if we ctrl-c a
-        //       download we should resume either from the start or the middle
-        //       on the next time
-        try!(handle.get(true));
-        try!(handle.url(&url.to_string()));
-        try!(handle.follow_location(true));
-        let mut state = Sha256::new();
-        let mut body = Vec::new();
-        {
-            let mut handle = handle.transfer();
-            try!(handle.write_function(|buf| {
-                state.update(buf);
-                body.extend_from_slice(buf);
-                Ok(buf.len())
-            }));
-            try!(network::with_retry(self.config, || {
-                handle.perform()
-            }))
-        }
-        let code = try!(handle.response_code());
-        if code != 200 && code != 0 {
-            bail!("failed to get 200 response from `{}`, got {}", url, code)
         }
 
         // Verify what we just downloaded
-        if state.finish().to_hex() != checksum {
+        if download::verify(&part, checksum).is_err() {
             bail!("failed to verify the checksum of `{}`", pkg)
         }
-
-        try!(dst.write_all(&body));
-        try!(dst.seek(SeekFrom::Start(0)));
+        try!(self.verify_canonical(pkg, checksum));
+        try!(self.store(&mut dst, &part));
         Ok(dst)
     }
+
+    fn prefetch(&mut self, pkgs: &[(PackageId, String)], json_messages: bool)
+                -> CargoResult<()> {
+        // Only what isn't in the cache already is downloaded, from where the
+        // index was fetched from. Anything failing here is downloaded again
+        // on its own, falling back on mirrors and reporting errors.
+        try!(self.cache_path.create_dir());
+        let cache = self.cache_path.clone().into_path_unlocked();
+        let dl = try!(self.config()).unwrap().dl;
+        let mut jobs = Vec::new();
+        let mut fetched = Vec::new();
+        for &(ref pkg, ref checksum) in pkgs {
+            let path = cache.join(format!("{}-{}.crate", pkg.name(),
+                                          pkg.version()));
+            if fs::metadata(&path).map(|m| m.len() > 0).unwrap_or(false) {
+                continue
+            }
+            jobs.push(download::Job {
+                pkg: pkg.clone(),
+                url: try!(download::crate_url(&dl, pkg)),
+                part: download::part_path(&path),
+                checksum: checksum.clone(),
+            });
+            fetched.push((pkg, checksum));
+        }
+        if jobs.is_empty() {
+            return Ok(())
+        }
+        try!(network::check_allowed(self.config, self.source_id.url()));
+
+        // Downloads of the same packages by other invocations of Cargo would
+        // write to the same `.part` files.
+        let _lock = try!(self.cache_path.open_rw(Path::new(DOWNLOAD_LOCK),
+                                                 self.config,
+                                                 "the package cache"));
+        let done = try!(download::parallel(self.config, jobs, json_messages));
+        for ((pkg, checksum), done) in fetched.into_iter().zip(done) {
+            if !done || self.verify_canonical(pkg, checksum).is_err() {
+                continue
+            }
+            let filename = format!("{}-{}.crate", pkg.name(), pkg.version());
+            let mut dst = try!(self.cache_path.open_rw(Path::new(&filename),
+                                                       self.config,
+                                                       &filename));
+            if try!(dst.file().metadata()).len() == 0 {
+                let part = download::part_path(dst.path());
+                try!(self.store(&mut dst, &part));
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Updating the index is done pretty regularly so we want it to be as fast as
@@ -49,6 +49,13 @@ impl<'cfg> Source for ReplacedSource<'cfg> {
         Ok(pkg.map_source(&self.replace_with, &self.to_replace))
     }
 
+    fn prefetch(&mut self, ids: &[PackageId], json_messages: bool)
+                -> CargoResult<()> {
+        let ids = ids.iter().map(|id| id.with_source_id(&self.replace_with))
+                     .collect::<Vec<_>>();
+        self.inner.prefetch(&ids, json_messages)
+    }
+
     fn fingerprint(&self, id: &Package) -> CargoResult<String> {
         self.inner.fingerprint(&id)
     }
@@ -57,4 +64,9 @@ impl<'cfg> Source for ReplacedSource<'cfg> {
         let id = id.with_source_id(&self.replace_with);
         self.inner.verify(&id)
     }
+
+    fn is_yanked(&mut self, id: &PackageId) -> CargoResult<bool> {
+        let id = id.with_source_id(&self.replace_with);
+        self.inner.is_yanked(&id)
+    }
 }
@@ -1,4 +1,4 @@
-use std::cell::{RefCell, RefMut, Cell};
+use std::cell::{Ref, RefCell, RefMut, Cell};
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::collections::hash_map::HashMap;
 use std::collections::HashSet;
@@ -13,10 +13,13 @@ use std::str::FromStr;
 
 use rustc_serialize::{Encodable,Encoder};
 use toml;
-use core::shell::{Verbosity, ColorConfig};
+use core::shell::{Verbosity, ColorConfig, Subsystem};
 use core::MultiShell;
+use sources::dep_archive;
 use util::{CargoResult, CargoError, ChainError, Rustc, internal, human};
 use util::{Filesystem, FileLock, LazyCell};
+use util::env_file;
+use util::network::NetException;
 
 use util::toml as cargo_toml;
 
@@ -32,7 +35,13 @@ pub struct Config {
     extra_verbose: Cell<bool>,
     frozen: Cell<bool>,
     locked: Cell<bool>,
+    offline: Cell<bool>,
+    net_exceptions: RefCell<Vec<NetException>>,
+    vendored: RefCell<Option<PathBuf>>,
+    dep_archive: RefCell<Option<PathBuf>>,
+    env_file: RefCell<Option<PathBuf>>,
     git_lock: LazyCell<FileLock>,
+    credentials: RefCell<HashMap<String, (String, Option<u64>)>>,
 }
 
 impl Config {
@@ -49,7 +58,13 @@ impl Config {
             extra_verbose: Cell::new(false),
             frozen: Cell::new(false),
             locked: Cell::new(false),
+            offline: Cell::new(false),
+            net_exceptions: RefCell::new(Vec::new()),
+            vendored: RefCell::new(None),
+            dep_archive: RefCell::new(None),
+            env_file: RefCell::new(None),
             git_lock: LazyCell::new(),
+            credentials: RefCell::new(HashMap::new()),
         }
     }
 
@@ -86,6 +101,13 @@ impl Config {
         })
     }
 
+    /// The tokens obtained from credential providers so far, by registry,
+    /// along with when they expire.
+    pub fn credential_cache(&self)
+                            -> RefMut<HashMap<String, (String, Option<u64>)>> {
+        self.credentials.borrow_mut()
+    }
+
     pub fn registry_index_path(&self) -> Filesystem {
         self.home_path.join("registry").join("index")
     }
@@ -98,6 +120,10 @@ impl Config {
         self.home_path.join("registry").join("src")
     }
 
+    pub fn registry_files_path(&self) -> Filesystem {
+        self.home_path.join("registry").join("files")
+    }
+
     pub fn shell(&self) -> RefMut<MultiShell> {
         self.shell.borrow_mut()
     }
@@ -337,22 +363,152 @@ impl Config {
         self.extra_verbose.set(extra_verbose);
         self.frozen.set(frozen);
         self.locked.set(locked);
+        try!(self.configure_network(false, &[]));
 
         Ok(())
     }
 
+    /// Whether the output of build scripts is shown, as with `-vv` or
+    /// `-vv=compile`.
     pub fn extra_verbose(&self) -> bool {
-        self.extra_verbose.get()
+        self.extra_verbose.get() ||
+            self.shell().verbosity_of(Subsystem::Compile) >= Verbosity::Trace
     }
 
     pub fn network_allowed(&self) -> bool {
         !self.frozen.get()
     }
 
+    /// Configures whether the network may only be used to reach the
+    /// endpoints in `allow_net`. Both are also read from the `net.offline`
+    /// and `net.allow-net` configuration keys.
+    pub fn configure_network(&self, offline: bool, allow_net: &[String])
+                             -> CargoResult<()> {
+        let cfg_offline = try!(self.get_bool("net.offline")).map(|v| v.val);
+        self.offline.set(offline || cfg_offline.unwrap_or(false));
+
+        let mut exceptions = Vec::new();
+        if let Some(list) = try!(self.get_list("net.allow-net")) {
+            for &(ref spec, _) in list.val.iter() {
+                exceptions.push(try!(spec.parse()));
+            }
+        }
+        for spec in allow_net {
+            exceptions.push(try!(spec.parse()));
+        }
+        if !allow_net.is_empty() && !self.offline.get() {
+            bail!("--allow-net can only be used together with --offline")
+        }
+        *self.net_exceptions.borrow_mut() = exceptions;
+        Ok(())
+    }
+
+    pub fn offline(&self) -> bool {
+        self.offline.get()
+    }
+
+    pub fn net_exceptions(&self) -> Ref<Vec<NetException>> {
+        self.net_exceptions.borrow()
+    }
+
+    /// Replaces every registry with the vendor directory `dir` for this
+    /// invocation only, on top of the configured `source.*` replacements.
+    pub fn configure_vendored(&self, dir: Option<&str>) -> CargoResult<()> {
+        let dir = match dir {
+            Some(dir) => self.cwd.join(dir),
+            None => return Ok(()),
+        };
+        if !dir.is_dir() {
+            bail!("the vendor directory `{}` passed to --vendored does not \
+                   exist", dir.display())
+        }
+        *self.vendored.borrow_mut() = Some(dir);
+        Ok(())
+    }
+
+    pub fn vendored(&self) -> Option<PathBuf> {
+        self.vendored.borrow().clone()
+    }
+
+    /// Replaces every registry and git repository with the packages of the
+    /// archive `file` made by `cargo fetch --archive`, for this invocation
+    /// only. The archive is unpacked the first time it's used.
+    pub fn configure_dep_archive(&self, file: Option<&str>) -> CargoResult<()> {
+        let file = match file {
+            Some(file) => self.cwd.join(file),
+            None => return Ok(()),
+        };
+        if self.vendored.borrow().is_some() {
+            bail!("--dep-archive can't be used together with --vendored")
+        }
+        if !file.is_file() {
+            bail!("the dependency archive `{}` passed to --dep-archive does \
+                   not exist", file.display())
+        }
+        let dir = try!(dep_archive::unpack(self, &file));
+        *self.dep_archive.borrow_mut() = Some(dir);
+        Ok(())
+    }
+
+    /// The directory source the archive passed to `--dep-archive` is
+    /// unpacked to.
+    pub fn dep_archive(&self) -> Option<PathBuf> {
+        self.dep_archive.borrow().clone()
+    }
+
+    /// Sets the environment variables in `file` for the binaries and tests
+    /// run in this invocation, instead of those of the `run.env-file`
+    /// configuration key.
+    pub fn configure_env_file(&self, file: Option<&str>) -> CargoResult<()> {
+        let file = match file {
+            Some(file) => self.cwd.join(file),
+            None => return Ok(()),
+        };
+        if !file.is_file() {
+            bail!("the environment file `{}` passed to --env-file does not \
+                   exist", file.display())
+        }
+        *self.env_file.borrow_mut() = Some(file);
+        Ok(())
+    }
+
+    /// The environment variables the binaries and tests run by Cargo get on
+    /// top of its own environment. They're left out of the environment of
+    /// the compiler, so they don't affect what's built.
+    pub fn run_env(&self) -> CargoResult<Vec<(String, String)>> {
+        let file = match *self.env_file.borrow() {
+            Some(ref file) => Some(file.clone()),
+            // Relative to the directory of the configuration file setting it.
+            None => try!(self.get_string("run.env-file")).map(|v| {
+                match v.definition {
+                    Definition::Path(ref config) => {
+                        config.parent().unwrap().join(&v.val)
+                    }
+                    Definition::Environment => self.cwd.join(&v.val),
+                }
+            }),
+        };
+        match file {
+            Some(file) => env_file::parse(&file),
+            None => Ok(Vec::new()),
+        }
+    }
+
     pub fn lock_update_allowed(&self) -> bool {
         !self.frozen.get() && !self.locked.get()
     }
 
+    /// Returns the paths of all configuration files which are in effect, in
+    /// order of precedence (highest first).
+    pub fn config_files(&self) -> CargoResult<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        try!(walk_tree(&self.cwd, |_, path| {
+            files.push(path.to_path_buf());
+            Ok(())
+        }));
+        Ok(files)
+    }
+
     fn load_values(&self) -> CargoResult<HashMap<String, ConfigValue>> {
         let mut cfg = CV::Table(HashMap::new(), PathBuf::from("."));
 
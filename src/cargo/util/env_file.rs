@@ -0,0 +1,78 @@
+//! Environment files, which set the environment variables of the binaries
+//! and tests Cargo runs.
+//!
+//! Each line is `KEY=VALUE`, optionally preceded by `export`. Blank lines and
+//! lines starting with `#` are ignored. A value in double quotes may contain
+//! the escapes `\n`, `\t`, `\"` and `\\`, and one in single quotes is taken
+//! as is.
+
+use std::path::Path;
+
+use util::{CargoResult, ChainError, human, paths};
+
+/// Reads the variables of the environment file at `path`, in order.
+pub fn parse(path: &Path) -> CargoResult<Vec<(String, String)>> {
+    let contents = try!(paths::read(path).chain_error(|| {
+        human(format!("failed to read the environment file `{}`", path.display()))
+    }));
+    let mut vars = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let line = if line.starts_with("export ") {
+            line[7..].trim_left()
+        } else {
+            line
+        };
+        let var = try!(parse_var(line).chain_error(|| {
+            human(format!("invalid line {} in the environment file `{}`, \
+                           expected `KEY=VALUE`", i + 1, path.display()))
+        }));
+        vars.push(var);
+    }
+    Ok(vars)
+}
+
+fn parse_var(line: &str) -> Option<(String, String)> {
+    let eq = match line.find('=') {
+        Some(eq) => eq,
+        None => return None,
+    };
+    let key = line[..eq].trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None
+    }
+    let value = line[eq + 1..].trim();
+    let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        match unescape(&value[1..value.len() - 1]) {
+            Some(value) => value,
+            None => return None,
+        }
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        value[1..value.len() - 1].to_string()
+    } else {
+        value.to_string()
+    };
+    Some((key.to_string(), value))
+}
+
+fn unescape(s: &str) -> Option<String> {
+    let mut ret = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue
+        }
+        ret.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            _ => return None,
+        });
+    }
+    Some(ret)
+}
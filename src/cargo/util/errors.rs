@@ -23,6 +23,8 @@ pub type CargoResult<T> = Result<T, Box<CargoError>>;
 pub trait CargoError: Error + Send + 'static {
     fn is_human(&self) -> bool { false }
     fn cargo_cause(&self) -> Option<&CargoError>{ None }
+    /// The error encoded as a JSON object, for errors tools can act on.
+    fn to_json(&self) -> Option<String> { None }
 }
 
 impl Error for Box<CargoError> {
@@ -33,6 +35,19 @@ impl Error for Box<CargoError> {
 impl CargoError for Box<CargoError> {
     fn is_human(&self) -> bool { (**self).is_human() }
     fn cargo_cause(&self) -> Option<&CargoError> { (**self).cargo_cause() }
+    fn to_json(&self) -> Option<String> { (**self).to_json() }
+}
+
+/// The JSON encoding of `err` or of the first of its causes which has one.
+pub fn error_json(err: &CargoError) -> Option<String> {
+    let mut cur = Some(err);
+    while let Some(err) = cur {
+        if let Some(json) = err.to_json() {
+            return Some(json)
+        }
+        cur = err.cargo_cause();
+    }
+    None
 }
 
 // =============================================================================
@@ -237,6 +252,7 @@ impl<E: fmt::Display> fmt::Display for Human<E> {
 impl<E: CargoError> CargoError for Human<E> {
     fn is_human(&self) -> bool { true }
     fn cargo_cause(&self) -> Option<&CargoError> { self.0.cargo_cause() }
+    fn to_json(&self) -> Option<String> { self.0.to_json() }
 }
 
 // =============================================================================
@@ -14,16 +14,53 @@
 //! Conveniently whenever a process in the job object spawns a new process the
 //! child will be associated with the job object as well. This means if we add
 //! ourselves to the job object we create then everything will get torn down!
+//!
+//! While the units of a build are being run, however, cargo itself catches
+//! the first Ctrl-C so it can stop starting new units, let the ones running
+//! finish or die along with it, and leave the fingerprints of the target
+//! directory describing what was actually built. A second Ctrl-C terminates
+//! cargo as usual.
+
+use std::sync::atomic::{AtomicBool, ATOMIC_BOOL_INIT, Ordering};
+
+static INTERRUPTED: AtomicBool = ATOMIC_BOOL_INIT;
 
 pub fn setup() {
     unsafe { imp::setup() }
 }
 
+/// Catches Ctrl-C until the returned value is dropped, after which it
+/// terminates cargo again.
+pub fn catch_interrupts() -> Interrupts {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    Interrupts { previous: unsafe { imp::catch_interrupts() } }
+}
+
+/// Returns whether Ctrl-C was pressed since `catch_interrupts` was called.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+pub struct Interrupts {
+    previous: imp::Handler,
+}
+
+impl Drop for Interrupts {
+    fn drop(&mut self) {
+        unsafe { imp::restore_interrupts(self.previous) }
+    }
+}
+
 #[cfg(unix)]
 mod imp {
     use std::env;
+    use std::sync::atomic::Ordering;
     use libc;
 
+    use super::INTERRUPTED;
+
+    pub type Handler = libc::sighandler_t;
+
     pub unsafe fn setup() {
         // There's a test case for the behavior of
         // when-cargo-is-killed-subprocesses-are-also-killed, but that requires
@@ -33,6 +70,22 @@ mod imp {
             libc::setsid();
         }
     }
+
+    pub unsafe fn catch_interrupts() -> Handler {
+        libc::signal(libc::SIGINT, on_interrupt as Handler)
+    }
+
+    pub unsafe fn restore_interrupts(previous: Handler) {
+        libc::signal(libc::SIGINT, previous);
+    }
+
+    extern fn on_interrupt(_signal: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        // The next Ctrl-C isn't caught.
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+    }
 }
 
 #[cfg(windows)]
@@ -41,6 +94,11 @@ mod imp {
     extern crate winapi;
 
     use std::mem;
+    use std::sync::atomic::Ordering;
+
+    use super::INTERRUPTED;
+
+    pub type Handler = ();
 
     pub unsafe fn setup() {
         // Creates a new job object for us to use and then adds ourselves to it.
@@ -88,4 +146,23 @@ mod imp {
         // be killed. This typically won't happen unless Cargo itself is
         // ctrl-c'd.
     }
+
+    pub unsafe fn catch_interrupts() {
+        kernel32::SetConsoleCtrlHandler(Some(on_interrupt), winapi::TRUE);
+    }
+
+    pub unsafe fn restore_interrupts(_previous: ()) {
+        kernel32::SetConsoleCtrlHandler(Some(on_interrupt), winapi::FALSE);
+    }
+
+    unsafe extern "system" fn on_interrupt(_ctrl_type: winapi::DWORD)
+                                           -> winapi::BOOL {
+        // The next Ctrl-C is left to the default handler, which terminates
+        // cargo.
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            winapi::FALSE
+        } else {
+            winapi::TRUE
+        }
+    }
 }
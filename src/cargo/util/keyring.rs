@@ -0,0 +1,272 @@
+//! Storing secrets in the keyring of the platform: the Keychain on macOS, the
+//! Credential Manager on Windows, and the Secret Service elsewhere, through
+//! `secret-tool` from libsecret.
+//!
+//! Secrets are identified by a service and an account, e.g. `cargo-registry`
+//! and the URL of a registry.
+
+pub use self::imp::{get, set, delete};
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::os::raw::{c_char, c_void};
+    use std::ptr;
+
+    use util::CargoResult;
+
+    const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+
+    #[link(name = "Security", kind = "framework")]
+    extern {
+        fn SecKeychainAddGenericPassword(keychain: *mut c_void,
+                                         service_len: u32,
+                                         service: *const c_char,
+                                         account_len: u32,
+                                         account: *const c_char,
+                                         password_len: u32,
+                                         password: *const c_void,
+                                         item: *mut *mut c_void) -> i32;
+        fn SecKeychainFindGenericPassword(keychain: *const c_void,
+                                          service_len: u32,
+                                          service: *const c_char,
+                                          account_len: u32,
+                                          account: *const c_char,
+                                          password_len: *mut u32,
+                                          password: *mut *mut c_void,
+                                          item: *mut *mut c_void) -> i32;
+        fn SecKeychainItemModifyAttributesAndData(item: *mut c_void,
+                                                  attrs: *const c_void,
+                                                  len: u32,
+                                                  data: *const c_void) -> i32;
+        fn SecKeychainItemDelete(item: *mut c_void) -> i32;
+        fn SecKeychainItemFreeContent(attrs: *mut c_void,
+                                      data: *mut c_void) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern {
+        fn CFRelease(cf: *const c_void);
+    }
+
+    /// Looks up the item of `account` of `service`, along with its secret.
+    fn find(service: &str, account: &str)
+            -> CargoResult<Option<(*mut c_void, String)>> {
+        let mut len = 0;
+        let mut data = ptr::null_mut();
+        let mut item = ptr::null_mut();
+        let status = unsafe {
+            SecKeychainFindGenericPassword(ptr::null(),
+                                           service.len() as u32,
+                                           service.as_ptr() as *const c_char,
+                                           account.len() as u32,
+                                           account.as_ptr() as *const c_char,
+                                           &mut len, &mut data, &mut item)
+        };
+        match status {
+            0 => {}
+            ERR_SEC_ITEM_NOT_FOUND => return Ok(None),
+            e => bail!("failed to read from the keychain (error {})", e),
+        }
+        let secret = unsafe {
+            let bytes = ::std::slice::from_raw_parts(data as *const u8,
+                                                     len as usize);
+            let secret = String::from_utf8_lossy(bytes).into_owned();
+            SecKeychainItemFreeContent(ptr::null_mut(), data);
+            secret
+        };
+        Ok(Some((item, secret)))
+    }
+
+    pub fn get(service: &str, account: &str) -> CargoResult<Option<String>> {
+        Ok(try!(find(service, account)).map(|(item, secret)| {
+            unsafe { CFRelease(item) }
+            secret
+        }))
+    }
+
+    pub fn set(service: &str, account: &str, secret: &str) -> CargoResult<()> {
+        let status = match try!(find(service, account)) {
+            Some((item, _)) => unsafe {
+                let status = SecKeychainItemModifyAttributesAndData(
+                    item, ptr::null(), secret.len() as u32,
+                    secret.as_ptr() as *const c_void);
+                CFRelease(item);
+                status
+            },
+            None => unsafe {
+                SecKeychainAddGenericPassword(ptr::null_mut(),
+                                              service.len() as u32,
+                                              service.as_ptr() as *const c_char,
+                                              account.len() as u32,
+                                              account.as_ptr() as *const c_char,
+                                              secret.len() as u32,
+                                              secret.as_ptr() as *const c_void,
+                                              ptr::null_mut())
+            },
+        };
+        if status != 0 {
+            bail!("failed to write to the keychain (error {})", status)
+        }
+        Ok(())
+    }
+
+    pub fn delete(service: &str, account: &str) -> CargoResult<bool> {
+        let item = match try!(find(service, account)) {
+            Some((item, _)) => item,
+            None => return Ok(false),
+        };
+        let status = unsafe {
+            let status = SecKeychainItemDelete(item);
+            CFRelease(item);
+            status
+        };
+        if status != 0 {
+            bail!("failed to delete from the keychain (error {})", status)
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    extern crate winapi;
+    extern crate advapi32;
+
+    use std::ffi::OsStr;
+    use std::io;
+    use std::mem;
+    use std::os::windows::prelude::*;
+    use std::ptr;
+    use std::slice;
+
+    use self::winapi::{CREDENTIALW, PCREDENTIALW, DWORD};
+    use self::winapi::{CRED_TYPE_GENERIC, CRED_PERSIST_LOCAL_MACHINE};
+    use self::advapi32::{CredDeleteW, CredFree, CredReadW, CredWriteW};
+
+    use util::CargoResult;
+
+    const ERROR_NOT_FOUND: i32 = 1168;
+
+    fn wide(s: &str) -> Vec<u16> {
+        OsStr::new(s).encode_wide().chain(Some(0)).collect()
+    }
+
+    fn target(service: &str, account: &str) -> Vec<u16> {
+        wide(&format!("{}:{}", service, account))
+    }
+
+    pub fn get(service: &str, account: &str) -> CargoResult<Option<String>> {
+        let target = target(service, account);
+        let mut cred: PCREDENTIALW = ptr::null_mut();
+        unsafe {
+            if CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut cred) == 0 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() == Some(ERROR_NOT_FOUND) {
+                    return Ok(None)
+                }
+                bail!("failed to read from the credential manager: {}", err)
+            }
+            let blob = slice::from_raw_parts((*cred).CredentialBlob,
+                                             (*cred).CredentialBlobSize as usize);
+            let secret = String::from_utf8_lossy(blob).into_owned();
+            CredFree(cred as *mut _);
+            Ok(Some(secret))
+        }
+    }
+
+    pub fn set(service: &str, account: &str, secret: &str) -> CargoResult<()> {
+        let mut target = target(service, account);
+        let mut user = wide(account);
+        unsafe {
+            let mut cred: CREDENTIALW = mem::zeroed();
+            cred.Type = CRED_TYPE_GENERIC;
+            cred.TargetName = target.as_mut_ptr();
+            cred.CredentialBlobSize = secret.len() as DWORD;
+            cred.CredentialBlob = secret.as_ptr() as *mut u8;
+            cred.Persist = CRED_PERSIST_LOCAL_MACHINE;
+            cred.UserName = user.as_mut_ptr();
+            if CredWriteW(&mut cred, 0) == 0 {
+                bail!("failed to write to the credential manager: {}",
+                      io::Error::last_os_error())
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete(service: &str, account: &str) -> CargoResult<bool> {
+        let target = target(service, account);
+        unsafe {
+            if CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) == 0 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() == Some(ERROR_NOT_FOUND) {
+                    return Ok(false)
+                }
+                bail!("failed to delete from the credential manager: {}", err)
+            }
+        }
+        Ok(true)
+    }
+}
+
+// The Secret Service is a D-Bus API, which `secret-tool` saves us from
+// speaking ourselves.
+#[cfg(not(any(target_os = "macos", windows)))]
+mod imp {
+    use std::io::prelude::*;
+    use std::process::Stdio;
+
+    use util::{process, human, CargoResult, ChainError};
+
+    fn secret_tool(args: &[&str], input: Option<&str>)
+                   -> CargoResult<(bool, String)> {
+        let mut cmd = process("secret-tool");
+        cmd.args(args);
+        let mut child = try!(cmd.build_command()
+                                .stdin(Stdio::piped())
+                                .stdout(Stdio::piped())
+                                .stderr(Stdio::piped())
+                                .spawn().chain_error(|| {
+            human("failed to run `secret-tool` to access the keyring, is \
+                   libsecret installed?")
+        }));
+        if let Some(input) = input {
+            try!(child.stdin.as_mut().unwrap().write_all(input.as_bytes()));
+        }
+        let output = try!(child.wait_with_output());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() && !stderr.trim().is_empty() {
+            bail!("`secret-tool {}` failed: {}", args[0], stderr.trim())
+        }
+        Ok((output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    pub fn get(service: &str, account: &str) -> CargoResult<Option<String>> {
+        // A secret which isn't found fails quietly.
+        let (found, secret) = try!(secret_tool(&["lookup",
+                                                 "service", service,
+                                                 "account", account], None));
+        Ok(if found { Some(secret) } else { None })
+    }
+
+    pub fn set(service: &str, account: &str, secret: &str) -> CargoResult<()> {
+        let label = format!("{} for {}", service, account);
+        let (stored, _) = try!(secret_tool(&["store", "--label", &label,
+                                             "service", service,
+                                             "account", account],
+                                           Some(secret)));
+        if !stored {
+            bail!("`secret-tool store` failed to store the secret")
+        }
+        Ok(())
+    }
+
+    pub fn delete(service: &str, account: &str) -> CargoResult<bool> {
+        if try!(get(service, account)).is_none() {
+            return Ok(false)
+        }
+        try!(secret_tool(&["clear", "service", service, "account", account],
+                         None));
+        Ok(true)
+    }
+}
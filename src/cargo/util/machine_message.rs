@@ -0,0 +1,20 @@
+use rustc_serialize::Encodable;
+use rustc_serialize::json::{self, Json};
+
+/// A message emitted on stdout when `--message-format=json` is in effect.
+///
+/// Each message is printed as a single line of JSON with an extra `reason`
+/// field identifying what kind of message it is.
+pub trait Message: Encodable {
+    fn reason(&self) -> &str;
+}
+
+pub fn emit<T: Message>(t: &T) {
+    let json = json::encode(t).unwrap();
+    let mut map = match json.parse().unwrap() {
+        Json::Object(obj) => obj,
+        _ => panic!("not a json object"),
+    };
+    map.insert("reason".to_string(), Json::String(t.reason().to_string()));
+    println!("{}", Json::Object(map));
+}
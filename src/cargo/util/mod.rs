@@ -3,7 +3,7 @@ pub use self::config::Config;
 pub use self::dependency_queue::{DependencyQueue, Fresh, Dirty, Freshness};
 pub use self::errors::{CargoResult, CargoError, ChainError, CliResult};
 pub use self::errors::{CliError, ProcessError, CargoTestError};
-pub use self::errors::{Human, caused_human};
+pub use self::errors::{Human, caused_human, error_json};
 pub use self::errors::{process_error, internal_error, internal, human};
 pub use self::flock::{FileLock, Filesystem};
 pub use self::graph::Graph;
@@ -17,10 +17,12 @@ pub use self::rustc::Rustc;
 pub use self::sha256::Sha256;
 pub use self::to_semver::ToSemver;
 pub use self::to_url::ToUrl;
-pub use self::vcs::{GitRepo, HgRepo};
+pub use self::vcs::{GitRepo, HgRepo, JjRepo, SaplingRepo, FossilRepo};
+pub use self::vcs::{Checkout, discover_checkout};
 pub use self::read2::read2;
 
 pub mod config;
+pub mod env_file;
 pub mod errors;
 pub mod graph;
 pub mod hex;
@@ -32,8 +34,11 @@ pub mod to_semver;
 pub mod to_url;
 pub mod toml;
 pub mod lev_distance;
+pub mod machine_message;
 pub mod job;
 pub mod network;
+pub mod keyring;
+pub mod signature;
 mod cfg;
 mod dependency_queue;
 mod rustc;
@@ -1,4 +1,94 @@
-use util::{CargoResult, Config, errors};
+use std::str::FromStr;
+
+use url::Url;
+
+use core::SourceId;
+use util::{CargoResult, CargoError, Config, ToUrl, errors};
+
+/// An endpoint which may still be reached when `--offline` is given, as
+/// specified with `--allow-net`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum NetException {
+    /// `registry:NAME`, either `crates-io` or a registry defined by a
+    /// `[source.NAME]` table in the configuration.
+    Registry(String),
+    /// `git:URL`, all git repositories at `URL` or in a path below it.
+    Git(String),
+}
+
+impl FromStr for NetException {
+    type Err = Box<CargoError>;
+
+    fn from_str(s: &str) -> CargoResult<NetException> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("registry"), Some(name)) if !name.is_empty() => {
+                Ok(NetException::Registry(name.to_string()))
+            }
+            (Some("git"), Some(url)) if !url.is_empty() => {
+                Ok(NetException::Git(url.to_string()))
+            }
+            _ => bail!("invalid network exception `{}`, expected \
+                        `registry:NAME` or `git:URL`", s),
+        }
+    }
+}
+
+impl NetException {
+    fn allows(&self, config: &Config, url: &Url) -> CargoResult<bool> {
+        match *self {
+            NetException::Registry(ref name) => {
+                let registry = if name == "crates-io" {
+                    try!(SourceId::crates_io(config)).url().clone()
+                } else {
+                    let key = format!("source.{}.registry", name);
+                    match try!(config.get_string(&key)) {
+                        Some(url) => try!(url.val.to_url()),
+                        None => bail!("no registry named `{}` is configured, \
+                                       expected `crates-io` or the name of a \
+                                       `[source]` with a `registry` key", name),
+                    }
+                };
+                Ok(registry.as_str().trim_right_matches('/') ==
+                   url.as_str().trim_right_matches('/'))
+            }
+            NetException::Git(ref prefix) => {
+                let prefix = try!(prefix.to_url());
+                Ok(url_is_within(url, &prefix))
+            }
+        }
+    }
+}
+
+/// Whether `url` is `prefix` or lies below it, comparing whole path
+/// components so that `https://host/org` doesn't also allow
+/// `https://host/org-other`.
+fn url_is_within(url: &Url, prefix: &Url) -> bool {
+    if url.scheme() != prefix.scheme() || url.host_str() != prefix.host_str() ||
+       url.port_or_known_default() != prefix.port_or_known_default() {
+        return false
+    }
+    let path = url.path().trim_right_matches('/');
+    let prefix = prefix.path().trim_right_matches('/');
+    path == prefix ||
+        (path.starts_with(prefix) && path[prefix.len()..].starts_with('/'))
+}
+
+/// Checks that `url` may be reached. This is always the case unless
+/// `--offline` was given, in which case the endpoint has to be allowed with
+/// `--allow-net`.
+pub fn check_allowed(config: &Config, url: &Url) -> CargoResult<()> {
+    if !config.offline() {
+        return Ok(())
+    }
+    for exception in config.net_exceptions().iter() {
+        if try!(exception.allows(config, url)) {
+            return Ok(())
+        }
+    }
+    bail!("attempting to reach `{}`, but --offline was specified and it is \
+           not allowed by --allow-net", url)
+}
 
 /// Wrapper method for network call retry logic.
 ///
@@ -84,3 +174,31 @@ fn with_retry_repeats_the_call_then_works() {
     let result = with_retry(&config, || results.pop().unwrap());
     assert_eq!(result.unwrap(), ())
 }
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+    use super::url_is_within;
+
+    fn within(url: &str, prefix: &str) -> bool {
+        url_is_within(&Url::parse(url).unwrap(), &Url::parse(prefix).unwrap())
+    }
+
+    #[test]
+    fn whole_components() {
+        assert!(within("https://host/org/repo", "https://host/org"));
+        assert!(within("https://host/org/repo", "https://host/org/"));
+        assert!(within("https://host/org", "https://host/org"));
+        assert!(within("https://host/org/repo", "https://host/"));
+        assert!(!within("https://host/org-other/repo", "https://host/org"));
+        assert!(!within("https://host/organisation", "https://host/org"));
+    }
+
+    #[test]
+    fn same_endpoint() {
+        assert!(within("https://host:443/org", "https://host/org"));
+        assert!(!within("https://hostile/org", "https://host/org"));
+        assert!(!within("http://host/org", "https://host/org"));
+        assert!(!within("https://host:8443/org", "https://host/org"));
+    }
+}
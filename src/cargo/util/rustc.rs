@@ -53,4 +53,11 @@ impl Rustc {
     pub fn process(&self) -> ProcessBuilder {
         util::process(&self.path)
     }
+
+    /// Whether this is a nightly or locally built compiler, which accepts
+    /// unstable `-Z` flags.
+    pub fn is_nightly(&self) -> bool {
+        self.verbose_version.contains("-nightly") ||
+            self.verbose_version.contains("-dev")
+    }
 }
@@ -0,0 +1,73 @@
+//! Verifying the signatures registries make of the packages they serve, and
+//! signing the provenance attestations of packages being published and the
+//! requests made to registries.
+//!
+//! Signatures are RSA signatures (PKCS #1 v1.5) of the SHA-256 digest of the
+//! data, as made by `openssl dgst -sha256 -sign`, and keys are in PEM format.
+
+pub use self::imp::{generate, sign, verify};
+
+// Like `Sha256`, this relies on the OpenSSL linked in by someone upstream.
+#[cfg(not(windows))]
+mod imp {
+    extern crate openssl;
+
+    use self::openssl::crypto::hash::Type;
+    use self::openssl::crypto::pkey::PKey;
+
+    use util::{CargoResult, human};
+
+    /// Whether `signature` is a signature of the data with the SHA-256 digest
+    /// `digest`, made with the private key of the public key `key`.
+    pub fn verify(key: &[u8], digest: &[u8], signature: &[u8]) -> CargoResult<bool> {
+        let key = try!(PKey::public_key_from_pem(&mut &key[..]).map_err(|e| {
+            human(format!("failed to parse the public key: {}", e))
+        }));
+        Ok(key.verify_with_hash(digest, signature, Type::SHA256))
+    }
+
+    /// Signs the data with the SHA-256 digest `digest` with the private key
+    /// `key`.
+    pub fn sign(key: &[u8], digest: &[u8]) -> CargoResult<Vec<u8>> {
+        let key = try!(PKey::private_key_from_pem(&mut &key[..]).map_err(|e| {
+            human(format!("failed to parse the private key: {}", e))
+        }));
+        Ok(key.sign_with_hash(digest, Type::SHA256))
+    }
+
+    /// Generates a new 2048-bit RSA key, returning its private and public keys
+    /// in PEM format.
+    pub fn generate() -> CargoResult<(Vec<u8>, Vec<u8>)> {
+        let mut key = PKey::new();
+        key.gen(2048);
+        let mut private = Vec::new();
+        let mut public = Vec::new();
+        try!(key.write_pem(&mut private).map_err(|e| {
+            human(format!("failed to write the private key: {}", e))
+        }));
+        try!(key.write_pub_pem(&mut public).map_err(|e| {
+            human(format!("failed to write the public key: {}", e))
+        }));
+        Ok((private, public))
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use util::CargoResult;
+
+    pub fn verify(_key: &[u8], _digest: &[u8], _signature: &[u8])
+                  -> CargoResult<bool> {
+        bail!("verifying the signatures of packages isn't supported on this \
+               platform yet")
+    }
+
+    pub fn sign(_key: &[u8], _digest: &[u8]) -> CargoResult<Vec<u8>> {
+        bail!("signing with a key isn't supported on this platform yet, \
+               configure `provenance.command` instead")
+    }
+
+    pub fn generate() -> CargoResult<(Vec<u8>, Vec<u8>)> {
+        bail!("generating keys isn't supported on this platform yet")
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::default::Default;
 use std::fmt;
 use std::fs;
@@ -11,7 +11,9 @@ use rustc_serialize::{Decodable, Decoder};
 
 use core::{SourceId, Profiles, PackageIdSpec, GitReference, WorkspaceConfig};
 use core::{Summary, Manifest, Target, Dependency, DependencyInner, PackageId};
-use core::{EitherManifest, VirtualManifest};
+use core::{EitherManifest, VirtualManifest, SystemDependency, LinkPreference};
+use core::{Prebuilt, Runner};
+use core::FeatureMetadata;
 use core::dependency::{Kind, Platform};
 use core::manifest::{LibKind, Profile, ManifestMetadata};
 use core::package_id::Metadata;
@@ -111,7 +113,10 @@ pub fn to_manifest(contents: &str,
         Some(path) => path.to_path_buf(),
         None => manifest.clone(),
     };
-    let root = try!(parse(contents, &manifest, config));
+    let mut root = try!(parse(contents, &manifest, config));
+    try!(inherit_from_workspace(&mut root, &manifest, &layout.root, config));
+    let conflicts = try!(feature_conflicts(&mut root));
+    let feature_metadata = try!(feature_metadata(&mut root));
     let mut d = toml::Decoder::new(toml::Value::Table(root));
     let manifest: TomlManifest = try!(Decodable::decode(&mut d).map_err(|e| {
         human(e.to_string())
@@ -122,6 +127,10 @@ pub fn to_manifest(contents: &str,
             if let Some(ref toml) = d.toml {
                 add_unused_keys(&mut manifest, toml, String::new());
             }
+            manifest.set_feature_docs(feature_docs(contents));
+            manifest.set_feature_metadata(feature_metadata);
+            let summary = manifest.summary().clone().set_conflicts(conflicts);
+            manifest.set_summary(summary);
             if !manifest.targets().iter().any(|t| !t.is_custom_build()) {
                 bail!("no targets specified in the manifest\n  \
                        either src/lib.rs, src/main.rs, a [lib] section, or \
@@ -138,7 +147,9 @@ pub fn to_manifest(contents: &str,
     };
 
     fn add_unused_keys(m: &mut Manifest, toml: &toml::Value, key: String) {
-        if key == "package.metadata" {
+        if key == "package.metadata" || key == "workspace.dependencies" ||
+           key == "workspace.package" || key == "workspace.tasks" ||
+           key == "workspace.default-members" {
             return
         }
         match *toml {
@@ -161,6 +172,166 @@ pub fn to_manifest(contents: &str,
     }
 }
 
+/// Extracts the documentation of each entry in the `[features]` table of a
+/// manifest. The TOML parser discards comments, so this scans the source for
+/// runs of `##` comments directly preceding a feature:
+///
+/// ```toml
+/// [features]
+/// ## Enables serialization of all public types.
+/// serde = ["serde_derive"]
+/// ```
+fn feature_docs(contents: &str) -> BTreeMap<String, String> {
+    let mut docs = BTreeMap::new();
+    let mut in_features = false;
+    let mut pending = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_features = line.trim_matches(|c| c == '[' || c == ']')
+                              .trim() == "features";
+            pending.clear();
+            continue
+        }
+        if !in_features {
+            continue
+        }
+        if line.starts_with("##") {
+            let doc = &line[2..];
+            pending.push(if doc.starts_with(' ') {&doc[1..]} else {doc});
+            continue
+        }
+        if !line.starts_with('#') {
+            if let Some(pos) = line.find('=') {
+                let name = line[..pos].trim().trim_matches(|c| {
+                    c == '"' || c == '\''
+                });
+                if !pending.is_empty() {
+                    docs.insert(name.to_string(), pending.join("\n"));
+                }
+            }
+        }
+        pending.clear();
+    }
+    docs
+}
+
+/// Takes the `[features.conflicts]` table out of a manifest, as the rest of
+/// `[features]` only lists what each feature enables. Each key is a feature
+/// followed by those it can't be enabled together with:
+///
+/// ```toml
+/// [features.conflicts]
+/// native-tls = ["rustls"]
+/// ```
+fn feature_conflicts(root: &mut toml::Table)
+                     -> CargoResult<Vec<(String, String)>> {
+    let features = match root.get_mut("features") {
+        Some(&mut toml::Value::Table(ref mut features)) => features,
+        _ => return Ok(Vec::new()),
+    };
+    // Anything but a table is a feature which happens to be named
+    // `conflicts`, and is left alone.
+    let table = match features.remove("conflicts") {
+        Some(toml::Value::Table(table)) => table,
+        Some(other) => {
+            features.insert("conflicts".to_string(), other);
+            return Ok(Vec::new())
+        }
+        None => return Ok(Vec::new()),
+    };
+    let mut conflicts = Vec::new();
+    for (feature, others) in table.iter() {
+        let others = try!(others.as_slice().and_then(|array| {
+            array.iter().map(|v| v.as_str()).collect::<Option<Vec<_>>>()
+        }).chain_error(|| {
+            human(format!("`features.conflicts.{}` must be an array of \
+                           strings", feature))
+        }));
+        for other in others {
+            for name in [&feature[..], other].iter() {
+                if !features.contains_key(*name) {
+                    bail!("`features.conflicts.{}` names `{}`, which isn't a \
+                           feature", feature, name)
+                }
+            }
+            if feature == other {
+                bail!("feature `{}` can't conflict with itself", feature)
+            }
+            conflicts.push((feature.clone(), other.to_string()));
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Takes the `[features.metadata]` table out of a manifest, which describes
+/// the features declared next to it for tools and readers:
+///
+/// ```toml
+/// [features.metadata.rustls]
+/// description = "Use rustls rather than the platform's TLS library"
+/// stability = "unstable"
+/// docs = "https://example.com/tls.html"
+/// ```
+fn feature_metadata(root: &mut toml::Table)
+                    -> CargoResult<BTreeMap<String, FeatureMetadata>> {
+    let features = match root.get_mut("features") {
+        Some(&mut toml::Value::Table(ref mut features)) => features,
+        _ => return Ok(BTreeMap::new()),
+    };
+    // Anything but a table is a feature which happens to be named
+    // `metadata`, and is left alone.
+    let table = match features.remove("metadata") {
+        Some(toml::Value::Table(table)) => table,
+        Some(other) => {
+            features.insert("metadata".to_string(), other);
+            return Ok(BTreeMap::new())
+        }
+        None => return Ok(BTreeMap::new()),
+    };
+    let mut metadata = BTreeMap::new();
+    for (feature, value) in table.iter() {
+        if !features.contains_key(feature) {
+            bail!("`features.metadata` describes `{}`, which isn't a feature",
+                  feature)
+        }
+        let value = try!(value.as_table().chain_error(|| {
+            human(format!("`features.metadata.{}` must be a table", feature))
+        }));
+        let mut meta = FeatureMetadata::default();
+        for (key, value) in value.iter() {
+            let value = try!(value.as_str().chain_error(|| {
+                human(format!("`features.metadata.{}.{}` must be a string",
+                              feature, key))
+            })).to_string();
+            match &key[..] {
+                "description" => meta.description = Some(value),
+                "stability" => {
+                    match &value[..] {
+                        "stable" | "unstable" | "deprecated" => {}
+                        _ => bail!("invalid stability `{}` of feature `{}`, \
+                                    expected `stable`, `unstable` or \
+                                    `deprecated`", value, feature),
+                    }
+                    meta.stability = Some(value);
+                }
+                "docs" => {
+                    try!((&value[..]).to_url().map_err(|e| {
+                        human(format!("invalid docs URL of feature `{}`: {}",
+                                      feature, e))
+                    }));
+                    meta.docs = Some(value);
+                }
+                _ => bail!("unknown key `{}` in `features.metadata.{}`, \
+                            expected one of `description`, `stability` or \
+                            `docs`", key, feature),
+            }
+        }
+        metadata.insert(feature.clone(), meta);
+    }
+    Ok(metadata)
+}
+
 pub fn parse(toml: &str,
              file: &Path,
              config: &Config) -> CargoResult<toml::Table> {
@@ -202,6 +373,270 @@ in the future.", file.display());
     Err(human(error_str))
 }
 
+/// The tables of a manifest which declare dependencies.
+const DEPENDENCY_TABLES: &'static [&'static str] = &[
+    "dependencies",
+    "dev-dependencies",
+    "dev_dependencies",
+    "build-dependencies",
+    "build_dependencies",
+];
+
+/// The keys of `[package]` which can be inherited from `[workspace.package]`.
+const INHERITABLE_PACKAGE_KEYS: &'static [&'static str] = &[
+    "version",
+    "authors",
+    "license",
+    "repository",
+];
+
+/// Replaces the values of a manifest declared with `workspace = true` by those
+/// given by the root of its workspace. Keys of `[package]` are taken from
+/// `[workspace.package]`, and dependencies from `[workspace.dependencies]`,
+/// which they may add `features` to or make `optional`. Returns whether there
+/// were any.
+///
+/// ```toml
+/// [package]
+/// version = { workspace = true }
+///
+/// [dependencies]
+/// log = { workspace = true, features = ["release_max_level_info"] }
+/// ```
+pub fn inherit_from_workspace(manifest: &mut toml::Table,
+                              file: &Path,
+                              dir: &Path,
+                              config: &Config) -> CargoResult<bool> {
+    let explicit_root = ["package", "project"].iter().filter_map(|key| {
+        manifest.get(*key).and_then(|p| p.lookup("workspace"))
+                .and_then(|w| w.as_str()).map(|w| w.to_string())
+    }).next();
+    // The root of a workspace can inherit from itself.
+    let own = manifest.get("workspace").map(|w| {
+        (file.to_path_buf(), dir.to_path_buf(), w.clone())
+    });
+    let package_key = if manifest.contains_key("package") {"package"} else {"project"};
+    let keys = match manifest.get(package_key) {
+        Some(&toml::Value::Table(ref package)) => {
+            package.iter().filter(|&(k, v)| k != "metadata" && is_inherited(v))
+                   .map(|(k, _)| k.clone()).collect::<Vec<_>>()
+        }
+        _ => Vec::new(),
+    };
+
+    let deps_inherited = dependency_tables(manifest).iter().any(|t| {
+        t.values().any(is_inherited)
+    });
+    if keys.is_empty() && !deps_inherited {
+        return Ok(false)
+    }
+    let (root_file, root_dir, workspace) = match own {
+        Some(own) => own,
+        None => {
+            match try!(find_workspace(dir, explicit_root, config)) {
+                Some(root) => root,
+                None => bail!("`{}` inherits from the workspace, but it isn't \
+                               a member of one", file.display()),
+            }
+        }
+    };
+
+    if !keys.is_empty() {
+        let root_package = match workspace.lookup("package") {
+            Some(&toml::Value::Table(ref package)) => package.clone(),
+            Some(..) => bail!("`workspace.package` of `{}` must be a table",
+                              root_file.display()),
+            None => toml::Table::new(),
+        };
+        let package = match manifest.get_mut(package_key) {
+            Some(&mut toml::Value::Table(ref mut package)) => package,
+            _ => unreachable!(),
+        };
+        for key in keys {
+            if !INHERITABLE_PACKAGE_KEYS.contains(&&key[..]) {
+                bail!("`{}.{}` cannot be inherited from the workspace",
+                      package_key, key)
+            }
+            if package[&key].as_table().map(|t| t.len()) != Some(1) ||
+               package[&key].lookup("workspace") != Some(&toml::Value::Boolean(true)) {
+                bail!("`{}.{}` is inherited from the workspace, which only \
+                       allows `{} = {{ workspace = true }}`", package_key, key,
+                      key)
+            }
+            let value = match root_package.get(&key) {
+                Some(value) => value.clone(),
+                None => bail!("`{}.{}` is inherited from the workspace, but \
+                               `workspace.package` of `{}` doesn't declare it",
+                              package_key, key, root_file.display()),
+            };
+            package.insert(key, value);
+        }
+    }
+
+    let empty = toml::Table::new();
+    let root_deps = match workspace.lookup("dependencies") {
+        Some(&toml::Value::Table(ref deps)) => deps,
+        Some(..) => bail!("`workspace.dependencies` of `{}` must be a table",
+                          root_file.display()),
+        None => &empty,
+    };
+    for table in dependency_tables(manifest).iter_mut() {
+        let names = table.iter().filter(|&(_, v)| is_inherited(v))
+                         .map(|(k, _)| k.clone()).collect::<Vec<_>>();
+        for name in names {
+            let dep = try!(inherit_dependency(&name, &table[&name], root_deps,
+                                              &root_file, &root_dir, dir));
+            table.insert(name, dep);
+        }
+    }
+    Ok(true)
+}
+
+fn dependency_tables(manifest: &mut toml::Table) -> Vec<&mut toml::Table> {
+    let mut tables = Vec::new();
+    for (key, value) in manifest.iter_mut() {
+        if let toml::Value::Table(ref mut table) = *value {
+            if key == "target" {
+                for platform in table.values_mut() {
+                    if let toml::Value::Table(ref mut platform) = *platform {
+                        for (key, value) in platform.iter_mut() {
+                            if let toml::Value::Table(ref mut deps) = *value {
+                                if DEPENDENCY_TABLES.contains(&&key[..]) {
+                                    tables.push(deps);
+                                }
+                            }
+                        }
+                    }
+                }
+            } else if DEPENDENCY_TABLES.contains(&&key[..]) {
+                tables.push(table);
+            }
+        }
+    }
+    tables
+}
+
+fn is_inherited(dep: &toml::Value) -> bool {
+    dep.lookup("workspace").is_some()
+}
+
+/// Finds the manifest of the root of the workspace containing the package in
+/// `dir`, returning its path, its directory and its `[workspace]` table.
+fn find_workspace(dir: &Path,
+                  explicit_root: Option<String>,
+                  config: &Config)
+                  -> CargoResult<Option<(PathBuf, PathBuf, toml::Value)>> {
+    let candidates = match explicit_root {
+        Some(root) => vec![util::paths::normalize_path(&dir.join(root))],
+        None => {
+            let mut candidates = Vec::new();
+            let mut cur = dir.parent();
+            while let Some(path) = cur {
+                candidates.push(path.to_path_buf());
+                cur = path.parent();
+            }
+            candidates
+        }
+    };
+    for candidate in candidates {
+        let file = candidate.join("Cargo.toml");
+        let contents = match util::paths::read(&file) {
+            Ok(contents) => contents,
+            Err(..) => continue,
+        };
+        let mut root = try!(parse(&contents, &file, config));
+        if let Some(workspace) = root.remove("workspace") {
+            return Ok(Some((file, candidate, workspace)))
+        }
+    }
+    Ok(None)
+}
+
+fn inherit_dependency(name: &str,
+                      dep: &toml::Value,
+                      root_deps: &toml::Table,
+                      root_file: &Path,
+                      root_dir: &Path,
+                      dir: &Path) -> CargoResult<toml::Value> {
+    let mut inherited = match root_deps.get(name) {
+        Some(&toml::Value::String(ref version)) => {
+            let mut table = toml::Table::new();
+            table.insert("version".to_string(),
+                         toml::Value::String(version.clone()));
+            table
+        }
+        Some(&toml::Value::Table(ref table)) => table.clone(),
+        Some(..) => bail!("dependency `{}` in `workspace.dependencies` of `{}` \
+                           must be a version or a table", name,
+                          root_file.display()),
+        None => bail!("dependency `{}` is inherited from the workspace, but \
+                       `workspace.dependencies` of `{}` doesn't declare it",
+                      name, root_file.display()),
+    };
+    // Paths are relative to the root of the workspace, which the member is
+    // somewhere below.
+    if let Some(path) = inherited.get("path").and_then(|p| p.as_str())
+                                 .map(|p| root_dir.join(p)) {
+        let path = relative_path(dir, &util::paths::normalize_path(&path));
+        inherited.insert("path".to_string(),
+                         toml::Value::String(path.to_string_lossy()
+                                                 .replace("\\", "/")));
+    }
+
+    let dep = match *dep {
+        toml::Value::Table(ref dep) => dep,
+        _ => unreachable!(),
+    };
+    for (key, value) in dep.iter() {
+        match (&key[..], value) {
+            ("workspace", &toml::Value::Boolean(true)) => {}
+            ("workspace", _) => {
+                bail!("`workspace` of dependency `{}` can only be `true`", name)
+            }
+            ("features", &toml::Value::Array(ref features)) => {
+                let mut all = match inherited.remove("features") {
+                    Some(toml::Value::Array(features)) => features,
+                    _ => Vec::new(),
+                };
+                for feature in features.iter() {
+                    if !all.contains(feature) {
+                        all.push(feature.clone());
+                    }
+                }
+                inherited.insert(key.clone(), toml::Value::Array(all));
+            }
+            ("optional", _) => {
+                inherited.insert(key.clone(), value.clone());
+            }
+            _ => {
+                bail!("dependency `{}` is inherited from the workspace, which \
+                       only lets it add `features` or be made `optional`, \
+                       not set `{}`", name, key)
+            }
+        }
+    }
+    Ok(toml::Value::Table(inherited))
+}
+
+/// The path of `path` relative to the directory `dir`, both of which are
+/// absolute.
+pub fn relative_path(dir: &Path, path: &Path) -> PathBuf {
+    let mut dir = dir.components().peekable();
+    let mut path = path.components().peekable();
+    while dir.peek().is_some() && dir.peek() == path.peek() {
+        dir.next();
+        path.next();
+    }
+    let mut relative = PathBuf::new();
+    for _ in dir {
+        relative.push("..");
+    }
+    for component in path {
+        relative.push(component.as_os_str());
+    }
+    relative
+}
+
 type TomlLibTarget = TomlTarget;
 type TomlBinTarget = TomlTarget;
 type TomlExampleTarget = TomlTarget;
@@ -225,9 +660,45 @@ pub struct DetailedTomlDependency {
     rev: Option<String>,
     features: Option<Vec<String>>,
     optional: Option<bool>,
+    public: Option<bool>,
     default_features: Option<bool>,
 }
 
+#[derive(RustcDecodable)]
+pub enum TomlSystemDependency {
+    Simple(String),
+    Detailed(DetailedTomlSystemDependency)
+}
+
+#[derive(RustcDecodable, Clone, Default)]
+pub struct DetailedTomlSystemDependency {
+    name: Option<String>,
+    version: Option<String>,
+    link: Option<String>,
+}
+
+/// An entry of the `[prebuilt]` table, keyed by target triple.
+#[derive(RustcDecodable)]
+pub struct TomlPrebuilt {
+    url: String,
+    sha256: String,
+}
+
+/// The `build` key, naming either a single build script or several which are
+/// run independently of one another.
+#[derive(RustcDecodable)]
+pub enum TomlBuild {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+/// The `links` key, naming either a single native library or several.
+#[derive(RustcDecodable)]
+pub enum TomlLinks {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
 #[derive(RustcDecodable)]
 pub struct TomlManifest {
     package: Option<Box<TomlProject>>,
@@ -245,6 +716,8 @@ pub struct TomlManifest {
     target: Option<HashMap<String, TomlPlatform>>,
     replace: Option<HashMap<String, TomlDependency>>,
     workspace: Option<TomlWorkspace>,
+    system_dependencies: Option<HashMap<String, TomlSystemDependency>>,
+    prebuilt: Option<HashMap<String, TomlPrebuilt>>,
 }
 
 #[derive(RustcDecodable, Clone, Default)]
@@ -291,8 +764,8 @@ pub struct TomlProject {
     name: String,
     version: TomlVersion,
     authors: Vec<String>,
-    build: Option<String>,
-    links: Option<String>,
+    build: Option<TomlBuild>,
+    links: Option<TomlLinks>,
     exclude: Option<Vec<String>>,
     include: Option<Vec<String>>,
     publish: Option<bool>,
@@ -307,6 +780,7 @@ pub struct TomlProject {
     license: Option<String>,
     license_file: Option<String>,
     repository: Option<String>,
+    rust_version: Option<String>,
 }
 
 #[derive(RustcDecodable)]
@@ -333,6 +807,25 @@ impl TomlProject {
         PackageId::new(&self.name, self.version.version.clone(),
                        source_id)
     }
+
+    fn links(&self) -> CargoResult<Vec<String>> {
+        let links = match self.links {
+            Some(TomlLinks::Single(ref lib)) => vec![lib.clone()],
+            Some(TomlLinks::Multiple(ref libs)) => libs.clone(),
+            None => return Ok(Vec::new()),
+        };
+        let mut seen = HashSet::new();
+        for lib in links.iter() {
+            if lib.is_empty() {
+                bail!("`links` cannot contain an empty library name")
+            }
+            if !seen.insert(lib) {
+                bail!("native library `{}` is listed more than once in \
+                       `links`", lib)
+            }
+        }
+        Ok(links)
+    }
 }
 
 struct Context<'a, 'b> {
@@ -539,8 +1032,23 @@ impl TomlManifest {
                    have a unique name", e)
         }
 
-        // processing the custom build script
-        let new_build = project.build.as_ref().map(PathBuf::from);
+        // processing the custom build scripts
+        let mut new_build = try!(build_scripts(project.build.as_ref(), None));
+        for (name, platform) in self.target.iter().flat_map(|t| t) {
+            if platform.build.is_some() {
+                let cfg = Some(try!(name.parse()));
+                new_build.extend(try!(build_scripts(platform.build.as_ref(),
+                                                    cfg)));
+            }
+        }
+        let mut build_names = HashSet::new();
+        for &(ref build, _) in new_build.iter() {
+            if !build_names.insert(build.file_stem()) {
+                bail!("found duplicate build script name `{}`, but all build \
+                       scripts must have a unique file name",
+                      build.display())
+            }
+        }
 
         // Get targets
         let targets = normalize(&lib,
@@ -645,6 +1153,7 @@ impl TomlManifest {
             license_file: project.license_file.clone(),
             repository: project.repository.clone(),
             keywords: project.keywords.clone().unwrap_or(Vec::new()),
+            rust_version: project.rust_version.clone(),
         };
 
         let workspace_config = match (self.workspace.as_ref(),
@@ -662,16 +1171,20 @@ impl TomlManifest {
         };
         let profiles = build_profiles(&self.profile);
         let publish = project.publish.unwrap_or(true);
+        let system_dependencies = try!(self.system_dependencies());
+        let links = try!(project.links());
         let mut manifest = Manifest::new(summary,
                                          targets,
                                          exclude,
                                          include,
-                                         project.links.clone(),
+                                         links,
                                          metadata,
                                          profiles,
                                          publish,
                                          replace,
-                                         workspace_config);
+                                         workspace_config,
+                                         system_dependencies);
+        manifest.set_prebuilt(try!(self.prebuilt()));
         if project.license_file.is_some() && project.license.is_some() {
             manifest.add_warning("only one of `license` or \
                                  `license-file` is necessary".to_string());
@@ -709,6 +1222,12 @@ impl TomlManifest {
         if self.bench.is_some() {
             bail!("virtual manifests do not specifiy [[bench]]");
         }
+        if self.system_dependencies.is_some() {
+            bail!("virtual manifests do not specify [system-dependencies]");
+        }
+        if self.prebuilt.is_some() {
+            bail!("virtual manifests do not specify [prebuilt]");
+        }
 
         let mut nested_paths = Vec::new();
         let mut warnings = Vec::new();
@@ -733,6 +1252,58 @@ impl TomlManifest {
         Ok((VirtualManifest::new(replace, workspace_config), nested_paths))
     }
 
+    fn system_dependencies(&self) -> CargoResult<Vec<SystemDependency>> {
+        let mut ret = Vec::new();
+        for (name, dep) in self.system_dependencies.iter().flat_map(|x| x) {
+            let details = match *dep {
+                TomlSystemDependency::Simple(ref version) => {
+                    DetailedTomlSystemDependency {
+                        version: Some(version.clone()),
+                        .. Default::default()
+                    }
+                }
+                TomlSystemDependency::Detailed(ref details) => details.clone(),
+            };
+            let link = match details.link.as_ref().map(|s| &s[..]) {
+                None => None,
+                Some("static") => Some(LinkPreference::Static),
+                Some("dynamic") => Some(LinkPreference::Dynamic),
+                Some(other) => {
+                    bail!("system dependency `{}` has an invalid `link` value \
+                           `{}`, expected `static` or `dynamic`", name, other)
+                }
+            };
+            ret.push(SystemDependency {
+                name: name.clone(),
+                pkg_config_name: details.name.unwrap_or(name.clone()),
+                version: details.version,
+                link: link,
+            });
+        }
+        ret.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ret)
+    }
+
+    fn prebuilt(&self) -> CargoResult<BTreeMap<String, Prebuilt>> {
+        let mut ret = BTreeMap::new();
+        for (target, prebuilt) in self.prebuilt.iter().flat_map(|x| x) {
+            try!((&prebuilt.url[..]).to_url().chain_error(|| {
+                human(format!("invalid `url` for the prebuilt binaries of \
+                               target `{}`", target))
+            }));
+            if prebuilt.sha256.len() != 64 ||
+               !prebuilt.sha256.chars().all(|c| c.is_digit(16)) {
+                bail!("the `sha256` of the prebuilt binaries of target `{}` \
+                       must be 64 hexadecimal digits", target)
+            }
+            ret.insert(target.clone(), Prebuilt {
+                url: prebuilt.url.clone(),
+                sha256: prebuilt.sha256.to_lowercase(),
+            });
+        }
+        Ok(ret)
+    }
+
     fn replace(&self, cx: &mut Context)
                -> CargoResult<Vec<(PackageIdSpec, Dependency)>> {
         let mut replace = Vec::new();
@@ -878,11 +1449,26 @@ impl TomlDependency {
             (None, None) => try!(SourceId::crates_io(cx.config)),
         };
 
+        if details.public.is_some() {
+            match kind {
+                Some(Kind::Development) => {
+                    bail!("dev-dependency `{}` can't be `public`, only normal \
+                           dependencies can", name)
+                }
+                Some(Kind::Build) => {
+                    bail!("build-dependency `{}` can't be `public`, only normal \
+                           dependencies can", name)
+                }
+                _ => {}
+            }
+        }
+
         let version = details.version.as_ref().map(|v| &v[..]);
         let mut dep = try!(DependencyInner::parse(name, version, &new_source_id));
         dep = dep.set_features(details.features.unwrap_or(Vec::new()))
                  .set_default_features(details.default_features.unwrap_or(true))
                  .set_optional(details.optional.unwrap_or(false))
+                 .set_public(details.public.unwrap_or(false))
                  .set_platform(cx.platform.clone());
         if let Some(kind) = kind {
             dep = dep.set_kind(kind);
@@ -903,6 +1489,51 @@ struct TomlTarget {
     plugin: Option<bool>,
     rustc_macro: Option<bool>,
     harness: Option<bool>,
+    runner: Option<TomlRunner>,
+}
+
+/// The `runner` of a target: a command line, as a string split on whitespace
+/// or as an array, or a table with the program, its arguments, environment
+/// and working directory.
+#[derive(RustcDecodable, Debug, Clone)]
+enum TomlRunner {
+    Simple(String),
+    Command(Vec<String>),
+    Detailed(DetailedTomlRunner),
+}
+
+#[derive(RustcDecodable, Debug, Clone)]
+struct DetailedTomlRunner {
+    program: String,
+    args: Option<Vec<String>>,
+    env: Option<BTreeMap<String, String>>,
+    cwd: Option<String>,
+}
+
+impl TomlRunner {
+    fn to_runner(&self) -> Runner {
+        let mut command: Vec<String> = match *self {
+            TomlRunner::Simple(ref s) => {
+                s.split_whitespace().map(|s| s.to_string()).collect()
+            }
+            TomlRunner::Command(ref v) => v.clone(),
+            TomlRunner::Detailed(ref d) => {
+                return Runner {
+                    program: d.program.clone(),
+                    args: d.args.clone().unwrap_or(Vec::new()),
+                    env: d.env.clone().map(|env| env.into_iter().collect())
+                              .unwrap_or(Vec::new()),
+                    cwd: d.cwd.as_ref().map(PathBuf::from),
+                }
+            }
+        };
+        let program = if command.is_empty() {
+            String::new()
+        } else {
+            command.remove(0)
+        };
+        Runner { program: program, args: command, env: Vec::new(), cwd: None }
+    }
 }
 
 #[derive(RustcDecodable, Clone)]
@@ -917,6 +1548,7 @@ struct TomlPlatform {
     dependencies: Option<HashMap<String, TomlDependency>>,
     build_dependencies: Option<HashMap<String, TomlDependency>>,
     dev_dependencies: Option<HashMap<String, TomlDependency>>,
+    build: Option<TomlBuild>,
 }
 
 impl TomlTarget {
@@ -932,6 +1564,7 @@ impl TomlTarget {
             plugin: None,
             rustc_macro: None,
             harness: None,
+            runner: None,
         }
     }
 
@@ -1046,9 +1679,28 @@ impl fmt::Debug for PathValue {
     }
 }
 
+/// Lists the build scripts of a `build` key, all of which only apply to
+/// `platform` if one is given.
+fn build_scripts(build: Option<&TomlBuild>, platform: Option<Platform>)
+                 -> CargoResult<Vec<(PathBuf, Option<Platform>)>> {
+    match build {
+        Some(&TomlBuild::Single(ref path)) => {
+            Ok(vec![(PathBuf::from(path), platform)])
+        }
+        Some(&TomlBuild::Multiple(ref paths)) => {
+            if paths.is_empty() {
+                bail!("`build` must list at least one build script")
+            }
+            Ok(paths.iter().map(|p| (PathBuf::from(p), platform.clone()))
+                    .collect())
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
 fn normalize(lib: &Option<TomlLibTarget>,
              bins: &[TomlBinTarget],
-             custom_build: Option<PathBuf>,
+             custom_build: Vec<(PathBuf, Option<Platform>)>,
              examples: &[TomlExampleTarget],
              tests: &[TomlTestTarget],
              benches: &[TomlBenchTarget],
@@ -1060,6 +1712,7 @@ fn normalize(lib: &Option<TomlLibTarget>,
               .set_doctest(toml.doctest.unwrap_or(t2.doctested()))
               .set_benched(toml.bench.unwrap_or(t2.benched()))
               .set_harness(toml.harness.unwrap_or(t2.harness()))
+              .set_runner(toml.runner.as_ref().map(|r| r.to_runner()))
               .set_for_host(match (toml.plugin, toml.rustc_macro) {
                   (None, None) => t2.for_host(),
                   (Some(true), _) | (_, Some(true)) => true,
@@ -1107,11 +1760,14 @@ fn normalize(lib: &Option<TomlLibTarget>,
         }
     }
 
-    fn custom_build_target(dst: &mut Vec<Target>, cmd: &Path) {
+    fn custom_build_target(dst: &mut Vec<Target>, cmd: &Path,
+                           platform: &Option<Platform>) {
         let name = format!("build-script-{}",
                            cmd.file_stem().and_then(|s| s.to_str()).unwrap_or(""));
 
-        dst.push(Target::custom_build_target(&name, cmd, None));
+        let mut target = Target::custom_build_target(&name, cmd, None);
+        target.set_platform(platform.clone());
+        dst.push(target);
     }
 
     fn example_targets(dst: &mut Vec<Target>,
@@ -1180,8 +1836,8 @@ fn normalize(lib: &Option<TomlLibTarget>,
                                     .join(&format!("{}.rs", bin.name())));
     }
 
-    if let Some(custom_build) = custom_build {
-        custom_build_target(&mut ret, &custom_build);
+    for &(ref custom_build, ref platform) in custom_build.iter() {
+        custom_build_target(&mut ret, custom_build, platform);
     }
 
     example_targets(&mut ret, examples,
@@ -1,11 +1,33 @@
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use git2;
 
-use util::{CargoResult, process};
+use util::{CargoResult, ChainError, human, process};
 
 pub struct HgRepo;
 pub struct GitRepo;
+pub struct JjRepo;
+pub struct SaplingRepo;
+pub struct FossilRepo;
+
+/// A checkout of a version control system other than git, which is driven
+/// through its command line tool.
+pub trait Checkout {
+    /// The name of the command line tool for this VCS.
+    fn tool(&self) -> &'static str;
+
+    /// The root directory of the checkout.
+    fn root(&self) -> &Path;
+
+    /// Returns the paths, relative to `root`, of all files with uncommitted
+    /// changes. This includes files which are not yet tracked but not
+    /// ignored either.
+    fn changed_files(&self) -> CargoResult<Vec<PathBuf>>;
+
+    /// Returns the identifier of the revision which is checked out.
+    fn revision(&self) -> CargoResult<String>;
+}
 
 impl GitRepo {
     pub fn init(path: &Path, _: &Path) -> CargoResult<GitRepo> {
@@ -22,9 +44,164 @@ impl HgRepo {
         try!(process("hg").cwd(cwd).arg("init").arg(path).exec());
         Ok(HgRepo)
     }
-    pub fn discover(path: &Path, cwd: &Path) -> CargoResult<HgRepo> {
-        try!(process("hg").cwd(cwd).arg("root").cwd(path).exec_with_output());
-        Ok(HgRepo)
+    pub fn discover(path: &Path, _: &Path) -> CargoResult<Box<Checkout>> {
+        let root = try!(tool_root("hg", path));
+        Ok(Box::new(StatusCheckout { tool: "hg", root: root }))
+    }
+}
+
+impl SaplingRepo {
+    pub fn init(path: &Path, cwd: &Path) -> CargoResult<SaplingRepo> {
+        try!(process("sl").cwd(cwd).arg("init").arg(path).exec());
+        Ok(SaplingRepo)
+    }
+    pub fn discover(path: &Path, _: &Path) -> CargoResult<Box<Checkout>> {
+        let root = try!(tool_root("sl", path));
+        Ok(Box::new(StatusCheckout { tool: "sl", root: root }))
+    }
+}
+
+impl JjRepo {
+    pub fn init(path: &Path, cwd: &Path) -> CargoResult<JjRepo> {
+        try!(process("jj").cwd(cwd).arg("git").arg("init").arg(path).exec());
+        Ok(JjRepo)
+    }
+    pub fn discover(path: &Path, _: &Path) -> CargoResult<Box<Checkout>> {
+        let root = try!(tool_root("jj", path));
+        Ok(Box::new(JjCheckout { root: root }))
+    }
+}
+
+impl FossilRepo {
+    pub fn init(path: &Path, cwd: &Path) -> CargoResult<FossilRepo> {
+        // fossil keeps the repository database separate from the checkout,
+        // so create the database inside of the new directory and then open
+        // it right there.
+        try!(fs::create_dir_all(path));
+        let db = path.join(".fossil");
+        try!(process("fossil").cwd(cwd).arg("init").arg(&db).exec());
+        try!(process("fossil").cwd(path).arg("open").arg(&db).exec());
+        Ok(FossilRepo)
+    }
+    pub fn discover(path: &Path, _: &Path) -> CargoResult<Box<Checkout>> {
+        let output = try!(tool_output("fossil", &["info"], path));
+        let root = try!(output.lines().filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some("local-root"), Some(root)) => Some(root.trim()),
+                _ => None,
+            }
+        }).next().chain_error(|| {
+            human("could not find the root of the fossil checkout")
+        }));
+        Ok(Box::new(FossilCheckout { root: PathBuf::from(root) }))
+    }
+}
+
+/// Finds the checkout of any supported non-git VCS which contains `path`.
+pub fn discover_checkout(path: &Path) -> Option<Box<Checkout>> {
+    HgRepo::discover(path, path)
+        .or_else(|_| SaplingRepo::discover(path, path))
+        .or_else(|_| JjRepo::discover(path, path))
+        .or_else(|_| FossilRepo::discover(path, path))
+        .ok()
+}
+
+/// A checkout of hg or sapling, which share the format of `status`.
+struct StatusCheckout {
+    tool: &'static str,
+    root: PathBuf,
+}
+
+impl Checkout for StatusCheckout {
+    fn tool(&self) -> &'static str { self.tool }
+    fn root(&self) -> &Path { &self.root }
+
+    fn changed_files(&self) -> CargoResult<Vec<PathBuf>> {
+        // Lines look like `M src/lib.rs`, with paths relative to the root
+        let output = try!(tool_output(self.tool, &["status"], &self.root));
+        Ok(output.lines().filter(|l| l.len() > 2).map(|line| {
+            PathBuf::from(&line[2..])
+        }).collect())
+    }
+
+    fn revision(&self) -> CargoResult<String> {
+        let args = ["log", "-r", ".", "-T", "{node}"];
+        let output = try!(tool_output(self.tool, &args, &self.root));
+        Ok(output.trim().to_string())
+    }
+}
+
+struct JjCheckout {
+    root: PathBuf,
+}
+
+impl Checkout for JjCheckout {
+    fn tool(&self) -> &'static str { "jj" }
+    fn root(&self) -> &Path { &self.root }
+
+    fn changed_files(&self) -> CargoResult<Vec<PathBuf>> {
+        // jj snapshots the working copy into a commit of its own, so the
+        // changes are whatever that commit modifies.
+        let output = try!(tool_output("jj", &["diff", "--name-only"], &self.root));
+        Ok(output.lines().filter(|l| !l.is_empty()).map(PathBuf::from).collect())
+    }
+
+    fn revision(&self) -> CargoResult<String> {
+        let output = try!(tool_output("jj", &["log", "-r", "@", "--no-graph",
+                                              "-T", "commit_id"], &self.root));
+        Ok(output.trim().to_string())
     }
 }
 
+struct FossilCheckout {
+    root: PathBuf,
+}
+
+impl Checkout for FossilCheckout {
+    fn tool(&self) -> &'static str { "fossil" }
+    fn root(&self) -> &Path { &self.root }
+
+    fn changed_files(&self) -> CargoResult<Vec<PathBuf>> {
+        // `changes` prints lines like `EDITED     src/lib.rs` and `extras`
+        // prints the paths of untracked files.
+        let changes = try!(tool_output("fossil", &["changes"], &self.root));
+        let extras = try!(tool_output("fossil", &["extras"], &self.root));
+        let changes = changes.lines().filter_map(|line| {
+            let line = line.trim_left();
+            line.find(char::is_whitespace).map(|i| line[i..].trim())
+        });
+        let extras = extras.lines().map(|line| line.trim());
+        Ok(changes.chain(extras).filter(|l| !l.is_empty())
+                  .map(PathBuf::from).collect())
+    }
+
+    fn revision(&self) -> CargoResult<String> {
+        // `info` prints a line like `checkout: <hash> <date>`
+        let output = try!(tool_output("fossil", &["info"], &self.root));
+        let revision = output.lines().filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some("checkout"), Some(rest)) => rest.split_whitespace().next(),
+                _ => None,
+            }
+        }).next();
+        match revision {
+            Some(revision) => Ok(revision.to_string()),
+            None => bail!("could not find the checked out fossil revision"),
+        }
+    }
+}
+
+fn tool_output(tool: &str, args: &[&str], dir: &Path) -> CargoResult<String> {
+    let output = try!(process(tool).cwd(dir).args(args).exec_with_output());
+    match String::from_utf8(output.stdout) {
+        Ok(output) => Ok(output),
+        Err(..) => bail!("`{}` printed output which was not utf-8", tool),
+    }
+}
+
+fn tool_root(tool: &str, path: &Path) -> CargoResult<PathBuf> {
+    let output = try!(tool_output(tool, &["root"], path));
+    Ok(PathBuf::from(output.trim()))
+}
@@ -72,6 +72,12 @@ pub struct NewCrate {
     pub vers: String,
     pub deps: Vec<NewCrateDependency>,
     pub features: HashMap<String, Vec<String>>,
+    /// Pairs of features which can't be enabled together.
+    pub conflicts: Vec<(String, String)>,
+    /// One-line descriptions of features, by name.
+    pub feature_descriptions: HashMap<String, String>,
+    /// The oldest version of Rust the package builds with.
+    pub rust_version: Option<String>,
     pub authors: Vec<String>,
     pub description: Option<String>,
     pub documentation: Option<String>,
@@ -92,6 +98,7 @@ pub struct NewCrateDependency {
     pub version_req: String,
     pub target: Option<String>,
     pub kind: String,
+    pub public: bool,
 }
 
 #[derive(RustcDecodable)]
@@ -196,6 +203,13 @@ impl Registry {
         Ok(())
     }
 
+    pub fn publish_attestation(&mut self, krate: &str, version: &str,
+                               attestation: &[u8]) -> Result<()> {
+        try!(self.put(format!("/crates/{}/{}/provenance", krate, version),
+                      attestation));
+        Ok(())
+    }
+
     pub fn search(&mut self, query: &str, limit: u8) -> Result<(Vec<Crate>, u32)> {
         let formated_query = percent_encode(query.as_bytes(), QUERY_ENCODE_SET);
         let body = try!(self.req(
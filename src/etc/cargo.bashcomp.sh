@@ -8,7 +8,7 @@ _cargo()
 
 	cmd=${words[1]}
 
-	local vcs='git hg none'
+	local vcs='git hg jj sapling fossil none'
 	local color='auto always never'
 
 	local opt_help='-h --help'
@@ -22,34 +22,52 @@ _cargo()
 	local opt_jobs='-j --jobs'
 
 	local opt___nocmd="$opt_common -V --version --list"
-	local opt__bench="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --no-run"
-	local opt__build="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --release"
+	local opt__add="$opt_common $opt_mani --features --dev --build --optional --path --git --branch --tag --rev"
+	local opt__bench="$opt_common $opt_pkg --changed-since --exclude --select $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --no-run --message-format"
+	local opt__build="$opt_common $opt_pkg --changed-since --exclude --select $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --release --message-format --determinism-check --self-profile --preserve-old-artifacts --emit-ninja --sbom"
 	local opt__clean="$opt_common $opt_pkg $opt_mani --target --release"
-	local opt__doc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --open --no-deps --release"
-	local opt__fetch="$opt_common $opt_mani"
-	local opt__generate_lockfile="${opt__fetch}"
+	local opt__complete="$opt_common $opt_mani --values"
+	local opt__doc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --open --no-deps --release --message-format"
+	local opt__doctor="$opt_common $opt_mani"
+	local opt__export="$opt_common $opt_feat $opt_mani --format --platforms"
+	local opt__fetch="$opt_common $opt_mani --message-format --archive"
+	local opt__generate_lockfile="$opt_common $opt_mani --format"
 	local opt__git_checkout="$opt_common --reference --url"
+	local opt__hash_inputs="$opt_common $opt_pkg $opt_mani --sources"
 	local opt__help="$opt_help"
+	local opt__info="$opt_common $opt_mani --index --format"
 	local opt__init="$opt_common --bin --name --vcs"
-	local opt__install="$opt_common $opt_feat $opt_jobs --bin --branch --debug --example --git --list --path --rev --root --tag --vers"
-	local opt__locate_project="$opt_mani -h --help"
-	local opt__login="$opt_common --host"
-	local opt__metadata="$opt_common $opt_feat $opt_mani --format-version"
-	local opt__new="$opt_common --vcs --bin --name"
+	local opt__install="$opt_common $opt_feat $opt_jobs --bin --branch --debug --example --from-metadata --git --list --outdated --path --prebuilt --rev --root --tag --target --upgrade-all --vers"
+	local opt__local_registry="$opt_common $opt_mani"
+	local opt__locate_project="$opt_mani -h --help --workspace --file --config"
+	local opt__login="$opt_common --host --generate-key --key-id"
+	local opt__logout="$opt_common"
+	local opt__metadata="$opt_common $opt_feat $opt_mani --format-version --format --filter-platform"
+	local opt__new="$opt_common --vcs --bin --name --template"
+	local opt__outdated="$opt_common $opt_mani --direct --deny --format"
 	local opt__owner="$opt_common -a --add -r --remove -l --list --index --token"
-	local opt__package="$opt_common $opt_mani -l --list --no-verify --no-metadata"
-	local opt__pkgid="${opt__fetch}"
-	local opt__publish="$opt_common $opt_mani --host --token --no-verify"
+	local opt__package="$opt_common $opt_mani -l --list --no-verify --no-metadata --format"
+	local opt__pin_artifacts="$opt_common $opt_pkg $opt_mani --profile --target -l --list --unpin"
+	local opt__pkgid="$opt_common $opt_mani"
+	local opt__publish="$opt_common $opt_mani --host --token --no-verify --provenance"
 	local opt__read_manifest="$opt_help $opt_verbose $opt_mani $opt_color"
-	local opt__run="$opt_common $opt_feat $opt_mani $opt_jobs --target --bin --example --release"
-	local opt__rustc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --release"
-	local opt__rustdoc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --release --open"
+	local opt__remove="$opt_common $opt_mani --dev --build --target"
+	local opt__report="$opt_common $opt_mani --max-age --max-major-behind"
+	local opt__resolve="$opt_common $opt_mani --record --bench --iterations"
+	local opt__run="$opt_common $opt_feat $opt_mani $opt_jobs --target --bin --example --release --message-format --env-file"
+	local opt__rust_project="$opt_common $opt_feat $opt_mani $opt_jobs --target --release"
+	local opt__rustc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --release --message-format"
+	local opt__rustdoc="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --release --open --message-format"
 	local opt__search="$opt_common --host"
-	local opt__test="$opt_common $opt_pkg $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --no-run --release --no-fail-fast"
+	local opt__task="$opt_common $opt_mani -l --list --release"
+	local opt__test="$opt_common $opt_pkg --changed-since --exclude --select $opt_feat $opt_mani $opt_jobs --target --lib --bin --test --bench --example --no-run --release --no-fail-fast --message-format --env-file --shard --shard-tests"
+	local opt__tree="$opt_common $opt_feat $opt_mani --target --why-feature"
 	local opt__uninstall="$opt_common --bin --root"
-	local opt__update="$opt_common $opt_pkg $opt_mani --aggressive --precise"
-	local opt__verify_project="${opt__fetch}"
+	local opt__update="$opt_common $opt_pkg $opt_mani --aggressive --precise --allow-yanked --from-advisories --minimal-direct --breaking --dry-run --diff --format"
+	local opt__upgrade="$opt_common $opt_pkg $opt_mani --breaking --dry-run"
+	local opt__verify_project="$opt_common $opt_mani"
 	local opt__version="$opt_help $opt_verbose $opt_color"
+	local opt__vet="$opt_common $opt_mani --exempt"
 	local opt__yank="$opt_common --vers --undo --index --token"
 
 	if [[ $cword -eq 1 ]]; then
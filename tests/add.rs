@@ -0,0 +1,168 @@
+#[macro_use]
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+fn read(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn add_newest_version() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            # Logging.
+            [dependencies]
+            log = "0.3"   # pinned for now
+
+            [features]
+            extra = []
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    Package::new("log", "0.3.0").publish();
+    Package::new("bar", "0.1.0").publish();
+    Package::new("bar", "0.2.1").publish();
+    Package::new("bar", "0.3.0-pre").publish();
+
+    assert_that(p.cargo("add").arg("bar"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[ADDING] bar v0.2.1 to dependencies"));
+    assert!(read(&p.root().join("Cargo.toml")).contains(r#"
+            # Logging.
+            [dependencies]
+            log = "0.3"   # pinned for now
+bar = "0.2.1"
+
+            [features]
+"#));
+
+    assert!(p.read_lockfile().contains("name = \"bar\"\nversion = \"0.2.1\""));
+}
+
+#[test]
+fn add_dev_with_features() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    Package::new("bar", "0.1.0").feature("fast", &[]).publish();
+
+    assert_that(p.cargo("add").arg("bar@0.1").arg("--dev")
+                 .arg("--features").arg("fast"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[ADDING] bar v0.1 to dev-dependencies"));
+    assert!(read(&p.root().join("Cargo.toml")).ends_with(r#"
+[dev-dependencies]
+bar = { version = "0.1", features = ["fast"] }
+"#));
+}
+
+#[test]
+fn add_path_replaces_declaration() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("add").arg("bar").arg("--path").arg("bar"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[ADDING] bar from bar to dependencies"));
+    assert!(read(&p.root().join("Cargo.toml")).contains(r#"
+            [dependencies]
+            bar = { path = "bar" }
+"#));
+}
+
+#[test]
+fn add_inherited_from_workspace() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["member"]
+
+            [workspace.dependencies]
+            bar = "0.1"
+        "#)
+        .file("member/Cargo.toml", r#"
+            [package]
+            name = "member"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("member/src/lib.rs", "");
+    p.build();
+
+    Package::new("bar", "0.1.0").publish();
+
+    assert_that(p.cargo("add").arg("bar").arg("--optional")
+                 .cwd(p.root().join("member")),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[ADDING] bar from the workspace to dependencies"));
+    assert!(read(&p.root().join("member/Cargo.toml")).ends_with(r#"
+[dependencies]
+bar = { workspace = true, optional = true }
+"#));
+}
+
+#[test]
+fn unresolvable_leaves_manifest() {
+    let manifest = r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+        "#;
+    let p = project("foo")
+        .file("Cargo.toml", manifest)
+        .file("src/lib.rs", "");
+    p.build();
+
+    Package::new("bar", "0.1.0").publish();
+
+    assert_that(p.cargo("add").arg("bar@0.2"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] no matching package named `bar` found[..]"));
+    assert_eq!(read(&p.root().join("Cargo.toml")), manifest);
+}
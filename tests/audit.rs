@@ -0,0 +1,135 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+const ADVISORY: &'static str = r#"
+```toml
+[advisory]
+id = "RUSTSEC-2016-0001"
+package = "bar"
+date = "2016-01-01"
+
+[versions]
+patched = [">= 0.1.2"]
+unaffected = ["< 0.1.0"]
+```
+
+# Something bad in bar
+"#;
+
+fn project_with_audit(audit: &str) -> cargotest::support::ProjectBuilder {
+    project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+            baz = "0.1"
+        "#)
+        .file("src/lib.rs", "")
+        .file("advisory-db/crates/bar/RUSTSEC-2016-0001.md", ADVISORY)
+        .file("advisory-db/crates/baz/RUSTSEC-2016-0002.md", r#"
+```toml
+[advisory]
+id = "RUSTSEC-2016-0002"
+package = "baz"
+informational = "unmaintained"
+
+[versions]
+patched = []
+```
+"#)
+        .file(".cargo/config", audit)
+}
+
+#[test]
+fn build_warns_about_affected_packages() {
+    Package::new("bar", "0.1.1").publish();
+    Package::new("baz", "0.1.0").publish();
+    let p = project_with_audit(r#"
+        [audit]
+        databases = ["advisory-db"]
+    "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] `bar v0.1.1` is affected by `RUSTSEC-2016-0001` (patched in `>= 0.1.2`)
+"));
+}
+
+#[test]
+fn build_passes_with_patched_versions() {
+    Package::new("bar", "0.1.2").publish();
+    Package::new("baz", "0.1.0").publish();
+    let p = project_with_audit(r#"
+        [audit]
+        databases = ["advisory-db"]
+        action = "deny"
+    "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0));
+}
+
+#[test]
+fn build_denies_affected_packages() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publish();
+    let p = project_with_audit(r#"
+        [audit]
+        databases = ["old-db"]
+        action = "deny"
+    "#).file("old-db/crates/baz/RUSTSEC-2016-0003.toml", r#"
+        [advisory]
+        id = "RUSTSEC-2016-0003"
+        package = "baz"
+        patched_versions = ["^0.2"]
+        unaffected_versions = []
+    "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] locked packages are affected by advisories:
+  `baz v0.1.0` is affected by `RUSTSEC-2016-0003` (patched in `^0.2`)
+update them with `cargo update`, or set `audit.action` to `warn` in the \
+configuration
+"));
+}
+
+#[test]
+fn update_prefers_patched_versions() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publish();
+    let p = project_with_audit(r#"
+        [audit]
+        databases = ["advisory-db"]
+    "#);
+    assert_that(p.cargo_process("generate-lockfile"), execs().with_status(0));
+
+    Package::new("bar", "0.1.2").publish();
+    Package::new("baz", "0.1.1").publish();
+    assert_that(p.cargo("update").arg("-p").arg("baz"),
+                execs().with_status(0).with_stderr("\
+[UPDATING] registry `[..]`
+[UPDATING] bar v0.1.0 -> v0.1.2
+[UPDATING] baz v0.1.0 -> v0.1.1
+"));
+}
+
+#[test]
+fn missing_database() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publish();
+    let p = project_with_audit(r#"
+        [audit]
+        databases = ["missing"]
+    "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] failed to read the advisory database at `[..]missing`, which should \
+contain a `crates` directory
+"));
+}
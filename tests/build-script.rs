@@ -4,11 +4,11 @@ extern crate hamcrest;
 use std::fs::{self, File};
 use std::io::prelude::*;
 
-use cargotest::{rustc_host, is_nightly, sleep_ms};
+use cargotest::{rustc_host, is_nightly, process, sleep_ms};
 use cargotest::support::{project, execs};
 use cargotest::support::paths::CargoPathExt;
 use cargotest::support::registry::Package;
-use hamcrest::{assert_that, existing_file, existing_dir};
+use hamcrest::{assert_that, existing_file, existing_dir, is_not};
 
 #[test]
 fn custom_build_script_failed() {
@@ -253,9 +253,114 @@ linked to by one package
 
   [..] v0.5.0 (file://[..])
   [..] v0.5.0 (file://[..])
+
+if one of these packages provides the library for both, it can be chosen by \
+adding the following to .cargo/config:
+
+  [links]
+  a = \"[..]\"
 "));
 }
 
+#[test]
+fn links_multiple_libraries() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [dependencies.a]
+            path = "a"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r#"
+            use std::env;
+            fn main() {
+                assert_eq!(env::var("DEP_Z_INCLUDE").unwrap(), "z-include");
+                assert_eq!(env::var("DEP_SSL_INCLUDE").unwrap(), "ssl-include");
+                assert_eq!(env::var("DEP_Z_ROOT").unwrap(), "root");
+                assert_eq!(env::var("DEP_SSL_ROOT").unwrap(), "root");
+            }
+        "#)
+        .file("a/Cargo.toml", r#"
+            [project]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+            links = ["z", "ssl"]
+            build = "build.rs"
+        "#)
+        .file("a/src/lib.rs", "")
+        .file("a/build.rs", r#"
+            use std::env;
+            fn main() {
+                let lib = env::var("CARGO_MANIFEST_LINKS").unwrap();
+                assert_eq!(lib, "z,ssl");
+
+                println!("cargo:z.include=z-include");
+                println!("cargo:ssl.include=ssl-include");
+                println!("cargo:root=root");
+            }
+        "#);
+
+    assert_that(p.cargo_process("build").arg("-v"),
+                execs().with_status(0));
+}
+
+#[test]
+fn links_conflict_chosen_by_config() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            a = { path = "a" }
+            b = { path = "b" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("a/Cargo.toml", r#"
+            [project]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+            links = ["z", "ssl"]
+            build = "build.rs"
+        "#)
+        .file("a/src/lib.rs", "")
+        .file("a/build.rs", "fn main() {}")
+        .file("b/Cargo.toml", r#"
+            [project]
+            name = "b"
+            version = "0.5.0"
+            authors = []
+            links = "z"
+            build = "build.rs"
+        "#)
+        .file("b/src/lib.rs", "")
+        .file("b/build.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] native library `z` is being linked to by more than one package, and can only be \
+linked to by one package"));
+
+    fs::create_dir_all(p.root().join(".cargo")).unwrap();
+    File::create(p.root().join(".cargo/config")).unwrap().write_all(br#"
+        [links]
+        z = "a"
+    "#).unwrap();
+
+    assert_that(p.cargo("build"),
+                execs().with_status(0));
+}
+
 #[test]
 fn overrides_and_links() {
     let target = rustc_host();
@@ -2259,3 +2364,563 @@ fn rustc_and_rustdoc_set_correctly() {
     assert_that(build.cargo_process("bench"),
                 execs().with_status(0));
 }
+
+#[test]
+fn sandbox_allowlisted_package_runs_unrestricted() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file(".cargo/config", r#"
+            [build]
+            sandbox = true
+            sandbox-allow = ["foo"]
+        "#)
+        .file("build.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::path::PathBuf;
+
+            fn main() {
+                let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+                File::create(dir.join("outside-out-dir")).unwrap();
+            }
+        "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0));
+    assert_that(&p.root().join("outside-out-dir"), existing_file());
+}
+
+#[test]
+fn sandbox_blocks_writes_outside_out_dir() {
+    if !cfg!(target_os = "linux") {
+        return
+    }
+    // bubblewrap needs unprivileged user namespaces, which not every machine
+    // running the tests has.
+    let usable = process("bwrap").arg("--ro-bind").arg("/").arg("/")
+                                 .arg("true").exec_with_output().is_ok();
+    if !usable {
+        return
+    }
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file(".cargo/config", r#"
+            [build]
+            sandbox = true
+        "#)
+        .file("build.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::path::PathBuf;
+
+            fn main() {
+                let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+                File::create(out.join("inside-out-dir")).unwrap();
+                let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+                File::create(dir.join("outside-out-dir")).unwrap();
+            }
+        "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] failed to run custom build command for `foo v0.0.1 ([..])`"));
+    assert_that(&p.root().join("outside-out-dir"), is_not(existing_file()));
+}
+
+#[test]
+fn script_cache_keyed_by_build_script() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("gen.in", "")
+        .file(".cargo/config", r#"
+            [build]
+            script-cache = "cache"
+        "#)
+        .file("build.rs", r#"
+            use std::env;
+            use std::fs::{File, OpenOptions};
+            use std::io::Write;
+            use std::path::PathBuf;
+
+            fn main() {
+                let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+                OpenOptions::new().create(true).append(true)
+                    .open(dir.join("runs")).unwrap()
+                    .write_all(b"1").unwrap();
+                let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+                File::create(out.join("gen.rs")).unwrap();
+                println!("cargo:tracked-input=gen.in");
+                println!("cargo:declares-output=gen.rs");
+            }
+        "#);
+    let runs = || {
+        let mut s = String::new();
+        File::open(p.root().join("runs")).unwrap().read_to_string(&mut s).unwrap();
+        s.len()
+    };
+
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+    assert_eq!(runs(), 1);
+
+    // Restored from the cache after cleaning.
+    assert_that(p.cargo("clean"), execs().with_status(0));
+    assert_that(p.cargo("build"), execs().with_status(0));
+    assert_eq!(runs(), 1);
+
+    // A different script may print different things, so it runs again.
+    File::create(p.root().join("build.rs")).unwrap().write_all(br#"
+        use std::env;
+        use std::fs::{File, OpenOptions};
+        use std::io::Write;
+        use std::path::PathBuf;
+
+        fn main() {
+            let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+            OpenOptions::new().create(true).append(true)
+                .open(dir.join("runs")).unwrap()
+                .write_all(b"2").unwrap();
+            let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+            File::create(out.join("gen.rs")).unwrap();
+            println!("cargo:tracked-input=gen.in");
+            println!("cargo:declares-output=gen.rs");
+        }
+    "#).unwrap();
+    assert_that(p.cargo("build"), execs().with_status(0));
+    assert_eq!(runs(), 2);
+}
+
+#[test]
+fn declared_output_must_be_created() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r#"
+            fn main() {
+                println!("cargo::declares-output=generated.rs");
+            }
+        "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] build script of `foo v0.0.1 ([..])` declared output `generated.rs` \
+but did not create it in OUT_DIR"));
+}
+
+#[test]
+fn missing_declared_output_reruns_build_script() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", r#"
+            include!(concat!(env!("OUT_DIR"), "/generated.rs"));
+        "#)
+        .file("input.txt", "")
+        .file("build.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::io::Write;
+            use std::path::PathBuf;
+
+            fn main() {
+                println!("cargo:tracked-input=input.txt");
+                println!("cargo:declares-output=generated.rs");
+                let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+                let mut f = File::create(out.join("generated.rs")).unwrap();
+                f.write_all(b"pub fn foo() {}").unwrap();
+            }
+        "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0));
+    assert_that(p.cargo("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr("\
+[FRESH] foo v0.0.1 ([..])
+"));
+
+    let build = p.root().join("target/debug/build");
+    for entry in fs::read_dir(&build).unwrap() {
+        let generated = entry.unwrap().path().join("out/generated.rs");
+        if generated.exists() {
+            fs::remove_file(&generated).unwrap();
+        }
+    }
+    assert_that(p.cargo("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[RUNNING] `[..]build-script-build[..]`"));
+}
+
+#[test]
+fn protocol_v2_diagnostics() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r##"
+            use std::env;
+
+            fn main() {
+                assert_eq!(env::var("CARGO_BUILD_SCRIPT_PROTOCOL").unwrap(), "2");
+                println!("cargo:protocol=2");
+                println!(r#"{{"kind":"progress","message":"working"}}"#);
+                println!(r#"{{"kind":"diagnostic","level":"warning","message":"foo","spans":[{{"file":"foo.h","line":3,"column":1,"label":"here"}}],"notes":["bar"]}}"#);
+            }
+        "##);
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0)
+                       .with_stderr("\
+[COMPILING] foo v0.5.0 ([..])
+warning: foo
+  --> foo.h:3:1
+   | here
+   = note: bar
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+"));
+}
+
+#[test]
+fn protocol_v2_diagnostics_json() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r##"
+            fn main() {
+                println!("cargo:protocol=2");
+                println!(r#"{{"kind":"diagnostic","message":"foo"}}"#);
+            }
+        "##);
+
+    assert_that(p.cargo_process("build").arg("--message-format").arg("json"),
+                execs().with_status(0).with_json(r#"
+                    {
+                        "reason": "build-script-message",
+                        "package_id": "foo 0.5.0 [..]",
+                        "message": {
+                            "level": "warning",
+                            "message": "foo",
+                            "spans": [],
+                            "notes": []
+                        }
+                    }
+                "#));
+}
+
+#[test]
+fn protocol_v2_progress() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r##"
+            fn main() {
+                println!("cargo:protocol=2");
+                println!(r#"{{"kind":"progress","message":"working"}}"#);
+            }
+        "##);
+
+    assert_that(p.cargo_process("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[..]Progress foo v0.5.0 ([..]): working"));
+    assert_that(p.cargo_process("build").arg("--message-format").arg("json"),
+                execs().with_status(0).with_stdout_contains(
+                    r#"{"message":"working",[..]"reason":"build-script-progress"}"#));
+}
+
+#[test]
+fn protocol_v2_error_fails_build() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r##"
+            fn main() {
+                println!("cargo:protocol=2");
+                println!(r#"{{"kind":"diagnostic","level":"error","message":"no foo"}}"#);
+            }
+        "##);
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] build script of `foo v0.5.0 ([..])` reported errors:
+
+no foo"));
+}
+
+#[test]
+fn protocol_version_unsupported() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", r#"
+            fn main() {
+                println!("cargo:protocol=3");
+            }
+        "#);
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] build script of `foo v0.5.0 ([..])` requested protocol version 3, \
+but this version of Cargo only supports up to version 2"));
+}
+
+#[test]
+fn multiple_build_scripts() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = ["build/codegen.rs", "build/linkcheck.rs"]
+        "#)
+        .file("src/lib.rs", r#"
+            include!(concat!(env!("OUT_DIR_CODEGEN"), "/codegen.rs"));
+            include!(concat!(env!("OUT_DIR_LINKCHECK"), "/linkcheck.rs"));
+
+            #[cfg(all(codegen, linkcheck))]
+            pub fn both() -> (u32, u32) { (CODEGEN, LINKCHECK) }
+        "#)
+        .file("src/main.rs", r#"
+            extern crate foo;
+            fn main() { assert_eq!(foo::both(), (1, 2)); }
+        "#)
+        .file("build/codegen.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::io::Write;
+            use std::path::Path;
+
+            fn main() {
+                let out = env::var("OUT_DIR").unwrap();
+                assert!(!Path::new(&out).join("linkcheck.rs").exists());
+                File::create(Path::new(&out).join("codegen.rs")).unwrap()
+                    .write_all(b"pub const CODEGEN: u32 = 1;").unwrap();
+                println!("cargo:rustc-cfg=codegen");
+            }
+        "#)
+        .file("build/linkcheck.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::io::Write;
+            use std::path::Path;
+
+            fn main() {
+                let out = env::var("OUT_DIR").unwrap();
+                File::create(Path::new(&out).join("linkcheck.rs")).unwrap()
+                    .write_all(b"pub const LINKCHECK: u32 = 2;").unwrap();
+                println!("cargo:rustc-cfg=linkcheck");
+            }
+        "#);
+
+    assert_that(p.cargo_process("run"),
+                execs().with_status(0));
+}
+
+#[test]
+fn platform_specific_build_scripts() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [target.'cfg(target_os = "nonexistent")']
+            build = "build/never.rs"
+
+            [target.'cfg(not(target_os = "nonexistent"))']
+            build = "build/always.rs"
+        "#)
+        .file("src/main.rs", r#"
+            #[cfg(always)]
+            fn main() {}
+        "#)
+        .file("build/never.rs", r#"
+            fn main() { panic!("should not run"); }
+        "#)
+        .file("build/always.rs", r#"
+            fn main() { println!("cargo:rustc-cfg=always"); }
+        "#);
+
+    assert_that(p.cargo_process("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[RUNNING] `[..]build-script-always[..]`"));
+}
+
+#[test]
+fn multiple_build_scripts_rerun_independently() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = ["build/codegen.rs", "build/linkcheck.rs"]
+        "#)
+        .file("src/lib.rs", "")
+        .file("codegen.txt", "")
+        .file("linkcheck.txt", "")
+        .file("build/codegen.rs", r#"
+            fn main() { println!("cargo:rerun-if-changed=codegen.txt"); }
+        "#)
+        .file("build/linkcheck.rs", r#"
+            fn main() { println!("cargo:rerun-if-changed=linkcheck.txt"); }
+        "#);
+    p.build();
+
+    assert_that(p.cargo("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[RUNNING] `[..]build-script-codegen[..]`")
+                       .with_stderr_contains("\
+[RUNNING] `[..]build-script-linkcheck[..]`"));
+
+    // Each script keeps a fingerprint of its own, so neither of them looks
+    // stale next to the other's.
+    assert_that(p.cargo("build").arg("-v"),
+                execs().with_status(0).with_stderr("\
+[FRESH] foo v0.5.0 ([..])
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+"));
+
+    sleep_ms(1000);
+    File::create(p.root().join("codegen.txt")).unwrap();
+
+    assert_that(p.cargo("build").arg("-v"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] foo v0.5.0 ([..])
+[RUNNING] `[..]build-script-codegen[..]`
+[RUNNING] `rustc src[..]lib.rs [..]`
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+"));
+}
+
+#[test]
+fn duplicate_build_script_names() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = ["a/build.rs", "b/build.rs"]
+        "#)
+        .file("src/lib.rs", "")
+        .file("a/build.rs", "fn main() {}")
+        .file("b/build.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101)
+                       .with_stderr("\
+[ERROR] failed to parse manifest at `[..]`
+
+Caused by:
+  found duplicate build script name `b[..]build.rs`, but all build scripts \
+must have a unique file name
+"));
+}
+
+#[test]
+fn target_info_file() {
+    let target = rustc_host();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [features]
+            default = ["bar"]
+            bar = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", &format!(r##"
+            use std::env;
+            use std::fs::File;
+            use std::io::Read;
+            use std::path::Path;
+
+            fn main() {{
+                let path = env::var("CARGO_TARGET_INFO").unwrap();
+                let out = env::var("OUT_DIR").unwrap();
+                assert_eq!(Path::new(&path), Path::new(&out).join("target-info.json"));
+
+                let mut info = String::new();
+                File::open(&path).unwrap().read_to_string(&mut info).unwrap();
+                assert!(info.contains(r#""version":1"#));
+                assert!(info.contains(r#""target":"{0}""#));
+                assert!(info.contains(r#""features":["bar","default"]"#));
+                assert!(info.contains(r#""name":"debug""#));
+                assert!(info.contains(r#""debug_assertions""#));
+                assert!(info.contains("sysroot_target_libdir"));
+            }}
+        "##, target));
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0));
+}
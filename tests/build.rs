@@ -7,12 +7,12 @@ use std::env;
 use std::fs::{self, File};
 use std::io::prelude::*;
 
-use cargo::util::process;
+use cargo::util::{process, short_hash};
 use cargotest::{is_nightly, rustc_host, sleep_ms};
 use cargotest::support::paths::{CargoPathExt,root};
 use cargotest::support::{ProjectBuilder};
 use cargotest::support::{project, execs, main_file, basic_bin_manifest};
-use hamcrest::{assert_that, existing_file, is_not};
+use hamcrest::{assert_that, existing_file, existing_dir, is_not};
 use tempdir::TempDir;
 
 #[test]
@@ -2299,3 +2299,522 @@ fn no_warn_about_package_metadata() {
                        .with_stderr("[..] foo v0.0.1 ([..])\n\
                        [FINISHED] debug [unoptimized + debuginfo] target(s) in [..]\n"));
 }
+
+#[test]
+fn determinism_check() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}")
+        .file("src/main.rs", "extern crate foo; fn main() { foo::foo() }");
+
+    assert_that(p.cargo_process("build").arg("--determinism-check"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+  Rebuilding in `[..]determinism-check` to check determinism")
+                       .with_stderr_contains("\
+    Verified 2 artifacts are reproducible"));
+}
+
+#[test]
+fn determinism_check_finds_embedded_paths() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("build.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::io::Write;
+            use std::path::Path;
+
+            fn main() {
+                let out = env::var("OUT_DIR").unwrap();
+                let mut f = File::create(Path::new(&out).join("out.rs")).unwrap();
+                write!(f, "pub static OUT: &'static str = {:?};", out).unwrap();
+            }
+        "#)
+        .file("src/main.rs", r#"
+            include!(concat!(env!("OUT_DIR"), "/out.rs"));
+            fn main() { println!("{}", OUT) }
+        "#);
+
+    assert_that(p.cargo_process("build").arg("--determinism-check"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[WARNING] `foo[..]` differs between builds
+  the path of the target directory is embedded in it")
+                       .with_stderr_contains("\
+[ERROR] 1 of 1 artifacts were not reproducible"));
+}
+
+#[test]
+fn determinism_check_varies_environment() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("build.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::io::Write;
+            use std::path::Path;
+
+            fn main() {
+                let out = env::var("OUT_DIR").unwrap();
+                let mut f = File::create(Path::new(&out).join("out.rs")).unwrap();
+                let vars = env::vars().count();
+                write!(f, "pub static VARS: usize = {};", vars).unwrap();
+            }
+        "#)
+        .file("src/main.rs", r#"
+            include!(concat!(env!("OUT_DIR"), "/out.rs"));
+            fn main() { println!("{}", VARS) }
+        "#);
+
+    assert_that(p.cargo_process("build").arg("--determinism-check"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[WARNING] `foo[..]` differs between builds")
+                       .with_stderr_contains("\
+[ERROR] 1 of 1 artifacts were not reproducible"));
+}
+
+#[test]
+fn self_profile() {
+    if !is_nightly() {
+        return
+    }
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar(); }")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    assert_that(p.cargo_process("build").arg("-v")
+                 .arg("--self-profile").arg("prof"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[RUNNING] `rustc src[..]main.rs [..]-Z time-passes[..]`")
+                       .with_stderr_contains("\
+      Profiled 1 units, summary written to `[..]prof[..]summary.txt`"));
+
+    let mut summary = String::new();
+    File::open(p.root().join("prof/summary.txt")).unwrap()
+        .read_to_string(&mut summary).unwrap();
+    assert!(summary.starts_with("foo v0.0.1 ("), "{}", summary);
+    assert!(!summary.contains("bar v0.0.1"), "{}", summary);
+    assert_that(&p.root().join("target/debug/self-profile"), existing_dir());
+
+    // Fresh units aren't compiled again, so there's nothing to summarize.
+    assert_that(p.cargo("build").arg("--self-profile").arg("prof"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+      Profiled 0 units, summary written to `[..]prof[..]summary.txt`"));
+
+    // Dropping the flag rebuilds without profiling.
+    assert_that(p.cargo("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[RUNNING] `rustc src[..]main.rs [..]`"));
+}
+
+#[test]
+fn self_profile_requires_nightly() {
+    if is_nightly() {
+        return
+    }
+
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+
+    assert_that(p.cargo_process("build").arg("--self-profile").arg("prof"),
+                execs().with_status(101)
+                       .with_stderr("\
+[ERROR] `--self-profile` requires a nightly compiler, as it passes \
+`-Z time-passes` to rustc
+"));
+}
+
+#[test]
+fn compiler_rollover_notice() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    // Nothing is said as long as the compiler stays the same
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr("\
+[FINISHED] [..]
+"));
+
+    File::create(p.root().join("target/debug/.rustc-version")).unwrap()
+         .write_all(b"rustc 0.0.1 (old)\nhost: foo\n").unwrap();
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] the compiler changed since the last build
+  previous: rustc 0.0.1 (old)
+  current:  rustc [..]
+all 1 units will be rebuilt, pass --preserve-old-artifacts to keep those of \
+the previous compiler
+"));
+}
+
+#[test]
+fn compiler_rollover_preserve_old_artifacts() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    let version_file = p.root().join("target/debug/.rustc-version");
+    let mut current = String::new();
+    File::open(&version_file).unwrap()
+        .read_to_string(&mut current).unwrap();
+
+    // The artifacts of the "old" compiler are set aside
+    File::create(&version_file).unwrap()
+         .write_all(b"rustc 0.0.1 (old)").unwrap();
+    assert_that(p.cargo("build").arg("--preserve-old-artifacts"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] the compiler changed since the last build
+  previous: rustc 0.0.1 (old)
+  current:  rustc [..]
+all 1 units will be rebuilt, those of the previous compiler were moved to \
+`toolchains/[..]`
+").with_stderr_contains("[COMPILING] foo v0.5.0 ([..])"));
+    let old = p.root().join("target/debug/toolchains")
+               .join(short_hash(&"rustc 0.0.1 (old)"));
+    assert_that(&old.join("deps"), existing_dir());
+    assert_that(&old.join(".fingerprint"), existing_dir());
+
+    // Pretend those were built by the current compiler, and switch back to
+    // it from the "old" one.
+    fs::rename(&old, p.root().join("target/debug/toolchains")
+                      .join(short_hash(&current))).unwrap();
+    File::create(&version_file).unwrap()
+         .write_all(b"rustc 0.0.1 (old)").unwrap();
+    assert_that(p.cargo("build").arg("--preserve-old-artifacts"),
+                execs().with_status(0).with_stderr("\
+[WARNING] the compiler changed since the last build
+  previous: rustc 0.0.1 (old)
+  current:  rustc [..]
+artifacts previously built with the current compiler were restored, those of \
+the previous compiler were moved to `toolchains/[..]`
+[FINISHED] [..]
+"));
+}
+
+#[test]
+fn emit_ninja() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("build.rs", r#"
+            fn main() { println!("cargo:rustc-cfg=from_script"); }
+        "#)
+        .file("src/main.rs", r#"
+            #[cfg(from_script)]
+            fn main() { println!("configured"); }
+        "#);
+
+    assert_that(p.cargo_process("build").arg("--emit-ninja").arg("build.ninja"),
+                execs().with_status(0).with_stderr_contains("\
+[..]Emitted 5 build statements to `[..]build.ninja`"));
+    assert_that(&p.bin("foo"), is_not(existing_file()));
+
+    let mut ninja = String::new();
+    File::open(p.root().join("build.ninja")).unwrap()
+        .read_to_string(&mut ninja).unwrap();
+    assert!(ninja.contains("rule rustc"), "{}", ninja);
+    assert!(ninja.contains("build-script-build"), "{}", ninja);
+    assert!(ninja.contains("cfg-args"), "{}", ninja);
+
+    // Ninja isn't installed everywhere the tests are run.
+    if process("ninja").arg("--version").exec_with_output().is_err() {
+        return
+    }
+    assert_that(process("ninja").arg("-f").arg("build.ninja").cwd(p.root()),
+                execs().with_status(0));
+    assert_that(process(&p.bin("foo")),
+                execs().with_status(0).with_stdout("configured\n"));
+}
+
+#[test]
+fn emit_ninja_links_metadata() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [dependencies]
+            a = { path = "a" }
+        "#)
+        .file("build.rs", r#"
+            use std::env;
+            fn main() {
+                assert_eq!(env::var("DEP_A_ROOT").unwrap(), "it's here");
+                assert!(env::var("DEP_A_RERUN_IF_CHANGED").is_err());
+            }
+        "#)
+        .file("src/main.rs", "extern crate a; fn main() {}")
+        .file("a/Cargo.toml", r#"
+            [package]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+            links = "a"
+        "#)
+        .file("a/build.rs", r#"
+            fn main() {
+                println!("cargo:root=it's here");
+                println!("cargo:rerun-if-changed=build.rs");
+                println!("cargo:rustc-flags=-L native=nowhere");
+            }
+        "#)
+        .file("a/src/lib.rs", "");
+
+    assert_that(p.cargo_process("build").arg("--emit-ninja").arg("build.ninja"),
+                execs().with_status(0));
+
+    let mut ninja = String::new();
+    File::open(p.root().join("build.ninja")).unwrap()
+        .read_to_string(&mut ninja).unwrap();
+    assert!(ninja.contains("rule dep_env"), "{}", ninja);
+    assert!(ninja.contains("dep-env-a"), "{}", ninja);
+    assert!(ninja.contains("cargo:rustc-flags"), "{}", ninja);
+
+    // Ninja isn't installed everywhere the tests are run.
+    if process("ninja").arg("--version").exec_with_output().is_err() {
+        return
+    }
+    assert_that(process("ninja").arg("-f").arg("build.ninja").cwd(p.root()),
+                execs().with_status(0));
+    let args = fs::read_dir(p.root().join("target/debug/build")).unwrap()
+                  .map(|e| e.unwrap().path().join("search-args"))
+                  .find(|p| {
+                      p.exists() &&
+                          p.parent().unwrap().file_name().unwrap()
+                           .to_str().unwrap().starts_with("a-")
+                  }).unwrap();
+    let mut contents = String::new();
+    File::open(&args).unwrap().read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "-L\nnative=nowhere\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn output_transforms() {
+    let config = format!(r#"
+        [target.{}.transform.copy]
+        command = ["cp", "{{input}}", "{{output}}"]
+        suffix = ".copy"
+    "#, rustc_host());
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]))
+        .file(".cargo/config", &config);
+    assert_that(p.cargo_process("build").arg("-v"),
+                execs().with_status(0).with_stderr_contains("\
+[RUNNING] `cp [..]foo [..]foo.copy`
+"));
+    assert_that(&p.root().join("target/debug/foo.copy"), existing_file());
+
+    // Nothing is redone as long as the transform stays the same
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr("\
+[FINISHED] [..]
+"));
+
+    File::create(p.root().join(".cargo/config")).unwrap()
+         .write_all(config.replace(".copy", ".bak").as_bytes()).unwrap();
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] foo v0.5.0 ([..])
+[FINISHED] [..]
+"));
+    assert_that(&p.root().join("target/debug/foo.bak"), existing_file());
+}
+
+#[test]
+fn output_transform_without_command() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]))
+        .file(".cargo/config", &format!(r#"
+            [target.{}.transform.copy]
+            suffix = ".copy"
+        "#, rustc_host()));
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr(&format!("\
+[ERROR] `target.{}.transform.copy.command` must be specified
+", rustc_host())));
+}
+
+#[test]
+fn package_target_dir() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+            baz = { path = "baz" }
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            extern crate baz;
+            fn main() { bar::bar(); baz::baz(); }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            baz = { path = "../baz" }
+        "#)
+        .file("bar/src/lib.rs", r#"
+            extern crate baz;
+            pub fn bar() { baz::baz() }
+        "#)
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "pub fn baz() {}")
+        .file(".cargo/config", r#"
+            [build.package-target-dir]
+            foo = "elsewhere"
+            bar = "elsewhere"
+        "#);
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    let elsewhere = p.root().join("elsewhere/debug/deps");
+    let files = fs::read_dir(&elsewhere).unwrap()
+                   .map(|e| e.unwrap().file_name().into_string().unwrap())
+                   .collect::<Vec<_>>();
+    assert!(files.iter().any(|f| f.starts_with("libbar-")), "{:?}", files);
+    assert!(!files.iter().any(|f| f.starts_with("libbaz-")), "{:?}", files);
+    let foo = format!("foo{}", env::consts::EXE_SUFFIX);
+    assert_that(&p.root().join("elsewhere/debug").join(&foo),
+                is_not(existing_file()));
+
+    // The binary of the package being built is still uplifted
+    assert_that(&p.bin("foo"), existing_file());
+    assert_that(process(&p.bin("foo")), execs().with_status(0));
+
+    assert_that(p.cargo("clean"), execs().with_status(0));
+    assert_that(&p.root().join("elsewhere"), is_not(existing_dir()));
+}
+
+#[test]
+fn subsystem_verbosity() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    sleep_ms(1000);
+    File::create(&p.root().join("src/lib.rs")).unwrap()
+         .write_all(b"pub fn foo() {}").unwrap();
+    assert_that(p.cargo("build").arg("-vv=fingerprint"),
+                execs().with_status(0).with_stderr("\
+[DIRTY] foo v0.5.0 ([..]) (foo): [..]
+[COMPILING] foo v0.5.0 ([..])
+[FINISHED] [..]
+"));
+
+    // A single `v` is enough to see the details of everything but compiling
+    sleep_ms(1000);
+    File::create(&p.root().join("src/lib.rs")).unwrap()
+         .write_all(b"pub fn bar() {}").unwrap();
+    assert_that(p.cargo("build").arg("-v=fingerprint,uplift"),
+                execs().with_status(0).with_stderr("\
+[DIRTY] foo v0.5.0 ([..]) (foo): [..]
+[COMPILING] foo v0.5.0 ([..])
+[UPLIFTING] `[..]libfoo.rlib` to `[..]libfoo.rlib`
+[FINISHED] [..]
+"));
+
+    // ... and `-vv` on its own doesn't show them
+    sleep_ms(1000);
+    File::create(&p.root().join("src/lib.rs")).unwrap()
+         .write_all(b"pub fn baz() {}").unwrap();
+    assert_that(p.cargo("build").arg("-vv"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] foo v0.5.0 ([..])
+[RUNNING] `rustc [..]`
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("build").arg("-v=linking"),
+                execs().with_status(101).with_stderr("\
+[ERROR] unknown subsystem `linking`, expected one of `download`, `compile`, \
+`uplift` or `fingerprint`
+"));
+}
+
+#[test]
+fn compiler_messages_json() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "fn unused() {}");
+
+    assert_that(p.cargo_process("build").arg("--message-format").arg("json"),
+                execs().with_status(0).with_stdout_contains(
+                    r#"{"message":{[..]"level":"warning"[..]"reason":"compiler-message"[..]"#));
+}
@@ -1,8 +1,14 @@
 use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
 use std::path::{PathBuf, Path};
 
+use flate2::Compression::Default;
+use flate2::write::GzEncoder;
 use hamcrest::{Matcher, MatchResult, existing_file};
+use tar::{Builder, Header};
 use support::paths;
+use support::registry::cksum;
 
 pub use self::InstalledExe as has_installed_exe;
 
@@ -16,6 +22,27 @@ fn exe(name: &str) -> String {
     if cfg!(windows) {format!("{}.exe", name)} else {name.to_string()}
 }
 
+/// Writes an archive of prebuilt binaries named `bins` to `dst`, each one
+/// containing `contents`, and returns its checksum.
+pub fn prebuilt_archive(dst: &Path, bins: &[&str], contents: &str) -> String {
+    {
+        let f = File::create(dst).unwrap();
+        let mut a = Builder::new(GzEncoder::new(f, Default));
+        for bin in bins {
+            let mut header = Header::new_ustar();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_path(exe(bin)).unwrap();
+            header.set_cksum();
+            a.append(&header, contents.as_bytes()).unwrap();
+        }
+        a.into_inner().unwrap().finish().unwrap();
+    }
+    let mut data = Vec::new();
+    File::open(dst).unwrap().read_to_end(&mut data).unwrap();
+    cksum(&data)
+}
+
 impl<P: AsRef<Path>> Matcher<P> for InstalledExe {
     fn matches(&self, path: P) -> MatchResult {
         let path = path.as_ref().join("bin").join(exe(self.0));
@@ -12,6 +12,7 @@ extern crate tar;
 extern crate tempdir;
 extern crate term;
 extern crate url;
+#[cfg(unix)] extern crate openssl;
 #[cfg(windows)] extern crate kernel32;
 #[cfg(windows)] extern crate winapi;
 
@@ -655,6 +655,7 @@ fn substitute_macros(input: &str) -> String {
         ("[FRESH]",       "       Fresh"),
         ("[UPDATING]",    "    Updating"),
         ("[ADDING]",      "      Adding"),
+        ("[ADDED]",       "       Added"),
         ("[REMOVING]",    "    Removing"),
         ("[DOCTEST]",     "   Doc-tests"),
         ("[PACKAGING]",   "   Packaging"),
@@ -662,9 +663,19 @@ fn substitute_macros(input: &str) -> String {
         ("[UPLOADING]",   "   Uploading"),
         ("[VERIFYING]",   "   Verifying"),
         ("[ARCHIVING]",   "   Archiving"),
+        ("[ARCHIVED]",    "    Archived"),
         ("[INSTALLING]",  "  Installing"),
         ("[REPLACING]",   "   Replacing"),
         ("[UNPACKING]",   "   Unpacking"),
+        ("[EXPORTING]",   "   Exporting"),
+        ("[RECORDED]",    "    Recorded"),
+        ("[UNCHANGED]",   "   Unchanged"),
+        ("[FETCHING]",    "    Fetching"),
+        ("[UPLIFTING]",   "   Uplifting"),
+        ("[DIRTY]",       "       Dirty"),
+        ("[PINNED]",      "      Pinned"),
+        ("[UNPINNED]",    "    Unpinned"),
+        ("[UPGRADING]",   "   Upgrading"),
     ];
     let mut result = input.to_owned();
     for &(pat, subst) in macros.iter() {
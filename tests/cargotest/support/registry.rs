@@ -25,8 +25,16 @@ pub struct Package {
     vers: String,
     deps: Vec<Dependency>,
     files: Vec<(String, String)>,
+    extra_files: Vec<(String, String)>,
     yanked: bool,
+    pubtime: Option<String>,
+    size: Option<u64>,
+    rust_version: Option<String>,
+    feature_descriptions: HashMap<String, String>,
+    publisher: Option<String>,
+    signing_key: Option<String>,
     features: HashMap<String, Vec<String>>,
+    prebuilt: Vec<(String, String, String)>,
     local: bool,
 }
 
@@ -73,8 +81,16 @@ impl Package {
             vers: vers.to_string(),
             deps: Vec::new(),
             files: Vec::new(),
+            extra_files: Vec::new(),
             yanked: false,
+            pubtime: None,
+            size: None,
+            rust_version: None,
+            feature_descriptions: HashMap::new(),
+            publisher: None,
+            signing_key: None,
             features: HashMap::new(),
+            prebuilt: Vec::new(),
             local: false,
         }
     }
@@ -89,6 +105,13 @@ impl Package {
         self
     }
 
+    /// Adds a file to the `.crate` file at `path`, rather than under the
+    /// directory of the package.
+    pub fn extra_file(&mut self, path: &str, contents: &str) -> &mut Package {
+        self.extra_files.push((path.to_string(), contents.to_string()));
+        self
+    }
+
     pub fn dep(&mut self, name: &str, vers: &str) -> &mut Package {
         self.full_dep(name, vers, None, "normal", &[])
     }
@@ -127,11 +150,58 @@ impl Package {
         self
     }
 
+    pub fn feature(&mut self, name: &str, deps: &[&str]) -> &mut Package {
+        self.features.insert(name.to_string(),
+                             deps.iter().map(|s| s.to_string()).collect());
+        self
+    }
+
+    pub fn feature_description(&mut self, name: &str, description: &str)
+                               -> &mut Package {
+        self.feature_descriptions.insert(name.to_string(),
+                                         description.to_string());
+        self
+    }
+
     pub fn yanked(&mut self, yanked: bool) -> &mut Package {
         self.yanked = yanked;
         self
     }
 
+    pub fn pubtime(&mut self, pubtime: &str) -> &mut Package {
+        self.pubtime = Some(pubtime.to_string());
+        self
+    }
+
+    pub fn size(&mut self, size: u64) -> &mut Package {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn rust_version(&mut self, rust_version: &str) -> &mut Package {
+        self.rust_version = Some(rust_version.to_string());
+        self
+    }
+
+    pub fn publisher(&mut self, publisher: &str) -> &mut Package {
+        self.publisher = Some(publisher.to_string());
+        self
+    }
+
+    /// Declares prebuilt binaries for `target` in the manifest.
+    pub fn prebuilt(&mut self, target: &str, url: &str, sha256: &str)
+                    -> &mut Package {
+        self.prebuilt.push((target.to_string(), url.to_string(),
+                            sha256.to_string()));
+        self
+    }
+
+    /// Signs the `.crate` file with `private_key`, an RSA key in PEM format.
+    pub fn sign(&mut self, private_key: &str) -> &mut Package {
+        self.signing_key = Some(private_key.to_string());
+        self
+    }
+
     pub fn publish(&self) -> String {
         self.make_archive();
 
@@ -147,18 +217,35 @@ impl Package {
             map.insert("kind".to_string(), dep.kind.to_json());
             map
         }).collect::<Vec<_>>();
-        let cksum = {
-            let mut c = Vec::new();
-            t!(t!(File::open(&self.archive_dst())).read_to_end(&mut c));
-            cksum(&c)
-        };
+        let mut c = Vec::new();
+        t!(t!(File::open(&self.archive_dst())).read_to_end(&mut c));
+        let cksum = cksum(&c);
         let mut dep = HashMap::new();
         dep.insert("name".to_string(), self.name.to_json());
         dep.insert("vers".to_string(), self.vers.to_json());
         dep.insert("deps".to_string(), deps.to_json());
         dep.insert("cksum".to_string(), cksum.to_json());
         dep.insert("features".to_string(), self.features.to_json());
+        if !self.feature_descriptions.is_empty() {
+            dep.insert("feature_descriptions".to_string(),
+                       self.feature_descriptions.to_json());
+        }
         dep.insert("yanked".to_string(), self.yanked.to_json());
+        if let Some(ref pubtime) = self.pubtime {
+            dep.insert("pubtime".to_string(), pubtime.to_json());
+        }
+        if let Some(size) = self.size {
+            dep.insert("size".to_string(), size.to_json());
+        }
+        if let Some(ref rust_version) = self.rust_version {
+            dep.insert("rust_version".to_string(), rust_version.to_json());
+        }
+        if let Some(ref publisher) = self.publisher {
+            dep.insert("publisher".to_string(), publisher.to_json());
+        }
+        if let Some(ref key) = self.signing_key {
+            dep.insert("sig".to_string(), sign(key, &c).to_json());
+        }
         let line = dep.to_json().to_string();
 
         let file = match self.name.len() {
@@ -223,6 +310,13 @@ impl Package {
                 version = "{}"
             "#, target, kind, dep.name, dep.vers));
         }
+        for &(ref target, ref url, ref sha256) in self.prebuilt.iter() {
+            manifest.push_str(&format!(r#"
+                [prebuilt.{}]
+                url = "{}"
+                sha256 = "{}"
+            "#, target, url, sha256));
+        }
 
         let dst = self.archive_dst();
         t!(fs::create_dir_all(dst.parent().unwrap()));
@@ -236,12 +330,20 @@ impl Package {
                 self.append(&mut a, name, contents);
             }
         }
+        for &(ref path, ref contents) in self.extra_files.iter() {
+            self.append_raw(&mut a, path, contents);
+        }
     }
 
     fn append<W: Write>(&self, ar: &mut Builder<W>, file: &str, contents: &str) {
+        let path = format!("{}-{}/{}", self.name, self.vers, file);
+        self.append_raw(ar, &path, contents);
+    }
+
+    fn append_raw<W: Write>(&self, ar: &mut Builder<W>, path: &str, contents: &str) {
         let mut header = Header::new_ustar();
         header.set_size(contents.len() as u64);
-        t!(header.set_path(format!("{}-{}/{}", self.name, self.vers, file)));
+        t!(header.set_path(path));
         header.set_cksum();
 
         t!(ar.append(&header, contents.as_bytes()));
@@ -262,3 +364,19 @@ pub fn cksum(s: &[u8]) -> String {
     sha.update(s);
     sha.finish().to_hex()
 }
+
+#[cfg(unix)]
+fn sign(private_key: &str, s: &[u8]) -> String {
+    use openssl::crypto::hash::Type;
+    use openssl::crypto::pkey::PKey;
+
+    let mut sha = Sha256::new();
+    sha.update(s);
+    let key = t!(PKey::private_key_from_pem(&mut private_key.as_bytes()));
+    key.sign_with_hash(&sha.finish(), Type::SHA256).to_hex()
+}
+
+#[cfg(windows)]
+fn sign(_private_key: &str, _s: &[u8]) -> String {
+    panic!("signing packages isn't supported on windows")
+}
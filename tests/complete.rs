@@ -0,0 +1,87 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::fs::File;
+use std::io::prelude::*;
+
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+fn workspace() -> cargotest::support::ProjectBuilder {
+    project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [features]
+            fast = []
+
+            [workspace]
+            members = ["tool"]
+        "#)
+        .file("src/lib.rs", "")
+        .file("examples/demo.rs", "fn main() {}")
+        .file("tool/Cargo.toml", r#"
+            [package]
+            name = "tool"
+            version = "0.1.0"
+            authors = []
+
+            [features]
+            verbose = []
+        "#)
+        .file("tool/src/main.rs", "fn main() {}")
+        .file("tool/src/bin/helper.rs", "fn main() {}")
+}
+
+#[test]
+fn values_come_from_the_workspace() {
+    let p = workspace();
+    p.build();
+
+    assert_that(p.cargo("complete").arg("--values").arg("packages"),
+                execs().with_status(0).with_stdout("foo\ntool\n"));
+    assert_that(p.cargo("complete").arg("--values").arg("bins"),
+                execs().with_status(0).with_stdout("helper\ntool\n"));
+    assert_that(p.cargo("complete").arg("--values").arg("examples"),
+                execs().with_status(0).with_stdout("demo\n"));
+    assert_that(p.cargo("complete").arg("--values").arg("features"),
+                execs().with_status(0).with_stdout("fast\nverbose\n"));
+
+    // A new feature is picked up once the manifest changes.
+    File::create(p.root().join("tool/Cargo.toml")).unwrap().write_all(br#"
+        [package]
+        name = "tool"
+        version = "0.1.0"
+        authors = []
+
+        [features]
+        quiet = []
+    "#).unwrap();
+    assert_that(p.cargo("complete").arg("--values").arg("features"),
+                execs().with_status(0).with_stdout("fast\nquiet\n"));
+
+    assert_that(p.cargo("complete").arg("--values").arg("crates"),
+                execs().with_status(101).with_stderr("\
+[ERROR] unknown kind of value `crates`, expected one of `packages`, `bins`, \
+`examples`, `tests`, `benches`, `features`, `profiles`
+"));
+}
+
+#[test]
+fn scripts() {
+    let p = workspace();
+    p.build();
+
+    assert_that(p.cargo("complete").arg("bash"),
+                execs().with_status(0)
+                       .with_stdout_contains("complete -F _cargo_complete cargo"));
+    assert_that(p.cargo("complete").arg("zsh"),
+                execs().with_status(0).with_stdout_contains("#compdef cargo"));
+    assert_that(p.cargo("complete").arg("fish"),
+                execs().with_status(101).with_stderr("\
+[ERROR] completions can't be generated for `fish`, only for `bash` and `zsh`
+"));
+}
@@ -0,0 +1,177 @@
+#[macro_use]
+extern crate cargotest;
+extern crate hamcrest;
+extern crate url;
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use cargotest::support::git::repo;
+use cargotest::support::paths;
+use cargotest::support::{project, execs, ProjectBuilder};
+use hamcrest::assert_that;
+use url::Url;
+
+fn registry_path() -> PathBuf { paths::root().join("registry") }
+fn registry() -> Url { Url::from_file_path(&*registry_path()).ok().unwrap() }
+fn upload_path() -> PathBuf { paths::root().join("upload") }
+fn upload() -> Url { Url::from_file_path(&*upload_path()).ok().unwrap() }
+
+fn setup(registry_config: &str) {
+    let config = paths::root().join(".cargo/config");
+    t!(fs::create_dir_all(config.parent().unwrap()));
+    t!(t!(File::create(&config)).write_all(format!(r#"
+        [registry]
+        index = "{}"
+        {}
+    "#, registry(), registry_config).as_bytes()));
+    t!(fs::create_dir_all(&upload_path().join("api/v1/crates")));
+
+    repo(&registry_path())
+        .file("config.json", &format!(r#"{{
+            "dl": "{0}",
+            "api": "{0}"
+        }}"#, upload()))
+        .build();
+}
+
+fn read(path: &Path) -> String {
+    let mut contents = String::new();
+    t!(t!(File::open(path)).read_to_string(&mut contents));
+    contents
+}
+
+/// A package whose `provider.sh` answers requests for tokens with `get`, and
+/// any other request with `other`, recording them all in `requests.txt`.
+fn project_with_provider(get: &str, other: &str) -> ProjectBuilder {
+    project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "foo"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("provider.sh", &format!(r#"
+            read request
+            echo "$request" >> requests.txt
+            case "$request" in
+                *'"action":"get"'*) echo '{}' ;;
+                *) echo '{}' ;;
+            esac
+        "#, get, other))
+}
+
+#[test]
+fn login_and_logout_with_token() {
+    setup("");
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    let global = paths::home().join(".cargo/config");
+
+    assert_that(p.cargo_process("login").arg("api-token"),
+                execs().with_status(0));
+    assert!(read(&global).contains("token = \"api-token\""));
+
+    assert_that(p.cargo("logout"), execs().with_status(0));
+    assert!(!read(&global).contains("token"));
+    assert_that(p.cargo("logout"),
+                execs().with_status(101).with_stderr("\
+[ERROR] there's no token in the configuration to remove
+"));
+}
+
+#[cfg(unix)]
+#[test]
+fn publish_with_provider() {
+    setup(r#"credential-provider = ["sh", "provider.sh"]"#);
+    let p = project_with_provider(r#"{"token":"from-provider"}"#, "{}");
+
+    assert_that(p.cargo_process("publish").arg("--no-verify")
+                 .arg("--host").arg(registry().to_string()),
+                execs().with_status(0));
+    assert_eq!(read(&p.root().join("requests.txt")), format!("\
+{{\"v\":1,\"action\":\"get\",\"registry\":\"{}\",\"token\":null}}
+", registry()));
+}
+
+#[cfg(unix)]
+#[test]
+fn login_and_logout_with_provider() {
+    setup(r#"credential-provider = "sh provider.sh""#);
+    let p = project_with_provider("{}", "{}");
+
+    assert_that(p.cargo_process("login").arg("secret"), execs().with_status(0));
+    assert_that(p.cargo("logout"), execs().with_status(0));
+    assert_eq!(read(&p.root().join("requests.txt")), format!("\
+{{\"v\":1,\"action\":\"login\",\"registry\":\"{0}\",\"token\":\"secret\"}}
+{{\"v\":1,\"action\":\"logout\",\"registry\":\"{0}\",\"token\":null}}
+", registry()));
+    assert!(!paths::home().join(".cargo/config").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn publish_with_generated_key() {
+    setup("");
+    let p = project_with_provider("{}", "{}");
+    let global = paths::home().join(".cargo/config");
+
+    assert_that(p.cargo_process("login").arg("--generate-key")
+                 .arg("--key-id").arg("my-key"),
+                execs().with_status(0).with_stdout_contains("\
+-----BEGIN PUBLIC KEY-----
+"));
+    let config = read(&global);
+    assert!(config.contains("credential-provider = \"cargo:secret-key\""));
+    assert!(config.contains("secret-key-id = \"my-key\""));
+    assert!(paths::home().join(".cargo/registry-key.pem").is_file());
+
+    assert_that(p.cargo("login").arg("secret"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] the `cargo:secret-key` credential provider signs requests instead of \
+using tokens, run `cargo login --generate-key` to make a new key
+"));
+    assert_that(p.cargo("publish").arg("--no-verify")
+                 .arg("--host").arg(registry().to_string()),
+                execs().with_status(0));
+    assert!(upload_path().join("api/v1/crates/new").is_file());
+
+    assert_that(p.cargo("logout"), execs().with_status(0));
+    assert!(!read(&global).contains("secret-key"));
+}
+
+#[cfg(unix)]
+#[test]
+fn provider_error() {
+    setup(r#"credential-provider = ["sh", "provider.sh"]"#);
+    let p = project_with_provider(r#"{"error":"access denied"}"#, "{}");
+
+    assert_that(p.cargo_process("publish").arg("--no-verify")
+                 .arg("--host").arg(registry().to_string()),
+                execs().with_status(101).with_stderr_contains(&format!("\
+[ERROR] the credential provider `sh` failed to get the token of `{}`: access denied
+", registry())));
+    assert!(!upload_path().join("api/v1/crates/new").exists());
+}
+
+#[test]
+fn unknown_built_in_provider() {
+    setup(r#"credential-provider = "cargo:vault""#);
+    let p = project_with_provider("{}", "{}");
+
+    assert_that(p.cargo_process("login").arg("secret"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] unknown built-in credential provider `cargo:vault`, expected \
+`cargo:token`, `cargo:keyring` or `cargo:secret-key`
+"));
+}
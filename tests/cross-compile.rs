@@ -1,6 +1,7 @@
 extern crate cargo;
 extern crate cargotest;
 extern crate hamcrest;
+extern crate rustc_serialize;
 
 use std::env;
 
@@ -8,6 +9,7 @@ use cargo::util::process;
 use cargotest::{is_nightly, rustc_host};
 use cargotest::support::{project, execs, basic_bin_manifest};
 use hamcrest::{assert_that, existing_file};
+use rustc_serialize::json::Json;
 
 fn disabled() -> bool {
     // First, disable if ./configure requested so
@@ -491,7 +493,7 @@ test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured
 }
 
 #[test]
-fn no_cross_doctests() {
+fn cross_doctests() {
     if disabled() { return }
 
     let p = project("foo")
@@ -528,12 +530,44 @@ fn no_cross_doctests() {
 
     println!("c");
     let target = alternate();
-    assert_that(p.cargo_process("test").arg("--target").arg(&target),
+    assert_that(p.cargo_process("test").arg("--target").arg(&target).arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains(&format!("\
+[RUNNING] `rustdoc --test [..]src[..]lib.rs --crate-name foo --target {triple} [..]`",
+                                                      triple = target))
+                       .with_stderr_contains("[DOCTEST] foo"));
+}
+
+#[test]
+fn cross_doctests_skipped_without_runner() {
+    if disabled() { return }
+
+    let target = alternate();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            authors = []
+            version = "0.0.0"
+        "#)
+        .file("src/lib.rs", r#"
+            //! ```
+            //! assert!(true);
+            //! ```
+        "#)
+        .file(".cargo/config", &format!(r#"
+            [target.{}.runner]
+            preset = "qemu-system"
+            machine = "virt"
+        "#, target));
+
+    assert_that(p.cargo_process("test").arg("--doc").arg("--target").arg(&target),
                 execs().with_status(0)
                        .with_stderr(&format!("\
 [COMPILING] foo v0.0.0 ({foo})
 [FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
-[RUNNING] target[..]{triple}[..]foo-[..]
+[WARNING] skipping the doctests of `foo`: the `qemu-system` runner of `{triple}` \
+can only boot binaries it's given
 ", foo = p.url(), triple = target)));
 }
 
@@ -971,3 +1005,74 @@ fn platform_specific_variables_reflected_in_build_scripts() {
     assert_that(p.cargo_process("build").arg("-v").arg("--target").arg(&target),
                 execs().with_status(0));
 }
+
+#[test]
+fn rust_project_macro_for_host() {
+    if disabled() { return }
+    if !is_nightly() { return }
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.mac]
+            path = "mac"
+        "#)
+        .file("src/lib.rs", r#"
+            #![feature(rustc_macro)]
+
+            #[macro_use]
+            extern crate mac;
+        "#)
+        .file("mac/Cargo.toml", r#"
+            [package]
+            name = "mac"
+            version = "0.0.1"
+            authors = []
+
+            [lib]
+            rustc-macro = true
+        "#)
+        .file("mac/src/lib.rs", r#"
+            #![feature(rustc_macro, rustc_macro_lib)]
+
+            extern crate rustc_macro;
+            use rustc_macro::TokenStream;
+
+            #[rustc_macro_derive(Noop)]
+            pub fn noop(input: TokenStream) -> TokenStream {
+                input
+            }
+        "#);
+
+    let target = alternate();
+    assert_that(p.cargo_process("rust-project").arg("--target").arg(&target),
+                execs().with_status(0));
+    let output = p.cargo("rust-project").arg("--target").arg(&target)
+                  .exec_with_output().unwrap();
+    let json = Json::from_str(&String::from_utf8(output.stdout).unwrap()).unwrap();
+    let crates = json.find("crates").unwrap().as_array().unwrap();
+    let find = |name: &str| {
+        crates.iter().find(|c| {
+            c.find("display_name").unwrap().as_string() == Some(name)
+        }).unwrap()
+    };
+    let has_arch = |krate: &Json, arch: &str| {
+        let arch = format!("target_arch=\"{}\"", arch);
+        krate.find("cfg").unwrap().as_array().unwrap().iter().any(|c| {
+            c.as_string() == Some(&arch[..])
+        })
+    };
+
+    // The procedural macro is loaded by the compiler, so it's described
+    // with the host's platform.
+    let mac = find("mac");
+    assert_eq!(mac.find("target").unwrap().as_string(), Some(&host()[..]));
+    assert!(has_arch(mac, env::consts::ARCH));
+    let foo = find("foo");
+    assert_eq!(foo.find("target").unwrap().as_string(), Some(&target[..]));
+    assert!(has_arch(foo, alternate_arch()));
+}
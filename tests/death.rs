@@ -96,6 +96,51 @@ fn ctrl_c_kills_everyone() {
     }
 }
 
+#[cfg(unix)]
+#[test]
+fn ctrl_c_reports_aborted_jobs() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            build = "build.rs"
+        "#)
+        .file("src/lib.rs", "")
+        .file("build.rs", &format!(r#"
+            use std::net::TcpStream;
+            use std::io::Read;
+
+            fn main() {{
+                let mut socket = TcpStream::connect("{}").unwrap();
+                let _ = socket.read(&mut [0; 10]);
+                panic!("that read should never return");
+            }}
+        "#, addr));
+    p.build();
+
+    let mut cargo = p.cargo("build").build_command();
+    cargo.stdin(Stdio::piped())
+         .stdout(Stdio::piped())
+         .stderr(Stdio::piped())
+         .env("__CARGO_TEST_SETSID_PLEASE_DONT_USE_ELSEWHERE", "1");
+    let mut child = cargo.spawn().unwrap();
+
+    let _sock = listener.accept().unwrap().0;
+    ctrl_c(&mut child);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Aborted foo v0.0.1"), "{}", stderr);
+    assert!(stderr.contains("(running the build script)"), "{}", stderr);
+    assert!(stderr.contains("error: build interrupted: "), "{}", stderr);
+}
+
 #[cfg(unix)]
 fn ctrl_c(child: &mut Child) {
     use libc;
@@ -333,3 +333,47 @@ required then it is recommended that [replace] is used with a forked copy of \
 the source
 "));
 }
+
+#[test]
+fn vendored_flag() {
+    VendorPackage::new("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}")
+        .build();
+
+    let p = project("bar")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            foo = "0.1.0"
+        "#)
+        .file("src/lib.rs", r#"
+            extern crate foo;
+
+            pub fn bar() {
+                foo::foo();
+            }
+        "#);
+    p.build();
+
+    assert_that(p.cargo("build").arg("--vendored").arg("../index"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] foo v0.1.0
+[COMPILING] bar v0.1.0 ([..]bar)
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("build").arg("--vendored").arg("../missing"),
+                execs().with_status(101).with_stderr("\
+error: the vendor directory `[..]missing` passed to --vendored does not exist
+"));
+}
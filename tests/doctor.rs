@@ -0,0 +1,104 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+
+use cargotest::support::paths;
+use cargotest::support::registry::{self, Package};
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+fn project_with_dep() -> cargotest::support::ProjectBuilder {
+    Package::new("bar", "0.1.0").publish();
+    project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "")
+}
+
+#[test]
+fn no_problems() {
+    let p = project_with_dep();
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    assert_that(p.cargo("doctor"),
+                execs().with_status(0)
+                       .with_stderr_contains("[FINISHED] no problems found"));
+}
+
+#[test]
+fn corrupt_download() {
+    let p = project_with_dep();
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    let cache = paths::home().join(".cargo/registry/cache");
+    for dir in fs::read_dir(&cache).unwrap() {
+        let file = dir.unwrap().path().join("bar-0.1.0.crate");
+        File::create(&file).unwrap().write_all(b"corrupt").unwrap();
+    }
+
+    assert_that(p.cargo("doctor"),
+                execs().with_status(101).with_stderr_contains("\
+[WARNING] the downloaded `[..]bar-0.1.0.crate` does not match the checksum of \
+`bar v0.1.0` in Cargo.lock
+help: remove `[..]bar-0.1.0.crate` and `[..]bar-0.1.0` so that it is \
+downloaded again
+").with_stderr_contains("[ERROR] found 1 problem"));
+}
+
+#[test]
+fn corrupt_git_repository() {
+    registry::init();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    let repo = paths::home().join(".cargo/git/db/bar-0123456789abcdef");
+    fs::create_dir_all(&repo).unwrap();
+    File::create(repo.join("HEAD")).unwrap().write_all(b"garbage").unwrap();
+
+    assert_that(p.cargo("doctor"),
+                execs().with_status(101).with_stderr_contains("\
+[WARNING] the git repository `[..]bar-0123456789abcdef` is corrupt: [..]
+help: remove `[..]bar-0123456789abcdef`, it will be fetched again when needed
+").with_stderr_contains("[ERROR] found 1 problem"));
+}
+
+#[test]
+fn broken_replacement() {
+    registry::init();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file(".cargo/config", r#"
+            [source.crates-io]
+            replace-with = 'nowhere'
+        "#);
+    p.build();
+
+    assert_that(p.cargo("doctor"),
+                execs().with_status(101).with_stderr_contains("\
+[WARNING] source `crates-io` cannot be replaced with `nowhere`: could not find \
+a configured source with the name `nowhere` [..]
+help: correct `source.crates-io.replace-with` in the configuration
+"));
+}
@@ -0,0 +1,181 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::git;
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+#[test]
+fn bazel() {
+    Package::new("baz", "0.1.0").publish();
+    Package::new("winapi", "0.1.0").publish();
+    Package::new("bar", "0.1.0")
+            .dep("baz", "0.1.0")
+            .target_dep("winapi", "0.1.0", "x86_64-pc-windows-gnu")
+            .publish();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1.0"
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("export").arg("--format").arg("bazel"),
+                execs().with_status(0).with_stdout(r#"# Generated by `cargo export --format bazel`, do not edit.
+#
+# The sources of each package are expected in a directory named after its
+# rule next to this file. Platform-specific dependencies are selected with
+# conditions in `//platforms` named after the platform in Cargo.toml.
+
+load("@rules_rust//cargo:defs.bzl", "cargo_build_script")
+load("@rules_rust//rust:defs.bzl", "rust_library", "rust_proc_macro")
+
+rust_library(
+    name = "bar-0.1.0",
+    crate_name = "bar",
+    srcs = glob(["bar-0.1.0/**/*.rs"]),
+    crate_root = "bar-0.1.0/src/lib.rs",
+    crate_features = [],
+    deps = [":baz-0.1.0"] + select({
+        "//platforms:x86_64-pc-windows-gnu": [":winapi-0.1.0"],
+        "//conditions:default": [],
+    }),
+    proc_macro_deps = [],
+    compile_data = glob(["bar-0.1.0/**"]),
+    version = "0.1.0",
+    visibility = ["//visibility:public"],
+)
+
+rust_library(
+    name = "baz-0.1.0",
+    crate_name = "baz",
+    srcs = glob(["baz-0.1.0/**/*.rs"]),
+    crate_root = "baz-0.1.0/src/lib.rs",
+    crate_features = [],
+    deps = [],
+    proc_macro_deps = [],
+    compile_data = glob(["baz-0.1.0/**"]),
+    version = "0.1.0",
+    visibility = ["//visibility:public"],
+)
+
+rust_library(
+    name = "winapi-0.1.0",
+    crate_name = "winapi",
+    srcs = glob(["winapi-0.1.0/**/*.rs"]),
+    crate_root = "winapi-0.1.0/src/lib.rs",
+    crate_features = [],
+    deps = [],
+    proc_macro_deps = [],
+    compile_data = glob(["winapi-0.1.0/**"]),
+    version = "0.1.0",
+    visibility = ["//visibility:public"],
+)
+
+alias(
+    name = "bar",
+    actual = ":bar-0.1.0",
+    visibility = ["//visibility:public"],
+)
+"#));
+}
+
+#[test]
+fn buck2_build_script_and_features() {
+    let git = git::new("dep", |project| {
+        project.file("Cargo.toml", r#"
+            [package]
+            name = "dep"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [features]
+            default = ["fancy"]
+            fancy = []
+        "#)
+        .file("build.rs", "fn main() {}")
+        .file("src/lib.rs", "")
+    }).unwrap();
+
+    let p = project("foo")
+        .file("Cargo.toml", &format!(r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            dep = {{ git = '{}' }}
+        "#, git.url()))
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("export")
+                 .arg("--format").arg("buck2")
+                 .arg("--platforms").arg("//third-party/platforms"),
+                execs().with_status(0).with_stdout(r#"# Generated by `cargo export --format buck2`, do not edit.
+#
+# The sources of each package are expected in a directory named after its
+# rule next to this file. Platform-specific dependencies are selected with
+# conditions in `//third-party/platforms` named after the platform in Cargo.toml.
+
+rust_binary(
+    name = "dep-0.5.0-build-script-build",
+    srcs = glob(["dep-0.5.0/**"]),
+    crate = "build_script_build",
+    crate_root = "dep-0.5.0/build.rs",
+    features = ["default", "fancy"],
+    deps = [],
+)
+
+buildscript_run(
+    name = "dep-0.5.0-build-script-build-run",
+    package_name = "dep",
+    buildscript_rule = ":dep-0.5.0-build-script-build",
+    features = ["default", "fancy"],
+    version = "0.5.0",
+)
+
+rust_library(
+    name = "dep-0.5.0",
+    crate = "dep",
+    srcs = glob(["dep-0.5.0/**"]),
+    crate_root = "dep-0.5.0/src/lib.rs",
+    features = ["default", "fancy"],
+    deps = [],
+    env = {"OUT_DIR": "$(location :dep-0.5.0-build-script-build-run[out_dir])"},
+    rustc_flags = ["@$(location :dep-0.5.0-build-script-build-run[rustc_flags])"],
+    visibility = ["PUBLIC"],
+)
+
+alias(
+    name = "dep",
+    actual = ":dep-0.5.0",
+    visibility = ["PUBLIC"],
+)
+"#));
+}
+
+#[test]
+fn unknown_format() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("export").arg("--format").arg("make"),
+                execs().with_status(101).with_stderr("\
+[ERROR] unknown export format `make`, expected `bazel` or `buck2`
+"));
+}
@@ -1005,3 +1005,139 @@ fn all_features_flag_enables_all_features() {
     assert_that(p.cargo_process("build").arg("--all-features"),
                 execs().with_status(0));
 }
+
+#[test]
+fn conflicting_features() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar", features = ["native-tls"] }
+            mid = { path = "mid" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("mid/Cargo.toml", r#"
+            [package]
+            name = "mid"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = { path = "../bar" }
+
+            [features]
+            default = ["tls"]
+            tls = ["bar/rustls"]
+        "#)
+        .file("mid/src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+
+            [features]
+            native-tls = []
+            rustls = []
+
+            [features.conflicts]
+            native-tls = ["rustls"]
+        "#)
+        .file("bar/src/lib.rs", "");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] features `native-tls` and `rustls` of `bar v0.1.0 ([..])` conflict, \
+but both are enabled:
+  `native-tls` by `foo v0.1.0 ([..])` -> feature `native-tls` of \
+`bar v0.1.0 ([..])`
+  `rustls` by `foo v0.1.0 ([..])` -> feature `default` of `mid v0.1.0 ([..])` \
+-> feature `tls` of `mid v0.1.0 ([..])` -> feature `rustls` of \
+`bar v0.1.0 ([..])`
+"));
+
+    assert_that(p.cargo("build").arg("--manifest-path").arg("mid/Cargo.toml"),
+                execs().with_status(0));
+}
+
+#[test]
+fn conflicting_features_of_root() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [features]
+            default = ["native-tls"]
+            native-tls = []
+            rustls = []
+
+            [features.conflicts]
+            native-tls = ["rustls"]
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0));
+    assert_that(p.cargo("build").arg("--no-default-features")
+                 .arg("--features").arg("rustls"),
+                execs().with_status(0));
+    assert_that(p.cargo("build").arg("--features").arg("rustls"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] features `native-tls` and `rustls` of `foo v0.1.0 ([..])` conflict, \
+but both are enabled:"));
+}
+
+#[test]
+fn feature_named_conflicts() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [features]
+            conflicts = []
+        "#)
+        .file("src/lib.rs", r#"
+            #[cfg(feature = "conflicts")]
+            pub fn conflicts() {}
+        "#)
+        .file("src/main.rs", r#"
+            extern crate foo;
+            fn main() { foo::conflicts() }
+        "#);
+
+    assert_that(p.cargo_process("build").arg("--features").arg("conflicts"),
+                execs().with_status(0));
+}
+
+#[test]
+fn conflicts_name_unknown_feature() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [features]
+            native-tls = []
+
+            [features.conflicts]
+            native-tls = ["rustls"]
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+  `features.conflicts.native-tls` names `rustls`, which isn't a feature
+"));
+}
@@ -1,6 +1,19 @@
+#[macro_use]
 extern crate cargotest;
+extern crate bufstream;
+extern crate git2;
 extern crate hamcrest;
 
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use bufstream::BufStream;
+use cargotest::support::git;
+use cargotest::support::paths;
+use cargotest::support::registry::{self, Package};
 use cargotest::support::{project, execs};
 use hamcrest::assert_that;
 
@@ -21,3 +34,228 @@ fn no_deps() {
     assert_that(p.cargo_process("fetch"),
                 execs().with_status(0).with_stdout(""));
 }
+
+fn project_with_deps() -> cargotest::support::ProjectBuilder {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.2.0").publish();
+    Package::new("qux", "0.3.0").publish();
+    project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+
+            [dependencies]
+            bar = "0.1"
+            baz = "0.2"
+            qux = "0.3"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+}
+
+#[test]
+fn json_progress() {
+    let p = project_with_deps();
+
+    assert_that(p.cargo_process("fetch").arg("--message-format").arg("json"),
+                execs().with_status(0)
+                       .with_stdout_contains("\
+{\"attempt\":null,\"bytes\":[..],\"error\":null,\"package_id\":\"bar 0.1.0 [..]\",\
+\"reason\":\"download-progress\",\"state\":\"finished\",\"url\":\"[..]\"}")
+                       .with_stdout_contains("\
+{\"attempt\":null,\"bytes\":[..],\"error\":null,\"package_id\":\"qux 0.3.0 [..]\",\
+\"reason\":\"download-progress\",\"state\":\"finished\",\"url\":\"[..]\"}"));
+
+    // Everything is in the cache now.
+    assert_that(p.cargo("fetch").arg("--message-format").arg("json"),
+                execs().with_status(0).with_stdout(""));
+}
+
+/// Serves the `.crate` files of the test registry over HTTP under `/dl`,
+/// honoring `Range` headers, and makes the registry download from there.
+/// Returns the headers of each request received.
+fn serve_downloads() -> Arc<Mutex<Vec<Vec<String>>>> {
+    let listener = t!(TcpListener::bind("127.0.0.1:0"));
+    let addr: SocketAddr = t!(listener.local_addr());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut s = BufStream::new(t!(stream));
+            let headers = (&mut s).lines().map(|l| t!(l).trim().to_string())
+                                  .take_while(|l| !l.is_empty())
+                                  .collect::<Vec<_>>();
+            let path = headers[0].split_whitespace().nth(1).unwrap()
+                                 .trim_left_matches("/dl/").to_string();
+            let start = headers.iter().filter_map(|h| {
+                let h = h.to_lowercase();
+                if h.starts_with("range: bytes=") {
+                    h[13..].trim_right_matches('-').parse::<usize>().ok()
+                } else {
+                    None
+                }
+            }).next();
+            received.lock().unwrap().push(headers);
+
+            let mut contents = Vec::new();
+            t!(t!(File::open(registry::dl_path().join(&path)))
+                   .read_to_end(&mut contents));
+            let response = match start {
+                Some(start) => format!("\
+HTTP/1.1 206 Partial Content\r
+Content-Length: {}\r
+Content-Range: bytes {}-{}/{}\r
+Connection: close\r
+\r
+", contents.len() - start, start, contents.len() - 1, contents.len()),
+                None => format!("\
+HTTP/1.1 200 OK\r
+Content-Length: {}\r
+Connection: close\r
+\r
+", contents.len()),
+            };
+            t!(s.write_all(response.as_bytes()));
+            t!(s.write_all(&contents[start.unwrap_or(0)..]));
+            t!(s.flush());
+        }
+    });
+
+    let repo = t!(git2::Repository::open(&registry::registry_path()));
+    t!(t!(File::create(registry::registry_path().join("config.json")))
+           .write_all(format!(r#"{{"dl":"http://{0}/dl","api":"http://{0}/dl"}}"#,
+                              addr).as_bytes()));
+    git::add(&repo);
+    git::commit(&repo);
+    requests
+}
+
+#[test]
+fn resumes_partial_download() {
+    let p = project_with_deps();
+    let requests = serve_downloads();
+    assert_that(p.cargo_process("fetch"), execs().with_status(0));
+
+    let cache = t!(t!(fs::read_dir(paths::home().join(".cargo/registry/cache")))
+                       .next().unwrap()).path();
+    let dst = cache.join("bar-0.1.0.crate");
+    let mut contents = Vec::new();
+    t!(t!(File::open(&dst)).read_to_end(&mut contents));
+    t!(fs::remove_file(&dst));
+    t!(t!(File::create(cache.join("bar-0.1.0.crate.part")))
+           .write_all(&contents[..contents.len() / 2]));
+    requests.lock().unwrap().clear();
+
+    assert_that(p.cargo("fetch"),
+                execs().with_status(0).with_stderr_contains("\
+[DOWNLOADING] bar v0.1.0 ([..])
+"));
+    let mut resumed = Vec::new();
+    t!(t!(File::open(&dst)).read_to_end(&mut resumed));
+    assert_eq!(resumed, contents);
+    assert!(!cache.join("bar-0.1.0.crate.part").exists());
+
+    // Only the rest of the file was asked for.
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1, "{:?}", *requests);
+    let range = format!("range: bytes={}-", contents.len() / 2);
+    assert!(requests[0].iter().any(|h| h.to_lowercase() == range),
+            "{:?}", requests[0]);
+}
+
+#[test]
+fn archive_builds_offline() {
+    let p = project_with_deps();
+    assert_that(p.cargo_process("fetch").arg("--archive").arg("deps.tar.gz"),
+                execs().with_status(0).with_stderr_contains("\
+[ARCHIVED] 3 packages into `[..]deps.tar.gz`
+"));
+
+    // Nothing is left to download from.
+    t!(fs::remove_dir_all(paths::home().join(".cargo/registry")));
+    t!(fs::remove_dir_all(paths::root().join("registry")));
+
+    assert_that(p.cargo("build").arg("--offline")
+                 .arg("--dep-archive").arg("deps.tar.gz"),
+                execs().with_status(0).with_stderr_contains("\
+[UNPACKING] [..]deps.tar.gz
+"));
+
+    assert_that(p.cargo("build").arg("--offline")
+                 .arg("--dep-archive").arg("missing.tar.gz"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] the dependency archive `[..]missing.tar.gz` passed to --dep-archive does not exist
+"));
+}
+
+#[test]
+fn archive_rejects_crate_writing_outside_its_directory() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("evil", "0.1.0")
+            .extra_file("bar-0.1.0/src/lib.rs", "not rust")
+            .publish();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+
+            [dependencies]
+            bar = "0.1"
+            evil = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    assert_that(p.cargo_process("fetch").arg("--archive").arg("deps.tar.gz"),
+                execs().with_status(0));
+
+    assert_that(p.cargo("build").arg("--offline")
+                 .arg("--dep-archive").arg("deps.tar.gz"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] failed to unpack the dependency archive `[..]deps.tar.gz`
+").with_stderr_contains("\
+Caused by:
+  `[..]evil-0.1.0.crate` contains `bar-0.1.0` which is outside of `evil-0.1.0/`
+"));
+}
+
+#[test]
+fn archive_with_git_dependency() {
+    let git = git::new("dep", |project| {
+        project.file("Cargo.toml", r#"
+            [package]
+            name = "dep"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn dep() -> i32 { 1 }")
+        .file("src/inner.rs", "")
+    }).unwrap();
+    let p = project("foo")
+        .file("Cargo.toml", &format!(r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+
+            [dependencies]
+            dep = {{ git = '{}' }}
+        "#, git.url()))
+        .file("src/main.rs", r#"
+            extern crate dep;
+            fn main() { assert_eq!(dep::dep(), 1); }
+        "#);
+    assert_that(p.cargo_process("fetch").arg("--archive").arg("deps.tar.gz"),
+                execs().with_status(0).with_stderr_contains("\
+[ARCHIVED] 1 packages into `[..]deps.tar.gz`
+"));
+
+    // Nothing is left to fetch from.
+    t!(fs::remove_dir_all(paths::home().join(".cargo/git")));
+    t!(fs::remove_dir_all(git.root()));
+
+    assert_that(p.cargo("run").arg("--offline")
+                 .arg("--dep-archive").arg("deps.tar.gz"),
+                execs().with_status(0));
+}
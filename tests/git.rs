@@ -1826,3 +1826,217 @@ fn add_a_git_dep() {
 
     assert_that(p.cargo("build"), execs().with_status(0));
 }
+
+#[test]
+fn checkout_checksum_verified() {
+    let git = git::new("bar", |p| {
+        p.file("Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn bar() {}")
+    }).unwrap();
+
+    let p = project("foo")
+        .file("Cargo.toml", &format!(r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = {{ git = '{}' }}
+        "#, git.url()))
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+    let mut lockfile = String::new();
+    File::open(p.root().join("Cargo.lock")).unwrap()
+         .read_to_string(&mut lockfile).unwrap();
+    assert!(lockfile.contains("\"checksum bar 0.5.0 (git+"), "{}", lockfile);
+    assert!(!lockfile.contains("<none>"), "{}", lockfile);
+    let checkouts = paths::home().join(".cargo/git/checkouts");
+    let checkout = fs::read_dir(&checkouts).unwrap().next().unwrap().unwrap();
+    // The checksums of the files are cached apart from the checkout.
+    let hashes = paths::home().join(".cargo/git/file-hashes")
+                              .join(checkout.file_name()).join("master");
+    assert!(hashes.is_file());
+    assert!(!checkout.path().join("master/.git/cargo-file-hashes").exists());
+
+    // Lock files listing no checksum for it pick up the current one.
+    let start = lockfile.find("\" = \"").unwrap() + 5;
+    let end = start + lockfile[start..].find('"').unwrap();
+    let old = format!("{}<none>{}", &lockfile[..start], &lockfile[end..]);
+    File::create(p.root().join("Cargo.lock")).unwrap()
+         .write_all(old.as_bytes()).unwrap();
+    assert_that(p.cargo("build"), execs().with_status(0));
+
+    // Edit the checkout behind cargo's back.
+    File::create(checkout.path().join("master/src/lib.rs")).unwrap()
+         .write_all(b"pub fn bar() { panic!() }").unwrap();
+
+    assert_that(p.cargo("build"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] checksum for `bar v0.5.0 ([..])` changed between lock files
+
+the files checked out at the revision listed in the lock file differ from when
+the lock file was generated[..]"));
+}
+
+#[test]
+fn checkout_checksum_cache_disabled() {
+    let git = git::new("bar", |p| {
+        p.file("Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn bar() {}")
+    }).unwrap();
+
+    let p = project("foo")
+        .file("Cargo.toml", &format!(r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = {{ git = '{}' }}
+        "#, git.url()))
+        .file("src/lib.rs", "")
+        .file(".cargo/config", r#"
+            [net]
+            git-checksum-cache = false
+        "#);
+
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+    assert!(!paths::home().join(".cargo/git/file-hashes").exists());
+}
+
+#[test]
+fn rev_fetched_without_history() {
+    let bar = git::new("bar", |project| {
+        project.file("Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn bar() -> i32 { 1 }")
+    }).unwrap();
+
+    let repo = git2::Repository::open(&bar.root()).unwrap();
+    File::create(&bar.root().join("src/lib.rs")).unwrap().write_all(br#"
+        pub fn bar() -> i32 { 2 }
+    "#).unwrap();
+    git::add(&repo);
+    let rev = git::commit(&repo);
+
+    let p = project("foo")
+        .file("Cargo.toml", &format!(r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            git = '{}'
+            rev = "{}"
+        "#, bar.url(), rev))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { assert_eq!(bar::bar(), 2); }
+        "#);
+
+    assert_that(p.cargo_process("run"), execs().with_status(0));
+
+    let dbs = fs::read_dir(paths::home().join(".cargo/git/db")).unwrap()
+                 .map(|e| e.unwrap().path()).collect::<Vec<_>>();
+    assert_eq!(dbs.len(), 1);
+    assert!(dbs[0].to_str().unwrap().ends_with("-shallow"), "{:?}", dbs);
+    assert_that(&dbs[0].join("shallow"), existing_file());
+
+    // Without shallow fetches the whole repository is fetched.
+    assert_that(p.cargo("update").env("CARGO_NET_GIT_SHALLOW", "false"),
+                execs().with_status(0));
+    assert_that(p.cargo("run").env("CARGO_NET_GIT_SHALLOW", "false"),
+                execs().with_status(0));
+    assert_that(&paths::home().join(".cargo/git/db").join(
+                    dbs[0].file_name().unwrap().to_str().unwrap()
+                          .trim_right_matches("-shallow")).join("HEAD"),
+                existing_file());
+}
+
+#[test]
+fn sparse_checkout() {
+    let repo = git::new("monorepo", |project| {
+        project.file("a/Cargo.toml", r#"
+            [package]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+            build = "../build/a.rs"
+            readme = "../README.md"
+
+            [dependencies]
+            c = { path = "../c" }
+        "#)
+        .file("a/src/lib.rs", "extern crate c; pub fn a() -> i32 { c::c() }")
+        .file("a/src/helper.rs", "")
+        .file("build/a.rs", "fn main() {}")
+        .file("README.md", "shared")
+        .file("b/Cargo.toml", r#"
+            [package]
+            name = "b"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("b/src/lib.rs", "")
+        .file("b/huge.txt", "not needed")
+        .file("c/Cargo.toml", r#"
+            [package]
+            name = "c"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("c/src/lib.rs", "pub fn c() -> i32 { 1 }")
+        .file("c/data.txt", "needed")
+    }).unwrap();
+
+    let p = project("foo")
+        .file("Cargo.toml", &format!(r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            a = {{ git = '{}' }}
+        "#, repo.url()))
+        .file("src/main.rs", r#"
+            extern crate a;
+            fn main() { assert_eq!(a::a(), 1); }
+        "#);
+
+    assert_that(p.cargo_process("run").env("CARGO_NET_GIT_SPARSE_CHECKOUT", "true"),
+                execs().with_status(0));
+
+    let checkouts = paths::home().join(".cargo/git/checkouts");
+    let checkout = fs::read_dir(&checkouts).unwrap().next().unwrap().unwrap()
+                      .path().join("master");
+    assert_that(&checkout.join("a/src/helper.rs"), existing_file());
+    assert_that(&checkout.join("c/data.txt"), existing_file());
+    assert_that(&checkout.join("b/Cargo.toml"), existing_file());
+    assert!(!checkout.join("b/huge.txt").exists());
+    assert_that(&checkout.join("build/a.rs"), existing_file());
+    assert_that(&checkout.join("README.md"), existing_file());
+    assert!(!checkout.join(".cargo-sparse").exists());
+
+    // Without it the whole repository is checked out again.
+    assert_that(p.cargo("build"), execs().with_status(0));
+    assert_that(&checkout.join("b/huge.txt"), existing_file());
+}
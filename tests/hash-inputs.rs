@@ -0,0 +1,100 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+
+use cargotest::sleep_ms;
+use cargotest::support::{project, execs, ProjectBuilder};
+use hamcrest::assert_that;
+
+fn hash(p: &ProjectBuilder, sources: bool) -> String {
+    let mut cmd = p.cargo("hash-inputs");
+    if sources {
+        cmd.arg("--sources");
+    }
+    let output = cmd.exec_with_output().unwrap();
+    let hash = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(hash.trim().len(), 64, "not a hash: {}", hash);
+    hash
+}
+
+fn foo(name: &str) -> ProjectBuilder {
+    project(name)
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "")
+}
+
+#[test]
+fn stable_across_locations() {
+    let a = foo("a");
+    let b = foo("b");
+    assert_that(a.cargo_process("generate-lockfile"), execs().with_status(0));
+    assert_that(b.cargo_process("generate-lockfile"), execs().with_status(0));
+
+    assert_eq!(hash(&a, false), hash(&a, false));
+    assert_eq!(hash(&a, false), hash(&b, false));
+    assert_eq!(hash(&a, true), hash(&b, true));
+}
+
+#[test]
+fn covers_path_dependencies() {
+    let p = foo("foo");
+    assert_that(p.cargo_process("generate-lockfile"), execs().with_status(0));
+    let before = hash(&p, false);
+    let before_sources = hash(&p, true);
+
+    // Source files only matter with --sources
+    sleep_ms(1000);
+    File::create(p.root().join("bar/src/lib.rs")).unwrap()
+        .write_all(b"pub fn bar() {}").unwrap();
+    assert_eq!(before, hash(&p, false));
+    assert!(before_sources != hash(&p, true));
+
+    File::create(p.root().join("bar/Cargo.toml")).unwrap().write_all(br#"
+        [package]
+        name = "bar"
+        version = "0.0.1"
+        authors = []
+        description = "changed"
+    "#).unwrap();
+    assert!(before != hash(&p, false));
+}
+
+#[test]
+fn covers_config() {
+    let p = foo("foo");
+    assert_that(p.cargo_process("generate-lockfile"), execs().with_status(0));
+    let before = hash(&p, false);
+
+    fs::create_dir_all(p.root().join(".cargo")).unwrap();
+    File::create(p.root().join(".cargo/config")).unwrap().write_all(br#"
+        [build]
+        rustflags = ["-C", "opt-level=1"]
+    "#).unwrap();
+    assert!(before != hash(&p, false));
+}
+
+#[test]
+fn requires_lockfile() {
+    let p = foo("foo");
+    assert_that(p.cargo_process("hash-inputs"),
+                execs().with_status(101).with_stderr("\
+[ERROR] a Cargo.lock must exist for this command
+"));
+}
@@ -0,0 +1,94 @@
+#[macro_use]
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+#[test]
+fn describe_crate() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    Package::new("bar", "0.1.0").rust_version("1.10").publish();
+    assert_that(p.cargo("generate-lockfile"), execs().with_status(0));
+    Package::new("bar", "0.1.1").yanked(true).publish();
+    Package::new("bar", "0.2.0")
+            .dep("baz", "0.1")
+            .feature("default", &["fast"])
+            .feature("fast", &[])
+            .feature_description("fast", "Trades memory for speed")
+            .publish();
+
+    assert_that(p.cargo("info").arg("bar"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[WARNING] the owners of `bar` couldn't be listed: [..]")
+                       .with_stdout("\
+bar 0.2.0
+versions:
+    0.2.0
+    0.1.1 (yanked)
+    0.1.0 (rust 1.10)
+features:
+    default = [\"fast\"]
+    fast = []  # Trades memory for speed
+dependencies:
+    baz = \"[..]0.1\"
+locked: 0.1.0
+"));
+
+    assert_that(p.cargo("info").arg("bar@0.1"),
+                execs().with_status(0)
+                       .with_stdout_contains("bar 0.1.0"));
+    assert_that(p.cargo("info").arg("bar@0.1").arg("--format").arg("json"),
+                execs().with_status(0)
+                       .with_json(r#"
+                        {
+                            "name": "bar",
+                            "version": "0.1.0",
+                            "versions": [
+                                {"num": "0.2.0", "yanked": false,
+                                 "rust_version": null, "published": null},
+                                {"num": "0.1.1", "yanked": true,
+                                 "rust_version": null, "published": null},
+                                {"num": "0.1.0", "yanked": false,
+                                 "rust_version": "1.10", "published": null}
+                            ],
+                            "features": {},
+                            "feature_descriptions": {},
+                            "dependencies": [],
+                            "owners": null,
+                            "locked": ["0.1.0"]
+                        }
+                       "#));
+}
+
+#[test]
+fn unknown_crate() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    Package::new("bar", "0.1.0").publish();
+
+    assert_that(p.cargo_process("info").arg("baz"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] no crate named `baz` was found in `[..]`"));
+}
@@ -314,6 +314,47 @@ fn mercurial_autodetect() {
     assert_that(&paths::root().join(".hgignore"), existing_file());
 }
 
+#[test]
+fn jj_autodetect_colocated_with_git() {
+    fs::create_dir(&paths::root().join(".git")).unwrap();
+    fs::create_dir(&paths::root().join(".jj")).unwrap();
+
+    assert_that(cargo_process("init").arg("--lib")
+                                    .env("USER", "foo"),
+                execs().with_status(0));
+
+    assert_that(&paths::root().join("Cargo.toml"), existing_file());
+    assert_that(&paths::root().join(".gitignore"), existing_file());
+}
+
+#[test]
+fn fossil_autodetect() {
+    File::create(&paths::root().join(".fslckout")).unwrap();
+
+    assert_that(cargo_process("init").arg("--lib")
+                                    .env("USER", "foo"),
+                execs().with_status(0));
+
+    assert_that(&paths::root().join("Cargo.toml"), existing_file());
+    assert_that(&paths::root().join(".gitignore"), is_not(existing_file()));
+    let mut contents = String::new();
+    File::open(&paths::root().join(".fossil-settings/ignore-glob")).unwrap()
+         .read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "target\nCargo.lock\n");
+}
+
+#[test]
+fn multiple_vcs_autodetected() {
+    fs::create_dir(&paths::root().join(".git")).unwrap();
+    fs::create_dir(&paths::root().join(".hg")).unwrap();
+
+    assert_that(cargo_process("init").arg("--lib")
+                                    .env("USER", "foo"),
+                execs().with_status(101).with_stderr("\
+[ERROR] multiple version control directories found (.git, .hg) and the ignore \
+file can't be filled in as a result, specify --vcs to override detection"));
+}
+
 #[test]
 fn gitignore_appended_not_replaced() {
     fs::create_dir(&paths::root().join(".git")).unwrap();
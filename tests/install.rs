@@ -1,4 +1,5 @@
 extern crate cargo;
+#[macro_use]
 extern crate cargotest;
 extern crate hamcrest;
 
@@ -6,11 +7,11 @@ use std::fs::{self, File};
 use std::io::prelude::*;
 
 use cargo::util::ProcessBuilder;
-use cargotest::install::{cargo_home, has_installed_exe};
+use cargotest::install::{cargo_home, has_installed_exe, prebuilt_archive};
 use cargotest::support::git;
 use cargotest::support::paths;
 use cargotest::support::registry::Package;
-use cargotest::support::{project, execs};
+use cargotest::support::{project, execs, path2url};
 use hamcrest::{assert_that, is_not};
 
 fn cargo_process(s: &str) -> ProcessBuilder {
@@ -591,6 +592,182 @@ foo v0.0.1:
 "));
 }
 
+#[test]
+fn list_outdated_and_upgrade_all() {
+    pkg("foo", "0.0.1");
+    Package::new("bar", "0.2.1")
+        .feature("loud", &[])
+        .file("src/main.rs", "fn main() {}")
+        .publish();
+
+    assert_that(cargo_process("install").arg("bar").arg("--vers").arg("=0.2.1")
+                                        .arg("--features").arg("loud"),
+                execs().with_status(0));
+    assert_that(cargo_process("install").arg("foo"),
+                execs().with_status(0));
+    assert_that(cargo_process("install").arg("--list").arg("--outdated"),
+                execs().with_status(0).with_stdout(""));
+
+    // The new version only builds with the feature it was installed with.
+    Package::new("bar", "0.2.2")
+        .feature("loud", &[])
+        .file("src/main.rs", "#[cfg(feature = \"loud\")] fn main() {}")
+        .publish();
+    assert_that(cargo_process("install").arg("--list").arg("--outdated"),
+                execs().with_status(0).with_stdout("\
+bar v0.2.1: v0.2.2 is available
+"));
+
+    assert_that(cargo_process("install").arg("--upgrade-all"),
+                execs().with_status(0).with_stderr_contains("\
+[UPGRADING] bar v0.2.1 to v0.2.2
+"));
+    assert_that(cargo_process("install").arg("--list"),
+                execs().with_status(0).with_stdout("\
+bar v0.2.2:
+    bar[..]
+foo v0.0.1:
+    foo[..]
+"));
+    assert_that(cargo_process("install").arg("--list").arg("--outdated"),
+                execs().with_status(0).with_stdout(""));
+    assert_that(cargo_process("install").arg("--upgrade-all"),
+                execs().with_status(0).with_stderr_contains("\
+[FINISHED] all installed packages are up to date
+"));
+}
+
+#[test]
+fn reinstall_from_metadata() {
+    Package::new("bar", "0.2.1")
+        .feature("loud", &[])
+        .file("src/main.rs", "#[cfg(feature = \"loud\")] fn main() {}")
+        .publish();
+    assert_that(cargo_process("install").arg("bar").arg("--features").arg("loud"),
+                execs().with_status(0));
+
+    // A newer version isn't picked, and the features are the same.
+    Package::new("bar", "0.2.2")
+        .file("src/main.rs", "fn main() {}")
+        .publish();
+    let other = paths::root().join("other");
+    let mut listing = String::new();
+    t!(t!(File::open(cargo_home().join(".crates2.json"))).read_to_string(&mut listing));
+    assert!(listing.contains("\"rustc\":\"rustc "));
+    assert_that(cargo_process("install").arg("--from-metadata")
+                                        .arg(cargo_home().join(".crates2.json"))
+                                        .arg("--root").arg(&other),
+                execs().with_status(0));
+    assert_that(&other, has_installed_exe("bar"));
+    assert_that(cargo_process("install").arg("--list").arg("--root").arg(&other),
+                execs().with_status(0).with_stdout("\
+bar v0.2.1:
+    bar[..]
+"));
+    let mut reinstalled = String::new();
+    t!(t!(File::open(other.join(".crates2.json"))).read_to_string(&mut reinstalled));
+    assert!(reinstalled.contains("\"features\":[\"loud\"]"));
+}
+
+#[test]
+fn several_crates_share_dependencies() {
+    Package::new("common", "0.1.0")
+        .file("src/lib.rs", "pub fn common() {}")
+        .publish();
+    for name in &["foo", "bar"] {
+        Package::new(name, "0.1.0")
+            .dep("common", "0.1")
+            .file("src/main.rs", "
+                extern crate common;
+                fn main() { common::common() }
+            ")
+            .publish();
+    }
+
+    assert_that(cargo_process("install").arg("foo").arg("bar").arg("--vers").arg("0.1.0"),
+                execs().with_status(101).with_stderr("\
+[ERROR] --vers can't be used when installing several crates
+"));
+
+    let output = cargo_process("install").arg("foo").arg("bar")
+                                         .exec_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("Compiling common v0.1.0").count(), 1);
+    assert_eq!(stderr.matches("be sure to add").count(), 1);
+    assert_that(cargo_home(), has_installed_exe("foo"));
+    assert_that(cargo_home(), has_installed_exe("bar"));
+    assert_that(cargo_process("install").arg("--list"),
+                execs().with_status(0).with_stdout("\
+bar v0.1.0:
+    bar[..]
+foo v0.1.0:
+    foo[..]
+"));
+}
+
+#[test]
+fn several_crates_resolved_separately() {
+    Package::new("pinned", "0.1.0").publish();
+    Package::new("pinned", "0.1.1").publish();
+    Package::new("foo", "0.1.0")
+        .dep("pinned", "=0.1.0")
+        .file("src/main.rs", "extern crate pinned; fn main() {}")
+        .publish();
+    Package::new("bar", "0.1.0")
+        .dep("pinned", "=0.1.1")
+        .file("src/main.rs", "extern crate pinned; fn main() {}")
+        .publish();
+
+    assert_that(cargo_process("install").arg("foo").arg("bar"),
+                execs().with_status(0));
+    assert_that(cargo_home(), has_installed_exe("foo"));
+    assert_that(cargo_home(), has_installed_exe("bar"));
+}
+
+#[test]
+fn several_crates_built_concurrently() {
+    let markers = paths::root().join("markers");
+    fs::create_dir_all(&markers).unwrap();
+
+    // The build script of each crate waits for the other one's to start, so
+    // installing them only succeeds if they're built at the same time.
+    let mut repo = git::repo(&paths::root().join("repo"));
+    for &(name, other) in &[("foo", "bar"), ("bar", "foo")] {
+        repo = repo.file(&format!("{}/Cargo.toml", name), &format!(r#"
+                [package]
+                name = "{}"
+                version = "0.1.0"
+                authors = []
+                build = "build.rs"
+            "#, name))
+            .file(&format!("{}/build.rs", name), &format!(r#"
+                use std::fs::File;
+                use std::path::Path;
+                use std::thread;
+                use std::time::Duration;
+
+                fn main() {{
+                    File::create({:?}).unwrap();
+                    for _ in 0..600 {{
+                        if Path::new({:?}).exists() {{
+                            return
+                        }}
+                        thread::sleep(Duration::from_millis(100));
+                    }}
+                    panic!("`{}` wasn't built at the same time");
+                }}
+            "#, markers.join(name), markers.join(other), other))
+            .file(&format!("{}/src/main.rs", name), "fn main() {}");
+    }
+    repo.build();
+
+    assert_that(cargo_process("install").arg("--git").arg(repo.url().to_string())
+                                        .arg("foo").arg("bar").arg("-j2"),
+                execs().with_status(0));
+    assert_that(cargo_home(), has_installed_exe("foo"));
+    assert_that(cargo_home(), has_installed_exe("bar"));
+}
+
 #[test]
 fn uninstall_pkg_does_not_exist() {
     assert_that(cargo_process("uninstall").arg("foo"),
@@ -793,3 +970,48 @@ fn readonly_dir() {
                 execs().with_status(0));
     assert_that(cargo_home(), has_installed_exe("foo"));
 }
+
+#[test]
+fn prebuilt() {
+    let archive = paths::root().join("foo-prebuilt.tar.gz");
+    let sha256 = prebuilt_archive(&archive, &["foo"], "prebuilt");
+    Package::new("foo", "0.0.1")
+        .file("src/main.rs", "fn main() {}")
+        .prebuilt(&cargotest::rustc_host(), path2url(archive).as_str(), &sha256)
+        .publish();
+
+    assert_that(cargo_process("install").arg("foo").arg("--prebuilt"),
+                execs().with_status(0).with_stderr(&format!("\
+[UPDATING] registry `[..]`
+[DOWNLOADING] foo v0.0.1 (registry [..])
+[DOWNLOADING] foo v0.0.1 (registry [..]) prebuilt for `[..]`
+[INSTALLING] {home}[..]bin[..]foo[..]
+warning: be sure to add `[..]` to your PATH to be able to run the installed binaries
+",
+        home = cargo_home().display())));
+    let mut contents = String::new();
+    let exe = if cfg!(windows) { "foo.exe" } else { "foo" };
+    t!(t!(File::open(cargo_home().join("bin").join(exe)))
+           .read_to_string(&mut contents));
+    assert_eq!(contents, "prebuilt");
+}
+
+#[test]
+fn prebuilt_with_wrong_checksum_is_built() {
+    let archive = paths::root().join("foo-prebuilt.tar.gz");
+    prebuilt_archive(&archive, &["foo"], "prebuilt");
+    Package::new("foo", "0.0.1")
+        .file("src/main.rs", "fn main() {}")
+        .prebuilt(&cargotest::rustc_host(), path2url(archive).as_str(),
+                  "0000000000000000000000000000000000000000000000000000000000000000")
+        .publish();
+
+    assert_that(cargo_process("install").arg("foo").arg("--prebuilt"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] the prebuilt binaries of `foo v0.0.1 ([..])` can't be used, building \
+them instead: the archive downloaded from `[..]` doesn't match the `sha256` \
+checksum of the manifest
+[COMPILING] foo v0.0.1
+"));
+    assert_that(cargo_home(), has_installed_exe("foo"));
+}
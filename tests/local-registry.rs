@@ -348,3 +348,59 @@ unable to verify that `foo v0.0.1` is the same as when the lockfile was generate
 
 "));
 }
+
+#[test]
+fn built_from_lockfile() {
+    Package::new("foo", "0.0.1")
+            .file("src/lib.rs", "pub fn foo() {}")
+            .publish();
+    Package::new("bar", "0.1.0")
+            .dep("foo", "0.0.1")
+            .file("src/lib.rs", "extern crate foo; pub fn bar() { foo::foo() }")
+            .publish();
+
+    let p = project("local")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "local"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "extern crate bar; pub fn local() { bar::bar() }");
+
+    assert_that(p.cargo_process("local-registry").arg("../registry-local"),
+                execs().with_status(0).with_stderr_contains("\
+[ADDED] 2 packages to the local registry `[..]registry-local`
+"));
+    let local = paths::root().join("registry-local");
+    assert!(local.join("foo-0.0.1.crate").is_file());
+    assert!(local.join("bar-0.1.0.crate").is_file());
+    assert!(local.join("index/3/f/foo").is_file());
+    assert!(local.join("index/3/b/bar").is_file());
+
+    // Adding them again doesn't duplicate their index entries.
+    assert_that(p.cargo("local-registry").arg("../registry-local"),
+                execs().with_status(0));
+    let mut contents = String::new();
+    t!(t!(File::open(local.join("index/3/f/foo"))).read_to_string(&mut contents));
+    assert_eq!(contents.lines().count(), 1);
+
+    // The local registry is all that's needed to build.
+    paths::home().join(".cargo/registry").rm_rf();
+    paths::root().join("registry").rm_rf();
+    t!(fs::create_dir(p.root().join(".cargo")));
+    t!(t!(File::create(p.root().join(".cargo/config"))).write_all(br#"
+        [source.crates-io]
+        replace-with = 'local'
+
+        [source.local]
+        local-registry = '../registry-local'
+    "#));
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr_contains("\
+[UNPACKING] bar v0.1.0 ([..])
+"));
+}
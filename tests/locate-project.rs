@@ -0,0 +1,103 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+#[test]
+fn simple() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("locate-project"),
+                execs().with_status(0).with_json(r#"
+    {
+        "root": "[..]foo[..]Cargo.toml"
+    }
+"#));
+}
+
+#[test]
+fn workspace_root_and_owning_member() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [workspace]
+
+            [dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("locate-project")
+                 .arg("--workspace")
+                 .arg("--file").arg("src/lib.rs")
+                 .cwd(p.root().join("bar")),
+                execs().with_status(0).with_json(r#"
+    {
+        "root": "[..]foo[..]bar[..]Cargo.toml",
+        "workspace_root": "[..]foo[..]Cargo.toml",
+        "member": {
+            "name": "bar",
+            "root": "[..]foo[..]bar[..]Cargo.toml"
+        }
+    }
+"#));
+}
+
+#[test]
+fn file_outside_workspace() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("locate-project")
+                 .arg("--manifest-path").arg(p.root().join("Cargo.toml"))
+                 .arg("--file").arg("/"),
+                execs().with_status(1)
+                       .with_stderr("\
+[ERROR] `/` does not belong to any member of the workspace
+"));
+}
+
+#[test]
+fn config_files() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file(".cargo/config", "");
+
+    assert_that(p.cargo_process("locate-project").arg("--config"),
+                execs().with_status(0).with_stdout_contains("\
+{\"root\":\"[..]Cargo.toml\",\"config_files\":[\"[..]foo[..].cargo[..]config\"[..]]}
+"));
+}
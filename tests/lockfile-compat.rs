@@ -1,5 +1,6 @@
 #[macro_use]
 extern crate cargotest;
+extern crate git2;
 extern crate hamcrest;
 
 use cargotest::support::git;
@@ -205,10 +206,10 @@ this could be indicative of a few possible situations:
 "));
 }
 
-// If the checksum is listed in the lockfile yet we cannot calculate it (e.g.
-// git dependencies as of today), then make sure we choke.
+// Git dependencies are checksummed from the files checked out, so a listed
+// checksum which doesn't match them makes us choke.
 #[test]
-fn listed_checksum_bad_if_we_cannot_compute() {
+fn listed_git_checksum_must_match() {
     let git = git::new("foo", |p| {
         p.file("Cargo.toml", r#"
             [project]
@@ -218,6 +219,8 @@ fn listed_checksum_bad_if_we_cannot_compute() {
         "#)
         .file("src/lib.rs", "")
     }).unwrap();
+    let repo = git2::Repository::open(&git.root()).unwrap();
+    let rev = repo.revparse_single("HEAD").unwrap().id();
 
     let p = project("bar")
         .file("Cargo.toml", &format!(r#"
@@ -235,30 +238,30 @@ fn listed_checksum_bad_if_we_cannot_compute() {
 name = "bar"
 version = "0.0.1"
 dependencies = [
- "foo 0.1.0 (git+{0})"
+ "foo 0.1.0 (git+{0}#{1})"
 ]
 
 [[package]]
 name = "foo"
 version = "0.1.0"
-source = "git+{0}"
+source = "git+{0}#{1}"
 
 [metadata]
-"checksum foo 0.1.0 (git+{0})" = "checksum"
-"#, git.url()));
+"checksum foo 0.1.0 (git+{0}#{1})" = "checksum"
+"#, git.url(), rev));
 
     p.build();
 
     assert_that(p.cargo("fetch"),
                 execs().with_status(101).with_stderr("\
 [UPDATING] git repository `[..]`
-error: checksum for `foo v0.1.0 ([..])` could not be calculated, but a \
-checksum is listed in the existing lock file[..]
+error: checksum for `foo v0.1.0 ([..])` changed between lock files
 
-this could be indicative of a few possible situations:
+the files checked out at the revision listed in the lock file differ from when
+the lock file was generated, which could be indicative of a few possible
+situations:
 
-    * the source `[..]` supports checksums,
-      but was replaced with one that doesn't
+    * the checkout of the repository in the Cargo home directory was modified
     * the lock file is corrupt
 
 unable to verify that `foo v0.1.0 ([..])` is the same as when the lockfile was generated
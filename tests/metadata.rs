@@ -1,7 +1,12 @@
 extern crate cargotest;
 extern crate hamcrest;
 
+use std::fs::{self, File};
+use std::io::prelude::*;
+
 use hamcrest::assert_that;
+use cargotest::rustc_host;
+use cargotest::support::paths;
 use cargotest::support::registry::Package;
 use cargotest::support::{project, execs, basic_bin_manifest, basic_lib_manifest, main_file};
 
@@ -29,6 +34,8 @@ fn cargo_metadata_simple() {
                     }
                 ],
                 "features": {},
+                "feature_docs": {},
+                "feature_metadata": {},
                 "manifest_path": "[..]Cargo.toml"
             }
         ],
@@ -42,6 +49,8 @@ fn cargo_metadata_simple() {
             ],
             "root": "foo 0.5.0 (path+file:[..]foo)"
         },
+        "platforms": null,
+        "downloads": [],
         "version": 1
     }"#));
 }
@@ -76,6 +85,8 @@ fn cargo_metadata_with_deps_and_version() {
             {
                 "dependencies": [],
                 "features": {},
+                "feature_docs": {},
+                "feature_metadata": {},
                 "id": "baz 0.0.1 (registry+[..])",
                 "manifest_path": "[..]Cargo.toml",
                 "name": "baz",
@@ -105,6 +116,8 @@ fn cargo_metadata_with_deps_and_version() {
                     }
                 ],
                 "features": {},
+                "feature_docs": {},
+                "feature_metadata": {},
                 "id": "bar 0.0.1 (registry+[..])",
                 "manifest_path": "[..]Cargo.toml",
                 "name": "bar",
@@ -134,6 +147,8 @@ fn cargo_metadata_with_deps_and_version() {
                     }
                 ],
                 "features": {},
+                "feature_docs": {},
+                "feature_metadata": {},
                 "id": "foo 0.5.0 (path+file:[..]foo)",
                 "manifest_path": "[..]Cargo.toml",
                 "name": "foo",
@@ -172,6 +187,21 @@ fn cargo_metadata_with_deps_and_version() {
             ],
             "root": "foo 0.5.0 (path+file:[..]foo)"
         },
+        "platforms": null,
+        "downloads": [
+            {
+                "checksum": "[..]",
+                "id": "bar 0.0.1 (registry+[..])",
+                "size": null,
+                "url": "file://[..]/dl/bar/0.0.1/download"
+            },
+            {
+                "checksum": "[..]",
+                "id": "baz 0.0.1 (registry+[..])",
+                "size": null,
+                "url": "file://[..]/dl/baz/0.0.1/download"
+            }
+        ],
         "version": 1
     }"#));
 }
@@ -206,6 +236,8 @@ fn workspace_metadata() {
                     }
                 ],
                 "features": {},
+                "feature_docs": {},
+                "feature_metadata": {},
                 "manifest_path": "[..]bar[..]Cargo.toml"
             },
             {
@@ -222,6 +254,8 @@ fn workspace_metadata() {
                     }
                 ],
                 "features": {},
+                "feature_docs": {},
+                "feature_metadata": {},
                 "manifest_path": "[..]baz[..]Cargo.toml"
             }
         ],
@@ -239,6 +273,8 @@ fn workspace_metadata() {
             ],
             "root": null
         },
+        "platforms": null,
+        "downloads": [],
         "version": 1
     }"#))
 }
@@ -273,6 +309,8 @@ fn workspace_metadata_no_deps() {
                     }
                 ],
                 "features": {},
+                "feature_docs": {},
+                "feature_metadata": {},
                 "manifest_path": "[..]bar[..]Cargo.toml"
             },
             {
@@ -289,15 +327,199 @@ fn workspace_metadata_no_deps() {
                     }
                 ],
                 "features": {},
+                "feature_docs": {},
+                "feature_metadata": {},
                 "manifest_path": "[..]baz[..]Cargo.toml"
             }
         ],
         "workspace_members": ["baz 0.5.0 (path+file:[..]baz)", "bar 0.5.0 (path+file:[..]bar)"],
         "resolve": null,
+        "platforms": null,
+        "downloads": null,
         "version": 1
     }"#))
 }
 
+#[test]
+fn cargo_metadata_feature_docs() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [features]
+            ## Makes things faster.
+            ##
+            ## Requires nightly.
+            fast = []
+            # Not documentation.
+            slow = []
+            ## Separated from its feature.
+
+            "quoted-name" = ["fast"]
+            ## The default set.
+            default = [
+                "fast",
+            ]
+
+            [dependencies]
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("metadata").arg("--no-deps"),
+                execs().with_status(0).with_json(r#"
+    {
+        "packages": [
+            {
+                "name": "foo",
+                "version": "0.5.0",
+                "id": "foo[..]",
+                "source": null,
+                "dependencies": [],
+                "targets": [
+                    {
+                        "kind": [
+                            "lib"
+                        ],
+                        "name": "foo",
+                        "src_path": "src[..]lib.rs"
+                    }
+                ],
+                "features": {
+                    "default": ["fast"],
+                    "fast": [],
+                    "quoted-name": ["fast"],
+                    "slow": []
+                },
+                "feature_docs": {
+                    "default": "The default set.",
+                    "fast": "Makes things faster.\n\nRequires nightly."
+                },
+                "feature_metadata": {},
+                "manifest_path": "[..]Cargo.toml"
+            }
+        ],
+        "workspace_members": ["foo 0.5.0 (path+file:[..]foo)"],
+        "resolve": null,
+        "platforms": null,
+        "downloads": null,
+        "version": 1
+    }"#));
+}
+
+#[test]
+fn cargo_metadata_feature_metadata() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [features]
+            native-tls = []
+            rustls = []
+
+            [features.metadata.rustls]
+            description = "Use rustls rather than the platform's TLS library"
+            stability = "unstable"
+            docs = "https://example.com/tls.html"
+
+            [features.metadata.native-tls]
+            stability = "stable"
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("metadata").arg("--no-deps"),
+                execs().with_status(0).with_json(r#"
+    {
+        "packages": [
+            {
+                "name": "foo",
+                "version": "0.5.0",
+                "id": "foo[..]",
+                "source": null,
+                "dependencies": [],
+                "targets": [
+                    {
+                        "kind": [
+                            "lib"
+                        ],
+                        "name": "foo",
+                        "src_path": "src[..]lib.rs"
+                    }
+                ],
+                "features": {
+                    "native-tls": [],
+                    "rustls": []
+                },
+                "feature_docs": {},
+                "feature_metadata": {
+                    "native-tls": {
+                        "description": null,
+                        "stability": "stable",
+                        "docs": null
+                    },
+                    "rustls": {
+                        "description": "Use rustls rather than the platform's TLS library",
+                        "stability": "unstable",
+                        "docs": "https://example.com/tls.html"
+                    }
+                },
+                "manifest_path": "[..]Cargo.toml"
+            }
+        ],
+        "workspace_members": ["foo 0.5.0 (path+file:[..]foo)"],
+        "resolve": null,
+        "platforms": null,
+        "downloads": null,
+        "version": 1
+    }"#));
+
+    let p = p.file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [features]
+            rustls = []
+
+            [features.metadata.rustls]
+            stability = "experimental"
+        "#);
+
+    assert_that(p.cargo_process("metadata").arg("--no-deps"),
+                execs().with_status(101).with_stderr("\
+[ERROR] failed to parse manifest at `[..]`
+
+Caused by:
+  invalid stability `experimental` of feature `rustls`, expected `stable`, \
+`unstable` or `deprecated`
+"));
+}
+
+#[test]
+fn cargo_metadata_feature_named_metadata() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [features]
+            metadata = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("metadata").arg("--no-deps"),
+                execs().with_status(0).with_stdout_contains("\
+[..]\"features\":{\"metadata\":[]}[..]"));
+}
+
 #[test]
 fn cargo_metadata_with_invalid_manifest() {
     let p = project("foo")
@@ -326,10 +548,14 @@ const MANIFEST_OUTPUT: &'static str=
             "src_path":"src[..]foo.rs"
         }],
         "features":{},
+        "feature_docs":{},
+        "feature_metadata":{},
         "manifest_path":"[..]Cargo.toml"
     }],
     "workspace_members": [ "foo 0.5.0 (path+file:[..]foo)" ],
     "resolve": null,
+    "platforms": null,
+    "downloads": null,
     "version": 1
 }"#;
 
@@ -411,3 +637,157 @@ fn carg_metadata_bad_version() {
                 execs().with_status(101)
     .with_stderr("[ERROR] metadata version 2 not supported, only 1 is currently supported"));
 }
+
+#[test]
+fn cargo_metadata_cached() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+    assert_that(p.cargo_process("metadata").arg("--no-deps"),
+                execs().with_status(0).with_json(MANIFEST_OUTPUT));
+
+    // Tamper with the cached output to tell it apart from a fresh one
+    let dir = paths::home().join(".cargo/metadata-cache");
+    let files = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path())
+                   .collect::<Vec<_>>();
+    assert_eq!(files.len(), 1);
+    let mut contents = String::new();
+    File::open(&files[0]).unwrap().read_to_string(&mut contents).unwrap();
+    File::create(&files[0]).unwrap()
+         .write_all(contents.replace("0.5.0", "0.6.0").as_bytes()).unwrap();
+    assert_that(p.cargo("metadata").arg("--no-deps"),
+                execs().with_status(0)
+                       .with_stdout_contains("[..]\"version\":\"0.6.0\"[..]"));
+
+    // Changing the manifest invalidates it
+    File::create(p.root().join("Cargo.toml")).unwrap()
+         .write_all(basic_bin_manifest("foo").replace("0.5.0", "0.7.0")
+                                             .as_bytes()).unwrap();
+    assert_that(p.cargo("metadata").arg("--no-deps"),
+                execs().with_status(0)
+                       .with_stdout_contains("[..]\"version\":\"0.7.0\"[..]"));
+
+    // So does adding a file a target is inferred from
+    fs::create_dir(p.root().join("examples")).unwrap();
+    File::create(p.root().join("examples/ex.rs")).unwrap()
+         .write_all(b"fn main() {}").unwrap();
+    assert_that(p.cargo("metadata").arg("--no-deps"),
+                execs().with_status(0)
+                       .with_stdout_contains("[..]\"name\":\"ex\"[..]"));
+}
+
+#[test]
+fn cargo_metadata_filter_platform() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar", features = ["fast"] }
+
+            [target.not-a-real-triple.dependencies]
+            baz = { path = "baz" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [features]
+            default = ["std"]
+            std = []
+            fast = []
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", r#"
+            [project]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "");
+
+    assert_that(p.cargo_process("metadata")
+                 .arg("--filter-platform").arg(rustc_host()),
+                execs().with_status(0).with_stdout_contains(&format!("\
+[..]\"platforms\":[{{\"target\":\"{}\",\"nodes\":[\
+{{\"id\":\"bar 0.0.1 (path+file://[..])\",\"features\":[\"default\",\"fast\",\"std\"],\
+\"dependencies\":[]}},\
+{{\"id\":\"foo 0.5.0 (path+file://[..])\",\"features\":[],\
+\"dependencies\":[\"bar 0.0.1 (path+file://[..])\"]}}]}}][..]", rustc_host())));
+}
+
+#[test]
+fn cargo_metadata_ndjson() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+
+    assert_that(p.cargo_process("metadata").arg("--no-deps")
+                 .arg("--format").arg("ndjson"),
+                execs().with_status(0).with_stdout("\
+{\"package\":{\"name\":\"foo\",\"version\":\"0.5.0\",[..]}}
+{\"workspace_members\":[\"foo 0.5.0 (path+file://[..])\"],\"resolve\":null,\
+\"platforms\":null,\"downloads\":null,\"version\":1}
+"));
+}
+
+#[test]
+fn cargo_metadata_cbor() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+    p.build();
+
+    let output = p.cargo("metadata").arg("--no-deps")
+                  .arg("--format").arg("cbor")
+                  .exec_with_output().unwrap();
+    // A map of the six top-level fields, the first of them `packages`
+    assert_eq!(output.stdout[0], 0xa6);
+    assert_eq!(&output.stdout[1..10], b"\x68packages");
+    // ... and the last `version`, with no trailing newline
+    assert!(output.stdout.ends_with(b"\x67version\x01"));
+    // `resolve` is null without dependencies
+    let resolve = output.stdout.windows(9).position(|w| w == b"\x67resolve\xf6");
+    assert!(resolve.is_some());
+}
+
+#[test]
+fn cargo_metadata_bad_format() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+
+    assert_that(p.cargo_process("metadata").arg("--format").arg("yaml"),
+                execs().with_status(101).with_stderr("\
+[ERROR] unknown metadata format `yaml`, expected one of `json`, `ndjson` or \
+`cbor`
+"));
+}
+
+#[test]
+fn cargo_metadata_downloads() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "");
+    let cksum = Package::new("bar", "0.1.0").size(1234).publish();
+
+    assert_that(p.cargo_process("metadata").arg("-q"),
+                execs().with_status(0).with_stdout_contains(&format!("\
+[..]\"downloads\":[{{\"id\":\"bar 0.1.0 (registry+file://[..])\",\
+\"checksum\":\"{}\",\"size\":1234,\
+\"url\":\"file://[..]/dl/bar/0.1.0/download\"}}][..]", cksum)));
+}
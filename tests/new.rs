@@ -1,5 +1,6 @@
 extern crate cargo;
 extern crate cargotest;
+extern crate git2;
 extern crate hamcrest;
 extern crate tempdir;
 
@@ -9,7 +10,7 @@ use std::env;
 
 use cargo::util::ProcessBuilder;
 use cargotest::process;
-use cargotest::support::{execs, paths};
+use cargotest::support::{execs, git, paths, path2url};
 use hamcrest::{assert_that, existing_file, existing_dir, is_not};
 use tempdir::TempDir;
 
@@ -384,3 +385,114 @@ Usage:
     cargo new [..]
 "));
 }
+
+#[test]
+fn template_from_directory() {
+    let template = paths::root().join("template");
+    fs::create_dir_all(template.join("src")).unwrap();
+    File::create(template.join("Cargo.toml")).unwrap().write_all(br#"
+        [package]
+        name = "{{name}}"
+        version = "0.1.0"
+        authors = ["{{authors}}"]
+        license = "{{license}}"
+    "#).unwrap();
+    File::create(template.join("src/main.rs")).unwrap().write_all(br#"
+        fn main() { println!("{{name}}") }
+    "#).unwrap();
+    File::create(template.join("cargo-template.toml")).unwrap().write_all(br#"
+        [template]
+        license = "MIT"
+    "#).unwrap();
+
+    assert_that(cargo_process("new").arg("foo").arg("--template").arg("template")
+                                    .arg("--vcs").arg("none").env("USER", "foo"),
+                execs().with_status(0).with_stderr("\
+[CREATED] `foo` project from template `template`
+"));
+
+    let mut contents = String::new();
+    File::open(&paths::root().join("foo/Cargo.toml")).unwrap()
+        .read_to_string(&mut contents).unwrap();
+    assert!(contents.contains(r#"name = "foo""#));
+    assert!(contents.contains(r#"license = "MIT""#));
+    assert_that(&paths::root().join("foo/cargo-template.toml"), is_not(existing_file()));
+
+    assert_that(cargo_process("run").cwd(&paths::root().join("foo")),
+                execs().with_status(0).with_stdout("foo\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn template_from_git_with_hooks() {
+    let template = paths::root().join("template");
+    git::repo(&template)
+        .file("Cargo.toml", r#"
+            [package]
+            name = "{{name}}"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("cargo-template.toml", r#"
+            [template]
+            hooks = [["touch", "hooked"]]
+        "#)
+        .build();
+
+    assert_that(cargo_process("new").arg("bar")
+                                    .arg("--template").arg(path2url(template).to_string())
+                                    .arg("--vcs").arg("none").env("USER", "foo"),
+                execs().with_status(0));
+    assert_that(&paths::root().join("bar/hooked"), existing_file());
+    assert_that(&paths::root().join("bar/.git"), is_not(existing_dir()));
+    assert_that(cargo_process("build").cwd(&paths::root().join("bar")),
+                execs().with_status(0));
+}
+
+#[test]
+fn template_from_git_head_or_reference() {
+    let template = paths::root().join("template");
+    git::repo(&template)
+        .file("Cargo.toml", r#"
+            [package]
+            name = "{{name}}"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "// master")
+        .build();
+
+    // HEAD points to `main` rather than `master`.
+    let repo = git2::Repository::open(&template).unwrap();
+    let head = repo.head().unwrap().target().unwrap();
+    repo.branch("main", &repo.find_commit(head).unwrap(), false).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    File::create(template.join("src/lib.rs")).unwrap().write_all(b"// main").unwrap();
+    git::add(&repo);
+    git::commit(&repo);
+    let url = path2url(template).to_string();
+
+    assert_that(cargo_process("new").arg("foo").arg("--template").arg(&url)
+                                    .arg("--vcs").arg("none").env("USER", "foo"),
+                execs().with_status(0));
+    let mut contents = String::new();
+    File::open(paths::root().join("foo/src/lib.rs")).unwrap()
+        .read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "// main");
+
+    assert_that(cargo_process("new").arg("bar").arg("--template").arg(&url)
+                                    .arg("--branch").arg("master")
+                                    .arg("--vcs").arg("none").env("USER", "foo"),
+                execs().with_status(0));
+    let mut contents = String::new();
+    File::open(paths::root().join("bar/src/lib.rs")).unwrap()
+        .read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "// master");
+
+    assert_that(cargo_process("new").arg("baz").arg("--branch").arg("master")
+                                    .arg("--vcs").arg("none").env("USER", "foo"),
+                execs().with_status(101).with_stderr("\
+[ERROR] --branch, --tag and --rev can only be used with --template
+"));
+}
@@ -0,0 +1,101 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+#[test]
+fn outdated_direct_and_transitive() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    Package::new("baz", "0.1.0").publish();
+    Package::new("bar", "0.1.0").dep("baz", "0.1").publish();
+    assert_that(p.cargo("generate-lockfile"), execs().with_status(0));
+
+    Package::new("bar", "0.1.2").dep("baz", "0.1").publish();
+    Package::new("bar", "0.2.0").publish();
+    Package::new("baz", "1.0.0").publish();
+
+    assert_that(p.cargo("outdated"),
+                execs().with_status(0)
+                       .with_stdout("\
+foo 0.0.1
+    name  locked  compatible  latest  kind
+    bar   0.1.0   0.1.2       0.2.0   direct
+    baz   0.1.0   -           1.0.0   transitive
+"));
+    assert_that(p.cargo("outdated").arg("--direct").arg("--deny")
+                 .arg("--format").arg("json"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] 1 dependencies are outdated")
+                       .with_json(r#"
+                        {
+                            "members": [{
+                                "name": "foo",
+                                "version": "0.0.1",
+                                "outdated": [{
+                                    "name": "bar",
+                                    "version": "0.1.0",
+                                    "source": "registry+[..]",
+                                    "compatible": "0.1.2",
+                                    "latest": "0.2.0",
+                                    "direct": true
+                                }]
+                            }]
+                        }
+                       "#));
+
+    // Nothing is updated by the report.
+    assert!(p.read_lockfile().contains("name = \"bar\"\nversion = \"0.1.0\""));
+}
+
+#[test]
+fn outdated_direct_also_reached_transitively() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+            baz = "0.1"
+            qux = "0.1"
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    // Each of them is also a dependency of another one.
+    Package::new("qux", "0.1.0").publish();
+    Package::new("baz", "0.1.0").dep("qux", "0.1").publish();
+    Package::new("bar", "0.1.0").dep("baz", "0.1").dep("qux", "0.1").publish();
+    assert_that(p.cargo("generate-lockfile"), execs().with_status(0));
+
+    Package::new("bar", "0.2.0").publish();
+    Package::new("baz", "0.2.0").publish();
+    Package::new("qux", "0.2.0").publish();
+
+    assert_that(p.cargo("outdated"),
+                execs().with_status(0)
+                       .with_stdout("\
+foo 0.0.1
+    name  locked  compatible  latest  kind
+    bar   0.1.0   -           0.2.0   direct
+    baz   0.1.0   -           0.2.0   direct
+    qux   0.1.0   -           0.2.0   direct
+"));
+}
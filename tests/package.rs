@@ -6,7 +6,7 @@ extern crate hamcrest;
 extern crate tar;
 extern crate cargo;
 
-use std::fs::{File, OpenOptions};
+use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 
@@ -578,3 +578,186 @@ Cargo.toml
 to proceed despite this, pass the `--allow-dirty` flag
 "));
 }
+
+#[test]
+fn dirty_policy_warn() {
+    git::repo(&paths::root().join("foo"))
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            license = "MIT"
+            description = "foo"
+            documentation = "foo"
+            homepage = "foo"
+            repository = "foo"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .build();
+    t!(fs::create_dir_all(paths::root().join(".cargo")));
+    t!(t!(File::create(paths::root().join(".cargo/config"))).write_all(br#"
+        [package]
+        dirty = "warn"
+    "#));
+
+    let p = project("foo");
+    let manifest_path = p.root().join("Cargo.toml");
+    let mut manifest = t!(OpenOptions::new().append(true).open(manifest_path));
+    t!(writeln!(manifest, ""));
+
+    assert_that(p.cargo("package").arg("--no-verify"),
+                execs().with_status(0)
+                       .with_stderr("\
+[WARNING] 1 dirty files found in the working directory:
+
+Cargo.toml
+
+[PACKAGING] foo v0.0.1 ([..])
+"));
+}
+
+#[test]
+fn vcs_info_recorded() {
+    git::repo(&paths::root().join("foo"))
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            license = "MIT"
+            description = "foo"
+            documentation = "foo"
+            homepage = "foo"
+            repository = "foo"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .build();
+    let repo = t!(git2::Repository::open(&paths::root().join("foo")));
+    let head = t!(t!(repo.head()).peel_to_commit()).id();
+
+    let p = project("foo");
+    assert_that(p.cargo("package").arg("--no-verify"),
+                execs().with_status(0));
+
+    let f = t!(File::open(&p.root().join("target/package/foo-0.0.1.crate")));
+    let mut rdr = t!(GzDecoder::new(f));
+    let mut contents = Vec::new();
+    t!(rdr.read_to_end(&mut contents));
+    let mut ar = Archive::new(&contents[..]);
+    let mut info = None;
+    for f in t!(ar.entries()) {
+        let mut f = t!(f);
+        if &*f.header().path_bytes() == b"foo-0.0.1/.cargo_vcs_info.json" {
+            let mut s = String::new();
+            t!(f.read_to_string(&mut s));
+            info = Some(s);
+        }
+    }
+    assert_eq!(info.unwrap(), format!("{{\"vcs\":\"git\",\"revision\":\"{}\",\
+                                       \"dirty\":false,\"dirty_files\":[]}}",
+                                      head));
+}
+
+#[test]
+fn additional_formats() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "foo"
+            documentation = "foo"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("package").arg("--no-verify")
+                 .arg("--format").arg("tar.zst")
+                 .arg("--format").arg("dir"),
+                execs().with_status(0).with_stderr(&format!("\
+[PACKAGING] foo v0.0.1 ({dir})
+[EXPORTING] foo v0.0.1 ({dir}) as tar.zst
+[EXPORTING] foo v0.0.1 ({dir}) as dir
+",
+        dir = p.url())));
+
+    let package = p.root().join("target/package");
+    for file in ["foo-0.0.1.crate", "foo-0.0.1.crate.sha256",
+                 "foo-0.0.1.tar.zst", "foo-0.0.1.tar.zst.sha256",
+                 "export/foo-0.0.1/Cargo.toml",
+                 "export/foo-0.0.1/src/main.rs",
+                 "export/foo-0.0.1/.cargo-checksum.json"].iter() {
+        assert_that(&package.join(file), existing_file());
+    }
+
+    let mut checksum = String::new();
+    File::open(package.join("foo-0.0.1.crate.sha256")).unwrap()
+        .read_to_string(&mut checksum).unwrap();
+    assert!(checksum.ends_with("  foo-0.0.1.crate\n"), "{}", checksum);
+
+    let mut checksum = String::new();
+    File::open(package.join("export/foo-0.0.1/.cargo-checksum.json")).unwrap()
+        .read_to_string(&mut checksum).unwrap();
+    assert!(checksum.contains("\"src/main.rs\""), "{}", checksum);
+    assert!(checksum.contains("\"package\""), "{}", checksum);
+}
+
+#[test]
+fn unknown_format() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("package").arg("--format").arg("zip"),
+                execs().with_status(101).with_stderr("\
+[ERROR] unknown package format `zip`, expected one of `tar.zst` or `dir`
+"));
+}
+
+#[cfg(unix)]
+#[test]
+fn failing_vcs_tool_with_allow_dirty() {
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            license = "MIT"
+            description = "foo"
+            documentation = "foo"
+            homepage = "foo"
+            repository = "foo"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bin/hg", r#"#!/bin/sh
+            if [ "$1" = root ]; then pwd; exit 0; fi
+            echo "abort: repository is corrupted" >&2
+            exit 255
+        "#);
+    p.build();
+    let hg = p.root().join("bin/hg");
+    t!(fs::set_permissions(&hg, fs::Permissions::from_mode(0o755)));
+    let mut path = vec![p.root().join("bin")];
+    path.extend(env::split_paths(&env::var_os("PATH").unwrap()));
+    let path = t!(env::join_paths(path));
+
+    assert_that(p.cargo("package").arg("--no-verify").env("PATH", &path),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] failed to determine whether the hg checkout at `[..]` has uncommitted \
+changes, pass `--allow-dirty` to package it anyway"));
+    assert_that(p.cargo("package").arg("--no-verify").arg("--allow-dirty")
+                 .env("PATH", &path),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[WARNING] could not determine the state of the hg checkout at `[..]`, its \
+revision will not be recorded: [..]"));
+}
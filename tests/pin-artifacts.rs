@@ -0,0 +1,61 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::env;
+
+use cargotest::support::{project, execs, main_file, basic_bin_manifest};
+use hamcrest::{assert_that, existing_file, existing_dir, is_not};
+
+#[test]
+fn pinned_artifacts_survive_clean() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+
+    assert_that(p.cargo_process("build").arg("--release"),
+                execs().with_status(0));
+    assert_that(p.cargo("pin-artifacts").arg("-p").arg("foo")
+                 .arg("--profile").arg("release"),
+                execs().with_status(0).with_stderr("\
+[PINNED] 1 artifact(s) of foo v0.5.0 ([..])
+"));
+
+    assert_that(p.cargo("clean"), execs().with_status(0));
+    assert_that(&p.root().join("target/release"), is_not(existing_dir()));
+    let pinned = p.root().join("target/pinned/foo-0.5.0/release")
+                  .join(&format!("foo{}", env::consts::EXE_SUFFIX));
+    assert_that(&pinned, existing_file());
+
+    assert_that(p.cargo("pin-artifacts").arg("--list"),
+                execs().with_status(0).with_stdout(&format!("\
+foo-0.5.0/release/foo{}
+", env::consts::EXE_SUFFIX)));
+
+    assert_that(p.cargo("pin-artifacts").arg("--unpin")
+                 .arg("--profile").arg("release"),
+                execs().with_status(0).with_stderr("\
+[UNPINNED] foo v0.5.0 ([..])
+"));
+    assert_that(&p.root().join("target/pinned"), existing_dir());
+    assert_that(&pinned, is_not(existing_file()));
+    assert_that(p.cargo("pin-artifacts").arg("--list"),
+                execs().with_status(0).with_stdout(""));
+}
+
+#[test]
+fn pin_requires_built_artifacts() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/foo.rs", &main_file(r#""i am foo""#, &[]));
+
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+    assert_that(p.cargo("pin-artifacts").arg("--profile").arg("release"),
+                execs().with_status(101).with_stderr("\
+[ERROR] no artifacts of `foo v0.5.0 ([..])` built with the `release` profile \
+were found, build them before pinning them
+"));
+    assert_that(p.cargo("pin-artifacts").arg("--profile").arg("bench"),
+                execs().with_status(101).with_stderr("\
+[ERROR] unknown profile `bench`, expected `dev` or `release`
+"));
+}
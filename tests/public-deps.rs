@@ -0,0 +1,119 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+fn bar(version: &str) -> String {
+    format!(r#"
+        [package]
+        name = "bar"
+        version = "{}"
+        authors = []
+    "#, version)
+}
+
+#[test]
+fn incompatible_public_dependencies() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            mid = { path = "mid" }
+            bar = { path = "bar2" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("mid/Cargo.toml", r#"
+            [package]
+            name = "mid"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = { path = "../bar1", public = true }
+        "#)
+        .file("mid/src/lib.rs", "")
+        .file("bar1/Cargo.toml", &bar("1.0.0"))
+        .file("bar1/src/lib.rs", "")
+        .file("bar2/Cargo.toml", &bar("2.0.0"))
+        .file("bar2/src/lib.rs", "");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] `foo v0.1.0 ([..])` can use the types of both `bar v[..]` and \
+`bar v[..]`, which aren't interchangeable:
+  `bar v[..]` [..]
+  `bar v[..]` [..]
+a dependency declared `public` exposes its types to whatever depends on the \
+package declaring it
+").with_stderr_contains("  `bar v1.0.0 ([..])` through `mid v0.1.0 ([..])`")
+  .with_stderr_contains("  `bar v2.0.0 ([..])` directly"));
+}
+
+#[test]
+fn private_dependencies_are_not_checked() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            mid = { path = "mid" }
+            bar = { path = "bar2" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("mid/Cargo.toml", r#"
+            [package]
+            name = "mid"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = { path = "../bar1" }
+        "#)
+        .file("mid/src/lib.rs", "")
+        .file("bar1/Cargo.toml", &bar("1.0.0"))
+        .file("bar1/src/lib.rs", "")
+        .file("bar2/Cargo.toml", &bar("2.0.0"))
+        .file("bar2/src/lib.rs", "");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] [..]
+[COMPILING] [..]
+[COMPILING] [..]
+[COMPILING] [..]
+[FINISHED] [..]
+"));
+}
+
+#[test]
+fn dev_dependencies_cannot_be_public() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dev-dependencies]
+            bar = { path = "bar", public = true }
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &bar("1.0.0"))
+        .file("bar/src/lib.rs", "");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr("\
+[ERROR] failed to parse manifest at `[..]`
+
+Caused by:
+  dev-dependency `bar` can't be `public`, only normal dependencies can
+"));
+}
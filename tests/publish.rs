@@ -2,6 +2,7 @@
 extern crate cargotest;
 extern crate flate2;
 extern crate hamcrest;
+extern crate rustc_serialize;
 extern crate tar;
 extern crate url;
 
@@ -15,6 +16,8 @@ use cargotest::support::paths;
 use cargotest::support::{project, execs};
 use flate2::read::GzDecoder;
 use hamcrest::assert_that;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::json::Json;
 use tar::Archive;
 use url::Url;
 
@@ -93,6 +96,140 @@ See [..]
     }
 }
 
+/// The JSON metadata uploaded along with the last published package.
+fn uploaded_metadata() -> Json {
+    let mut f = t!(File::open(&upload_path().join("api/v1/crates/new")));
+    let mut sz = [0; 4];
+    assert_eq!(t!(f.read(&mut sz)), 4);
+    let sz = ((sz[0] as u32) <<  0) |
+             ((sz[1] as u32) <<  8) |
+             ((sz[2] as u32) << 16) |
+             ((sz[3] as u32) << 24);
+    let mut json = vec![0; sz as usize];
+    t!(f.read_exact(&mut json));
+    Json::from_str(&String::from_utf8(json).unwrap()).unwrap()
+}
+
+#[test]
+fn public_dependency() {
+    setup();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "foo"
+
+            [dependencies]
+            bar = { path = "bar", version = "0.0.1", public = true }
+            baz = { path = "baz", version = "0.0.1" }
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", r#"
+            [project]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "");
+
+    assert_that(p.cargo_process("publish").arg("--no-verify")
+                 .arg("--host").arg(registry().to_string()),
+                execs().with_status(0));
+
+    let metadata = uploaded_metadata();
+    let deps = metadata.find("deps").unwrap().as_array().unwrap();
+    let public = |name: &str| {
+        deps.iter().find(|d| d.find("name").unwrap().as_string() == Some(name))
+            .unwrap().find("public").unwrap().as_boolean().unwrap()
+    };
+    assert!(public("bar"));
+    assert!(!public("baz"));
+}
+
+#[test]
+fn feature_conflicts() {
+    setup();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "foo"
+
+            [features]
+            native-tls = []
+            rustls = []
+
+            [features.conflicts]
+            native-tls = ["rustls"]
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("publish").arg("--no-verify")
+                 .arg("--host").arg(registry().to_string()),
+                execs().with_status(0));
+
+    let metadata = uploaded_metadata();
+    assert_eq!(metadata.find("conflicts").unwrap().to_string(),
+               r#"[["native-tls","rustls"]]"#);
+}
+
+#[test]
+fn feature_descriptions_and_rust_version() {
+    setup();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "foo"
+            rust-version = "1.12"
+
+            [features]
+            ## Trades memory for speed.
+            ##
+            ## Uses lookup tables.
+            fast = []
+            tls = []
+            plain = []
+
+            [features.metadata.tls]
+            description = "Encrypts connections"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("publish").arg("--no-verify")
+                 .arg("--host").arg(registry().to_string()),
+                execs().with_status(0));
+
+    let metadata = uploaded_metadata();
+    assert_eq!(metadata.find("rust_version").unwrap().to_string(),
+               r#""1.12""#);
+    let descriptions = metadata.find("feature_descriptions").unwrap();
+    assert_eq!(descriptions.find("fast").unwrap().to_string(),
+               r#""Trades memory for speed.""#);
+    assert_eq!(descriptions.find("tls").unwrap().to_string(),
+               r#""Encrypts connections""#);
+    assert!(descriptions.find("plain").is_none());
+}
+
 #[test]
 fn git_deps() {
     setup();
@@ -371,3 +508,94 @@ See [..]
     // Ensure the API request wasn't actually made
     assert!(!upload_path().join("api/v1/crates/new").exists());
 }
+
+fn provenance_project() -> cargotest::support::ProjectBuilder {
+    project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+            description = "foo"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+}
+
+#[cfg(unix)]
+#[test]
+fn provenance() {
+    setup();
+    let config = paths::root().join(".cargo/config");
+    t!(t!(fs::OpenOptions::new().append(true).open(&config)).write_all(br#"
+        [provenance]
+        builder = "https://ci.example.com/runners/1"
+        command = ["cp", "{input}", "{output}"]
+    "#));
+    t!(fs::create_dir_all(&upload_path().join("api/v1/crates/foo/0.0.1")));
+
+    let p = provenance_project();
+    assert_that(p.cargo_process("publish").arg("--no-verify").arg("--provenance")
+                 .arg("--host").arg(registry().to_string()),
+                execs().with_status(0).with_stderr(&format!("\
+[UPDATING] registry `{reg}`
+[WARNING] manifest has no documentation, [..]
+See [..]
+[PACKAGING] foo v0.0.1 ({dir})
+   Attesting foo v0.0.1 ({dir})
+[UPLOADING] foo v0.0.1 ({dir})
+[UPLOADING] provenance attestation of foo v0.0.1 ({dir})
+",
+        dir = p.url(),
+        reg = registry())));
+
+    let mut contents = String::new();
+    t!(t!(File::open(&upload_path().join("api/v1/crates/foo/0.0.1/provenance")))
+        .read_to_string(&mut contents));
+    let envelope = Json::from_str(&contents).unwrap();
+    let payload = envelope.find("payload").unwrap().as_string().unwrap()
+                          .from_base64().unwrap();
+
+    // With `cp` as the signing command, the signature is the data signed.
+    let sig = envelope.find("signatures").unwrap()[0].find("sig").unwrap()
+                      .as_string().unwrap().from_base64().unwrap();
+    let mut pae = format!("DSSEv1 28 application/vnd.in-toto+json {} ",
+                          payload.len()).into_bytes();
+    pae.extend_from_slice(&payload);
+    assert_eq!(sig, pae);
+
+    let statement = Json::from_str(&String::from_utf8(payload).unwrap()).unwrap();
+    let subject = &statement.find("subject").unwrap()[0];
+    assert_eq!(subject.find("name").unwrap().as_string(), Some("foo-0.0.1.crate"));
+    let mut checksum = String::new();
+    t!(t!(File::open(p.root().join("target/package/foo-0.0.1.crate.sha256")))
+        .read_to_string(&mut checksum));
+    assert_eq!(subject.find_path(&["digest", "sha256"]).unwrap().as_string(),
+               Some(&checksum[..64]));
+    assert_eq!(statement.find_path(&["predicate", "runDetails", "builder", "id"])
+                        .unwrap().as_string(),
+               Some("https://ci.example.com/runners/1"));
+
+    let predicate = statement.find("predicate").unwrap();
+    let files = predicate.find_path(&["buildDefinition", "resolvedDependencies"])
+                         .unwrap().as_array().unwrap().iter().filter_map(|d| {
+        d.find("name").and_then(|n| n.as_string())
+    }).collect::<Vec<_>>();
+    assert_eq!(files, ["Cargo.toml", "src/main.rs"]);
+    assert!(p.root().join("target/package/foo-0.0.1.crate.intoto.json").exists());
+}
+
+#[test]
+fn provenance_needs_configuration() {
+    setup();
+
+    let p = provenance_project();
+    assert_that(p.cargo_process("publish").arg("--no-verify").arg("--provenance")
+                 .arg("--host").arg(registry().to_string()),
+                execs().with_status(101).with_stderr("\
+[UPDATING] registry `[..]`
+[ERROR] provenance attestations record who built the package, set \
+`provenance.builder` in the configuration
+"));
+    assert!(!upload_path().join("api/v1/crates/new").exists());
+}
@@ -22,6 +22,8 @@ fn read_manifest_output() -> String {
         "src_path":"src[..]foo.rs"
     }],
     "features":{},
+    "feature_docs":{},
+    "feature_metadata":{},
     "manifest_path":"[..]Cargo.toml"
 }"#)
 }
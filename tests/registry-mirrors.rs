@@ -0,0 +1,110 @@
+#[macro_use]
+extern crate cargotest;
+extern crate hamcrest;
+extern crate url;
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+use cargotest::support::git::repo;
+use cargotest::support::paths;
+use cargotest::support::registry::{self, Package};
+use cargotest::support::{project, execs, ProjectBuilder};
+use hamcrest::assert_that;
+use url::Url;
+
+fn mirror_path() -> PathBuf { paths::root().join("mirror") }
+fn mirror() -> Url { Url::from_file_path(&*mirror_path()).ok().unwrap() }
+fn mirror_dl_path() -> PathBuf { paths::root().join("mirror-dl") }
+fn mirror_dl() -> Url { Url::from_file_path(&*mirror_dl_path()).ok().unwrap() }
+
+/// Makes the test registry the default one, with `mirrors`.
+fn setup(mirrors: &[Url]) {
+    let mirrors = mirrors.iter().map(|m| format!("'{}'", m))
+                         .collect::<Vec<_>>().join(", ");
+    t!(fs::create_dir_all(paths::root().join(".cargo")));
+    t!(t!(File::create(paths::root().join(".cargo/config"))).write_all(format!(r#"
+        [registry]
+        index = '{}'
+        mirrors = [{}]
+    "#, registry::registry(), mirrors).as_bytes()));
+}
+
+fn project_using_bar() -> ProjectBuilder {
+    project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+}
+
+#[test]
+fn unreachable_mirror() {
+    let missing = Url::from_file_path(&*paths::root().join("missing")).unwrap();
+    setup(&[missing.clone()]);
+    Package::new("bar", "0.1.0").publish();
+    let p = project_using_bar();
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0).with_stderr_contains(&format!("\
+[WARNING] failed to fetch `{}`, trying `{}` instead: [..]
+", missing, registry::registry())));
+}
+
+#[test]
+fn mirror_checksum_differs() {
+    setup(&[mirror()]);
+    Package::new("bar", "0.1.0").publish();
+
+    // The mirror serves another `.crate` file under the same version, with a
+    // checksum to match.
+    let tarball = b"not the real bar";
+    let dl = mirror_dl_path().join("bar/0.1.0");
+    t!(fs::create_dir_all(&dl));
+    t!(t!(File::create(dl.join("download"))).write_all(tarball));
+    repo(&mirror_path())
+        .file("config.json", &format!(r#"{{"dl":"{0}","api":"{0}"}}"#,
+                                      mirror_dl()))
+        .file("3/b/bar", &format!(r#"{{"name":"bar","vers":"0.1.0","deps":[],"features":{{}},"cksum":"{}","yanked":false}}"#,
+                                  registry::cksum(tarball)))
+        .build();
+
+    let p = project_using_bar();
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr_contains(&format!("\
+[..]the checksum of `bar v0.1.0 ([..])` in the index of a mirror doesn't \
+match the one in the index of `{}`
+", registry::registry())));
+}
+
+#[test]
+fn mirror_version_missing_from_registry() {
+    setup(&[mirror()]);
+    Package::new("bar", "0.1.0").publish();
+
+    // The mirror serves a version which the registry never published.
+    let tarball = b"not a real bar";
+    let dl = mirror_dl_path().join("bar/0.1.1");
+    t!(fs::create_dir_all(&dl));
+    t!(t!(File::create(dl.join("download"))).write_all(tarball));
+    repo(&mirror_path())
+        .file("config.json", &format!(r#"{{"dl":"{0}","api":"{0}"}}"#,
+                                      mirror_dl()))
+        .file("3/b/bar", &format!(r#"{{"name":"bar","vers":"0.1.1","deps":[],"features":{{}},"cksum":"{}","yanked":false}}"#,
+                                  registry::cksum(tarball)))
+        .build();
+
+    let p = project_using_bar();
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr_contains(&format!("\
+[..]`bar v0.1.1 ([..])` is in the index of a mirror but not in the index of \
+`{}`
+", registry::registry())));
+}
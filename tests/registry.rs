@@ -1147,3 +1147,865 @@ Caused by:
   attempting to make an HTTP request, but --frozen was specified
 "));
 }
+
+#[test]
+fn offline_disallows_network() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            foo = "*"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    assert_that(p.cargo("build").arg("--offline"),
+                execs().with_status(101).with_stderr("\
+error: failed to load source for a dependency on `foo`
+
+Caused by:
+  Unable to update registry [..]
+
+Caused by:
+  attempting to reach `file://[..]`, but --offline was specified and it is \
+not allowed by --allow-net
+"));
+}
+
+#[test]
+fn offline_allow_net_registry() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            foo = "*"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("foo", "0.1.0").publish();
+
+    assert_that(p.cargo("build")
+                 .arg("--offline")
+                 .arg("--allow-net").arg("registry:dummy-registry"),
+                execs().with_status(0).with_stderr(&format!("\
+[UPDATING] registry `{reg}`
+[DOWNLOADING] foo v0.1.0 (registry file://[..])
+[COMPILING] foo v0.1.0
+[COMPILING] bar v0.5.0 ({dir})
+[FINISHED] [..]
+",
+        dir = p.url(),
+        reg = registry::registry())));
+}
+
+#[test]
+fn offline_allow_net_unrelated() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            foo = "*"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    assert_that(p.cargo("build")
+                 .arg("--offline")
+                 .arg("--allow-net").arg("git:https://example.com/"),
+                execs().with_status(101).with_stderr_contains("\
+[..]but --offline was specified and it is not allowed by --allow-net
+"));
+}
+
+#[test]
+fn offline_allow_net_git_whole_components() {
+    let allowed = git::repo(&paths::root().join("org"))
+        .file("Cargo.toml", r#"
+            [project]
+            name = "a"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    allowed.build();
+    let other = git::repo(&paths::root().join("org-other"))
+        .file("Cargo.toml", r#"
+            [project]
+            name = "b"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    other.build();
+
+    let p = project("foo")
+        .file("Cargo.toml", &format!(r#"
+            [project]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            a = {{ git = '{}' }}
+            b = {{ git = '{}' }}
+        "#, allowed.url(), other.url()))
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    // `org` doesn't also allow its sibling `org-other`.
+    assert_that(p.cargo("build")
+                 .arg("--offline")
+                 .arg("--allow-net").arg(format!("git:{}", allowed.url())),
+                execs().with_status(101).with_stderr_contains("\
+[..]attempting to reach `file://[..]org-other[..]`, but --offline was \
+specified and it is not allowed by --allow-net
+"));
+}
+
+#[test]
+fn allow_net_requires_offline() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    assert_that(p.cargo("build").arg("--allow-net").arg("registry:crates-io"),
+                execs().with_status(101).with_stderr("\
+error: --allow-net can only be used together with --offline
+"));
+}
+
+#[test]
+fn invalid_allow_net() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    assert_that(p.cargo("build").arg("--offline").arg("--allow-net").arg("foo"),
+                execs().with_status(101).with_stderr("\
+error: invalid network exception `foo`, expected `registry:NAME` or `git:URL`
+"));
+}
+
+#[test]
+fn update_from_advisories() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+            baz = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("advisories.toml", r#"
+            [[advisory]]
+            id = "TEST-0001"
+            package = "bar"
+            versions = ["< 0.1.1"]
+        "#);
+    p.build();
+
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+
+    Package::new("bar", "0.1.1").publish();
+    Package::new("baz", "0.1.1").publish();
+    assert_that(p.cargo("update").arg("--from-advisories").arg("advisories.toml"),
+                execs().with_status(0).with_stderr("\
+[UPDATING] registry `[..]`
+[UPDATING] bar v0.1.0 -> v0.1.1
+"));
+
+    assert_that(p.cargo("update").arg("--from-advisories").arg("advisories.toml"),
+                execs().with_status(0).with_stderr("\
+     Checked no locked packages are affected by advisories
+"));
+}
+
+#[test]
+fn update_from_advisories_updates_dependents() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            mid = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("advisories.toml", r#"
+            [[advisory]]
+            package = "bar"
+            versions = ["< 0.1.1"]
+        "#);
+    p.build();
+
+    Package::new("bar", "0.1.0").publish();
+    Package::new("mid", "0.1.0").dep("bar", "=0.1.0").publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+
+    Package::new("bar", "0.1.1").publish();
+    Package::new("mid", "0.1.1").dep("bar", "0.1").publish();
+    assert_that(p.cargo("update").arg("--from-advisories").arg("advisories.toml"),
+                execs().with_status(0)
+                       .with_stderr_contains("[UPDATING] bar v0.1.0 -> v0.1.1")
+                       .with_stderr_contains("[UPDATING] mid v0.1.0 -> v0.1.1"));
+}
+
+#[test]
+fn update_from_advisories_no_escape() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "=0.1.0"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("advisories.toml", r#"
+            [[advisory]]
+            id = "TEST-0001"
+            package = "bar"
+            versions = ["< 0.1.1"]
+        "#);
+    p.build();
+
+    Package::new("bar", "0.1.0").publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+
+    Package::new("bar", "0.1.1").publish();
+    assert_that(p.cargo("update").arg("--from-advisories").arg("advisories.toml"),
+                execs().with_status(101).with_stderr("\
+[UPDATING] registry `[..]`
+[ERROR] no compatible update escapes all advisories:
+
+  `bar v0.1.0` is affected by `TEST-0001` (versions `< 0.1.1`), and no \
+version outside of that range is accepted by `foo v0.0.1 ([..])`
+"));
+}
+
+#[test]
+fn damaged_unpacked_source_is_unpacked_again() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() { bar::bar(); }");
+
+    Package::new("bar", "0.0.1")
+            .file("src/lib.rs", "pub fn bar() {}")
+            .publish();
+
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    let src = paths::home().join(".cargo/registry/src");
+    for dir in fs::read_dir(&src).unwrap() {
+        let lib = dir.unwrap().path().join("bar-0.0.1/src/lib.rs");
+        File::create(&lib).unwrap().write_all(b"pub fn broken(").unwrap();
+    }
+
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] the unpacked source of `bar v0.0.1` in `[..]bar-0.0.1` was modified \
+or is incomplete, unpacking it again
+"));
+
+    // Removing a file is noticed as well
+    for dir in fs::read_dir(&src).unwrap() {
+        fs::remove_file(dir.unwrap().path().join("bar-0.0.1/src/lib.rs")).unwrap();
+    }
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] the unpacked source of `bar v0.0.1` in `[..]bar-0.0.1` was modified \
+or is incomplete, unpacking it again
+"));
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr("\
+[FINISHED] [..]
+"));
+}
+
+#[cfg(unix)]
+#[test]
+fn identical_unpacked_files_are_shared() {
+    use std::os::unix::fs::MetadataExt;
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.0.1"
+            baz = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    Package::new("bar", "0.0.1")
+            .file("src/lib.rs", "pub fn same() {}")
+            .publish();
+    Package::new("baz", "0.0.1")
+            .file("src/lib.rs", "pub fn same() {}")
+            .publish();
+
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    let src = paths::home().join(".cargo/registry/src");
+    let dir = fs::read_dir(&src).unwrap().next().unwrap().unwrap().path();
+    let bar = fs::metadata(dir.join("bar-0.0.1/src/lib.rs")).unwrap();
+    let baz = fs::metadata(dir.join("baz-0.0.1/src/lib.rs")).unwrap();
+    assert_eq!(bar.ino(), baz.ino());
+
+    // Modifying it through one package damages both, and they both get their
+    // own intact copy back.
+    File::create(dir.join("bar-0.0.1/src/lib.rs")).unwrap()
+        .write_all(b"pub fn broken(").unwrap();
+    assert_that(p.cargo("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[WARNING] the unpacked source of `bar v0.0.1` [..]")
+                       .with_stderr_contains("\
+[WARNING] the unpacked source of `baz v0.0.1` [..]"));
+    let mut contents = String::new();
+    File::open(dir.join("baz-0.0.1/src/lib.rs")).unwrap()
+        .read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "pub fn same() {}");
+
+    // Without sharing, each package has its own files.
+    paths::home().join(".cargo/registry/src").rm_rf();
+    t!(fs::create_dir(p.root().join(".cargo")));
+    t!(t!(File::create(p.root().join(".cargo/config"))).write_all(br#"
+        [registry]
+        share-files = false
+    "#));
+    assert_that(p.cargo("build"), execs().with_status(0));
+    let bar = fs::metadata(dir.join("bar-0.0.1/src/lib.rs")).unwrap();
+    let baz = fs::metadata(dir.join("baz-0.0.1/src/lib.rs")).unwrap();
+    assert!(bar.ino() != baz.ino());
+}
+
+#[test]
+fn update_minimal_direct() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1.1"
+            mid = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("bar", "0.1.0").publish();
+    Package::new("bar", "0.1.1").publish();
+    Package::new("bar", "0.1.2").publish();
+    Package::new("baz", "0.1.0").publish();
+    Package::new("baz", "0.1.1").publish();
+    Package::new("mid", "0.1.0").dep("baz", "0.1").publish();
+    Package::new("mid", "0.1.1").dep("baz", "0.1").publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+
+    // Only the dependencies of the package itself go down, to the oldest
+    // versions they allow.
+    assert_that(p.cargo("update").arg("--minimal-direct"),
+                execs().with_status(0).with_stderr("\
+[UPDATING] registry `[..]`
+[UPDATING] bar v0.1.2 -> v0.1.1
+[UPDATING] mid v0.1.1 -> v0.1.0
+"));
+    let lock = p.read_lockfile();
+    assert!(lock.contains("name = \"baz\"\nversion = \"0.1.1\""));
+
+    assert_that(p.cargo("update").arg("--minimal-direct")
+                 .arg("--precise").arg("0.1.2"),
+                execs().with_status(101).with_stderr("\
+[ERROR] cannot update to minimal versions along with --precise or \
+--from-advisories
+"));
+}
+
+#[test]
+fn conflict_explanation() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+            qux = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("baz", "1.0.0").publish();
+    Package::new("baz", "1.0.1").publish();
+    Package::new("bar", "0.1.0").dep("baz", "=1.0.0").publish();
+    Package::new("qux", "0.1.0").dep("baz", "=1.0.1").publish();
+
+    // Whichever of `bar` and `qux` is resolved first gets its version of
+    // `baz` selected.
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] failed to select a version for `baz` (required by `[..]`):
+all possible versions conflict with previously selected versions of `baz`
+  required `= 1.0.[..]` by `foo v0.0.1 ([..])` -> `[..] v0.1.0[..]`
+  version 1.0.[..] selected for
+    `= 1.0.[..]` by `foo v0.0.1 ([..])` -> `[..] v0.1.0[..]`
+  possible versions to select: 1.0.[..]
+"));
+
+    assert_that(p.cargo("generate-lockfile").arg("--format").arg("json"),
+                execs().with_status(101).with_json(r#"
+    {
+        "name": "baz",
+        "requirement": {
+            "req": "= 1.0.[..]",
+            "path": ["foo 0.0.1 (path+file://[..])", "[..] 0.1.0 ([..])"]
+        },
+        "selected": [
+            {
+                "version": "1.0.[..]",
+                "required_by": [
+                    {
+                        "req": "= 1.0.[..]",
+                        "path": ["foo 0.0.1 (path+file://[..])", "[..] 0.1.0 ([..])"]
+                    }
+                ]
+            }
+        ],
+        "candidates": ["1.0.[..]"]
+    }"#));
+}
+
+#[test]
+fn update_breaking() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["foo"]
+
+            [workspace.dependencies]
+            baz = "1.0"
+        "#)
+        .file("foo/Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            # Kept at 0.1 on purpose.
+            bar = "0.1"
+            baz = { workspace = true }
+            qux = "0.2"
+        "#)
+        .file("foo/src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "1.0.0").publish();
+    Package::new("qux", "0.2.0").publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+
+    Package::new("bar", "0.3.0").publish();
+    Package::new("baz", "2.1.0").publish();
+    Package::new("qux", "0.2.1").publish();
+    assert_that(p.cargo("update").arg("--breaking")
+                 .arg("-p").arg("bar").arg("-p").arg("baz"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[UPGRADING] bar 0.1 -> 0.3 in foo[..]Cargo.toml")
+                       .with_stderr_contains("\
+[UPGRADING] baz 1.0 -> 2.1 in Cargo.toml")
+                       .with_stderr_contains("\
+[UPDATING] bar v0.1.0 -> v0.3.0")
+                       .with_stderr_contains("\
+[UPDATING] baz v1.0.0 -> v2.1.0"));
+
+    let mut manifest = String::new();
+    File::open(p.root().join("foo/Cargo.toml")).unwrap()
+        .read_to_string(&mut manifest).unwrap();
+    assert!(manifest.contains("# Kept at 0.1 on purpose.\n            bar = \"0.3\""));
+    assert!(manifest.contains("baz = { workspace = true }"));
+    assert!(manifest.contains("qux = \"0.2\""));
+    let mut root = String::new();
+    File::open(p.root().join("Cargo.toml")).unwrap()
+        .read_to_string(&mut root).unwrap();
+    assert!(root.contains("baz = \"2.1\""));
+
+    // `qux` isn't selected, and its newest version is allowed anyway.
+    let lock = p.read_lockfile();
+    assert!(lock.contains("name = \"qux\"\nversion = \"0.2.0\""));
+
+    assert_that(p.cargo("update").arg("--breaking"),
+                execs().with_status(0).with_stderr_contains("\
+[UPGRADING] no requirement excludes the newest version of its dependency"));
+}
+
+#[test]
+fn update_precise_allow_yanked() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("bar", "0.1.0").yanked(true).publish();
+    Package::new("bar", "0.1.1").publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+
+    assert_that(p.cargo("update").arg("-p").arg("bar")
+                 .arg("--precise").arg("0.1.0"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] version 0.1.0 of `bar` has been yanked, pass --allow-yanked to lock it \
+anyway"));
+    assert_that(p.cargo("update").arg("--allow-yanked"),
+                execs().with_status(101).with_stderr("\
+[ERROR] --allow-yanked can only be specified along with --precise
+"));
+
+    assert_that(p.cargo("update").arg("-p").arg("bar")
+                 .arg("--precise").arg("0.1.0").arg("--allow-yanked"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[WARNING] locking `bar v0.1.0 (registry [..])` even though it has been yanked; \
+this is recorded in Cargo.lock, and reported until it's updated")
+                       .with_stderr_contains("\
+[UPDATING] bar v0.1.1 -> v0.1.0"));
+    let lock = p.read_lockfile();
+    assert!(lock.contains("\"yanked bar 0.1.0 (registry+"), "{}", lock);
+
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] `bar v0.1.0 (registry [..])` is locked to a version which has been \
+yanked, as pinned with `cargo update --precise --allow-yanked`"));
+
+    // Updating away from the yanked version forgets about it.
+    assert_that(p.cargo("update").arg("-p").arg("bar"),
+                execs().with_status(0));
+    let lock = p.read_lockfile();
+    assert!(!lock.contains("yanked"), "{}", lock);
+}
+
+#[test]
+fn update_dry_run_diff() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("gone", "0.1.0").publish();
+    Package::new("bar", "0.1.0").dep("gone", "0.1").publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+    let lock = p.read_lockfile();
+
+    Package::new("new", "0.1.0").publish();
+    Package::new("bar", "0.1.1").dep("new", "0.1").publish();
+    assert_that(p.cargo("update").arg("--dry-run").arg("--diff"),
+                execs().with_status(0)
+                       .with_stdout("\
+~ bar 0.1.0 -> 0.1.1
+- gone 0.1.0
++ new 0.1.0
+")
+                       .with_stderr_contains("\
+[UPDATING] bar v0.1.0 -> v0.1.1")
+                       .with_stderr_contains("\
+[WARNING] not writing Cargo.lock, as this is a dry run"));
+    assert_eq!(p.read_lockfile(), lock);
+
+    assert_that(p.cargo("update").arg("--dry-run").arg("--diff")
+                 .arg("--format").arg("json"),
+                execs().with_status(0).with_json(r#"
+    {
+        "changes": [
+            {
+                "name": "bar",
+                "source": "registry+file://[..]",
+                "old_version": "0.1.0",
+                "new_version": "0.1.1",
+                "features_added": [],
+                "features_removed": []
+            },
+            {
+                "name": "gone",
+                "source": "registry+file://[..]",
+                "old_version": "0.1.0",
+                "new_version": null,
+                "features_added": [],
+                "features_removed": []
+            },
+            {
+                "name": "new",
+                "source": "registry+file://[..]",
+                "old_version": null,
+                "new_version": "0.1.0",
+                "features_added": [],
+                "features_removed": []
+            }
+        ]
+    }"#));
+    assert_eq!(p.read_lockfile(), lock);
+}
+
+#[test]
+fn update_diff_features() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("baz", "0.1.0")
+            .feature("std", &[])
+            .feature("alloc", &[])
+            .publish();
+    Package::new("bar", "0.1.0").feature_dep("baz", "0.1", &["alloc"]).publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+
+    // `baz` stays at the same version, but with another feature.
+    Package::new("bar", "0.1.1").feature_dep("baz", "0.1", &["std"]).publish();
+    assert_that(p.cargo("update").arg("--dry-run").arg("--diff"),
+                execs().with_status(0)
+                       .with_stdout("\
+~ bar 0.1.0 -> 0.1.1
+~ baz 0.1.0
+    + feature `std`
+    - feature `alloc`
+"));
+
+    assert_that(p.cargo("update").arg("--dry-run").arg("--diff")
+                 .arg("--format").arg("json"),
+                execs().with_status(0).with_json(r#"
+    {
+        "changes": [
+            {
+                "name": "bar",
+                "source": "registry+file://[..]",
+                "old_version": "0.1.0",
+                "new_version": "0.1.1",
+                "features_added": [],
+                "features_removed": []
+            },
+            {
+                "name": "baz",
+                "source": "registry+file://[..]",
+                "old_version": "0.1.0",
+                "new_version": "0.1.0",
+                "features_added": ["std"],
+                "features_removed": ["alloc"]
+            }
+        ]
+    }"#));
+}
+
+#[test]
+fn edited_requirement_keeps_other_pins() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+            baz = "0.1"
+            gone = "0.1"
+            qux = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("baz", "0.1.0").publish();
+    Package::new("gone", "0.1.0").publish();
+    Package::new("bar", "0.1.0").publish();
+    Package::new("quux", "0.1.0").publish();
+    Package::new("qux", "0.1.0").dep("quux", "0.1").publish();
+    assert_that(p.cargo("generate-lockfile"),
+                execs().with_status(0));
+    let before = p.read_lockfile();
+
+    Package::new("baz", "0.1.1").publish();
+    Package::new("bar", "0.1.1").dep("baz", "0.1").publish();
+    Package::new("quux", "0.1.1").publish();
+    Package::new("qux", "0.1.1").dep("quux", "0.1").publish();
+    File::create(p.root().join("Cargo.toml")).unwrap().write_all(br#"
+        [project]
+        name = "foo"
+        version = "0.0.1"
+        authors = []
+
+        [dependencies]
+        bar = "0.1.1"
+        baz = "0.1"
+        qux = "0.1"
+    "#).unwrap();
+
+    // Only `bar` is resolved again, and it's given the `baz` already locked.
+    assert_that(p.cargo("fetch"),
+                execs().with_status(0));
+    let lock = p.read_lockfile();
+    assert!(lock.contains("name = \"bar\"\nversion = \"0.1.1\""), "{}", lock);
+    assert!(lock.contains("name = \"baz\"\nversion = \"0.1.0\""), "{}", lock);
+    assert!(!lock.contains("baz 0.1.1"), "{}", lock);
+    assert!(!lock.contains("name = \"gone\""), "{}", lock);
+
+    // The dependencies which weren't edited are locked exactly as before.
+    for name in ["baz", "qux", "quux"].iter() {
+        let entry = |lock: &str| {
+            let start = lock.find(&format!("name = \"{}\"", name)).unwrap();
+            let end = lock[start..].find("\n\n").map(|e| start + e)
+                                  .unwrap_or(lock.len());
+            lock[start..end].to_string()
+        };
+        assert_eq!(entry(&lock), entry(&before));
+    }
+    assert!(lock.contains("name = \"qux\"\nversion = \"0.1.0\""), "{}", lock);
+    assert!(lock.contains("name = \"quux\"\nversion = \"0.1.0\""), "{}", lock);
+}
+
+#[test]
+fn build_after_editing_features_and_requirements() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+            qux = "0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    Package::new("bar", "0.1.0")
+            .feature("fast", &[])
+            .file("src/lib.rs", r#"
+                #[cfg(feature = "fast")]
+                pub fn fast() {}
+            "#)
+            .publish();
+    Package::new("qux", "0.1.0").publish();
+    assert_that(p.cargo("build"), execs().with_status(0));
+
+    Package::new("bar", "0.1.1").feature("fast", &[]).publish();
+    Package::new("qux", "0.1.1").publish();
+    File::create(p.root().join("Cargo.toml")).unwrap().write_all(br#"
+        [project]
+        name = "foo"
+        version = "0.0.1"
+        authors = []
+
+        [dependencies]
+        bar = { version = "0.1", features = ["fast"] }
+        qux = "0.1.1"
+    "#).unwrap();
+    File::create(p.root().join("src/main.rs")).unwrap().write_all(br#"
+        extern crate bar;
+        fn main() { bar::fast() }
+    "#).unwrap();
+
+    assert_that(p.cargo("build"),
+                execs().with_status(0)
+                       .with_stderr_contains("[COMPILING] bar v0.1.0")
+                       .with_stderr_contains("[COMPILING] qux v0.1.1"));
+    let lock = p.read_lockfile();
+    assert!(lock.contains("name = \"bar\"\nversion = \"0.1.0\""), "{}", lock);
+
+    // Taking the feature away again builds too.
+    File::create(p.root().join("Cargo.toml")).unwrap().write_all(br#"
+        [project]
+        name = "foo"
+        version = "0.0.1"
+        authors = []
+
+        [dependencies]
+        bar = "0.1"
+        qux = "0.1.1"
+    "#).unwrap();
+    File::create(p.root().join("src/main.rs")).unwrap()
+        .write_all(b"fn main() {}").unwrap();
+    assert_that(p.cargo("build"), execs().with_status(0));
+}
@@ -0,0 +1,118 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+fn read(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn remove_cleans_features() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = { version = "0.1", optional = true }
+            baz = "0.1"   # keep me
+
+            [features]
+            default = ["bar", "fast"]
+            fast = [
+                "bar/fast",
+                "baz/fast",
+            ]
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    Package::new("bar", "0.1.0").feature("fast", &[]).publish();
+    Package::new("baz", "0.1.0").feature("fast", &[]).publish();
+    assert_that(p.cargo("generate-lockfile"), execs().with_status(0));
+
+    assert_that(p.cargo("remove").arg("bar"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[REMOVING] bar from dependencies"));
+    assert!(read(&p.root().join("Cargo.toml")).contains(r#"
+            [dependencies]
+            baz = "0.1"   # keep me
+
+            [features]
+            default = ["fast"]
+            fast = [
+                "baz/fast",
+            ]
+"#));
+    let lock = p.read_lockfile();
+    assert!(!lock.contains("name = \"bar\""), "{}", lock);
+    assert!(lock.contains("name = \"baz\""), "{}", lock);
+}
+
+#[test]
+fn remove_target_table() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [target.'cfg(unix)'.dev-dependencies.bar]
+            path = "bar"
+
+            [target.'cfg(windows)'.dev-dependencies]
+            bar = { path = "bar" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("remove").arg("bar").arg("--dev")
+                 .arg("--target").arg("cfg(unix)"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[REMOVING] bar from target.cfg(unix).dev-dependencies"));
+    let manifest = read(&p.root().join("Cargo.toml"));
+    assert!(!manifest.contains("cfg(unix)"), "{}", manifest);
+    assert!(manifest.contains(r#"
+            [target.'cfg(windows)'.dev-dependencies]
+            bar = { path = "bar" }
+"#), "{}", manifest);
+}
+
+#[test]
+fn remove_missing() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("remove").arg("bar").arg("--build"),
+                execs().with_status(101)
+                       .with_stderr(&format!("\
+[ERROR] the dependency `bar` could not be found in `build-dependencies` of `{}`
+", p.root().join("Cargo.toml").display())));
+}
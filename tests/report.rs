@@ -0,0 +1,100 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+fn publish_bar() {
+    Package::new("bar", "0.1.0").pubtime("2000-01-01T00:00:00Z").publish();
+    Package::new("bar", "1.0.0").publish();
+    Package::new("bar", "1.1.0").publish();
+    Package::new("bar", "2.0.0").publish();
+    Package::new("bar", "3.0.0-beta").publish();
+}
+
+fn project_with_policy(policy: &str) -> cargotest::support::ProjectBuilder {
+    publish_bar();
+    project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "")
+        .file(".cargo/config", policy)
+}
+
+#[test]
+fn build_warns_about_stale_dependencies() {
+    let p = project_with_policy(r#"
+        [staleness]
+        max-age = 5
+        max-major-behind = 1
+    "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0).with_stderr_contains("\
+[WARNING] `bar v0.1.0` is stale: published [..] years ago, 2 major versions \
+behind 2.0.0
+"));
+}
+
+#[test]
+fn build_passes_within_policy() {
+    let p = project_with_policy(r#"
+        [staleness]
+        max-major-behind = 2
+    "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(0).with_stderr("\
+[UPDATING] registry `[..]`
+[DOWNLOADING] bar v0.1.0 ([..])
+[COMPILING] bar v0.1.0
+[COMPILING] foo v0.0.1 ([..])
+[FINISHED] [..]
+"));
+}
+
+#[test]
+fn build_denies_stale_dependencies() {
+    let p = project_with_policy(r#"
+        [staleness]
+        max-age = 5
+        action = "deny"
+    "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] the staleness policy denies these dependencies:
+  bar v0.1.0: published [..] years ago
+update them or relax `staleness` in the configuration
+"));
+}
+
+#[test]
+fn report_staleness() {
+    let p = project_with_policy("");
+    assert_that(p.cargo_process("generate-lockfile"), execs().with_status(0));
+
+    assert_that(p.cargo("report").arg("staleness")
+                 .arg("--max-major-behind").arg("1"),
+                execs().with_status(0).with_stdout("\
+bar v0.1.0: 2 major versions behind 2.0.0
+"));
+    assert_that(p.cargo("report").arg("staleness")
+                 .arg("--max-major-behind").arg("2"),
+                execs().with_status(0).with_stdout(""));
+}
+
+#[test]
+fn report_staleness_requires_policy() {
+    let p = project_with_policy("");
+    assert_that(p.cargo_process("report").arg("staleness"),
+                execs().with_status(101).with_stderr("\
+[ERROR] no staleness policy to report on, configure `staleness.max-age` or \
+`staleness.max-major-behind`, or pass `--max-age` or `--max-major-behind`
+"));
+}
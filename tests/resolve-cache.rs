@@ -0,0 +1,119 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::fs;
+
+use cargotest::support::paths;
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::{assert_that, existing_dir, existing_file, is_not};
+
+#[test]
+fn resolution_is_reused_until_the_index_changes() {
+    Package::new("bar", "0.1.0").publish();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "")
+        .file(".cargo/config", r#"
+            [build]
+            resolve-cache = true
+        "#);
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+    let cache = paths::home().join(".cargo/resolve-cache");
+    assert!(fs::read_dir(&cache).unwrap().count() > 0);
+
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr("\
+[FINISHED] [..]
+"));
+
+    // A new version in the index is picked up by an update
+    Package::new("bar", "0.1.1").publish();
+    assert_that(p.cargo("update"),
+                execs().with_status(0).with_stderr("\
+[UPDATING] registry `[..]`
+[UPDATING] bar v0.1.0 -> v0.1.1
+"));
+}
+
+#[test]
+fn cache_disabled_by_default() {
+    Package::new("bar", "0.1.0").publish();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+    assert_that(&paths::home().join(".cargo/resolve-cache"),
+                is_not(existing_dir()));
+}
+
+#[test]
+fn record_and_bench() {
+    Package::new("baz", "0.1.0").publish();
+    Package::new("baz", "0.1.1").publish();
+    Package::new("baz", "0.2.0").publish();
+    Package::new("bar", "0.1.0").dep("baz", "0.1").publish();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+            baz = "0.1"
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("resolve").arg("--record").arg("graph.json"),
+                execs().with_status(0).with_stderr_contains("\
+[RECORDED] 3 candidates to `[..]graph.json`
+"));
+    assert_that(&p.root().join("graph.json"), existing_file());
+
+    assert_that(p.cargo("resolve").arg("--bench").arg("graph.json")
+                 .arg("--iterations").arg("3"),
+                execs().with_status(0).with_stdout("\
+packages: 3
+candidates: 3
+iterations: 3
+min: [..]ms
+mean: [..]ms
+max: [..]ms
+"));
+}
+
+#[test]
+fn record_or_bench_required() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("resolve"),
+                execs().with_status(101).with_stderr("\
+[ERROR] exactly one of --record and --bench must be given
+"));
+}
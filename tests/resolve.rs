@@ -11,7 +11,7 @@ use cargo::core::source::{SourceId, GitReference};
 use cargo::core::dependency::Kind::{self, Development};
 use cargo::core::{Dependency, PackageId, Summary, Registry};
 use cargo::util::{CargoResult, ToUrl};
-use cargo::core::resolver::{self, Method};
+use cargo::core::resolver::{self, Method, VersionOrder};
 
 fn resolve<R: Registry>(pkg: PackageId, deps: Vec<Dependency>,
                         registry: &mut R)
@@ -20,6 +20,7 @@ fn resolve<R: Registry>(pkg: PackageId, deps: Vec<Dependency>,
     let method = Method::Everything;
     Ok(try!(resolver::resolve(&[(summary, method)],
                               &[],
+                              VersionOrder::Newest,
                               registry)).iter().map(|p| {
         p.clone()
     }).collect())
@@ -2,11 +2,14 @@ extern crate cargo;
 extern crate cargotest;
 extern crate hamcrest;
 
-use std::path::MAIN_SEPARATOR as SEP;
+use std::path::{Path, MAIN_SEPARATOR as SEP};
 
 use cargo::util::paths::dylib_path_envvar;
+use cargotest::rustc_host;
+use cargotest::support::paths;
+use cargotest::support::registry::Package;
 use cargotest::support::{project, execs, path2url};
-use hamcrest::{assert_that, existing_file};
+use hamcrest::{assert_that, existing_dir, existing_file, is_not};
 
 #[test]
 fn simple() {
@@ -638,3 +641,240 @@ fn fail_no_extra_verbose() {
                        .with_stdout("")
                        .with_stderr(""));
 }
+
+#[test]
+fn single_file_package() {
+    Package::new("bar", "0.1.0")
+        .file("src/lib.rs", "pub fn bar() -> u32 { 4 }")
+        .publish();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", r#"
+            fn main() { println!("foo {}", std::env::args().nth(1).unwrap()); }
+        "#)
+        .file("hello.rs", r#"
+            //! Says hello.
+            //!
+            //! ```cargo
+            //! [dependencies]
+            //! bar = "0.1"
+            //! ```
+
+            extern crate bar;
+
+            fn main() {
+                println!("hello {} {}", bar::bar(), std::env::args().nth(1).unwrap());
+            }
+        "#);
+
+    assert_that(p.cargo_process("run").arg("hello.rs").arg("world"),
+                execs().with_status(0).with_stdout("hello 4 world\n"));
+    assert_that(&paths::home().join(".cargo/scripts"), existing_dir());
+    assert_that(&p.root().join("target"), is_not(existing_dir()));
+
+    // With `--bin` the file is an argument of the binary of the package.
+    assert_that(p.cargo("run").arg("--bin").arg("foo").arg("hello.rs"),
+                execs().with_status(0).with_stdout("foo hello.rs\n"));
+}
+
+#[test]
+fn single_file_package_path_dependency() {
+    let p = project("foo")
+        .file("scripts/hello.rs", r#"
+            //! ```cargo
+            //! [dependencies]
+            //! bar = { path = "../bar" }
+            //! ```
+
+            extern crate bar;
+
+            fn main() { println!("hello {}", bar::bar()); }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() -> u32 { 4 }");
+
+    assert_that(p.cargo_process("run").arg("scripts/hello.rs"),
+                execs().with_status(0).with_stdout("hello 4\n"));
+}
+
+#[test]
+fn env_file() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", r#"
+            fn main() {
+                assert_eq!(option_env!("FOO"), None);
+                println!("{} {}", std::env::var("FOO").unwrap(),
+                         std::env::var("BAR").unwrap());
+            }
+        "#)
+        .file("run.env", r#"
+            # comment
+            FOO=1
+            export BAR="a \"b\""
+        "#);
+
+    assert_that(p.cargo_process("run").arg("--env-file").arg("run.env"),
+                execs().with_status(0).with_stdout("1 a \"b\"\n"));
+
+    assert_that(p.cargo("run").arg("--env-file").arg("missing.env"),
+                execs().with_status(101).with_stderr("\
+[ERROR] the environment file `[..]missing.env` passed to --env-file does not exist
+"));
+}
+
+#[cfg(unix)]
+#[test]
+fn runners() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "a"
+            path = "src/a.rs"
+
+            [[bin]]
+            name = "b"
+            path = "src/b.rs"
+            runner = ["sh", "-c", "echo manifest; exec \"$0\" \"$@\""]
+        "#)
+        .file("src/a.rs", r#"
+            fn main() { println!("a {}", std::env::args().nth(1).unwrap()); }
+        "#)
+        .file("src/b.rs", r#"
+            fn main() { println!("b {}", std::env::args().nth(1).unwrap()); }
+        "#)
+        .file(".cargo/config", &format!(r#"
+            [target.{}.runner]
+            program = "sh"
+            args = ["-c", "echo config $RUNNER_VAR; exec \"$0\" \"$@\""]
+            env = {{ RUNNER_VAR = "var" }}
+        "#, rustc_host()));
+
+    assert_that(p.cargo_process("run").arg("--bin").arg("a").arg("x"),
+                execs().with_status(0).with_stdout("config var\na x\n"));
+    assert_that(p.cargo("run").arg("--bin").arg("b").arg("y"),
+                execs().with_status(0).with_stdout("manifest\nb y\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn runners_for_tests() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[test]]
+            name = "t"
+            path = "tests/t.rs"
+            runner = ["sh", "-c", "echo manifest; exec \"$0\" \"$@\""]
+        "#)
+        .file("src/lib.rs", r#"
+            #[test]
+            fn lib_test() {}
+        "#)
+        .file("tests/t.rs", r#"
+            #[test]
+            fn integration_test() {}
+        "#)
+        .file(".cargo/config", &format!(r#"
+            [target.{}.runner]
+            program = "sh"
+            args = ["-c", "echo config $RUNNER_VAR; exec \"$0\" \"$@\""]
+            env = {{ RUNNER_VAR = "var" }}
+        "#, rustc_host()));
+
+    assert_that(p.cargo_process("test").arg("--lib"),
+                execs().with_status(0)
+                       .with_stdout_contains("config var")
+                       .with_stdout_contains("test lib_test ... ok"));
+    assert_that(p.cargo("test").arg("--test").arg("t"),
+                execs().with_status(0)
+                       .with_stdout_contains("manifest")
+                       .with_stdout_contains("test integration_test ... ok"));
+}
+
+#[cfg(unix)]
+#[test]
+fn qemu_user_runner() {
+    use std::env;
+    use std::fs;
+    use std::os::unix::prelude::*;
+
+    let host = rustc_host();
+    let arch = host.split('-').next().unwrap().to_string();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", r#"
+            fn main() { println!("foo {}", std::env::args().nth(1).unwrap()); }
+        "#)
+        .file(".cargo/config", &format!(r#"
+            [target.{}.runner]
+            preset = "qemu-user"
+            sysroot = "sysroot"
+        "#, host))
+        .file(&format!("emulators/qemu-{}", arch), "#!/bin/sh
+echo qemu $QEMU_LD_PREFIX
+exec \"$@\"
+");
+    p.build();
+
+    let emulator = p.root().join(format!("emulators/qemu-{}", arch));
+    let mut perms = fs::metadata(&emulator).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&emulator, perms).unwrap();
+    let path = env::var_os("PATH").unwrap();
+    let path = Some(p.root().join("emulators")).into_iter()
+                                               .chain(env::split_paths(&path));
+    let path = env::join_paths(path).unwrap();
+
+    assert_that(p.cargo("run").arg("x").env("PATH", &path),
+                execs().with_status(0).with_stdout(&format!("qemu {}\nfoo x\n",
+                                                  p.root().join("sysroot").display())));
+
+    // Without the emulator, nor a binfmt_misc handler for the host, there's
+    // nothing to run the binary with. The directories of PATH with an
+    // emulator installed are left out, rustc being found through RUSTC.
+    let emulator = format!("qemu-{}", arch);
+    let rustc = env::split_paths(&env::var_os("PATH").unwrap())
+                    .map(|dir| dir.join(format!("rustc{}", env::consts::EXE_SUFFIX)))
+                    .find(|rustc| rustc.is_file())
+                    .unwrap();
+    let path = env::split_paths(&env::var_os("PATH").unwrap())
+                   .filter(|dir| !dir.join(&emulator).exists())
+                   .collect::<Vec<_>>();
+    let path = env::join_paths(path).unwrap();
+    if !Path::new("/proc/sys/fs/binfmt_misc").join(&emulator).exists() {
+        assert_that(p.cargo("run").env("PATH", &path).env("RUSTC", &rustc),
+                    execs().with_status(101).with_stderr_contains(&format!("\
+[ERROR] cannot run binaries built for `{0}`: QEMU's emulator `qemu-{1}` is not \
+in PATH, and no binfmt_misc handler is registered for it", host, arch)));
+    }
+}
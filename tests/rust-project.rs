@@ -0,0 +1,77 @@
+extern crate cargotest;
+extern crate hamcrest;
+extern crate rustc_serialize;
+
+use std::path::Path;
+
+use cargotest::support::{project, execs};
+use hamcrest::{assert_that, existing_file};
+use rustc_serialize::json::Json;
+
+#[test]
+fn describes_crate_graph() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar", features = ["fancy"] }
+        "#)
+        .file("src/lib.rs", "extern crate bar;")
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar(); }")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+            build = "build.rs"
+
+            [features]
+            fancy = []
+        "#)
+        .file("bar/build.rs", r#"
+            use std::env;
+            use std::fs::File;
+            use std::path::Path;
+
+            fn main() {
+                let out = env::var("OUT_DIR").unwrap();
+                File::create(Path::new(&out).join("generated.rs")).unwrap();
+            }
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    assert_that(p.cargo_process("rust-project"), execs().with_status(0));
+    let output = p.cargo("rust-project").exec_with_output().unwrap();
+    let json = Json::from_str(&String::from_utf8(output.stdout).unwrap()).unwrap();
+
+    let crates = json.find("crates").unwrap().as_array().unwrap();
+    assert_eq!(crates.len(), 3);
+    let name = |c: &Json| c.find("display_name").unwrap().as_string().unwrap().to_string();
+    assert_eq!(crates.iter().map(|c| name(c)).collect::<Vec<_>>(),
+               ["bar", "foo", "foo"]);
+
+    // The library of bar, with its features and build script output
+    let bar = &crates[0];
+    assert_eq!(bar.find("is_workspace_member").unwrap().as_boolean(), Some(false));
+    let cfg = bar.find("cfg").unwrap().as_array().unwrap();
+    assert!(cfg.iter().any(|c| c.as_string() == Some("feature=\"fancy\"")));
+    let out_dir = bar.find_path(&["env", "OUT_DIR"]).unwrap().as_string().unwrap();
+    assert_that(&Path::new(out_dir).join("generated.rs"), existing_file());
+    assert!(bar.find("root_module").unwrap().as_string().unwrap()
+               .ends_with("lib.rs"));
+
+    // The library and binary of foo, which depend on bar and on foo's
+    // library respectively.
+    let foo_lib = &crates[1];
+    let foo_bin = &crates[2];
+    assert_eq!(foo_lib.find("is_workspace_member").unwrap().as_boolean(), Some(true));
+    assert_eq!(foo_lib.find_path(&["deps"]).unwrap().as_array().unwrap().len(), 1);
+    assert_eq!(foo_lib.find_path(&["deps"]).unwrap()[0].find("crate").unwrap()
+                      .as_u64(), Some(0));
+    assert_eq!(foo_bin.find_path(&["deps"]).unwrap()[0].find("name").unwrap()
+                      .as_string(), Some("foo"));
+}
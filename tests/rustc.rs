@@ -4,6 +4,7 @@ extern crate hamcrest;
 use std::path::MAIN_SEPARATOR as SEP;
 
 use cargotest::support::{execs, project};
+use cargotest::support::registry::Package;
 use hamcrest::assert_that;
 
 const CARGO_RUSTC_ERROR: &'static str =
@@ -391,3 +392,75 @@ fn rustc_with_other_profile() {
     assert_that(foo.cargo("rustc").arg("--profile").arg("test"),
                 execs().with_status(0));
 }
+
+#[test]
+fn args_to_registry_dependency_reuse_artifacts() {
+    Package::new("bar", "0.1.0")
+        .file("src/lib.rs", "pub fn baz() {}")
+        .publish();
+    let foo = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() {
+                bar::baz()
+            }
+        "#);
+
+    assert_that(foo.cargo_process("build"),
+                execs().with_status(0));
+
+    assert_that(foo.cargo("rustc").arg("-v").arg("-p").arg("bar")
+                   .arg("--").arg("-C").arg("debug-assertions"),
+                execs()
+                .with_status(0)
+                .with_stderr("\
+[COMPILING] bar v0.1.0
+[RUNNING] `rustc [..] --crate-name bar [..] -C debug-assertions [..]`
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+"));
+
+    assert_that(foo.cargo("build").arg("-v"),
+                execs()
+                .with_status(0)
+                .with_stderr("\
+[FRESH] bar v0.1.0
+[FRESH] foo v0.0.1 ([..])
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+"));
+}
+
+#[test]
+fn args_to_path_test_rebuild_in_place() {
+    let foo = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("tests/it.rs", r#"
+            #[test]
+            fn it() { assert!(cfg!(foo)) }
+        "#);
+
+    assert_that(foo.cargo_process("rustc").arg("--test").arg("it")
+                   .arg("--profile").arg("test")
+                   .arg("--").arg("--cfg").arg("foo"),
+                execs().with_status(0));
+
+    // The flags aren't part of the name of the test executable, so it has to
+    // be rebuilt without them rather than being considered fresh.
+    assert_that(foo.cargo("test").arg("-v"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[RUNNING] `rustc tests[..]it.rs [..]`"));
+}
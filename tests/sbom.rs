@@ -0,0 +1,145 @@
+extern crate cargotest;
+extern crate hamcrest;
+extern crate rustc_serialize;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use cargotest::rustc_host;
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+use rustc_serialize::json::Json;
+
+fn read_json(path: &Path) -> Json {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    Json::from_str(&contents).unwrap()
+}
+
+fn project_with_deps() -> cargotest::support::ProjectBuilder {
+    Package::new("bar", "0.1.0").publish();
+    project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            license = "MIT"
+
+            [dependencies]
+            bar = "0.1"
+            baz = { path = "baz", features = ["fancy"] }
+        "#)
+        .file("src/main.rs", "extern crate bar; extern crate baz; fn main() {}")
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.2.0"
+            authors = []
+            license = "MIT OR Apache-2.0"
+
+            [features]
+            fancy = []
+        "#)
+        .file("baz/src/lib.rs", "")
+}
+
+fn string<'a>(json: &'a Json, path: &[&str]) -> &'a str {
+    json.find_path(path).unwrap().as_string().unwrap()
+}
+
+#[test]
+fn cyclonedx() {
+    let p = project_with_deps();
+    assert_that(p.cargo_process("build").arg("--sbom").arg("cyclonedx")
+                 .env("SOURCE_DATE_EPOCH", "1476489600"),
+                execs().with_status(0).with_stderr_contains("\
+[..]Generated SBOM of foo v0.0.1 ([..]) at `[..]foo.cdx.json`
+"));
+
+    let json = read_json(&p.root().join("target/debug/foo.cdx.json"));
+    assert_eq!(string(&json, &["bomFormat"]), "CycloneDX");
+    assert_eq!(string(&json, &["metadata", "timestamp"]), "2016-10-15T00:00:00Z");
+    assert_eq!(string(&json, &["metadata", "component", "type"]), "application");
+    assert_eq!(string(&json, &["metadata", "component", "name"]), "foo");
+    let license = json.find_path(&["metadata", "component", "licenses"]).unwrap();
+    assert_eq!(string(&license[0], &["expression"]), "MIT");
+    assert!(string(&json.find_path(&["metadata", "properties"]).unwrap()[0],
+                &["value"]).starts_with("rustc "));
+
+    let components = json.find("components").unwrap().as_array().unwrap();
+    assert_eq!(components.len(), 2);
+
+    let bar = &components[0];
+    assert_eq!(string(bar, &["name"]), "bar");
+    assert_eq!(string(bar, &["version"]), "0.1.0");
+    assert_eq!(string(bar, &["purl"]), "pkg:cargo/bar@0.1.0");
+    let hash = &bar.find("hashes").unwrap()[0];
+    assert_eq!(string(hash, &["alg"]), "SHA-256");
+    assert_eq!(string(hash, &["content"]).len(), 64);
+
+    let baz = &components[1];
+    assert_eq!(string(baz, &["name"]), "baz");
+    assert!(baz.find("purl").is_none());
+    assert!(baz.find("hashes").is_none());
+    let properties = baz.find("properties").unwrap().as_array().unwrap();
+    let property = |name: &str| {
+        properties.iter().filter(|p| string(p, &["name"]) == name)
+                  .map(|p| string(p, &["value"])).collect::<Vec<_>>()
+    };
+    assert_eq!(property("cargo:feature"), ["fancy"]);
+    assert_eq!(property("cargo:target"), [&rustc_host()[..]]);
+
+    let dependencies = json.find("dependencies").unwrap().as_array().unwrap();
+    let foo = dependencies.iter().find(|d| {
+        string(d, &["ref"]).starts_with("foo 0.0.1 ")
+    }).unwrap();
+    let deps = foo.find("dependsOn").unwrap().as_array().unwrap();
+    assert_eq!(deps.len(), 2);
+    assert!(deps[0].as_string().unwrap().starts_with("bar 0.1.0 (registry+"));
+    assert!(deps[1].as_string().unwrap().starts_with("baz 0.2.0 (path+"));
+}
+
+#[test]
+fn spdx() {
+    let p = project_with_deps();
+    assert_that(p.cargo_process("build").arg("--sbom").arg("spdx")
+                 .env("SOURCE_DATE_EPOCH", "1476489600"),
+                execs().with_status(0));
+
+    let json = read_json(&p.root().join("target/debug/foo.spdx.json"));
+    assert_eq!(string(&json, &["spdxVersion"]), "SPDX-2.3");
+    assert_eq!(string(&json, &["creationInfo", "created"]), "2016-10-15T00:00:00Z");
+
+    let packages = json.find("packages").unwrap().as_array().unwrap();
+    let names = packages.iter().map(|p| string(p, &["name"])).collect::<Vec<_>>();
+    assert_eq!(names, ["bar", "baz", "foo"]);
+    let checksum = &packages[0].find("checksums").unwrap()[0];
+    assert_eq!(string(checksum, &["algorithm"]), "SHA256");
+    assert_eq!(string(&packages[1], &["licenseDeclared"]), "MIT OR Apache-2.0");
+    assert!(string(&packages[1], &["comment"]).contains("features: fancy;"));
+
+    let relationships = json.find("relationships").unwrap().as_array().unwrap();
+    assert_eq!(string(&relationships[0], &["relationshipType"]), "DESCRIBES");
+    assert_eq!(string(&relationships[0], &["relatedSpdxElement"]),
+               string(&packages[2], &["SPDXID"]));
+    assert_eq!(relationships.iter().filter(|r| {
+        string(r, &["relationshipType"]) == "DEPENDS_ON"
+    }).count(), 2);
+}
+
+#[test]
+fn invalid_format() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("build").arg("--sbom").arg("swid"),
+                execs().with_status(1));
+}
@@ -149,3 +149,17 @@ fn help() {
     assert_that(cargo_process("help").arg("search"),
                 execs().with_status(0));
 }
+
+#[test]
+fn offline() {
+    setup();
+
+    assert_that(cargo_process("search").arg("postgres")
+                    .arg("--host").arg(registry().to_string())
+                    .arg("--offline"),
+                execs().with_status(101)
+                       .with_stderr("\
+[ERROR] attempting to reach `file://[..]registry`, but --offline was \
+specified and it is not allowed by --allow-net
+"));
+}
@@ -0,0 +1,174 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::rustc_host;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+#[test]
+fn override_system_dependency() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [system-dependencies]
+            foo = "1.0"
+        "#)
+        .file("src/lib.rs", "")
+        .file(".cargo/config", &format!(r#"
+            [target.{}.foo]
+            rustc-link-search = ["native=/path/to/foo"]
+            rustc-link-lib = ["foo"]
+        "#, rustc_host()));
+
+    assert_that(p.cargo_process("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr("\
+[COMPILING] foo v0.5.0 ([..])
+[RUNNING] `rustc [..] --crate-name foo [..] -L native=/path/to/foo -l foo`
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+"));
+}
+
+#[test]
+fn system_dependency_with_build_script() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "build.rs"
+
+            [system-dependencies]
+            foo = "1.0"
+        "#)
+        .file("build.rs", r#"
+            fn main() { println!("cargo:rustc-cfg=from_script"); }
+        "#)
+        .file("src/lib.rs", r#"
+            #[cfg(from_script)]
+            pub fn foo() {}
+        "#)
+        .file(".cargo/config", &format!(r#"
+            [target.{}.foo]
+            rustc-link-search = ["native=/path/to/foo"]
+            rustc-link-lib = ["foo"]
+        "#, rustc_host()));
+
+    // The build script still runs, its output joined by the system
+    // dependency's.
+    assert_that(p.cargo_process("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr_contains("[RUNNING] `[..]build-script-build`")
+                       .with_stderr_contains("\
+[RUNNING] `rustc [..] --crate-name foo [..]-L native=/path/to/foo[..]`")
+                       .with_stderr_contains("\
+[RUNNING] `rustc [..] --crate-name foo [..]--cfg from_script[..]`"));
+}
+
+#[cfg(unix)]
+#[test]
+fn probe_with_pkg_config() {
+    use std::fs::{self, File};
+    use std::io::prelude::*;
+    use std::os::unix::prelude::*;
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [system-dependencies]
+            bar = { name = "libbar", version = "2.0" }
+        "#)
+        .file("src/lib.rs", "")
+        .file("pkg-config", r#"#!/bin/sh
+            case "$1" in
+                --atleast-version=2.0) exit 0 ;;
+                --libs) echo "-L/opt/bar/lib -lbar" ;;
+                *) exit 1 ;;
+            esac
+        "#);
+    p.build();
+    let script = p.root().join("pkg-config");
+    let mut perms = fs::metadata(&script).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&script, perms).unwrap();
+
+    fs::create_dir_all(p.root().join(".cargo")).unwrap();
+    File::create(p.root().join(".cargo/config")).unwrap()
+        .write_all(format!(r#"
+            [target.{}]
+            pkg-config = "{}"
+        "#, rustc_host(), script.display()).as_bytes()).unwrap();
+
+    assert_that(p.cargo("build").arg("-v"),
+                execs().with_status(0)
+                       .with_stderr("\
+[COMPILING] foo v0.5.0 ([..])
+[RUNNING] `rustc [..] --crate-name foo [..] -L /opt/bar/lib -l bar`
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+"));
+}
+
+#[test]
+fn missing_system_dependency() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [system-dependencies]
+            foo = "1.0"
+        "#)
+        .file("src/lib.rs", "")
+        .file(".cargo/config", &format!(r#"
+            [target.{}]
+            pkg-config = "/path/to/nowhere/pkg-config"
+        "#, rustc_host()));
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("\
+[ERROR] failed to locate the system dependencies of `foo v0.5.0 ([..])`
+
+Caused by:
+  could not find system library `foo` (version >= 1.0) for `[..]` with \
+`/path/to/nowhere/pkg-config`
+
+to use a library which isn't known to pkg-config, override it with \
+`[target.[..].foo]`"));
+}
+
+#[test]
+fn invalid_link_preference() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [system-dependencies]
+            foo = { link = "sometimes" }
+        "#)
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101)
+                       .with_stderr("\
+[ERROR] failed to parse manifest at `[..]`
+
+Caused by:
+  system dependency `foo` has an invalid `link` value `sometimes`, expected \
+`static` or `dynamic`
+"));
+}
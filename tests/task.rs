@@ -0,0 +1,88 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+fn project_with_tasks(tasks: &str) -> cargotest::support::ProjectBuilder {
+    project("foo")
+        .file("Cargo.toml", &format!(r#"
+            [workspace]
+            members = ["gen"]
+
+            [workspace.tasks]
+            {}
+        "#, tasks))
+        .file("gen/Cargo.toml", r#"
+            [package]
+            name = "gen"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("gen/src/main.rs", r#"
+            use std::env;
+
+            fn main() {
+                let args = env::args().skip(1).collect::<Vec<_>>();
+                println!("generated {}", args.join(" "));
+                assert!(env::var("CARGO").is_ok());
+                assert!(env::var("CARGO_WORKSPACE_DIR").is_ok());
+            }
+        "#)
+}
+
+#[test]
+fn run_task_with_dependencies() {
+    let p = project_with_tasks(r#"
+        codegen = { command = ["gen", "x"], build = ["gen"] }
+        all = { command = "gen all", build = ["gen"], depends-on = ["codegen"] }
+    "#);
+
+    assert_that(p.cargo_process("task").arg("all").arg("--").arg("y"),
+                execs().with_status(0).with_stdout("\
+generated x
+generated all y
+").with_stderr("\
+[COMPILING] gen v0.1.0 ([..])
+[FINISHED] [..]
+[RUNNING] task `codegen`: `gen x`
+[FINISHED] [..]
+[RUNNING] task `all`: `gen all y`
+"));
+}
+
+#[test]
+fn list_tasks() {
+    let p = project_with_tasks(r#"
+        codegen = { command = ["gen", "x"], build = ["gen"] }
+        fmt = "rustfmt src/lib.rs"
+    "#);
+
+    assert_that(p.cargo_process("task").arg("--list"),
+                execs().with_status(0).with_stdout("\
+codegen: gen x
+fmt: rustfmt src/lib.rs
+"));
+}
+
+#[test]
+fn task_errors() {
+    let p = project_with_tasks(r#"
+        a = { command = "gen", depends-on = ["b"] }
+        b = { command = "gen", depends-on = ["a"] }
+        c = { command = "gen", depends-on = ["d"] }
+    "#);
+
+    assert_that(p.cargo_process("task").arg("a"),
+                execs().with_status(101).with_stderr("\
+[ERROR] task `a` depends on itself through `a` -> `b`
+"));
+    assert_that(p.cargo("task").arg("c"),
+                execs().with_status(101).with_stderr("\
+[ERROR] task `c` depends on `d`, which isn't in `workspace.tasks`
+"));
+    assert_that(p.cargo("task").arg("e"),
+                execs().with_status(101).with_stderr("\
+[ERROR] no task named `e` in `workspace.tasks`
+"));
+}
@@ -2,6 +2,7 @@ extern crate cargo;
 extern crate cargotest;
 extern crate hamcrest;
 
+use std::env;
 use std::fs::File;
 use std::io::prelude::*;
 use std::str;
@@ -2262,3 +2263,272 @@ fn panic_abort_multiple() {
                  .arg("-p").arg("a"),
                 execs().with_status(0));
 }
+
+#[test]
+fn stable_test_bin_links() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "#[test] fn lib_test() {}")
+        .file("tests/bar.rs", "#[test] fn bar_test() {}");
+    assert_that(p.cargo_process("test").arg("--no-run"),
+                execs().with_status(0));
+
+    let bins = p.root().join("target/debug/test-bins/foo");
+    for &(kind, name) in [("lib", "foo"), ("test", "bar")].iter() {
+        let bin = bins.join(kind)
+                      .join(&format!("{}{}", name, env::consts::EXE_SUFFIX));
+        assert_that(&bin, existing_file());
+        assert_that(process(&bin), execs().with_status(0));
+    }
+
+    // The links are kept up to date as the tests are rebuilt
+    sleep_ms(1000);
+    File::create(&p.root().join("tests/bar.rs")).unwrap()
+         .write_all(b"#[test] fn bar_test() { panic!() }").unwrap();
+    assert_that(p.cargo("test").arg("--no-run"),
+                execs().with_status(0));
+    let bin = bins.join("test")
+                  .join(&format!("bar{}", env::consts::EXE_SUFFIX));
+    assert_that(process(&bin), execs().with_status(101));
+}
+
+#[test]
+fn stable_test_bin_links_same_name() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("tests/foo.rs", "#[test] fn test_test() {}")
+        .file("benches/foo.rs", "#[test] fn bench_test() { panic!() }");
+    assert_that(p.cargo_process("test").arg("--no-run")
+                 .arg("--test").arg("foo").arg("--bench").arg("foo"),
+                execs().with_status(0));
+
+    let bins = p.root().join("target/debug/test-bins/foo");
+    let bin = format!("foo{}", env::consts::EXE_SUFFIX);
+    assert_that(process(&bins.join("test").join(&bin)),
+                execs().with_status(0));
+    assert_that(process(&bins.join("bench").join(&bin)),
+                execs().with_status(101));
+}
+
+#[test]
+fn stable_test_bin_links_json() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("test").arg("--no-run")
+                 .arg("--message-format").arg("json"),
+                execs().with_status(0).with_stdout_contains("\
+{\"executable\":\"[..]foo-[..]\",\"link\":\"[..]test-bins[..]foo[..]\",\
+\"package_id\":\"foo 0.0.1 [..]\",\"reason\":\"test-binary\",\"target\":\"foo\"}
+"));
+
+    // Fresh units are announced as well
+    assert_that(p.cargo("test").arg("--no-run")
+                 .arg("--message-format").arg("json"),
+                execs().with_status(0).with_stdout_contains("\
+[..]\"reason\":\"test-binary\"[..]
+"));
+}
+
+#[test]
+fn env_file_from_config() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", r#"
+            #[test]
+            fn env() {
+                assert_eq!(option_env!("FOO"), None);
+                assert_eq!(std::env::var("FOO").unwrap(), "bar");
+            }
+        "#)
+        .file(".cargo/test.env", "FOO=bar\n")
+        .file(".cargo/config", r#"
+            [run]
+            env-file = "test.env"
+        "#);
+
+    // The file is relative to the configuration, not to the current directory.
+    assert_that(p.cargo_process("test").arg("--lib").cwd(p.root().join("src")),
+                execs().with_status(0).with_stdout_contains("test env ... ok"));
+}
+
+#[test]
+fn doctests_json() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", r#"
+            /// ```
+            /// assert_eq!(foo::foo(), 1);
+            /// ```
+            pub fn foo() -> i32 { 1 }
+
+            /// ```
+            /// let x: i32 = "not a number";
+            /// ```
+            pub fn bar() {}
+        "#);
+
+    // Other toolchains run the doctests as without JSON.
+    if !is_nightly() {
+        assert_that(p.cargo_process("test").arg("--doc")
+                     .arg("--message-format").arg("json"),
+                    execs().with_status(101)
+                           .with_stdout_contains("test src[..]lib.rs - foo (line 2) ... ok")
+                           .with_stdout_contains("test src[..]lib.rs - bar (line 7) ... FAILED")
+                           .with_stderr_contains("[ERROR] test failed"));
+        return
+    }
+
+    assert_that(p.cargo_process("test").arg("--doc")
+                 .arg("--message-format").arg("json"),
+                execs().with_status(101)
+                       .with_stdout_contains("\
+{\"diagnostics\":[],\"event\":\"ok\",\"exec_time\":[..],\"name\":\"src[..]lib.rs - foo \
+(line 2)\",\"output\":null,\"package_id\":\"foo 0.5.0 [..]\",\"reason\":\"doctest\",\
+\"target\":\"foo\"}")
+                       .with_stdout_contains("\
+{\"diagnostics\":[{[..]\"level\":\"error\"[..]}],\"event\":\"failed\",[..]\
+\"name\":\"src[..]lib.rs - bar (line 7)\",[..]\"reason\":\"doctest\",\"target\":\"foo\"}")
+                       .with_stdout_contains("\
+{\"event\":\"failed\",\"exec_time\":[..],\"failed\":1,\"filtered_out\":0,\"ignored\":0,\
+\"package_id\":\"foo 0.5.0 [..]\",\"passed\":1,\"reason\":\"doctest-suite\",\
+\"target\":\"foo\"}")
+                       .with_stderr_contains("[ERROR] test failed"));
+}
+
+#[test]
+fn shard_targets() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", r#"
+            /// ```
+            /// assert_eq!(std::env::var("SHARD").unwrap(), "1");
+            /// ```
+            pub fn foo() {}
+
+            #[test]
+            fn unit() { assert_eq!(std::env::var("SHARD").unwrap(), "2"); }
+        "#)
+        .file("tests/a.rs", r#"
+            #[test]
+            fn a() { assert_eq!(std::env::var("SHARD").unwrap(), "1"); }
+        "#)
+        .file("tests/b.rs", r#"
+            #[test]
+            fn b() { assert_eq!(std::env::var("SHARD").unwrap(), "2"); }
+        "#);
+
+    assert_that(p.cargo_process("test").arg("--shard").arg("1/2").env("SHARD", "1"),
+                execs().with_status(0)
+                       .with_stderr(&format!("\
+[COMPILING] foo v0.5.0 ({url})
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+[RUNNING] target[..]a-[..]
+[DOCTEST] foo", url = p.url()))
+                       .with_stdout_contains("test a ... ok")
+                       .with_stdout_contains("test src[..]lib.rs - foo [..] ... ok"));
+
+    assert_that(p.cargo("test").arg("--shard").arg("2/2").env("SHARD", "2"),
+                execs().with_status(0)
+                       .with_stderr(&format!("\
+[COMPILING] foo v0.5.0 ({url})
+[FINISHED] debug [unoptimized + debuginfo] target(s) in [..]
+[RUNNING] target[..]b-[..]
+[RUNNING] target[..]foo-[..]", url = p.url()))
+                       .with_stdout_contains("test b ... ok")
+                       .with_stdout_contains("test unit ... ok"));
+}
+
+#[test]
+fn shard_tests() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", r#"
+            fn shard() -> String { std::env::var("SHARD").unwrap() }
+
+            #[test] fn t1() { assert_eq!(shard(), "1"); }
+            #[test] fn t2() { assert_eq!(shard(), "2"); }
+            #[test] fn t3() { assert_eq!(shard(), "1"); }
+        "#)
+        .file("tests/t4.rs", r#"
+            #[test] fn t4() { assert_eq!(std::env::var("SHARD").unwrap(), "2"); }
+        "#);
+
+    assert_that(p.cargo_process("test").arg("--shard").arg("1/2").arg("--shard-tests")
+                 .env("SHARD", "1"),
+                execs().with_status(0)
+                       .with_stdout_contains("test t1 ... ok")
+                       .with_stdout_contains("test t3 ... ok"));
+
+    assert_that(p.cargo("test").arg("--shard").arg("2/2").arg("--shard-tests")
+                 .env("SHARD", "2"),
+                execs().with_status(0)
+                       .with_stdout_contains("test t2 ... ok")
+                       .with_stdout_contains("test t4 ... ok"));
+}
+
+#[test]
+fn shard_tests_without_harness() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [[test]]
+            name = "nh"
+            harness = false
+        "#)
+        .file("src/lib.rs", r#"
+            #[test] fn t1() {}
+            #[test] fn t2() {}
+        "#)
+        .file("tests/nh.rs", r#"
+            fn main() {
+                assert_eq!(std::env::var("SHARD").unwrap(), "1");
+                println!("nh ran");
+            }
+        "#);
+
+    // Only the first shard runs the binary, which is never asked to list its
+    // tests.
+    assert_that(p.cargo_process("test").arg("--shard").arg("1/2").arg("--shard-tests")
+                 .env("SHARD", "1"),
+                execs().with_status(0)
+                       .with_stdout_contains("test t1 ... ok")
+                       .with_stdout_contains("nh ran"));
+
+    assert_that(p.cargo("test").arg("--shard").arg("2/2").arg("--shard-tests")
+                 .env("SHARD", "2"),
+                execs().with_status(0)
+                       .with_stdout_contains("test t2 ... ok"));
+}
+
+#[test]
+fn invalid_shard() {
+    let p = project("foo")
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", "");
+
+    assert_that(p.cargo_process("test").arg("--shard").arg("3/2"),
+                execs().with_status(101)
+                       .with_stderr("\
+[ERROR] invalid shard `3/2`, expected `K/N` where K is between 1 and N"));
+}
@@ -0,0 +1,93 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use cargotest::support::{project, execs, ProjectBuilder};
+use hamcrest::assert_that;
+
+fn chain() -> ProjectBuilder {
+    project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            mid = { path = "mid" }
+
+            [features]
+            default = ["turbo"]
+            turbo = ["mid/turbo"]
+        "#)
+        .file("src/lib.rs", "")
+        .file("mid/Cargo.toml", r#"
+            [package]
+            name = "mid"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = { path = "../bar" }
+
+            [features]
+            turbo = ["bar/fast"]
+        "#)
+        .file("mid/src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+
+            [features]
+            default = ["fast"]
+            fast = []
+            slow = []
+        "#)
+        .file("bar/src/lib.rs", "")
+}
+
+#[test]
+fn dependency_tree() {
+    let p = chain();
+
+    assert_that(p.cargo_process("tree"),
+                execs().with_status(0).with_stdout("\
+foo v0.1.0 ([..])
+└── mid v0.1.0 ([..])
+    └── bar v0.1.0 ([..])
+"));
+}
+
+#[test]
+fn why_feature() {
+    let p = chain();
+
+    assert_that(p.cargo_process("tree").arg("--why-feature").arg("bar/fast"),
+                execs().with_status(0).with_stdout("\
+feature \"fast\" of bar v0.1.0 ([..]), for [..]
+├── enabled by feature \"default\" of bar v0.1.0 ([..])
+│   └── requested by mid v0.1.0 ([..]), which depends on `bar` with its default features
+│       └── foo v0.1.0 ([..]) depends on `mid`
+│           └── foo v0.1.0 ([..]) is a member of the workspace
+└── enabled by feature \"turbo\" of mid v0.1.0 ([..])
+    └── enabled by feature \"turbo\" of foo v0.1.0 ([..])
+        └── enabled by feature \"default\" of foo v0.1.0 ([..])
+            └── foo v0.1.0 ([..]) is a member of the workspace, built with its default features
+"));
+}
+
+#[test]
+fn why_feature_not_enabled() {
+    let p = chain();
+
+    assert_that(p.cargo_process("tree").arg("--why-feature").arg("bar/slow"),
+                execs().with_status(101).with_stderr("\
+[ERROR] feature `slow` of `bar v0.1.0 ([..])` isn't enabled when building for `[..]`
+"));
+
+    assert_that(p.cargo("tree").arg("--why-feature").arg("bar"),
+                execs().with_status(101).with_stderr("\
+[ERROR] `--why-feature` expects `PACKAGE/FEATURE`, found `bar`
+"));
+}
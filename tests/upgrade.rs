@@ -0,0 +1,85 @@
+#[macro_use]
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::fs::File;
+use std::io::prelude::*;
+
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+#[test]
+fn upgrade_compatible() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = "1.2"   # raised by hand so far
+            baz = "0.3"
+            qux = ">= 0.1, < 0.3"
+
+            [target.'cfg(not(windows))'.dependencies]
+            quux = { version = "0.1.0", default-features = false }
+
+            [dev-dependencies.corge]
+            version = "0.5.0"
+        "#)
+        .file("src/lib.rs", "");
+    p.build();
+
+    Package::new("bar", "1.2.0").publish();
+    Package::new("baz", "0.3.0").publish();
+    Package::new("qux", "0.1.0").publish();
+    Package::new("quux", "0.1.0").publish();
+    Package::new("corge", "0.5.0").publish();
+    assert_that(p.cargo("generate-lockfile"), execs().with_status(0));
+
+    Package::new("bar", "1.4.1").publish();
+    Package::new("bar", "2.0.0").publish();
+    Package::new("baz", "0.3.5").publish();
+    Package::new("qux", "0.2.0").publish();
+    Package::new("quux", "0.1.2").publish();
+    Package::new("corge", "0.5.3").publish();
+
+    assert_that(p.cargo("upgrade").arg("--dry-run"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[UPGRADING] bar 1.2 -> 1.4 in Cargo.toml")
+                       .with_stderr_contains("\
+[WARNING] not writing the manifests, as this is a dry run"));
+    assert_that(p.cargo("upgrade"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[UPGRADING] bar 1.2 -> 1.4 in Cargo.toml")
+                       .with_stderr_contains("\
+[UPGRADING] quux 0.1.0 -> 0.1.2 in Cargo.toml")
+                       .with_stderr_contains("\
+[UPGRADING] corge 0.5.0 -> 0.5.3 in Cargo.toml")
+                       .with_stderr_contains("\
+[UPDATING] bar v1.2.0 -> v1.4.1"));
+
+    let mut manifest = String::new();
+    File::open(p.root().join("Cargo.toml")).unwrap()
+        .read_to_string(&mut manifest).unwrap();
+    assert!(manifest.contains("bar = \"1.4\"   # raised by hand so far"));
+    assert!(manifest.contains("baz = \"0.3\""));
+    assert!(manifest.contains("qux = \">= 0.1, < 0.3\""));
+    assert!(manifest.contains("\
+            [target.'cfg(not(windows))'.dependencies]
+            quux = { version = \"0.1.2\", default-features = false }"));
+    assert!(manifest.contains("\
+            [dev-dependencies.corge]
+            version = \"0.5.3\""));
+    let lock = p.read_lockfile();
+    assert!(lock.contains("name = \"baz\"\nversion = \"0.3.0\""));
+
+    assert_that(p.cargo("upgrade"),
+                execs().with_status(0)
+                       .with_stderr_contains("\
+[UPGRADING] every requirement already starts at the newest version it allows"));
+}
@@ -0,0 +1,153 @@
+extern crate cargotest;
+extern crate hamcrest;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use cargotest::support::registry::Package;
+use cargotest::support::{project, execs};
+use hamcrest::assert_that;
+
+fn read(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+fn project_with_policy(policy: &str) -> cargotest::support::ProjectBuilder {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+            baz = "0.1"
+        "#)
+        .file("src/lib.rs", "");
+    if policy.is_empty() {
+        p
+    } else {
+        p.file("supply-chain.toml", policy)
+    }
+}
+
+#[test]
+fn build_refuses_unvetted_packages() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publish();
+    let p = project_with_policy(r#"
+        [[audits.bar]]
+        version = "0.1.0"
+        who = "Jane Doe"
+    "#);
+    assert_that(p.cargo_process("build"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] these packages aren't audited, trusted or exempted by the policy in \
+`[..]supply-chain.toml`:
+  baz v0.1.0
+review them and record an audit for each, or exempt them with `cargo vet --exempt`
+"));
+}
+
+#[test]
+fn trusted_publisher() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publisher("alice").publish();
+    let p = project_with_policy(r#"
+        [[audits.bar]]
+        version = "0.1.0"
+
+        [[trusted.baz]]
+        publisher = "alice"
+    "#);
+    assert_that(p.cargo_process("build"), execs().with_status(0));
+
+    Package::new("baz", "0.1.1").publisher("mallory").publish();
+    assert_that(p.cargo("update"), execs().with_status(0));
+    assert_that(p.cargo("build"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] these packages aren't audited, trusted or exempted by the policy in \
+`[..]supply-chain.toml`:
+  baz v0.1.1
+[..]
+"));
+}
+
+#[test]
+fn exempt_current_dependencies() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publish();
+    let p = project_with_policy("");
+    p.build();
+    assert_that(p.cargo("vet"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] there's no supply-chain policy in `[..]supply-chain.toml`, adopt one \
+by exempting the current dependencies with `cargo vet --exempt`
+"));
+
+    assert_that(p.cargo("vet").arg("--exempt"),
+                execs().with_status(0).with_stderr_contains("\
+   Exempting bar v0.1.0
+   Exempting baz v0.1.0
+"));
+    assert_eq!(read(&p.root().join("supply-chain.toml")), "\
+[[exemptions.bar]]
+version = \"0.1.0\"
+
+[[exemptions.baz]]
+version = \"0.1.0\"
+");
+    assert_that(p.cargo("build"), execs().with_status(0));
+
+    Package::new("baz", "0.1.1").publish();
+    assert_that(p.cargo("update"), execs().with_status(0));
+    assert_that(p.cargo("vet"),
+                execs().with_status(101).with_stderr_contains("\
+[WARNING] the exemption of `baz 0.1.0` isn't needed anymore and can be removed
+").with_stderr_contains("\
+[ERROR] these packages aren't audited, trusted or exempted:
+  baz v0.1.1
+review them and record an audit for each, or exempt them with `cargo vet --exempt`
+"));
+}
+
+#[test]
+fn add_refuses_unvetted_packages() {
+    Package::new("bar", "0.1.0").publish();
+    Package::new("baz", "0.1.0").publish();
+    let manifest = r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies]
+            bar = "0.1"
+        "#;
+    let p = project("foo")
+        .file("Cargo.toml", manifest)
+        .file("src/lib.rs", "")
+        .file("supply-chain.toml", r#"
+            [[exemptions.bar]]
+            version = "0.1.0"
+        "#);
+    assert_that(p.cargo_process("vet"),
+                execs().with_status(0).with_stderr_contains("\
+      Vetted every dependency is covered by the supply-chain policy
+"));
+    let lockfile = p.read_lockfile();
+
+    assert_that(p.cargo("add").arg("baz"),
+                execs().with_status(101).with_stderr_contains("\
+[ERROR] these packages aren't audited, trusted or exempted by the policy in \
+`[..]supply-chain.toml`:
+  baz v0.1.0
+[..]
+"));
+    assert_eq!(read(&p.root().join("Cargo.toml")), manifest);
+    assert_eq!(p.read_lockfile(), lockfile);
+}
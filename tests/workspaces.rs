@@ -1,5 +1,6 @@
 #[macro_use]
 extern crate cargotest;
+extern crate git2;
 extern crate hamcrest;
 
 use std::io::{Read, Write};
@@ -962,3 +963,603 @@ fn you_cannot_generate_lockfile_for_empty_workspaces() {
 error: you can't generate a lockfile for an empty workspace.
 "));
 }
+
+#[test]
+fn targeted_build_loads_only_needed_members() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [workspace]
+            members = ["bar", "baz", "qux"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            qux = { path = "../qux" }
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", r#"
+            [project]
+            name = "baz"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "")
+        .file("qux/Cargo.toml", r#"
+            [project]
+            name = "qux"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("qux/src/lib.rs", "");
+    p.build();
+    assert_that(p.cargo("generate-lockfile"), execs().with_status(0));
+
+    // A member which can't be loaded doesn't get in the way of building the
+    // others
+    File::create(p.root().join("baz/Cargo.toml")).unwrap().write_all(br#"
+        [project]
+        name = "baz"
+        version = "0.1.0"
+        authors = []
+
+        [dependencies]
+        missing = { path = "../missing" }
+    "#).unwrap();
+    let mut lock = String::new();
+    File::open(p.root().join("Cargo.lock")).unwrap()
+        .read_to_string(&mut lock).unwrap();
+
+    assert_that(p.cargo("build").arg("-p").arg("bar"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] qux v0.1.0 ([..])
+[COMPILING] bar v0.1.0 ([..])
+[FINISHED] [..]
+"));
+    let mut lock2 = String::new();
+    File::open(p.root().join("Cargo.lock")).unwrap()
+        .read_to_string(&mut lock2).unwrap();
+    assert_eq!(lock, lock2);
+
+    assert_that(p.cargo("build"),
+                execs().with_status(101)
+                       .with_stderr_contains("[..]missing[..]"));
+}
+
+#[test]
+fn build_changed_since() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = { path = "bar" }
+
+            [workspace]
+            members = ["baz"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", r#"
+            [project]
+            name = "baz"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "")
+        .file(".gitignore", "target\n");
+    p.build();
+    assert_that(p.cargo("build"), execs().with_status(0));
+    assert_that(p.cargo("build").arg("-p").arg("baz"), execs().with_status(0));
+    let repo = git2::Repository::init(&p.root()).unwrap();
+    {
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "name").unwrap();
+        config.set_str("user.email", "email").unwrap();
+    }
+    git::add(&repo);
+    git::commit(&repo);
+
+    assert_that(p.cargo("build").arg("--changed-since").arg("HEAD"),
+                execs().with_status(0).with_stderr("\
+[UNCHANGED] no packages changed since `HEAD`
+"));
+
+    // A change to bar affects foo, which depends on it, but not baz
+    sleep_ms(1000);
+    File::create(p.root().join("bar/src/lib.rs")).unwrap()
+        .write_all(b"pub fn bar() {}").unwrap();
+    assert_that(p.cargo("build").arg("--changed-since").arg("HEAD"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] bar v0.1.0 ([..])
+[COMPILING] foo v0.1.0 ([..])
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("build").arg("--changed-since").arg("HEAD")
+                 .arg("-p").arg("baz"),
+                execs().with_status(101).with_stderr("\
+[ERROR] `--changed-since` cannot be used with `--package`
+"));
+}
+
+#[test]
+fn build_changed_since_path_dep_outside_workspace() {
+    let p = project("foo")
+        .file("ws/Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            bar = { path = "../bar" }
+
+            [workspace]
+            members = ["baz"]
+        "#)
+        .file("ws/src/main.rs", "fn main() {}")
+        .file("ws/baz/Cargo.toml", r#"
+            [project]
+            name = "baz"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("ws/baz/src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            qux = { path = "../qux" }
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("qux/Cargo.toml", r#"
+            [project]
+            name = "qux"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("qux/src/lib.rs", "")
+        .file(".gitignore", "target\n");
+    p.build();
+    let ws = p.root().join("ws");
+    assert_that(p.cargo("build").cwd(&ws), execs().with_status(0));
+    let repo = git2::Repository::init(&p.root()).unwrap();
+    {
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "name").unwrap();
+        config.set_str("user.email", "email").unwrap();
+    }
+    git::add(&repo);
+    git::commit(&repo);
+
+    // qux is depended on by bar, which foo depends on, neither of them being
+    // members of the workspace.
+    sleep_ms(1000);
+    File::create(p.root().join("qux/src/lib.rs")).unwrap()
+        .write_all(b"pub fn qux() {}").unwrap();
+    assert_that(p.cargo("build").cwd(&ws)
+                 .arg("--changed-since").arg("HEAD"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] qux v0.1.0 ([..])
+[COMPILING] bar v0.1.0 ([..])
+[COMPILING] foo v0.1.0 ([..])
+[FINISHED] [..]
+"));
+}
+
+#[test]
+fn build_changed_since_root_profile() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [workspace]
+            members = ["baz"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("baz/Cargo.toml", r#"
+            [project]
+            name = "baz"
+            version = "0.1.0"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "")
+        .file(".gitignore", "target\n");
+    p.build();
+    let repo = git2::Repository::init(&p.root()).unwrap();
+    {
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "name").unwrap();
+        config.set_str("user.email", "email").unwrap();
+    }
+    git::add(&repo);
+    git::commit(&repo);
+
+    // Editing the package itself only affects the root package
+    File::create(p.root().join("Cargo.toml")).unwrap().write_all(br#"
+        [project]
+        name = "foo"
+        version = "0.1.0"
+        authors = []
+        description = "foo"
+
+        [workspace]
+        members = ["baz"]
+    "#).unwrap();
+    assert_that(p.cargo("build").arg("--changed-since").arg("HEAD"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] foo v0.1.0 ([..])
+[FINISHED] [..]
+"));
+
+    // but a profile applies to every member
+    File::create(p.root().join("Cargo.toml")).unwrap().write_all(br#"
+        [project]
+        name = "foo"
+        version = "0.1.0"
+        authors = []
+
+        [workspace]
+        members = ["baz"]
+
+        [profile.dev]
+        opt-level = 1
+    "#).unwrap();
+    assert_that(p.cargo("build").arg("--changed-since").arg("HEAD"),
+                execs().with_status(0)
+                       .with_stderr_contains("[COMPILING] foo v0.1.0 ([..])")
+                       .with_stderr_contains("[COMPILING] baz v0.1.0 ([..])"));
+}
+
+fn project_with_tiers() -> cargotest::support::ProjectBuilder {
+    let mut p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [workspace]
+            members = ["net-a", "net-b", "core"]
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    for &(name, tier) in [("net-a", 1), ("net-b", 2), ("core", 1)].iter() {
+        p = p.file(&format!("{}/Cargo.toml", name), &format!(r#"
+                [package]
+                name = "{}"
+                version = "0.1.0"
+                authors = []
+
+                [package.metadata.ci]
+                tier = {}
+            "#, name, tier))
+             .file(&format!("{}/src/lib.rs", name), "");
+    }
+    p
+}
+
+#[test]
+fn build_package_patterns() {
+    let p = project_with_tiers();
+    p.build();
+
+    assert_that(p.cargo("build").arg("-p").arg("net-*").arg("-p").arg("!net-b"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] net-a v0.1.0 ([..])
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("build").arg("-p").arg("*").arg("--exclude").arg("net-*")
+                 .arg("--exclude").arg("foo"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] core v0.1.0 ([..])
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("build").arg("-p").arg("missing-*"),
+                execs().with_status(101).with_stderr("\
+[ERROR] no members of the workspace match `missing-*`
+"));
+}
+
+#[test]
+fn build_select_by_metadata() {
+    let p = project_with_tiers();
+    p.build();
+
+    assert_that(p.cargo("build").arg("--select")
+                 .arg("package.metadata.ci.tier == 2"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] net-b v0.1.0 ([..])
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("build").arg("--select").arg("package.metadata.ci.tier")
+                 .arg("--exclude").arg("net-*"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] core v0.1.0 ([..])
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("build").arg("--select")
+                 .arg("package.metadata.ci.tier == 3"),
+                execs().with_status(101).with_stderr("\
+[ERROR] no packages are left to build once the exclusions and selections are \
+applied
+"));
+
+    assert_that(p.cargo("build").arg("--select")
+                 .arg("package.metadata.ci.owner == infra"),
+                execs().with_status(101).with_stderr("\
+[ERROR] invalid value in `package.metadata.ci.owner == infra`, strings must be \
+quoted
+"));
+}
+
+#[test]
+fn inherit_dependencies() {
+    Package::new("log", "0.3.1").file("src/lib.rs", "pub fn log() {}").publish();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["foo", "util"]
+
+            [workspace.dependencies]
+            log = "0.3"
+            util = { path = "util" }
+        "#)
+        .file("foo/Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            log = { workspace = true }
+            util = { workspace = true, features = ["extra"] }
+        "#)
+        .file("foo/src/lib.rs", r#"
+            extern crate log;
+            extern crate util;
+
+            pub fn foo() {
+                log::log();
+                util::extra();
+            }
+        "#)
+        .file("util/Cargo.toml", r#"
+            [project]
+            name = "util"
+            version = "0.1.0"
+            authors = []
+
+            [features]
+            extra = []
+        "#)
+        .file("util/src/lib.rs", r#"
+            #[cfg(feature = "extra")]
+            pub fn extra() {}
+        "#);
+    p.build();
+
+    assert_that(p.cargo("build").cwd(p.root().join("foo")),
+                execs().with_status(0));
+}
+
+#[test]
+fn inherit_undeclared_dependency() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["foo"]
+        "#)
+        .file("foo/Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            log = { workspace = true }
+        "#)
+        .file("foo/src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("build").cwd(p.root().join("foo")),
+                execs().with_status(101).with_stderr_contains("\
+[..]dependency `log` is inherited from the workspace, but \
+`workspace.dependencies` of `[..]Cargo.toml` doesn't declare it
+"));
+}
+
+#[test]
+fn inherit_dependency_only_adds_features() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["foo"]
+
+            [workspace.dependencies]
+            log = "0.3"
+        "#)
+        .file("foo/Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies]
+            log = { workspace = true, version = "0.2" }
+        "#)
+        .file("foo/src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("build").cwd(p.root().join("foo")),
+                execs().with_status(101).with_stderr_contains("\
+[..]dependency `log` is inherited from the workspace, which only lets it add \
+`features` or be made `optional`, not set `version`
+"));
+}
+
+#[test]
+fn package_writes_out_inherited_dependencies() {
+    Package::new("log", "0.3.1").publish();
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["foo"]
+
+            [workspace.dependencies]
+            log = "0.3"
+        "#)
+        .file("foo/Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+            license = "MIT"
+            description = "foo"
+
+            [dependencies]
+            log = { workspace = true }
+        "#)
+        .file("foo/src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("package").cwd(p.root().join("foo")),
+                execs().with_status(0));
+
+    let mut contents = String::new();
+    File::open(p.root().join("target/package/foo-0.1.0/Cargo.toml")).unwrap()
+         .read_to_string(&mut contents).unwrap();
+    assert!(!contents.contains("workspace"), "{}", contents);
+    assert!(contents.contains("version = \"0.3\""), "{}", contents);
+}
+
+#[test]
+fn inherit_package_fields() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["foo", "bar"]
+
+            [workspace.package]
+            version = "1.2.3"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("foo/Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = { workspace = true }
+            authors = { workspace = true }
+
+            [dependencies]
+            bar = { path = "../bar", version = "1.2.3" }
+        "#)
+        .file("foo/src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [project]
+            name = "bar"
+            version = { workspace = true }
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("build").cwd(p.root().join("foo")),
+                execs().with_status(0).with_stderr("\
+[COMPILING] bar v1.2.3 ([..])
+[COMPILING] foo v1.2.3 ([..])
+[FINISHED] [..]
+"));
+}
+
+#[test]
+fn inherit_package_field_not_declared() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["foo"]
+        "#)
+        .file("foo/Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = { workspace = true }
+            authors = []
+        "#)
+        .file("foo/src/lib.rs", "");
+    p.build();
+
+    assert_that(p.cargo("build").cwd(p.root().join("foo")),
+                execs().with_status(101).with_stderr_contains("\
+[..]`project.version` is inherited from the workspace, but \
+`workspace.package` of `[..]Cargo.toml` doesn't declare it
+"));
+}
+
+#[test]
+fn default_members_per_command() {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [workspace]
+            members = ["net-a", "net-b", "core"]
+
+            [workspace.default-members]
+            build = ["core"]
+            test = ["net-*"]
+        "#);
+    let p = ["net-a", "net-b", "core"].iter().fold(p, |p, name| {
+        p.file(&format!("{}/Cargo.toml", name), &format!(r#"
+                [package]
+                name = "{}"
+                version = "0.1.0"
+                authors = []
+            "#, name))
+         .file(&format!("{}/src/lib.rs", name), "")
+    });
+    p.build();
+
+    assert_that(p.cargo("build"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] core v0.1.0 ([..])
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("build").arg("-p").arg("net-a"),
+                execs().with_status(0).with_stderr("\
+[COMPILING] net-a v0.1.0 ([..])
+[FINISHED] [..]
+"));
+
+    assert_that(p.cargo("test").arg("--no-run"),
+                execs().with_status(0).with_stderr_contains("\
+[COMPILING] net-b v0.1.0 ([..])
+"));
+}